@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use eth_trie_utils::nibbles::Nibbles;
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::{H160, H256, U256};
+use keccak_hash::keccak;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::util::timing::TimingTree;
+use plonky2_evm::all_stark::AllStark;
+use plonky2_evm::config::StarkConfig;
+use plonky2_evm::generation::mpt::AccountRlp;
+use plonky2_evm::generation::{generate_traces, GenerationInputs, TrieInputs};
+use plonky2_evm::proof::{BlockHashes, BlockMetadata, TrieRoots};
+use plonky2_evm::Node;
+
+type F = GoldilocksField;
+const D: usize = 2;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+
+    let mut group = c.benchmark_group("generate_traces");
+    group.sample_size(10);
+    for num_withdrawals in [0, 64, 256] {
+        let inputs = block_with_withdrawals(num_withdrawals);
+        group.bench_function(BenchmarkId::from_parameter(num_withdrawals), |b| {
+            b.iter(|| {
+                let mut timing = TimingTree::default();
+                generate_traces(&all_stark, inputs.clone(), &config, &mut timing).unwrap()
+            })
+        });
+    }
+}
+
+/// A block with no transactions, and the given number of withdrawals to fresh accounts. Each
+/// withdrawal inserts a new leaf in the state trie, which makes for large CPU and memory traces.
+fn block_with_withdrawals(num_withdrawals: u64) -> GenerationInputs {
+    let withdrawals = (1..=num_withdrawals)
+        .map(|i| (H160::from_low_u64_be(i), U256::from(i)))
+        .collect::<Vec<_>>();
+
+    let mut state_trie_after = HashedPartialTrie::from(Node::Empty);
+    for &(address, amount) in &withdrawals {
+        let nibbles = Nibbles::from_bytes_be(keccak(address).as_bytes()).unwrap();
+        let account = AccountRlp {
+            balance: amount,
+            ..AccountRlp::default()
+        };
+        state_trie_after.insert(nibbles, rlp::encode(&account).to_vec());
+    }
+
+    let transactions_trie = HashedPartialTrie::from(Node::Empty);
+    let receipts_trie = HashedPartialTrie::from(Node::Empty);
+    let trie_roots_after = TrieRoots {
+        state_root: state_trie_after.hash(),
+        transactions_root: transactions_trie.hash(),
+        receipts_root: receipts_trie.hash(),
+    };
+
+    let mut contract_code = HashMap::new();
+    contract_code.insert(keccak(vec![]), vec![]);
+
+    GenerationInputs {
+        signed_txns: vec![],
+        withdrawals,
+        tries: TrieInputs {
+            state_trie: HashedPartialTrie::from(Node::Empty),
+            transactions_trie,
+            receipts_trie,
+            storage_tries: vec![],
+        },
+        trie_roots_after,
+        contract_code,
+        genesis_state_trie_root: HashedPartialTrie::from(Node::Empty).hash(),
+        block_metadata: BlockMetadata::default(),
+        txn_number_before: 0.into(),
+        gas_used_before: 0.into(),
+        gas_used_after: 0.into(),
+        block_bloom_before: [0.into(); 8],
+        block_bloom_after: [0.into(); 8],
+        block_hashes: BlockHashes {
+            prev_hashes: vec![H256::default(); 256],
+            cur_hash: H256::default(),
+        },
+        addresses: vec![],
+        ..GenerationInputs::default()
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,408 @@
+//! The Solidity ABI encoding of `PublicValues`, for settlement contracts.
+//!
+//! The public values are encoded as `abi.encode(publicValues)` for a `publicValues` of the type
+//! `PublicValues` of [`PUBLIC_VALUES_SOLIDITY`]. All its fields have static types, so the encoding
+//! is the concatenation of its fields, each in a 32-byte word, and its digest is the `keccak256` of
+//! the encoding.
+//!
+//! The block metadata extensions, i.e. the header fields added by forks, and then the extra public
+//! values of chains follow the other fields. Each is encoded with a presence flag, and its values
+//! are 0 if absent. Chain constants aren't part of this encoding, so public values overriding any
+//! of them can't be encoded.
+
+use anyhow::{ensure, Result};
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::keccak;
+
+use crate::proof::{
+    BlockHashes, BlockMetadata, BlockMetadataExtensions, ChainConstants, ExtraBlockData,
+    ExtraPublicValue, ExtraPublicValues, PublicValues, TrieRoots, NUM_BLOCK_METADATA_EXTENSIONS,
+    NUM_EXTRA_PUBLIC_VALUES,
+};
+use crate::witness::errors::{ProgramError, ProverInputError};
+
+/// The Solidity definition of the public values, as encoded by [`PublicValues::abi_encode`].
+pub const PUBLIC_VALUES_SOLIDITY: &str = "\
+struct TrieRoots {
+    bytes32 stateRoot;
+    bytes32 transactionsRoot;
+    bytes32 receiptsRoot;
+}
+
+struct BlockMetadata {
+    address blockBeneficiary;
+    uint256 blockTimestamp;
+    uint256 blockNumber;
+    uint256 blockDifficulty;
+    bytes32 blockRandom;
+    uint256 blockGasLimit;
+    uint256 blockChainId;
+    uint256 blockBaseFee;
+    uint256 blockGasUsed;
+    uint256[8] blockBloom;
+}
+
+struct BlockHashes {
+    bytes32[256] prevHashes;
+    bytes32 curHash;
+}
+
+struct ExtraBlockData {
+    bytes32 genesisStateTrieRoot;
+    uint256 txnNumberBefore;
+    uint256 txnNumberAfter;
+    uint256 gasUsedBefore;
+    uint256 gasUsedAfter;
+    uint256[8] blockBloomBefore;
+    uint256[8] blockBloomAfter;
+}
+
+struct BlockMetadataExtensions {
+    bool[8] present;
+    uint256[8] values;
+}
+
+struct ExtraPublicValues {
+    bool[4] declared;
+    uint256[4] valuesBefore;
+    uint256[4] valuesAfter;
+}
+
+struct PublicValues {
+    TrieRoots trieRootsBefore;
+    TrieRoots trieRootsAfter;
+    BlockMetadata blockMetadata;
+    BlockHashes blockHashes;
+    ExtraBlockData extraBlockData;
+    BlockMetadataExtensions blockMetadataExtensions;
+    ExtraPublicValues extraPublicValues;
+}
+";
+
+/// The number of bytes of the ABI encoding of the public values.
+pub const PUBLIC_VALUES_ABI_SIZE: usize = 32 * (6 + 17 + 257 + 21 + 16 + 12);
+
+impl PublicValues {
+    /// Returns `abi.encode(publicValues)`, see [`PUBLIC_VALUES_SOLIDITY`]. Fails if a chain
+    /// constant is overridden, as chain constants aren't part of the encoding.
+    pub fn abi_encode(&self) -> Result<Vec<u8>, ProgramError> {
+        if self.block_hashes.prev_hashes.len() != 256 || !self.chain_constants.is_empty() {
+            return Err(ProgramError::ProverInputError(
+                ProverInputError::InvalidInput,
+            ));
+        }
+
+        let mut words: Vec<[u8; 32]> = Vec::with_capacity(PUBLIC_VALUES_ABI_SIZE / 32);
+        let h256 = |hash: H256| hash.0;
+        let u256 = |x: U256| {
+            let mut word = [0; 32];
+            x.to_big_endian(&mut word);
+            word
+        };
+        for trie_roots in [&self.trie_roots_before, &self.trie_roots_after] {
+            words.push(h256(trie_roots.state_root));
+            words.push(h256(trie_roots.transactions_root));
+            words.push(h256(trie_roots.receipts_root));
+        }
+
+        let md = &self.block_metadata;
+        words.push(h256(md.block_beneficiary.into()));
+        words.push(u256(md.block_timestamp));
+        words.push(u256(md.block_number));
+        words.push(u256(md.block_difficulty));
+        words.push(h256(md.block_random));
+        words.push(u256(md.block_gaslimit));
+        words.push(u256(md.block_chain_id));
+        words.push(u256(md.block_base_fee));
+        words.push(u256(md.block_gas_used));
+        words.extend(md.block_bloom.map(u256));
+
+        let hashes = &self.block_hashes;
+        words.extend(hashes.prev_hashes.iter().map(|&hash| h256(hash)));
+        words.push(h256(hashes.cur_hash));
+
+        let ed = &self.extra_block_data;
+        words.push(h256(ed.genesis_state_trie_root));
+        words.push(u256(ed.txn_number_before));
+        words.push(u256(ed.txn_number_after));
+        words.push(u256(ed.gas_used_before));
+        words.push(u256(ed.gas_used_after));
+        words.extend(ed.block_bloom_before.map(u256));
+        words.extend(ed.block_bloom_after.map(u256));
+
+        let extensions = &md.extensions.fields;
+        words.extend(extensions.map(|x| u256(u8::from(x.is_some()).into())));
+        words.extend(extensions.map(|x| u256(x.unwrap_or_default())));
+
+        let extra = &self.extra_public_values;
+        words.extend(extra.slots.map(|x| u256(u8::from(x.is_some()).into())));
+        words.extend(extra.before().map(u256));
+        words.extend(extra.after().map(u256));
+
+        debug_assert_eq!(words.len() * 32, PUBLIC_VALUES_ABI_SIZE);
+        Ok(words.concat())
+    }
+
+    /// Returns `keccak256(abi.encode(publicValues))`.
+    pub fn abi_hash(&self) -> Result<H256, ProgramError> {
+        Ok(keccak(self.abi_encode()?))
+    }
+
+    /// Decodes public values from their ABI encoding, rejecting non-canonical encodings like
+    /// `abi.decode` does.
+    pub fn abi_decode(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == PUBLIC_VALUES_ABI_SIZE,
+            "Expected {PUBLIC_VALUES_ABI_SIZE} bytes, found {}",
+            bytes.len()
+        );
+        let mut words = bytes.chunks_exact(32);
+        let mut next = || H256::from_slice(words.next().unwrap());
+        let trie_roots_before = TrieRoots {
+            state_root: next(),
+            transactions_root: next(),
+            receipts_root: next(),
+        };
+        let trie_roots_after = TrieRoots {
+            state_root: next(),
+            transactions_root: next(),
+            receipts_root: next(),
+        };
+
+        let beneficiary = next();
+        ensure!(
+            beneficiary[..12].iter().all(|&b| b == 0),
+            "Invalid address {beneficiary:?}"
+        );
+        let u256 = |hash: H256| U256::from_big_endian(&hash.0);
+        let mut block_metadata = BlockMetadata {
+            block_beneficiary: Address::from(beneficiary),
+            block_timestamp: u256(next()),
+            block_number: u256(next()),
+            block_difficulty: u256(next()),
+            block_random: next(),
+            block_gaslimit: u256(next()),
+            block_chain_id: u256(next()),
+            block_base_fee: u256(next()),
+            block_gas_used: u256(next()),
+            block_bloom: core::array::from_fn(|_| u256(next())),
+            extensions: BlockMetadataExtensions::default(),
+        };
+
+        let block_hashes = BlockHashes {
+            prev_hashes: (0..256).map(|_| next()).collect(),
+            cur_hash: next(),
+        };
+
+        let extra_block_data = ExtraBlockData {
+            genesis_state_trie_root: next(),
+            txn_number_before: u256(next()),
+            txn_number_after: u256(next()),
+            gas_used_before: u256(next()),
+            gas_used_after: u256(next()),
+            block_bloom_before: core::array::from_fn(|_| u256(next())),
+            block_bloom_after: core::array::from_fn(|_| u256(next())),
+        };
+
+        let present: [H256; NUM_BLOCK_METADATA_EXTENSIONS] = core::array::from_fn(|_| next());
+        let values: [U256; NUM_BLOCK_METADATA_EXTENSIONS] = core::array::from_fn(|_| u256(next()));
+        for (i, field) in block_metadata.extensions.fields.iter_mut().enumerate() {
+            *field = decode_optional(present[i], values[i])?;
+        }
+
+        let declared: [H256; NUM_EXTRA_PUBLIC_VALUES] = core::array::from_fn(|_| next());
+        let before: [U256; NUM_EXTRA_PUBLIC_VALUES] = core::array::from_fn(|_| u256(next()));
+        let after: [U256; NUM_EXTRA_PUBLIC_VALUES] = core::array::from_fn(|_| u256(next()));
+        let mut extra_public_values = ExtraPublicValues::default();
+        for (i, slot) in extra_public_values.slots.iter_mut().enumerate() {
+            let before = decode_optional(declared[i], before[i])?;
+            let after = decode_optional(declared[i], after[i])?;
+            *slot = before
+                .zip(after)
+                .map(|(before, after)| ExtraPublicValue { before, after });
+        }
+
+        Ok(Self {
+            trie_roots_before,
+            trie_roots_after,
+            block_metadata,
+            block_hashes,
+            extra_block_data,
+            extra_public_values,
+            chain_constants: ChainConstants::default(),
+        })
+    }
+}
+
+/// Decodes an optional value from its `bool` presence flag and its value, which must be 0 if
+/// absent.
+fn decode_optional(present: H256, value: U256) -> Result<Option<U256>> {
+    if present.is_zero() {
+        ensure!(value.is_zero(), "Nonzero value {value} of an absent field");
+        Ok(None)
+    } else {
+        ensure!(
+            present == H256::from_low_u64_be(1),
+            "Invalid bool {present:?}"
+        );
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::goldilocks_field::GoldilocksField;
+
+    use super::*;
+    use crate::proof::BlockMetadataExtension;
+
+    fn public_values() -> PublicValues {
+        let hash = |i: u8| H256::repeat_byte(i);
+        PublicValues {
+            trie_roots_before: TrieRoots {
+                state_root: hash(1),
+                transactions_root: hash(2),
+                receipts_root: hash(3),
+            },
+            trie_roots_after: TrieRoots {
+                state_root: hash(4),
+                transactions_root: hash(5),
+                receipts_root: hash(6),
+            },
+            block_metadata: BlockMetadata {
+                block_beneficiary: Address::repeat_byte(7),
+                block_timestamp: 0x1000.into(),
+                block_number: 42.into(),
+                block_chain_id: 1.into(),
+                block_bloom: [3.into(); 8],
+                ..BlockMetadata::default()
+            },
+            block_hashes: BlockHashes {
+                prev_hashes: (0..=255).map(hash).collect(),
+                cur_hash: hash(8),
+            },
+            extra_block_data: ExtraBlockData {
+                genesis_state_trie_root: hash(9),
+                txn_number_after: 2.into(),
+                gas_used_after: 21000.into(),
+                ..ExtraBlockData::default()
+            },
+            extra_public_values: ExtraPublicValues::default(),
+            chain_constants: ChainConstants::default(),
+        }
+    }
+
+    #[test]
+    fn test_abi_round_trip() -> Result<()> {
+        let public_values = public_values();
+        let encoding = public_values.abi_encode().unwrap();
+        assert_eq!(encoding.len(), PUBLIC_VALUES_ABI_SIZE);
+
+        // The beneficiary is left-padded in the 7th word.
+        assert_eq!(&encoding[6 * 32..6 * 32 + 12], &[0; 12]);
+        assert_eq!(&encoding[6 * 32 + 12..7 * 32], &[7; 20]);
+        // The block number is a big-endian integer in the 9th word.
+        assert_eq!(encoding[9 * 32 - 1], 42);
+
+        let decoded = PublicValues::abi_decode(&encoding)?;
+        assert_eq!(decoded.abi_encode().unwrap(), encoding);
+        assert_eq!(decoded.abi_hash().unwrap(), keccak(&encoding));
+        Ok(())
+    }
+
+    #[test]
+    fn test_abi_round_trip_with_extensions() -> Result<()> {
+        let mut public_values = public_values();
+        let extensions = &mut public_values.block_metadata.extensions;
+        extensions.set(BlockMetadataExtension::ExcessBlobGas, 0x20000.into());
+        extensions.set(BlockMetadataExtension::ParentBeaconBlockRoot, U256::MAX);
+        let encoding = public_values.abi_encode().unwrap();
+        assert_eq!(encoding.len(), PUBLIC_VALUES_ABI_SIZE);
+
+        // The presence flags of the extensions follow the extra block data.
+        let flags = &encoding[301 * 32..309 * 32];
+        let flag = |i: usize| &flags[i * 32..(i + 1) * 32];
+        assert_eq!(flag(0), &[0; 32]);
+        assert_eq!(flag(1)[31], 1);
+        assert_eq!(flag(2)[31], 1);
+
+        let decoded = PublicValues::abi_decode(&encoding)?;
+        assert_eq!(
+            decoded.block_metadata.extensions,
+            public_values.block_metadata.extensions
+        );
+        assert_eq!(decoded.abi_encode().unwrap(), encoding);
+
+        let pis = public_values.to_public_inputs::<GoldilocksField>().unwrap();
+        let decoded = PublicValues::from_public_inputs(&pis)?;
+        assert_eq!(decoded.abi_encode().unwrap(), encoding);
+        Ok(())
+    }
+
+    #[test]
+    fn test_abi_round_trip_with_extra_public_values() -> Result<()> {
+        let mut public_values = public_values();
+        public_values.extra_public_values.slots[1] = Some(ExtraPublicValue {
+            before: 5.into(),
+            after: U256::MAX,
+        });
+        let encoding = public_values.abi_encode().unwrap();
+        assert_eq!(encoding.len(), PUBLIC_VALUES_ABI_SIZE);
+
+        // The extra public values follow the block metadata extensions.
+        assert_eq!(encoding[319 * 32 - 1], 1);
+        assert_eq!(encoding[323 * 32 - 1], 5);
+        assert_eq!(&encoding[326 * 32..327 * 32], &[0xff; 32]);
+
+        let decoded = PublicValues::abi_decode(&encoding)?;
+        assert_eq!(
+            decoded.extra_public_values,
+            public_values.extra_public_values
+        );
+        assert_eq!(decoded.abi_encode().unwrap(), encoding);
+
+        let pis = public_values.to_public_inputs::<GoldilocksField>().unwrap();
+        let decoded = PublicValues::from_public_inputs(&pis)?;
+        assert_eq!(decoded.abi_encode().unwrap(), encoding);
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_inputs_round_trip() -> Result<()> {
+        let public_values = public_values();
+        let pis = public_values.to_public_inputs::<GoldilocksField>().unwrap();
+        let decoded = PublicValues::from_public_inputs(&pis)?;
+        assert_eq!(
+            decoded.abi_encode().unwrap(),
+            public_values.abi_encode().unwrap()
+        );
+        assert!(PublicValues::from_public_inputs(&pis[1..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_abi_invalid_encodings() -> Result<()> {
+        let mut public_values = public_values();
+        let mut encoding = public_values.abi_encode().unwrap();
+        assert!(PublicValues::abi_decode(&encoding[1..]).is_err());
+        encoding[6 * 32] = 1;
+        assert!(PublicValues::abi_decode(&encoding).is_err());
+        encoding[6 * 32] = 0;
+        PublicValues::abi_decode(&encoding)?;
+
+        // A presence flag must be a bool, and an absent extension must be 0.
+        encoding[302 * 32 - 1] = 2;
+        assert!(PublicValues::abi_decode(&encoding).is_err());
+        encoding[302 * 32 - 1] = 0;
+        encoding[310 * 32 - 1] = 1;
+        assert!(PublicValues::abi_decode(&encoding).is_err());
+        encoding[310 * 32 - 1] = 0;
+        // So must the values of an undeclared extra public value.
+        encoding[326 * 32 - 1] = 1;
+        assert!(PublicValues::abi_decode(&encoding).is_err());
+
+        public_values.block_hashes.prev_hashes.pop();
+        assert!(public_values.abi_encode().is_err());
+        Ok(())
+    }
+}
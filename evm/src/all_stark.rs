@@ -3,6 +3,7 @@ use std::iter;
 use plonky2::field::extension::Extendable;
 use plonky2::field::types::Field;
 use plonky2::hash::hash_types::RichField;
+use serde::{Deserialize, Serialize};
 
 use crate::arithmetic::arithmetic_stark;
 use crate::arithmetic::arithmetic_stark::ArithmeticStark;
@@ -19,6 +20,7 @@ use crate::keccak_sponge::keccak_sponge_stark;
 use crate::keccak_sponge::keccak_sponge_stark::KeccakSpongeStark;
 use crate::logic;
 use crate::logic::LogicStark;
+use crate::lookup::Lookup;
 use crate::memory::memory_stark;
 use crate::memory::memory_stark::MemoryStark;
 use crate::stark::Stark;
@@ -64,10 +66,34 @@ impl<F: RichField + Extendable<D>, const D: usize> AllStark<F, D> {
             self.memory_stark.num_lookup_helper_columns(config),
         ]
     }
+
+    pub(crate) fn lookups(&self) -> [Vec<Lookup>; NUM_TABLES] {
+        [
+            self.arithmetic_stark.lookups(),
+            self.byte_packing_stark.lookups(),
+            self.cpu_stark.lookups(),
+            self.keccak_stark.lookups(),
+            self.keccak_sponge_stark.lookups(),
+            self.logic_stark.lookups(),
+            self.memory_stark.lookups(),
+        ]
+    }
+
+    pub(crate) fn constraint_degrees(&self) -> [usize; NUM_TABLES] {
+        [
+            self.arithmetic_stark.constraint_degree(),
+            self.byte_packing_stark.constraint_degree(),
+            self.cpu_stark.constraint_degree(),
+            self.keccak_stark.constraint_degree(),
+            self.keccak_sponge_stark.constraint_degree(),
+            self.logic_stark.constraint_degree(),
+            self.memory_stark.constraint_degree(),
+        ]
+    }
 }
 
 /// Associates STARK tables with a unique index.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Table {
     Arithmetic = 0,
     BytePacking = 1,
@@ -83,7 +109,7 @@ pub(crate) const NUM_TABLES: usize = Table::Memory as usize + 1;
 
 impl Table {
     /// Returns all STARK table indices.
-    pub(crate) fn all() -> [Self; NUM_TABLES] {
+    pub fn all() -> [Self; NUM_TABLES] {
         [
             Self::Arithmetic,
             Self::BytePacking,
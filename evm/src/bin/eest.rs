@@ -0,0 +1,54 @@
+//! Runs execution-spec-tests fixtures through witness generation.
+//!
+//! Usage: `eest [--fork <name>]... [--prove <count>] [--seed <seed>] <path>...`, where each path
+//! is a fixture file or a directory of them.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use anyhow::{anyhow, Result};
+use env_logger::{try_init_from_env, Env, DEFAULT_FILTER_ENV};
+use plonky2_evm::eest::{fixture_files, run_fixture_files, RunnerConfig};
+
+fn main() -> Result<ExitCode> {
+    let _ = try_init_from_env(Env::default().filter_or(DEFAULT_FILTER_ENV, "warn"));
+
+    let mut config = RunnerConfig::default();
+    let mut forks = vec![];
+    let mut paths = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--fork" => forks.push(value()?),
+            "--prove" => config.num_proofs = value()?.parse()?,
+            "--seed" => config.seed = value()?.parse()?,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!(
+            "Usage: eest [--fork <name>]... [--prove <count>] [--seed <seed>] <path>..."
+        ));
+    }
+    if !forks.is_empty() {
+        config.forks = forks;
+    }
+
+    let mut files = vec![];
+    for path in paths {
+        fs::metadata(&path).map_err(|err| anyhow!("{}: {err}", path.display()))?;
+        files.extend(fixture_files(&path)?);
+    }
+    let report = run_fixture_files(&files, &config);
+    println!("{report}");
+    Ok(if report.is_success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,322 @@
+//! A disk cache of the recursive circuits of `AllRecursiveCircuits`, which take a long time to
+//! build.
+//!
+//! Circuits are stored under a key hashing everything they depend on: the crate version, which
+//! stands in for the recursion code, a digest of the definitions of the tables and cross-table
+//! lookups, the kernel hash, the generic config, the relevant prover configs and the degree
+//! ranges. The circuits of each table are stored apart from the root, aggregation and block
+//! circuits, so that after changing e.g. the degree range of one table, only the circuits of that
+//! table and those above them are rebuilt. Entries which no longer match any configuration are left
+//! on disk.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ethereum_types::H256;
+use keccak_hash::keccak;
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::{Field, PrimeField64};
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+use plonky2::util::serialization::{Buffer, IoResult, Remaining};
+
+use crate::all_stark::{AllStark, Table, NUM_TABLES};
+use crate::config::EvmProverConfig;
+use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::cpu::kernel::aggregator::KERNEL;
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::stark::Stark;
+
+/// A directory holding serialized recursive circuits, see
+/// `AllRecursiveCircuits::new_with_cache`.
+#[derive(Clone, Debug)]
+pub struct CircuitCache {
+    dir: PathBuf,
+}
+
+impl CircuitCache {
+    /// A cache in `dir`, which is created when the first circuits are stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The key of the circuits shrinking the proofs of `table` in `all_stark`, for degrees in
+    /// `degree_bits_range`.
+    pub fn table_key<C: GenericConfig<D>, const D: usize>(
+        all_stark: &AllStark<C::F, D>,
+        table: Table,
+        degree_bits_range: &Range<usize>,
+        config: &EvmProverConfig,
+    ) -> H256 {
+        hash_key::<C, D>(&format!(
+            "table {table:?} {:?} {degree_bits_range:?} {:?} {:?}",
+            table_definition_digest::<C, D>(all_stark, table),
+            config.stark_config,
+            config.shrinking_config,
+        ))
+    }
+
+    /// The key of the root, aggregation and block circuits, built on top of the table circuits
+    /// with keys `table_keys`.
+    pub fn recursion_key<C: GenericConfig<D>, const D: usize>(
+        table_keys: &[H256; NUM_TABLES],
+        config: &EvmProverConfig,
+    ) -> H256 {
+        hash_key::<C, D>(&format!(
+            "recursion {table_keys:?} {:?} {:?} {:?}",
+            config.stark_config, config.recursion_config, config.extra_public_values,
+        ))
+    }
+
+    /// Returns the value stored under `name` and `key`, or builds it and stores it. Cache failures
+    /// are logged and otherwise ignored, since they only cost a rebuild.
+    pub(crate) fn load_or_build<T>(
+        &self,
+        name: &str,
+        key: H256,
+        read: impl FnOnce(&mut Buffer) -> IoResult<T>,
+        write: impl FnOnce(&T, &mut Vec<u8>) -> IoResult<()>,
+        build: impl FnOnce() -> T,
+    ) -> T {
+        let path = self.path(name, key);
+        if let Ok(bytes) = fs::read(&path) {
+            let mut buffer = Buffer::new(&bytes);
+            match read(&mut buffer).and_then(|value| buffer.ensure_empty().map(|()| value)) {
+                Ok(value) => {
+                    log::info!("Loaded the {name} circuits from {path:?}");
+                    return value;
+                }
+                Err(_) => log::warn!("Failed to read the {name} circuits from {path:?}"),
+            }
+        }
+
+        let value = build();
+        let mut bytes = Vec::new();
+        if write(&value, &mut bytes).is_err() {
+            log::warn!("Failed to serialize the {name} circuits");
+        } else if let Err(err) = self.store(&path, &bytes) {
+            log::warn!("Failed to cache the {name} circuits: {err:?}");
+        }
+        value
+    }
+
+    /// Writes `bytes` to `path`, through a temporary file so that concurrent builds never read a
+    /// partial entry.
+    fn store(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {:?}", self.dir))?;
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, bytes).with_context(|| format!("Failed to write {tmp:?}"))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to rename {tmp:?} to {path:?}"))
+    }
+
+    fn path(&self, name: &str, key: H256) -> PathBuf {
+        self.dir.join(format!("{name}-{key:x}.bin"))
+    }
+}
+
+fn hash_key<C: GenericConfig<D>, const D: usize>(description: &str) -> H256 {
+    keccak(format!(
+        "plonky2_evm {} kernel {:?} {} {D} {description}",
+        env!("CARGO_PKG_VERSION"),
+        KERNEL.code_hash,
+        std::any::type_name::<C>(),
+    ))
+}
+
+/// A digest of the definition of `table` in `all_stark`, so that changing its constraints or the
+/// cross-table lookups invalidates its circuits even if the crate version stays the same. The
+/// constraints are fingerprinted by their evaluation at a fixed pseudo-random frame, and their
+/// recursive version by the digest of a circuit evaluating them.
+fn table_definition_digest<C: GenericConfig<D>, const D: usize>(
+    all_stark: &AllStark<C::F, D>,
+    table: Table,
+) -> H256 {
+    let (constraints, recursive_constraints) = match table {
+        Table::Arithmetic => constraints_fingerprints::<C, _, D>(&all_stark.arithmetic_stark),
+        Table::BytePacking => constraints_fingerprints::<C, _, D>(&all_stark.byte_packing_stark),
+        Table::Cpu => constraints_fingerprints::<C, _, D>(&all_stark.cpu_stark),
+        Table::Keccak => constraints_fingerprints::<C, _, D>(&all_stark.keccak_stark),
+        Table::KeccakSponge => constraints_fingerprints::<C, _, D>(&all_stark.keccak_sponge_stark),
+        Table::Logic => constraints_fingerprints::<C, _, D>(&all_stark.logic_stark),
+        Table::Memory => constraints_fingerprints::<C, _, D>(&all_stark.memory_stark),
+    };
+    keccak(format!(
+        "{constraints} {recursive_constraints:?} {} {:?} {:?}",
+        all_stark.constraint_degrees()[table as usize],
+        all_stark.lookups()[table as usize],
+        all_stark.cross_table_lookups,
+    ))
+}
+
+/// Evaluates the constraints of `stark` at a frame, and Lagrange and challenge values, derived
+/// from fixed seeds, so that the result only changes with the constraints.
+fn eval_constraints_at_fixed_frame<F, S, const D: usize>(stark: &S) -> u64
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let value = |i: usize| F::from_noncanonical_u64(keccak(i.to_le_bytes()).to_low_u64_le());
+    let local_values = (0..S::COLUMNS).map(value).collect::<Vec<_>>();
+    let next_values = (S::COLUMNS..2 * S::COLUMNS).map(value).collect::<Vec<_>>();
+    let vars = S::EvaluationFrame::from_values(&local_values, &next_values);
+    let [alpha, z_last, lagrange_first, lagrange_last] =
+        core::array::from_fn(|i| value(2 * S::COLUMNS + i));
+    let mut consumer =
+        ConstraintConsumer::<F>::new(vec![alpha], z_last, lagrange_first, lagrange_last);
+    stark.eval_packed_base(&vars, &mut consumer);
+    consumer.accumulators()[0].to_canonical_u64()
+}
+
+/// The fingerprints of the constraints of `stark` and of their recursive version.
+fn constraints_fingerprints<C, S, const D: usize>(
+    stark: &S,
+) -> (u64, <C::Hasher as Hasher<C::F>>::Hash)
+where
+    C: GenericConfig<D>,
+    S: Stark<C::F, D>,
+{
+    (
+        eval_constraints_at_fixed_frame(stark),
+        recursive_constraints_digest::<C, S, D>(stark),
+    )
+}
+
+/// Builds a circuit evaluating the recursive constraints of `stark` at a virtual frame, and returns
+/// its digest, which changes with the gates and wiring generated by `eval_ext_circuit`.
+fn recursive_constraints_digest<C, S, const D: usize>(
+    stark: &S,
+) -> <C::Hasher as Hasher<C::F>>::Hash
+where
+    C: GenericConfig<D>,
+    S: Stark<C::F, D>,
+{
+    let mut builder = CircuitBuilder::<C::F, D>::new(CircuitConfig::standard_recursion_config());
+    let local_values = builder.add_virtual_extension_targets(S::COLUMNS);
+    let next_values = builder.add_virtual_extension_targets(S::COLUMNS);
+    let vars = S::EvaluationFrameTarget::from_values(&local_values, &next_values);
+    let alpha = builder.add_virtual_target();
+    let [z_last, lagrange_first, lagrange_last] =
+        core::array::from_fn(|_| builder.add_virtual_extension_target());
+    let mut consumer = RecursiveConstraintConsumer::new(
+        builder.zero_extension(),
+        vec![alpha],
+        z_last,
+        lagrange_first,
+        lagrange_last,
+    );
+    stark.eval_ext_circuit(&mut builder, &vars, &mut consumer);
+    for acc in consumer.accumulators() {
+        builder.register_public_inputs(&acc.0);
+    }
+    builder.build::<C>().verifier_only.circuit_digest
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+    use plonky2::util::serialization::{Read, Write};
+
+    use super::*;
+    use crate::config::StarkConfig;
+
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    const D: usize = 2;
+
+    #[test]
+    fn test_keys() {
+        let all_stark = AllStark::<F, D>::default();
+        let config = EvmProverConfig::from_stark_config(StarkConfig::standard_fast_config());
+        let ranges = core::array::from_fn::<_, NUM_TABLES, _>(|_| 16..20);
+        let table_keys = |all_stark: &AllStark<_, D>, ranges: &[Range<usize>; NUM_TABLES]| {
+            Table::all().map(|t| {
+                CircuitCache::table_key::<C, D>(all_stark, t, &ranges[t as usize], &config)
+            })
+        };
+        let keys = table_keys(&all_stark, &ranges);
+        let recursion_key = CircuitCache::recursion_key::<C, D>(&keys, &config);
+
+        // Changing a table's range only changes its key, and the recursion key.
+        let mut new_ranges = ranges.clone();
+        new_ranges[Table::Memory as usize] = 16..21;
+        let new_keys = table_keys(&all_stark, &new_ranges);
+        for table in Table::all() {
+            let i = table as usize;
+            assert_eq!(keys[i] == new_keys[i], table != Table::Memory);
+        }
+        assert_ne!(
+            CircuitCache::recursion_key::<C, D>(&new_keys, &config),
+            recursion_key
+        );
+
+        // Changing the cross-table lookups changes the keys.
+        let mut new_all_stark = all_stark.clone();
+        new_all_stark.cross_table_lookups.pop();
+        let new_keys = table_keys(&new_all_stark, &ranges);
+        assert!(keys
+            .iter()
+            .zip(&new_keys)
+            .all(|(key, new_key)| key != new_key));
+
+        let mut wider_config = config;
+        wider_config.recursion_config.num_wires += 1;
+        assert_ne!(
+            CircuitCache::recursion_key::<C, D>(&keys, &wider_config),
+            recursion_key
+        );
+    }
+
+    #[test]
+    fn test_constraints_digest() {
+        let all_stark = AllStark::<F, D>::default();
+        // The digest is deterministic, and distinguishes tables.
+        let digests = Table::all().map(|t| table_definition_digest::<C, D>(&all_stark, t));
+        assert_eq!(
+            digests,
+            Table::all().map(|t| table_definition_digest::<C, D>(&all_stark, t))
+        );
+        assert_ne!(
+            eval_constraints_at_fixed_frame(&all_stark.logic_stark),
+            eval_constraints_at_fixed_frame(&all_stark.arithmetic_stark)
+        );
+        assert_ne!(
+            recursive_constraints_digest::<C, _, D>(&all_stark.logic_stark),
+            recursive_constraints_digest::<C, _, D>(&all_stark.arithmetic_stark)
+        );
+    }
+
+    #[test]
+    fn test_load_or_build() {
+        let dir = std::env::temp_dir().join(format!("circuit_cache_{}", std::process::id()));
+        let cache = CircuitCache::new(&dir);
+        let key = H256::repeat_byte(1);
+        let load_or_build = |value: usize| {
+            cache.load_or_build(
+                "test",
+                key,
+                |buffer| buffer.read_usize(),
+                |&value, buffer| buffer.write_usize(value),
+                || value,
+            )
+        };
+
+        assert_eq!(load_or_build(7), 7);
+        // The value is now read from the cache rather than built.
+        assert_eq!(load_or_build(8), 7);
+
+        // A corrupted entry is rebuilt.
+        fs::write(cache.path("test", key), [1, 2, 3]).unwrap();
+        assert_eq!(load_or_build(9), 9);
+        assert_eq!(load_or_build(10), 9);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
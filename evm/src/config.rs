@@ -1,6 +1,23 @@
+use std::ops::Range;
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::Field;
 use plonky2::fri::reduction_strategies::FriReductionStrategy;
 use plonky2::fri::{FriConfig, FriParams};
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::circuit_data::CircuitConfig;
+
+use crate::all_stark::{AllStark, NUM_TABLES};
+use crate::cpu::kernel::aggregator::KERNEL;
+use crate::proof::NUM_EXTRA_PUBLIC_VALUES;
+use crate::security::{security_report, SoundnessCategory};
 
+/// The parameters of the table STARKs, which their soundness and the circuits verifying them depend
+/// on. Options which only affect how proofs are computed are in [`ProverOptions`].
+///
+/// [`ProverOptions`]: crate::prover::ProverOptions
+#[derive(Clone, Debug)]
 pub struct StarkConfig {
     pub security_bits: usize,
 
@@ -9,6 +26,13 @@ pub struct StarkConfig {
     pub num_challenges: usize,
 
     pub fri_config: FriConfig,
+
+    /// Whether proofs should hide the traces. If so, Merkle leaves are salted, each committed
+    /// trace or auxiliary polynomial `p` is masked as `p + Z_H r` for a random `r` with
+    /// [`Self::num_mask_coeffs`] coefficients, and quotient chunks are masked similarly. This
+    /// doubles the degree bound of committed polynomials, and requires traces at least as long as
+    /// the masks.
+    pub zero_knowledge: bool,
 }
 
 impl StarkConfig {
@@ -25,10 +49,308 @@ impl StarkConfig {
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 84,
             },
+            zero_knowledge: false,
+        }
+    }
+
+    /// A zero-knowledge configuration targeting ~100 bit conjectured security. Since masking
+    /// doubles the degree of committed polynomials, quotients need one more chunk, which requires
+    /// a rate of 1/4 for the degree 3 constraints used by the EVM tables.
+    pub fn standard_fast_zk_config() -> Self {
+        Self {
+            security_bits: 100,
+            num_challenges: 2,
+            fri_config: FriConfig {
+                rate_bits: 2,
+                cap_height: 4,
+                proof_of_work_bits: 16,
+                reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+                num_query_rounds: 42,
+            },
+            zero_knowledge: true,
+        }
+    }
+
+    /// The number of random coefficients of the masks of committed polynomials in zero-knowledge
+    /// mode, for traces of length `2^degree_bits`. This is the number of base field values revealed
+    /// about each polynomial, as in plonky2's blinding: `D` for each of the openings at `zeta` and
+    /// `g zeta`, and per FRI query, one value of the polynomial plus `D` per folding point and per
+    /// coefficient of the final polynomial. This many random coefficients make all revealed values
+    /// uniformly random.
+    pub fn num_mask_coeffs<const D: usize>(&self, degree_bits: usize) -> usize {
+        let fri_params = self.fri_params(degree_bits);
+        let num_folding_points = fri_params
+            .reduction_arity_bits
+            .iter()
+            .map(|&arity_bits| (1 << arity_bits) - 1)
+            .sum::<usize>();
+        let fri_openings = self.fri_config.num_query_rounds
+            * (1 + D * num_folding_points + D * fri_params.final_poly_len());
+        2 * D + fri_openings
+    }
+
+    /// Checks that traces of length `2^degree_bits` can be masked in zero-knowledge mode, which
+    /// needs masks of degree below the trace length.
+    pub fn check_zk_degree_bits<const D: usize>(&self, degree_bits: usize) -> Result<()> {
+        if !self.zero_knowledge {
+            return Ok(());
         }
+        let num_mask_coeffs = self.num_mask_coeffs::<D>(degree_bits);
+        ensure!(
+            1 << degree_bits >= num_mask_coeffs,
+            "Zero-knowledge proofs of traces of length 2^{} need masks of {} coefficients, more \
+             than the trace length.",
+            degree_bits,
+            num_mask_coeffs
+        );
+        Ok(())
+    }
+
+    /// The log of the degree bound of committed polynomials, for a trace of length
+    /// `2^degree_bits`.
+    pub fn committed_degree_bits(&self, degree_bits: usize) -> usize {
+        degree_bits + usize::from(self.zero_knowledge)
+    }
+
+    /// The log of the largest trace length that can be proven, as the LDEs of committed
+    /// polynomials must fit in the multiplicative subgroups of `F`.
+    pub fn max_degree_bits<F: Field>(&self) -> usize {
+        F::TWO_ADICITY - self.fri_config.rate_bits - usize::from(self.zero_knowledge)
     }
 
     pub(crate) fn fri_params(&self, degree_bits: usize) -> FriParams {
-        self.fri_config.fri_params(degree_bits, false)
+        self.fri_config
+            .fri_params(self.committed_degree_bits(degree_bits), self.zero_knowledge)
+    }
+}
+
+/// What to favour when choosing the configs of the EVM proving pipeline.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProvingGoal {
+    /// Low FRI rates everywhere, for the fastest proving, with large proofs.
+    ProverLatency,
+    /// Higher FRI rates for the table proofs, which are the ones sent between workers in
+    /// distributed proving, and for the final wrapping of block proofs, which is the one sent to
+    /// verifiers. Proving is slower.
+    ProofSize,
+}
+
+/// The configs of each phase of the EVM proving pipeline: the table STARKs, the circuits
+/// shrinking their proofs, the root, aggregation and block circuits, and the block wrapper.
+#[derive(Clone, Debug)]
+pub struct EvmProverConfig {
+    pub stark_config: StarkConfig,
+    /// The config of the circuits shrinking table proofs down to the recursion threshold.
+    pub shrinking_config: CircuitConfig,
+    /// The config of the root circuit, shared by the aggregation and block circuits which
+    /// recursively verify it.
+    pub recursion_config: CircuitConfig,
+    /// The config of `BlockWrapperCircuitData`, whose generic config may differ from the others.
+    pub wrap_config: CircuitConfig,
+    /// The extra public value slots declared by the chain, which the root circuit only accepts.
+    pub extra_public_values: ExtraPublicValuesConfig,
+}
+
+impl EvmProverConfig {
+    /// The configs used along with `stark_config` by `AllRecursiveCircuits::new`.
+    pub fn from_stark_config(stark_config: StarkConfig) -> Self {
+        Self {
+            stark_config,
+            shrinking_config: shrinking_config(CircuitConfig::standard_recursion_config()),
+            recursion_config: CircuitConfig::standard_recursion_config(),
+            wrap_config: CircuitConfig::wrap_config(),
+            extra_public_values: ExtraPublicValuesConfig::default(),
+        }
+    }
+
+    /// Chooses the configs of all phases for `goal`, targeting `security_bits` of conjectured
+    /// security, for tables of at most `2^(degree_bits_ranges[i].end - 1)` rows.
+    ///
+    /// The FRI queries of every phase are set to reach the target. The number of STARK challenges
+    /// is set so that the arguments with challenges from the base field, i.e. the CTLs, lookups and
+    /// constraint combinations, reach it as well. Other components, such as out-of-domain
+    /// evaluations, don't depend on the config; see `security_report` for the full picture.
+    ///
+    /// The recursion thresholds of `AllRecursiveCircuits` are tuned for 100 bits, and much higher
+    /// targets may make its circuits outgrow them.
+    pub fn new<F: RichField + Extendable<D>, const D: usize>(
+        goal: ProvingGoal,
+        security_bits: usize,
+        all_stark: &AllStark<F, D>,
+        degree_bits_ranges: &[Range<usize>; NUM_TABLES],
+    ) -> Self {
+        let (stark_rate_bits, wrap_rate_bits) = match goal {
+            ProvingGoal::ProverLatency => (1, 3),
+            ProvingGoal::ProofSize => (2, 7),
+        };
+
+        let mut stark_config = StarkConfig {
+            security_bits,
+            num_challenges: 1,
+            fri_config: fri_config(stark_rate_bits, security_bits),
+            ..StarkConfig::standard_fast_config()
+        };
+        // The soundness of the base field arguments grows linearly with the number of challenges.
+        let report = security_report(all_stark, &stark_config, degree_bits_ranges, &[]);
+        stark_config.num_challenges = report
+            .components
+            .iter()
+            .filter(|component| {
+                matches!(
+                    component.category,
+                    SoundnessCategory::ConstraintCombination
+                        | SoundnessCategory::Lookup
+                        | SoundnessCategory::CrossTableLookup
+                )
+            })
+            .map(|component| {
+                assert!(
+                    component.bits > 0.0,
+                    "{} is unsound with base field challenges",
+                    component.name
+                );
+                (security_bits as f64 / component.bits).ceil() as usize
+            })
+            .max()
+            .unwrap_or(1)
+            .max(1);
+
+        let recursion_config = CircuitConfig {
+            security_bits,
+            fri_config: fri_config(3, security_bits),
+            ..CircuitConfig::standard_recursion_config()
+        };
+        Self {
+            stark_config,
+            shrinking_config: shrinking_config(recursion_config.clone()),
+            wrap_config: CircuitConfig {
+                fri_config: fri_config(wrap_rate_bits, security_bits),
+                ..recursion_config.clone()
+            },
+            recursion_config,
+            extra_public_values: ExtraPublicValuesConfig::default(),
+        }
+    }
+
+    /// The recursion configs, named as expected by `security_report`.
+    pub fn recursion_configs(&self) -> [(&str, &CircuitConfig); 3] {
+        [
+            ("shrinking", &self.shrinking_config),
+            ("recursion", &self.recursion_config),
+            ("wrap", &self.wrap_config),
+        ]
+    }
+}
+
+/// A slot of `ExtraPublicValues` declared by a chain.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExtraPublicValueSlot {
+    /// The name of the value, e.g. `l2_to_l1_message_root`.
+    pub name: String,
+    /// The global label of the kernel routine updating the value, with
+    /// `%mstore_extra_public_value`.
+    pub routine: String,
+}
+
+/// The extra public value slots declared by a chain, each updated by a designated kernel routine,
+/// see `ExtraPublicValues`. Chains without such values declare none.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ExtraPublicValuesConfig {
+    pub slots: [Option<ExtraPublicValueSlot>; NUM_EXTRA_PUBLIC_VALUES],
+}
+
+impl ExtraPublicValuesConfig {
+    /// Declares `slot` as holding the value `name`, updated by the kernel routine at the global
+    /// label `routine`.
+    pub fn with_slot(mut self, slot: usize, name: &str, routine: &str) -> Self {
+        assert!(
+            slot < NUM_EXTRA_PUBLIC_VALUES,
+            "There are only {NUM_EXTRA_PUBLIC_VALUES} extra public value slots"
+        );
+        self.slots[slot] = Some(ExtraPublicValueSlot {
+            name: name.into(),
+            routine: routine.into(),
+        });
+        self
+    }
+
+    pub fn is_declared(&self, slot: usize) -> bool {
+        self.slots[slot].is_some()
+    }
+
+    /// Checks that the routine of each declared slot is a global label of the kernel.
+    pub fn check_kernel_routines(&self) -> anyhow::Result<()> {
+        for slot in self.slots.iter().flatten() {
+            ensure!(
+                KERNEL.global_labels.contains_key(&slot.routine),
+                "The kernel has no routine {} updating the extra public value {}",
+                slot.routine,
+                slot.name
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A FRI config with the usual parameters, and enough queries at `rate_bits` to reach
+/// `security_bits` of conjectured security. The proof of work alone can't provide all of it, as
+/// that would leave FRI without any query.
+fn fri_config(rate_bits: usize, security_bits: usize) -> FriConfig {
+    let proof_of_work_bits = 16;
+    assert!(
+        security_bits > proof_of_work_bits as usize,
+        "security_bits ({security_bits}) must exceed the {proof_of_work_bits} bits of proof of work, \
+         or FRI would make no queries"
+    );
+    FriConfig {
+        rate_bits,
+        cap_height: 4,
+        proof_of_work_bits,
+        reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+        num_query_rounds: (security_bits - proof_of_work_bits as usize).div_ceil(rate_bits),
+    }
+}
+
+/// Our usual recursion threshold is 2^12 gates, but for the circuits shrinking table proofs, we use
+/// a few more gates for a constant inner VK and for public inputs. This pushes us over the
+/// threshold to 2^13. As long as we're at 2^13 gates, we might as well use a narrower witness.
+fn shrinking_config(recursion_config: CircuitConfig) -> CircuitConfig {
+    CircuitConfig {
+        num_routed_wires: 40,
+        ..recursion_config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fri_config_query_rounds() {
+        assert_eq!(fri_config(3, 17).num_query_rounds, 1);
+        assert_eq!(fri_config(3, 100).num_query_rounds, 28);
+    }
+
+    #[test]
+    fn test_zk_degree_bits() {
+        const D: usize = 2;
+        let config = StarkConfig::standard_fast_zk_config();
+        // Traces of 2^13 rows are committed with degree 2^14, and FRI folds them with arities 16,
+        // 16 and 16 down to a final polynomial of 4 coefficients.
+        assert_eq!(
+            config.num_mask_coeffs::<D>(13),
+            2 * 2 + 42 * (1 + 2 * 3 * 15 + 2 * 4)
+        );
+        assert!(config.check_zk_degree_bits::<D>(12).is_err());
+        config.check_zk_degree_bits::<D>(13).unwrap();
+        StarkConfig::standard_fast_config()
+            .check_zk_degree_bits::<D>(1)
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "FRI would make no queries")]
+    fn test_fri_config_without_queries() {
+        fri_config(3, 16);
     }
 }
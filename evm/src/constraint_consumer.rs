@@ -1,4 +1,5 @@
 use std::marker::PhantomData;
+use std::panic::Location;
 
 use plonky2::field::extension::Extendable;
 use plonky2::field::packed::PackedField;
@@ -26,6 +27,9 @@ pub struct ConstraintConsumer<P: PackedField> {
     /// The evaluation of the Lagrange basis polynomial which is nonzero at the point associated
     /// with the last trace row, and zero at other points in the subgroup.
     lagrange_basis_last: P,
+
+    /// If recording, each constraint emitted so far, along with the location it was emitted from.
+    recorded: Option<Vec<(P, &'static Location<'static>)>>,
 }
 
 impl<P: PackedField> ConstraintConsumer<P> {
@@ -41,6 +45,19 @@ impl<P: PackedField> ConstraintConsumer<P> {
             z_last,
             lagrange_basis_first,
             lagrange_basis_last,
+            recorded: None,
+        }
+    }
+
+    /// Creates a consumer which records each constraint rather than accumulating them.
+    pub(crate) fn new_recording(
+        z_last: P,
+        lagrange_basis_first: P,
+        lagrange_basis_last: P,
+    ) -> Self {
+        Self {
+            recorded: Some(vec![]),
+            ..Self::new(vec![], z_last, lagrange_basis_first, lagrange_basis_last)
         }
     }
 
@@ -48,27 +65,40 @@ impl<P: PackedField> ConstraintConsumer<P> {
         self.constraint_accs
     }
 
+    /// Returns the constraints recorded by a consumer created with `new_recording`, in the order
+    /// they were emitted.
+    pub(crate) fn recorded(self) -> Vec<(P, &'static Location<'static>)> {
+        self.recorded.unwrap_or_default()
+    }
+
     /// Add one constraint valid on all rows except the last.
+    #[track_caller]
     pub fn constraint_transition(&mut self, constraint: P) {
         self.constraint(constraint * self.z_last);
     }
 
     /// Add one constraint on all rows.
+    #[track_caller]
     pub fn constraint(&mut self, constraint: P) {
         for (&alpha, acc) in self.alphas.iter().zip(&mut self.constraint_accs) {
             *acc *= alpha;
             *acc += constraint;
         }
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push((constraint, Location::caller()));
+        }
     }
 
     /// Add one constraint, but first multiply it by a filter such that it will only apply to the
     /// first row of the trace.
+    #[track_caller]
     pub fn constraint_first_row(&mut self, constraint: P) {
         self.constraint(constraint * self.lagrange_basis_first);
     }
 
     /// Add one constraint, but first multiply it by a filter such that it will only apply to the
     /// last row of the trace.
+    #[track_caller]
     pub fn constraint_last_row(&mut self, constraint: P) {
         self.constraint(constraint * self.lagrange_basis_last);
     }
@@ -0,0 +1,586 @@
+//! A coordinator for proving blocks with many workers.
+//!
+//! The [`Coordinator`] splits a block into the tasks of [`prover_tasks`](crate::prover_tasks), for
+//! each transaction, or batch of transactions as chosen with a [`ProofGranularity`], and each
+//! table, and hands them to a [`ProverTransport`], which may run them on other machines. The tasks
+//! of all transactions are independent, so each call to the transport holds the tasks of a step of
+//! the pipeline for all transactions at once. Returned STARK proofs
+//! are verified before being shrunk, and recursive proofs before being aggregated.
+
+use anyhow::{anyhow, ensure, Result};
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::H256;
+use itertools::Itertools;
+use plonky2::field::extension::Extendable;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::timed;
+use plonky2::util::timing::TimingTree;
+use plonky2_util::log2_strict;
+use serde::{Deserialize, Serialize};
+
+use crate::all_stark::{AllStark, Table, NUM_TABLES};
+use crate::config::StarkConfig;
+use crate::fixed_recursive_verifier::AllRecursiveCircuits;
+use crate::generation::batch::merge_generation_inputs;
+use crate::generation::block_trace::empty_trie_root;
+use crate::generation::{generate_traces_with_limits, GenerationInputs, TrieInputs};
+use crate::proof::{AllProof, ExtraBlockData, PublicValues};
+use crate::prover::ProverOptions;
+use crate::prover_tasks::{
+    ctl_challenges, AggregationRequest, BlockRequest, CtlChallenges, RecursiveProofResponse,
+    RootRequest, TableProofRequest, TableProofResponse, TableShrinkRequest, TableShrinkResponse,
+    TraceCommitmentRequest, TraceCommitmentResponse,
+};
+use crate::verifier::verify_proof;
+use crate::Node;
+
+/// How finely the transactions of a block are split into root proofs, which are then aggregated.
+/// Finer proofs are proven in parallel, while coarser ones need fewer aggregations.
+///
+/// Splitting the execution of a transaction into segments isn't supported: each root proof starts
+/// and ends between transactions.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProofGranularity {
+    /// A root proof for each transaction.
+    PerTransaction,
+    /// A root proof for each batch of this many consecutive transactions, the last batch possibly
+    /// being smaller.
+    Batches(usize),
+    /// A single root proof for all the transactions of the block.
+    PerBlock,
+}
+
+/// A unit of work for a worker.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum ProverTask<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    TraceCommitment(TraceCommitmentRequest<F>),
+    TableProof(TableProofRequest<F, C::Hasher>),
+    TableShrink(TableShrinkRequest<F, C, D>),
+    Root(RootRequest<F, C, D>),
+    Aggregation(AggregationRequest<F, C, D>),
+    Block(BlockRequest<F, C, D>),
+}
+
+/// The result of a [`ProverTask`]. Root, aggregation and block tasks all return a
+/// [`RecursiveProofResponse`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub enum ProverTaskResult<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    TraceCommitment(TraceCommitmentResponse<F, C::Hasher>),
+    TableProof(TableProofResponse<F, C, D>),
+    TableShrink(TableShrinkResponse<F, C, D>),
+    RecursiveProof(RecursiveProofResponse<F, C, D>),
+}
+
+/// Runs prover tasks on behalf of a [`Coordinator`], e.g. by sending them to remote workers
+/// through a job queue. Workers can execute the tasks they receive with a [`LocalWorker`].
+pub trait ProverTransport<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    /// Runs independent tasks, possibly concurrently, and returns their results in the same order.
+    fn dispatch(&self, tasks: Vec<ProverTask<F, C, D>>) -> Result<Vec<ProverTaskResult<F, C, D>>>;
+}
+
+/// Executes prover tasks on the current machine. As a [`ProverTransport`], it runs all tasks
+/// locally, one after the other.
+pub struct LocalWorker<'a, F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub all_stark: &'a AllStark<F, D>,
+    pub config: &'a StarkConfig,
+    pub circuits: &'a AllRecursiveCircuits<F, C, D>,
+}
+
+impl<'a, F, C, const D: usize> LocalWorker<'a, F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn execute(
+        &self,
+        task: ProverTask<F, C, D>,
+        timing: &mut TimingTree,
+    ) -> Result<ProverTaskResult<F, C, D>> {
+        Ok(match task {
+            ProverTask::TraceCommitment(request) => {
+                ProverTaskResult::TraceCommitment(request.execute::<C, D>(self.config, timing))
+            }
+            ProverTask::TableProof(request) => ProverTaskResult::TableProof(request.execute(
+                self.all_stark,
+                self.config,
+                timing,
+            )?),
+            ProverTask::TableShrink(request) => {
+                ProverTaskResult::TableShrink(request.execute(self.circuits, self.config)?)
+            }
+            ProverTask::Root(request) => {
+                ProverTaskResult::RecursiveProof(request.execute(self.circuits)?)
+            }
+            ProverTask::Aggregation(request) => {
+                ProverTaskResult::RecursiveProof(request.execute(self.circuits)?)
+            }
+            ProverTask::Block(request) => {
+                ProverTaskResult::RecursiveProof(request.execute(self.circuits)?)
+            }
+        })
+    }
+}
+
+impl<'a, F, C, const D: usize> ProverTransport<F, C, D> for LocalWorker<'a, F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    fn dispatch(&self, tasks: Vec<ProverTask<F, C, D>>) -> Result<Vec<ProverTaskResult<F, C, D>>> {
+        tasks
+            .into_iter()
+            .map(|task| self.execute(task, &mut TimingTree::default()))
+            .collect()
+    }
+}
+
+/// Splits blocks into prover tasks, dispatches them with a [`ProverTransport`], and checks their
+/// results.
+pub struct Coordinator<'a, F, C, T, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub all_stark: &'a AllStark<F, D>,
+    pub config: &'a StarkConfig,
+    /// The circuits the workers use, against which returned recursive proofs are verified.
+    pub circuits: &'a AllRecursiveCircuits<F, C, D>,
+    pub transport: T,
+}
+
+impl<'a, F, C, T, const D: usize> Coordinator<'a, F, C, T, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+    T: ProverTransport<F, C, D>,
+{
+    /// Proves a block from the inputs of each of its transactions, in order, and the proof of its
+    /// parent block, if any, with a root proof for each transaction.
+    pub fn prove_block(
+        &self,
+        txn_inputs: Vec<GenerationInputs>,
+        parent_block_proof: Option<ProofWithPublicInputs<F, C, D>>,
+        timing: &mut TimingTree,
+    ) -> Result<RecursiveProofResponse<F, C, D>> {
+        self.prove_block_with_granularity(
+            txn_inputs,
+            ProofGranularity::PerTransaction,
+            parent_block_proof,
+            timing,
+        )
+    }
+
+    /// Proves a block from the inputs of each of its transactions, in order, and the proof of its
+    /// parent block, if any. The inputs of the transactions of each root proof are merged with
+    /// `merge_generation_inputs`.
+    ///
+    /// The root proofs are aggregated pairwise. A single root proof is aggregated with itself if
+    /// it doesn't change the state, like an empty transaction list, and with the proof of an
+    /// empty transaction list following it otherwise.
+    pub fn prove_block_with_granularity(
+        &self,
+        txn_inputs: Vec<GenerationInputs>,
+        granularity: ProofGranularity,
+        parent_block_proof: Option<ProofWithPublicInputs<F, C, D>>,
+        timing: &mut TimingTree,
+    ) -> Result<RecursiveProofResponse<F, C, D>> {
+        ensure!(!txn_inputs.is_empty(), "A block must have a transaction");
+        let batch_size = match granularity {
+            ProofGranularity::PerTransaction => 1,
+            ProofGranularity::Batches(batch_size) => {
+                ensure!(batch_size > 0, "Batches must hold at least one transaction");
+                batch_size
+            }
+            ProofGranularity::PerBlock => txn_inputs.len(),
+        };
+        let batch_inputs = txn_inputs
+            .into_iter()
+            .chunks(batch_size)
+            .into_iter()
+            .map(|batch| merge_generation_inputs(batch.collect()))
+            .collect::<Result<Vec<_>>>()?;
+        let last_inputs = batch_inputs.last().unwrap().clone();
+
+        let mut root_proofs = self.prove_transactions(batch_inputs, timing)?;
+        if let [root_proof] = &root_proofs[..] {
+            if !is_noop(&root_proof.public_values) {
+                let padding_inputs = noop_inputs(&last_inputs, &root_proof.public_values);
+                let padding_proofs = timed!(
+                    timing,
+                    "prove padding transition",
+                    self.prove_transactions(vec![padding_inputs], timing)?
+                );
+                root_proofs.extend(padding_proofs);
+            }
+        }
+        let agg_proof = timed!(
+            timing,
+            "aggregate root proofs",
+            self.aggregate(root_proofs)?
+        );
+
+        let mut results = self
+            .transport
+            .dispatch(vec![ProverTask::Block(BlockRequest {
+                parent_block_proof,
+                agg_root_proof: agg_proof.proof,
+                public_values: agg_proof.public_values,
+            })])?;
+        let block_proof = expect_recursive_proof(results.pop())?;
+        self.circuits.verify_block(&block_proof.proof)?;
+        Ok(block_proof)
+    }
+
+    /// Proves each transaction with a root proof.
+    pub fn prove_transactions(
+        &self,
+        txn_inputs: Vec<GenerationInputs>,
+        timing: &mut TimingTree,
+    ) -> Result<Vec<RecursiveProofResponse<F, C, D>>> {
+        ensure!(!txn_inputs.is_empty(), "A block must have a transaction");
+
+        let mut txn_traces = Vec::with_capacity(txn_inputs.len());
+        let mut txn_public_values = Vec::with_capacity(txn_inputs.len());
+        for inputs in txn_inputs {
+            let (traces, public_values, _outputs) = timed!(
+                timing,
+                "generate all traces",
+                generate_traces_with_limits(
+                    self.all_stark,
+                    inputs,
+                    self.config,
+                    &self.circuits.max_degree_bits(),
+                    &ProverOptions::default(),
+                    timing
+                )?
+            );
+            let degree_bits = core::array::from_fn(|table| log2_strict(traces[table][0].len()));
+            self.circuits.check_degree_bits(&degree_bits)?;
+            txn_traces.push(traces);
+            txn_public_values.push(public_values);
+        }
+
+        // Commit to all traces at once.
+        let tasks = txn_traces
+            .iter()
+            .flat_map(|traces| {
+                Table::all().into_iter().zip(traces).map(|(table, trace)| {
+                    ProverTask::TraceCommitment(TraceCommitmentRequest {
+                        table,
+                        trace: trace.clone(),
+                    })
+                })
+            })
+            .collect();
+        let mut results = self.transport.dispatch(tasks)?.into_iter();
+        let mut txn_commitments = Vec::with_capacity(txn_traces.len());
+        let mut txn_challenges = Vec::with_capacity(txn_traces.len());
+        for public_values in &txn_public_values {
+            let commitments = Table::all()
+                .into_iter()
+                .map(|table| match results.next() {
+                    Some(ProverTaskResult::TraceCommitment(response))
+                        if response.table == table =>
+                    {
+                        Ok(response)
+                    }
+                    result => Err(unexpected_result(result)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            txn_challenges.push(ctl_challenges::<F, C, D>(
+                &commitments,
+                public_values,
+                self.config,
+            )?);
+            txn_commitments.push(commitments);
+        }
+
+        // The tables of a transaction are proven one after the other, but the transactions are
+        // independent.
+        let mut challenger_states = txn_challenges
+            .iter()
+            .map(|challenges| challenges.challenger_state.clone())
+            .collect::<Vec<_>>();
+        let mut txn_stark_proofs = vec![Vec::with_capacity(NUM_TABLES); txn_traces.len()];
+        for table in Table::all() {
+            let tasks = txn_traces
+                .iter()
+                .zip(&txn_commitments)
+                .zip(&txn_challenges)
+                .zip(&challenger_states)
+                .map(|(((traces, commitments), challenges), challenger_state)| {
+                    ProverTask::TableProof(TableProofRequest {
+                        table,
+                        trace: traces[table as usize].clone(),
+                        trace_cap: commitments[table as usize].trace_cap.clone(),
+                        ctl_challenges: challenges.ctl_challenges.clone(),
+                        challenger_state: challenger_state.clone(),
+                    })
+                })
+                .collect();
+            let results = self.transport.dispatch(tasks)?;
+            ensure!(
+                results.len() == txn_traces.len(),
+                "Expected {} results, found {}",
+                txn_traces.len(),
+                results.len()
+            );
+            for ((result, stark_proofs), challenger_state) in results
+                .into_iter()
+                .zip(&mut txn_stark_proofs)
+                .zip(&mut challenger_states)
+            {
+                let TableProofResponse {
+                    table: proven_table,
+                    proof,
+                    challenger_state: next_challenger_state,
+                } = match result {
+                    ProverTaskResult::TableProof(response) => response,
+                    result => return Err(unexpected_result(Some(result))),
+                };
+                ensure!(
+                    proven_table == table,
+                    "Expected a proof of the {:?} table, found one of the {:?} table",
+                    table,
+                    proven_table
+                );
+                stark_proofs.push(proof);
+                *challenger_state = next_challenger_state;
+            }
+        }
+
+        // Check all STARK proofs before shrinking them.
+        let mut tasks = vec![];
+        for ((stark_proofs, challenges), public_values) in txn_stark_proofs
+            .into_iter()
+            .zip(txn_challenges)
+            .zip(&txn_public_values)
+        {
+            let CtlChallenges { ctl_challenges, .. } = challenges;
+            let all_proof = AllProof {
+                stark_proofs: stark_proofs.try_into().unwrap(),
+                ctl_challenges,
+                public_values: public_values.clone(),
+            };
+            verify_proof(self.all_stark, all_proof.clone(), self.config)?;
+            for (table, proof) in Table::all().into_iter().zip(all_proof.stark_proofs) {
+                tasks.push(ProverTask::TableShrink(TableShrinkRequest {
+                    table,
+                    proof,
+                    ctl_challenges: all_proof.ctl_challenges.clone(),
+                }));
+            }
+        }
+        let mut results = self.transport.dispatch(tasks)?.into_iter();
+
+        let mut tasks = vec![];
+        for public_values in txn_public_values {
+            let shrunk_proofs = Table::all()
+                .into_iter()
+                .map(|table| match results.next() {
+                    Some(ProverTaskResult::TableShrink(response)) if response.table == table => {
+                        Ok(response)
+                    }
+                    result => Err(unexpected_result(result)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            tasks.push(ProverTask::Root(RootRequest {
+                shrunk_proofs,
+                public_values,
+            }));
+        }
+        let root_proofs = self
+            .transport
+            .dispatch(tasks)?
+            .into_iter()
+            .map(|result| expect_recursive_proof(Some(result)))
+            .collect::<Result<Vec<_>>>()?;
+        for root_proof in &root_proofs {
+            self.circuits.verify_root(root_proof.proof.clone())?;
+        }
+        Ok(root_proofs)
+    }
+
+    /// Aggregates consecutive root or aggregation proofs pairwise, until a single aggregation
+    /// proof is left.
+    pub fn aggregate(
+        &self,
+        root_proofs: Vec<RecursiveProofResponse<F, C, D>>,
+    ) -> Result<RecursiveProofResponse<F, C, D>> {
+        ensure!(!root_proofs.is_empty(), "No proofs to aggregate");
+        let mut proofs = root_proofs
+            .into_iter()
+            .map(|proof| (false, proof))
+            .collect::<Vec<_>>();
+        if proofs.len() == 1 {
+            proofs.push(proofs[0].clone());
+        }
+
+        while proofs.len() > 1 {
+            let carried = (proofs.len() % 2 == 1).then(|| proofs.pop().unwrap());
+            let mut pairs = proofs.into_iter();
+            let mut tasks = vec![];
+            while let (Some((lhs_is_agg, lhs)), Some((rhs_is_agg, rhs))) =
+                (pairs.next(), pairs.next())
+            {
+                let public_values = merge_public_values(&lhs.public_values, &rhs.public_values);
+                tasks.push(ProverTask::Aggregation(AggregationRequest {
+                    lhs_is_agg,
+                    lhs_proof: lhs.proof,
+                    rhs_is_agg,
+                    rhs_proof: rhs.proof,
+                    public_values,
+                }));
+            }
+            proofs = self
+                .transport
+                .dispatch(tasks)?
+                .into_iter()
+                .map(|result| {
+                    let agg_proof = expect_recursive_proof(Some(result))?;
+                    self.circuits.verify_aggregation(&agg_proof.proof)?;
+                    Ok((true, agg_proof))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            proofs.extend(carried);
+        }
+
+        let (is_agg, proof) = proofs.pop().unwrap();
+        debug_assert!(is_agg);
+        Ok(proof)
+    }
+}
+
+/// Whether the state transition with `public_values` leaves the state unchanged, so that its proof
+/// can be aggregated with itself.
+fn is_noop(public_values: &PublicValues) -> bool {
+    let (before, after) = (
+        &public_values.trie_roots_before,
+        &public_values.trie_roots_after,
+    );
+    let extra_data = &public_values.extra_block_data;
+    before.state_root == after.state_root
+        && before.transactions_root == after.transactions_root
+        && before.receipts_root == after.receipts_root
+        && extra_data.txn_number_before == extra_data.txn_number_after
+        && extra_data.gas_used_before == extra_data.gas_used_after
+        && extra_data.block_bloom_before == extra_data.block_bloom_after
+        && public_values.extra_public_values.before() == public_values.extra_public_values.after()
+}
+
+/// The inputs of an empty transaction list following the state transition of `inputs`, whose
+/// proof has `public_values`. Such a transition doesn't access the state, so its tries are only
+/// given by their roots.
+fn noop_inputs(inputs: &GenerationInputs, public_values: &PublicValues) -> GenerationInputs {
+    let hashed_trie = |root: H256| {
+        if root == empty_trie_root() {
+            HashedPartialTrie::from(Node::Empty)
+        } else {
+            HashedPartialTrie::new(Node::Hash(root))
+        }
+    };
+    let roots = &public_values.trie_roots_after;
+    GenerationInputs {
+        txn_number_before: public_values.extra_block_data.txn_number_after,
+        gas_used_before: inputs.gas_used_after,
+        block_bloom_before: inputs.block_bloom_after,
+        gas_used_after: inputs.gas_used_after,
+        block_bloom_after: inputs.block_bloom_after,
+        tries: TrieInputs {
+            state_trie: hashed_trie(roots.state_root),
+            transactions_trie: hashed_trie(roots.transactions_root),
+            receipts_trie: hashed_trie(roots.receipts_root),
+            storage_tries: vec![],
+        },
+        trie_roots_after: roots.clone(),
+        genesis_state_trie_root: inputs.genesis_state_trie_root,
+        contract_code: inputs.contract_code.clone(),
+        block_metadata: inputs.block_metadata.clone(),
+        block_hashes: inputs.block_hashes.clone(),
+        extra_public_values: inputs.extra_public_values.unchanged_after(),
+        chain_constants: inputs.chain_constants,
+        ..GenerationInputs::default()
+    }
+}
+
+/// The public values of the aggregation of two consecutive state transitions.
+pub fn merge_public_values(lhs: &PublicValues, rhs: &PublicValues) -> PublicValues {
+    PublicValues {
+        trie_roots_before: lhs.trie_roots_before.clone(),
+        trie_roots_after: rhs.trie_roots_after.clone(),
+        extra_block_data: ExtraBlockData {
+            genesis_state_trie_root: lhs.extra_block_data.genesis_state_trie_root,
+            txn_number_before: lhs.extra_block_data.txn_number_before,
+            txn_number_after: rhs.extra_block_data.txn_number_after,
+            gas_used_before: lhs.extra_block_data.gas_used_before,
+            gas_used_after: rhs.extra_block_data.gas_used_after,
+            block_bloom_before: lhs.extra_block_data.block_bloom_before,
+            block_bloom_after: rhs.extra_block_data.block_bloom_after,
+        },
+        block_metadata: rhs.block_metadata.clone(),
+        block_hashes: rhs.block_hashes.clone(),
+        extra_public_values: lhs.extra_public_values.merge(&rhs.extra_public_values),
+        chain_constants: rhs.chain_constants,
+    }
+}
+
+fn expect_recursive_proof<F, C, const D: usize>(
+    result: Option<ProverTaskResult<F, C, D>>,
+) -> Result<RecursiveProofResponse<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    match result {
+        Some(ProverTaskResult::RecursiveProof(response)) => Ok(response),
+        result => Err(unexpected_result(result)),
+    }
+}
+
+fn unexpected_result<F, C, const D: usize>(
+    result: Option<ProverTaskResult<F, C, D>>,
+) -> anyhow::Error
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    match result {
+        None => anyhow!("The transport returned too few results"),
+        Some(ProverTaskResult::TraceCommitment(response)) => anyhow!(
+            "Unexpected trace commitment of the {:?} table",
+            response.table
+        ),
+        Some(ProverTaskResult::TableProof(response)) => {
+            anyhow!("Unexpected proof of the {:?} table", response.table)
+        }
+        Some(ProverTaskResult::TableShrink(response)) => {
+            anyhow!("Unexpected shrunk proof of the {:?} table", response.table)
+        }
+        Some(ProverTaskResult::RecursiveProof(_)) => anyhow!("Unexpected recursive proof"),
+    }
+}
@@ -0,0 +1,388 @@
+//! Constraint coverage analysis, to find constraints and CTL filters which a corpus of blocks never
+//! exercises.
+//!
+//! A constraint is exercised on a row if it constrains the trace there, i.e. if it isn't
+//! trivially satisfied because one of its selectors is zero. This is detected through the
+//! derivative of the constraint along a random direction: for a constraint `s * e` on a valid
+//! row, where `e = 0`, it is `s * e'`, which is non-zero with overwhelming probability if and only
+//! if `s` is non-zero.
+//!
+//! Only the constraints of each STARK are covered, not those of the lookup and CTL arguments.
+
+use std::fmt::{self, Display, Formatter};
+use std::panic::Location;
+
+use anyhow::{ensure, Result};
+use itertools::Itertools;
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::{Field, Sample};
+use plonky2::hash::hash_types::RichField;
+use plonky2::util::timing::TimingTree;
+use plonky2_maybe_rayon::*;
+
+use crate::all_stark::{AllStark, Table, NUM_TABLES};
+use crate::config::StarkConfig;
+use crate::constraint_consumer::ConstraintConsumer;
+use crate::cross_table_lookup::TableWithColumns;
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::generation::{generate_traces, GenerationInputs};
+use crate::stark::Stark;
+
+/// The number of rows processed by each parallel task.
+const ROWS_PER_CHUNK: usize = 1 << 10;
+
+/// How often a constraint was exercised.
+#[derive(Clone, Debug)]
+pub struct ConstraintCoverage {
+    /// Where the constraint is emitted. Constraints emitted in a loop share their location.
+    pub location: &'static Location<'static>,
+    /// The number of rows on which the constraint was exercised.
+    pub active_rows: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct TableCoverage {
+    pub table: Table,
+    /// The number of rows of the table's traces.
+    pub num_rows: usize,
+    /// The table's constraints, in the order they are emitted.
+    pub constraints: Vec<ConstraintCoverage>,
+}
+
+/// How often the filter of a table in a CTL selected a row.
+#[derive(Clone, Debug)]
+pub struct FilterCoverage {
+    /// The index of the CTL in `AllStark::cross_table_lookups`.
+    pub ctl_index: usize,
+    pub table: Table,
+    /// The index of the table among the CTL's looking tables, or `None` for its looked table.
+    pub looking_index: Option<usize>,
+    /// The number of rows the filter selected.
+    pub active_rows: usize,
+}
+
+/// Coverage accumulated over the traces of several blocks.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageReport {
+    pub num_blocks: usize,
+    pub tables: Vec<TableCoverage>,
+    pub filters: Vec<FilterCoverage>,
+}
+
+impl CoverageReport {
+    /// Generates the traces of a block and records the coverage of its constraints and CTL
+    /// filters.
+    pub fn record_block<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        all_stark: &AllStark<F, D>,
+        inputs: GenerationInputs,
+        config: &StarkConfig,
+        timing: &mut TimingTree,
+    ) -> Result<()> {
+        let (traces, _, _) = generate_traces(all_stark, inputs, config, timing)?;
+        self.record_traces(all_stark, &traces)
+    }
+
+    /// Records the coverage of the given traces, which must be valid.
+    pub fn record_traces<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        all_stark: &AllStark<F, D>,
+        traces: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+    ) -> Result<()> {
+        let [arithmetic, byte_packing, cpu, keccak, keccak_sponge, logic, memory] = traces;
+        let table_coverages = [
+            table_coverage(&all_stark.arithmetic_stark, Table::Arithmetic, arithmetic),
+            table_coverage(
+                &all_stark.byte_packing_stark,
+                Table::BytePacking,
+                byte_packing,
+            ),
+            table_coverage(&all_stark.cpu_stark, Table::Cpu, cpu),
+            table_coverage(&all_stark.keccak_stark, Table::Keccak, keccak),
+            table_coverage(
+                &all_stark.keccak_sponge_stark,
+                Table::KeccakSponge,
+                keccak_sponge,
+            ),
+            table_coverage(&all_stark.logic_stark, Table::Logic, logic),
+            table_coverage(&all_stark.memory_stark, Table::Memory, memory),
+        ];
+        let filter_coverages = all_stark
+            .cross_table_lookups
+            .iter()
+            .enumerate()
+            .flat_map(|(ctl_index, ctl)| {
+                let looking = ctl.looking_tables.iter().enumerate();
+                looking
+                    .map(|(i, twc)| (Some(i), twc))
+                    .chain([(None, &ctl.looked_table)])
+                    .map(move |(looking_index, twc)| {
+                        filter_coverage(ctl_index, looking_index, twc, traces)
+                    })
+            })
+            .collect_vec();
+
+        if self.num_blocks == 0 {
+            self.tables = table_coverages.into();
+            self.filters = filter_coverages;
+        } else {
+            for (total, coverage) in self.tables.iter_mut().zip(table_coverages) {
+                total.merge(coverage)?;
+            }
+            for (total, coverage) in self.filters.iter_mut().zip(filter_coverages) {
+                total.active_rows += coverage.active_rows;
+            }
+        }
+        self.num_blocks += 1;
+        Ok(())
+    }
+
+    /// Returns the constraints which were never exercised, along with their table.
+    pub fn dead_constraints(&self) -> impl Iterator<Item = (Table, &ConstraintCoverage)> {
+        self.tables.iter().flat_map(|table| {
+            table
+                .constraints
+                .iter()
+                .filter(|constraint| constraint.active_rows == 0)
+                .map(|constraint| (table.table, constraint))
+        })
+    }
+
+    /// Returns the CTL filters which never selected a row.
+    pub fn dead_filters(&self) -> impl Iterator<Item = &FilterCoverage> {
+        self.filters.iter().filter(|filter| filter.active_rows == 0)
+    }
+}
+
+impl TableCoverage {
+    fn merge(&mut self, other: Self) -> Result<()> {
+        ensure!(
+            self.constraints.len() == other.constraints.len(),
+            "{:?} emitted {} constraints, then {}",
+            self.table,
+            self.constraints.len(),
+            other.constraints.len()
+        );
+        self.num_rows += other.num_rows;
+        for (total, constraint) in self.constraints.iter_mut().zip(other.constraints) {
+            total.active_rows += constraint.active_rows;
+        }
+        Ok(())
+    }
+}
+
+impl Display for CoverageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Coverage over {} blocks", self.num_blocks)?;
+        for table in &self.tables {
+            let num_exercised = table
+                .constraints
+                .iter()
+                .filter(|constraint| constraint.active_rows > 0)
+                .count();
+            writeln!(
+                f,
+                "{:?}: {num_exercised}/{} constraints exercised over {} rows",
+                table.table,
+                table.constraints.len(),
+                table.num_rows
+            )?;
+        }
+        for (table, constraint) in self.dead_constraints() {
+            writeln!(
+                f,
+                "Never exercised: {table:?} constraint at {}",
+                constraint.location
+            )?;
+        }
+        for filter in self.dead_filters() {
+            let role = match filter.looking_index {
+                Some(i) => format!("looking table {i}"),
+                None => "looked table".to_string(),
+            };
+            writeln!(
+                f,
+                "Never fired: filter of {:?} ({role}) in CTL {}",
+                filter.table, filter.ctl_index
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts, for each constraint of `stark`, the rows of `trace` on which it is exercised.
+fn table_coverage<F, S, const D: usize>(
+    stark: &S,
+    table: Table,
+    trace: &[PolynomialValues<F>],
+) -> TableCoverage
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let num_rows = trace[0].len();
+    let degree_bits = num_rows.trailing_zeros() as usize;
+    let subgroup = F::two_adic_subgroup(degree_bits);
+    let last = subgroup[num_rows - 1];
+    let row = |i: usize| trace.iter().map(|column| column.values[i]).collect_vec();
+
+    let chunk_counts: Vec<Vec<(usize, &'static Location<'static>)>> = (0..num_rows)
+        .step_by(ROWS_PER_CHUNK)
+        .collect_vec()
+        .into_par_iter()
+        .map(|start| {
+            let mut counts: Vec<(usize, &'static Location<'static>)> = vec![];
+            for i in start..num_rows.min(start + ROWS_PER_CHUNK) {
+                let local_values = row(i);
+                let next_values = row((i + 1) % num_rows);
+                let local_direction = F::rand_vec(S::COLUMNS);
+                let next_direction = F::rand_vec(S::COLUMNS);
+
+                // Evaluate the constraints at `t = 0, 1, ..., degree` along the direction.
+                let evals = (0..=stark.constraint_degree())
+                    .map(|t| {
+                        let t = F::from_canonical_usize(t);
+                        let shift = |values: &[F], direction: &[F]| {
+                            values
+                                .iter()
+                                .zip(direction)
+                                .map(|(&v, &d)| v + t * d)
+                                .collect_vec()
+                        };
+                        let vars = S::EvaluationFrame::from_values(
+                            &shift(&local_values, &local_direction),
+                            &shift(&next_values, &next_direction),
+                        );
+                        let mut consumer = ConstraintConsumer::new_recording(
+                            subgroup[i] - last,
+                            F::from_bool(i == 0),
+                            F::from_bool(i == num_rows - 1),
+                        );
+                        stark.eval_packed_base(&vars, &mut consumer);
+                        consumer.recorded()
+                    })
+                    .collect_vec();
+
+                if counts.is_empty() {
+                    counts = evals[0]
+                        .iter()
+                        .map(|&(_, location)| (0, location))
+                        .collect();
+                }
+                for (c, (count, _)) in counts.iter_mut().enumerate() {
+                    let constraint_evals = evals.iter().map(|evals_t| evals_t[c].0).collect_vec();
+                    if derivative_at_zero(&constraint_evals) != F::ZERO {
+                        *count += 1;
+                    }
+                }
+            }
+            counts
+        })
+        .collect();
+
+    let mut constraints: Vec<ConstraintCoverage> = vec![];
+    for counts in chunk_counts {
+        if constraints.is_empty() {
+            constraints = counts
+                .iter()
+                .map(|&(_, location)| ConstraintCoverage {
+                    location,
+                    active_rows: 0,
+                })
+                .collect();
+        }
+        for (constraint, (count, _)) in constraints.iter_mut().zip(counts) {
+            constraint.active_rows += count;
+        }
+    }
+    TableCoverage {
+        table,
+        num_rows,
+        constraints,
+    }
+}
+
+/// Counts the rows selected by the filter of a table in a CTL.
+fn filter_coverage<F: Field>(
+    ctl_index: usize,
+    looking_index: Option<usize>,
+    twc: &TableWithColumns<F>,
+    traces: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+) -> FilterCoverage {
+    let trace = &traces[twc.table as usize];
+    let num_rows = trace[0].len();
+    let active_rows = match &twc.filter_column {
+        Some(filter) => (0..num_rows)
+            .filter(|&row| filter.eval_table(trace, row) != F::ZERO)
+            .count(),
+        None => num_rows,
+    };
+    FilterCoverage {
+        ctl_index,
+        table: twc.table,
+        looking_index,
+        active_rows,
+    }
+}
+
+/// Returns `f'(0)` for a polynomial `f` of degree less than `evals.len()`, given
+/// `f(0), f(1), ...`, as the sum of `(-1)^(k+1) Δ^k f(0) / k`.
+fn derivative_at_zero<F: Field>(evals: &[F]) -> F {
+    let mut differences = evals.to_vec();
+    let mut derivative = F::ZERO;
+    for k in 1..evals.len() {
+        // Turn the (k-1)-th forward differences into the k-th ones.
+        for i in 0..evals.len() - k {
+            differences[i] = differences[i + 1] - differences[i];
+        }
+        let term = differences[0] * F::from_canonical_usize(k).inverse();
+        if k % 2 == 1 {
+            derivative += term;
+        } else {
+            derivative -= term;
+        }
+    }
+    derivative
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::goldilocks_field::GoldilocksField;
+
+    use super::*;
+    use crate::byte_packing::byte_packing_stark::BytePackingStark;
+    use crate::byte_packing::NUM_BYTES;
+
+    type F = GoldilocksField;
+
+    #[test]
+    fn test_derivative_at_zero() {
+        // f(t) = 4 + 3t + 5t^2 + 2t^3
+        let f = |t: u64| F::from_canonical_u64(4 + 3 * t + 5 * t * t + 2 * t * t * t);
+        let evals = (0..4).map(f).collect_vec();
+        assert_eq!(derivative_at_zero(&evals), F::from_canonical_u64(3));
+        // Extra evaluations don't change the result.
+        let evals = (0..6).map(f).collect_vec();
+        assert_eq!(derivative_at_zero(&evals), F::from_canonical_u64(3));
+    }
+
+    #[test]
+    fn test_padding_coverage() {
+        type S = BytePackingStark<F, 2>;
+        let num_rows = 8;
+        let trace = vec![PolynomialValues::zero(num_rows); S::COLUMNS];
+        let coverage = table_coverage(&S::default(), Table::BytePacking, &trace);
+
+        assert_eq!(coverage.num_rows, num_rows);
+        assert!(coverage.constraints.iter().all(|constraint| constraint
+            .location
+            .file()
+            .ends_with("byte_packing_stark.rs")));
+        // The filter is boolean on every row, and must start by one.
+        assert_eq!(coverage.constraints[0].active_rows, num_rows);
+        assert_eq!(coverage.constraints[1].active_rows, 1);
+        // Constraints on active rows are dead in padding rows, such as the one on the next filter.
+        assert_eq!(coverage.constraints[NUM_BYTES + 6].active_rows, 0);
+    }
+}
@@ -0,0 +1,539 @@
+//! A static analysis of the kernel, run as part of its assembly.
+//!
+//! The analysis builds the control flow graph of the expanded kernel code, with an edge for each
+//! fall-through and each static jump, i.e. each `JUMP` or `JUMPI` directly preceded by a `PUSH`.
+//! Dynamic jumps, such as returns, end a path. It then computes, for each label, the worst-case
+//! growth of the stack along any path starting at that label, and reports:
+//! - labels which can't be reached, i.e. local labels which are neither referenced nor reached
+//!   from a global label,
+//! - code falling through into another routine, into data, or past the end of its file,
+//! - static jumps to offsets which aren't valid jump destinations.
+//!
+//! In kernel mode the CPU doesn't check that jumps land on a `JUMPDEST`, and kernel routines
+//! don't start with one, so a label is a valid destination as long as it precedes code. A literal
+//! destination must be a label or a `JUMPDEST`.
+
+use std::collections::{HashMap, HashSet};
+
+use ethereum_types::U256;
+use itertools::zip_eq;
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::kernel::assembler::{push_target_size, BYTES_PER_OFFSET};
+use crate::cpu::kernel::ast::{Item, PushTarget};
+
+/// The results of the analysis of a kernel.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct KernelAnalysis {
+    /// Every label of the kernel, in the order of their offsets.
+    pub labels: Vec<LabelAnalysis>,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LabelAnalysis {
+    pub name: String,
+    pub offset: usize,
+    pub global: bool,
+    /// The worst-case growth of the stack along any path starting at this label, until a dynamic
+    /// jump or a terminating instruction. `None` if a loop can grow the stack without bound.
+    pub max_stack_growth: Option<usize>,
+}
+
+/// A suspicious pattern found in the kernel.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// A local label which is never referenced, and can't be reached from a global label.
+    UnreachableLabel { label: String, offset: usize },
+    /// Code of the routine `from` which falls through into `into`, which is either a global label,
+    /// `"<data>"` or `"<end of file>"`, at `offset`.
+    FallThrough {
+        from: String,
+        into: String,
+        offset: usize,
+    },
+    /// A static jump, at `offset` in `routine`, to a `target` which isn't a valid destination.
+    InvalidJumpTarget {
+        routine: String,
+        offset: usize,
+        target: U256,
+    },
+}
+
+/// A maximal sequence of instructions which is only entered at its start, and only left at its end.
+#[derive(Default)]
+struct Block {
+    /// Whether the block contains any instruction.
+    has_code: bool,
+    /// The net change of the stack length over the block.
+    delta: isize,
+    /// The maximum growth of the stack length within the block, relative to its entry.
+    growth: isize,
+    successors: Vec<usize>,
+}
+
+impl Block {
+    fn apply(&mut self, (pops, pushes): (usize, usize)) {
+        self.has_code = true;
+        self.delta += pushes as isize - pops as isize;
+        self.growth = self.growth.max(self.delta);
+    }
+}
+
+/// Analyzes the expanded files of a kernel, given the offsets of their labels.
+pub(crate) fn analyze_kernel(
+    files: &[Vec<Item>],
+    local_labels: &[HashMap<String, usize>],
+    global_labels: &HashMap<String, usize>,
+) -> KernelAnalysis {
+    let mut blocks: Vec<Block> = vec![];
+    let mut block_at_offset = HashMap::new();
+    let mut new_block = |blocks: &mut Vec<Block>, offset: usize| {
+        let block = blocks.len();
+        blocks.push(Block::default());
+        block_at_offset.entry(offset).or_insert(block);
+        block
+    };
+
+    let mut labels = vec![];
+    let mut label_blocks = vec![];
+    let mut instruction_offsets = HashSet::new();
+    let mut jumpdest_offsets = HashSet::new();
+    let mut referenced_offsets = HashSet::new();
+    // Each static jump, with its block, routine, offset, target, and whether it is a label.
+    let mut static_jumps = vec![];
+    let mut findings = vec![];
+
+    let mut offset = 0;
+    for (body, locals) in zip_eq(files, local_labels) {
+        let resolve = |label: &String| {
+            *locals
+                .get(label)
+                .or_else(|| global_labels.get(label))
+                .unwrap_or_else(|| panic!("No such label: {label}"))
+        };
+        let mut routine = "<start of file>".to_string();
+        // The block being built, if the current item can be reached by falling through.
+        let mut current: Option<usize> = None;
+        let mut last_push: Option<&PushTarget> = None;
+
+        for item in body {
+            match item {
+                Item::GlobalLabelDeclaration(label) | Item::LocalLabelDeclaration(label) => {
+                    let global = matches!(item, Item::GlobalLabelDeclaration(_));
+                    let block = new_block(&mut blocks, offset);
+                    if let Some(prev) = current {
+                        blocks[prev].successors.push(block);
+                        if global && blocks[prev].has_code {
+                            findings.push(Finding::FallThrough {
+                                from: routine.clone(),
+                                into: label.clone(),
+                                offset,
+                            });
+                        }
+                    }
+                    if global {
+                        routine = label.clone();
+                    }
+                    labels.push(LabelAnalysis {
+                        name: label.clone(),
+                        offset,
+                        global,
+                        max_stack_growth: None,
+                    });
+                    label_blocks.push(block);
+                    current = Some(block);
+                    last_push = None;
+                }
+                Item::Push(target) => {
+                    let block = current.unwrap_or_else(|| new_block(&mut blocks, offset));
+                    blocks[block].apply((0, 1));
+                    if let PushTarget::Label(label) = target {
+                        referenced_offsets.insert(resolve(label));
+                    }
+                    instruction_offsets.insert(offset);
+                    current = Some(block);
+                    last_push = Some(target);
+                    offset += 1 + push_target_size(target) as usize;
+                }
+                Item::ProverInput(_) => {
+                    let block = current.unwrap_or_else(|| new_block(&mut blocks, offset));
+                    blocks[block].apply((0, 1));
+                    instruction_offsets.insert(offset);
+                    current = Some(block);
+                    last_push = None;
+                    offset += 1;
+                }
+                Item::StandardOp(op) => {
+                    let op = op.to_uppercase();
+                    let block = current.unwrap_or_else(|| new_block(&mut blocks, offset));
+                    blocks[block].apply(stack_effect(&op));
+                    instruction_offsets.insert(offset);
+                    if op == "JUMPDEST" {
+                        jumpdest_offsets.insert(offset);
+                    }
+                    if op == "JUMP" || op == "JUMPI" {
+                        if let Some(target) = last_push {
+                            let (target, is_label) = match target {
+                                PushTarget::Label(label) => (U256::from(resolve(label)), true),
+                                PushTarget::Literal(n) => (*n, false),
+                                _ => panic!("Item should have been expanded already: {item:?}"),
+                            };
+                            static_jumps.push((block, routine.clone(), offset, target, is_label));
+                        }
+                    }
+                    current = if op == "JUMPI" {
+                        // The fall-through of a conditional jump starts a new block.
+                        let next = new_block(&mut blocks, offset + 1);
+                        blocks[block].successors.push(next);
+                        Some(next)
+                    } else if is_terminal(&op) {
+                        None
+                    } else {
+                        Some(block)
+                    };
+                    last_push = None;
+                    offset += 1;
+                }
+                Item::Bytes(_) | Item::Jumptable(_) => {
+                    if current.is_some_and(|block| blocks[block].has_code) {
+                        findings.push(Finding::FallThrough {
+                            from: routine.clone(),
+                            into: "<data>".to_string(),
+                            offset,
+                        });
+                    }
+                    if let Item::Jumptable(table) = item {
+                        referenced_offsets.extend(table.iter().map(resolve));
+                        offset += table.len() * BYTES_PER_OFFSET as usize;
+                    } else if let Item::Bytes(bytes) = item {
+                        offset += bytes.len();
+                    }
+                    current = None;
+                    last_push = None;
+                }
+                Item::MacroDef(_, _, _)
+                | Item::MacroCall(_, _)
+                | Item::Repeat(_, _)
+                | Item::StackManipulation(_, _)
+                | Item::MacroLabelDeclaration(_) => {
+                    panic!("Item should have been expanded already: {item:?}");
+                }
+            }
+        }
+
+        if current.is_some_and(|block| blocks[block].has_code) {
+            findings.push(Finding::FallThrough {
+                from: routine,
+                into: "<end of file>".to_string(),
+                offset,
+            });
+        }
+    }
+
+    let label_offsets: HashSet<usize> = labels.iter().map(|label| label.offset).collect();
+    for (block, routine, offset, target, is_label) in static_jumps {
+        let destination = (target <= U256::from(usize::MAX))
+            .then(|| target.as_usize())
+            .filter(|dest| {
+                instruction_offsets.contains(dest)
+                    && (is_label || label_offsets.contains(dest) || jumpdest_offsets.contains(dest))
+            });
+        match destination.and_then(|dest| block_at_offset.get(&dest)) {
+            Some(&successor) => blocks[block].successors.push(successor),
+            None if destination.is_none() => findings.push(Finding::InvalidJumpTarget {
+                routine,
+                offset,
+                target,
+            }),
+            // A `JUMPDEST` within a block, which we treat like a dynamic jump.
+            None => {}
+        }
+    }
+
+    // Global labels are entry points, and referenced labels may be jumped to dynamically.
+    let mut reachable = vec![false; blocks.len()];
+    let mut queue = labels
+        .iter()
+        .zip(&label_blocks)
+        .filter(|(label, _)| label.global || referenced_offsets.contains(&label.offset))
+        .map(|(_, &block)| block)
+        .collect::<Vec<_>>();
+    while let Some(block) = queue.pop() {
+        if !reachable[block] {
+            reachable[block] = true;
+            queue.extend(&blocks[block].successors);
+        }
+    }
+
+    let max_stack_growth = max_stack_growth(&blocks);
+    for (label, &block) in labels.iter_mut().zip(&label_blocks) {
+        label.max_stack_growth = max_stack_growth[block].map(|growth| growth as usize);
+        if !reachable[block] {
+            findings.push(Finding::UnreachableLabel {
+                label: label.name.clone(),
+                offset: label.offset,
+            });
+        }
+    }
+
+    KernelAnalysis { labels, findings }
+}
+
+/// Computes the worst-case growth of the stack along any path starting at each block, or `None`
+/// if it is unbounded.
+fn max_stack_growth(blocks: &[Block]) -> Vec<Option<isize>> {
+    let successors = blocks
+        .iter()
+        .map(|block| block.successors.clone())
+        .collect::<Vec<_>>();
+    let mut growth = blocks
+        .iter()
+        .map(|block| Some(block.growth))
+        .collect::<Vec<_>>();
+
+    // The growth of a block depends on that of its successors, so we process components sinks
+    // first. Within a component, the longest paths are found after as many rounds as it has
+    // blocks, unless a loop grows the stack.
+    for component in strongly_connected_components(&successors) {
+        let mut changed = true;
+        for _ in 0..=component.len() {
+            if !changed {
+                break;
+            }
+            changed = false;
+            for &b in &component {
+                let block = &blocks[b];
+                let new_growth = block.successors.iter().try_fold(block.growth, |acc, &s| {
+                    growth[s].map(|succ_growth| acc.max(block.delta + succ_growth))
+                });
+                if new_growth != growth[b] {
+                    growth[b] = new_growth;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            for &b in &component {
+                growth[b] = None;
+            }
+        }
+    }
+    growth
+}
+
+/// Returns the strongly connected components of a graph, in reverse topological order, using
+/// Kosaraju's algorithm.
+fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = successors.len();
+
+    // Order the nodes by the time a depth-first search finishes visiting them.
+    let mut visited = HashSet::new();
+    let mut finished = Vec::with_capacity(n);
+    for root in 0..n {
+        if !visited.insert(root) {
+            continue;
+        }
+        let mut stack = vec![(root, 0)];
+        while let Some((node, i)) = stack.last_mut() {
+            if let Some(&succ) = successors[*node].get(*i) {
+                *i += 1;
+                if visited.insert(succ) {
+                    stack.push((succ, 0));
+                }
+            } else {
+                finished.push(*node);
+                stack.pop();
+            }
+        }
+    }
+
+    // Collect the components of the transposed graph, from the last finished node.
+    let mut predecessors = vec![vec![]; n];
+    for (node, succs) in successors.iter().enumerate() {
+        for &succ in succs {
+            predecessors[succ].push(node);
+        }
+    }
+    let mut assigned = vec![false; n];
+    let mut components = vec![];
+    for &root in finished.iter().rev() {
+        if assigned[root] {
+            continue;
+        }
+        assigned[root] = true;
+        let mut component = vec![root];
+        let mut i = 0;
+        while let Some(&node) = component.get(i) {
+            i += 1;
+            for &pred in &predecessors[node] {
+                if !assigned[pred] {
+                    assigned[pred] = true;
+                    component.push(pred);
+                }
+            }
+        }
+        components.push(component);
+    }
+    components.reverse();
+    components
+}
+
+/// Whether control never falls through a standard operation.
+fn is_terminal(op: &str) -> bool {
+    matches!(
+        op,
+        "JUMP"
+            | "STOP"
+            | "RETURN"
+            | "REVERT"
+            | "INVALID"
+            | "PANIC"
+            | "EXIT_KERNEL"
+            | "SELFDESTRUCT"
+    )
+}
+
+/// The number of items popped and pushed by a standard operation.
+fn stack_effect(op: &str) -> (usize, usize) {
+    if let Some(n) = op.strip_prefix("DUP") {
+        let n: usize = n
+            .parse()
+            .unwrap_or_else(|_| panic!("Unrecognized mnemonic {op}"));
+        return (n, n + 1);
+    }
+    if let Some(n) = op.strip_prefix("SWAP") {
+        let n: usize = n
+            .parse()
+            .unwrap_or_else(|_| panic!("Unrecognized mnemonic {op}"));
+        return (n + 1, n + 1);
+    }
+    if let Some(n) = op.strip_prefix("LOG") {
+        let n: usize = n
+            .parse()
+            .unwrap_or_else(|_| panic!("Unrecognized mnemonic {op}"));
+        return (n + 2, 0);
+    }
+    match op {
+        "STOP" | "JUMPDEST" | "INVALID" | "PANIC" => (0, 0),
+        "ADDRESS" | "ORIGIN" | "CALLER" | "CALLVALUE" | "CALLDATASIZE" | "CODESIZE"
+        | "GASPRICE" | "RETURNDATASIZE" | "COINBASE" | "TIMESTAMP" | "NUMBER" | "DIFFICULTY"
+        | "GASLIMIT" | "CHAINID" | "BASEFEE" | "PROVER_INPUT" | "GETPC" | "MSIZE" | "GAS"
+        | "GET_CONTEXT" => (0, 1),
+        "POP" | "JUMP" | "SET_CONTEXT" | "EXIT_KERNEL" | "SELFDESTRUCT" => (1, 0),
+        "ISZERO" | "NOT" | "BALANCE" | "CALLDATALOAD" | "EXTCODESIZE" | "EXTCODEHASH"
+        | "BLOCKHASH" | "MLOAD" | "SLOAD" => (1, 1),
+        "MSTORE" | "MSTORE8" | "SSTORE" | "JUMPI" | "RETURN" | "REVERT" => (2, 0),
+        "ADD" | "MUL" | "SUB" | "DIV" | "SDIV" | "MOD" | "SMOD" | "EXP" | "SIGNEXTEND"
+        | "ADDFP254" | "MULFP254" | "SUBFP254" | "LT" | "GT" | "SLT" | "SGT" | "EQ" | "AND"
+        | "OR" | "XOR" | "BYTE" | "SHL" | "SHR" | "SAR" | "KECCAK256" => (2, 1),
+        "CALLDATACOPY" | "CODECOPY" | "RETURNDATACOPY" => (3, 0),
+        "ADDMOD" | "MULMOD" | "SUBMOD" | "MLOAD_GENERAL" | "CREATE" => (3, 1),
+        "EXTCODECOPY" | "MSTORE_GENERAL" => (4, 0),
+        "KECCAK_GENERAL" | "MLOAD_32BYTES" | "CREATE2" => (4, 1),
+        "MSTORE_32BYTES" => (5, 0),
+        "DELEGATECALL" | "STATICCALL" => (6, 1),
+        "CALL" | "CALLCODE" => (7, 1),
+        _ => panic!("Unrecognized mnemonic {op}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::cpu::kernel::assembler::assemble;
+    use crate::cpu::kernel::parser::parse;
+
+    fn analyze(files: &[&str]) -> KernelAnalysis {
+        let parsed_files = files.iter().map(|f| parse(f)).collect_vec();
+        assemble(parsed_files, HashMap::new(), false).analysis
+    }
+
+    fn growth(analysis: &KernelAnalysis, name: &str) -> Option<usize> {
+        analysis
+            .labels
+            .iter()
+            .find(|label| label.name == name)
+            .unwrap()
+            .max_stack_growth
+    }
+
+    #[test]
+    fn stack_growth() {
+        let analysis = analyze(&["
+            global f:
+                PUSH 1 PUSH 2 DUP2
+                PUSH g JUMPI
+                POP JUMP
+            global g:
+                PUSH 3 PUSH 4 ADD
+                %stack (x, retdest) -> (retdest, x)
+                JUMP
+            global grow:
+                PUSH 1
+                PUSH grow JUMP
+        "]);
+        assert_eq!(analysis.findings, vec![]);
+        // `f` pushes 3 items and a jump target, then pops 2 to jump to `g`, which pushes 2 more.
+        assert_eq!(growth(&analysis, "f"), Some(4));
+        assert_eq!(growth(&analysis, "g"), Some(2));
+        assert_eq!(growth(&analysis, "grow"), None);
+    }
+
+    #[test]
+    fn findings() {
+        let analysis = analyze(&[
+            "
+            global f:
+                PUSH 1
+            global g:
+                PUSH ok JUMP
+            unused:
+                STOP
+            ok:
+                PUSH 3 JUMP
+            ",
+            "
+            global h:
+                ADD
+            ",
+        ]);
+        let offset = |name| {
+            analysis
+                .labels
+                .iter()
+                .find(|label| label.name == name)
+                .unwrap()
+                .offset
+        };
+        assert_eq!(
+            analysis.findings,
+            vec![
+                Finding::FallThrough {
+                    from: "f".to_string(),
+                    into: "g".to_string(),
+                    offset: offset("g"),
+                },
+                Finding::FallThrough {
+                    from: "h".to_string(),
+                    into: "<end of file>".to_string(),
+                    offset: offset("h") + 1,
+                },
+                Finding::InvalidJumpTarget {
+                    routine: "g".to_string(),
+                    offset: offset("ok") + 2,
+                    target: 3.into(),
+                },
+                Finding::UnreachableLabel {
+                    label: "unused".to_string(),
+                    offset: offset("unused"),
+                },
+            ]
+        );
+    }
+}
@@ -150,7 +150,7 @@ after_constructor:
     // stack: leftover_gas, success, address, kexit_info
     %returndatasize // Size of the code.
     // stack: code_size, leftover_gas, success, address, kexit_info
-    DUP1 %gt_const(@MAX_CODE_SIZE) %jumpi(create_code_too_large)
+    DUP1 %mload_chain_constant(@CHAIN_CONSTANT_MAX_CODE_SIZE) LT %jumpi(create_code_too_large)
     // stack: code_size, leftover_gas, success, address, kexit_info
     %mul_const(@GAS_CODEDEPOSIT)
     // stack: code_size_cost, leftover_gas, success, address, kexit_info
@@ -258,7 +258,7 @@ global set_codehash:
 // Pre stack: code_size, kexit_info
 // Post stack: kexit_info
 %macro check_initcode_size
-    DUP1 %gt_const(@MAX_INITCODE_SIZE) %jumpi(fault_exception)
+    DUP1 %mload_chain_constant(@CHAIN_CONSTANT_MAX_INITCODE_SIZE) LT %jumpi(fault_exception)
     // stack: code_size, kexit_info
     %num_bytes_to_num_words %mul_const(@INITCODE_WORD_COST)
     %charge_gas
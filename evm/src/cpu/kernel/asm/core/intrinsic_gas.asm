@@ -52,7 +52,7 @@ count_zeros_finish:
     // stack: is_creation, gas_creation, gas_txndata, retdest
     DUP1
     // stack: is_creation, is_creation, gas_creation, gas_txndata, retdest
-    %mload_txn_field(@TXN_FIELD_DATA_LEN) %gt_const(@MAX_INITCODE_SIZE)
+    %mload_txn_field(@TXN_FIELD_DATA_LEN) %mload_chain_constant(@CHAIN_CONSTANT_MAX_INITCODE_SIZE) LT
     // stack: initcode_size > max, is_creation, is_creation, gas_creation, gas_txndata, retdest
     MUL // Cheaper than AND
     %assert_zero
@@ -193,7 +193,7 @@ global process_contract_creation_txn_after_constructor:
     // stack: leftover_gas, new_ctx, address, retdest, success
     %returndatasize // Size of the code.
     // stack: code_size, leftover_gas, new_ctx, address, retdest, success
-    DUP1 %gt_const(@MAX_CODE_SIZE) %jumpi(contract_creation_fault_4)
+    DUP1 %mload_chain_constant(@CHAIN_CONSTANT_MAX_CODE_SIZE) LT %jumpi(contract_creation_fault_4)
     // stack: code_size, leftover_gas, new_ctx, address, retdest, success
     %mul_const(@GAS_CODEDEPOSIT) SWAP1
     // stack: leftover_gas, codedeposit_cost, new_ctx, address, retdest, success
@@ -346,6 +346,22 @@ process_message_txn_fail:
     SUB
     // stack: used_gas', leftover_gas'
 
+    // Pay the base fee to the chain's recipient, if it has one. Otherwise it is burnt.
+    %mload_chain_constant(@CHAIN_CONSTANT_BASE_FEE_RECIPIENT)
+    // stack: base_fee_recipient, used_gas', leftover_gas'
+    DUP1 ISZERO %jumpi(%%burn_base_fee)
+    DUP2 %mload_global_metadata(@GLOBAL_METADATA_BLOCK_BASE_FEE) MUL
+    // stack: used_gas_base_fee, base_fee_recipient, used_gas', leftover_gas'
+    SWAP1
+    // stack: base_fee_recipient, used_gas_base_fee, used_gas', leftover_gas'
+    %add_eth
+    %jump(%%after_base_fee)
+%%burn_base_fee:
+    // stack: base_fee_recipient, used_gas', leftover_gas'
+    POP
+%%after_base_fee:
+    // stack: used_gas', leftover_gas'
+
     // Pay the coinbase.
     %mload_txn_field(@TXN_FIELD_COMPUTED_PRIORITY_FEE_PER_GAS)
     MUL
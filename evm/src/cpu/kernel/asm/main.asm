@@ -61,6 +61,7 @@ global hash_final_tries:
     %mpt_hash_state_trie   %mload_global_metadata(@GLOBAL_METADATA_STATE_TRIE_DIGEST_AFTER)     %assert_eq
     %mpt_hash_txn_trie     %mload_global_metadata(@GLOBAL_METADATA_TXN_TRIE_DIGEST_AFTER)       %assert_eq
     %mpt_hash_receipt_trie %mload_global_metadata(@GLOBAL_METADATA_RECEIPT_TRIE_DIGEST_AFTER)   %assert_eq
+    %check_extra_public_values
     %jump(halt)
 
 initialize_block_bloom:
@@ -122,3 +123,32 @@ check_bloom_loop_end:
     %jump(check_metadata_block_bloom)
 %%after:
 %endmacro
+
+// Check that each extra public value slot ends with its expected value. Undeclared slots are
+// expected to stay 0.
+check_extra_public_values:
+    // stack: retdest
+    PUSH 0
+
+check_extra_public_values_loop:
+    // stack: i, retdest
+    DUP1 %eq_const(@NUM_EXTRA_PUBLIC_VALUES) %jumpi(check_extra_public_values_end)
+    DUP1 %mload_kernel(@SEGMENT_EXTRA_PUBLIC_VALUES)
+    // stack: value, i, retdest
+    DUP2 %add_const(@NUM_EXTRA_PUBLIC_VALUES) %mload_kernel(@SEGMENT_EXTRA_PUBLIC_VALUES)
+    // stack: expected_value, value, i, retdest
+    %assert_eq
+    // stack: i, retdest
+    %increment
+    %jump(check_extra_public_values_loop)
+
+check_extra_public_values_end:
+    // stack: i, retdest
+    POP
+    JUMP
+
+%macro check_extra_public_values
+    PUSH %%after
+    %jump(check_extra_public_values)
+%%after:
+%endmacro
@@ -16,6 +16,34 @@
     // stack: (empty)
 %endmacro
 
+// Load the current value of the given extra public value slot, see `ExtraPublicValues`.
+%macro mload_extra_public_value(slot)
+    // stack: (empty)
+    PUSH $slot
+    // stack: offset
+    %mload_kernel(@SEGMENT_EXTRA_PUBLIC_VALUES)
+    // stack: value
+%endmacro
+
+// Update the value of the given extra public value slot. This should only be done by the routine
+// which the chain designates for the slot.
+%macro mstore_extra_public_value(slot)
+    // stack: value
+    PUSH $slot
+    // stack: offset, value
+    %mstore_kernel(@SEGMENT_EXTRA_PUBLIC_VALUES)
+    // stack: (empty)
+%endmacro
+
+// Load the value of the given chain constant, see `ChainConstant`.
+%macro mload_chain_constant(constant)
+    // stack: (empty)
+    PUSH $constant
+    // stack: offset
+    %mload_kernel(@SEGMENT_CHAIN_CONSTANTS)
+    // stack: value
+%endmacro
+
 // Load the given context metadata field from memory.
 %macro mload_context_metadata(field)
     // stack: (empty)
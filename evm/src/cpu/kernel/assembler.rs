@@ -9,6 +9,7 @@ use log::debug;
 use serde::{Deserialize, Serialize};
 
 use super::ast::PushTarget;
+use crate::cpu::kernel::analysis::{analyze_kernel, KernelAnalysis};
 use crate::cpu::kernel::ast::Item::LocalLabelDeclaration;
 use crate::cpu::kernel::ast::{File, Item, StackReplacement};
 use crate::cpu::kernel::opcodes::{get_opcode, get_push_opcode};
@@ -35,6 +36,10 @@ pub struct Kernel {
 
     /// Map from `PROVER_INPUT` offsets to their corresponding `ProverInputFn`.
     pub(crate) prover_inputs: HashMap<usize, ProverInputFn>,
+
+    /// The static analysis of the kernel code, computed during assembly.
+    #[serde(default)]
+    pub(crate) analysis: KernelAnalysis,
 }
 
 impl Kernel {
@@ -42,6 +47,7 @@ impl Kernel {
         code: Vec<u8>,
         global_labels: HashMap<String, usize>,
         prover_inputs: HashMap<usize, ProverInputFn>,
+        analysis: KernelAnalysis,
     ) -> Self {
         let code_hash_bytes = keccak(&code).0;
         let code_hash_be = core::array::from_fn(|i| {
@@ -60,9 +66,16 @@ impl Kernel {
             global_labels,
             ordered_labels,
             prover_inputs,
+            analysis,
         }
     }
 
+    /// The static analysis of the kernel code: the worst-case stack growth of each label, and
+    /// suspicious patterns such as unreachable labels or fall-throughs into other routines.
+    pub fn analysis(&self) -> &KernelAnalysis {
+        &self.analysis
+    }
+
     pub fn to_file(&self, path: &str) {
         let kernel_serialized = serde_json::to_string(self).unwrap();
         fs::write(path, kernel_serialized).expect("Unable to write kernel to file");
@@ -142,6 +155,8 @@ pub(crate) fn assemble(
         expanded_files.push(file);
         debug!("Expanding file took {:?}", start.elapsed());
     }
+    let analysis = analyze_kernel(&expanded_files, &local_labels, &global_labels);
+    debug!("Kernel analysis findings: {:?}", analysis.findings);
     let mut code = vec![];
     for (file, locals) in izip!(expanded_files, local_labels) {
         let prev_len = code.len();
@@ -151,7 +166,7 @@ pub(crate) fn assemble(
     }
     assert_eq!(code.len(), offset, "Code length doesn't match offset.");
     debug!("Total kernel size: {} bytes", code.len());
-    Kernel::new(code, global_labels, prover_inputs)
+    Kernel::new(code, global_labels, prover_inputs, analysis)
 }
 
 fn find_macros(files: &[File]) -> HashMap<MacroSignature, Macro> {
@@ -399,7 +414,7 @@ fn assemble_file(
 }
 
 /// The size of a `PushTarget`, in bytes.
-fn push_target_size(target: &PushTarget) -> u8 {
+pub(crate) fn push_target_size(target: &PushTarget) -> u8 {
     match target {
         PushTarget::Literal(n) => u256_to_trimmed_be_bytes(n).len() as u8,
         PushTarget::Label(_) => BYTES_PER_OFFSET,
@@ -415,6 +430,7 @@ mod tests {
 
     use itertools::Itertools;
 
+    use crate::cpu::kernel::analysis::{Finding, LabelAnalysis};
     use crate::cpu::kernel::assembler::*;
     use crate::cpu::kernel::ast::*;
     use crate::cpu::kernel::parser::parse;
@@ -466,7 +482,41 @@ mod tests {
         expected_global_labels.insert("function_1".to_string(), 0);
         expected_global_labels.insert("function_2".to_string(), 3);
 
-        let expected_kernel = Kernel::new(expected_code, expected_global_labels, HashMap::new());
+        // The first file falls through past its end, and the second one loops over `mylabel`.
+        let expected_analysis = KernelAnalysis {
+            labels: vec![
+                LabelAnalysis {
+                    name: "function_1".to_string(),
+                    offset: 0,
+                    global: true,
+                    max_stack_growth: Some(0),
+                },
+                LabelAnalysis {
+                    name: "function_2".to_string(),
+                    offset: 3,
+                    global: true,
+                    max_stack_growth: Some(0),
+                },
+                LabelAnalysis {
+                    name: "mylabel".to_string(),
+                    offset: 5,
+                    global: false,
+                    max_stack_growth: Some(0),
+                },
+            ],
+            findings: vec![Finding::FallThrough {
+                from: "function_1".to_string(),
+                into: "<end of file>".to_string(),
+                offset: 3,
+            }],
+        };
+
+        let expected_kernel = Kernel::new(
+            expected_code,
+            expected_global_labels,
+            HashMap::new(),
+            expected_analysis,
+        );
 
         let program = vec![file_1, file_2];
         assert_eq!(assemble(program, HashMap::new(), false), expected_kernel);
@@ -9,6 +9,7 @@ use crate::cpu::kernel::constants::journal_entry::JournalEntry;
 use crate::cpu::kernel::constants::trie_type::PartialTrieType;
 use crate::cpu::kernel::constants::txn_fields::NormalizedTxnField;
 use crate::memory::segments::Segment;
+use crate::proof::{ChainConstant, NUM_EXTRA_PUBLIC_VALUES};
 
 pub(crate) mod context_metadata;
 mod exc_bitfields;
@@ -56,7 +57,14 @@ pub fn evm_constants() -> HashMap<String, U256> {
 
     c.insert(MAX_NONCE.0.into(), U256::from(MAX_NONCE.1));
     c.insert(CALL_STACK_LIMIT.0.into(), U256::from(CALL_STACK_LIMIT.1));
+    c.insert(
+        "NUM_EXTRA_PUBLIC_VALUES".into(),
+        U256::from(NUM_EXTRA_PUBLIC_VALUES),
+    );
 
+    for constant in ChainConstant::all() {
+        c.insert(constant.var_name().into(), (constant as u32).into());
+    }
     for segment in Segment::all() {
         c.insert(segment.var_name().into(), (segment as u32).into());
     }
@@ -262,11 +270,8 @@ const PRECOMPILES_GAS: [(&str, u16); 13] = [
 
 const SNARKV_POINTERS: [(&str, u64); 2] = [("SNARKV_INP", 112), ("SNARKV_OUT", 100)];
 
-const CODE_SIZE_LIMIT: [(&str, u64); 3] = [
-    ("MAX_CODE_SIZE", 0x6000),
-    ("MAX_INITCODE_SIZE", 0xc000),
-    ("INITCODE_WORD_COST", 2),
-];
+// The maximum code and init code sizes are chain constants, see `ChainConstant`.
+const CODE_SIZE_LIMIT: [(&str, u64); 1] = [("INITCODE_WORD_COST", 2)];
 
 const MAX_NONCE: (&str, u64) = ("MAX_NONCE", 0xffffffffffffffff);
 const CALL_STACK_LIMIT: (&str, u64) = ("CALL_STACK_LIMIT", 1024);
@@ -18,6 +18,7 @@ use crate::generation::prover_input::ProverInputFn;
 use crate::generation::state::GenerationState;
 use crate::generation::GenerationInputs;
 use crate::memory::segments::Segment;
+use crate::proof::ChainConstants;
 use crate::witness::memory::{MemoryAddress, MemoryContextState, MemorySegmentState, MemoryState};
 use crate::witness::util::stack_peek;
 
@@ -133,6 +134,11 @@ impl<'a> Interpreter<'a> {
             *result.stack_segment_mut() = initial_stack;
             result.stack_segment_mut().truncate(initial_stack_len - 1);
         }
+        // Like the prover, start with the Ethereum values of the chain constants read by the kernel.
+        result.set_memory_segment(
+            Segment::ChainConstants,
+            ChainConstants::default().values().to_vec(),
+        );
 
         result
     }
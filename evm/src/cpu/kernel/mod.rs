@@ -1,4 +1,5 @@
 pub mod aggregator;
+pub mod analysis;
 pub mod assembler;
 mod ast;
 pub(crate) mod constants;
@@ -29,7 +29,7 @@
 
 use std::borrow::Borrow;
 use std::fmt::Debug;
-use std::iter::repeat;
+use std::iter::{once, repeat};
 
 use anyhow::{ensure, Result};
 use itertools::Itertools;
@@ -47,6 +47,8 @@ use plonky2::plonk::plonk_common::{
     reduce_with_powers, reduce_with_powers_circuit, reduce_with_powers_ext_circuit,
 };
 use plonky2::util::serialization::{Buffer, IoResult, Read, Write};
+use plonky2_maybe_rayon::*;
+use serde::{Deserialize, Serialize};
 
 use crate::all_stark::{Table, NUM_TABLES};
 use crate::config::StarkConfig;
@@ -305,8 +307,8 @@ impl<F: Field> Column<F> {
 /// `columns` represents linear combinations of the columns of `Table`.
 #[derive(Clone, Debug)]
 pub struct TableWithColumns<F: Field> {
-    table: Table,
-    columns: Vec<Column<F>>,
+    pub(crate) table: Table,
+    pub(crate) columns: Vec<Column<F>>,
     pub(crate) filter_column: Option<Column<F>>,
 }
 
@@ -323,7 +325,7 @@ impl<F: Field> TableWithColumns<F> {
 
 /// Cross-table lookup data consisting in the lookup table (`looked_table`) and all the tables that look into `looked_table` (`looking_tables`).
 /// Each `looking_table` corresponds to a STARK's table whose rows have been filtered out and whose columns have been through a linear combination (see `eval_table`). The concatenation of those smaller tables should result in the `looked_table`.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct CrossTableLookup<F: Field> {
     /// Column linear combinations for all tables that are looking into the current table.
     pub(crate) looking_tables: Vec<TableWithColumns<F>>,
@@ -400,7 +402,7 @@ impl<F: Field> CtlData<F> {
 }
 
 /// Randomness for a single instance of a permutation check protocol.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub(crate) struct GrandProductChallenge<T: Copy + Eq + PartialEq + Debug> {
     /// Randomness used to combine multiple columns into one.
     pub(crate) beta: T,
@@ -446,8 +448,8 @@ impl GrandProductChallenge<Target> {
 }
 
 /// Like `PermutationChallenge`, but with `num_challenges` copies to boost soundness.
-#[derive(Clone, Eq, PartialEq, Debug)]
-pub(crate) struct GrandProductChallengeSet<T: Copy + Eq + PartialEq + Debug> {
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct GrandProductChallengeSet<T: Copy + Eq + PartialEq + Debug> {
     pub(crate) challenges: Vec<GrandProductChallenge<T>>,
 }
 
@@ -531,49 +533,83 @@ pub(crate) fn cross_table_lookup_data<F: RichField, const D: usize>(
     cross_table_lookups: &[CrossTableLookup<F>],
     ctl_challenges: &GrandProductChallengeSet<F>,
 ) -> [CtlData<F>; NUM_TABLES] {
-    let mut ctl_data_per_table = [0; NUM_TABLES].map(|_| CtlData::default());
+    // The `Z` polynomial of each (CTL, challenge, table) triple, in the order in which they are
+    // added to the `CtlData`s, which is the order in which the verifier expects them.
+    let mut zs_tables = vec![];
     for CrossTableLookup {
         looking_tables,
         looked_table,
     } in cross_table_lookups
     {
-        log::debug!("Processing CTL for {:?}", looked_table.table);
         for &challenge in &ctl_challenges.challenges {
-            let zs_looking = looking_tables.iter().map(|table| {
-                partial_products(
-                    &trace_poly_values[table.table as usize],
-                    &table.columns,
-                    &table.filter_column,
-                    challenge,
-                )
-            });
-            let z_looked = partial_products(
-                &trace_poly_values[looked_table.table as usize],
-                &looked_table.columns,
-                &looked_table.filter_column,
+            for table in looking_tables.iter().chain(once(looked_table)) {
+                zs_tables.push((table, challenge));
+            }
+        }
+    }
+
+    // The partial products are independent, so they are computed in parallel.
+    let zs = zs_tables
+        .par_iter()
+        .map(|&(table, challenge)| {
+            partial_products(
+                &trace_poly_values[table.table as usize],
+                &table.columns,
+                &table.filter_column,
                 challenge,
-            );
-            for (table, z) in looking_tables.iter().zip(zs_looking) {
-                ctl_data_per_table[table.table as usize]
-                    .zs_columns
-                    .push(CtlZData {
-                        z,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut ctl_data_per_table = [0; NUM_TABLES].map(|_| CtlData::default());
+    for ((table, challenge), z) in zs_tables.into_iter().zip(zs) {
+        ctl_data_per_table[table.table as usize]
+            .zs_columns
+            .push(CtlZData {
+                z,
+                challenge,
+                columns: table.columns.clone(),
+                filter_column: table.filter_column.clone(),
+            });
+    }
+    ctl_data_per_table
+}
+
+/// Like [`cross_table_lookup_data`], but only computes the `CtlData` of `table`, whose trace is
+/// `trace_poly_values`. The other traces aren't needed.
+pub(crate) fn table_cross_table_lookup_data<F: RichField>(
+    table: Table,
+    trace_poly_values: &[PolynomialValues<F>],
+    cross_table_lookups: &[CrossTableLookup<F>],
+    ctl_challenges: &GrandProductChallengeSet<F>,
+) -> CtlData<F> {
+    let mut ctl_data = CtlData::default();
+    for CrossTableLookup {
+        looking_tables,
+        looked_table,
+    } in cross_table_lookups
+    {
+        for &challenge in &ctl_challenges.challenges {
+            for table_with_columns in looking_tables
+                .iter()
+                .chain(once(looked_table))
+                .filter(|t| t.table == table)
+            {
+                ctl_data.zs_columns.push(CtlZData {
+                    z: partial_products(
+                        trace_poly_values,
+                        &table_with_columns.columns,
+                        &table_with_columns.filter_column,
                         challenge,
-                        columns: table.columns.clone(),
-                        filter_column: table.filter_column.clone(),
-                    });
-            }
-            ctl_data_per_table[looked_table.table as usize]
-                .zs_columns
-                .push(CtlZData {
-                    z: z_looked,
+                    ),
                     challenge,
-                    columns: looked_table.columns.clone(),
-                    filter_column: looked_table.filter_column.clone(),
+                    columns: table_with_columns.columns.clone(),
+                    filter_column: table_with_columns.filter_column.clone(),
                 });
+            }
         }
     }
-    ctl_data_per_table
+    ctl_data
 }
 
 /// Computes the cross-table lookup partial products for one table and given column linear combinations.
@@ -590,26 +626,37 @@ fn partial_products<F: Field>(
     filter_column: &Option<Column<F>>,
     challenge: GrandProductChallenge<F>,
 ) -> PolynomialValues<F> {
-    let mut partial_prod = F::ONE;
     let degree = trace[0].len();
-    let mut res = Vec::with_capacity(degree);
-    for i in (0..degree).rev() {
-        let filter = if let Some(column) = filter_column {
-            column.eval_table(trace, i)
-        } else {
-            F::ONE
-        };
-        if filter.is_one() {
-            let evals = columns
-                .iter()
-                .map(|c| c.eval_table(trace, i))
-                .collect::<Vec<_>>();
-            partial_prod *= challenge.combine(evals.iter());
-        } else {
-            assert_eq!(filter, F::ZERO, "Non-binary filter?")
-        };
-        res.push(partial_prod);
-    }
+    let factors = (0..degree)
+        .into_par_iter()
+        .map(|i| {
+            let filter = if let Some(column) = filter_column {
+                column.eval_table(trace, i)
+            } else {
+                F::ONE
+            };
+            if filter.is_one() {
+                let evals = columns
+                    .iter()
+                    .map(|c| c.eval_table(trace, i))
+                    .collect::<Vec<_>>();
+                challenge.combine(evals.iter())
+            } else {
+                assert_eq!(filter, F::ZERO, "Non-binary filter?");
+                F::ONE
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut partial_prod = F::ONE;
+    let mut res = factors
+        .into_iter()
+        .rev()
+        .map(|factor| {
+            partial_prod *= factor;
+            partial_prod
+        })
+        .collect::<Vec<_>>();
     res.reverse();
     res.into()
 }
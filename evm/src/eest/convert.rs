@@ -0,0 +1,331 @@
+//! Conversion of fixtures into `GenerationInputs`.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use eth_trie_utils::nibbles::Nibbles;
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::{Address, H256, U256};
+use keccak_hash::keccak;
+use rlp::Rlp;
+
+use super::fixtures::{Alloc, BlockFixture, ChainConfig, Fixture, StateFixture};
+use crate::generation::mpt::AccountRlp;
+use crate::generation::{GenerationInputs, TrieInputs};
+use crate::proof::{BlockHashes, BlockMetadata, BlockMetadataExtensions, TrieRoots};
+use crate::util::bloom_words;
+use crate::Node;
+
+/// A test case, i.e. a single block to execute.
+#[derive(Clone, Debug)]
+pub(crate) struct TestCase {
+    pub(crate) name: String,
+    pub(crate) inputs: GenerationInputs,
+    pub(crate) expected: Expected,
+}
+
+/// What is known of the block's execution ahead of time.
+#[derive(Clone, Debug)]
+pub(crate) enum Expected {
+    /// `inputs` holds all the expected values, as given by a block test.
+    Block,
+    /// Only the state root and the hash of the logs are known, as in a state test. The other
+    /// expected values in `inputs` are placeholders, to be filled in from the kernel's execution.
+    State { state_root: H256, logs_hash: H256 },
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Case {
+    Run(Box<TestCase>),
+    Skip { name: String, reason: String },
+}
+
+/// Converts a fixture into the test cases it holds, skipping those for forks other than `forks`
+/// and those the kernel can't run.
+pub(crate) fn test_cases(name: &str, fixture: &Fixture, forks: &[String]) -> Result<Vec<Case>> {
+    match fixture {
+        Fixture::State(fixture) => state_test_cases(name, fixture, forks),
+        Fixture::Block(fixture) => block_test_cases(name, fixture, forks),
+    }
+}
+
+fn state_test_cases(name: &str, fixture: &StateFixture, forks: &[String]) -> Result<Vec<Case>> {
+    let pre_state = PreState::new(&fixture.pre);
+    let env = &fixture.env;
+    let block_metadata = BlockMetadata {
+        block_beneficiary: env.current_coinbase,
+        block_timestamp: env.current_timestamp,
+        block_number: env.current_number,
+        block_difficulty: env.current_difficulty,
+        block_random: env.current_random.unwrap_or_default(),
+        block_gaslimit: env.current_gas_limit,
+        block_chain_id: chain_id(&fixture.config),
+        block_base_fee: env.current_base_fee.unwrap_or_default(),
+        // Placeholders, see `Expected::State`.
+        block_gas_used: U256::zero(),
+        block_bloom: [U256::zero(); 8],
+        extensions: BlockMetadataExtensions::default(),
+    };
+
+    let mut cases = vec![];
+    for (fork, posts) in &fixture.post {
+        for post in posts {
+            let indexes = post.indexes;
+            let name = format!(
+                "{name}[{fork}-d{}g{}v{}]",
+                indexes.data, indexes.gas, indexes.value
+            );
+            let skip = |reason: String| Case::Skip {
+                name: name.clone(),
+                reason,
+            };
+            if !forks.contains(fork) {
+                cases.push(skip(format!("Unsupported fork {fork}")));
+                continue;
+            }
+            if let Some(exception) = &post.expect_exception {
+                cases.push(skip(format!("Expects the invalid transaction {exception}")));
+                continue;
+            }
+            let Some(txn) = &post.txbytes else {
+                cases.push(skip("Lacks the signed transaction".to_string()));
+                continue;
+            };
+
+            let inputs = GenerationInputs {
+                signed_txns: vec![txn.0.clone()],
+                tries: pre_state.tries.clone(),
+                trie_roots_after: TrieRoots {
+                    state_root: post.hash,
+                    ..TrieRoots::default()
+                },
+                genesis_state_trie_root: pre_state.tries.state_trie.hash(),
+                contract_code: pre_state.contract_code.clone(),
+                block_metadata: block_metadata.clone(),
+                block_hashes: state_test_block_hashes(env.current_number),
+                addresses: pre_state.addresses.clone(),
+                ..GenerationInputs::default()
+            };
+            cases.push(Case::Run(Box::new(TestCase {
+                name,
+                inputs,
+                expected: Expected::State {
+                    state_root: post.hash,
+                    logs_hash: post.logs,
+                },
+            })));
+        }
+    }
+    Ok(cases)
+}
+
+fn block_test_cases(name: &str, fixture: &BlockFixture, forks: &[String]) -> Result<Vec<Case>> {
+    let skip = |reason: String| {
+        Ok(vec![Case::Skip {
+            name: name.to_string(),
+            reason,
+        }])
+    };
+    if !forks.contains(&fixture.network) {
+        return skip(format!("Unsupported fork {}", fixture.network));
+    }
+    // Chaining blocks would require the post-state of each block, including the code of any
+    // contracts it creates, which `GenerationOutputs` doesn't provide yet.
+    let [block] = fixture.blocks.as_slice() else {
+        return skip(format!(
+            "Has {} blocks, while only single-block tests are supported",
+            fixture.blocks.len()
+        ));
+    };
+    if let Some(exception) = &block.expect_exception {
+        return skip(format!("Expects the invalid block {exception}"));
+    }
+    let header = block
+        .block_header
+        .as_ref()
+        .ok_or_else(|| anyhow!("The block of {name} has no header"))?;
+
+    let pre_state = PreState::new(&fixture.pre);
+    let block_bloom = bloom_words(&header.bloom.0)?;
+    let block_metadata = BlockMetadata {
+        block_beneficiary: header.coinbase,
+        block_timestamp: header.timestamp,
+        block_number: header.number,
+        block_difficulty: header.difficulty,
+        block_random: header.mix_hash,
+        block_gaslimit: header.gas_limit,
+        block_chain_id: chain_id(&fixture.config),
+        block_base_fee: header.base_fee_per_gas.unwrap_or_default(),
+        block_gas_used: header.gas_used,
+        block_bloom,
+        extensions: BlockMetadataExtensions::default(),
+    };
+    let mut prev_hashes = vec![H256::zero(); 256];
+    prev_hashes[255] = fixture.genesis_block_header.hash;
+    let gwei = U256::exp10(9);
+
+    let inputs = GenerationInputs {
+        gas_used_after: header.gas_used,
+        block_bloom_after: block_bloom,
+        signed_txns: block_transactions(&block.rlp.0)?,
+        withdrawals: block
+            .withdrawals
+            .iter()
+            .map(|withdrawal| (withdrawal.address, withdrawal.amount * gwei))
+            .collect(),
+        tries: pre_state.tries,
+        trie_roots_after: TrieRoots {
+            state_root: header.state_root,
+            transactions_root: header.transactions_trie,
+            receipts_root: header.receipt_trie,
+        },
+        genesis_state_trie_root: fixture.genesis_block_header.state_root,
+        contract_code: pre_state.contract_code,
+        block_metadata,
+        block_hashes: BlockHashes {
+            prev_hashes,
+            cur_hash: header.hash,
+        },
+        addresses: pre_state.addresses,
+        ..GenerationInputs::default()
+    };
+    Ok(vec![Case::Run(Box::new(TestCase {
+        name: name.to_string(),
+        inputs,
+        expected: Expected::Block,
+    }))])
+}
+
+/// The tries and code of a fixture's pre-state.
+struct PreState {
+    tries: TrieInputs,
+    contract_code: HashMap<H256, Vec<u8>>,
+    addresses: Vec<Address>,
+}
+
+impl PreState {
+    fn new(alloc: &Alloc) -> Self {
+        let mut state_trie = HashedPartialTrie::from(Node::Empty);
+        let mut storage_tries = vec![];
+        let mut contract_code = HashMap::new();
+        contract_code.insert(keccak([]), vec![]);
+
+        for (address, account) in alloc {
+            let mut storage_trie = HashedPartialTrie::from(Node::Empty);
+            for (slot, value) in &account.storage {
+                if value.is_zero() {
+                    continue;
+                }
+                let mut slot_bytes = [0; 32];
+                slot.to_big_endian(&mut slot_bytes);
+                storage_trie.insert(nibbles(keccak(slot_bytes)), rlp::encode(value).to_vec());
+            }
+
+            let code_hash = keccak(&account.code.0);
+            contract_code.insert(code_hash, account.code.0.clone());
+            let account_rlp = AccountRlp {
+                nonce: account.nonce,
+                balance: account.balance,
+                storage_root: storage_trie.hash(),
+                code_hash,
+            };
+            let state_key = keccak(address);
+            state_trie.insert(nibbles(state_key), rlp::encode(&account_rlp).to_vec());
+            storage_tries.push((state_key, storage_trie));
+        }
+
+        Self {
+            tries: TrieInputs {
+                state_trie,
+                transactions_trie: Node::Empty.into(),
+                receipts_trie: Node::Empty.into(),
+                storage_tries,
+            },
+            contract_code,
+            addresses: alloc.keys().copied().collect(),
+        }
+    }
+}
+
+fn nibbles(key: H256) -> Nibbles {
+    Nibbles::from_bytes_be(key.as_bytes()).unwrap()
+}
+
+fn chain_id(config: &Option<ChainConfig>) -> U256 {
+    config.as_ref().map_or(U256::one(), |config| config.chainid)
+}
+
+/// Extracts the signed transactions of an RLP-encoded block, i.e. its second field. Legacy
+/// transactions are RLP lists, while typed ones are byte strings wrapping their envelope.
+fn block_transactions(block_rlp: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let transactions = Rlp::new(block_rlp)
+        .at(1)
+        .map_err(|err| anyhow!("Invalid block RLP: {err}"))?;
+    transactions
+        .iter()
+        .map(|txn| {
+            if txn.is_list() {
+                Ok(txn.as_raw().to_vec())
+            } else {
+                txn.data()
+                    .map(<[u8]>::to_vec)
+                    .map_err(|err| anyhow!("Invalid transaction RLP: {err}"))
+            }
+        })
+        .collect()
+}
+
+/// The hashes of the blocks preceding `block_number`. As in geth's state tests, the hash of block
+/// `n` is the Keccak hash of `n` written in decimal.
+fn state_test_block_hashes(block_number: U256) -> BlockHashes {
+    let prev_hashes = (0..256)
+        .map(|i| {
+            // `prev_hashes[i]` is the hash of block `block_number - 256 + i`.
+            let number = block_number + i;
+            if number < 256.into() {
+                H256::zero()
+            } else {
+                keccak((number - 256).to_string())
+            }
+        })
+        .collect();
+    BlockHashes {
+        prev_hashes,
+        cur_hash: H256::zero(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+    use rlp::RlpStream;
+
+    use super::*;
+
+    #[test]
+    fn test_block_transactions() -> Result<()> {
+        let legacy_txn = rlp::encode_list::<u8, u8>(&[1, 2, 3]).to_vec();
+        let typed_txn = hex!("02c3010203").to_vec();
+
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&"header");
+        stream.begin_list(2);
+        stream.append_raw(&legacy_txn, 1);
+        stream.append(&typed_txn);
+        let block = stream.out();
+
+        assert_eq!(block_transactions(&block)?, vec![legacy_txn, typed_txn]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_test_block_hashes() {
+        let hashes = state_test_block_hashes(300.into());
+        assert_eq!(hashes.prev_hashes[255], keccak("299"));
+        assert_eq!(hashes.prev_hashes[0], keccak("44"));
+
+        let hashes = state_test_block_hashes(1.into());
+        assert_eq!(hashes.prev_hashes[255], keccak("0"));
+        assert_eq!(hashes.prev_hashes[254], H256::zero());
+    }
+}
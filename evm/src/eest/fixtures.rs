@@ -0,0 +1,202 @@
+//! The subset of the execution-spec-tests fixture formats needed to build `GenerationInputs`.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use ethereum_types::{Address, H256, U256};
+use serde::Deserialize;
+
+use crate::util::HexBytes;
+
+/// The accounts of a state, by address.
+pub(crate) type Alloc = BTreeMap<Address, AccountState>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct AccountState {
+    pub(crate) nonce: U256,
+    pub(crate) balance: U256,
+    pub(crate) code: HexBytes,
+    pub(crate) storage: BTreeMap<U256, U256>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct ChainConfig {
+    pub(crate) chainid: U256,
+}
+
+/// A state test: a single transaction, run against `pre` for each fork in `post`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StateFixture {
+    pub(crate) env: Environment,
+    pub(crate) pre: Alloc,
+    /// The expected outcomes of the transaction's variants, by fork.
+    pub(crate) post: BTreeMap<String, Vec<PostState>>,
+    pub(crate) config: Option<ChainConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Environment {
+    pub(crate) current_coinbase: Address,
+    pub(crate) current_gas_limit: U256,
+    pub(crate) current_number: U256,
+    pub(crate) current_timestamp: U256,
+    #[serde(default)]
+    pub(crate) current_difficulty: U256,
+    pub(crate) current_random: Option<H256>,
+    pub(crate) current_base_fee: Option<U256>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PostState {
+    /// The expected state root.
+    pub(crate) hash: H256,
+    /// The hash of the RLP list of the transaction's logs.
+    pub(crate) logs: H256,
+    /// The signed transaction, which older fixtures don't include.
+    pub(crate) txbytes: Option<HexBytes>,
+    pub(crate) indexes: Indexes,
+    pub(crate) expect_exception: Option<String>,
+}
+
+/// The indices of the transaction's data, gas limit and value among the test's variants.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub(crate) struct Indexes {
+    pub(crate) data: usize,
+    pub(crate) gas: usize,
+    pub(crate) value: usize,
+}
+
+/// A block test: a chain of blocks built on a genesis block whose state is `pre`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BlockFixture {
+    pub(crate) network: String,
+    pub(crate) genesis_block_header: BlockHeader,
+    pub(crate) pre: Alloc,
+    pub(crate) blocks: Vec<Block>,
+    pub(crate) config: Option<ChainConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Block {
+    pub(crate) rlp: HexBytes,
+    /// The decoded header, which invalid blocks may lack.
+    pub(crate) block_header: Option<BlockHeader>,
+    #[serde(default)]
+    pub(crate) withdrawals: Vec<Withdrawal>,
+    pub(crate) expect_exception: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BlockHeader {
+    pub(crate) coinbase: Address,
+    pub(crate) state_root: H256,
+    pub(crate) transactions_trie: H256,
+    pub(crate) receipt_trie: H256,
+    pub(crate) bloom: HexBytes,
+    pub(crate) difficulty: U256,
+    pub(crate) number: U256,
+    pub(crate) gas_limit: U256,
+    pub(crate) gas_used: U256,
+    pub(crate) timestamp: U256,
+    pub(crate) mix_hash: H256,
+    pub(crate) base_fee_per_gas: Option<U256>,
+    pub(crate) hash: H256,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Withdrawal {
+    pub(crate) address: Address,
+    /// The amount withdrawn, in Gwei.
+    pub(crate) amount: U256,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Fixture {
+    State(Box<StateFixture>),
+    Block(Box<BlockFixture>),
+}
+
+/// Parses a fixture file, which maps test names to state or block tests.
+pub(crate) fn parse_fixtures(json: &str) -> Result<Vec<(String, Fixture)>> {
+    let fixtures: BTreeMap<String, serde_json::Value> = serde_json::from_str(json)?;
+    fixtures
+        .into_iter()
+        .map(|(name, value)| {
+            let fixture = if value.get("blocks").is_some() {
+                serde_json::from_value(value).map(Fixture::Block)
+            } else if value.get("post").is_some() {
+                serde_json::from_value(value).map(Fixture::State)
+            } else {
+                return Err(anyhow!("{name} is neither a state test nor a block test"));
+            };
+            let fixture = fixture.with_context(|| format!("Failed to parse {name}"))?;
+            Ok((name, fixture))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state_fixture() -> Result<()> {
+        let json = r#"{
+            "push0": {
+                "env": {
+                    "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                    "currentGasLimit": "0x0f4240",
+                    "currentNumber": "0x01",
+                    "currentTimestamp": "0x03e8",
+                    "currentRandom": "0x0000000000000000000000000000000000000000000000000000000000000000",
+                    "currentDifficulty": "0x00",
+                    "currentBaseFee": "0x07"
+                },
+                "pre": {
+                    "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b": {
+                        "nonce": "0x00",
+                        "balance": "0x3635c9adc5dea00000",
+                        "code": "0x",
+                        "storage": {}
+                    },
+                    "0x0000000000000000000000000000000000001000": {
+                        "nonce": "0x01",
+                        "balance": "0x00",
+                        "code": "0x5f5f55",
+                        "storage": { "0x00": "0x01" }
+                    }
+                },
+                "transaction": {},
+                "post": {
+                    "Shanghai": [{
+                        "hash": "0x1111111111111111111111111111111111111111111111111111111111111111",
+                        "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+                        "txbytes": "0xf86080",
+                        "indexes": { "data": 0, "gas": 0, "value": 0 }
+                    }]
+                },
+                "_info": {}
+            }
+        }"#;
+
+        let fixtures = parse_fixtures(json)?;
+        assert_eq!(fixtures.len(), 1);
+        let (name, Fixture::State(fixture)) = &fixtures[0] else {
+            panic!("Expected a state test");
+        };
+        assert_eq!(name, "push0");
+        let contract = &fixture.pre[&Address::from_low_u64_be(0x1000)];
+        assert_eq!(contract.code.0, vec![0x5f, 0x5f, 0x55]);
+        assert_eq!(contract.storage[&U256::zero()], U256::one());
+        let post = &fixture.post["Shanghai"][0];
+        assert_eq!(post.txbytes, Some(HexBytes(vec![0xf8, 0x60, 0x80])));
+        assert!(post.expect_exception.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+//! A runner for the [execution-spec-tests](https://github.com/ethereum/execution-spec-tests)
+//! fixtures, to check the kernel's conformance to the EVM specification.
+//!
+//! Both state tests and block tests are supported. Each test case is converted into
+//! `GenerationInputs` and run through witness generation, then the values computed by the kernel
+//! are checked against the fixture's. A block test provides all of them, while a state test only
+//! provides the post-state root and the hash of the logs, so its remaining expected values are
+//! taken from the kernel. A random sample of the passing test cases can also be proven.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use itertools::Itertools;
+use keccak_hash::keccak;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::plonk::config::KeccakGoldilocksConfig;
+use plonky2::util::timing::TimingTree;
+use plonky2_maybe_rayon::*;
+use rand::seq::index::sample;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::all_stark::AllStark;
+use crate::config::StarkConfig;
+use crate::eest::convert::{test_cases, Case, Expected, TestCase};
+use crate::eest::fixtures::parse_fixtures;
+use crate::generation::mpt::LogRlp;
+use crate::generation::simulation::{
+    initialize_state, read_final_values, simulate_until_final_tries,
+};
+use crate::generation::GenerationInputs;
+use crate::prover::prove;
+use crate::verifier::verify_proof;
+
+mod convert;
+mod fixtures;
+
+type F = GoldilocksField;
+const D: usize = 2;
+type C = KeccakGoldilocksConfig;
+
+#[derive(Clone, Debug)]
+pub struct RunnerConfig {
+    /// The forks whose test cases are run. Test cases for other forks are skipped.
+    pub forks: Vec<String>,
+    /// The number of passing test cases to also prove and verify.
+    pub num_proofs: usize,
+    /// The seed used to sample the test cases to prove.
+    pub seed: u64,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            forks: vec!["Shanghai".to_string()],
+            num_proofs: 0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// Witness generation computed the expected values.
+    Passed,
+    /// Witness generation computed the expected values, and the block was proven.
+    Proven,
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct CaseResult {
+    /// The fixture file the test case comes from.
+    pub file: PathBuf,
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub results: Vec<CaseResult>,
+}
+
+impl Report {
+    fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.results
+            .iter()
+            .filter(|result| predicate(&result.outcome))
+            .count()
+    }
+
+    pub fn num_passed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Passed | Outcome::Proven))
+    }
+
+    pub fn num_skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Skipped(_)))
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.results
+            .iter()
+            .filter(|result| matches!(result.outcome, Outcome::Failed(_)))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for result in self.failures() {
+            if let Outcome::Failed(reason) = &result.outcome {
+                writeln!(
+                    f,
+                    "FAILED {} ({}): {reason}",
+                    result.name,
+                    result.file.display()
+                )?;
+            }
+        }
+        let num_proven = self.count(|outcome| *outcome == Outcome::Proven);
+        write!(
+            f,
+            "{} passed ({num_proven} proven), {} failed, {} skipped",
+            self.num_passed(),
+            self.failures().count(),
+            self.num_skipped()
+        )
+    }
+}
+
+/// Lists the JSON fixture files at `path`, which is either one of them or a directory searched
+/// recursively.
+pub fn fixture_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(path).with_context(|| format!("Failed to read {path:?}"))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(fixture_files(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Runs the test cases of the given fixture files. A file which can't be loaded is reported as a
+/// failure rather than aborting the run.
+pub fn run_fixture_files(files: &[PathBuf], config: &RunnerConfig) -> Report {
+    let mut results = vec![];
+    let mut cases = vec![];
+    for file in files {
+        match load_test_cases(file, &config.forks) {
+            Ok(file_cases) => {
+                for case in file_cases {
+                    match case {
+                        Case::Run(case) => cases.push((file, case)),
+                        Case::Skip { name, reason } => results.push(CaseResult {
+                            file: file.clone(),
+                            name,
+                            outcome: Outcome::Skipped(reason),
+                        }),
+                    }
+                }
+            }
+            Err(err) => results.push(CaseResult {
+                file: file.clone(),
+                name: file.display().to_string(),
+                outcome: Outcome::Failed(format!("{err:#}")),
+            }),
+        }
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+    let mut to_prove = vec![false; cases.len()];
+    for i in sample(&mut rng, cases.len(), config.num_proofs.min(cases.len())) {
+        to_prove[i] = true;
+    }
+
+    let cases = cases.into_iter().zip(to_prove).collect_vec();
+    let case_results: Vec<_> = cases
+        .par_iter()
+        .map(|((file, case), prove)| CaseResult {
+            file: file.to_path_buf(),
+            name: case.name.clone(),
+            outcome: run_case(case, *prove),
+        })
+        .collect();
+    results.extend(case_results);
+    Report { results }
+}
+
+fn load_test_cases(file: &Path, forks: &[String]) -> Result<Vec<Case>> {
+    let json = fs::read_to_string(file).with_context(|| format!("Failed to read {file:?}"))?;
+    let cases = parse_fixtures(&json)?
+        .iter()
+        .map(|(name, fixture)| test_cases(name, fixture, forks))
+        .flatten_ok()
+        .collect::<Result<_>>()?;
+    Ok(cases)
+}
+
+fn run_case(case: &TestCase, prove: bool) -> Outcome {
+    // The kernel may panic on unexpected inputs, which shouldn't abort the other test cases.
+    let result = catch_unwind(AssertUnwindSafe(|| -> Result<()> {
+        let inputs = check_case(case)?;
+        if prove {
+            prove_case(inputs)?;
+        }
+        Ok(())
+    }))
+    .unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(anyhow!("Panicked: {message}"))
+    });
+    match result {
+        Ok(()) if prove => Outcome::Proven,
+        Ok(()) => Outcome::Passed,
+        Err(err) => Outcome::Failed(format!("{err:#}")),
+    }
+}
+
+/// Runs witness generation on the test case and checks the values it computes, then returns the
+/// inputs with all their expected values filled in.
+fn check_case(case: &TestCase) -> Result<GenerationInputs> {
+    let mut state = initialize_state::<F, D>(&case.inputs)?;
+    simulate_until_final_tries(&mut state, |_| {})?;
+    let values = read_final_values(&mut state)?;
+
+    let mut inputs = case.inputs.clone();
+    match case.expected {
+        Expected::Block => {
+            let roots = &inputs.trie_roots_after;
+            check("gas used", inputs.gas_used_after, values.gas_used)?;
+            check("bloom", inputs.block_bloom_after, values.block_bloom)?;
+            check("state root", roots.state_root, values.trie_roots.state_root)?;
+            check(
+                "transactions root",
+                roots.transactions_root,
+                values.trie_roots.transactions_root,
+            )?;
+            check(
+                "receipts root",
+                roots.receipts_root,
+                values.trie_roots.receipts_root,
+            )?;
+        }
+        Expected::State {
+            state_root,
+            logs_hash,
+        } => {
+            check("state root", state_root, values.trie_roots.state_root)?;
+            let logs = values
+                .receipts
+                .iter()
+                .flat_map(|receipt| receipt.logs.iter().cloned())
+                .collect_vec();
+            let actual_logs_hash = keccak(rlp::encode_list::<LogRlp, LogRlp>(&logs));
+            check("logs hash", logs_hash, actual_logs_hash)?;
+
+            inputs.gas_used_after = values.gas_used;
+            inputs.block_bloom_after = values.block_bloom;
+            inputs.block_metadata.block_gas_used = values.gas_used;
+            inputs.block_metadata.block_bloom = values.block_bloom;
+            inputs.trie_roots_after = values.trie_roots;
+        }
+    }
+    Ok(inputs)
+}
+
+fn check<T: Debug + PartialEq>(name: &str, expected: T, actual: T) -> Result<()> {
+    ensure!(
+        expected == actual,
+        "Wrong {name}: expected {expected:?}, got {actual:?}"
+    );
+    Ok(())
+}
+
+fn prove_case(inputs: GenerationInputs) -> Result<()> {
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+    let mut timing = TimingTree::new("prove", log::Level::Debug);
+    let proof = prove::<F, C, D>(&all_stark, &config, inputs, &mut timing)?;
+    verify_proof(&all_stark, proof, &config)
+}
@@ -1,3 +1,4 @@
+use core::marker::PhantomData;
 use core::mem::{self, MaybeUninit};
 use std::collections::BTreeMap;
 use std::ops::Range;
@@ -5,6 +6,7 @@ use std::ops::Range;
 use eth_trie_utils::partial_trie::{HashedPartialTrie, Node, PartialTrie};
 use hashbrown::HashMap;
 use itertools::{zip_eq, Itertools};
+use once_cell::sync::Lazy;
 use plonky2::field::extension::Extendable;
 use plonky2::fri::FriParams;
 use plonky2::gates::constant::ConstantGate;
@@ -21,25 +23,31 @@ use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
 use plonky2::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
 use plonky2::recursion::cyclic_recursion::check_cyclic_proof_verifier_data;
 use plonky2::recursion::dummy_circuit::cyclic_base_proof;
+use plonky2::timed;
 use plonky2::util::serialization::{
-    Buffer, GateSerializer, IoResult, Read, WitnessGeneratorSerializer, Write,
+    ArtifactKind, Buffer, GateSerializer, IoResult, Read, Remaining, WitnessGeneratorSerializer,
+    Write,
 };
 use plonky2::util::timing::TimingTree;
-use plonky2_util::log2_ceil;
+use plonky2_util::{log2_ceil, log2_strict};
 
 use crate::all_stark::{all_cross_table_lookups, AllStark, Table, NUM_TABLES};
-use crate::config::StarkConfig;
+use crate::circuit_cache::CircuitCache;
+use crate::config::{EvmProverConfig, StarkConfig};
+use crate::cpu::kernel::aggregator::KERNEL;
 use crate::cross_table_lookup::{
     get_grand_product_challenge_set_target, verify_cross_table_lookups_circuit, CrossTableLookup,
     GrandProductChallengeSet,
 };
-use crate::generation::GenerationInputs;
+use crate::generation::trace_limits::{UnsupportedTableSize, UnsupportedTraceSizes};
+use crate::generation::{generate_traces_with_limits, GenerationInputs};
 use crate::get_challenges::observe_public_values_target;
 use crate::proof::{
-    BlockHashesTarget, BlockMetadataTarget, ExtraBlockDataTarget, PublicValues, PublicValuesTarget,
+    BlockHashesTarget, BlockMetadataExtensionsTarget, BlockMetadataTarget, ChainConstantsTarget,
+    ExtraBlockDataTarget, ExtraPublicValuesTarget, PublicValues, PublicValuesTarget,
     StarkProofWithMetadata, TrieRootsTarget,
 };
-use crate::prover::prove;
+use crate::prover::{prove_with_traces, ProverOptions};
 use crate::recursive_verifier::{
     add_common_recursion_gates, add_virtual_public_values,
     get_memory_extra_looking_products_circuit, recursive_stark_circuit, set_public_value_targets,
@@ -55,6 +63,12 @@ const THRESHOLD_DEGREE_BITS: usize = 13;
 /// `degree_bits`, this contains a chain of recursive circuits for shrinking that STARK from
 /// `degree_bits` to a constant `THRESHOLD_DEGREE_BITS`. It also contains a special root circuit
 /// for combining each STARK's shrunk wrapper proof into a single proof.
+///
+/// The table STARKs and all these circuits use the config `C`. As the STARK transcripts, using
+/// `C::Hasher`, are replayed in circuits, it must be an algebraic hasher such as Poseidon. STARK
+/// proofs with other transcripts, e.g. Keccak ones, can still be generated and verified natively
+/// with `prover::prove` and `verifier::verify_proof`, and block proofs wrapped into proofs of any
+/// config with `BlockWrapperCircuitData`.
 #[derive(Eq, PartialEq, Debug)]
 pub struct AllRecursiveCircuits<F, C, const D: usize>
 where
@@ -287,6 +301,104 @@ where
     }
 }
 
+/// Data for the block wrapper circuit, which verifies a block proof and exposes the hash of its
+/// public values, `PublicValues::hash`, as its sole public input. External verifiers then bind to
+/// a single digest rather than to the full list of public values.
+///
+/// The wrapper circuit has its own config `C`, while `InnerC` is that of the block circuit. Only
+/// `InnerC` must have an algebraic hasher, so the wrapper can use e.g. `KeccakGoldilocksConfig`
+/// for proofs that are cheaper to verify outside plonky2. The public values are hashed with
+/// `C::InnerHasher`.
+#[derive(Eq, PartialEq, Debug)]
+pub struct BlockWrapperCircuitData<F, C, InnerC, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    InnerC: GenericConfig<D, F = F>,
+{
+    pub circuit: CircuitData<F, C, D>,
+    block_proof: ProofWithPublicInputsTarget<D>,
+    _phantom: PhantomData<InnerC>,
+}
+
+impl<F, C, InnerC, const D: usize> BlockWrapperCircuitData<F, C, InnerC, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    InnerC: GenericConfig<D, F = F>,
+    InnerC::Hasher: AlgebraicHasher<F>,
+{
+    /// Builds a circuit wrapping proofs of the given block circuit, with
+    /// `CircuitConfig::wrap_config`.
+    pub fn new(block: &BlockCircuitData<F, InnerC, D>) -> Self {
+        Self::new_with_config(block, CircuitConfig::wrap_config())
+    }
+
+    /// Builds a circuit wrapping proofs of the given block circuit, with the given config, e.g.
+    /// `EvmProverConfig::wrap_config`.
+    pub fn new_with_config(block: &BlockCircuitData<F, InnerC, D>, config: CircuitConfig) -> Self {
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let block_proof = builder.add_virtual_proof_with_pis(&block.circuit.common);
+        let block_verifier_data = builder.constant_verifier_data(&block.circuit.verifier_only);
+        builder.verify_proof::<InnerC>(&block_proof, &block_verifier_data, &block.circuit.common);
+
+        // The block circuit is cyclic, so its proofs also carry the verifier data they were
+        // checked against. It must be that of the block circuit itself.
+        let cyclic_vk = block_verifier_data
+            .circuit_digest
+            .elements
+            .into_iter()
+            .chain(
+                block_verifier_data
+                    .constants_sigmas_cap
+                    .0
+                    .iter()
+                    .flat_map(|hash| hash.elements),
+            );
+        for (pi, vk) in zip_eq(
+            &block_proof.public_inputs[PublicValuesTarget::SIZE..],
+            cyclic_vk,
+        ) {
+            builder.connect(*pi, vk);
+        }
+
+        let public_values = PublicValuesTarget::from_public_inputs(&block_proof.public_inputs);
+        let public_values_hash = public_values.hash_circuit::<F, C::InnerHasher, D>(&mut builder);
+        builder.register_public_inputs(&public_values_hash.elements);
+
+        Self {
+            circuit: builder.build::<C>(),
+            block_proof,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn prove(
+        &self,
+        block_proof: &ProofWithPublicInputs<F, InnerC, D>,
+    ) -> anyhow::Result<ProofWithPublicInputs<F, C, D>> {
+        let mut inputs = PartialWitness::new();
+        inputs.set_proof_with_pis_target(&self.block_proof, block_proof);
+        self.circuit.prove(inputs)
+    }
+
+    /// Verifies a wrapped block proof, and checks that it commits to `public_values`.
+    pub fn verify(
+        &self,
+        proof: &ProofWithPublicInputs<F, C, D>,
+        public_values: &PublicValues,
+    ) -> anyhow::Result<()> {
+        let public_values_hash = public_values
+            .hash::<F, C::InnerHasher>()
+            .map_err(|_| anyhow::Error::msg("Invalid conversion of public values."))?;
+        anyhow::ensure!(
+            proof.public_inputs == public_values_hash.elements,
+            "The proof doesn't commit to the given public values."
+        );
+        self.circuit.verify(proof.clone())
+    }
+}
+
 impl<F, C, const D: usize> AllRecursiveCircuits<F, C, D>
 where
     F: RichField + Extendable<D>,
@@ -300,6 +412,7 @@ where
     ) -> IoResult<Vec<u8>> {
         // TODO: would be better to initialize it dynamically based on the supported max degree.
         let mut buffer = Vec::with_capacity(1 << 34);
+        buffer.write_header(ArtifactKind::AllRecursiveCircuits)?;
         self.root
             .to_buffer(&mut buffer, gate_serializer, generator_serializer)?;
         self.aggregation
@@ -318,6 +431,7 @@ where
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<Self> {
         let mut buffer = Buffer::new(bytes);
+        buffer.read_header(ArtifactKind::AllRecursiveCircuits)?;
         let root =
             RootCircuitData::from_buffer(&mut buffer, gate_serializer, generator_serializer)?;
         let aggregation = AggregationCircuitData::from_buffer(
@@ -345,6 +459,7 @@ where
                 mem::transmute::<_, [RecursiveCircuitsForTable<F, C, D>; NUM_TABLES]>(by_table)
             }
         };
+        buffer.ensure_empty()?;
 
         Ok(Self {
             root,
@@ -354,72 +469,35 @@ where
         })
     }
 
-    /// Preprocess all recursive circuits used by the system.
+    /// Preprocess all recursive circuits used by the system, with the usual recursion configs.
     pub fn new(
         all_stark: &AllStark<F, D>,
         degree_bits_ranges: &[Range<usize>; NUM_TABLES],
         stark_config: &StarkConfig,
     ) -> Self {
-        let arithmetic = RecursiveCircuitsForTable::new(
-            Table::Arithmetic,
-            &all_stark.arithmetic_stark,
-            degree_bits_ranges[Table::Arithmetic as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
-        let byte_packing = RecursiveCircuitsForTable::new(
-            Table::BytePacking,
-            &all_stark.byte_packing_stark,
-            degree_bits_ranges[Table::BytePacking as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
-        let cpu = RecursiveCircuitsForTable::new(
-            Table::Cpu,
-            &all_stark.cpu_stark,
-            degree_bits_ranges[Table::Cpu as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
-        let keccak = RecursiveCircuitsForTable::new(
-            Table::Keccak,
-            &all_stark.keccak_stark,
-            degree_bits_ranges[Table::Keccak as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
-        let keccak_sponge = RecursiveCircuitsForTable::new(
-            Table::KeccakSponge,
-            &all_stark.keccak_sponge_stark,
-            degree_bits_ranges[Table::KeccakSponge as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
-        let logic = RecursiveCircuitsForTable::new(
-            Table::Logic,
-            &all_stark.logic_stark,
-            degree_bits_ranges[Table::Logic as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
-        let memory = RecursiveCircuitsForTable::new(
-            Table::Memory,
-            &all_stark.memory_stark,
-            degree_bits_ranges[Table::Memory as usize].clone(),
-            &all_stark.cross_table_lookups,
-            stark_config,
-        );
+        Self::new_with_config(
+            all_stark,
+            degree_bits_ranges,
+            &EvmProverConfig::from_stark_config(stark_config.clone()),
+        )
+    }
 
-        let by_table = [
-            arithmetic,
-            byte_packing,
-            cpu,
-            keccak,
-            keccak_sponge,
-            logic,
-            memory,
-        ];
-        let root = Self::create_root_circuit(&by_table, stark_config);
+    /// Preprocess all recursive circuits used by the system, with the configs of `config`. Proofs
+    /// must then be generated with `config.stark_config`.
+    pub fn new_with_config(
+        all_stark: &AllStark<F, D>,
+        degree_bits_ranges: &[Range<usize>; NUM_TABLES],
+        config: &EvmProverConfig,
+    ) -> Self {
+        let by_table = Table::all().map(|table| {
+            Self::create_table_circuits(
+                all_stark,
+                table,
+                degree_bits_ranges[table as usize].clone(),
+                config,
+            )
+        });
+        let root = Self::create_root_circuit(&by_table, config);
         let aggregation = Self::create_aggregation_circuit(&root);
         let block = Self::create_block_circuit(&aggregation);
         Self {
@@ -430,17 +508,170 @@ where
         }
     }
 
+    /// Like `new_with_config`, but reloads from `cache` the circuits which were built for the same
+    /// inputs, and only builds the others, storing them into `cache`.
+    pub fn new_with_cache(
+        all_stark: &AllStark<F, D>,
+        degree_bits_ranges: &[Range<usize>; NUM_TABLES],
+        config: &EvmProverConfig,
+        cache: &CircuitCache,
+        gate_serializer: &dyn GateSerializer<F, D>,
+        generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+    ) -> Self {
+        let table_keys = Table::all().map(|table| {
+            CircuitCache::table_key::<C, D>(
+                all_stark,
+                table,
+                &degree_bits_ranges[table as usize],
+                config,
+            )
+        });
+        let by_table = Table::all().map(|table| {
+            cache.load_or_build(
+                &format!("{table:?}"),
+                table_keys[table as usize],
+                |buffer| {
+                    RecursiveCircuitsForTable::from_buffer(
+                        buffer,
+                        gate_serializer,
+                        generator_serializer,
+                    )
+                },
+                |circuits, buffer| {
+                    circuits.to_buffer(buffer, gate_serializer, generator_serializer)
+                },
+                || {
+                    Self::create_table_circuits(
+                        all_stark,
+                        table,
+                        degree_bits_ranges[table as usize].clone(),
+                        config,
+                    )
+                },
+            )
+        });
+
+        let (root, aggregation, block) = cache.load_or_build(
+            "recursion",
+            CircuitCache::recursion_key::<C, D>(&table_keys, config),
+            |buffer| {
+                Ok((
+                    RootCircuitData::from_buffer(buffer, gate_serializer, generator_serializer)?,
+                    AggregationCircuitData::from_buffer(
+                        buffer,
+                        gate_serializer,
+                        generator_serializer,
+                    )?,
+                    BlockCircuitData::from_buffer(buffer, gate_serializer, generator_serializer)?,
+                ))
+            },
+            |(root, aggregation, block), buffer| {
+                root.to_buffer(buffer, gate_serializer, generator_serializer)?;
+                aggregation.to_buffer(buffer, gate_serializer, generator_serializer)?;
+                block.to_buffer(buffer, gate_serializer, generator_serializer)
+            },
+            || {
+                let root = Self::create_root_circuit(&by_table, config);
+                let aggregation = Self::create_aggregation_circuit(&root);
+                let block = Self::create_block_circuit(&aggregation);
+                (root, aggregation, block)
+            },
+        );
+        Self {
+            root,
+            aggregation,
+            block,
+            by_table,
+        }
+    }
+
+    fn create_table_circuits(
+        all_stark: &AllStark<F, D>,
+        table: Table,
+        degree_bits_range: Range<usize>,
+        config: &EvmProverConfig,
+    ) -> RecursiveCircuitsForTable<F, C, D> {
+        let ctls = &all_stark.cross_table_lookups;
+        match table {
+            Table::Arithmetic => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.arithmetic_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+            Table::BytePacking => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.byte_packing_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+            Table::Cpu => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.cpu_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+            Table::Keccak => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.keccak_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+            Table::KeccakSponge => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.keccak_sponge_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+            Table::Logic => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.logic_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+            Table::Memory => RecursiveCircuitsForTable::new(
+                table,
+                &all_stark.memory_stark,
+                degree_bits_range,
+                ctls,
+                config,
+            ),
+        }
+    }
+
     fn create_root_circuit(
         by_table: &[RecursiveCircuitsForTable<F, C, D>; NUM_TABLES],
-        stark_config: &StarkConfig,
+        config: &EvmProverConfig,
     ) -> RootCircuitData<F, C, D> {
+        let stark_config = &config.stark_config;
         let inner_common_data: [_; NUM_TABLES] =
             core::array::from_fn(|i| &by_table[i].final_circuits()[0].common);
 
-        let mut builder = CircuitBuilder::new(CircuitConfig::standard_recursion_config());
+        let mut builder = CircuitBuilder::new(config.recursion_config.clone());
 
         let public_values = add_virtual_public_values(&mut builder);
 
+        // Only the extra public value slots declared by the chain may be used.
+        let extra_public_values = &config.extra_public_values;
+        extra_public_values
+            .check_kernel_routines()
+            .expect("Invalid extra public values config");
+        for (slot, &declared) in public_values
+            .extra_public_values
+            .declared
+            .iter()
+            .enumerate()
+        {
+            let expected = builder.constant_bool(extra_public_values.is_declared(slot));
+            builder.connect(declared, expected.target);
+        }
+
         let recursive_proofs =
             core::array::from_fn(|i| builder.add_virtual_proof_with_pis(inner_common_data[i]));
         let pis: [_; NUM_TABLES] = core::array::from_fn(|i| {
@@ -596,6 +827,26 @@ where
             public_values.block_metadata,
             rhs_public_values.block_metadata,
         );
+        BlockMetadataExtensionsTarget::connect(
+            &mut builder,
+            public_values.block_metadata_extensions,
+            lhs_public_values.block_metadata_extensions,
+        );
+        BlockMetadataExtensionsTarget::connect(
+            &mut builder,
+            public_values.block_metadata_extensions,
+            rhs_public_values.block_metadata_extensions,
+        );
+        ChainConstantsTarget::connect(
+            &mut builder,
+            public_values.chain_constants,
+            lhs_public_values.chain_constants,
+        );
+        ChainConstantsTarget::connect(
+            &mut builder,
+            public_values.chain_constants,
+            rhs_public_values.chain_constants,
+        );
         // Connect aggregation `trie_roots_before` with lhs `trie_roots_before`.
         TrieRootsTarget::connect(
             &mut builder,
@@ -621,6 +872,12 @@ where
             &lhs_public_values.extra_block_data,
             &rhs_public_values.extra_block_data,
         );
+        ExtraPublicValuesTarget::connect_aggregation(
+            &mut builder,
+            public_values.extra_public_values,
+            lhs_public_values.extra_public_values,
+            rhs_public_values.extra_public_values,
+        );
 
         // Pad to match the root circuit's degree.
         while log2_ceil(builder.num_gates()) < root.circuit.common.degree_bits() {
@@ -721,7 +978,7 @@ where
             ..agg.circuit.common.clone()
         };
 
-        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut builder = CircuitBuilder::<F, D>::new(agg.circuit.common.config.clone());
         let public_values = add_virtual_public_values(&mut builder);
         let has_parent_block = builder.add_virtual_bool_target_safe();
         let parent_block_proof = builder.add_virtual_proof_with_pis(&expected_common_data);
@@ -748,6 +1005,11 @@ where
         let agg_verifier_data = builder.constant_verifier_data(&agg.circuit.verifier_only);
         builder.verify_proof::<C>(&agg_root_proof, &agg_verifier_data, &agg.circuit.common);
 
+        // Pad to the expected degree, which configs with fewer FRI queries may fall short of.
+        while log2_ceil(builder.num_gates()) < expected_common_data.degree_bits() {
+            builder.add_gate(NoopGate, vec![]);
+        }
+
         let circuit = builder.build::<C>();
         BlockCircuitData {
             circuit,
@@ -808,6 +1070,18 @@ where
             builder.connect(limb0, limb1);
         }
 
+        // Like the state, the extra public values carry over from the parent block, if any.
+        for (&limb0, &limb1) in lhs
+            .extra_public_values
+            .after
+            .iter()
+            .zip(&rhs.extra_public_values.before)
+        {
+            let constr = builder.sub(limb0, limb1);
+            let constr = builder.mul(has_parent_block.target, constr);
+            builder.assert_zero(constr);
+        }
+
         // Connect block numbers.
         let one = builder.one();
         let prev_block_nb = builder.sub(rhs.block_metadata.block_number, one);
@@ -898,7 +1172,54 @@ where
         }
     }
 
+    /// Returns, for each table, the sizes (as `log_2(height)`) of the traces for which these
+    /// circuits can aggregate proofs.
+    pub fn supported_degree_bits(&self) -> [Vec<usize>; NUM_TABLES] {
+        core::array::from_fn(|table| self.by_table[table].by_stark_size.keys().copied().collect())
+    }
+
+    /// Returns, for each table, the size (as `log_2(height)`) of the largest traces for which these
+    /// circuits can aggregate proofs.
+    pub fn max_degree_bits(&self) -> [usize; NUM_TABLES] {
+        core::array::from_fn(|table| {
+            let sizes = self.by_table[table].by_stark_size.keys();
+            sizes.last().copied().unwrap_or_default()
+        })
+    }
+
+    /// Checks that these circuits can aggregate proofs of traces with the given sizes, as
+    /// `log_2(height)`, listing every table whose trace doesn't fit otherwise. Traces are never
+    /// split into segments, so on failure the caller must retry with a smaller batch of
+    /// transactions, or with circuits built for wider degree ranges.
+    pub fn check_degree_bits(
+        &self,
+        degree_bits: &[usize; NUM_TABLES],
+    ) -> Result<(), UnsupportedTraceSizes> {
+        let tables = Table::all()
+            .into_iter()
+            .zip(degree_bits)
+            .filter_map(|(table, &degree_bits)| {
+                let sizes = &self.by_table[table as usize].by_stark_size;
+                (!sizes.contains_key(&degree_bits)).then(|| UnsupportedTableSize {
+                    table,
+                    degree_bits,
+                    supported_degree_bits: sizes.keys().copied().collect(),
+                })
+            })
+            .collect::<Vec<_>>();
+        if tables.is_empty() {
+            Ok(())
+        } else {
+            Err(UnsupportedTraceSizes { tables })
+        }
+    }
+
     /// Create a proof for each STARK, then combine them, eventually culminating in a root proof.
+    ///
+    /// Each table is sized to the smallest power of two fitting its trace, and its proof is
+    /// shrunk with the circuits for that size. The sizes are checked against the preprocessed
+    /// circuits before any STARK is proven, and traces too large for any of them are rejected with
+    /// a `TraceOverflow` as soon as they are generated.
     pub fn prove_root(
         &self,
         all_stark: &AllStark<F, D>,
@@ -906,34 +1227,110 @@ where
         generation_inputs: GenerationInputs,
         timing: &mut TimingTree,
     ) -> anyhow::Result<(ProofWithPublicInputs<F, C, D>, PublicValues)> {
-        let all_proof = prove::<F, C, D>(all_stark, config, generation_inputs, timing)?;
+        self.prove_root_with_options(
+            all_stark,
+            config,
+            generation_inputs,
+            &ProverOptions::default(),
+            timing,
+        )
+    }
+
+    /// Like `prove_root`, with the STARK proofs computed as tuned by `options`.
+    pub fn prove_root_with_options(
+        &self,
+        all_stark: &AllStark<F, D>,
+        config: &StarkConfig,
+        generation_inputs: GenerationInputs,
+        options: &ProverOptions,
+        timing: &mut TimingTree,
+    ) -> anyhow::Result<(ProofWithPublicInputs<F, C, D>, PublicValues)> {
+        timed!(timing, "build kernel", Lazy::force(&KERNEL));
+        let (traces, public_values, _outputs) = timed!(
+            timing,
+            "generate all traces",
+            generate_traces_with_limits(
+                all_stark,
+                generation_inputs,
+                config,
+                &self.max_degree_bits(),
+                options,
+                timing
+            )?
+        );
+        let degree_bits = core::array::from_fn(|table| log2_strict(traces[table][0].len()));
+        self.check_degree_bits(&degree_bits)?;
+        let all_proof = prove_with_traces::<F, C, D>(
+            all_stark,
+            config,
+            traces,
+            public_values,
+            options,
+            timing,
+        )?;
+
+        let mut shrunk_proofs = Vec::with_capacity(NUM_TABLES);
+        for (table, stark_proof) in Table::all().into_iter().zip(&all_proof.stark_proofs) {
+            shrunk_proofs.push(self.prove_table_shrink(
+                table,
+                stark_proof,
+                &all_proof.ctl_challenges,
+                config,
+            )?);
+        }
+        let shrunk_proofs = shrunk_proofs.try_into().unwrap();
+        self.prove_root_from_shrunk_proofs(&shrunk_proofs, all_proof.public_values)
+    }
+
+    /// Shrinks the STARK proof of `table` with the circuits for its size. Returns that size, as
+    /// `log_2(height)`, along with the shrunk proof.
+    pub fn prove_table_shrink(
+        &self,
+        table: Table,
+        stark_proof: &StarkProofWithMetadata<F, C, D>,
+        ctl_challenges: &GrandProductChallengeSet<F>,
+        config: &StarkConfig,
+    ) -> anyhow::Result<(usize, ProofWithPublicInputs<F, C, D>)> {
+        let original_degree_bits = stark_proof.proof.recover_degree_bits(config);
+        let shrunk_proof = self.by_table[table as usize]
+            .by_stark_size
+            .get(&original_degree_bits)
+            .ok_or_else(|| {
+                anyhow::Error::msg(format!(
+                    "Missing preprocessed circuits for {:?} table with size {}.",
+                    table, original_degree_bits,
+                ))
+            })?
+            .shrink(stark_proof, ctl_challenges)?;
+        Ok((original_degree_bits, shrunk_proof))
+    }
+
+    /// Combines the shrunk proof of each table, along with its original size as returned by
+    /// [`prove_table_shrink`](Self::prove_table_shrink), into a root proof.
+    pub fn prove_root_from_shrunk_proofs(
+        &self,
+        shrunk_proofs: &[(usize, ProofWithPublicInputs<F, C, D>); NUM_TABLES],
+        public_values: PublicValues,
+    ) -> anyhow::Result<(ProofWithPublicInputs<F, C, D>, PublicValues)> {
         let mut root_inputs = PartialWitness::new();
 
-        for table in 0..NUM_TABLES {
-            let stark_proof = &all_proof.stark_proofs[table];
-            let original_degree_bits = stark_proof.proof.recover_degree_bits(config);
-            let table_circuits = &self.by_table[table];
-            let shrunk_proof = table_circuits
+        for (table, (original_degree_bits, shrunk_proof)) in shrunk_proofs.iter().enumerate() {
+            let index_verifier_data = self.by_table[table]
                 .by_stark_size
-                .get(&original_degree_bits)
+                .keys()
+                .position(|size| size == original_degree_bits)
                 .ok_or_else(|| {
                     anyhow::Error::msg(format!(
                         "Missing preprocessed circuits for {:?} table with size {}.",
                         Table::all()[table],
                         original_degree_bits,
                     ))
-                })?
-                .shrink(stark_proof, &all_proof.ctl_challenges)?;
-            let index_verifier_data = table_circuits
-                .by_stark_size
-                .keys()
-                .position(|&size| size == original_degree_bits)
-                .unwrap();
+                })?;
             root_inputs.set_target(
                 self.root.index_verifier_data[table],
                 F::from_canonical_usize(index_verifier_data),
             );
-            root_inputs.set_proof_with_pis_target(&self.root.proof_with_pis[table], &shrunk_proof);
+            root_inputs.set_proof_with_pis_target(&self.root.proof_with_pis[table], shrunk_proof);
         }
 
         root_inputs.set_verifier_data_target(
@@ -941,18 +1338,14 @@ where
             &self.aggregation.circuit.verifier_only,
         );
 
-        set_public_value_targets(
-            &mut root_inputs,
-            &self.root.public_values,
-            &all_proof.public_values,
-        )
-        .map_err(|_| {
-            anyhow::Error::msg("Invalid conversion when setting public values targets.")
-        })?;
+        set_public_value_targets(&mut root_inputs, &self.root.public_values, &public_values)
+            .map_err(|_| {
+                anyhow::Error::msg("Invalid conversion when setting public values targets.")
+            })?;
 
         let root_proof = self.root.circuit.prove(root_inputs)?;
 
-        Ok((root_proof, all_proof.public_values))
+        Ok((root_proof, public_values))
     }
 
     pub fn verify_root(&self, agg_proof: ProofWithPublicInputs<F, C, D>) -> anyhow::Result<()> {
@@ -1141,7 +1534,7 @@ where
         stark: &S,
         degree_bits_range: Range<usize>,
         all_ctls: &[CrossTableLookup<F>],
-        stark_config: &StarkConfig,
+        config: &EvmProverConfig,
     ) -> Self {
         let by_stark_size = degree_bits_range
             .map(|degree_bits| {
@@ -1152,7 +1545,7 @@ where
                         stark,
                         degree_bits,
                         all_ctls,
-                        stark_config,
+                        config,
                     ),
                 )
             })
@@ -1262,15 +1655,15 @@ where
         stark: &S,
         degree_bits: usize,
         all_ctls: &[CrossTableLookup<F>],
-        stark_config: &StarkConfig,
+        config: &EvmProverConfig,
     ) -> Self {
         let initial_wrapper = recursive_stark_circuit(
             table,
             stark,
             degree_bits,
             all_ctls,
-            stark_config,
-            &shrinking_config(),
+            &config.stark_config,
+            &config.shrinking_config,
             THRESHOLD_DEGREE_BITS,
         );
         let mut shrinking_wrappers = vec![];
@@ -1287,7 +1680,7 @@ where
                 break;
             }
 
-            let mut builder = CircuitBuilder::new(shrinking_config());
+            let mut builder = CircuitBuilder::new(config.shrinking_config.clone());
             let proof_with_pis_target = builder.add_virtual_proof_with_pis(&last.common);
             let last_vk = builder.constant_verifier_data(&last.verifier_only);
             builder.verify_proof::<C>(&proof_with_pis_target, &last_vk, &last.common);
@@ -1327,13 +1720,3 @@ where
         Ok(proof)
     }
 }
-
-/// Our usual recursion threshold is 2^12 gates, but for these shrinking circuits, we use a few more
-/// gates for a constant inner VK and for public inputs. This pushes us over the threshold to 2^13.
-/// As long as we're at 2^13 gates, we might as well use a narrower witness.
-fn shrinking_config() -> CircuitConfig {
-    CircuitConfig {
-        num_routed_wires: 40,
-        ..CircuitConfig::standard_recursion_config()
-    }
-}
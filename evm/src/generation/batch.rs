@@ -0,0 +1,312 @@
+//! Merging of the inputs of consecutive transactions, to prove them together.
+//!
+//! The inputs of each transaction hold partial tries of the state before it, with the subtries it
+//! doesn't access hashed out. The merged inputs start from the partial tries of the first
+//! transaction, in which every subtrie hashed out but present in the tries of a later transaction
+//! is expanded. Such a subtrie has the same hash in both, so it wasn't changed by the transactions
+//! in between, and its nodes are those of the state before the first transaction.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use anyhow::{anyhow, ensure, Result};
+use eth_trie_utils::nibbles::Nibbles;
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::{H256, U512};
+
+use crate::generation::block_trace::{collect_leaves, empty_trie_root, full_key};
+use crate::generation::mpt::AccountRlp;
+use crate::generation::{GenerationInputs, TrieInputs};
+use crate::Node;
+
+/// Merges the inputs of consecutive transactions of a block, in order, into the inputs proving
+/// them all at once. Only the last inputs may hold withdrawals, which are processed after all
+/// transactions.
+pub fn merge_generation_inputs(inputs: Vec<GenerationInputs>) -> Result<GenerationInputs> {
+    ensure!(!inputs.is_empty(), "No inputs to merge");
+    for (i, pair) in inputs.windows(2).enumerate() {
+        let (prev, next) = (&pair[0], &pair[1]);
+        ensure!(
+            prev.withdrawals.is_empty(),
+            "Inputs {i} hold withdrawals, but aren't the last ones"
+        );
+        ensure!(
+            next.txn_number_before == prev.txn_number_before + prev.signed_txns.len(),
+            "Inputs {} don't start with the transaction following those of inputs {i}",
+            i + 1
+        );
+        ensure!(
+            next.gas_used_before == prev.gas_used_after
+                && next.block_bloom_before == prev.block_bloom_after,
+            "Inputs {} don't start with the gas used and bloom left by inputs {i}",
+            i + 1
+        );
+        ensure!(
+            next.tries.state_trie.hash() == prev.trie_roots_after.state_root
+                && next.tries.transactions_trie.hash() == prev.trie_roots_after.transactions_root
+                && next.tries.receipts_trie.hash() == prev.trie_roots_after.receipts_root,
+            "Inputs {} don't start from the tries left by inputs {i}",
+            i + 1
+        );
+        let (prev_extra, next_extra) = (&prev.extra_public_values, &next.extra_public_values);
+        ensure!(
+            next_extra.slots.map(|slot| slot.is_some())
+                == prev_extra.slots.map(|slot| slot.is_some())
+                && next_extra.before() == prev_extra.after(),
+            "Inputs {} don't start with the extra public values left by inputs {i}",
+            i + 1
+        );
+        ensure!(
+            next.chain_constants == prev.chain_constants,
+            "Inputs {} and {i} have different chain constants",
+            i + 1
+        );
+        ensure!(
+            next.genesis_state_trie_root == prev.genesis_state_trie_root,
+            "Inputs {} and {i} have different genesis state roots",
+            i + 1
+        );
+    }
+
+    let mut inputs = inputs.into_iter();
+    let first = inputs.next().unwrap();
+    let rest = inputs.collect::<Vec<_>>();
+    let Some(last) = rest.last() else {
+        return Ok(first);
+    };
+
+    let subtries = |trie: fn(&TrieInputs) -> &HashedPartialTrie| {
+        let mut subtries = HashMap::new();
+        for inputs in &rest {
+            collect_subtries(trie(&inputs.tries), &mut subtries);
+        }
+        subtries
+    };
+    let state_trie: HashedPartialTrie = expand_hashed_subtries(
+        &first.tries.state_trie,
+        &subtries(|tries| &tries.state_trie),
+    )
+    .into();
+    let transactions_trie = expand_hashed_subtries(
+        &first.tries.transactions_trie,
+        &subtries(|tries| &tries.transactions_trie),
+    )
+    .into();
+    let receipts_trie = expand_hashed_subtries(
+        &first.tries.receipts_trie,
+        &subtries(|tries| &tries.receipts_trie),
+    )
+    .into();
+    let storage_tries = merge_storage_tries(&state_trie, &first.tries.storage_tries, &rest)?;
+
+    let mut contract_code = first.contract_code;
+    let mut addresses = first.addresses;
+    let mut signed_txns = first.signed_txns;
+    for inputs in &rest {
+        contract_code.extend(inputs.contract_code.clone());
+        addresses.extend(&inputs.addresses);
+        signed_txns.extend(inputs.signed_txns.iter().cloned());
+    }
+    addresses.sort_unstable();
+    addresses.dedup();
+
+    Ok(GenerationInputs {
+        txn_number_before: first.txn_number_before,
+        gas_used_before: first.gas_used_before,
+        block_bloom_before: first.block_bloom_before,
+        gas_used_after: last.gas_used_after,
+        block_bloom_after: last.block_bloom_after,
+        signed_txns,
+        withdrawals: last.withdrawals.clone(),
+        tries: TrieInputs {
+            state_trie,
+            transactions_trie,
+            receipts_trie,
+            storage_tries,
+        },
+        trie_roots_after: last.trie_roots_after.clone(),
+        genesis_state_trie_root: first.genesis_state_trie_root,
+        contract_code,
+        block_metadata: first.block_metadata,
+        block_hashes: first.block_hashes,
+        extra_public_values: first.extra_public_values.merge(&last.extra_public_values),
+        chain_constants: first.chain_constants,
+        addresses,
+    })
+}
+
+/// Returns the storage trie of each account of the merged `state_trie`, starting from those of
+/// the first inputs, or from the storage root of the account otherwise, and expanded with those of
+/// the `later` inputs.
+fn merge_storage_tries(
+    state_trie: &HashedPartialTrie,
+    first_storage_tries: &[(H256, HashedPartialTrie)],
+    later: &[GenerationInputs],
+) -> Result<Vec<(H256, HashedPartialTrie)>> {
+    let mut subtries = HashMap::new();
+    for inputs in later {
+        for (_, storage_trie) in &inputs.tries.storage_tries {
+            collect_subtries(storage_trie, &mut subtries);
+        }
+    }
+    let first_storage_tries = first_storage_tries
+        .iter()
+        .map(|(state_key, storage_trie)| (*state_key, storage_trie))
+        .collect::<HashMap<_, _>>();
+
+    let mut accounts = vec![];
+    let empty_key = Nibbles {
+        count: 0,
+        packed: U512::zero(),
+    };
+    collect_leaves(state_trie, empty_key, &mut accounts);
+    let mut storage_tries = vec![];
+    for (key, account) in accounts {
+        let state_key = full_key(key)?;
+        let account: AccountRlp =
+            rlp::decode(&account).map_err(|err| anyhow!("Invalid account {state_key:?}: {err}"))?;
+        let storage_trie = match first_storage_tries.get(&state_key) {
+            Some(&storage_trie) => expand_hashed_subtries(storage_trie, &subtries),
+            None if account.storage_root == empty_trie_root() => continue,
+            None => match subtries.get(&account.storage_root) {
+                Some(storage_trie) => expand_hashed_subtries(storage_trie, &subtries),
+                None => continue,
+            },
+        };
+        storage_tries.push((state_key, storage_trie.into()));
+    }
+    Ok(storage_tries)
+}
+
+/// Records every subtrie of `trie` which isn't hashed out, by hash.
+fn collect_subtries(trie: &HashedPartialTrie, subtries: &mut HashMap<H256, HashedPartialTrie>) {
+    match trie.deref() {
+        Node::Empty | Node::Hash(_) => return,
+        Node::Branch { children, .. } => {
+            for child in children {
+                collect_subtries(child, subtries);
+            }
+        }
+        Node::Extension { child, .. } => collect_subtries(child, subtries),
+        Node::Leaf { .. } => {}
+    }
+    subtries.insert(trie.hash(), trie.clone());
+}
+
+/// Returns `trie`, with each hashed out subtrie replaced by the subtrie of the same hash in
+/// `subtries`, if any, recursively.
+fn expand_hashed_subtries(
+    trie: &HashedPartialTrie,
+    subtries: &HashMap<H256, HashedPartialTrie>,
+) -> Node {
+    match trie.deref() {
+        Node::Hash(hash) => match subtries.get(hash) {
+            Some(subtrie) => expand_hashed_subtries(subtrie, subtries),
+            None => Node::Hash(*hash),
+        },
+        Node::Branch { children, value } => Node::Branch {
+            children: core::array::from_fn(|i| {
+                expand_hashed_subtries(&children[i], subtries).into()
+            }),
+            value: value.clone(),
+        },
+        Node::Extension { nibbles, child } => Node::Extension {
+            nibbles: *nibbles,
+            child: expand_hashed_subtries(child, subtries).into(),
+        },
+        node => node.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::U256;
+
+    use super::*;
+    use crate::proof::TrieRoots;
+
+    /// Hashes out all the children of the root branch of `trie`, except the `kept` one.
+    fn hash_out_children(trie: &HashedPartialTrie, kept: usize) -> HashedPartialTrie {
+        let Node::Branch { children, value } = trie.deref() else {
+            panic!("Expected a branch");
+        };
+        HashedPartialTrie::new(Node::Branch {
+            children: core::array::from_fn(|i| {
+                let child: &HashedPartialTrie = &children[i];
+                match child.deref() {
+                    Node::Empty => Node::Empty.into(),
+                    _ if i == kept => children[i].clone(),
+                    _ => Node::Hash(child.hash()).into(),
+                }
+            }),
+            value: value.clone(),
+        })
+    }
+
+    fn roots(tries: &TrieInputs) -> TrieRoots {
+        TrieRoots {
+            state_root: tries.state_trie.hash(),
+            transactions_root: tries.transactions_trie.hash(),
+            receipts_root: tries.receipts_trie.hash(),
+        }
+    }
+
+    #[test]
+    fn test_merge_generation_inputs() -> Result<()> {
+        // Three accounts, in different children of the root branch. The first transaction changes
+        // the balance of the first account, and the second one reads the second account.
+        let keys = [0x11, 0x22, 0x33].map(|byte| Nibbles::from_bytes_be(&[byte; 32]).unwrap());
+        let account = |balance: u64| {
+            rlp::encode(&AccountRlp {
+                balance: balance.into(),
+                ..AccountRlp::default()
+            })
+            .to_vec()
+        };
+        let mut state_before = HashedPartialTrie::from(Node::Empty);
+        for (i, key) in keys.into_iter().enumerate() {
+            state_before.insert(key, account(i as u64 + 1));
+        }
+        let mut state_between = state_before.clone();
+        state_between.insert(keys[0], account(100));
+
+        let empty_tries = |state_trie| TrieInputs {
+            state_trie,
+            transactions_trie: Node::Empty.into(),
+            receipts_trie: Node::Empty.into(),
+            storage_tries: vec![],
+        };
+        let first = GenerationInputs {
+            signed_txns: vec![vec![1]],
+            tries: empty_tries(hash_out_children(&state_before, 1)),
+            trie_roots_after: roots(&empty_tries(state_between.clone())),
+            gas_used_after: 21000.into(),
+            ..GenerationInputs::default()
+        };
+        let second = GenerationInputs {
+            txn_number_before: U256::one(),
+            gas_used_before: 21000.into(),
+            signed_txns: vec![vec![2]],
+            tries: empty_tries(hash_out_children(&state_between, 2)),
+            trie_roots_after: roots(&empty_tries(state_between.clone())),
+            gas_used_after: 42000.into(),
+            ..GenerationInputs::default()
+        };
+
+        let merged = merge_generation_inputs(vec![first.clone(), second.clone()])?;
+        assert_eq!(merged.signed_txns, vec![vec![1], vec![2]]);
+        assert_eq!(merged.gas_used_after, U256::from(42000));
+        assert_eq!(merged.tries.state_trie.hash(), state_before.hash());
+        let mut accounts = vec![];
+        let empty_key = Nibbles {
+            count: 0,
+            packed: U512::zero(),
+        };
+        collect_leaves(&merged.tries.state_trie, empty_key, &mut accounts);
+        assert_eq!(accounts, vec![(keys[0], account(1)), (keys[1], account(2))]);
+
+        // The inputs must be consecutive.
+        assert!(merge_generation_inputs(vec![second, first]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+//! A parser for block traces, the JSON artifacts from which stateless clients and zkEVM provers
+//! re-execute a block, into `GenerationInputs`.
+//!
+//! A block trace holds the block's header, in the format of `eth_getBlockByNumber`, its signed
+//! transactions and withdrawals, the hashes of up to 256 ancestor blocks, and a witness in the
+//! format of `debug_executionWitness`: the RLP-encoded trie nodes of the pre-state, the code of
+//! the contracts it uses, and optionally the preimages of its trie keys.
+//!
+//! ```json
+//! {
+//!     "chainId": "0x1",
+//!     "genesisStateRoot": "0x...",
+//!     "preStateRoot": "0x...",
+//!     "header": { "number": "0x...", "stateRoot": "0x...", ... },
+//!     "transactions": ["0x..."],
+//!     "withdrawals": [{ "address": "0x...", "amount": "0x..." }],
+//!     "ancestorHashes": ["0x..."],
+//!     "witness": { "state": ["0x..."], "codes": ["0x..."], "keys": ["0x..."] }
+//! }
+//! ```
+//!
+//! The state and storage tries are rebuilt from the nodes reachable from their roots, and
+//! subtries whose nodes are missing are kept as hashes. Fields the prover doesn't know of are
+//! rejected, except in headers and withdrawals, which carry many fields it doesn't use.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use eth_trie_utils::nibbles::Nibbles;
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::{Address, BigEndianHash, H256, U256, U512};
+use keccak_hash::keccak;
+use rlp::Rlp;
+use serde::Deserialize;
+
+use crate::generation::mpt::AccountRlp;
+use crate::generation::{GenerationInputs, TrieInputs};
+use crate::proof::{
+    BlockHashes, BlockMetadata, BlockMetadataExtension, BlockMetadataExtensions, TrieRoots,
+};
+use crate::util::{bloom_words, h2u, HexBytes};
+use crate::Node;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct BlockTrace {
+    chain_id: U256,
+    genesis_state_root: H256,
+    /// The state root of the parent block.
+    pre_state_root: H256,
+    header: Header,
+    transactions: Vec<HexBytes>,
+    #[serde(default)]
+    withdrawals: Vec<Withdrawal>,
+    /// The hashes of the preceding blocks, the parent last.
+    ancestor_hashes: Vec<H256>,
+    witness: Witness,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    parent_hash: H256,
+    miner: Address,
+    state_root: H256,
+    transactions_root: H256,
+    receipts_root: H256,
+    logs_bloom: HexBytes,
+    difficulty: U256,
+    number: U256,
+    gas_limit: U256,
+    gas_used: U256,
+    timestamp: U256,
+    mix_hash: H256,
+    base_fee_per_gas: Option<U256>,
+    blob_gas_used: Option<U256>,
+    excess_blob_gas: Option<U256>,
+    parent_beacon_block_root: Option<H256>,
+    hash: H256,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Withdrawal {
+    address: Address,
+    /// The amount withdrawn, in Gwei.
+    amount: U256,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Witness {
+    /// The RLP encodings of the nodes of the state and storage tries.
+    state: Vec<HexBytes>,
+    /// The code of the contracts.
+    codes: Vec<HexBytes>,
+    /// The preimages of the trie keys, i.e. addresses and storage slots.
+    #[serde(default)]
+    keys: Vec<HexBytes>,
+}
+
+/// Parses a block trace into the inputs for proving its block.
+pub fn parse_block_trace(json: &str) -> Result<GenerationInputs> {
+    let trace: BlockTrace =
+        serde_json::from_str(json).context("Failed to parse the block trace")?;
+    generation_inputs(trace)
+}
+
+fn generation_inputs(trace: BlockTrace) -> Result<GenerationInputs> {
+    let header = &trace.header;
+    ensure!(
+        trace.ancestor_hashes.len() <= 256,
+        "Expected at most 256 ancestor hashes, found {}",
+        trace.ancestor_hashes.len()
+    );
+    if let Some(&parent_hash) = trace.ancestor_hashes.last() {
+        ensure!(
+            parent_hash == header.parent_hash,
+            "The last ancestor hash {parent_hash:?} isn't the parent hash {:?}",
+            header.parent_hash
+        );
+    }
+    let mut prev_hashes = vec![H256::zero(); 256 - trace.ancestor_hashes.len()];
+    prev_hashes.extend(&trace.ancestor_hashes);
+
+    let nodes: HashMap<H256, &[u8]> = trace
+        .witness
+        .state
+        .iter()
+        .map(|node| (keccak(&node.0), node.0.as_slice()))
+        .collect();
+    ensure!(
+        trace.pre_state_root == empty_trie_root() || nodes.contains_key(&trace.pre_state_root),
+        "The witness lacks the root node {:?} of the state trie",
+        trace.pre_state_root
+    );
+    let state_trie = resolve_trie(trace.pre_state_root, &nodes).context("Invalid state trie")?;
+
+    let mut accounts = vec![];
+    let empty_key = Nibbles {
+        count: 0,
+        packed: U512::zero(),
+    };
+    collect_leaves(&state_trie, empty_key, &mut accounts);
+    let storage_tries = accounts
+        .into_iter()
+        .map(|(key, account)| {
+            let state_key = full_key(key)?;
+            let account: AccountRlp = rlp::decode(&account)
+                .map_err(|err| anyhow!("Invalid account {state_key:?}: {err}"))?;
+            let storage_trie = resolve_trie(account.storage_root, &nodes)
+                .with_context(|| format!("Invalid storage trie of account {state_key:?}"))?;
+            Ok((state_key, storage_trie))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut contract_code: HashMap<H256, Vec<u8>> = trace
+        .witness
+        .codes
+        .iter()
+        .map(|code| (keccak(&code.0), code.0.clone()))
+        .collect();
+    contract_code.insert(keccak([]), vec![]);
+
+    let block_bloom = bloom_words(&header.logs_bloom.0)?;
+    let mut extensions = BlockMetadataExtensions::default();
+    let optional_fields = [
+        (BlockMetadataExtension::BlobGasUsed, header.blob_gas_used),
+        (
+            BlockMetadataExtension::ExcessBlobGas,
+            header.excess_blob_gas,
+        ),
+        (
+            BlockMetadataExtension::ParentBeaconBlockRoot,
+            header.parent_beacon_block_root.map(h2u),
+        ),
+    ];
+    for (field, value) in optional_fields {
+        if let Some(value) = value {
+            extensions.set(field, value);
+        }
+    }
+    let block_metadata = BlockMetadata {
+        block_beneficiary: header.miner,
+        block_timestamp: header.timestamp,
+        block_number: header.number,
+        block_difficulty: header.difficulty,
+        block_random: header.mix_hash,
+        block_gaslimit: header.gas_limit,
+        block_chain_id: trace.chain_id,
+        block_base_fee: header.base_fee_per_gas.unwrap_or_default(),
+        block_gas_used: header.gas_used,
+        block_bloom,
+        extensions,
+    };
+    let gwei = U256::exp10(9);
+
+    Ok(GenerationInputs {
+        gas_used_after: header.gas_used,
+        block_bloom_after: block_bloom,
+        signed_txns: trace.transactions.into_iter().map(|txn| txn.0).collect(),
+        withdrawals: trace
+            .withdrawals
+            .iter()
+            .map(|withdrawal| (withdrawal.address, withdrawal.amount * gwei))
+            .collect(),
+        tries: TrieInputs {
+            state_trie,
+            transactions_trie: Node::Empty.into(),
+            receipts_trie: Node::Empty.into(),
+            storage_tries,
+        },
+        trie_roots_after: TrieRoots {
+            state_root: header.state_root,
+            transactions_root: header.transactions_root,
+            receipts_root: header.receipts_root,
+        },
+        genesis_state_trie_root: trace.genesis_state_root,
+        contract_code,
+        block_metadata,
+        block_hashes: BlockHashes {
+            prev_hashes,
+            cur_hash: header.hash,
+        },
+        addresses: trace
+            .witness
+            .keys
+            .iter()
+            .filter(|key| key.0.len() == Address::len_bytes())
+            .map(|key| Address::from_slice(&key.0))
+            .collect(),
+        ..GenerationInputs::default()
+    })
+}
+
+pub(crate) fn empty_trie_root() -> H256 {
+    HashedPartialTrie::from(Node::Empty).hash()
+}
+
+/// Rebuilds the partial trie with the given root from the nodes of the witness.
+fn resolve_trie(root: H256, nodes: &HashMap<H256, &[u8]>) -> Result<HashedPartialTrie> {
+    let trie: HashedPartialTrie = resolve_hash(root, nodes)?.into();
+    ensure!(
+        trie.hash() == root,
+        "The rebuilt trie has root {:?} instead of {root:?}",
+        trie.hash()
+    );
+    Ok(trie)
+}
+
+/// The node with the given hash, or a hash node if the witness lacks it.
+fn resolve_hash(hash: H256, nodes: &HashMap<H256, &[u8]>) -> Result<Node> {
+    if hash == empty_trie_root() {
+        return Ok(Node::Empty);
+    }
+    match nodes.get(&hash) {
+        Some(node) => decode_node(node, nodes).with_context(|| format!("Invalid node {hash:?}")),
+        None => Ok(Node::Hash(hash)),
+    }
+}
+
+fn decode_node(bytes: &[u8], nodes: &HashMap<H256, &[u8]>) -> Result<Node> {
+    let rlp = Rlp::new(bytes);
+    let payload_info = rlp.payload_info()?;
+    ensure!(
+        payload_info.header_len + payload_info.value_len == bytes.len(),
+        "Trailing bytes after the node's RLP"
+    );
+    match rlp.item_count()? {
+        17 => {
+            let mut children = (0..16)
+                .map(|i| decode_child(rlp.at(i)?, nodes))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter();
+            Ok(Node::Branch {
+                children: core::array::from_fn(|_| children.next().unwrap().into()),
+                value: rlp.at(16)?.data()?.to_vec(),
+            })
+        }
+        2 => {
+            let (nibbles, is_leaf) = decode_path(rlp.at(0)?.data()?)?;
+            if is_leaf {
+                Ok(Node::Leaf {
+                    nibbles,
+                    value: rlp.at(1)?.data()?.to_vec(),
+                })
+            } else {
+                Ok(Node::Extension {
+                    nibbles,
+                    child: decode_child(rlp.at(1)?, nodes)?.into(),
+                })
+            }
+        }
+        n => bail!("A node has 2 or 17 items, not {n}"),
+    }
+}
+
+/// Decodes a reference to a child node, which is either its hash or, if its encoding is shorter
+/// than 32 bytes, the node itself.
+fn decode_child(item: Rlp, nodes: &HashMap<H256, &[u8]>) -> Result<Node> {
+    if item.is_list() {
+        return decode_node(item.as_raw(), nodes);
+    }
+    match item.data()? {
+        [] => Ok(Node::Empty),
+        hash if hash.len() == 32 => resolve_hash(H256::from_slice(hash), nodes),
+        data => bail!("Invalid child reference of {} bytes", data.len()),
+    }
+}
+
+/// Decodes a hex-prefix encoded path, and whether it's the path of a leaf.
+fn decode_path(bytes: &[u8]) -> Result<(Nibbles, bool)> {
+    let Some(&first) = bytes.first() else {
+        bail!("Empty path");
+    };
+    let flag = first >> 4;
+    ensure!(flag <= 3, "Invalid path flag {flag}");
+    let is_leaf = flag & 2 != 0;
+    let (count, packed) = if flag & 1 != 0 {
+        ensure!(
+            bytes.len() <= 32,
+            "Path of {} bytes is too long",
+            bytes.len()
+        );
+        let mut path = bytes.to_vec();
+        path[0] &= 0xf;
+        (2 * bytes.len() - 1, U256::from_big_endian(&path))
+    } else {
+        ensure!(first & 0xf == 0, "Invalid padding of an even path");
+        ensure!(
+            bytes.len() <= 33,
+            "Path of {} bytes is too long",
+            bytes.len()
+        );
+        (2 * bytes.len() - 2, U256::from_big_endian(&bytes[1..]))
+    };
+    Ok((
+        Nibbles {
+            count,
+            packed: packed.into(),
+        },
+        is_leaf,
+    ))
+}
+
+/// Collects the keys and values of the leaves of a trie.
+pub(crate) fn collect_leaves(trie: &Node, key: Nibbles, leaves: &mut Vec<(Nibbles, Vec<u8>)>) {
+    match trie {
+        Node::Empty | Node::Hash(_) => {}
+        Node::Branch { children, value } => {
+            if !value.is_empty() {
+                leaves.push((key, value.clone()));
+            }
+            for (i, child) in children.iter().enumerate() {
+                let nibble = Nibbles {
+                    count: 1,
+                    packed: i.into(),
+                };
+                collect_leaves(child, key.merge_nibbles(&nibble), leaves);
+            }
+        }
+        Node::Extension { nibbles, child } => {
+            collect_leaves(child, key.merge_nibbles(nibbles), leaves)
+        }
+        Node::Leaf { nibbles, value } => leaves.push((key.merge_nibbles(nibbles), value.clone())),
+    }
+}
+
+pub(crate) fn full_key(key: Nibbles) -> Result<H256> {
+    ensure!(
+        key.count == 64,
+        "Key of {} nibbles in the state trie",
+        key.count
+    );
+    let key = key
+        .try_into_u256()
+        .map_err(|_| anyhow!("Invalid state key"))?;
+    Ok(H256::from_uint(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use rlp::RlpStream;
+    use serde_json::json;
+
+    use super::*;
+
+    fn leaf(path: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&path.to_vec());
+        stream.append(&value.to_vec());
+        stream.out().to_vec()
+    }
+
+    fn block_trace(state_root: H256, nodes: &[&[u8]], parent_hash: H256) -> serde_json::Value {
+        let hex = |bytes: &[u8]| format!("0x{}", hex::encode(bytes));
+        json!({
+            "chainId": "0x1",
+            "genesisStateRoot": state_root,
+            "preStateRoot": state_root,
+            "header": {
+                "parentHash": parent_hash,
+                "miner": Address::zero(),
+                "stateRoot": state_root,
+                "transactionsRoot": empty_trie_root(),
+                "receiptsRoot": empty_trie_root(),
+                "logsBloom": hex(&[0; 256]),
+                "difficulty": "0x0",
+                "number": "0x1",
+                "gasLimit": "0x1c9c380",
+                "gasUsed": "0x0",
+                "timestamp": "0x64",
+                "mixHash": H256::zero(),
+                "baseFeePerGas": "0x7",
+                "hash": H256::repeat_byte(2),
+                "extraData": "0x",
+            },
+            "transactions": [],
+            "withdrawals": [],
+            "ancestorHashes": [H256::repeat_byte(1)],
+            "witness": {
+                "state": nodes.iter().map(|node| hex(node)).collect::<Vec<_>>(),
+                "codes": [hex(&[0x5f, 0x5f, 0x55])],
+                "keys": [hex(Address::repeat_byte(3).as_bytes())],
+            },
+        })
+    }
+
+    #[test]
+    fn test_parse_block_trace() -> Result<()> {
+        // A storage trie with a single slot.
+        let slot_key = keccak([0; 32]);
+        let storage_leaf = leaf(
+            &[&[0x20][..], slot_key.as_bytes()].concat(),
+            &rlp::encode(&1u8),
+        );
+        let storage_root = keccak(&storage_leaf);
+
+        // A state trie whose root is a branch, with an account under one child and a subtrie
+        // missing from the witness under another.
+        let code = [0x5f, 0x5f, 0x55];
+        let account = AccountRlp {
+            nonce: 1.into(),
+            balance: 0.into(),
+            storage_root,
+            code_hash: keccak(code),
+        };
+        let state_key = keccak(Address::repeat_byte(3));
+        let first_nibble = state_key[0] >> 4;
+        let path = [&[0x30 | (state_key[0] & 0xf)][..], &state_key[1..]].concat();
+        let account_leaf = leaf(&path, &rlp::encode(&account));
+        let pruned_hash = H256::repeat_byte(0x11);
+        let other_nibble = (first_nibble + 1) % 16;
+        let mut stream = RlpStream::new_list(17);
+        for i in 0..16 {
+            if i == first_nibble {
+                stream.append(&keccak(&account_leaf));
+            } else if i == other_nibble {
+                stream.append(&pruned_hash);
+            } else {
+                stream.append_empty_data();
+            }
+        }
+        stream.append_empty_data();
+        let branch = stream.out().to_vec();
+        let state_root = keccak(&branch);
+
+        let trace = block_trace(
+            state_root,
+            &[&branch, &account_leaf, &storage_leaf],
+            H256::repeat_byte(1),
+        );
+        let inputs = parse_block_trace(&trace.to_string())?;
+
+        assert_eq!(inputs.tries.state_trie.hash(), state_root);
+        assert_eq!(inputs.tries.storage_tries.len(), 1);
+        let (key, storage_trie) = &inputs.tries.storage_tries[0];
+        assert_eq!(*key, state_key);
+        assert_eq!(storage_trie.hash(), storage_root);
+        assert_eq!(inputs.contract_code[&keccak(code)], code.to_vec());
+        assert_eq!(inputs.block_metadata.block_base_fee, 7.into());
+        assert_eq!(inputs.block_hashes.prev_hashes[255], H256::repeat_byte(1));
+        assert_eq!(inputs.block_hashes.prev_hashes[0], H256::zero());
+        assert_eq!(inputs.addresses, vec![Address::repeat_byte(3)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_block_traces() {
+        let node = leaf(&[0x20; 33], &rlp::encode(&AccountRlp::default()));
+        let root = keccak(&node);
+        let parent_hash = H256::repeat_byte(1);
+        assert!(parse_block_trace(&block_trace(root, &[&node], parent_hash).to_string()).is_ok());
+
+        // The parent hash doesn't match the last ancestor hash.
+        let trace = block_trace(root, &[&node], H256::repeat_byte(4));
+        assert!(parse_block_trace(&trace.to_string()).is_err());
+
+        // The witness lacks the state root.
+        let trace = block_trace(root, &[], parent_hash);
+        assert!(parse_block_trace(&trace.to_string()).is_err());
+
+        // A node has trailing bytes.
+        let trailing = [&node[..], &[0]].concat();
+        let trace = block_trace(keccak(&trailing), &[&trailing], parent_hash);
+        assert!(parse_block_trace(&trace.to_string()).is_err());
+
+        // An unknown field.
+        let mut trace = block_trace(root, &[&node], parent_hash);
+        trace["witness"]["headers"] = json!([]);
+        assert!(parse_block_trace(&trace.to_string()).is_err());
+    }
+}
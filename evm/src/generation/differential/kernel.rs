@@ -0,0 +1,76 @@
+//! Execution of inputs by the kernel, as during witness generation.
+
+use anyhow::Result;
+use plonky2::field::goldilocks_field::GoldilocksField;
+
+use super::{BlockOutput, Execution, LogOutput, ReceiptOutput, Step};
+use crate::cpu::kernel::aggregator::KERNEL;
+use crate::generation::simulation::{
+    initialize_state, read_final_values, simulate_until_final_tries,
+};
+use crate::generation::GenerationInputs;
+use crate::memory::segments::Segment;
+use crate::witness::memory::MemoryAddress;
+
+type F = GoldilocksField;
+
+pub(super) fn execute(inputs: &GenerationInputs) -> Execution {
+    let mut steps = vec![];
+    let output = execute_with_steps(inputs, &mut steps).map_err(|err| format!("{err:?}"));
+    Execution { steps, output }
+}
+
+/// Runs the kernel until it is about to check and hash the final tries, recording user-mode
+/// instructions along the way, then reads the outputs the kernel computed.
+fn execute_with_steps(inputs: &GenerationInputs, steps: &mut Vec<Step>) -> Result<BlockOutput> {
+    let mut state = initialize_state::<F, 2>(inputs)?;
+
+    let route_txn = KERNEL.global_labels["route_txn"];
+    let mut txn_index = None;
+    simulate_until_final_tries(&mut state, |state| {
+        let registers = state.registers;
+        let pc = registers.program_counter;
+        if registers.is_kernel {
+            if pc == route_txn {
+                txn_index = Some(txn_index.map_or(0, |i| i + 1));
+            }
+        } else {
+            let code_address = MemoryAddress::new(registers.code_context(), Segment::Code, pc);
+            steps.push(Step {
+                txn_index: txn_index.unwrap_or_default(),
+                pc,
+                opcode: state.memory.get(code_address).low_u32() as u8,
+                stack_len: registers.stack_len,
+                stack_top: (registers.stack_len > 0).then_some(registers.stack_top),
+                gas_used: registers.gas_used,
+            });
+        }
+    })?;
+
+    let values = read_final_values(&mut state)?;
+    let receipts = values
+        .receipts
+        .into_iter()
+        .map(|receipt| ReceiptOutput {
+            status: receipt.status,
+            cum_gas_used: receipt.cum_gas_used,
+            bloom: receipt.bloom.to_vec(),
+            logs: receipt
+                .logs
+                .into_iter()
+                .map(|log| LogOutput {
+                    address: log.address,
+                    topics: log.topics,
+                    data: log.data.to_vec(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(BlockOutput {
+        receipts,
+        gas_used: values.gas_used,
+        state_root: values.trie_roots.state_root,
+        receipts_root: values.trie_roots.receipts_root,
+    })
+}
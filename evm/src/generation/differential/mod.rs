@@ -0,0 +1,293 @@
+//! Differential testing of the kernel against [revm](https://github.com/bluealloy/revm).
+//!
+//! [`diff_against_revm`] executes a block's [`GenerationInputs`] both with the kernel, as during
+//! witness generation, and with revm, and compares the two executions: the user-mode instructions
+//! they execute, then each transaction's receipt, the block's gas used and the final state and
+//! receipt trie roots. The first divergence points at the opcode whose semantics differ, which is
+//! much cheaper to find this way than from a failing proof.
+
+use core::fmt::{self, Display, Formatter};
+
+use anyhow::{anyhow, Result};
+use ethereum_types::{Address, H256, U256};
+use plonky2_maybe_rayon::*;
+use revm::interpreter::OPCODE_JUMPMAP;
+
+use crate::generation::GenerationInputs;
+
+mod kernel;
+mod reference;
+
+/// The state of the EVM right before it executes a user-mode instruction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Step {
+    /// The index of the transaction in `GenerationInputs::signed_txns`.
+    pub txn_index: usize,
+    pub pc: usize,
+    pub opcode: u8,
+    pub stack_len: usize,
+    /// The top of the stack, if it isn't empty.
+    pub stack_top: Option<U256>,
+    /// The gas used so far by the current call frame.
+    pub gas_used: u64,
+}
+
+/// A log emitted by a transaction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogOutput {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+}
+
+/// The receipt of a transaction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReceiptOutput {
+    pub status: bool,
+    pub cum_gas_used: U256,
+    pub bloom: Vec<u8>,
+    pub logs: Vec<LogOutput>,
+}
+
+/// The outcome of an execution of a block's transactions and withdrawals.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockOutput {
+    /// The receipts of the transactions, in order.
+    pub receipts: Vec<ReceiptOutput>,
+    /// The cumulative gas used after the transactions.
+    pub gas_used: U256,
+    pub state_root: H256,
+    pub receipts_root: H256,
+}
+
+/// An execution by the kernel or revm.
+#[derive(Clone, Debug)]
+pub(crate) struct Execution {
+    /// The user-mode instructions executed, even if the execution failed.
+    pub(crate) steps: Vec<Step>,
+    pub(crate) output: Result<BlockOutput, String>,
+}
+
+/// The first difference between the kernel's execution of some inputs and revm's.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Divergence {
+    /// The executions differ at their `index`th user-mode instruction, i.e. after executing
+    /// `previous`. A missing step means that the execution ended before it.
+    Step {
+        index: usize,
+        previous: Option<Step>,
+        kernel: Option<Step>,
+        revm: Option<Step>,
+    },
+    /// Exactly one of the executions failed, after the same instructions as the other.
+    Failure {
+        kernel: Option<String>,
+        revm: Option<String>,
+    },
+    /// The receipts of a transaction differ.
+    Receipt {
+        txn_index: usize,
+        kernel: Option<ReceiptOutput>,
+        revm: Option<ReceiptOutput>,
+    },
+    GasUsed {
+        kernel: U256,
+        revm: U256,
+    },
+    StateRoot {
+        kernel: H256,
+        revm: H256,
+    },
+    ReceiptsRoot {
+        kernel: H256,
+        revm: H256,
+    },
+}
+
+impl Divergence {
+    /// Returns the last opcode executed by both executions before they diverged, if they diverged
+    /// within a transaction.
+    pub fn offending_opcode(&self) -> Option<u8> {
+        match self {
+            Self::Step { previous, .. } => previous.map(|step| step.opcode),
+            _ => None,
+        }
+    }
+}
+
+fn opcode_name(opcode: u8) -> &'static str {
+    OPCODE_JUMPMAP[opcode as usize].unwrap_or("INVALID")
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Step {
+                index,
+                previous,
+                kernel,
+                revm,
+            } => {
+                write!(f, "executions diverge at user instruction {index}")?;
+                if let Some(previous) = previous {
+                    write!(
+                        f,
+                        ", after {} ({:#04x}) at pc {} of transaction {}",
+                        opcode_name(previous.opcode),
+                        previous.opcode,
+                        previous.pc,
+                        previous.txn_index
+                    )?;
+                }
+                write!(f, ": kernel {kernel:?}, revm {revm:?}")
+            }
+            Self::Failure { kernel, revm } => {
+                write!(f, "execution failed: kernel {kernel:?}, revm {revm:?}")
+            }
+            Self::Receipt {
+                txn_index,
+                kernel,
+                revm,
+            } => write!(
+                f,
+                "receipts of transaction {txn_index} differ: kernel {kernel:?}, revm {revm:?}"
+            ),
+            Self::GasUsed { kernel, revm } => {
+                write!(f, "gas used differs: kernel {kernel}, revm {revm}")
+            }
+            Self::StateRoot { kernel, revm } => {
+                write!(f, "state roots differ: kernel {kernel:?}, revm {revm:?}")
+            }
+            Self::ReceiptsRoot { kernel, revm } => {
+                write!(f, "receipt roots differ: kernel {kernel:?}, revm {revm:?}")
+            }
+        }
+    }
+}
+
+/// Executes `inputs` with the kernel and with revm concurrently, and returns their first
+/// divergence, if any. Fails if both executions fail, e.g. on malformed inputs.
+pub fn diff_against_revm(inputs: &GenerationInputs) -> Result<Option<Divergence>> {
+    let (kernel, revm) = join(|| kernel::execute(inputs), || reference::execute(inputs));
+    first_divergence(&kernel, &revm)
+}
+
+/// Runs [`diff_against_revm`] on each of `inputs` in parallel.
+pub fn diff_all_against_revm(inputs: &[GenerationInputs]) -> Result<Vec<Option<Divergence>>> {
+    inputs.par_iter().map(diff_against_revm).collect()
+}
+
+pub(crate) fn first_divergence(kernel: &Execution, revm: &Execution) -> Result<Option<Divergence>> {
+    let num_steps = kernel.steps.len().max(revm.steps.len());
+    if let Some(index) = (0..num_steps).find(|&i| kernel.steps.get(i) != revm.steps.get(i)) {
+        return Ok(Some(Divergence::Step {
+            index,
+            previous: index.checked_sub(1).map(|i| kernel.steps[i]),
+            kernel: kernel.steps.get(index).copied(),
+            revm: revm.steps.get(index).copied(),
+        }));
+    }
+
+    let (kernel, revm) = match (&kernel.output, &revm.output) {
+        (Ok(kernel), Ok(revm)) => (kernel, revm),
+        (Err(kernel), Err(revm)) => {
+            return Err(anyhow!(
+                "Both executions failed: kernel {kernel:?}, revm {revm:?}"
+            ))
+        }
+        (kernel, revm) => {
+            return Ok(Some(Divergence::Failure {
+                kernel: kernel.as_ref().err().cloned(),
+                revm: revm.as_ref().err().cloned(),
+            }))
+        }
+    };
+
+    let num_receipts = kernel.receipts.len().max(revm.receipts.len());
+    if let Some(txn_index) =
+        (0..num_receipts).find(|&i| kernel.receipts.get(i) != revm.receipts.get(i))
+    {
+        return Ok(Some(Divergence::Receipt {
+            txn_index,
+            kernel: kernel.receipts.get(txn_index).cloned(),
+            revm: revm.receipts.get(txn_index).cloned(),
+        }));
+    }
+    if kernel.gas_used != revm.gas_used {
+        return Ok(Some(Divergence::GasUsed {
+            kernel: kernel.gas_used,
+            revm: revm.gas_used,
+        }));
+    }
+    if kernel.state_root != revm.state_root {
+        return Ok(Some(Divergence::StateRoot {
+            kernel: kernel.state_root,
+            revm: revm.state_root,
+        }));
+    }
+    if kernel.receipts_root != revm.receipts_root {
+        return Ok(Some(Divergence::ReceiptsRoot {
+            kernel: kernel.receipts_root,
+            revm: revm.receipts_root,
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(pc: usize, opcode: u8, stack_top: Option<U256>) -> Step {
+        Step {
+            txn_index: 0,
+            pc,
+            opcode,
+            stack_len: stack_top.is_some() as usize,
+            stack_top,
+            gas_used: 3 * pc as u64,
+        }
+    }
+
+    fn execution(steps: Vec<Step>, gas_used: u64) -> Execution {
+        Execution {
+            steps,
+            output: Ok(BlockOutput {
+                receipts: vec![],
+                gas_used: gas_used.into(),
+                state_root: H256::zero(),
+                receipts_root: H256::zero(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_first_divergence() -> Result<()> {
+        let steps = vec![step(0, 0x60, None), step(2, 0x60, Some(1.into()))];
+        let kernel = execution(steps.clone(), 21_000);
+        assert_eq!(first_divergence(&kernel, &kernel)?, None);
+
+        // A wrong PUSH1 result is blamed on the PUSH1.
+        let mut wrong_steps = steps.clone();
+        wrong_steps[1].stack_top = Some(2.into());
+        let divergence = first_divergence(&kernel, &execution(wrong_steps, 21_000))?.unwrap();
+        assert_eq!(divergence.offending_opcode(), Some(0x60));
+        assert!(divergence
+            .to_string()
+            .contains("after PUSH1 (0x60) at pc 0"));
+
+        let divergence = first_divergence(&kernel, &execution(steps[..1].to_vec(), 21_000))?;
+        assert!(matches!(
+            divergence,
+            Some(Divergence::Step {
+                index: 1,
+                revm: None,
+                ..
+            })
+        ));
+
+        let divergence = first_divergence(&kernel, &execution(steps, 21_001))?;
+        assert!(matches!(divergence, Some(Divergence::GasUsed { .. })));
+        Ok(())
+    }
+}
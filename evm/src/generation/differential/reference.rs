@@ -0,0 +1,451 @@
+//! Execution of inputs by revm, the reference implementation. The state is read from and written
+//! back to the input tries, so that the final roots can be compared with the kernel's.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use eth_trie_utils::nibbles::Nibbles;
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::{Address, Bloom, BloomInput, H256, U256};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use keccak_hash::keccak;
+use revm::inspector_handle_register;
+use revm::interpreter::Interpreter;
+use revm::primitives::{
+    self, AccountInfo, Bytecode, Bytes, ExecutionResult, SpecId, State, TransactTo, TxEnv,
+    KECCAK_EMPTY,
+};
+use revm::{Database, Evm, EvmContext, Inspector};
+use rlp::Rlp;
+
+use super::{BlockOutput, Execution, LogOutput, ReceiptOutput, Step};
+use crate::generation::mpt::{AccountRlp, LegacyReceiptRlp, LogRlp};
+use crate::generation::GenerationInputs;
+use crate::proof::BlockHashes;
+use crate::Node;
+
+pub(super) fn execute(inputs: &GenerationInputs) -> Execution {
+    let mut recorder = StepRecorder::default();
+    let output = execute_with_recorder(inputs, &mut recorder).map_err(|err| format!("{err:?}"));
+    Execution {
+        steps: recorder.steps,
+        output,
+    }
+}
+
+fn execute_with_recorder(
+    inputs: &GenerationInputs,
+    recorder: &mut StepRecorder,
+) -> Result<BlockOutput> {
+    if !inputs.chain_constants.is_empty() {
+        bail!("revm only supports the Ethereum values of the chain constants");
+    }
+    let metadata = &inputs.block_metadata;
+    let mut db = TrieDb::new(inputs);
+    let mut receipts_trie = inputs.tries.receipts_trie.clone();
+    let mut receipts = Vec::with_capacity(inputs.signed_txns.len());
+    let mut gas_used = inputs.gas_used_before;
+
+    for (txn_index, signed_txn) in inputs.signed_txns.iter().enumerate() {
+        let (txn_type, tx_env) = decode_txn(signed_txn)?;
+        recorder.txn_index = txn_index;
+        let mut evm = Evm::builder()
+            .with_db(&mut db)
+            .with_external_context(&mut *recorder)
+            .with_spec_id(SpecId::SHANGHAI)
+            .modify_cfg_env(|cfg| cfg.chain_id = metadata.block_chain_id.as_u64())
+            .modify_block_env(|block| {
+                block.number = to_revm_u256(metadata.block_number);
+                block.coinbase = to_revm_address(metadata.block_beneficiary);
+                block.timestamp = to_revm_u256(metadata.block_timestamp);
+                block.gas_limit = to_revm_u256(metadata.block_gaslimit);
+                block.basefee = to_revm_u256(metadata.block_base_fee);
+                block.difficulty = to_revm_u256(metadata.block_difficulty);
+                block.prevrandao = Some(metadata.block_random.0.into());
+            })
+            .with_tx_env(tx_env)
+            .append_handler_register(inspector_handle_register)
+            .build();
+        let result = evm
+            .transact()
+            .map_err(|err| anyhow!("revm failed on transaction {txn_index}: {err:?}"))?;
+        drop(evm);
+        db.commit(result.state)?;
+
+        gas_used += U256::from(result.result.gas_used());
+        let logs = result
+            .result
+            .logs()
+            .iter()
+            .map(|log| LogOutput {
+                address: from_revm_address(log.address),
+                topics: log.topics().iter().map(|topic| H256(topic.0)).collect(),
+                data: log.data.data.to_vec(),
+            })
+            .collect::<Vec<_>>();
+        let receipt = ReceiptOutput {
+            status: matches!(result.result, ExecutionResult::Success { .. }),
+            cum_gas_used: gas_used,
+            bloom: logs_bloom(&logs).as_bytes().to_vec(),
+            logs,
+        };
+
+        let txn_number = inputs.txn_number_before + txn_index;
+        let encoded_receipt = LegacyReceiptRlp {
+            status: receipt.status,
+            cum_gas_used: receipt.cum_gas_used,
+            bloom: receipt.bloom.clone().into(),
+            logs: receipt
+                .logs
+                .iter()
+                .map(|log| LogRlp {
+                    address: log.address,
+                    topics: log.topics.clone(),
+                    data: log.data.clone().into(),
+                })
+                .collect(),
+        }
+        .encode(txn_type);
+        receipts_trie.insert(
+            Nibbles::from_bytes_be(&rlp::encode(&txn_number)).unwrap(),
+            encoded_receipt,
+        );
+        receipts.push(receipt);
+    }
+
+    for &(address, amount) in &inputs.withdrawals {
+        db.add_balance(address, amount)?;
+    }
+
+    Ok(BlockOutput {
+        receipts,
+        gas_used,
+        state_root: db.state_trie.hash(),
+        receipts_root: receipts_trie.hash(),
+    })
+}
+
+/// Records the user-mode instructions executed by revm.
+#[derive(Default)]
+struct StepRecorder {
+    txn_index: usize,
+    steps: Vec<Step>,
+}
+
+impl<DB: Database> Inspector<DB> for StepRecorder {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let stack = interp.stack();
+        self.steps.push(Step {
+            txn_index: self.txn_index,
+            pc: interp.program_counter(),
+            opcode: interp.current_opcode(),
+            stack_len: stack.len(),
+            stack_top: stack.peek(0).ok().map(from_revm_u256),
+            gas_used: interp.gas().spend(),
+        });
+    }
+}
+
+/// A revm database backed by the input tries, which are updated as transactions are committed.
+struct TrieDb<'a> {
+    state_trie: HashedPartialTrie,
+    /// The storage tries, by hashed address.
+    storage_tries: HashMap<H256, HashedPartialTrie>,
+    contract_code: HashMap<H256, Vec<u8>>,
+    block_number: U256,
+    block_hashes: &'a BlockHashes,
+}
+
+impl<'a> TrieDb<'a> {
+    fn new(inputs: &'a GenerationInputs) -> Self {
+        Self {
+            state_trie: inputs.tries.state_trie.clone(),
+            storage_tries: inputs.tries.storage_tries.iter().cloned().collect(),
+            contract_code: inputs.contract_code.clone(),
+            block_number: inputs.block_metadata.block_number,
+            block_hashes: &inputs.block_hashes,
+        }
+    }
+
+    fn account(&self, address: Address) -> Result<Option<AccountRlp>> {
+        self.state_trie
+            .get(state_key_nibbles(address))
+            .map(|bytes| rlp::decode(bytes).map_err(|err| anyhow!("Invalid account: {err:?}")))
+            .transpose()
+    }
+
+    fn set_account(&mut self, address: Address, account: &AccountRlp) {
+        self.state_trie
+            .insert(state_key_nibbles(address), rlp::encode(account).to_vec());
+    }
+
+    /// Returns the storage trie of `address`. Tries missing from the inputs are only known by
+    /// their root, unless they are empty.
+    fn storage_trie(&mut self, address: Address, storage_root: H256) -> &mut HashedPartialTrie {
+        self.storage_tries
+            .entry(keccak(address.0))
+            .or_insert_with(|| {
+                let empty_trie = HashedPartialTrie::from(Node::Empty);
+                if storage_root == empty_trie.hash() {
+                    empty_trie
+                } else {
+                    HashedPartialTrie::new(Node::Hash(storage_root))
+                }
+            })
+    }
+
+    /// Applies the changes made by a transaction, deleting touched empty accounts as per EIP-161.
+    fn commit(&mut self, changes: State) -> Result<()> {
+        for (address, account) in changes {
+            let address = from_revm_address(address);
+            if !account.is_touched() {
+                continue;
+            }
+            if account.is_selfdestructed() || account.is_empty() {
+                self.state_trie.delete(state_key_nibbles(address));
+                self.storage_tries.remove(&keccak(address.0));
+                continue;
+            }
+
+            let previous = self.account(address)?.unwrap_or_default();
+            if account.is_created() {
+                self.storage_tries
+                    .insert(keccak(address.0), HashedPartialTrie::from(Node::Empty));
+            }
+            let storage_trie = self.storage_trie(address, previous.storage_root);
+            for (slot, value) in account.changed_storage_slots() {
+                let key =
+                    Nibbles::from_bytes_be(keccak(slot.to_be_bytes::<32>()).as_bytes()).unwrap();
+                let value = from_revm_u256(value.present_value());
+                if value.is_zero() {
+                    storage_trie.delete(key);
+                } else {
+                    storage_trie.insert(key, rlp::encode(&value).to_vec());
+                }
+            }
+            let storage_root = storage_trie.hash();
+
+            let code_hash = H256(account.info.code_hash.0);
+            if let Some(code) = &account.info.code {
+                self.contract_code
+                    .entry(code_hash)
+                    .or_insert_with(|| code.original_bytes().to_vec());
+            }
+            let account = AccountRlp {
+                nonce: account.info.nonce.into(),
+                balance: from_revm_u256(account.info.balance),
+                storage_root,
+                code_hash,
+            };
+            self.set_account(address, &account);
+        }
+        Ok(())
+    }
+
+    fn add_balance(&mut self, address: Address, amount: U256) -> Result<()> {
+        let mut account = self.account(address)?.unwrap_or_default();
+        account.balance += amount;
+        self.set_account(address, &account);
+        Ok(())
+    }
+}
+
+impl<'a> Database for TrieDb<'a> {
+    type Error = anyhow::Error;
+
+    fn basic(&mut self, address: primitives::Address) -> Result<Option<AccountInfo>> {
+        Ok(self.account(from_revm_address(address))?.map(|account| {
+            // The code is loaded on demand with `code_by_hash`.
+            AccountInfo {
+                balance: to_revm_u256(account.balance),
+                nonce: account.nonce.as_u64(),
+                code_hash: account.code_hash.0.into(),
+                code: None,
+            }
+        }))
+    }
+
+    fn code_by_hash(&mut self, code_hash: primitives::B256) -> Result<Bytecode> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::new());
+        }
+        let code = self
+            .contract_code
+            .get(&H256(code_hash.0))
+            .ok_or_else(|| anyhow!("Unknown contract code {code_hash}"))?;
+        Ok(Bytecode::new_raw(Bytes::copy_from_slice(code)))
+    }
+
+    fn storage(
+        &mut self,
+        address: primitives::Address,
+        index: primitives::U256,
+    ) -> Result<primitives::U256> {
+        let address = from_revm_address(address);
+        let Some(account) = self.account(address)? else {
+            return Ok(primitives::U256::ZERO);
+        };
+        let key = Nibbles::from_bytes_be(keccak(index.to_be_bytes::<32>()).as_bytes()).unwrap();
+        let value = match self.storage_trie(address, account.storage_root).get(key) {
+            Some(bytes) => rlp::decode(bytes).map_err(|err| anyhow!("Invalid slot: {err:?}"))?,
+            None => U256::zero(),
+        };
+        Ok(to_revm_u256(value))
+    }
+
+    fn block_hash(&mut self, number: primitives::U256) -> Result<primitives::B256> {
+        let number = from_revm_u256(number);
+        let hashes = &self.block_hashes.prev_hashes;
+        if number >= self.block_number || self.block_number - number > hashes.len().into() {
+            return Ok(primitives::B256::ZERO);
+        }
+        let index = hashes.len() - (self.block_number - number).as_usize();
+        Ok(hashes[index].0.into())
+    }
+}
+
+fn state_key_nibbles(address: Address) -> Nibbles {
+    Nibbles::from_bytes_be(keccak(address.0).as_bytes()).unwrap()
+}
+
+fn logs_bloom(logs: &[LogOutput]) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+        for topic in &log.topics {
+            bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+        }
+    }
+    bloom
+}
+
+/// Decodes a signed transaction, of type 0, 1 or 2, into its type and revm environment.
+fn decode_txn(signed_txn: &[u8]) -> Result<(u8, TxEnv)> {
+    let (txn_type, payload) = match signed_txn.first() {
+        Some(&txn_type @ (1 | 2)) => (txn_type, &signed_txn[1..]),
+        Some(_) => (0, signed_txn),
+        None => bail!("Empty transaction"),
+    };
+    let rlp = Rlp::new(payload);
+    let field = |i: usize| {
+        rlp.at(i)
+            .map_err(|err| anyhow!("Invalid transaction: {err:?}"))
+    };
+    let u256 = |i: usize| -> Result<U256> {
+        field(i)?
+            .as_val()
+            .map_err(|err| anyhow!("Invalid transaction: {err:?}"))
+    };
+    let bytes = |i: usize| -> Result<Vec<u8>> {
+        field(i)?
+            .data()
+            .map(<[u8]>::to_vec)
+            .map_err(|err| anyhow!("Invalid transaction: {err:?}"))
+    };
+
+    // The index of the nonce, and the number of signed fields.
+    let (offset, num_signed_fields) = match txn_type {
+        0 => (0, 6),
+        1 => (1, 8),
+        _ => (1, 9),
+    };
+    let mut tx_env = TxEnv {
+        nonce: Some(u256(offset)?.as_u64()),
+        ..Default::default()
+    };
+    // Type 2 transactions have a priority fee and a maximum fee instead of a gas price.
+    let fee_offset = (txn_type == 2) as usize;
+    tx_env.gas_price = to_revm_u256(u256(offset + 1 + fee_offset)?);
+    if txn_type == 2 {
+        tx_env.gas_priority_fee = Some(to_revm_u256(u256(offset + 1)?));
+    }
+    let offset = offset + fee_offset;
+    tx_env.gas_limit = u256(offset + 2)?.as_u64();
+    let to = bytes(offset + 3)?;
+    tx_env.transact_to = match to.len() {
+        0 => TransactTo::create(),
+        20 => TransactTo::Call(primitives::Address::from_slice(&to)),
+        _ => bail!("Invalid transaction recipient"),
+    };
+    tx_env.value = to_revm_u256(u256(offset + 4)?);
+    tx_env.data = bytes(offset + 5)?.into();
+    if txn_type != 0 {
+        tx_env.chain_id = Some(u256(0)?.as_u64());
+        tx_env.access_list = field(offset + 6)?
+            .iter()
+            .map(|entry| {
+                let address: Address = entry.val_at(0)?;
+                let keys: Vec<H256> = entry.list_at(1)?;
+                Ok((
+                    to_revm_address(address),
+                    keys.into_iter()
+                        .map(|key| primitives::U256::from_be_bytes(key.0))
+                        .collect(),
+                ))
+            })
+            .collect::<Result<_, rlp::DecoderError>>()
+            .map_err(|err| anyhow!("Invalid access list: {err:?}"))?;
+    }
+
+    let v = u256(num_signed_fields)?;
+    let r = u256(num_signed_fields + 1)?;
+    let s = u256(num_signed_fields + 2)?;
+    let mut signed_payload = rlp::RlpStream::new_list(match (txn_type, v.as_u64()) {
+        (0, 27 | 28) => num_signed_fields,
+        (0, _) => num_signed_fields + 3,
+        _ => num_signed_fields,
+    });
+    for i in 0..num_signed_fields {
+        signed_payload.append_raw(field(i)?.as_raw(), 1);
+    }
+    let recovery_id = match (txn_type, v.as_u64()) {
+        (0, v @ (27 | 28)) => v - 27,
+        (0, v) => {
+            // EIP-155 transactions also sign their chain ID.
+            let chain_id = (v - 35) / 2;
+            tx_env.chain_id = Some(chain_id);
+            signed_payload.append(&chain_id).append(&0u8).append(&0u8);
+            (v - 35) % 2
+        }
+        (_, v) => v,
+    };
+    let mut signing_bytes = signed_payload.out().to_vec();
+    if txn_type != 0 {
+        signing_bytes.insert(0, txn_type);
+    }
+    let sender = recover_sender(keccak(signing_bytes), r, s, recovery_id as u8)?;
+    tx_env.caller = to_revm_address(sender);
+
+    Ok((txn_type, tx_env))
+}
+
+fn recover_sender(hash: H256, r: U256, s: U256, recovery_id: u8) -> Result<Address> {
+    let mut r_bytes = [0; 32];
+    let mut s_bytes = [0; 32];
+    r.to_big_endian(&mut r_bytes);
+    s.to_big_endian(&mut s_bytes);
+    let signature = Signature::from_scalars(r_bytes, s_bytes)
+        .map_err(|err| anyhow!("Invalid signature: {err}"))?;
+    let recovery_id =
+        RecoveryId::from_byte(recovery_id).ok_or_else(|| anyhow!("Invalid recovery ID"))?;
+    let key = VerifyingKey::recover_from_prehash(hash.as_bytes(), &signature, recovery_id)
+        .map_err(|err| anyhow!("Invalid signature: {err}"))?;
+    let public_key = key.to_encoded_point(false);
+    Ok(Address::from(keccak(&public_key.as_bytes()[1..])))
+}
+
+fn to_revm_u256(x: U256) -> primitives::U256 {
+    primitives::U256::from_limbs(x.0)
+}
+
+fn from_revm_u256(x: primitives::U256) -> U256 {
+    U256(x.into_limbs())
+}
+
+fn to_revm_address(address: Address) -> primitives::Address {
+    address.0.into()
+}
+
+fn from_revm_address(address: primitives::Address) -> Address {
+    Address::from(address.0 .0)
+}
@@ -0,0 +1,208 @@
+//! A log of all the memory operations of an execution, for auditing and debugging.
+//!
+//! The log is stored by columns, one entry per operation in the order they were performed, and
+//! can be exported to a compact binary format. It allows tracing how a value was written to and
+//! read from memory, e.g. to find where a bad state value first appeared.
+
+use ethereum_types::U256;
+use plonky2::util::serialization::{Buffer, IoError, IoResult, Read, Write};
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::membus::NUM_CHANNELS;
+use crate::witness::memory::{MemoryOp, MemoryOpKind};
+
+/// A memory operation of a `MemoryLog`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemoryLogEntry {
+    pub context: u32,
+    pub segment: u8,
+    pub virt: u32,
+    pub value: U256,
+    pub timestamp: usize,
+    pub is_write: bool,
+}
+
+impl MemoryLogEntry {
+    /// The row of the CPU trace which performed this operation.
+    pub fn cpu_row(&self) -> usize {
+        self.timestamp / NUM_CHANNELS
+    }
+
+    /// The memory channel of the CPU used for this operation, `0` being the code channel.
+    pub fn channel(&self) -> usize {
+        self.timestamp % NUM_CHANNELS
+    }
+}
+
+/// The memory operations of an execution, in the order they were performed.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MemoryLog {
+    pub context: Vec<u32>,
+    pub segment: Vec<u8>,
+    pub virt: Vec<u32>,
+    pub value: Vec<U256>,
+    pub timestamp: Vec<usize>,
+    pub is_write: Vec<bool>,
+}
+
+impl MemoryLog {
+    pub(crate) fn new(ops: &[MemoryOp]) -> Self {
+        let mut log = Self::default();
+        for op in ops.iter().filter(|op| op.filter) {
+            log.context.push(op.address.context as u32);
+            log.segment.push(op.address.segment as u8);
+            log.virt.push(op.address.virt as u32);
+            log.value.push(op.value);
+            log.timestamp.push(op.timestamp);
+            log.is_write.push(op.kind == MemoryOpKind::Write);
+        }
+        log
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamp.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> MemoryLogEntry {
+        MemoryLogEntry {
+            context: self.context[i],
+            segment: self.segment[i],
+            virt: self.virt[i],
+            value: self.value[i],
+            timestamp: self.timestamp[i],
+            is_write: self.is_write[i],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = MemoryLogEntry> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// Returns the operations on the given address, in the order they were performed.
+    pub fn history(
+        &self,
+        context: u32,
+        segment: u8,
+        virt: u32,
+    ) -> impl Iterator<Item = MemoryLogEntry> + '_ {
+        self.iter().filter(move |entry| {
+            entry.context == context && entry.segment == segment && entry.virt == virt
+        })
+    }
+
+    /// Serializes the log column by column: the number of operations, then each column with
+    /// fixed-size little-endian values, and the read/write flags packed as bits.
+    pub fn to_bytes(&self) -> IoResult<Vec<u8>> {
+        let mut buffer = Vec::new();
+        buffer.write_usize(self.len())?;
+        for &context in &self.context {
+            buffer.write_u32(context)?;
+        }
+        buffer.write_all(&self.segment)?;
+        for &virt in &self.virt {
+            buffer.write_u32(virt)?;
+        }
+        for value in &self.value {
+            let mut bytes = [0; 32];
+            value.to_little_endian(&mut bytes);
+            buffer.write_all(&bytes)?;
+        }
+        for &timestamp in &self.timestamp {
+            buffer.write_usize(timestamp)?;
+        }
+        for flags in self.is_write.chunks(8) {
+            let byte = flags
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, &flag)| acc | ((flag as u8) << i));
+            buffer.write_u8(byte)?;
+        }
+        Ok(buffer)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> IoResult<Self> {
+        let mut buffer = Buffer::new(bytes);
+        let len = buffer.read_usize()?;
+        let context = (0..len)
+            .map(|_| buffer.read_u32())
+            .collect::<IoResult<_>>()?;
+        let segment = (0..len)
+            .map(|_| buffer.read_u8())
+            .collect::<IoResult<_>>()?;
+        let virt = (0..len)
+            .map(|_| buffer.read_u32())
+            .collect::<IoResult<_>>()?;
+        let value = (0..len)
+            .map(|_| {
+                let mut bytes = [0; 32];
+                buffer.read_exact(&mut bytes)?;
+                Ok(U256::from_little_endian(&bytes))
+            })
+            .collect::<IoResult<_>>()?;
+        let timestamp = (0..len)
+            .map(|_| buffer.read_usize())
+            .collect::<IoResult<_>>()?;
+        let mut is_write = Vec::with_capacity(len);
+        for _ in 0..len.div_ceil(8) {
+            let byte = buffer.read_u8()?;
+            is_write.extend((0..8).map(|i| (byte >> i) & 1 == 1));
+        }
+        is_write.truncate(len);
+        if !buffer.unread_bytes().is_empty() {
+            return Err(IoError::TrailingBytes);
+        }
+
+        Ok(Self {
+            context,
+            segment,
+            virt,
+            value,
+            timestamp,
+            is_write,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::segments::Segment;
+    use crate::witness::memory::{MemoryAddress, MemoryChannel};
+
+    #[test]
+    fn test_roundtrip() {
+        let address = MemoryAddress::new(1, Segment::MainMemory, 7);
+        let ops = (0..11)
+            .map(|clock| {
+                let kind = if clock % 3 == 0 {
+                    MemoryOpKind::Write
+                } else {
+                    MemoryOpKind::Read
+                };
+                let channel = MemoryChannel::GeneralPurpose(clock % 2);
+                MemoryOp::new(channel, clock, address, kind, U256::from(clock / 3))
+            })
+            .collect::<Vec<_>>();
+        let log = MemoryLog::new(&ops);
+        assert_eq!(log.len(), 11);
+
+        let entry = log.get(4);
+        assert_eq!(entry.cpu_row(), 4);
+        assert_eq!(entry.channel(), 1);
+        assert!(!entry.is_write);
+        assert_eq!(
+            log.history(1, Segment::MainMemory as u8, 7)
+                .filter(|entry| entry.is_write)
+                .count(),
+            4
+        );
+
+        let bytes = log.to_bytes().unwrap();
+        assert_eq!(MemoryLog::from_bytes(&bytes).unwrap(), log);
+        assert!(MemoryLog::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -20,19 +20,32 @@ use crate::cpu::bootstrap_kernel::generate_bootstrap_kernel;
 use crate::cpu::columns::CpuColumnsView;
 use crate::cpu::kernel::aggregator::KERNEL;
 use crate::cpu::kernel::constants::global_metadata::GlobalMetadata;
+use crate::generation::memory_log::MemoryLog;
 use crate::generation::outputs::{get_outputs, GenerationOutputs};
 use crate::generation::state::GenerationState;
+use crate::generation::trace_limits::check_trace_lengths;
 use crate::memory::segments::Segment;
-use crate::proof::{BlockHashes, BlockMetadata, ExtraBlockData, PublicValues, TrieRoots};
+use crate::proof::{
+    BlockHashes, BlockMetadata, ChainConstants, ExtraBlockData, ExtraPublicValues, PublicValues,
+    TrieRoots,
+};
+use crate::prover::ProverOptions;
 use crate::util::h2u;
 use crate::witness::memory::{MemoryAddress, MemoryChannel};
 use crate::witness::transition::transition;
 
+pub mod batch;
+pub mod block_trace;
+#[cfg(feature = "differential")]
+pub mod differential;
+pub mod memory_log;
 pub mod mpt;
 pub mod outputs;
 pub(crate) mod prover_input;
 pub(crate) mod rlp;
+pub(crate) mod simulation;
 pub(crate) mod state;
+pub mod trace_limits;
 mod trie_extractor;
 
 use crate::witness::util::mem_write_log;
@@ -63,6 +76,16 @@ pub struct GenerationInputs {
 
     pub block_hashes: BlockHashes,
 
+    /// The values of the extra public value slots declared by the chain before the transactions
+    /// are executed, and their expected values after.
+    #[serde(default)]
+    pub extra_public_values: ExtraPublicValues,
+
+    /// The constants of the chain which differ from their Ethereum values, e.g. its recipient of
+    /// base fees. Left empty for Ethereum.
+    #[serde(default)]
+    pub chain_constants: ChainConstants,
+
     /// A list of known addresses in the input state trie (which itself doesn't hold addresses,
     /// only state keys). This is only useful for debugging, so that we can return addresses in the
     /// post-state rather than state keys. (See `GenerationOutputs`, and in particular
@@ -90,7 +113,7 @@ pub struct TrieInputs {
     pub storage_tries: Vec<(H256, HashedPartialTrie)>,
 }
 
-fn apply_metadata_and_tries_memops<F: RichField + Extendable<D>, const D: usize>(
+pub(crate) fn apply_metadata_and_tries_memops<F: RichField + Extendable<D>, const D: usize>(
     state: &mut GenerationState<F>,
     inputs: &GenerationInputs,
 ) {
@@ -210,11 +233,69 @@ fn apply_metadata_and_tries_memops<F: RichField + Extendable<D>, const D: usize>
             })
             .collect::<Vec<_>>(),
     );
+    // Write the presence flags and values of the block metadata extensions.
+    let extensions = &metadata.extensions.fields;
+    ops.extend(
+        extensions
+            .iter()
+            .map(|x| U256::from(x.is_some() as u8))
+            .chain(extensions.iter().map(|x| x.unwrap_or_default()))
+            .enumerate()
+            .map(|(i, val)| {
+                mem_write_log(
+                    channel,
+                    MemoryAddress::new(0, Segment::BlockMetadataExtensions, i),
+                    state,
+                    val,
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+    // Write the extra public values before the transactions, followed by their expected values
+    // after them.
+    let extra_public_values = &inputs.extra_public_values;
+    ops.extend(
+        extra_public_values
+            .before()
+            .into_iter()
+            .chain(extra_public_values.after())
+            .enumerate()
+            .map(|(i, val)| {
+                mem_write_log(
+                    channel,
+                    MemoryAddress::new(0, Segment::ExtraPublicValues, i),
+                    state,
+                    val,
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+    // Write the value of each chain constant.
+    ops.extend(
+        inputs
+            .chain_constants
+            .values()
+            .into_iter()
+            .enumerate()
+            .map(|(i, val)| {
+                mem_write_log(
+                    channel,
+                    MemoryAddress::new(0, Segment::ChainConstants, i),
+                    state,
+                    val,
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
 
     state.memory.apply_ops(&ops);
     state.traces.memory_ops.extend(ops);
 }
 
+/// Generates the traces of all tables, failing with a [`TraceOverflow`] if one is too long to be
+/// proven with `config`, see [`StarkConfig::max_degree_bits`].
+///
+/// [`TraceOverflow`]: trace_limits::TraceOverflow
 pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
     all_stark: &AllStark<F, D>,
     inputs: GenerationInputs,
@@ -224,6 +305,35 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
     [Vec<PolynomialValues<F>>; NUM_TABLES],
     PublicValues,
     GenerationOutputs,
+)> {
+    let max_degree_bits = [config.max_degree_bits::<F>(); NUM_TABLES];
+    generate_traces_with_limits(
+        all_stark,
+        inputs,
+        config,
+        &max_degree_bits,
+        &ProverOptions::default(),
+        timing,
+    )
+}
+
+/// Generates the traces of all tables, failing with a [`TraceOverflow`] as soon as the CPU halts
+/// if the trace of a table has more than `2^max_degree_bits[table]` rows, before the traces are
+/// built. The error can be recovered with `anyhow::Error::downcast_ref`, e.g. to retry with
+/// fewer transactions. The memory log is recorded if `options.record_memory_log` is set.
+///
+/// [`TraceOverflow`]: trace_limits::TraceOverflow
+pub fn generate_traces_with_limits<F: RichField + Extendable<D>, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    inputs: GenerationInputs,
+    config: &StarkConfig,
+    max_degree_bits: &[usize; NUM_TABLES],
+    options: &ProverOptions,
+    timing: &mut TimingTree,
+) -> anyhow::Result<(
+    [Vec<PolynomialValues<F>>; NUM_TABLES],
+    PublicValues,
+    GenerationOutputs,
 )> {
     let mut state = GenerationState::<F>::new(inputs.clone(), &KERNEL.code)
         .map_err(|err| anyhow!("Failed to parse all the initial prover inputs: {:?}", err))?;
@@ -243,9 +353,13 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
         "Trace lengths (before padding): {:?}",
         state.traces.get_lengths()
     );
+    check_trace_lengths(&state.traces.table_lengths(), max_degree_bits)?;
 
-    let outputs = get_outputs(&mut state)
+    let mut outputs = get_outputs(&mut state)
         .map_err(|err| anyhow!("Failed to generate post-state info: {:?}", err))?;
+    if options.record_memory_log {
+        outputs.memory_log = Some(MemoryLog::new(&state.traces.memory_ops));
+    }
 
     let read_metadata = |field| state.memory.read_global_metadata(field);
     let trie_roots_before = TrieRoots {
@@ -278,6 +392,8 @@ pub fn generate_traces<F: RichField + Extendable<D>, const D: usize>(
         block_metadata: inputs.block_metadata,
         block_hashes: inputs.block_hashes,
         extra_block_data,
+        extra_public_values: inputs.extra_public_values,
+        chain_constants: inputs.chain_constants,
     };
 
     let tables = timed!(
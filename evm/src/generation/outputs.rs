@@ -4,6 +4,7 @@ use ethereum_types::{Address, BigEndianHash, H256, U256};
 use plonky2::field::types::Field;
 
 use crate::cpu::kernel::constants::global_metadata::GlobalMetadata::StateTrieRoot;
+use crate::generation::memory_log::MemoryLog;
 use crate::generation::state::GenerationState;
 use crate::generation::trie_extractor::{
     read_state_trie_value, read_storage_trie_value, read_trie, AccountTrieRecord,
@@ -15,6 +16,8 @@ use crate::witness::errors::ProgramError;
 #[derive(Clone, Debug)]
 pub struct GenerationOutputs {
     pub accounts: HashMap<AddressOrStateKey, AccountOutput>,
+    /// The log of all memory operations, if `ProverOptions::record_memory_log` is set.
+    pub memory_log: Option<MemoryLog>,
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
@@ -61,7 +64,10 @@ pub(crate) fn get_outputs<F: Field>(
         accounts.insert(addr_or_state_key, account_output);
     }
 
-    Ok(GenerationOutputs { accounts })
+    Ok(GenerationOutputs {
+        accounts,
+        memory_log: None,
+    })
 }
 
 fn account_trie_record_to_output<F: Field>(
@@ -0,0 +1,139 @@
+//! Runs the kernel up to its final checks, so that the values it computed for a block can be read
+//! rather than asserted against the expected ones.
+
+use anyhow::{anyhow, bail, Result};
+use eth_trie_utils::nibbles::Nibbles;
+use ethereum_types::{BigEndianHash, H256, U256};
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+
+use crate::cpu::bootstrap_kernel::generate_bootstrap_kernel;
+use crate::cpu::kernel::aggregator::KERNEL;
+use crate::cpu::kernel::constants::global_metadata::GlobalMetadata;
+use crate::generation::mpt::LegacyReceiptRlp;
+use crate::generation::state::GenerationState;
+use crate::generation::trie_extractor::{read_receipt_trie_value, read_trie};
+use crate::generation::{apply_metadata_and_tries_memops, GenerationInputs};
+use crate::memory::segments::Segment;
+use crate::proof::TrieRoots;
+use crate::util::u256_to_usize;
+use crate::witness::memory::MemoryAddress;
+use crate::witness::transition::transition;
+use crate::witness::util::{push_no_write, stack_peek};
+
+/// The values the kernel computed for a block, which it would otherwise check against the ones in
+/// `GenerationInputs`.
+#[derive(Clone, Debug)]
+pub(crate) struct FinalValues {
+    pub(crate) gas_used: U256,
+    pub(crate) block_bloom: [U256; 8],
+    /// The receipts of the block's transactions, in order.
+    pub(crate) receipts: Vec<LegacyReceiptRlp>,
+    pub(crate) trie_roots: TrieRoots,
+}
+
+/// Creates a `GenerationState` for `inputs`, with the kernel bootstrapped.
+pub(crate) fn initialize_state<F: RichField + Extendable<D>, const D: usize>(
+    inputs: &GenerationInputs,
+) -> Result<GenerationState<F>> {
+    let mut state = GenerationState::<F>::new(inputs.clone(), &KERNEL.code)
+        .map_err(|err| anyhow!("Failed to parse all the initial prover inputs: {:?}", err))?;
+    apply_metadata_and_tries_memops::<F, D>(&mut state, inputs);
+    generate_bootstrap_kernel::<F>(&mut state);
+    Ok(state)
+}
+
+/// Runs the kernel until it is about to check and hash the final tries, calling `observe` before
+/// each transition.
+pub(crate) fn simulate_until_final_tries<F: Field>(
+    state: &mut GenerationState<F>,
+    mut observe: impl FnMut(&GenerationState<F>),
+) -> Result<()> {
+    let hash_final_tries = KERNEL.global_labels["hash_final_tries"];
+    let halt = KERNEL.global_labels["halt"];
+    loop {
+        if state.registers.is_kernel {
+            let pc = state.registers.program_counter;
+            if pc == hash_final_tries {
+                return Ok(());
+            } else if pc == halt {
+                bail!("Halted before hashing the final tries");
+            }
+        }
+        observe(state);
+        transition(state)?;
+    }
+}
+
+/// Reads the values computed by the kernel, which must have been run with
+/// `simulate_until_final_tries`.
+pub(crate) fn read_final_values<F: Field>(state: &mut GenerationState<F>) -> Result<FinalValues> {
+    // stack: cum_gas, txn_counter, num_nibbles, txn_nb
+    let gas_used = stack_peek(state, 0).map_err(|err| anyhow!("{:?}", err))?;
+    let bloom_bytes: Vec<u8> = (0..256)
+        .map(|i| {
+            let address = MemoryAddress::new(0, Segment::BlockBloom, i);
+            state.memory.get(address).low_u32() as u8
+        })
+        .collect();
+    let block_bloom = core::array::from_fn(|i| U256::from_big_endian(&bloom_bytes[32 * i..][..32]));
+    let receipts = read_receipts(state)?;
+
+    let mut hash = |label| call_kernel_function(state, label).map(|root| H256::from_uint(&root));
+    let trie_roots = TrieRoots {
+        state_root: hash("mpt_hash_state_trie")?,
+        transactions_root: hash("mpt_hash_txn_trie")?,
+        receipts_root: hash("mpt_hash_receipt_trie")?,
+    };
+
+    Ok(FinalValues {
+        gas_used,
+        block_bloom,
+        receipts,
+        trie_roots,
+    })
+}
+
+/// Calls the kernel function at `label`, which takes only a return address, and returns the
+/// value it returns.
+fn call_kernel_function<F: Field>(state: &mut GenerationState<F>, label: &str) -> Result<U256> {
+    let halt = KERNEL.global_labels["halt"];
+    if state.registers.stack_len > 0 {
+        let top_address = MemoryAddress::new(
+            state.registers.context,
+            Segment::Stack,
+            state.registers.stack_len - 1,
+        );
+        state.memory.set(top_address, state.registers.stack_top);
+    }
+    push_no_write(state, halt.into());
+    state
+        .jump_to(KERNEL.global_labels[label])
+        .map_err(|err| anyhow!("{:?}", err))?;
+    while state.registers.program_counter != halt {
+        transition(state)?;
+    }
+    stack_peek(state, 0).map_err(|err| anyhow!("{:?}", err))
+}
+
+fn read_receipts<F: Field>(state: &GenerationState<F>) -> Result<Vec<LegacyReceiptRlp>> {
+    let ptr = u256_to_usize(
+        state
+            .memory
+            .read_global_metadata(GlobalMetadata::ReceiptTrieRoot),
+    )
+    .map_err(|err| anyhow!("{:?}", err))?;
+    let receipts = read_trie(&state.memory, ptr, read_receipt_trie_value)
+        .map_err(|err| anyhow!("Failed to read the receipt trie: {:?}", err))?;
+    (0..state.inputs.signed_txns.len())
+        .map(|i| {
+            let txn_number = state.inputs.txn_number_before + i;
+            let key = Nibbles::from_bytes_be(&rlp::encode(&txn_number)).unwrap();
+            let (_, receipt) = receipts
+                .get(&key)
+                .ok_or_else(|| anyhow!("No receipt for transaction {txn_number}"))?;
+            Ok(receipt.clone())
+        })
+        .collect()
+}
@@ -0,0 +1,140 @@
+//! Limits on the heights of the traces, checked at the end of generation.
+//!
+//! A table with more rows than can be proven otherwise only fails once its trace is committed to,
+//! e.g. in an FFT larger than the field supports, or for lack of recursive circuits of its size.
+//! Checking the heights as soon as the CPU halts instead rejects the batch before any commitment
+//! work, and reports every overflowing table along with how much it overflows by.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::all_stark::{Table, NUM_TABLES};
+
+/// A table whose trace has more rows than allowed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TableOverflow {
+    pub table: Table,
+    /// The number of rows of the trace, before padding. For the memory table, this is a lower
+    /// bound, as gaps between consecutive addresses are only filled when building the trace.
+    pub rows: usize,
+    pub max_rows: usize,
+}
+
+impl TableOverflow {
+    /// The number of rows over the maximum.
+    pub fn excess(&self) -> usize {
+        self.rows - self.max_rows
+    }
+}
+
+/// The traces of a batch of transactions have more rows than allowed. Splitting a batch into
+/// segments isn't supported, so the transactions must be proven in smaller batches, or with
+/// circuits supporting larger traces.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceOverflow {
+    /// The overflowing tables, in the order of [`Table`].
+    pub overflows: Vec<TableOverflow>,
+}
+
+impl Display for TraceOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "traces have too many rows:")?;
+        for overflow in &self.overflows {
+            write!(
+                f,
+                " {:?} table has {} rows, {} over its maximum of {};",
+                overflow.table,
+                overflow.rows,
+                overflow.excess(),
+                overflow.max_rows
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TraceOverflow {}
+
+/// A table whose trace size has no recursive circuits.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnsupportedTableSize {
+    pub table: Table,
+    /// The size of the trace, as `log_2(height)`.
+    pub degree_bits: usize,
+    /// The sizes for which the table has recursive circuits.
+    pub supported_degree_bits: Vec<usize>,
+}
+
+/// The traces of a batch of transactions have sizes for which the recursive circuits can't shrink
+/// the table proofs. Splitting a batch into segments isn't supported, so callers must prove the
+/// transactions in smaller batches, or build the circuits with wider degree ranges.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnsupportedTraceSizes {
+    /// The unsupported tables, in the order of [`Table`].
+    pub tables: Vec<UnsupportedTableSize>,
+}
+
+impl Display for UnsupportedTraceSizes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "missing recursive circuits for the trace sizes:")?;
+        for table in &self.tables {
+            write!(
+                f,
+                " {:?} table of degree {} (supported degrees: {:?});",
+                table.table, table.degree_bits, table.supported_degree_bits
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnsupportedTraceSizes {}
+
+/// Checks that the trace of each table, of `lengths[table]` rows, has at most
+/// `2^max_degree_bits[table]` rows.
+pub fn check_trace_lengths(
+    lengths: &[usize; NUM_TABLES],
+    max_degree_bits: &[usize; NUM_TABLES],
+) -> Result<(), TraceOverflow> {
+    let overflows = Table::all()
+        .into_iter()
+        .zip(lengths.iter().zip(max_degree_bits))
+        .map(|(table, (&rows, &max_bits))| TableOverflow {
+            table,
+            rows,
+            max_rows: 1 << max_bits,
+        })
+        .filter(|overflow| overflow.rows > overflow.max_rows)
+        .collect::<Vec<_>>();
+    if overflows.is_empty() {
+        Ok(())
+    } else {
+        Err(TraceOverflow { overflows })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_trace_lengths() {
+        let max_degree_bits = [20, 16, 22, 18, 16, 16, 24];
+        let mut lengths = max_degree_bits.map(|bits| 1 << bits);
+        assert_eq!(check_trace_lengths(&lengths, &max_degree_bits), Ok(()));
+
+        lengths[Table::Cpu as usize] += 1000;
+        lengths[Table::Memory as usize] *= 2;
+        let overflow = check_trace_lengths(&lengths, &max_degree_bits).unwrap_err();
+        assert_eq!(
+            overflow
+                .overflows
+                .iter()
+                .map(|overflow| (overflow.table, overflow.excess()))
+                .collect::<Vec<_>>(),
+            vec![(Table::Cpu, 1000), (Table::Memory, 1 << 24)]
+        );
+        assert!(overflow
+            .to_string()
+            .contains("Cpu table has 4195304 rows, 1000 over its maximum of 4194304"));
+    }
+}
@@ -3,9 +3,11 @@
 use std::collections::HashMap;
 
 use eth_trie_utils::nibbles::Nibbles;
-use ethereum_types::{BigEndianHash, H256, U256, U512};
+use ethereum_types::{Address, BigEndianHash, H256, U256, U512};
+use itertools::Itertools;
 
 use crate::cpu::kernel::constants::trie_type::PartialTrieType;
+use crate::generation::mpt::{LegacyReceiptRlp, LogRlp};
 use crate::memory::segments::Segment;
 use crate::util::u256_to_usize;
 use crate::witness::errors::ProgramError;
@@ -33,6 +35,57 @@ pub(crate) fn read_storage_trie_value(slice: &[U256]) -> U256 {
     slice[0]
 }
 
+/// Reads a receipt, as written by `process_receipt`, along with its transaction type if it isn't
+/// a legacy one.
+pub(crate) fn read_receipt_trie_value(
+    slice: &[U256],
+) -> Result<(Option<u8>, LegacyReceiptRlp), ProgramError> {
+    // A receipt's payload is longer than a bloom filter, so a first word of 1 or 2 is a type.
+    let (txn_type, slice) = match slice[0].low_u32() {
+        t @ (1 | 2) => (Some(t as u8), &slice[1..]),
+        _ => (None, slice),
+    };
+    let status = !slice[1].is_zero();
+    let cum_gas_used = slice[2];
+    let bloom = slice[3..259]
+        .iter()
+        .map(|b| b.low_u32() as u8)
+        .collect_vec();
+    let num_logs = u256_to_usize(slice[260])?;
+
+    let mut logs = Vec::with_capacity(num_logs);
+    let mut ptr = 261;
+    for _ in 0..num_logs {
+        // Skip the log's payload length.
+        let address = Address::from(H256::from_uint(&slice[ptr + 1]));
+        let num_topics = u256_to_usize(slice[ptr + 2])?;
+        let topics = slice[ptr + 3..ptr + 3 + num_topics]
+            .iter()
+            .map(H256::from_uint)
+            .collect();
+        ptr += 3 + num_topics;
+        let data_len = u256_to_usize(slice[ptr])?;
+        let data = slice[ptr + 1..ptr + 1 + data_len]
+            .iter()
+            .map(|b| b.low_u32() as u8)
+            .collect_vec();
+        ptr += 1 + data_len;
+        logs.push(LogRlp {
+            address,
+            topics,
+            data: data.into(),
+        });
+    }
+
+    let receipt = LegacyReceiptRlp {
+        status,
+        cum_gas_used,
+        bloom: bloom.into(),
+        logs,
+    };
+    Ok((txn_type, receipt))
+}
+
 pub(crate) fn read_trie<V>(
     memory: &MemoryState,
     ptr: usize,
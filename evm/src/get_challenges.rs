@@ -6,6 +6,7 @@ use plonky2::iop::challenger::{Challenger, RecursiveChallenger};
 use plonky2::plonk::circuit_builder::CircuitBuilder;
 use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
 
+use crate::all_stark::Table;
 use crate::config::StarkConfig;
 use crate::cross_table_lookup::get_grand_product_challenge_set;
 use crate::proof::*;
@@ -174,6 +175,97 @@ fn observe_block_hashes_target<
     challenger.observe_elements(&block_hashes.cur_hash);
 }
 
+fn observe_block_metadata_extensions<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut Challenger<F, C::Hasher>,
+    extensions: &BlockMetadataExtensions,
+) {
+    for field in extensions.fields {
+        challenger.observe_element(F::from_bool(field.is_some()));
+    }
+    for field in extensions.fields {
+        challenger.observe_elements(&u256_limbs::<F>(field.unwrap_or_default()));
+    }
+}
+
+fn observe_block_metadata_extensions_target<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
+    extensions: &BlockMetadataExtensionsTarget,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    challenger.observe_elements(&extensions.present);
+    challenger.observe_elements(&extensions.values);
+}
+
+fn observe_extra_public_values<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut Challenger<F, C::Hasher>,
+    extra: &ExtraPublicValues,
+) {
+    for slot in extra.slots {
+        challenger.observe_element(F::from_bool(slot.is_some()));
+    }
+    for value in extra.before().into_iter().chain(extra.after()) {
+        challenger.observe_elements(&u256_limbs::<F>(value));
+    }
+}
+
+fn observe_extra_public_values_target<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
+    extra: &ExtraPublicValuesTarget,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    challenger.observe_elements(&extra.declared);
+    challenger.observe_elements(&extra.before);
+    challenger.observe_elements(&extra.after);
+}
+
+fn observe_chain_constants<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut Challenger<F, C::Hasher>,
+    constants: &ChainConstants,
+) {
+    for value in constants.overrides {
+        challenger.observe_element(F::from_bool(value.is_some()));
+    }
+    for value in constants.overrides {
+        challenger.observe_elements(&u256_limbs::<F>(value.unwrap_or_default()));
+    }
+}
+
+fn observe_chain_constants_target<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
+    constants: &ChainConstantsTarget,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    challenger.observe_elements(&constants.overridden);
+    challenger.observe_elements(&constants.values);
+}
+
 pub(crate) fn observe_public_values<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -182,11 +274,26 @@ pub(crate) fn observe_public_values<
     challenger: &mut Challenger<F, C::Hasher>,
     public_values: &PublicValues,
 ) -> Result<(), ProgramError> {
+    challenger.set_transcript_label("trie roots before");
     observe_trie_roots::<F, C, D>(challenger, &public_values.trie_roots_before);
+    challenger.set_transcript_label("trie roots after");
     observe_trie_roots::<F, C, D>(challenger, &public_values.trie_roots_after);
+    challenger.set_transcript_label("block metadata");
     observe_block_metadata::<F, C, D>(challenger, &public_values.block_metadata)?;
+    challenger.set_transcript_label("block hashes");
     observe_block_hashes::<F, C, D>(challenger, &public_values.block_hashes);
-    observe_extra_block_data::<F, C, D>(challenger, &public_values.extra_block_data)
+    challenger.set_transcript_label("extra block data");
+    observe_extra_block_data::<F, C, D>(challenger, &public_values.extra_block_data)?;
+    challenger.set_transcript_label("block metadata extensions");
+    observe_block_metadata_extensions::<F, C, D>(
+        challenger,
+        &public_values.block_metadata.extensions,
+    );
+    challenger.set_transcript_label("extra public values");
+    observe_extra_public_values::<F, C, D>(challenger, &public_values.extra_public_values);
+    challenger.set_transcript_label("chain constants");
+    observe_chain_constants::<F, C, D>(challenger, &public_values.chain_constants);
+    Ok(())
 }
 
 pub(crate) fn observe_public_values_target<
@@ -204,31 +311,39 @@ pub(crate) fn observe_public_values_target<
     observe_block_metadata_target::<F, C, D>(challenger, &public_values.block_metadata);
     observe_block_hashes_target::<F, C, D>(challenger, &public_values.block_hashes);
     observe_extra_block_data_target::<F, C, D>(challenger, &public_values.extra_block_data);
+    observe_block_metadata_extensions_target::<F, C, D>(
+        challenger,
+        &public_values.block_metadata_extensions,
+    );
+    observe_extra_public_values_target::<F, C, D>(challenger, &public_values.extra_public_values);
+    observe_chain_constants_target::<F, C, D>(challenger, &public_values.chain_constants);
 }
 
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> AllProof<F, C, D> {
-    /// Computes all Fiat-Shamir challenges used in the STARK proof.
+    /// Computes all Fiat-Shamir challenges used in the STARK proof, with the given challenger,
+    /// which must be fresh.
     pub(crate) fn get_challenges(
         &self,
+        challenger: &mut Challenger<F, C::Hasher>,
         config: &StarkConfig,
     ) -> Result<AllProofChallenges<F, D>, ProgramError> {
-        let mut challenger = Challenger::<F, C::Hasher>::new();
-
+        challenger.set_transcript_label("trace caps");
         for proof in &self.stark_proofs {
             challenger.observe_cap(&proof.proof.trace_cap);
         }
 
-        observe_public_values::<F, C, D>(&mut challenger, &self.public_values)?;
+        observe_public_values::<F, C, D>(challenger, &self.public_values)?;
 
-        let ctl_challenges =
-            get_grand_product_challenge_set(&mut challenger, config.num_challenges);
+        challenger.set_transcript_label("ctl challenges");
+        let ctl_challenges = get_grand_product_challenge_set(challenger, config.num_challenges);
 
         Ok(AllProofChallenges {
             stark_challenges: core::array::from_fn(|i| {
+                challenger.set_transcript_scope(&format!("{:?}", Table::all()[i]));
                 challenger.compact();
                 self.stark_proofs[i]
                     .proof
-                    .get_challenges(&mut challenger, config)
+                    .get_challenges(challenger, config)
             }),
             ctl_challenges,
         })
@@ -264,13 +379,18 @@ where
 
         let num_challenges = config.num_challenges;
 
+        challenger.set_transcript_label("auxiliary polys cap");
         challenger.observe_cap(auxiliary_polys_cap);
 
+        challenger.set_transcript_label("stark alphas");
         let stark_alphas = challenger.get_n_challenges(num_challenges);
 
+        challenger.set_transcript_label("quotient polys cap");
         challenger.observe_cap(quotient_polys_cap);
+        challenger.set_transcript_label("stark zeta");
         let stark_zeta = challenger.get_extension_challenge::<D>();
 
+        challenger.set_transcript_label("openings");
         challenger.observe_openings(&openings.to_fri_openings());
 
         StarkProofChallenges {
@@ -280,7 +400,7 @@ where
                 commit_phase_merkle_caps,
                 final_poly,
                 *pow_witness,
-                degree_bits,
+                config.committed_degree_bits(degree_bits),
                 &config.fri_config,
             ),
         }
@@ -628,6 +628,7 @@ mod tests {
     use plonky2::iop::challenger::Challenger;
     use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use plonky2::timed;
+    use plonky2::util::randomness::ProverRandomness;
     use plonky2::util::timing::TimingTree;
     use tiny_keccak::keccakf;
 
@@ -637,7 +638,8 @@ mod tests {
     };
     use crate::keccak::columns::reg_output_limb;
     use crate::keccak::keccak_stark::{KeccakStark, NUM_INPUTS, NUM_ROUNDS};
-    use crate::prover::prove_single_table;
+    use crate::prover::{compute_auxiliary_polys_commitment, prove_single_table};
+    use crate::stark::Stark;
     use crate::stark_testing::{test_stark_circuit_constraints, test_stark_low_degree};
 
     #[test]
@@ -753,16 +755,29 @@ mod tests {
         let ctl_data = CtlData {
             zs_columns: vec![ctl_z_data.clone(); config.num_challenges],
         };
+        let ctl_challenges = GrandProductChallengeSet {
+            challenges: vec![ctl_z_data.challenge; config.num_challenges],
+        };
+        let auxiliary_polys_commitment = compute_auxiliary_polys_commitment::<F, C, D>(
+            &trace_poly_values,
+            &stark.lookups(),
+            stark.constraint_degree(),
+            &ctl_data,
+            &ctl_challenges,
+            &config,
+            ProverRandomness::Os,
+            &mut timing,
+        );
 
         prove_single_table(
             &stark,
             &config,
             &trace_poly_values,
             &trace_commitments,
+            &auxiliary_polys_commitment,
             &ctl_data,
-            &GrandProductChallengeSet {
-                challenges: vec![ctl_z_data.challenge; config.num_challenges],
-            },
+            &ctl_challenges,
+            ProverRandomness::Os,
             &mut Challenger::new(),
             &mut timing,
         )?;
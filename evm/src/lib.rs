@@ -5,14 +5,19 @@
 #![allow(clippy::field_reassign_with_default)]
 #![feature(let_chains)]
 
+pub mod abi;
 pub mod all_stark;
 pub mod arithmetic;
 pub mod byte_packing;
+pub mod circuit_cache;
 pub mod config;
 pub mod constraint_consumer;
+pub mod coordinator;
+pub mod coverage;
 pub mod cpu;
 pub mod cross_table_lookup;
 pub mod curve_pairings;
+pub mod eest;
 pub mod evaluation_frame;
 pub mod extension_tower;
 pub mod fixed_recursive_verifier;
@@ -24,8 +29,13 @@ pub mod logic;
 pub mod lookup;
 pub mod memory;
 pub mod proof;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
 pub mod prover;
+pub mod prover_tasks;
 pub mod recursive_verifier;
+pub mod sanity_check;
+pub mod security;
 pub mod stark;
 pub mod stark_testing;
 pub mod util;
@@ -9,12 +9,14 @@ use plonky2::hash::hash_types::RichField;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::iop::target::Target;
 use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2_maybe_rayon::*;
 use plonky2_util::ceil_div_usize;
 
 use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
 use crate::evaluation_frame::StarkEvaluationFrame;
 use crate::stark::Stark;
 
+#[derive(Debug)]
 pub struct Lookup {
     /// Columns whose values should be contained in the lookup table.
     /// These are the f_i(x) polynomials in the logUp paper.
@@ -40,6 +42,21 @@ impl Lookup {
 /// Given columns `f0,...,fk` and a column `t`, such that `∪fi ⊆ t`, and challenges `x`,
 /// this computes the helper columns `h_i = 1/(x+f_2i) + 1/(x+f_2i+1)`, `g = 1/(x+t)`,
 /// and `Z(gx) = Z(x) + sum h_i(x) - m(x)g(x)` where `m` is the frequencies column.
+/// The number of values inverted at once by `shifted_inverses`, with a single field inversion.
+const BATCH_INVERSION_CHUNK_SIZE: usize = 1 << 12;
+
+/// Returns `1/(x+challenge)` for every value `x` of the column. The values are split in chunks,
+/// which are batch-inverted in parallel.
+fn shifted_inverses<F: Field>(values: &[F], challenge: F) -> Vec<F> {
+    values
+        .par_chunks(BATCH_INVERSION_CHUNK_SIZE)
+        .flat_map_iter(|chunk| {
+            let shifted = chunk.iter().map(|&x| challenge + x).collect::<Vec<_>>();
+            F::batch_multiplicative_inverse(&shifted)
+        })
+        .collect()
+}
+
 pub(crate) fn lookup_helper_columns<F: Field>(
     lookup: &Lookup,
     trace_poly_values: &[PolynomialValues<F>],
@@ -59,9 +76,6 @@ pub(crate) fn lookup_helper_columns<F: Field>(
 
     // For each batch of `constraint_degree-1` columns `fi`, compute `sum 1/(f_i+challenge)` and
     // add it to the helper columns.
-    // TODO: This does one batch inversion per column. It would also be possible to do one batch inversion
-    // for every group of columns, but that would require building a big vector of all the columns concatenated.
-    // Not sure which approach is better.
     // Note: these are the h_k(x) polynomials in the paper, with a few differences:
     //       * Here, the first ratio m_0(x)/phi_0(x) is not included with the columns batched up to create the
     //         h_k polynomials; instead there's a separate helper column for it (see below).
@@ -69,19 +83,9 @@ pub(crate) fn lookup_helper_columns<F: Field>(
     //       * Here, for now, the batch size (l) is always constraint_degree - 1 = 2.
     for mut col_inds in &lookup.columns.iter().chunks(constraint_degree - 1) {
         let first = *col_inds.next().unwrap();
-        // TODO: The clone could probably be avoided by using a modified version of `batch_multiplicative_inverse`
-        // taking `challenge` as an additional argument.
-        let mut column = trace_poly_values[first].values.clone();
-        for x in column.iter_mut() {
-            *x = challenge + *x;
-        }
-        let mut acc = F::batch_multiplicative_inverse(&column);
+        let mut acc = shifted_inverses(&trace_poly_values[first].values, challenge);
         for &ind in col_inds {
-            let mut column = trace_poly_values[ind].values.clone();
-            for x in column.iter_mut() {
-                *x = challenge + *x;
-            }
-            column = F::batch_multiplicative_inverse(&column);
+            let column = shifted_inverses(&trace_poly_values[ind].values, challenge);
             batch_add_inplace(&mut acc, &column);
         }
         helper_columns.push(acc.into());
@@ -90,25 +94,26 @@ pub(crate) fn lookup_helper_columns<F: Field>(
     // Add `1/(table+challenge)` to the helper columns.
     // This is 1/phi_0(x) = 1/(x + t(x)) from the paper.
     // Here, we don't include m(x) in the numerator, instead multiplying it with this column later.
-    let mut table = trace_poly_values[lookup.table_column].values.clone();
-    for x in table.iter_mut() {
-        *x = challenge + *x;
-    }
-    let table_inverse: Vec<F> = F::batch_multiplicative_inverse(&table);
+    let table_inverse = shifted_inverses(&trace_poly_values[lookup.table_column].values, challenge);
 
     // Compute the `Z` polynomial with `Z(1)=0` and `Z(gx) = Z(x) + sum h_i(x) - frequencies(x)g(x)`.
     // This enforces the check from the paper, that the sum of the h_k(x) polynomials is 0 over H.
     // In the paper, that sum includes m(x)/(x + t(x)) = frequencies(x)/g(x), because that was bundled
     // into the h_k(x) polynomials.
     let frequencies = &trace_poly_values[lookup.frequencies_column].values;
+    let increments = (0..frequencies.len() - 1)
+        .into_par_iter()
+        .map(|i| {
+            helper_columns[..num_helper_columns - 1]
+                .iter()
+                .map(|col| col.values[i])
+                .sum::<F>()
+                - frequencies[i] * table_inverse[i]
+        })
+        .collect::<Vec<_>>();
     let mut z = Vec::with_capacity(frequencies.len());
     z.push(F::ZERO);
-    for i in 0..frequencies.len() - 1 {
-        let x = helper_columns[..num_helper_columns - 1]
-            .iter()
-            .map(|col| col.values[i])
-            .sum::<F>()
-            - frequencies[i] * table_inverse[i];
+    for (i, x) in increments.into_iter().enumerate() {
         z.push(z[i] + x);
     }
     helper_columns.push(z.into());
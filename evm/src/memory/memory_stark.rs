@@ -10,7 +10,6 @@ use plonky2::hash::hash_types::RichField;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::timed;
 use plonky2::util::timing::TimingTree;
-use plonky2::util::transpose;
 use plonky2_maybe_rayon::*;
 
 use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
@@ -24,8 +23,8 @@ use crate::memory::columns::{
 };
 use crate::memory::VALUE_LIMBS;
 use crate::stark::Stark;
+use crate::witness::memory::MemoryOp;
 use crate::witness::memory::MemoryOpKind::Read;
-use crate::witness::memory::{MemoryAddress, MemoryOp};
 
 /// Creates the vector of `Columns` corresponding to:
 /// - the memory operation type,
@@ -50,46 +49,18 @@ pub struct MemoryStark<F, const D: usize> {
     pub(crate) f: PhantomData<F>,
 }
 
-impl MemoryOp {
-    /// Generate a row for a given memory operation. Note that this does not generate columns which
-    /// depend on the next operation, such as `CONTEXT_FIRST_CHANGE`; those are generated later.
-    /// It also does not generate columns such as `COUNTER`, which are generated later, after the
-    /// trace has been transposed into column-major form.
-    fn into_row<F: Field>(self) -> [F; NUM_COLUMNS] {
-        let mut row = [F::ZERO; NUM_COLUMNS];
-        row[FILTER] = F::from_bool(self.filter);
-        row[TIMESTAMP] = F::from_canonical_usize(self.timestamp);
-        row[IS_READ] = F::from_bool(self.kind == Read);
-        let MemoryAddress {
-            context,
-            segment,
-            virt,
-        } = self.address;
-        row[ADDR_CONTEXT] = F::from_canonical_usize(context);
-        row[ADDR_SEGMENT] = F::from_canonical_usize(segment);
-        row[ADDR_VIRTUAL] = F::from_canonical_usize(virt);
-        for j in 0..VALUE_LIMBS {
-            row[value_limb(j)] = F::from_canonical_u32((self.value >> (j * 32)).low_u32());
-        }
-        row
-    }
-}
-
 /// Generates the `_FIRST_CHANGE` columns and the `RANGE_CHECK` column in the trace.
-pub fn generate_first_change_flags_and_rc<F: RichField>(trace_rows: &mut [[F; NUM_COLUMNS]]) {
-    let num_ops = trace_rows.len();
+pub fn generate_first_change_flags_and_rc<F: RichField>(trace_col_vecs: &mut [Vec<F>]) {
+    let num_ops = trace_col_vecs[0].len();
     for idx in 0..num_ops - 1 {
-        let row = trace_rows[idx].as_slice();
-        let next_row = trace_rows[idx + 1].as_slice();
-
-        let context = row[ADDR_CONTEXT];
-        let segment = row[ADDR_SEGMENT];
-        let virt = row[ADDR_VIRTUAL];
-        let timestamp = row[TIMESTAMP];
-        let next_context = next_row[ADDR_CONTEXT];
-        let next_segment = next_row[ADDR_SEGMENT];
-        let next_virt = next_row[ADDR_VIRTUAL];
-        let next_timestamp = next_row[TIMESTAMP];
+        let context = trace_col_vecs[ADDR_CONTEXT][idx];
+        let segment = trace_col_vecs[ADDR_SEGMENT][idx];
+        let virt = trace_col_vecs[ADDR_VIRTUAL][idx];
+        let timestamp = trace_col_vecs[TIMESTAMP][idx];
+        let next_context = trace_col_vecs[ADDR_CONTEXT][idx + 1];
+        let next_segment = trace_col_vecs[ADDR_SEGMENT][idx + 1];
+        let next_virt = trace_col_vecs[ADDR_VIRTUAL][idx + 1];
+        let next_timestamp = trace_col_vecs[TIMESTAMP][idx + 1];
 
         let context_changed = context != next_context;
         let segment_changed = segment != next_segment;
@@ -100,12 +71,11 @@ pub fn generate_first_change_flags_and_rc<F: RichField>(trace_rows: &mut [[F; NU
         let virtual_first_change =
             virtual_changed && !segment_first_change && !context_first_change;
 
-        let row = trace_rows[idx].as_mut_slice();
-        row[CONTEXT_FIRST_CHANGE] = F::from_bool(context_first_change);
-        row[SEGMENT_FIRST_CHANGE] = F::from_bool(segment_first_change);
-        row[VIRTUAL_FIRST_CHANGE] = F::from_bool(virtual_first_change);
+        trace_col_vecs[CONTEXT_FIRST_CHANGE][idx] = F::from_bool(context_first_change);
+        trace_col_vecs[SEGMENT_FIRST_CHANGE][idx] = F::from_bool(segment_first_change);
+        trace_col_vecs[VIRTUAL_FIRST_CHANGE][idx] = F::from_bool(virtual_first_change);
 
-        row[RANGE_CHECK] = if context_first_change {
+        let range_check = if context_first_change {
             next_context - context - F::ONE
         } else if segment_first_change {
             next_segment - segment - F::ONE
@@ -114,19 +84,20 @@ pub fn generate_first_change_flags_and_rc<F: RichField>(trace_rows: &mut [[F; NU
         } else {
             next_timestamp - timestamp
         };
+        trace_col_vecs[RANGE_CHECK][idx] = range_check;
 
         assert!(
-            row[RANGE_CHECK].to_canonical_u64() < num_ops as u64,
+            range_check.to_canonical_u64() < num_ops as u64,
             "Range check of {} is too large. Bug in fill_gaps?",
-            row[RANGE_CHECK]
+            range_check
         );
     }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> MemoryStark<F, D> {
-    /// Generate most of the trace rows. Excludes a few columns like `COUNTER`, which are generated
-    /// later, after transposing to column-major form.
-    fn generate_trace_row_major(&self, mut memory_ops: Vec<MemoryOp>) -> Vec<[F; NUM_COLUMNS]> {
+    /// Generate most of the trace columns, directly in column-major form. Excludes a few columns
+    /// like `COUNTER`, which are generated later.
+    fn generate_trace_columns(&self, mut memory_ops: Vec<MemoryOp>) -> Vec<Vec<F>> {
         // fill_gaps expects an ordered list of operations.
         memory_ops.sort_by_key(MemoryOp::sorting_key);
         Self::fill_gaps(&mut memory_ops);
@@ -136,17 +107,31 @@ impl<F: RichField + Extendable<D>, const D: usize> MemoryStark<F, D> {
         // fill_gaps may have added operations at the end which break the order, so sort again.
         memory_ops.sort_by_key(MemoryOp::sorting_key);
 
-        let mut trace_rows = memory_ops
-            .into_par_iter()
-            .map(|op| op.into_row())
-            .collect::<Vec<_>>();
-        generate_first_change_flags_and_rc(trace_rows.as_mut_slice());
-        trace_rows
+        let column = |f: &(dyn Fn(&MemoryOp) -> F + Sync)| -> Vec<F> {
+            memory_ops.par_iter().map(f).collect()
+        };
+        let mut trace_col_vecs: Vec<Vec<F>> = vec![vec![]; NUM_COLUMNS];
+        trace_col_vecs[FILTER] = column(&|op| F::from_bool(op.filter));
+        trace_col_vecs[TIMESTAMP] = column(&|op| F::from_canonical_usize(op.timestamp));
+        trace_col_vecs[IS_READ] = column(&|op| F::from_bool(op.kind == Read));
+        trace_col_vecs[ADDR_CONTEXT] = column(&|op| F::from_canonical_usize(op.address.context));
+        trace_col_vecs[ADDR_SEGMENT] = column(&|op| F::from_canonical_usize(op.address.segment));
+        trace_col_vecs[ADDR_VIRTUAL] = column(&|op| F::from_canonical_usize(op.address.virt));
+        for j in 0..VALUE_LIMBS {
+            trace_col_vecs[value_limb(j)] =
+                column(&|op| F::from_canonical_u32((op.value >> (j * 32)).low_u32()));
+        }
+        // The remaining columns depend on neighbouring operations, and are filled in below.
+        for col in trace_col_vecs.iter_mut().filter(|col| col.is_empty()) {
+            *col = vec![F::ZERO; memory_ops.len()];
+        }
+
+        generate_first_change_flags_and_rc(&mut trace_col_vecs);
+        trace_col_vecs
     }
 
-    /// Generates the `COUNTER`, `RANGE_CHECK` and `FREQUENCIES` columns, given a
-    /// trace in column-major form.
-    fn generate_trace_col_major(trace_col_vecs: &mut [Vec<F>]) {
+    /// Generates the `COUNTER` and `FREQUENCIES` columns, given the `RANGE_CHECK` column.
+    fn generate_counter_and_frequencies(trace_col_vecs: &mut [Vec<F>]) {
         let height = trace_col_vecs[0].len();
         trace_col_vecs[COUNTER] = (0..height).map(|i| F::from_canonical_usize(i)).collect();
 
@@ -222,19 +207,12 @@ impl<F: RichField + Extendable<D>, const D: usize> MemoryStark<F, D> {
         memory_ops: Vec<MemoryOp>,
         timing: &mut TimingTree,
     ) -> Vec<PolynomialValues<F>> {
-        // Generate most of the trace in row-major form.
-        let trace_rows = timed!(
+        let mut trace_col_vecs = timed!(
             timing,
-            "generate trace rows",
-            self.generate_trace_row_major(memory_ops)
+            "generate trace columns",
+            self.generate_trace_columns(memory_ops)
         );
-        let trace_row_vecs: Vec<_> = trace_rows.into_iter().map(|row| row.to_vec()).collect();
-
-        // Transpose to column-major form.
-        let mut trace_col_vecs = transpose(&trace_row_vecs);
-
-        // A few final generation steps, which work better in column-major form.
-        Self::generate_trace_col_major(&mut trace_col_vecs);
+        Self::generate_counter_and_frequencies(&mut trace_col_vecs);
 
         trace_col_vecs
             .into_iter()
@@ -70,10 +70,20 @@ pub enum Segment {
     ContextCheckpoints = 35,
     /// List of 256 previous block hashes.
     BlockHashes = 36,
+    /// Contains the header fields added by forks, see `BlockMetadataExtensions`. The first
+    /// `NUM_BLOCK_METADATA_EXTENSIONS` elements are the presence flags of each field, and the next
+    /// ones their values, which are 0 for absent fields.
+    BlockMetadataExtensions = 37,
+    /// Contains the extra public values of the chain, see `ExtraPublicValues`. The first
+    /// `NUM_EXTRA_PUBLIC_VALUES` words hold the current value of each slot, starting from its value
+    /// before the state transition, and the next ones its expected value after it.
+    ExtraPublicValues = 38,
+    /// Contains the value of each chain constant read by the kernel, see `ChainConstants`.
+    ChainConstants = 39,
 }
 
 impl Segment {
-    pub(crate) const COUNT: usize = 37;
+    pub(crate) const COUNT: usize = 40;
 
     pub(crate) fn all() -> [Self; Self::COUNT] {
         [
@@ -114,6 +124,9 @@ impl Segment {
             Self::TouchedAddresses,
             Self::ContextCheckpoints,
             Self::BlockHashes,
+            Self::BlockMetadataExtensions,
+            Self::ExtraPublicValues,
+            Self::ChainConstants,
         ]
     }
 
@@ -157,6 +170,9 @@ impl Segment {
             Segment::TouchedAddresses => "SEGMENT_TOUCHED_ADDRESSES",
             Segment::ContextCheckpoints => "SEGMENT_CONTEXT_CHECKPOINTS",
             Segment::BlockHashes => "SEGMENT_BLOCK_HASHES",
+            Segment::BlockMetadataExtensions => "SEGMENT_BLOCK_METADATA_EXTENSIONS",
+            Segment::ExtraPublicValues => "SEGMENT_EXTRA_PUBLIC_VALUES",
+            Segment::ChainConstants => "SEGMENT_CHAIN_CONSTANTS",
         }
     }
 
@@ -200,6 +216,9 @@ impl Segment {
             Segment::TouchedAddresses => 256,
             Segment::ContextCheckpoints => 256,
             Segment::BlockHashes => 256,
+            Segment::BlockMetadataExtensions => 256,
+            Segment::ExtraPublicValues => 256,
+            Segment::ChainConstants => 256,
         }
     }
 }
@@ -1,17 +1,20 @@
 use ethereum_types::{Address, H256, U256};
 use itertools::Itertools;
 use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::{Field, PrimeField64};
 use plonky2::fri::oracle::PolynomialBatch;
 use plonky2::fri::proof::{FriChallenges, FriChallengesTarget, FriProof, FriProofTarget};
 use plonky2::fri::structure::{
     FriOpeningBatch, FriOpeningBatchTarget, FriOpenings, FriOpeningsTarget,
 };
-use plonky2::hash::hash_types::{MerkleCapTarget, RichField};
+use plonky2::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
+use plonky2::hash::hashing::PlonkyPermutation;
 use plonky2::hash::merkle_tree::MerkleCap;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::iop::target::{BoolTarget, Target};
 use plonky2::plonk::circuit_builder::CircuitBuilder;
-use plonky2::plonk::config::{GenericConfig, Hasher};
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
 use plonky2::util::serialization::{Buffer, IoResult, Read, Write};
 use plonky2_maybe_rayon::*;
 use serde::{Deserialize, Serialize};
@@ -19,6 +22,8 @@ use serde::{Deserialize, Serialize};
 use crate::all_stark::NUM_TABLES;
 use crate::config::StarkConfig;
 use crate::cross_table_lookup::GrandProductChallengeSet;
+use crate::util::{h256_limbs, u256_limbs, u256_to_u32, u256_to_u64};
+use crate::witness::errors::{ProgramError, ProverInputError};
 
 /// A STARK proof for each table, plus some metadata used to create recursive wrapper proofs.
 #[derive(Debug, Clone)]
@@ -59,6 +64,12 @@ pub struct PublicValues {
     pub block_hashes: BlockHashes,
     /// Extra block data that is specific to the current proof.
     pub extra_block_data: ExtraBlockData,
+    /// Values exposed by the chain beyond those of the EVM, e.g. an L2 to L1 message root.
+    #[serde(default)]
+    pub extra_public_values: ExtraPublicValues,
+    /// Constants of the chain which the kernel reads at runtime, e.g. the recipient of base fees.
+    #[serde(default)]
+    pub chain_constants: ChainConstants,
 }
 
 /// Trie hashes.
@@ -126,6 +137,50 @@ pub struct BlockMetadata {
     /// The block bloom of this block, represented as the consecutive
     /// 32-byte chunks of a block's final bloom filter string.
     pub block_bloom: [U256; 8],
+    /// The header fields added by forks after the fields above.
+    #[serde(default)]
+    pub extensions: BlockMetadataExtensions,
+}
+
+/// The number of slots for header fields added by forks, see [`BlockMetadataExtension`].
+pub const NUM_BLOCK_METADATA_EXTENSIONS: usize = 8;
+
+/// A header field added by a fork after the fields of [`BlockMetadata`], identified by its slot.
+///
+/// Each new field takes the next free slot, out of [`NUM_BLOCK_METADATA_EXTENSIONS`] reserved
+/// ones, so supporting it doesn't change the layout of the public inputs of the recursive
+/// circuits. The digest of the public values of blocks without any of those fields doesn't
+/// depend on them either, see [`PublicValues::hash`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BlockMetadataExtension {
+    /// The blob gas used by the transactions of this block, since Cancun (EIP-4844).
+    BlobGasUsed = 0,
+    /// The excess blob gas of this block, since Cancun (EIP-4844).
+    ExcessBlobGas = 1,
+    /// The root of the parent beacon block, since Cancun (EIP-4788).
+    ParentBeaconBlockRoot = 2,
+}
+
+/// The values of the header fields added by forks after the fields of [`BlockMetadata`], by slot.
+/// A field is absent from the blocks preceding the fork which adds it.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct BlockMetadataExtensions {
+    pub fields: [Option<U256>; NUM_BLOCK_METADATA_EXTENSIONS],
+}
+
+impl BlockMetadataExtensions {
+    pub fn get(&self, field: BlockMetadataExtension) -> Option<U256> {
+        self.fields[field as usize]
+    }
+
+    pub fn set(&mut self, field: BlockMetadataExtension, value: U256) {
+        self.fields[field as usize] = Some(value);
+    }
+
+    /// Returns whether all fields are absent.
+    pub fn is_empty(&self) -> bool {
+        self.fields.iter().all(Option::is_none)
+    }
 }
 
 /// Additional block data that are specific to the local transaction being proven,
@@ -153,6 +208,412 @@ pub struct ExtraBlockData {
     pub block_bloom_after: [U256; 8],
 }
 
+/// The number of slots for extra public values, see [`ExtraPublicValues`].
+pub const NUM_EXTRA_PUBLIC_VALUES: usize = 4;
+
+/// The value of an extra public value slot before and after the local state transition.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ExtraPublicValue {
+    pub before: U256,
+    pub after: U256,
+}
+
+/// Values computed during execution which a chain exposes beyond those of the EVM, such as the
+/// root of its L2 to L1 messages or its exit root, in the slots declared by its
+/// [`ExtraPublicValuesConfig`](crate::config::ExtraPublicValuesConfig).
+///
+/// The kernel starts a state transition with the `before` value of each slot, lets the routine
+/// designated for the slot update it, and checks that it ends with the `after` value. Like the gas
+/// used, the values are chained between consecutive state transitions when proofs are aggregated.
+/// Undeclared slots are `None`, and can't be updated.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ExtraPublicValues {
+    pub slots: [Option<ExtraPublicValue>; NUM_EXTRA_PUBLIC_VALUES],
+}
+
+impl ExtraPublicValues {
+    /// Returns whether no slot is declared.
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// The values of the slots when the state transition starts, 0 for undeclared slots.
+    pub fn before(&self) -> [U256; NUM_EXTRA_PUBLIC_VALUES] {
+        self.slots.map(|slot| slot.unwrap_or_default().before)
+    }
+
+    /// The values of the slots when the state transition ends, 0 for undeclared slots.
+    pub fn after(&self) -> [U256; NUM_EXTRA_PUBLIC_VALUES] {
+        self.slots.map(|slot| slot.unwrap_or_default().after)
+    }
+
+    /// The extra public values of two consecutive state transitions taken together, from those of
+    /// `self` before them to those of `next` after them. Slots must be declared by both.
+    pub fn merge(&self, next: &Self) -> Self {
+        Self {
+            slots: core::array::from_fn(|i| {
+                let (first, next) = (self.slots[i]?, next.slots[i]?);
+                Some(ExtraPublicValue {
+                    before: first.before,
+                    after: next.after,
+                })
+            }),
+        }
+    }
+
+    /// The extra public values of a state transition which follows this one, and leaves them
+    /// unchanged.
+    pub fn unchanged_after(&self) -> Self {
+        Self {
+            slots: self.slots.map(|slot| {
+                slot.map(|slot| ExtraPublicValue {
+                    before: slot.after,
+                    after: slot.after,
+                })
+            }),
+        }
+    }
+}
+
+/// The number of slots for chain constants, see [`ChainConstant`].
+pub const NUM_CHAIN_CONSTANTS: usize = 8;
+
+/// A constant of the chain which the kernel reads from memory at runtime rather than having it
+/// compiled in, so that a single kernel build can prove blocks of chains which differ in it.
+///
+/// Each constant takes a slot out of [`NUM_CHAIN_CONSTANTS`] reserved ones, and has the value it
+/// has on Ethereum unless overridden in [`ChainConstants`]. The effective values are public, so
+/// a verifier knows which chain a proof is for.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ChainConstant {
+    /// The address credited with the base fee of each transaction, or 0 to burn it as Ethereum
+    /// does (EIP-1559).
+    BaseFeeRecipient = 0,
+    /// The maximum size of the code of a contract, 0x6000 on Ethereum (EIP-170).
+    MaxCodeSize = 1,
+    /// The maximum size of the init code of a contract creation, 0xc000 on Ethereum (EIP-3860).
+    MaxInitcodeSize = 2,
+}
+
+impl ChainConstant {
+    pub(crate) const COUNT: usize = 3;
+
+    pub(crate) fn all() -> [Self; Self::COUNT] {
+        [
+            Self::BaseFeeRecipient,
+            Self::MaxCodeSize,
+            Self::MaxInitcodeSize,
+        ]
+    }
+
+    /// The variable name that gets passed into kernel assembly code.
+    pub(crate) fn var_name(&self) -> &'static str {
+        match self {
+            Self::BaseFeeRecipient => "CHAIN_CONSTANT_BASE_FEE_RECIPIENT",
+            Self::MaxCodeSize => "CHAIN_CONSTANT_MAX_CODE_SIZE",
+            Self::MaxInitcodeSize => "CHAIN_CONSTANT_MAX_INITCODE_SIZE",
+        }
+    }
+
+    /// The value of this constant on Ethereum.
+    pub fn ethereum_value(&self) -> U256 {
+        match self {
+            Self::BaseFeeRecipient => U256::zero(),
+            Self::MaxCodeSize => U256::from(0x6000),
+            Self::MaxInitcodeSize => U256::from(0xc000),
+        }
+    }
+}
+
+/// The constants of a chain which differ from their Ethereum values, by slot.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ChainConstants {
+    pub overrides: [Option<U256>; NUM_CHAIN_CONSTANTS],
+}
+
+impl ChainConstants {
+    /// Returns the value of `constant` on this chain.
+    pub fn get(&self, constant: ChainConstant) -> U256 {
+        self.overrides[constant as usize].unwrap_or_else(|| constant.ethereum_value())
+    }
+
+    pub fn set(&mut self, constant: ChainConstant, value: U256) {
+        self.overrides[constant as usize] = Some(value);
+    }
+
+    /// Returns whether no constant is overridden, i.e. the chain uses the Ethereum values.
+    pub fn is_empty(&self) -> bool {
+        self.overrides.iter().all(Option::is_none)
+    }
+
+    /// The Ethereum values of the constants by slot, 0 for unused slots.
+    pub(crate) fn ethereum_values() -> [U256; NUM_CHAIN_CONSTANTS] {
+        let mut values = [U256::zero(); NUM_CHAIN_CONSTANTS];
+        for constant in ChainConstant::all() {
+            values[constant as usize] = constant.ethereum_value();
+        }
+        values
+    }
+
+    /// The values of the constants on this chain by slot, as read by the kernel.
+    pub fn values(&self) -> [U256; NUM_CHAIN_CONSTANTS] {
+        let defaults = Self::ethereum_values();
+        core::array::from_fn(|i| self.overrides[i].unwrap_or(defaults[i]))
+    }
+}
+
+impl PublicValues {
+    /// Returns the canonical encoding of these public values as field elements, in the order in
+    /// which they appear as public inputs of the recursive circuits. Larger integers are encoded
+    /// with 32-bit limbs in little-endian order.
+    pub fn to_public_inputs<F: Field>(&self) -> Result<Vec<F>, ProgramError> {
+        let mut pis = Vec::with_capacity(PublicValuesTarget::SIZE);
+        for trie_roots in [&self.trie_roots_before, &self.trie_roots_after] {
+            pis.extend(h256_limbs::<F>(trie_roots.state_root));
+            pis.extend(h256_limbs::<F>(trie_roots.transactions_root));
+            pis.extend(h256_limbs::<F>(trie_roots.receipts_root));
+        }
+
+        let md = &self.block_metadata;
+        pis.extend_from_slice(
+            &u256_limbs::<F>(U256::from_big_endian(&md.block_beneficiary.0))[..5],
+        );
+        pis.push(u256_to_u32(md.block_timestamp)?);
+        pis.push(u256_to_u32(md.block_number)?);
+        pis.push(u256_to_u32(md.block_difficulty)?);
+        pis.extend(h256_limbs::<F>(md.block_random));
+        let (lo, hi) = u256_to_u64(md.block_gaslimit)?;
+        pis.extend([lo, hi]);
+        pis.push(u256_to_u32(md.block_chain_id)?);
+        let (lo, hi) = u256_to_u64(md.block_base_fee)?;
+        pis.extend([lo, hi]);
+        let (lo, hi) = u256_to_u64(md.block_gas_used)?;
+        pis.extend([lo, hi]);
+        pis.extend(md.block_bloom.iter().flat_map(|&x| u256_limbs::<F>(x)));
+
+        let hashes = &self.block_hashes;
+        pis.extend(
+            hashes
+                .prev_hashes
+                .iter()
+                .flat_map(|&hash| h256_limbs::<F>(hash)),
+        );
+        pis.extend(h256_limbs::<F>(hashes.cur_hash));
+
+        let ed = &self.extra_block_data;
+        pis.extend(h256_limbs::<F>(ed.genesis_state_trie_root));
+        pis.push(u256_to_u32(ed.txn_number_before)?);
+        pis.push(u256_to_u32(ed.txn_number_after)?);
+        let (lo, hi) = u256_to_u64(ed.gas_used_before)?;
+        pis.extend([lo, hi]);
+        let (lo, hi) = u256_to_u64(ed.gas_used_after)?;
+        pis.extend([lo, hi]);
+        pis.extend(
+            ed.block_bloom_before
+                .iter()
+                .flat_map(|&x| u256_limbs::<F>(x)),
+        );
+        pis.extend(
+            ed.block_bloom_after
+                .iter()
+                .flat_map(|&x| u256_limbs::<F>(x)),
+        );
+
+        let extensions = &md.extensions.fields;
+        pis.extend(extensions.iter().map(|x| F::from_bool(x.is_some())));
+        pis.extend(
+            extensions
+                .iter()
+                .flat_map(|&x| u256_limbs::<F>(x.unwrap_or_default())),
+        );
+
+        let extra = &self.extra_public_values;
+        pis.extend(extra.slots.iter().map(|x| F::from_bool(x.is_some())));
+        pis.extend(extra.before().iter().flat_map(|&x| u256_limbs::<F>(x)));
+        pis.extend(extra.after().iter().flat_map(|&x| u256_limbs::<F>(x)));
+
+        let overrides = &self.chain_constants.overrides;
+        pis.extend(overrides.iter().map(|x| F::from_bool(x.is_some())));
+        pis.extend(
+            overrides
+                .iter()
+                .flat_map(|&x| u256_limbs::<F>(x.unwrap_or_default())),
+        );
+
+        if pis.len() != PublicValuesTarget::SIZE {
+            // `prev_hashes` must contain exactly 256 hashes.
+            return Err(ProgramError::ProverInputError(
+                ProverInputError::InvalidInput,
+            ));
+        }
+        Ok(pis)
+    }
+
+    /// Decodes public values from their canonical encoding as field elements, the inverse of
+    /// `to_public_inputs`. Any public inputs following the public values, like the verifier data
+    /// of cyclic proofs, are ignored.
+    pub fn from_public_inputs<F: PrimeField64>(pis: &[F]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            pis.len() >= PublicValuesTarget::SIZE,
+            "Expected at least {} public inputs, found {}",
+            PublicValuesTarget::SIZE,
+            pis.len()
+        );
+        let limbs = pis[..PublicValuesTarget::SIZE]
+            .iter()
+            .map(|x| {
+                u32::try_from(x.to_canonical_u64())
+                    .map_err(|_| anyhow::anyhow!("Public input {x} is not a 32-bit limb"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut limbs = limbs.into_iter();
+        let mut u256 = |num_limbs: usize| {
+            (&mut limbs)
+                .take(num_limbs)
+                .enumerate()
+                .fold(U256::zero(), |acc, (i, limb)| {
+                    acc | (U256::from(limb) << (32 * i))
+                })
+        };
+        let h256 = |x: U256| {
+            let mut bytes = [0; 32];
+            x.to_big_endian(&mut bytes);
+            H256(bytes)
+        };
+        let mut trie_roots = || TrieRoots {
+            state_root: h256(u256(8)),
+            transactions_root: h256(u256(8)),
+            receipts_root: h256(u256(8)),
+        };
+        let trie_roots_before = trie_roots();
+        let trie_roots_after = trie_roots();
+
+        let mut block_metadata = BlockMetadata {
+            block_beneficiary: Address::from(h256(u256(5))),
+            block_timestamp: u256(1),
+            block_number: u256(1),
+            block_difficulty: u256(1),
+            block_random: h256(u256(8)),
+            block_gaslimit: u256(2),
+            block_chain_id: u256(1),
+            block_base_fee: u256(2),
+            block_gas_used: u256(2),
+            block_bloom: core::array::from_fn(|_| u256(8)),
+            extensions: BlockMetadataExtensions::default(),
+        };
+
+        let block_hashes = BlockHashes {
+            prev_hashes: (0..256).map(|_| h256(u256(8))).collect(),
+            cur_hash: h256(u256(8)),
+        };
+
+        let extra_block_data = ExtraBlockData {
+            genesis_state_trie_root: h256(u256(8)),
+            txn_number_before: u256(1),
+            txn_number_after: u256(1),
+            gas_used_before: u256(2),
+            gas_used_after: u256(2),
+            block_bloom_before: core::array::from_fn(|_| u256(8)),
+            block_bloom_after: core::array::from_fn(|_| u256(8)),
+        };
+
+        let present: [U256; NUM_BLOCK_METADATA_EXTENSIONS] = core::array::from_fn(|_| u256(1));
+        for (slot, present) in present.into_iter().enumerate() {
+            let value = u256(8);
+            block_metadata.extensions.fields[slot] = match present.as_u32() {
+                0 => {
+                    anyhow::ensure!(
+                        value.is_zero(),
+                        "Absent block metadata extension {slot} has a nonzero value"
+                    );
+                    None
+                }
+                1 => Some(value),
+                flag => {
+                    anyhow::bail!("Invalid presence flag {flag} of block metadata extension {slot}")
+                }
+            };
+        }
+
+        let mut extra_public_values = ExtraPublicValues::default();
+        let declared: [U256; NUM_EXTRA_PUBLIC_VALUES] = core::array::from_fn(|_| u256(1));
+        let before: [U256; NUM_EXTRA_PUBLIC_VALUES] = core::array::from_fn(|_| u256(8));
+        let after: [U256; NUM_EXTRA_PUBLIC_VALUES] = core::array::from_fn(|_| u256(8));
+        for (slot, ((declared, before), after)) in
+            declared.into_iter().zip(before).zip(after).enumerate()
+        {
+            extra_public_values.slots[slot] = match declared.as_u32() {
+                0 => {
+                    anyhow::ensure!(
+                        before.is_zero() && after.is_zero(),
+                        "Undeclared extra public value {slot} has a nonzero value"
+                    );
+                    None
+                }
+                1 => Some(ExtraPublicValue { before, after }),
+                flag => {
+                    anyhow::bail!("Invalid declaration flag {flag} of extra public value {slot}")
+                }
+            };
+        }
+
+        let mut chain_constants = ChainConstants::default();
+        let overridden: [U256; NUM_CHAIN_CONSTANTS] = core::array::from_fn(|_| u256(1));
+        for (slot, overridden) in overridden.into_iter().enumerate() {
+            let value = u256(8);
+            chain_constants.overrides[slot] = match overridden.as_u32() {
+                0 => {
+                    anyhow::ensure!(
+                        value.is_zero(),
+                        "Chain constant {slot} isn't overridden but has a nonzero value"
+                    );
+                    None
+                }
+                1 => Some(value),
+                flag => anyhow::bail!("Invalid override flag {flag} of chain constant {slot}"),
+            };
+        }
+
+        Ok(Self {
+            trie_roots_before,
+            trie_roots_after,
+            block_metadata,
+            block_hashes,
+            extra_block_data,
+            extra_public_values,
+            chain_constants,
+        })
+    }
+
+    /// Returns the canonical byte encoding of these public values, i.e. each limb of
+    /// `to_public_inputs` as 4 little-endian bytes.
+    pub fn canonical_bytes(&self) -> Result<Vec<u8>, ProgramError> {
+        Ok(self
+            .to_public_inputs::<GoldilocksField>()?
+            .into_iter()
+            .flat_map(|limb| (limb.to_canonical_u64() as u32).to_le_bytes())
+            .collect())
+    }
+
+    /// Returns the digest of these public values, which is the sole public input of a wrapped
+    /// block proof.
+    ///
+    /// The block metadata extensions, extra public values and chain constants are only hashed if
+    /// one of them is present or overridden, so that the digest of a block without them is the
+    /// same as before they were introduced.
+    pub fn hash<F: RichField, H: Hasher<F>>(&self) -> Result<H::Hash, ProgramError> {
+        let mut pis = self.to_public_inputs()?;
+        if self.block_metadata.extensions.is_empty()
+            && self.extra_public_values.is_empty()
+            && self.chain_constants.is_empty()
+        {
+            pis.truncate(PublicValuesTarget::BASE_SIZE);
+        }
+        Ok(H::hash_no_pad(&pis))
+    }
+}
+
 /// Memory values which are public.
 /// Note: All the larger integers are encoded with 32-bit limbs in little-endian order.
 #[derive(Eq, PartialEq, Debug)]
@@ -167,9 +628,101 @@ pub struct PublicValuesTarget {
     pub block_hashes: BlockHashesTarget,
     /// Extra block data that is specific to the current proof.
     pub extra_block_data: ExtraBlockDataTarget,
+    /// Header fields added by forks, i.e. `BlockMetadata::extensions`. They come last, so that
+    /// the other public values are a prefix of the public inputs which doesn't depend on them.
+    pub block_metadata_extensions: BlockMetadataExtensionsTarget,
+    /// Values exposed by the chain beyond those of the EVM, i.e. `PublicValues::extra_public_values`.
+    pub extra_public_values: ExtraPublicValuesTarget,
+    /// Constants of the chain read by the kernel, i.e. `PublicValues::chain_constants`.
+    pub chain_constants: ChainConstantsTarget,
 }
 
 impl PublicValuesTarget {
+    /// The number of public inputs taken by the public values other than the block metadata
+    /// extensions, extra public values and chain constants.
+    pub const BASE_SIZE: usize = TrieRootsTarget::SIZE * 2
+        + BlockMetadataTarget::SIZE
+        + BlockHashesTarget::BLOCK_HASHES_SIZE
+        + ExtraBlockDataTarget::SIZE;
+
+    /// The number of public inputs taken by the public values.
+    pub const SIZE: usize = Self::BASE_SIZE
+        + BlockMetadataExtensionsTarget::SIZE
+        + ExtraPublicValuesTarget::SIZE
+        + ChainConstantsTarget::SIZE;
+
+    /// Returns the targets of the public values in their canonical order, the inverse of
+    /// `from_public_inputs`. This matches `PublicValues::to_public_inputs`.
+    pub fn to_public_inputs(&self) -> Vec<Target> {
+        let tr0 = &self.trie_roots_before;
+        let tr1 = &self.trie_roots_after;
+        let md = &self.block_metadata;
+        let bh = &self.block_hashes;
+        let ed = &self.extra_block_data;
+        let ext = &self.block_metadata_extensions;
+        let extra = &self.extra_public_values;
+        let cc = &self.chain_constants;
+
+        let pis = [
+            &tr0.state_root[..],
+            &tr0.transactions_root,
+            &tr0.receipts_root,
+            &tr1.state_root,
+            &tr1.transactions_root,
+            &tr1.receipts_root,
+            &md.block_beneficiary,
+            &[md.block_timestamp, md.block_number, md.block_difficulty],
+            &md.block_random,
+            &md.block_gaslimit,
+            &[md.block_chain_id],
+            &md.block_base_fee,
+            &md.block_gas_used,
+            &md.block_bloom,
+            &bh.prev_hashes,
+            &bh.cur_hash,
+            &ed.genesis_state_trie_root,
+            &[ed.txn_number_before, ed.txn_number_after],
+            &ed.gas_used_before,
+            &ed.gas_used_after,
+            &ed.block_bloom_before,
+            &ed.block_bloom_after,
+            &ext.present,
+            &ext.values,
+            &extra.declared,
+            &extra.before,
+            &extra.after,
+            &cc.overridden,
+            &cc.values,
+        ]
+        .concat();
+        debug_assert_eq!(pis.len(), Self::SIZE);
+        pis
+    }
+
+    /// Computes in-circuit the digest of the public values, matching `PublicValues::hash`.
+    pub fn hash_circuit<F, H, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> HashOutTarget
+    where
+        F: RichField + Extendable<D>,
+        H: AlgebraicHasher<F>,
+    {
+        let pis = self.to_public_inputs();
+        let base_hash = builder.hash_n_to_hash_no_pad::<H>(pis[..Self::BASE_SIZE].to_vec());
+        let full_hash = builder.hash_n_to_hash_no_pad::<H>(pis);
+        let has_extensions = self.block_metadata_extensions.any_present(builder);
+        let has_extra_public_values = self.extra_public_values.any_declared(builder);
+        let has_chain_constants = self.chain_constants.any_overridden(builder);
+        let hash_all = builder.or(has_extensions, has_extra_public_values);
+        let hash_all = builder.or(hash_all, has_chain_constants);
+        HashOutTarget {
+            elements: core::array::from_fn(|i| {
+                builder.select(hash_all, full_hash.elements[i], base_hash.elements[i])
+            }),
+        }
+    }
+
     /// Serializes public value targets.
     pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
         let TrieRootsTarget {
@@ -240,6 +793,23 @@ impl PublicValuesTarget {
         buffer.write_target_array(&block_bloom_before)?;
         buffer.write_target_array(&block_bloom_after)?;
 
+        let BlockMetadataExtensionsTarget { present, values } = self.block_metadata_extensions;
+        buffer.write_target_array(&present)?;
+        buffer.write_target_array(&values)?;
+
+        let ExtraPublicValuesTarget {
+            declared,
+            before,
+            after,
+        } = self.extra_public_values;
+        buffer.write_target_array(&declared)?;
+        buffer.write_target_array(&before)?;
+        buffer.write_target_array(&after)?;
+
+        let ChainConstantsTarget { overridden, values } = self.chain_constants;
+        buffer.write_target_array(&overridden)?;
+        buffer.write_target_array(&values)?;
+
         Ok(())
     }
 
@@ -285,12 +855,31 @@ impl PublicValuesTarget {
             block_bloom_after: buffer.read_target_array()?,
         };
 
+        let block_metadata_extensions = BlockMetadataExtensionsTarget {
+            present: buffer.read_target_array()?,
+            values: buffer.read_target_array()?,
+        };
+
+        let extra_public_values = ExtraPublicValuesTarget {
+            declared: buffer.read_target_array()?,
+            before: buffer.read_target_array()?,
+            after: buffer.read_target_array()?,
+        };
+
+        let chain_constants = ChainConstantsTarget {
+            overridden: buffer.read_target_array()?,
+            values: buffer.read_target_array()?,
+        };
+
         Ok(Self {
             trie_roots_before,
             trie_roots_after,
             block_metadata,
             block_hashes,
             extra_block_data,
+            block_metadata_extensions,
+            extra_public_values,
+            chain_constants,
         })
     }
 
@@ -298,14 +887,7 @@ impl PublicValuesTarget {
     /// Public values are always the first public inputs added to the circuit,
     /// so we can start extracting at index 0.
     pub fn from_public_inputs(pis: &[Target]) -> Self {
-        assert!(
-            pis.len()
-                > TrieRootsTarget::SIZE * 2
-                    + BlockMetadataTarget::SIZE
-                    + BlockHashesTarget::BLOCK_HASHES_SIZE
-                    + ExtraBlockDataTarget::SIZE
-                    - 1
-        );
+        assert!(pis.len() >= Self::SIZE);
 
         Self {
             trie_roots_before: TrieRootsTarget::from_public_inputs(&pis[0..TrieRootsTarget::SIZE]),
@@ -325,11 +907,17 @@ impl PublicValuesTarget {
             extra_block_data: ExtraBlockDataTarget::from_public_inputs(
                 &pis[TrieRootsTarget::SIZE * 2
                     + BlockMetadataTarget::SIZE
-                    + BlockHashesTarget::BLOCK_HASHES_SIZE
-                    ..TrieRootsTarget::SIZE * 2
-                        + BlockMetadataTarget::SIZE
-                        + BlockHashesTarget::BLOCK_HASHES_SIZE
-                        + ExtraBlockDataTarget::SIZE],
+                    + BlockHashesTarget::BLOCK_HASHES_SIZE..Self::BASE_SIZE],
+            ),
+            block_metadata_extensions: BlockMetadataExtensionsTarget::from_public_inputs(
+                &pis[Self::BASE_SIZE..Self::BASE_SIZE + BlockMetadataExtensionsTarget::SIZE],
+            ),
+            extra_public_values: ExtraPublicValuesTarget::from_public_inputs(
+                &pis[Self::BASE_SIZE + BlockMetadataExtensionsTarget::SIZE
+                    ..Self::SIZE - ChainConstantsTarget::SIZE],
+            ),
+            chain_constants: ChainConstantsTarget::from_public_inputs(
+                &pis[Self::SIZE - ChainConstantsTarget::SIZE..Self::SIZE],
             ),
         }
     }
@@ -372,6 +960,24 @@ impl PublicValuesTarget {
                 pv0.extra_block_data,
                 pv1.extra_block_data,
             ),
+            block_metadata_extensions: BlockMetadataExtensionsTarget::select(
+                builder,
+                condition,
+                pv0.block_metadata_extensions,
+                pv1.block_metadata_extensions,
+            ),
+            extra_public_values: ExtraPublicValuesTarget::select(
+                builder,
+                condition,
+                pv0.extra_public_values,
+                pv1.extra_public_values,
+            ),
+            chain_constants: ChainConstantsTarget::select(
+                builder,
+                condition,
+                pv0.chain_constants,
+                pv1.chain_constants,
+            ),
         }
     }
 }
@@ -762,8 +1368,302 @@ impl ExtraBlockDataTarget {
     }
 }
 
+/// Circuit version of `BlockMetadataExtensions`.
+/// `Target`s for the header fields added by forks after the fields of `BlockMetadata`, by slot.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct BlockMetadataExtensionsTarget {
+    /// `Target`s for whether each field is present, as 0 or 1.
+    pub present: [Target; NUM_BLOCK_METADATA_EXTENSIONS],
+    /// `Target`s for the value of each field, which is 0 if absent.
+    pub values: [Target; 8 * NUM_BLOCK_METADATA_EXTENSIONS],
+}
+
+impl BlockMetadataExtensionsTarget {
+    /// Number of `Target`s required for the block metadata extensions.
+    pub const SIZE: usize = 9 * NUM_BLOCK_METADATA_EXTENSIONS;
+
+    /// Extracts the block metadata extensions `Target`s from the public input `Target`s.
+    /// The provided `pis` should start with the block metadata extensions.
+    pub fn from_public_inputs(pis: &[Target]) -> Self {
+        Self {
+            present: pis[0..NUM_BLOCK_METADATA_EXTENSIONS].try_into().unwrap(),
+            values: pis[NUM_BLOCK_METADATA_EXTENSIONS..Self::SIZE]
+                .try_into()
+                .unwrap(),
+        }
+    }
+
+    /// If `condition`, returns the block metadata extensions in `ext0`,
+    /// otherwise returns the block metadata extensions in `ext1`.
+    pub fn select<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        condition: BoolTarget,
+        ext0: Self,
+        ext1: Self,
+    ) -> Self {
+        Self {
+            present: core::array::from_fn(|i| {
+                builder.select(condition, ext0.present[i], ext1.present[i])
+            }),
+            values: core::array::from_fn(|i| {
+                builder.select(condition, ext0.values[i], ext1.values[i])
+            }),
+        }
+    }
+
+    /// Connects the block metadata extensions in `ext0` to those in `ext1`.
+    pub fn connect<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        ext0: Self,
+        ext1: Self,
+    ) {
+        for i in 0..NUM_BLOCK_METADATA_EXTENSIONS {
+            builder.connect(ext0.present[i], ext1.present[i]);
+        }
+        for i in 0..8 * NUM_BLOCK_METADATA_EXTENSIONS {
+            builder.connect(ext0.values[i], ext1.values[i]);
+        }
+    }
+
+    /// Checks that each presence flag is a boolean, and that the value of each absent field is 0,
+    /// so that the extensions have a single encoding.
+    pub fn assert_canonical<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) {
+        for (i, &present) in self.present.iter().enumerate() {
+            let present = BoolTarget::new_unsafe(present);
+            builder.assert_bool(present);
+            let absent = builder.not(present);
+            for &limb in &self.values[8 * i..8 * (i + 1)] {
+                let constr = builder.mul(absent.target, limb);
+                builder.assert_zero(constr);
+            }
+        }
+    }
+
+    /// Returns whether any field is present. The presence flags must be booleans, see
+    /// `assert_canonical`.
+    pub fn any_present<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> BoolTarget {
+        let mut any_present = builder._false();
+        for &present in &self.present {
+            any_present = builder.or(any_present, BoolTarget::new_unsafe(present));
+        }
+        any_present
+    }
+}
+
+/// Circuit version of `ExtraPublicValues`.
+/// `Target`s for the values a chain exposes beyond those of the EVM, by slot.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct ExtraPublicValuesTarget {
+    /// `Target`s for whether each slot is declared, as 0 or 1.
+    pub declared: [Target; NUM_EXTRA_PUBLIC_VALUES],
+    /// `Target`s for the value of each slot before the state transition, which is 0 if undeclared.
+    pub before: [Target; 8 * NUM_EXTRA_PUBLIC_VALUES],
+    /// `Target`s for the value of each slot after the state transition, which is 0 if undeclared.
+    pub after: [Target; 8 * NUM_EXTRA_PUBLIC_VALUES],
+}
+
+impl ExtraPublicValuesTarget {
+    /// Number of `Target`s required for the extra public values.
+    pub const SIZE: usize = 17 * NUM_EXTRA_PUBLIC_VALUES;
+
+    /// Extracts the extra public values `Target`s from the public input `Target`s.
+    /// The provided `pis` should start with the extra public values.
+    pub fn from_public_inputs(pis: &[Target]) -> Self {
+        const N: usize = NUM_EXTRA_PUBLIC_VALUES;
+        Self {
+            declared: pis[0..N].try_into().unwrap(),
+            before: pis[N..9 * N].try_into().unwrap(),
+            after: pis[9 * N..Self::SIZE].try_into().unwrap(),
+        }
+    }
+
+    /// If `condition`, returns the extra public values in `extra0`,
+    /// otherwise returns the extra public values in `extra1`.
+    pub fn select<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        condition: BoolTarget,
+        extra0: Self,
+        extra1: Self,
+    ) -> Self {
+        Self {
+            declared: core::array::from_fn(|i| {
+                builder.select(condition, extra0.declared[i], extra1.declared[i])
+            }),
+            before: core::array::from_fn(|i| {
+                builder.select(condition, extra0.before[i], extra1.before[i])
+            }),
+            after: core::array::from_fn(|i| {
+                builder.select(condition, extra0.after[i], extra1.after[i])
+            }),
+        }
+    }
+
+    /// Connects the extra public values of an aggregation with those of its two children: the
+    /// declared slots are the same, and the values are chained from `lhs` to `rhs`.
+    pub fn connect_aggregation<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        pvs: Self,
+        lhs: Self,
+        rhs: Self,
+    ) {
+        for i in 0..NUM_EXTRA_PUBLIC_VALUES {
+            builder.connect(pvs.declared[i], lhs.declared[i]);
+            builder.connect(pvs.declared[i], rhs.declared[i]);
+        }
+        for i in 0..8 * NUM_EXTRA_PUBLIC_VALUES {
+            builder.connect(pvs.before[i], lhs.before[i]);
+            builder.connect(lhs.after[i], rhs.before[i]);
+            builder.connect(pvs.after[i], rhs.after[i]);
+        }
+    }
+
+    /// Checks that each declaration flag is a boolean, and that the values of each undeclared slot
+    /// are 0, so that the extra public values have a single encoding.
+    pub fn assert_canonical<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) {
+        for (i, &declared) in self.declared.iter().enumerate() {
+            let declared = BoolTarget::new_unsafe(declared);
+            builder.assert_bool(declared);
+            let undeclared = builder.not(declared);
+            for &limb in self.before[8 * i..8 * (i + 1)]
+                .iter()
+                .chain(&self.after[8 * i..8 * (i + 1)])
+            {
+                let constr = builder.mul(undeclared.target, limb);
+                builder.assert_zero(constr);
+            }
+        }
+    }
+
+    /// Returns whether any slot is declared. The declaration flags must be booleans, see
+    /// `assert_canonical`.
+    pub fn any_declared<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> BoolTarget {
+        let mut any_declared = builder._false();
+        for &declared in &self.declared {
+            any_declared = builder.or(any_declared, BoolTarget::new_unsafe(declared));
+        }
+        any_declared
+    }
+}
+
+/// Circuit version of `ChainConstants`.
+/// `Target`s for the constants of the chain which differ from their Ethereum values, by slot.
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct ChainConstantsTarget {
+    /// `Target`s for whether each constant is overridden, as 0 or 1.
+    pub overridden: [Target; NUM_CHAIN_CONSTANTS],
+    /// `Target`s for the value of each constant, which is 0 if not overridden.
+    pub values: [Target; 8 * NUM_CHAIN_CONSTANTS],
+}
+
+impl ChainConstantsTarget {
+    /// Number of `Target`s required for the chain constants.
+    pub const SIZE: usize = 9 * NUM_CHAIN_CONSTANTS;
+
+    /// Extracts the chain constants `Target`s from the public input `Target`s.
+    /// The provided `pis` should start with the chain constants.
+    pub fn from_public_inputs(pis: &[Target]) -> Self {
+        Self {
+            overridden: pis[0..NUM_CHAIN_CONSTANTS].try_into().unwrap(),
+            values: pis[NUM_CHAIN_CONSTANTS..Self::SIZE].try_into().unwrap(),
+        }
+    }
+
+    /// If `condition`, returns the chain constants in `cc0`,
+    /// otherwise returns the chain constants in `cc1`.
+    pub fn select<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        condition: BoolTarget,
+        cc0: Self,
+        cc1: Self,
+    ) -> Self {
+        Self {
+            overridden: core::array::from_fn(|i| {
+                builder.select(condition, cc0.overridden[i], cc1.overridden[i])
+            }),
+            values: core::array::from_fn(|i| {
+                builder.select(condition, cc0.values[i], cc1.values[i])
+            }),
+        }
+    }
+
+    /// Connects the chain constants in `cc0` to those in `cc1`.
+    pub fn connect<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        cc0: Self,
+        cc1: Self,
+    ) {
+        for i in 0..NUM_CHAIN_CONSTANTS {
+            builder.connect(cc0.overridden[i], cc1.overridden[i]);
+        }
+        for i in 0..8 * NUM_CHAIN_CONSTANTS {
+            builder.connect(cc0.values[i], cc1.values[i]);
+        }
+    }
+
+    /// Checks that each override flag is a boolean, and that the value of each constant which
+    /// isn't overridden is 0, so that the chain constants have a single encoding.
+    pub fn assert_canonical<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) {
+        for (i, &overridden) in self.overridden.iter().enumerate() {
+            let overridden = BoolTarget::new_unsafe(overridden);
+            builder.assert_bool(overridden);
+            let not_overridden = builder.not(overridden);
+            for &limb in &self.values[8 * i..8 * (i + 1)] {
+                let constr = builder.mul(not_overridden.target, limb);
+                builder.assert_zero(constr);
+            }
+        }
+    }
+
+    /// Returns the limbs of the values of the constants as read by the kernel, i.e. the Ethereum
+    /// value of each constant which isn't overridden. The chain constants must be canonical, see
+    /// `assert_canonical`.
+    pub fn effective_values<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> [Target; 8 * NUM_CHAIN_CONSTANTS] {
+        let defaults = ChainConstants::ethereum_values().map(u256_limbs::<F>);
+        core::array::from_fn(|i| {
+            let not_overridden = builder.not(BoolTarget::new_unsafe(self.overridden[i / 8]));
+            builder.mul_const_add(
+                defaults[i / 8][i % 8],
+                not_overridden.target,
+                self.values[i],
+            )
+        })
+    }
+
+    /// Returns whether any constant is overridden. The override flags must be booleans, see
+    /// `assert_canonical`.
+    pub fn any_overridden<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> BoolTarget {
+        let mut any_overridden = builder._false();
+        for &overridden in &self.overridden {
+            any_overridden = builder.or(any_overridden, BoolTarget::new_unsafe(overridden));
+        }
+        any_overridden
+    }
+}
+
 /// Merkle caps and openings that form the proof of a single STARK.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct StarkProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
     /// Merkle cap of LDEs of trace values.
     pub trace_cap: MerkleCap<F, C::Hasher>,
@@ -779,7 +1679,12 @@ pub struct StarkProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>,
 
 /// A `StarkProof` along with some metadata about the initial Fiat-Shamir state, which is used when
 /// creating a recursive wrapper proof around a STARK proof.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    bound = "",
+    into = "StarkProofWithMetadataSerde<F, C, D>",
+    try_from = "StarkProofWithMetadataSerde<F, C, D>"
+)]
 pub struct StarkProofWithMetadata<F, C, const D: usize>
 where
     F: RichField + Extendable<D>,
@@ -791,6 +1696,57 @@ where
     pub(crate) proof: StarkProof<F, C, D>,
 }
 
+/// The serde representation of a `StarkProofWithMetadata`, where the initial Fiat-Shamir state is
+/// the array of its elements.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct StarkProofWithMetadataSerde<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    init_challenger_state: Vec<F>,
+    proof: StarkProof<F, C, D>,
+}
+
+impl<F, C, const D: usize> From<StarkProofWithMetadata<F, C, D>>
+    for StarkProofWithMetadataSerde<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn from(proof_with_metadata: StarkProofWithMetadata<F, C, D>) -> Self {
+        Self {
+            init_challenger_state: proof_with_metadata.init_challenger_state.as_ref().to_vec(),
+            proof: proof_with_metadata.proof,
+        }
+    }
+}
+
+impl<F, C, const D: usize> TryFrom<StarkProofWithMetadataSerde<F, C, D>>
+    for StarkProofWithMetadata<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    type Error = String;
+
+    fn try_from(serde: StarkProofWithMetadataSerde<F, C, D>) -> Result<Self, Self::Error> {
+        let width = <<C::Hasher as Hasher<F>>::Permutation as PlonkyPermutation<F>>::WIDTH;
+        if serde.init_challenger_state.len() != width {
+            return Err(format!(
+                "Expected a challenger state of {} elements, found {}",
+                width,
+                serde.init_challenger_state.len()
+            ));
+        }
+        Ok(Self {
+            init_challenger_state: PlonkyPermutation::new(serde.init_challenger_state),
+            proof: serde.proof,
+        })
+    }
+}
+
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> StarkProof<F, C, D> {
     /// Recover the length of the trace from a STARK proof and a STARK config.
     pub fn recover_degree_bits(&self, config: &StarkConfig) -> usize {
@@ -799,7 +1755,8 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> S
             .evals_proofs[0]
             .1;
         let lde_bits = config.fri_config.cap_height + initial_merkle_proof.siblings.len();
-        lde_bits - config.fri_config.rate_bits
+        // Committed polynomials have a higher degree than the trace in zero-knowledge mode.
+        lde_bits - config.fri_config.rate_bits - usize::from(config.zero_knowledge)
     }
 
     /// Returns the number of cross-table lookup polynomials computed for the current STARK.
@@ -859,7 +1816,8 @@ impl<const D: usize> StarkProofTarget<D> {
             .evals_proofs[0]
             .1;
         let lde_bits = config.fri_config.cap_height + initial_merkle_proof.siblings.len();
-        lde_bits - config.fri_config.rate_bits
+        // Committed polynomials have a higher degree than the trace in zero-knowledge mode.
+        lde_bits - config.fri_config.rate_bits - usize::from(config.zero_knowledge)
     }
 }
 
@@ -886,7 +1844,8 @@ pub(crate) struct StarkProofChallengesTarget<const D: usize> {
 }
 
 /// Purported values of each polynomial at the challenge point.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct StarkOpeningSet<F: RichField + Extendable<D>, const D: usize> {
     /// Openings of trace polynomials at `zeta`.
     pub local_values: Vec<F::Extension>,
@@ -1063,3 +2022,130 @@ impl<const D: usize> StarkOpeningSetTarget<D> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use plonky2::iop::witness::PartialWitness;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use super::*;
+    use crate::recursive_verifier::{add_virtual_public_values, set_public_value_targets};
+
+    type F = GoldilocksField;
+    type C = PoseidonGoldilocksConfig;
+    type H = <C as GenericConfig<2>>::InnerHasher;
+
+    #[test]
+    fn test_block_metadata_extensions() -> anyhow::Result<()> {
+        let mut builder = CircuitBuilder::<F, 2>::new(CircuitConfig::standard_recursion_config());
+        let public_values_target = add_virtual_public_values(&mut builder);
+        let hash = public_values_target.hash_circuit::<F, H, 2>(&mut builder);
+        builder.register_public_inputs(&hash.elements);
+        let data = builder.build::<C>();
+
+        let mut public_values = PublicValues::default();
+        let hash_circuit = |public_values: &PublicValues| -> anyhow::Result<Vec<F>> {
+            let mut pw = PartialWitness::new();
+            set_public_value_targets(&mut pw, &public_values_target, public_values).unwrap();
+            let proof = data.prove(pw)?;
+            Ok(proof.public_inputs[PublicValuesTarget::SIZE..].to_vec())
+        };
+
+        // Without extensions, the digest only covers the other public values.
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        let hash = public_values.hash::<F, H>().unwrap();
+        assert_eq!(hash, H::hash_no_pad(&pis[..PublicValuesTarget::BASE_SIZE]));
+        assert_eq!(hash_circuit(&public_values)?, hash.elements);
+
+        public_values
+            .block_metadata
+            .extensions
+            .set(BlockMetadataExtension::ParentBeaconBlockRoot, U256::MAX);
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        let hash = public_values.hash::<F, H>().unwrap();
+        assert_eq!(hash, H::hash_no_pad(&pis));
+        assert_eq!(hash_circuit(&public_values)?, hash.elements);
+        assert_eq!(
+            PublicValues::from_public_inputs(&pis)?
+                .block_metadata
+                .extensions,
+            public_values.block_metadata.extensions
+        );
+
+        // An absent extension must have a zero value.
+        let mut pis = pis;
+        pis[PublicValuesTarget::BASE_SIZE + BlockMetadataExtensionsTarget::SIZE - 1] = F::ONE;
+        assert!(PublicValues::from_public_inputs(&pis).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_public_values() -> anyhow::Result<()> {
+        let mut public_values = PublicValues::default();
+        let hash_without_extra_values = public_values.hash::<F, H>().unwrap();
+
+        // Declaring a slot changes the digest, even if its values are 0.
+        public_values.extra_public_values.slots[1] = Some(ExtraPublicValue::default());
+        let hash = public_values.hash::<F, H>().unwrap();
+        assert_ne!(hash, hash_without_extra_values);
+        assert_eq!(
+            hash,
+            H::hash_no_pad(&public_values.to_public_inputs::<F>().unwrap())
+        );
+
+        public_values.extra_public_values.slots[1] = Some(ExtraPublicValue {
+            before: U256::from(7),
+            after: U256::MAX,
+        });
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        assert_eq!(
+            PublicValues::from_public_inputs(&pis)?.extra_public_values,
+            public_values.extra_public_values
+        );
+
+        // An undeclared slot must have zero values.
+        let mut pis = pis;
+        pis[PublicValuesTarget::SIZE - ChainConstantsTarget::SIZE - 1] = F::ONE;
+        assert!(PublicValues::from_public_inputs(&pis).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_constants() -> anyhow::Result<()> {
+        let mut public_values = PublicValues::default();
+        let hash_without_overrides = public_values.hash::<F, H>().unwrap();
+        assert_eq!(
+            public_values
+                .chain_constants
+                .get(ChainConstant::MaxCodeSize),
+            U256::from(0x6000)
+        );
+
+        // Overriding a constant changes the digest, and the value read by the kernel.
+        let recipient = U256::from(0x4200);
+        public_values
+            .chain_constants
+            .set(ChainConstant::BaseFeeRecipient, recipient);
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        let hash = public_values.hash::<F, H>().unwrap();
+        assert_ne!(hash, hash_without_overrides);
+        assert_eq!(hash, H::hash_no_pad(&pis));
+        let values = public_values.chain_constants.values();
+        assert_eq!(values[ChainConstant::BaseFeeRecipient as usize], recipient);
+        assert_eq!(
+            values[ChainConstant::MaxInitcodeSize as usize],
+            U256::from(0xc000)
+        );
+        assert_eq!(
+            PublicValues::from_public_inputs(&pis)?.chain_constants,
+            public_values.chain_constants
+        );
+
+        // A constant which isn't overridden must have a zero value.
+        let mut pis = pis;
+        pis[PublicValuesTarget::SIZE - 1] = F::ONE;
+        assert!(PublicValues::from_public_inputs(&pis).is_err());
+        Ok(())
+    }
+}
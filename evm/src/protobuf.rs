@@ -0,0 +1,460 @@
+//! Protobuf messages for public values and recursive proofs, for gRPC services.
+//!
+//! The messages are described for other languages by `proto/plonky2_evm.proto`, and proofs use
+//! the messages of [`plonky2::plonk::protobuf`]. Hashes and integers are encoded as 32 big-endian
+//! bytes, and addresses as 20 bytes.
+
+use anyhow::{anyhow, ensure, Result};
+use ethereum_types::{Address, H256, U256};
+use plonky2::field::extension::Extendable;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::config::GenericConfig;
+use plonky2::plonk::protobuf::ProofWithPublicInputs;
+
+use crate::proof;
+use crate::prover_tasks::RecursiveProofResponse;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TrieRoots {
+    #[prost(bytes = "vec", tag = "1")]
+    pub state_root: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub transactions_root: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub receipts_root: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BlockMetadata {
+    #[prost(bytes = "vec", tag = "1")]
+    pub block_beneficiary: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub block_timestamp: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub block_number: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub block_difficulty: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub block_random: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub block_gaslimit: Vec<u8>,
+    #[prost(bytes = "vec", tag = "7")]
+    pub block_chain_id: Vec<u8>,
+    #[prost(bytes = "vec", tag = "8")]
+    pub block_base_fee: Vec<u8>,
+    #[prost(bytes = "vec", tag = "9")]
+    pub block_gas_used: Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "10")]
+    pub block_bloom: Vec<Vec<u8>>,
+    #[prost(message, repeated, tag = "11")]
+    pub extensions: Vec<BlockMetadataExtension>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BlockMetadataExtension {
+    #[prost(uint32, tag = "1")]
+    pub slot: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BlockHashes {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub prev_hashes: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub cur_hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ExtraBlockData {
+    #[prost(bytes = "vec", tag = "1")]
+    pub genesis_state_trie_root: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub txn_number_before: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub txn_number_after: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub gas_used_before: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub gas_used_after: Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "6")]
+    pub block_bloom_before: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "7")]
+    pub block_bloom_after: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PublicValues {
+    #[prost(message, optional, tag = "1")]
+    pub trie_roots_before: Option<TrieRoots>,
+    #[prost(message, optional, tag = "2")]
+    pub trie_roots_after: Option<TrieRoots>,
+    #[prost(message, optional, tag = "3")]
+    pub block_metadata: Option<BlockMetadata>,
+    #[prost(message, optional, tag = "4")]
+    pub block_hashes: Option<BlockHashes>,
+    #[prost(message, optional, tag = "5")]
+    pub extra_block_data: Option<ExtraBlockData>,
+    #[prost(message, repeated, tag = "6")]
+    pub extra_public_values: Vec<ExtraPublicValue>,
+    #[prost(message, repeated, tag = "7")]
+    pub chain_constants: Vec<ChainConstant>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ExtraPublicValue {
+    #[prost(uint32, tag = "1")]
+    pub slot: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub before: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub after: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ChainConstant {
+    #[prost(uint32, tag = "1")]
+    pub slot: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
+/// A root, aggregation or block proof, with its public values.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RecursiveProof {
+    #[prost(message, optional, tag = "1")]
+    pub proof: Option<ProofWithPublicInputs>,
+    #[prost(message, optional, tag = "2")]
+    pub public_values: Option<PublicValues>,
+}
+
+impl From<&proof::PublicValues> for PublicValues {
+    fn from(public_values: &proof::PublicValues) -> Self {
+        let trie_roots = |roots: &proof::TrieRoots| TrieRoots {
+            state_root: h256_to_proto(roots.state_root),
+            transactions_root: h256_to_proto(roots.transactions_root),
+            receipts_root: h256_to_proto(roots.receipts_root),
+        };
+        let md = &public_values.block_metadata;
+        let hashes = &public_values.block_hashes;
+        let ed = &public_values.extra_block_data;
+        Self {
+            trie_roots_before: Some(trie_roots(&public_values.trie_roots_before)),
+            trie_roots_after: Some(trie_roots(&public_values.trie_roots_after)),
+            block_metadata: Some(BlockMetadata {
+                block_beneficiary: md.block_beneficiary.as_bytes().to_vec(),
+                block_timestamp: u256_to_proto(md.block_timestamp),
+                block_number: u256_to_proto(md.block_number),
+                block_difficulty: u256_to_proto(md.block_difficulty),
+                block_random: h256_to_proto(md.block_random),
+                block_gaslimit: u256_to_proto(md.block_gaslimit),
+                block_chain_id: u256_to_proto(md.block_chain_id),
+                block_base_fee: u256_to_proto(md.block_base_fee),
+                block_gas_used: u256_to_proto(md.block_gas_used),
+                block_bloom: md.block_bloom.iter().map(|&x| u256_to_proto(x)).collect(),
+                extensions: md
+                    .extensions
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(slot, value)| {
+                        value.map(|value| BlockMetadataExtension {
+                            slot: slot as u32,
+                            value: u256_to_proto(value),
+                        })
+                    })
+                    .collect(),
+            }),
+            block_hashes: Some(BlockHashes {
+                prev_hashes: hashes
+                    .prev_hashes
+                    .iter()
+                    .map(|&hash| h256_to_proto(hash))
+                    .collect(),
+                cur_hash: h256_to_proto(hashes.cur_hash),
+            }),
+            extra_block_data: Some(ExtraBlockData {
+                genesis_state_trie_root: h256_to_proto(ed.genesis_state_trie_root),
+                txn_number_before: u256_to_proto(ed.txn_number_before),
+                txn_number_after: u256_to_proto(ed.txn_number_after),
+                gas_used_before: u256_to_proto(ed.gas_used_before),
+                gas_used_after: u256_to_proto(ed.gas_used_after),
+                block_bloom_before: ed
+                    .block_bloom_before
+                    .iter()
+                    .map(|&x| u256_to_proto(x))
+                    .collect(),
+                block_bloom_after: ed
+                    .block_bloom_after
+                    .iter()
+                    .map(|&x| u256_to_proto(x))
+                    .collect(),
+            }),
+            extra_public_values: public_values
+                .extra_public_values
+                .slots
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, value)| {
+                    value.map(|value| ExtraPublicValue {
+                        slot: slot as u32,
+                        before: u256_to_proto(value.before),
+                        after: u256_to_proto(value.after),
+                    })
+                })
+                .collect(),
+            chain_constants: public_values
+                .chain_constants
+                .overrides
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, value)| {
+                    value.map(|value| ChainConstant {
+                        slot: slot as u32,
+                        value: u256_to_proto(value),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<PublicValues> for proof::PublicValues {
+    type Error = anyhow::Error;
+
+    fn try_from(public_values: PublicValues) -> Result<Self> {
+        let trie_roots = |roots: Option<TrieRoots>, name: &str| -> Result<proof::TrieRoots> {
+            let roots = required(roots, name)?;
+            Ok(proof::TrieRoots {
+                state_root: h256_from_proto(&roots.state_root)?,
+                transactions_root: h256_from_proto(&roots.transactions_root)?,
+                receipts_root: h256_from_proto(&roots.receipts_root)?,
+            })
+        };
+        let md = required(public_values.block_metadata, "block_metadata")?;
+        let hashes = required(public_values.block_hashes, "block_hashes")?;
+        let ed = required(public_values.extra_block_data, "extra_block_data")?;
+        ensure!(
+            md.block_beneficiary.len() == Address::len_bytes(),
+            "Expected an address of 20 bytes, found {}",
+            md.block_beneficiary.len()
+        );
+        let mut extensions = proof::BlockMetadataExtensions::default();
+        for extension in &md.extensions {
+            let slot = extension.slot as usize;
+            ensure!(
+                slot < proof::NUM_BLOCK_METADATA_EXTENSIONS,
+                "Invalid block metadata extension slot {slot}"
+            );
+            ensure!(
+                extensions.fields[slot].is_none(),
+                "Duplicate block metadata extension slot {slot}"
+            );
+            extensions.fields[slot] = Some(u256_from_proto(&extension.value)?);
+        }
+        let mut extra_public_values = proof::ExtraPublicValues::default();
+        for value in &public_values.extra_public_values {
+            let slot = value.slot as usize;
+            ensure!(
+                slot < proof::NUM_EXTRA_PUBLIC_VALUES,
+                "Invalid extra public value slot {slot}"
+            );
+            ensure!(
+                extra_public_values.slots[slot].is_none(),
+                "Duplicate extra public value slot {slot}"
+            );
+            extra_public_values.slots[slot] = Some(proof::ExtraPublicValue {
+                before: u256_from_proto(&value.before)?,
+                after: u256_from_proto(&value.after)?,
+            });
+        }
+        let mut chain_constants = proof::ChainConstants::default();
+        for constant in &public_values.chain_constants {
+            let slot = constant.slot as usize;
+            ensure!(
+                slot < proof::NUM_CHAIN_CONSTANTS,
+                "Invalid chain constant slot {slot}"
+            );
+            ensure!(
+                chain_constants.overrides[slot].is_none(),
+                "Duplicate chain constant slot {slot}"
+            );
+            chain_constants.overrides[slot] = Some(u256_from_proto(&constant.value)?);
+        }
+        ensure!(
+            hashes.prev_hashes.len() == 256,
+            "Expected 256 previous block hashes, found {}",
+            hashes.prev_hashes.len()
+        );
+
+        Ok(Self {
+            trie_roots_before: trie_roots(public_values.trie_roots_before, "trie_roots_before")?,
+            trie_roots_after: trie_roots(public_values.trie_roots_after, "trie_roots_after")?,
+            block_metadata: proof::BlockMetadata {
+                block_beneficiary: Address::from_slice(&md.block_beneficiary),
+                block_timestamp: u256_from_proto(&md.block_timestamp)?,
+                block_number: u256_from_proto(&md.block_number)?,
+                block_difficulty: u256_from_proto(&md.block_difficulty)?,
+                block_random: h256_from_proto(&md.block_random)?,
+                block_gaslimit: u256_from_proto(&md.block_gaslimit)?,
+                block_chain_id: u256_from_proto(&md.block_chain_id)?,
+                block_base_fee: u256_from_proto(&md.block_base_fee)?,
+                block_gas_used: u256_from_proto(&md.block_gas_used)?,
+                block_bloom: bloom_from_proto(&md.block_bloom)?,
+                extensions,
+            },
+            block_hashes: proof::BlockHashes {
+                prev_hashes: hashes
+                    .prev_hashes
+                    .iter()
+                    .map(|hash| h256_from_proto(hash))
+                    .collect::<Result<_>>()?,
+                cur_hash: h256_from_proto(&hashes.cur_hash)?,
+            },
+            extra_block_data: proof::ExtraBlockData {
+                genesis_state_trie_root: h256_from_proto(&ed.genesis_state_trie_root)?,
+                txn_number_before: u256_from_proto(&ed.txn_number_before)?,
+                txn_number_after: u256_from_proto(&ed.txn_number_after)?,
+                gas_used_before: u256_from_proto(&ed.gas_used_before)?,
+                gas_used_after: u256_from_proto(&ed.gas_used_after)?,
+                block_bloom_before: bloom_from_proto(&ed.block_bloom_before)?,
+                block_bloom_after: bloom_from_proto(&ed.block_bloom_after)?,
+            },
+            extra_public_values,
+            chain_constants,
+        })
+    }
+}
+
+impl<F, C, const D: usize> From<&RecursiveProofResponse<F, C, D>> for RecursiveProof
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn from(proof: &RecursiveProofResponse<F, C, D>) -> Self {
+        Self {
+            proof: Some((&proof.proof).into()),
+            public_values: Some((&proof.public_values).into()),
+        }
+    }
+}
+
+impl<F, C, const D: usize> TryFrom<RecursiveProof> for RecursiveProofResponse<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(proof: RecursiveProof) -> Result<Self> {
+        Ok(Self {
+            proof: required(proof.proof, "proof")?.try_into()?,
+            public_values: required(proof.public_values, "public_values")?.try_into()?,
+        })
+    }
+}
+
+fn required<T>(message: Option<T>, name: &str) -> Result<T> {
+    message.ok_or_else(|| anyhow!("Missing field `{name}`"))
+}
+
+fn h256_to_proto(hash: H256) -> Vec<u8> {
+    hash.as_bytes().to_vec()
+}
+
+fn h256_from_proto(bytes: &[u8]) -> Result<H256> {
+    ensure!(
+        bytes.len() == 32,
+        "Expected 32 bytes, found {}",
+        bytes.len()
+    );
+    Ok(H256::from_slice(bytes))
+}
+
+fn u256_to_proto(x: U256) -> Vec<u8> {
+    let mut bytes = vec![0; 32];
+    x.to_big_endian(&mut bytes);
+    bytes
+}
+
+fn u256_from_proto(bytes: &[u8]) -> Result<U256> {
+    Ok(U256::from_big_endian(h256_from_proto(bytes)?.as_bytes()))
+}
+
+fn bloom_from_proto(words: &[Vec<u8>]) -> Result<[U256; 8]> {
+    ensure!(
+        words.len() == 8,
+        "Expected a bloom filter of 8 words, found {}",
+        words.len()
+    );
+    let words = words
+        .iter()
+        .map(|word| u256_from_proto(word))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(words.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+
+    #[test]
+    fn test_public_values_round_trip() -> Result<()> {
+        let mut public_values = proof::PublicValues {
+            trie_roots_after: proof::TrieRoots {
+                state_root: H256::repeat_byte(1),
+                ..proof::TrieRoots::default()
+            },
+            block_metadata: proof::BlockMetadata {
+                block_beneficiary: Address::repeat_byte(2),
+                block_number: 42.into(),
+                block_bloom: [U256::MAX; 8],
+                ..proof::BlockMetadata::default()
+            },
+            ..proof::PublicValues::default()
+        };
+        let bytes = PublicValues::from(&public_values).encode_to_vec();
+        let decoded: proof::PublicValues = PublicValues::decode(bytes.as_slice())?.try_into()?;
+        assert_eq!(
+            decoded.abi_encode().unwrap(),
+            public_values.abi_encode().unwrap()
+        );
+
+        public_values
+            .block_metadata
+            .extensions
+            .set(proof::BlockMetadataExtension::ExcessBlobGas, 7.into());
+        let bytes = PublicValues::from(&public_values).encode_to_vec();
+        let decoded: proof::PublicValues = PublicValues::decode(bytes.as_slice())?.try_into()?;
+        assert_eq!(
+            decoded.block_metadata.extensions,
+            public_values.block_metadata.extensions
+        );
+
+        public_values.extra_public_values.slots[2] = Some(proof::ExtraPublicValue {
+            before: 1.into(),
+            after: U256::MAX,
+        });
+        let bytes = PublicValues::from(&public_values).encode_to_vec();
+        let decoded: proof::PublicValues = PublicValues::decode(bytes.as_slice())?.try_into()?;
+        assert_eq!(
+            decoded.extra_public_values,
+            public_values.extra_public_values
+        );
+
+        public_values
+            .chain_constants
+            .set(proof::ChainConstant::BaseFeeRecipient, 0x4200.into());
+        let bytes = PublicValues::from(&public_values).encode_to_vec();
+        let decoded: proof::PublicValues = PublicValues::decode(bytes.as_slice())?.try_into()?;
+        assert_eq!(decoded.chain_constants, public_values.chain_constants);
+
+        let mut message = PublicValues::from(&public_values);
+        message.block_hashes.as_mut().unwrap().prev_hashes.pop();
+        assert!(proof::PublicValues::try_from(message).is_err());
+        Ok(())
+    }
+}
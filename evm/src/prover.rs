@@ -1,17 +1,19 @@
-use anyhow::{ensure, Result};
+use anyhow::{ensure, Context, Result};
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use plonky2::field::extension::Extendable;
 use plonky2::field::packable::Packable;
 use plonky2::field::packed::PackedField;
 use plonky2::field::polynomial::{PolynomialCoeffs, PolynomialValues};
-use plonky2::field::types::Field;
+use plonky2::field::types::{Field, Sample};
 use plonky2::field::zero_poly_coset::ZeroPolyOnCoset;
 use plonky2::fri::oracle::PolynomialBatch;
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::Challenger;
+use plonky2::iop::transcript::Transcript;
 use plonky2::plonk::config::GenericConfig;
 use plonky2::timed;
+use plonky2::util::randomness::ProverRandomness;
 use plonky2::util::timing::TimingTree;
 use plonky2::util::transpose;
 use plonky2_maybe_rayon::*;
@@ -27,10 +29,11 @@ use crate::cross_table_lookup::{
 };
 use crate::evaluation_frame::StarkEvaluationFrame;
 use crate::generation::outputs::GenerationOutputs;
-use crate::generation::{generate_traces, GenerationInputs};
+use crate::generation::{generate_traces, generate_traces_with_limits, GenerationInputs};
 use crate::get_challenges::observe_public_values;
 use crate::lookup::{lookup_helper_columns, Lookup, LookupCheckVars};
 use crate::proof::{AllProof, PublicValues, StarkOpeningSet, StarkProof, StarkProofWithMetadata};
+use crate::sanity_check::check_traces;
 use crate::stark::Stark;
 use crate::vanishing_poly::eval_vanishing_poly;
 #[cfg(test)]
@@ -38,6 +41,33 @@ use crate::{
     cross_table_lookup::testutils::check_ctls, verifier::testutils::get_memory_extra_looking_values,
 };
 
+/// Options tuning how proofs are computed, which don't affect their validity, unlike the
+/// [`StarkConfig`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProverOptions {
+    /// Whether the prover checks the traces against all constraints before committing to them,
+    /// failing with the first violation found. This is slow, and only meant for debugging.
+    pub check_traces: bool,
+    /// Whether trace generation records the log of all memory operations in its outputs, see
+    /// `MemoryLog`. This uses a lot of memory, and is only meant for auditing and debugging.
+    pub record_memory_log: bool,
+    /// Whether the prover commits to the traces and auxiliary polynomials of all tables
+    /// concurrently. The rest of each table proof depends on the challenger state left by the
+    /// previous table, so it is still done in order. The timings of individual tables are not
+    /// recorded in the concurrent phases.
+    pub concurrent_tables: bool,
+    /// The source of the masks and salts of zero-knowledge proofs. The randomness of each table is
+    /// derived from it and the table, so a seeded one gives the same proofs whether or not tables
+    /// are processed concurrently.
+    pub randomness: ProverRandomness,
+}
+
+/// The purposes, in the sense of [`ProverRandomness::derive`], of the randomness of a table proof.
+const TRACE_RANDOMNESS: u64 = 0;
+const AUXILIARY_RANDOMNESS: u64 = 1;
+const QUOTIENT_RANDOMNESS: u64 = 2;
+const MASK_RANDOMNESS: u64 = 3;
+
 /// Generate traces, then create all STARK proofs.
 pub fn prove<F, C, const D: usize>(
     all_stark: &AllStark<F, D>,
@@ -49,7 +79,22 @@ where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
 {
-    let (proof, _outputs) = prove_with_outputs(all_stark, config, inputs, timing)?;
+    prove_with_options(all_stark, config, inputs, &ProverOptions::default(), timing)
+}
+
+/// Generate traces, then create all STARK proofs, as tuned by `options`.
+pub fn prove_with_options<F, C, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+    inputs: GenerationInputs,
+    options: &ProverOptions,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let (proof, _outputs) = prove_with_outputs(all_stark, config, inputs, options, timing)?;
     Ok(proof)
 }
 
@@ -59,6 +104,7 @@ pub fn prove_with_outputs<F, C, const D: usize>(
     all_stark: &AllStark<F, D>,
     config: &StarkConfig,
     inputs: GenerationInputs,
+    options: &ProverOptions,
     timing: &mut TimingTree,
 ) -> Result<(AllProof<F, C, D>, GenerationOutputs)>
 where
@@ -66,54 +112,124 @@ where
     C: GenericConfig<D, F = F>,
 {
     timed!(timing, "build kernel", Lazy::force(&KERNEL));
+    let max_degree_bits = [config.max_degree_bits::<F>(); NUM_TABLES];
     let (traces, public_values, outputs) = timed!(
         timing,
         "generate all traces",
-        generate_traces(all_stark, inputs, config, timing)?
+        generate_traces_with_limits(all_stark, inputs, config, &max_degree_bits, options, timing)?
     );
-    let proof = prove_with_traces(all_stark, config, traces, public_values, timing)?;
+    let proof = prove_with_traces(all_stark, config, traces, public_values, options, timing)?;
     Ok((proof, outputs))
 }
 
+/// Generate traces, then create all STARK proofs. Returns the Fiat-Shamir transcript of the
+/// proof, intended for auditing, in addition to the proof.
+pub fn prove_with_transcript<F, C, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+    inputs: GenerationInputs,
+    timing: &mut TimingTree,
+) -> Result<(AllProof<F, C, D>, Transcript<F>)>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    timed!(timing, "build kernel", Lazy::force(&KERNEL));
+    let (traces, public_values, _outputs) = timed!(
+        timing,
+        "generate all traces",
+        generate_traces(all_stark, inputs, config, timing)?
+    );
+    let mut challenger = Challenger::new_recording();
+    let proof = prove_with_challenger(
+        all_stark,
+        config,
+        traces,
+        public_values,
+        &ProverOptions::default(),
+        &mut challenger,
+        timing,
+    )?;
+    let transcript = challenger
+        .take_transcript()
+        .expect("The challenger is recording");
+    Ok((proof, transcript))
+}
+
 /// Compute all STARK proofs.
 pub(crate) fn prove_with_traces<F, C, const D: usize>(
     all_stark: &AllStark<F, D>,
     config: &StarkConfig,
     trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
     public_values: PublicValues,
+    options: &ProverOptions,
     timing: &mut TimingTree,
 ) -> Result<AllProof<F, C, D>>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
 {
-    let rate_bits = config.fri_config.rate_bits;
-    let cap_height = config.fri_config.cap_height;
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    prove_with_challenger(
+        all_stark,
+        config,
+        trace_poly_values,
+        public_values,
+        options,
+        &mut challenger,
+        timing,
+    )
+}
+
+/// Compute all STARK proofs, with the given challenger, which must be fresh.
+fn prove_with_challenger<F, C, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+    trace_poly_values: [Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: PublicValues,
+    options: &ProverOptions,
+    challenger: &mut Challenger<F, C::Hasher>,
+    timing: &mut TimingTree,
+) -> Result<AllProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    if options.check_traces {
+        timed!(
+            timing,
+            "check traces",
+            check_traces(all_stark, &trace_poly_values, &public_values, config)?
+        );
+    }
+
+    for (table, trace) in Table::all().into_iter().zip(&trace_poly_values) {
+        config
+            .check_zk_degree_bits::<D>(log2_strict(trace[0].len()))
+            .with_context(|| format!("Can't mask the {table:?} trace"))?;
+    }
 
     // For each STARK, we compute the polynomial commitments for the polynomials interpolating its trace.
     let trace_commitments = timed!(
         timing,
         "compute all trace commitments",
-        trace_poly_values
-            .iter()
-            .zip_eq(Table::all())
-            .map(|(trace, table)| {
-                timed!(
+        map_tables(options, timing, |table, timing| {
+            timed!(
+                timing,
+                &format!("compute trace commitment for {:?}", table),
+                commit_values::<F, C, D>(
+                    // TODO: Cloning this isn't great; consider having `from_values` accept a reference,
+                    // or having `compute_permutation_z_polys` read trace values from the `PolynomialBatch`.
+                    trace_poly_values[table as usize].clone(),
+                    config,
+                    options
+                        .randomness
+                        .derive(table as u64)
+                        .derive(TRACE_RANDOMNESS),
                     timing,
-                    &format!("compute trace commitment for {:?}", table),
-                    PolynomialBatch::<F, C, D>::from_values(
-                        // TODO: Cloning this isn't great; consider having `from_values` accept a reference,
-                        // or having `compute_permutation_z_polys` read trace values from the `PolynomialBatch`.
-                        trace.clone(),
-                        rate_bits,
-                        false,
-                        cap_height,
-                        timing,
-                        None,
-                    )
                 )
-            })
-            .collect::<Vec<_>>()
+            )
+        })
     );
 
     // Get the Merkle caps for all trace commitments and observe them.
@@ -121,16 +237,17 @@ where
         .iter()
         .map(|c| c.merkle_tree.cap.clone())
         .collect::<Vec<_>>();
-    let mut challenger = Challenger::<F, C::Hasher>::new();
+    challenger.set_transcript_label("trace caps");
     for cap in &trace_caps {
         challenger.observe_cap(cap);
     }
 
-    observe_public_values::<F, C, D>(&mut challenger, &public_values)
+    observe_public_values::<F, C, D>(challenger, &public_values)
         .map_err(|_| anyhow::Error::msg("Invalid conversion of public values."))?;
 
     // Get challenges for the cross-table lookups.
-    let ctl_challenges = get_grand_product_challenge_set(&mut challenger, config.num_challenges);
+    challenger.set_transcript_label("ctl challenges");
+    let ctl_challenges = get_grand_product_challenge_set(challenger, config.num_challenges);
     // For each STARK, compute its cross-table lookup Z polynomials and get the associated `CtlData`.
     let ctl_data_per_table = timed!(
         timing,
@@ -142,6 +259,34 @@ where
         )
     );
 
+    // The auxiliary polynomials only depend on the CTL challenges, so we can commit to them for all
+    // tables before sampling any table-specific challenge.
+    let lookups = all_stark.lookups();
+    let constraint_degrees = all_stark.constraint_degrees();
+    let auxiliary_polys_commitments = timed!(
+        timing,
+        "compute all auxiliary polynomials commitments",
+        map_tables(options, timing, |table, timing| {
+            timed!(
+                timing,
+                &format!("compute auxiliary polynomials commitment for {:?}", table),
+                compute_auxiliary_polys_commitment::<F, C, D>(
+                    &trace_poly_values[table as usize],
+                    &lookups[table as usize],
+                    constraint_degrees[table as usize],
+                    &ctl_data_per_table[table as usize],
+                    &ctl_challenges,
+                    config,
+                    options
+                        .randomness
+                        .derive(table as u64)
+                        .derive(AUXILIARY_RANDOMNESS),
+                    timing,
+                )
+            )
+        })
+    );
+
     let stark_proofs = timed!(
         timing,
         "compute all proofs given commitments",
@@ -150,9 +295,11 @@ where
             config,
             &trace_poly_values,
             trace_commitments,
+            auxiliary_polys_commitments,
             ctl_data_per_table,
-            &mut challenger,
+            challenger,
             &ctl_challenges,
+            options.randomness,
             timing
         )?
     );
@@ -178,22 +325,27 @@ where
 /// and we have the cross-table lookup data for each table, including the associated challenges.
 /// - `trace_poly_values` are the trace values for each STARK.
 /// - `trace_commitments` are the trace polynomials commitments for each STARK.
+/// - `auxiliary_polys_commitments` are the auxiliary polynomials commitments for each STARK.
 /// - `ctl_data_per_table` group all the cross-table lookup data for each STARK.
+/// - `randomness` is the randomness of all tables, from which that of each table is derived.
 /// Each STARK uses its associated data to generate a proof.
 fn prove_with_commitments<F, C, const D: usize>(
     all_stark: &AllStark<F, D>,
     config: &StarkConfig,
     trace_poly_values: &[Vec<PolynomialValues<F>>; NUM_TABLES],
     trace_commitments: Vec<PolynomialBatch<F, C, D>>,
+    auxiliary_polys_commitments: Vec<PolynomialBatch<F, C, D>>,
     ctl_data_per_table: [CtlData<F>; NUM_TABLES],
     challenger: &mut Challenger<F, C::Hasher>,
     ctl_challenges: &GrandProductChallengeSet<F>,
+    randomness: ProverRandomness,
     timing: &mut TimingTree,
 ) -> Result<[StarkProofWithMetadata<F, C, D>; NUM_TABLES]>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
 {
+    challenger.set_transcript_scope(&format!("{:?}", Table::Arithmetic));
     let arithmetic_proof = timed!(
         timing,
         "prove Arithmetic STARK",
@@ -202,12 +354,15 @@ where
             config,
             &trace_poly_values[Table::Arithmetic as usize],
             &trace_commitments[Table::Arithmetic as usize],
+            &auxiliary_polys_commitments[Table::Arithmetic as usize],
             &ctl_data_per_table[Table::Arithmetic as usize],
             ctl_challenges,
+            randomness.derive(Table::Arithmetic as u64),
             challenger,
             timing,
         )?
     );
+    challenger.set_transcript_scope(&format!("{:?}", Table::BytePacking));
     let byte_packing_proof = timed!(
         timing,
         "prove byte packing STARK",
@@ -216,12 +371,15 @@ where
             config,
             &trace_poly_values[Table::BytePacking as usize],
             &trace_commitments[Table::BytePacking as usize],
+            &auxiliary_polys_commitments[Table::BytePacking as usize],
             &ctl_data_per_table[Table::BytePacking as usize],
             ctl_challenges,
+            randomness.derive(Table::BytePacking as u64),
             challenger,
             timing,
         )?
     );
+    challenger.set_transcript_scope(&format!("{:?}", Table::Cpu));
     let cpu_proof = timed!(
         timing,
         "prove CPU STARK",
@@ -230,12 +388,15 @@ where
             config,
             &trace_poly_values[Table::Cpu as usize],
             &trace_commitments[Table::Cpu as usize],
+            &auxiliary_polys_commitments[Table::Cpu as usize],
             &ctl_data_per_table[Table::Cpu as usize],
             ctl_challenges,
+            randomness.derive(Table::Cpu as u64),
             challenger,
             timing,
         )?
     );
+    challenger.set_transcript_scope(&format!("{:?}", Table::Keccak));
     let keccak_proof = timed!(
         timing,
         "prove Keccak STARK",
@@ -244,12 +405,15 @@ where
             config,
             &trace_poly_values[Table::Keccak as usize],
             &trace_commitments[Table::Keccak as usize],
+            &auxiliary_polys_commitments[Table::Keccak as usize],
             &ctl_data_per_table[Table::Keccak as usize],
             ctl_challenges,
+            randomness.derive(Table::Keccak as u64),
             challenger,
             timing,
         )?
     );
+    challenger.set_transcript_scope(&format!("{:?}", Table::KeccakSponge));
     let keccak_sponge_proof = timed!(
         timing,
         "prove Keccak sponge STARK",
@@ -258,12 +422,15 @@ where
             config,
             &trace_poly_values[Table::KeccakSponge as usize],
             &trace_commitments[Table::KeccakSponge as usize],
+            &auxiliary_polys_commitments[Table::KeccakSponge as usize],
             &ctl_data_per_table[Table::KeccakSponge as usize],
             ctl_challenges,
+            randomness.derive(Table::KeccakSponge as u64),
             challenger,
             timing,
         )?
     );
+    challenger.set_transcript_scope(&format!("{:?}", Table::Logic));
     let logic_proof = timed!(
         timing,
         "prove logic STARK",
@@ -272,12 +439,15 @@ where
             config,
             &trace_poly_values[Table::Logic as usize],
             &trace_commitments[Table::Logic as usize],
+            &auxiliary_polys_commitments[Table::Logic as usize],
             &ctl_data_per_table[Table::Logic as usize],
             ctl_challenges,
+            randomness.derive(Table::Logic as u64),
             challenger,
             timing,
         )?
     );
+    challenger.set_transcript_scope(&format!("{:?}", Table::Memory));
     let memory_proof = timed!(
         timing,
         "prove memory STARK",
@@ -286,8 +456,10 @@ where
             config,
             &trace_poly_values[Table::Memory as usize],
             &trace_commitments[Table::Memory as usize],
+            &auxiliary_polys_commitments[Table::Memory as usize],
             &ctl_data_per_table[Table::Memory as usize],
             ctl_challenges,
+            randomness.derive(Table::Memory as u64),
             challenger,
             timing,
         )?
@@ -308,13 +480,18 @@ where
 /// - the initial state of the challenger,
 /// - all the requires Merkle caps,
 /// - all the required polynomial and FRI argument openings.
+///
+/// The quotient polynomials are masked and salted with randomness derived from `randomness`, the
+/// randomness of the table.
 pub(crate) fn prove_single_table<F, C, S, const D: usize>(
     stark: &S,
     config: &StarkConfig,
     trace_poly_values: &[PolynomialValues<F>],
     trace_commitment: &PolynomialBatch<F, C, D>,
+    auxiliary_polys_commitment: &PolynomialBatch<F, C, D>,
     ctl_data: &CtlData<F>,
     ctl_challenges: &GrandProductChallengeSet<F>,
+    randomness: ProverRandomness,
     challenger: &mut Challenger<F, C::Hasher>,
     timing: &mut TimingTree,
 ) -> Result<StarkProofWithMetadata<F, C, D>>
@@ -329,13 +506,12 @@ where
     let rate_bits = config.fri_config.rate_bits;
     let cap_height = config.fri_config.cap_height;
     assert!(
-        fri_params.total_arities() <= degree_bits + rate_bits - cap_height,
+        fri_params.total_arities() <= fri_params.lde_bits() - cap_height,
         "FRI total reduction arity is too large.",
     );
 
     let init_challenger_state = challenger.compact();
 
-    let constraint_degree = stark.constraint_degree();
     let lookup_challenges = stark.uses_lookups().then(|| {
         ctl_challenges
             .challenges
@@ -344,54 +520,13 @@ where
             .collect::<Vec<_>>()
     });
     let lookups = stark.lookups();
-    let lookup_helper_columns = timed!(
-        timing,
-        "compute lookup helper columns",
-        lookup_challenges.as_ref().map(|challenges| {
-            let mut columns = Vec::new();
-            for lookup in &lookups {
-                for &challenge in challenges {
-                    columns.extend(lookup_helper_columns(
-                        lookup,
-                        trace_poly_values,
-                        challenge,
-                        constraint_degree,
-                    ));
-                }
-            }
-            columns
-        })
-    );
-    let num_lookup_columns = lookup_helper_columns.as_ref().map(|v| v.len()).unwrap_or(0);
-
-    // We add CTLs to the permutation arguments so that we can batch commit to
-    // all auxiliary polynomials.
-    let auxiliary_polys = match lookup_helper_columns {
-        None => ctl_data.z_polys(),
-        Some(mut lookup_columns) => {
-            lookup_columns.extend(ctl_data.z_polys());
-            lookup_columns
-        }
-    };
-    assert!(!auxiliary_polys.is_empty(), "No CTL?");
-
-    // Get the polynomial commitments for all auxiliary polynomials.
-    let auxiliary_polys_commitment = timed!(
-        timing,
-        "compute auxiliary polynomials commitment",
-        PolynomialBatch::from_values(
-            auxiliary_polys,
-            rate_bits,
-            false,
-            config.fri_config.cap_height,
-            timing,
-            None,
-        )
-    );
+    let num_lookup_columns = stark.num_lookup_helper_columns(config);
 
     let auxiliary_polys_cap = auxiliary_polys_commitment.merkle_tree.cap.clone();
+    challenger.set_transcript_label("auxiliary polys cap");
     challenger.observe_cap(&auxiliary_polys_cap);
 
+    challenger.set_transcript_label("stark alphas");
     let alphas = challenger.get_n_challenges(config.num_challenges);
 
     #[cfg(test)]
@@ -399,7 +534,7 @@ where
         check_constraints(
             stark,
             trace_commitment,
-            &auxiliary_polys_commitment,
+            auxiliary_polys_commitment,
             lookup_challenges.as_ref(),
             &lookups,
             ctl_data,
@@ -415,7 +550,7 @@ where
         compute_quotient_polys::<F, <F as Packable>::Packing, C, S, D>(
             stark,
             trace_commitment,
-            &auxiliary_polys_commitment,
+            auxiliary_polys_commitment,
             lookup_challenges.as_ref(),
             &lookups,
             ctl_data,
@@ -425,6 +560,7 @@ where
             config,
         )
     );
+    let num_quotient_chunks = stark.num_quotient_chunks(config);
     let all_quotient_chunks = timed!(
         timing,
         "split quotient polys",
@@ -432,32 +568,47 @@ where
             .into_par_iter()
             .flat_map(|mut quotient_poly| {
                 quotient_poly
-                    .trim_to_len(degree * stark.quotient_degree_factor())
+                    .trim_to_len(degree * num_quotient_chunks)
                     .expect(
                         "Quotient has failed, the vanishing polynomial is not divisible by Z_H",
                     );
-                // Split quotient into degree-n chunks.
+                // Split quotient into chunks of the same degree as the trace.
                 quotient_poly.chunks(degree)
             })
             .collect()
     );
+    let quotient_randomness = randomness.derive(QUOTIENT_RANDOMNESS);
+    let all_quotient_chunks = if config.zero_knowledge {
+        mask_quotient_chunks(
+            all_quotient_chunks,
+            num_quotient_chunks,
+            config.num_mask_coeffs::<D>(degree_bits),
+            quotient_randomness.derive(MASK_RANDOMNESS),
+        )
+    } else {
+        all_quotient_chunks
+    };
     // Commit to the quotient polynomials.
     let quotient_commitment = timed!(
         timing,
         "compute quotient commitment",
-        PolynomialBatch::from_coeffs(
+        PolynomialBatch::from_coeffs_with_randomness(
             all_quotient_chunks,
             rate_bits,
-            false,
+            config.zero_knowledge,
             config.fri_config.cap_height,
             timing,
             None,
+            None,
+            quotient_randomness,
         )
     );
     // Observe the quotient polynomials Merkle cap.
     let quotient_polys_cap = quotient_commitment.merkle_tree.cap.clone();
+    challenger.set_transcript_label("quotient polys cap");
     challenger.observe_cap(&quotient_polys_cap);
 
+    challenger.set_transcript_label("stark zeta");
     let zeta = challenger.get_extension_challenge::<D>();
     // To avoid leaking witness data, we want to ensure that our opening locations, `zeta` and
     // `g * zeta`, are not in our subgroup `H`. It suffices to check `zeta` only, since
@@ -473,16 +624,17 @@ where
         zeta,
         g,
         trace_commitment,
-        &auxiliary_polys_commitment,
+        auxiliary_polys_commitment,
         &quotient_commitment,
-        stark.num_lookup_helper_columns(config),
+        num_lookup_columns,
     );
     // Get the FRI openings and observe them.
+    challenger.set_transcript_label("openings");
     challenger.observe_openings(&openings.to_fri_openings());
 
     let initial_merkle_trees = vec![
         trace_commitment,
-        &auxiliary_polys_commitment,
+        auxiliary_polys_commitment,
         &quotient_commitment,
     ];
 
@@ -511,6 +663,182 @@ where
     })
 }
 
+/// Computes the commitment to the auxiliary polynomials of a table, i.e. its lookup helper columns
+/// followed by its CTL Z polynomials. These only depend on the trace and on the CTL challenges. In
+/// zero-knowledge mode, they are masked and salted with `randomness`.
+pub(crate) fn compute_auxiliary_polys_commitment<F, C, const D: usize>(
+    trace_poly_values: &[PolynomialValues<F>],
+    lookups: &[Lookup],
+    constraint_degree: usize,
+    ctl_data: &CtlData<F>,
+    ctl_challenges: &GrandProductChallengeSet<F>,
+    config: &StarkConfig,
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> PolynomialBatch<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    // The helper columns of each (lookup, challenge) pair are computed in parallel.
+    let lookups_with_challenges = lookups
+        .iter()
+        .cartesian_product(&ctl_challenges.challenges)
+        .collect::<Vec<_>>();
+    let mut auxiliary_polys = timed!(
+        timing,
+        "compute lookup helper columns",
+        lookups_with_challenges
+            .into_par_iter()
+            .map(|(lookup, challenge)| {
+                lookup_helper_columns(lookup, trace_poly_values, challenge.beta, constraint_degree)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+    );
+    // We add CTLs to the permutation arguments so that we can batch commit to
+    // all auxiliary polynomials.
+    auxiliary_polys.extend(ctl_data.z_polys());
+    assert!(!auxiliary_polys.is_empty(), "No CTL?");
+
+    timed!(
+        timing,
+        "compute auxiliary polynomials commitment",
+        commit_values(auxiliary_polys, config, randomness, timing)
+    )
+}
+
+/// Applies `f` to every table, in order. If `options.concurrent_tables` is set, the tables are
+/// processed concurrently, and the timings of individual tables are not recorded.
+fn map_tables<R, G>(options: &ProverOptions, timing: &mut TimingTree, f: G) -> Vec<R>
+where
+    R: Send,
+    G: Fn(Table, &mut TimingTree) -> R + Sync,
+{
+    if options.concurrent_tables {
+        Table::all()
+            .par_iter()
+            .map(|&table| f(table, &mut TimingTree::default()))
+            .collect()
+    } else {
+        Table::all()
+            .into_iter()
+            .map(|table| f(table, timing))
+            .collect()
+    }
+}
+
+/// Commits to polynomials given by their values on the trace domain `H`. In zero-knowledge mode,
+/// each polynomial `p` is replaced by `p + Z_H r` for a random `r` with
+/// `config.num_mask_coeffs(degree_bits)` coefficients. This has the same values on `H`, so
+/// constraints still hold, but the values it reveals outside `H`, which are no more numerous than
+/// the coefficients of `r`, are independent of `p`. The `r`s and the salts are drawn from
+/// `randomness`.
+pub(crate) fn commit_values<F, C, const D: usize>(
+    values: Vec<PolynomialValues<F>>,
+    config: &StarkConfig,
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> PolynomialBatch<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let rate_bits = config.fri_config.rate_bits;
+    let cap_height = config.fri_config.cap_height;
+    if !config.zero_knowledge {
+        return PolynomialBatch::from_values(values, rate_bits, false, cap_height, timing, None);
+    }
+
+    let num_mask_coeffs = config.num_mask_coeffs::<D>(log2_strict(values[0].len()));
+    let masks = randomness.derive(MASK_RANDOMNESS);
+    let masked_polys = values
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, values)| {
+            let degree = values.len();
+            assert!(
+                num_mask_coeffs <= degree,
+                "Traces must have at least {num_mask_coeffs} rows in zero-knowledge mode."
+            );
+            let mut coeffs = values.ifft().coeffs;
+            let mut rng = masks.rng(i as u64);
+            let r = (0..num_mask_coeffs)
+                .map(|_| F::sample(&mut rng))
+                .collect_vec();
+            // `Z_H r = X^n r - r`.
+            for (c, &r_i) in coeffs.iter_mut().zip(&r) {
+                *c -= r_i;
+            }
+            coeffs.extend(r);
+            PolynomialCoeffs::new(coeffs).padded(2 * degree)
+        })
+        .collect();
+    PolynomialBatch::from_coeffs_with_randomness(
+        masked_polys,
+        rate_bits,
+        true,
+        cap_height,
+        timing,
+        None,
+        None,
+        randomness,
+    )
+}
+
+/// Masks the chunks of each quotient polynomial `t(X) = sum_i X^{n i} t_i(X)`, given as
+/// `num_chunks` consecutive polynomials of degree less than `n`. Each chunk `t_i` is replaced by
+/// `t_i - b_{i-1} + X^n b_i`, for random `b_i`s with `num_mask_coeffs` coefficients and
+/// `b_{-1} = b_{num_chunks-1} = 0`. The masked chunks still recombine to `t`, but as long as at
+/// most `num_mask_coeffs` values of each are revealed, those values are only correlated through
+/// that recombination.
+fn mask_quotient_chunks<F: RichField>(
+    chunks: Vec<PolynomialCoeffs<F>>,
+    num_chunks: usize,
+    num_mask_coeffs: usize,
+    randomness: ProverRandomness,
+) -> Vec<PolynomialCoeffs<F>> {
+    chunks
+        .par_chunks(num_chunks)
+        .enumerate()
+        .flat_map(|(i, chunks)| {
+            let mut rng = randomness.rng(i as u64);
+            let masks = (1..num_chunks)
+                .map(|_| {
+                    (0..num_mask_coeffs)
+                        .map(|_| F::sample(&mut rng))
+                        .collect_vec()
+                })
+                .collect::<Vec<_>>();
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(j, chunk)| {
+                    let degree = chunk.len();
+                    let mut coeffs = chunk.clone().padded(2 * degree).coeffs;
+                    if j > 0 {
+                        for (c, &b_k) in coeffs.iter_mut().zip(&masks[j - 1]) {
+                            *c -= b_k;
+                        }
+                    }
+                    if let Some(b) = masks.get(j) {
+                        for (c, &b_k) in coeffs[degree..].iter_mut().zip(b) {
+                            *c += b_k;
+                        }
+                    }
+                    PolynomialCoeffs::new(coeffs)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The number of points of the quotient domain at which constraints are evaluated at once, when
+/// computing the quotient polynomials.
+const QUOTIENT_CHUNK_SIZE: usize = 1 << 16;
+
 /// Computes the quotient polynomials `(sum alpha^i C_i(x)) / Z_H(x)` for `alpha` in `alphas`,
 /// where the `C_i`s are the Stark constraints.
 fn compute_quotient_polys<'a, F, P, C, S, const D: usize>(
@@ -533,23 +861,23 @@ where
 {
     let degree = 1 << degree_bits;
     let rate_bits = config.fri_config.rate_bits;
+    // Committed polynomials may have a higher degree than the trace, in zero-knowledge mode.
+    let committed_degree_bits = config.committed_degree_bits(degree_bits);
+    let mask_bits = committed_degree_bits - degree_bits;
 
-    let quotient_degree_bits = log2_ceil(stark.quotient_degree_factor());
+    // The quotients have degree less than `2^quotient_degree_bits` times the degree bound of
+    // committed polynomials.
+    let quotient_degree_bits =
+        log2_ceil(stark.quotient_degree_factor() + usize::from(config.zero_knowledge));
     assert!(
         quotient_degree_bits <= rate_bits,
         "Having constraints of degree higher than the rate is not supported yet."
     );
     let step = 1 << (rate_bits - quotient_degree_bits);
     // When opening the `Z`s polys at the "next" point, need to look at the point `next_step` steps away.
-    let next_step = 1 << quotient_degree_bits;
+    let next_step = 1 << (quotient_degree_bits + mask_bits);
 
-    // Evaluation of the first Lagrange polynomial on the LDE domain.
-    let lagrange_first = PolynomialValues::selector(degree, 0).lde_onto_coset(quotient_degree_bits);
-    // Evaluation of the last Lagrange polynomial on the LDE domain.
-    let lagrange_last =
-        PolynomialValues::selector(degree, degree - 1).lde_onto_coset(quotient_degree_bits);
-
-    let z_h_on_coset = ZeroPolyOnCoset::<F>::new(degree_bits, quotient_degree_bits);
+    let z_h_on_coset = ZeroPolyOnCoset::<F>::new(degree_bits, quotient_degree_bits + mask_bits);
 
     // Retrieve the LDE values at index `i`.
     let get_trace_values_packed =
@@ -557,96 +885,125 @@ where
 
     // Last element of the subgroup.
     let last = F::primitive_root_of_unity(degree_bits).inverse();
-    let size = degree << quotient_degree_bits;
-    let coset = F::cyclic_subgroup_coset_known_order(
-        F::primitive_root_of_unity(degree_bits + quotient_degree_bits),
-        F::coset_shift(),
-        size,
-    );
+    let degree_inv = F::from_canonical_usize(degree).inverse();
+    let size = 1 << (committed_degree_bits + quotient_degree_bits);
+    let coset_generator = F::primitive_root_of_unity(committed_degree_bits + quotient_degree_bits);
+    let num_challenges = alphas.len();
 
-    // We will step by `P::WIDTH`, and in each iteration, evaluate the quotient polynomial at
-    // a batch of `P::WIDTH` points.
-    let quotient_values = (0..size)
-        .into_par_iter()
-        .step_by(P::WIDTH)
-        .flat_map_iter(|i_start| {
-            let i_next_start = (i_start + next_step) % size;
-            let i_range = i_start..i_start + P::WIDTH;
-
-            let x = *P::from_slice(&coset[i_range.clone()]);
-            let z_last = x - last;
-            let lagrange_basis_first = *P::from_slice(&lagrange_first.values[i_range.clone()]);
-            let lagrange_basis_last = *P::from_slice(&lagrange_last.values[i_range]);
+    // The quotient is evaluated over the coset in chunks, so that the intermediate values only
+    // take memory proportional to the chunk size. Each chunk is written to the quotient values,
+    // which are stored in column-major form, with one column per challenge.
+    let chunk_size = QUOTIENT_CHUNK_SIZE.min(size);
+    let mut quotient_values = vec![vec![F::ZERO; size]; num_challenges];
+    for chunk_start in (0..size).step_by(chunk_size) {
+        let chunk_shift = F::coset_shift() * coset_generator.exp_u64(chunk_start as u64);
+        let xs = coset_generator
+            .powers()
+            .take(chunk_size)
+            .map(|x| chunk_shift * x)
+            .collect::<Vec<_>>();
+        // Evaluations of the first and last Lagrange polynomials, `L_0(x) = Z_H(x) / (n (x - 1))`
+        // and `L_{n-1}(x) = w^{n-1} Z_H(x) / (n (x - w^{n-1}))`.
+        let denominators = xs
+            .iter()
+            .flat_map(|&x| [x - F::ONE, x - last])
+            .collect::<Vec<_>>();
+        let denominator_invs = F::batch_multiplicative_inverse(&denominators);
+        let (lagrange_first, lagrange_last): (Vec<_>, Vec<_>) = denominator_invs
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(k, invs)| {
+                let z_h_over_n = z_h_on_coset.eval(chunk_start + k) * degree_inv;
+                (z_h_over_n * invs[0], z_h_over_n * last * invs[1])
+            })
+            .unzip();
 
-            let mut consumer = ConstraintConsumer::new(
-                alphas.clone(),
-                z_last,
-                lagrange_basis_first,
-                lagrange_basis_last,
-            );
-            // Get the local and next row evaluations for the current STARK.
-            let vars = S::EvaluationFrame::from_values(
-                &get_trace_values_packed(i_start),
-                &get_trace_values_packed(i_next_start),
-            );
-            // Get the local and next row evaluations for the permutation argument, as well as the associated challenges.
-            let lookup_vars = lookup_challenges.map(|challenges| LookupCheckVars {
-                local_values: auxiliary_polys_commitment.get_lde_values_packed(i_start, step)
-                    [..num_lookup_columns]
-                    .to_vec(),
-                next_values: auxiliary_polys_commitment.get_lde_values_packed(i_next_start, step),
-                challenges: challenges.to_vec(),
-            });
+        // We will step by `P::WIDTH`, and in each iteration, evaluate the quotient polynomial at
+        // a batch of `P::WIDTH` points.
+        let chunk_evals = (0..chunk_size)
+            .into_par_iter()
+            .step_by(P::WIDTH)
+            .map(|offset| {
+                let i_start = chunk_start + offset;
+                let i_next_start = (i_start + next_step) % size;
+                let i_range = offset..offset + P::WIDTH;
 
-            // Get all the data for this STARK's CTLs:
-            // - the local and next row evaluations for the CTL Z polynomials
-            // - the associated challenges.
-            // - for each CTL:
-            //     - the filter `Column`
-            //     - the `Column`s that form the looking/looked table.
-            let ctl_vars = ctl_data
-                .zs_columns
-                .iter()
-                .enumerate()
-                .map(|(i, zs_columns)| CtlCheckVars::<F, F, P, 1> {
-                    local_z: auxiliary_polys_commitment.get_lde_values_packed(i_start, step)
-                        [num_lookup_columns + i],
-                    next_z: auxiliary_polys_commitment.get_lde_values_packed(i_next_start, step)
-                        [num_lookup_columns + i],
-                    challenges: zs_columns.challenge,
-                    columns: &zs_columns.columns,
-                    filter_column: &zs_columns.filter_column,
-                })
-                .collect::<Vec<_>>();
+                let x = *P::from_slice(&xs[i_range.clone()]);
+                let z_last = x - last;
+                let lagrange_basis_first = *P::from_slice(&lagrange_first[i_range.clone()]);
+                let lagrange_basis_last = *P::from_slice(&lagrange_last[i_range]);
 
-            // Evaluate the polynomial combining all constraints, including those associated
-            // to the permutation and CTL arguments.
-            eval_vanishing_poly::<F, F, P, S, D, 1>(
-                stark,
-                &vars,
-                lookups,
-                lookup_vars,
-                &ctl_vars,
-                &mut consumer,
-            );
-            let mut constraints_evals = consumer.accumulators();
-            // We divide the constraints evaluations by `Z_H(x)`.
-            let denominator_inv: P = z_h_on_coset.eval_inverse_packed(i_start);
-            for eval in &mut constraints_evals {
-                *eval *= denominator_inv;
-            }
+                let mut consumer = ConstraintConsumer::new(
+                    alphas.clone(),
+                    z_last,
+                    lagrange_basis_first,
+                    lagrange_basis_last,
+                );
+                // Get the local and next row evaluations for the current STARK.
+                let vars = S::EvaluationFrame::from_values(
+                    &get_trace_values_packed(i_start),
+                    &get_trace_values_packed(i_next_start),
+                );
+                // Get the local and next row evaluations for the permutation argument, as well as the associated challenges.
+                let lookup_vars = lookup_challenges.map(|challenges| LookupCheckVars {
+                    local_values: auxiliary_polys_commitment.get_lde_values_packed(i_start, step)
+                        [..num_lookup_columns]
+                        .to_vec(),
+                    next_values: auxiliary_polys_commitment
+                        .get_lde_values_packed(i_next_start, step),
+                    challenges: challenges.to_vec(),
+                });
 
-            let num_challenges = alphas.len();
+                // Get all the data for this STARK's CTLs:
+                // - the local and next row evaluations for the CTL Z polynomials
+                // - the associated challenges.
+                // - for each CTL:
+                //     - the filter `Column`
+                //     - the `Column`s that form the looking/looked table.
+                let ctl_vars = ctl_data
+                    .zs_columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, zs_columns)| CtlCheckVars::<F, F, P, 1> {
+                        local_z: auxiliary_polys_commitment.get_lde_values_packed(i_start, step)
+                            [num_lookup_columns + i],
+                        next_z: auxiliary_polys_commitment
+                            .get_lde_values_packed(i_next_start, step)[num_lookup_columns + i],
+                        challenges: zs_columns.challenge,
+                        columns: &zs_columns.columns,
+                        filter_column: &zs_columns.filter_column,
+                    })
+                    .collect::<Vec<_>>();
 
-            (0..P::WIDTH).map(move |i| {
-                (0..num_challenges)
-                    .map(|j| constraints_evals[j].as_slice()[i])
-                    .collect()
+                // Evaluate the polynomial combining all constraints, including those associated
+                // to the permutation and CTL arguments.
+                eval_vanishing_poly::<F, F, P, S, D, 1>(
+                    stark,
+                    &vars,
+                    lookups,
+                    lookup_vars,
+                    &ctl_vars,
+                    &mut consumer,
+                );
+                let mut constraints_evals = consumer.accumulators();
+                // We divide the constraints evaluations by `Z_H(x)`.
+                let denominator_inv: P = z_h_on_coset.eval_inverse_packed(i_start);
+                for eval in &mut constraints_evals {
+                    *eval *= denominator_inv;
+                }
+                constraints_evals
             })
-        })
-        .collect::<Vec<_>>();
+            .collect::<Vec<_>>();
+
+        for (k, constraints_evals) in chunk_evals.into_iter().enumerate() {
+            let i_start = chunk_start + k * P::WIDTH;
+            for (column, eval) in quotient_values.iter_mut().zip(constraints_evals) {
+                column[i_start..i_start + P::WIDTH].copy_from_slice(eval.as_slice());
+            }
+        }
+    }
 
-    transpose(&quotient_values)
+    quotient_values
         .into_par_iter()
         .map(PolynomialValues::new)
         .map(|values| values.coset_ifft(F::coset_shift()))
@@ -684,12 +1041,21 @@ fn check_constraints<'a, F, C, S, const D: usize>(
 
     let subgroup = F::two_adic_subgroup(degree_bits + rate_bits);
 
-    // Get the evaluations of a batch of polynomials over our subgroup.
+    // Get the evaluations of a batch of polynomials over our subgroup. Committed polynomials are
+    // masked by multiples of `Z_H` in zero-knowledge mode, so we first reduce them modulo `Z_H`.
     let get_subgroup_evals = |comm: &PolynomialBatch<F, C, D>| -> Vec<Vec<F>> {
         let values = comm
             .polynomials
             .par_iter()
-            .map(|coeffs| coeffs.clone().fft().values)
+            .map(|poly| {
+                let mut reduced = vec![F::ZERO; degree];
+                for chunk in poly.coeffs.chunks(degree) {
+                    for (r, &c) in reduced.iter_mut().zip(chunk) {
+                        *r += c;
+                    }
+                }
+                PolynomialCoeffs::new(reduced).fft().values
+            })
             .collect::<Vec<_>>();
         transpose(&values)
     };
@@ -764,3 +1130,101 @@ fn check_constraints<'a, F, C, S, const D: usize>(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use eth_trie_utils::partial_trie::HashedPartialTrie;
+    use ethereum_types::H256;
+    use keccak_hash::keccak;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use super::*;
+    use crate::generation::block_trace::empty_trie_root;
+    use crate::generation::TrieInputs;
+    use crate::proof::{BlockHashes, TrieRoots};
+    use crate::Node;
+
+    type F = GoldilocksField;
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+
+    /// The inputs of an empty transaction list on an empty state.
+    fn empty_block_inputs() -> GenerationInputs {
+        let mut contract_code = HashMap::new();
+        contract_code.insert(keccak(vec![]), vec![]);
+        GenerationInputs {
+            tries: TrieInputs {
+                state_trie: HashedPartialTrie::from(Node::Empty),
+                transactions_trie: HashedPartialTrie::from(Node::Empty),
+                receipts_trie: HashedPartialTrie::from(Node::Empty),
+                storage_tries: vec![],
+            },
+            trie_roots_after: TrieRoots {
+                state_root: empty_trie_root(),
+                transactions_root: empty_trie_root(),
+                receipts_root: empty_trie_root(),
+            },
+            contract_code,
+            genesis_state_trie_root: empty_trie_root(),
+            block_hashes: BlockHashes {
+                prev_hashes: vec![H256::default(); 256],
+                cur_hash: H256::default(),
+            },
+            ..GenerationInputs::default()
+        }
+    }
+
+    /// With seeded randomness, committing to the tables concurrently gives the same trace caps and
+    /// leaves the challenger in the same state as committing to them in order.
+    #[test]
+    fn test_concurrent_tables_match_sequential() -> Result<()> {
+        let all_stark = AllStark::<F, D>::default();
+        let config = StarkConfig::standard_fast_zk_config();
+        let (traces, public_values, _) = generate_traces(
+            &all_stark,
+            empty_block_inputs(),
+            &config,
+            &mut TimingTree::default(),
+        )?;
+
+        let prove_tables = |concurrent_tables| -> Result<_> {
+            let options = ProverOptions {
+                concurrent_tables,
+                randomness: ProverRandomness::Seeded(1201),
+                ..ProverOptions::default()
+            };
+            let mut challenger = Challenger::<F, <C as GenericConfig<D>>::Hasher>::new();
+            let proof = prove_with_challenger::<F, C, D>(
+                &all_stark,
+                &config,
+                traces.clone(),
+                public_values.clone(),
+                &options,
+                &mut challenger,
+                &mut TimingTree::default(),
+            )?;
+            Ok((proof, challenger.compact()))
+        };
+        let (sequential, sequential_state) = prove_tables(false)?;
+        let (concurrent, concurrent_state) = prove_tables(true)?;
+
+        for (table, (sequential, concurrent)) in Table::all()
+            .into_iter()
+            .zip(sequential.stark_proofs.iter().zip(&concurrent.stark_proofs))
+        {
+            assert_eq!(
+                sequential.proof.trace_cap, concurrent.proof.trace_cap,
+                "{table:?}"
+            );
+            assert_eq!(
+                sequential.init_challenger_state, concurrent.init_challenger_state,
+                "{table:?}"
+            );
+        }
+        assert_eq!(sequential_state, concurrent_state);
+        Ok(())
+    }
+}
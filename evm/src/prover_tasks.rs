@@ -0,0 +1,408 @@
+//! The units of work of the EVM proving pipeline, as serializable requests and responses, so that
+//! a job queue can distribute them across machines.
+//!
+//! A block is proven in the following steps:
+//! 1. A [`TraceCommitmentRequest`] for each table, which are independent, commits to its trace.
+//! 2. The coordinator derives the CTL challenges from all trace caps and the public values with
+//!    [`ctl_challenges`], along with the Fiat-Shamir state the first table proof starts from.
+//! 3. A [`TableProofRequest`] for each table proves its STARK. The Fiat-Shamir state of each table
+//!    continues from the previous table, in the order of [`Table`], so these are sequential: each
+//!    response holds the state the next request starts from.
+//! 4. A [`TableShrinkRequest`] for each table, which are independent, shrinks its STARK proof.
+//! 5. A [`RootRequest`] combines the shrunk proofs, and [`AggregationRequest`]s and
+//!    [`BlockRequest`]s combine root proofs as usual.
+//!
+//! Table proof workers recompute the trace commitments of step 1, and check that they match, so
+//! zero-knowledge configurations, whose commitments are randomized, aren't supported.
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::hashing::PlonkyPermutation;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::iop::challenger::Challenger;
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::util::randomness::ProverRandomness;
+use plonky2::util::timing::TimingTree;
+use serde::{Deserialize, Serialize};
+
+use crate::all_stark::{AllStark, Table, NUM_TABLES};
+use crate::config::StarkConfig;
+use crate::cross_table_lookup::{
+    get_grand_product_challenge_set, table_cross_table_lookup_data, GrandProductChallengeSet,
+};
+use crate::fixed_recursive_verifier::AllRecursiveCircuits;
+use crate::get_challenges::observe_public_values;
+use crate::proof::{PublicValues, StarkProofWithMetadata};
+use crate::prover::{commit_values, compute_auxiliary_polys_commitment, prove_single_table};
+
+/// Commits to the trace of a table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TraceCommitmentRequest<F: RichField> {
+    pub table: Table,
+    pub trace: Vec<PolynomialValues<F>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TraceCommitmentResponse<F: RichField, H: Hasher<F>> {
+    pub table: Table,
+    pub trace_cap: MerkleCap<F, H>,
+}
+
+impl<F: RichField> TraceCommitmentRequest<F> {
+    pub fn execute<C: GenericConfig<D, F = F>, const D: usize>(
+        self,
+        config: &StarkConfig,
+        timing: &mut TimingTree,
+    ) -> TraceCommitmentResponse<F, C::Hasher>
+    where
+        F: Extendable<D>,
+    {
+        let trace_commitment =
+            commit_values::<F, C, D>(self.trace, config, ProverRandomness::Os, timing);
+        TraceCommitmentResponse {
+            table: self.table,
+            trace_cap: trace_commitment.merkle_tree.cap,
+        }
+    }
+}
+
+/// The challenges shared by all table proofs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct CtlChallenges<F: RichField> {
+    pub ctl_challenges: GrandProductChallengeSet<F>,
+    /// The Fiat-Shamir state the proof of the first table starts from.
+    pub challenger_state: Vec<F>,
+}
+
+/// Derives the CTL challenges from the trace caps of all tables, in the order of [`Table`], and
+/// the public values.
+pub fn ctl_challenges<F, C, const D: usize>(
+    trace_commitments: &[TraceCommitmentResponse<F, C::Hasher>],
+    public_values: &PublicValues,
+    config: &StarkConfig,
+) -> Result<CtlChallenges<F>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    ensure!(
+        trace_commitments.len() == NUM_TABLES,
+        "Expected {} trace commitments, found {}",
+        NUM_TABLES,
+        trace_commitments.len()
+    );
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    for (table, commitment) in Table::all().into_iter().zip(trace_commitments) {
+        ensure!(
+            commitment.table == table,
+            "Expected the trace commitment of the {:?} table, found that of the {:?} table",
+            table,
+            commitment.table
+        );
+        challenger.observe_cap(&commitment.trace_cap);
+    }
+    observe_public_values::<F, C, D>(&mut challenger, public_values)
+        .map_err(|_| anyhow::Error::msg("Invalid conversion of public values."))?;
+    let ctl_challenges = get_grand_product_challenge_set(&mut challenger, config.num_challenges);
+    Ok(CtlChallenges {
+        ctl_challenges,
+        challenger_state: challenger.compact().as_ref().to_vec(),
+    })
+}
+
+/// Proves the STARK of a table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TableProofRequest<F: RichField, H: Hasher<F>> {
+    pub table: Table,
+    pub trace: Vec<PolynomialValues<F>>,
+    /// The trace cap of the table, as returned by its [`TraceCommitmentRequest`].
+    pub trace_cap: MerkleCap<F, H>,
+    pub ctl_challenges: GrandProductChallengeSet<F>,
+    /// The Fiat-Shamir state the proof starts from, as returned by [`ctl_challenges`] for the
+    /// first table, and by the proof of the previous table otherwise.
+    pub challenger_state: Vec<F>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TableProofResponse<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub table: Table,
+    pub proof: StarkProofWithMetadata<F, C, D>,
+    /// The Fiat-Shamir state the proof of the next table starts from.
+    pub challenger_state: Vec<F>,
+}
+
+impl<F: RichField, H: Hasher<F>> TableProofRequest<F, H> {
+    pub fn execute<C, const D: usize>(
+        self,
+        all_stark: &AllStark<F, D>,
+        config: &StarkConfig,
+        timing: &mut TimingTree,
+    ) -> Result<TableProofResponse<F, C, D>>
+    where
+        F: Extendable<D>,
+        C: GenericConfig<D, F = F, Hasher = H>,
+    {
+        ensure!(
+            self.challenger_state.len() == H::Permutation::WIDTH,
+            "Expected a challenger state of {} elements, found {}",
+            H::Permutation::WIDTH,
+            self.challenger_state.len()
+        );
+        let mut challenger =
+            Challenger::<F, H>::from_state(H::Permutation::new(self.challenger_state));
+
+        let trace_commitment =
+            commit_values::<F, C, D>(self.trace.clone(), config, ProverRandomness::Os, timing);
+        ensure!(
+            trace_commitment.merkle_tree.cap == self.trace_cap,
+            "The trace of the {:?} table doesn't match its commitment",
+            self.table
+        );
+        let ctl_data = table_cross_table_lookup_data(
+            self.table,
+            &self.trace,
+            &all_stark.cross_table_lookups,
+            &self.ctl_challenges,
+        );
+        let table = self.table as usize;
+        let auxiliary_polys_commitment = compute_auxiliary_polys_commitment::<F, C, D>(
+            &self.trace,
+            &all_stark.lookups()[table],
+            all_stark.constraint_degrees()[table],
+            &ctl_data,
+            &self.ctl_challenges,
+            config,
+            ProverRandomness::Os,
+            timing,
+        );
+
+        macro_rules! prove_table {
+            ($stark:expr) => {
+                prove_single_table(
+                    $stark,
+                    config,
+                    &self.trace,
+                    &trace_commitment,
+                    &auxiliary_polys_commitment,
+                    &ctl_data,
+                    &self.ctl_challenges,
+                    ProverRandomness::Os,
+                    &mut challenger,
+                    timing,
+                )?
+            };
+        }
+        let proof = match self.table {
+            Table::Arithmetic => prove_table!(&all_stark.arithmetic_stark),
+            Table::BytePacking => prove_table!(&all_stark.byte_packing_stark),
+            Table::Cpu => prove_table!(&all_stark.cpu_stark),
+            Table::Keccak => prove_table!(&all_stark.keccak_stark),
+            Table::KeccakSponge => prove_table!(&all_stark.keccak_sponge_stark),
+            Table::Logic => prove_table!(&all_stark.logic_stark),
+            Table::Memory => prove_table!(&all_stark.memory_stark),
+        };
+
+        Ok(TableProofResponse {
+            table: self.table,
+            proof,
+            challenger_state: challenger.compact().as_ref().to_vec(),
+        })
+    }
+}
+
+/// Shrinks the STARK proof of a table with the recursive circuits for its size.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TableShrinkRequest<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub table: Table,
+    pub proof: StarkProofWithMetadata<F, C, D>,
+    pub ctl_challenges: GrandProductChallengeSet<F>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TableShrinkResponse<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub table: Table,
+    /// The size of the table, as `log_2(height)`.
+    pub degree_bits: usize,
+    pub proof: ProofWithPublicInputs<F, C, D>,
+}
+
+impl<F, C, const D: usize> TableShrinkRequest<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn execute(
+        &self,
+        circuits: &AllRecursiveCircuits<F, C, D>,
+        config: &StarkConfig,
+    ) -> Result<TableShrinkResponse<F, C, D>> {
+        let (degree_bits, proof) =
+            circuits.prove_table_shrink(self.table, &self.proof, &self.ctl_challenges, config)?;
+        Ok(TableShrinkResponse {
+            table: self.table,
+            degree_bits,
+            proof,
+        })
+    }
+}
+
+/// A root, aggregation or block proof, along with its public values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct RecursiveProofResponse<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub proof: ProofWithPublicInputs<F, C, D>,
+    pub public_values: PublicValues,
+}
+
+/// Combines the shrunk proofs of all tables into a root proof.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct RootRequest<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    /// The shrunk proof of each table, in the order of [`Table`].
+    pub shrunk_proofs: Vec<TableShrinkResponse<F, C, D>>,
+    pub public_values: PublicValues,
+}
+
+impl<F, C, const D: usize> RootRequest<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn execute(
+        self,
+        circuits: &AllRecursiveCircuits<F, C, D>,
+    ) -> Result<RecursiveProofResponse<F, C, D>> {
+        ensure!(
+            self.shrunk_proofs.len() == NUM_TABLES,
+            "Expected {} shrunk proofs, found {}",
+            NUM_TABLES,
+            self.shrunk_proofs.len()
+        );
+        let mut shrunk_proofs = Vec::with_capacity(NUM_TABLES);
+        for (table, shrunk_proof) in Table::all().into_iter().zip(self.shrunk_proofs) {
+            ensure!(
+                shrunk_proof.table == table,
+                "Expected the shrunk proof of the {:?} table, found that of the {:?} table",
+                table,
+                shrunk_proof.table
+            );
+            shrunk_proofs.push((shrunk_proof.degree_bits, shrunk_proof.proof));
+        }
+        let (proof, public_values) = circuits.prove_root_from_shrunk_proofs(
+            &shrunk_proofs.try_into().unwrap(),
+            self.public_values,
+        )?;
+        Ok(RecursiveProofResponse {
+            proof,
+            public_values,
+        })
+    }
+}
+
+/// Aggregates two root or aggregation proofs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct AggregationRequest<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub lhs_is_agg: bool,
+    pub lhs_proof: ProofWithPublicInputs<F, C, D>,
+    pub rhs_is_agg: bool,
+    pub rhs_proof: ProofWithPublicInputs<F, C, D>,
+    /// The public values of the aggregated transactions.
+    pub public_values: PublicValues,
+}
+
+impl<F, C, const D: usize> AggregationRequest<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn execute(
+        self,
+        circuits: &AllRecursiveCircuits<F, C, D>,
+    ) -> Result<RecursiveProofResponse<F, C, D>> {
+        let (proof, public_values) = circuits.prove_aggregation(
+            self.lhs_is_agg,
+            &self.lhs_proof,
+            self.rhs_is_agg,
+            &self.rhs_proof,
+            self.public_values,
+        )?;
+        Ok(RecursiveProofResponse {
+            proof,
+            public_values,
+        })
+    }
+}
+
+/// Proves a block from the aggregation proof of its transactions and the proof of its parent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct BlockRequest<F, C, const D: usize>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    /// The proof of the parent block, or `None` for the first block proven.
+    pub parent_block_proof: Option<ProofWithPublicInputs<F, C, D>>,
+    pub agg_root_proof: ProofWithPublicInputs<F, C, D>,
+    pub public_values: PublicValues,
+}
+
+impl<F, C, const D: usize> BlockRequest<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn execute(
+        self,
+        circuits: &AllRecursiveCircuits<F, C, D>,
+    ) -> Result<RecursiveProofResponse<F, C, D>> {
+        let (proof, public_values) = circuits.prove_block(
+            self.parent_block_proof.as_ref(),
+            &self.agg_root_proof,
+            self.public_values,
+        )?;
+        Ok(RecursiveProofResponse {
+            proof,
+            public_values,
+        })
+    }
+}
@@ -1,7 +1,6 @@
 use std::fmt::Debug;
 
 use anyhow::Result;
-use ethereum_types::{BigEndianHash, U256};
 use plonky2::field::extension::Extendable;
 use plonky2::field::types::Field;
 use plonky2::fri::witness_util::set_fri_proof_target;
@@ -17,6 +16,7 @@ use plonky2::iop::witness::{PartialWitness, Witness, WitnessWrite};
 use plonky2::plonk::circuit_builder::CircuitBuilder;
 use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
 use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2::plonk::plonk_common::salt_size;
 use plonky2::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
 use plonky2::util::reducing::ReducingFactorTarget;
 use plonky2::util::serialization::{
@@ -37,13 +37,13 @@ use crate::lookup::LookupCheckVarsTarget;
 use crate::memory::segments::Segment;
 use crate::memory::VALUE_LIMBS;
 use crate::proof::{
-    BlockHashes, BlockHashesTarget, BlockMetadata, BlockMetadataTarget, ExtraBlockData,
-    ExtraBlockDataTarget, PublicValues, PublicValuesTarget, StarkOpeningSetTarget, StarkProof,
-    StarkProofChallengesTarget, StarkProofTarget, StarkProofWithMetadata, TrieRoots,
-    TrieRootsTarget,
+    BlockHashesTarget, BlockMetadataExtensionsTarget, BlockMetadataTarget, ChainConstantsTarget,
+    ExtraBlockDataTarget, ExtraPublicValuesTarget, PublicValues, PublicValuesTarget,
+    StarkOpeningSetTarget, StarkProof, StarkProofChallengesTarget, StarkProofTarget,
+    StarkProofWithMetadata, TrieRootsTarget, NUM_BLOCK_METADATA_EXTENSIONS, NUM_CHAIN_CONSTANTS,
+    NUM_EXTRA_PUBLIC_VALUES,
 };
 use crate::stark::Stark;
-use crate::util::{h256_limbs, u256_limbs, u256_to_u32, u256_to_u64};
 use crate::vanishing_poly::eval_vanishing_poly_circuit;
 use crate::witness::errors::ProgramError;
 
@@ -387,7 +387,7 @@ fn verify_stark_proof_with_challenges_circuit<
     // Check each polynomial identity, of the form `vanishing(x) = Z_H(x) quotient(x)`, at zeta.
     let mut scale = ReducingFactorTarget::new(zeta_pow_deg);
     for (i, chunk) in quotient_polys
-        .chunks(stark.quotient_degree_factor())
+        .chunks(stark.num_quotient_chunks(inner_config))
         .enumerate()
     {
         let recombined_quotient = scale.reduce(chunk, builder);
@@ -532,6 +532,70 @@ pub(crate) fn get_memory_extra_looking_products_circuit<
         );
     }
 
+    // Add block metadata extensions writes.
+    let extensions = &public_values.block_metadata_extensions;
+    let extensions_segment = builder.constant(F::from_canonical_u32(
+        Segment::BlockMetadataExtensions as u32,
+    ));
+    for i in 0..NUM_BLOCK_METADATA_EXTENSIONS {
+        product = add_data_write(
+            builder,
+            challenge,
+            product,
+            extensions_segment,
+            i,
+            &[extensions.present[i]],
+        );
+    }
+    for i in 0..NUM_BLOCK_METADATA_EXTENSIONS {
+        product = add_data_write(
+            builder,
+            challenge,
+            product,
+            extensions_segment,
+            NUM_BLOCK_METADATA_EXTENSIONS + i,
+            &extensions.values[8 * i..8 * (i + 1)],
+        );
+    }
+
+    // Add extra public values writes.
+    let extra = &public_values.extra_public_values;
+    let extra_segment = builder.constant(F::from_canonical_u32(Segment::ExtraPublicValues as u32));
+    for i in 0..NUM_EXTRA_PUBLIC_VALUES {
+        product = add_data_write(
+            builder,
+            challenge,
+            product,
+            extra_segment,
+            i,
+            &extra.before[8 * i..8 * (i + 1)],
+        );
+    }
+    for i in 0..NUM_EXTRA_PUBLIC_VALUES {
+        product = add_data_write(
+            builder,
+            challenge,
+            product,
+            extra_segment,
+            NUM_EXTRA_PUBLIC_VALUES + i,
+            &extra.after[8 * i..8 * (i + 1)],
+        );
+    }
+
+    // Add chain constants writes, with the Ethereum value of each constant not overridden.
+    let constants = public_values.chain_constants.effective_values(builder);
+    let constants_segment = builder.constant(F::from_canonical_u32(Segment::ChainConstants as u32));
+    for i in 0..NUM_CHAIN_CONSTANTS {
+        product = add_data_write(
+            builder,
+            challenge,
+            product,
+            constants_segment,
+            i,
+            &constants[8 * i..8 * (i + 1)],
+        );
+    }
+
     // Add block bloom filters writes.
     let bloom_segment = builder.constant(F::from_canonical_u32(Segment::GlobalBlockBloom as u32));
     for i in 0..8 {
@@ -674,12 +738,18 @@ pub(crate) fn add_virtual_public_values<F: RichField + Extendable<D>, const D: u
     let block_metadata = add_virtual_block_metadata(builder);
     let block_hashes = add_virtual_block_hashes(builder);
     let extra_block_data = add_virtual_extra_block_data(builder);
+    let block_metadata_extensions = add_virtual_block_metadata_extensions(builder);
+    let extra_public_values = add_virtual_extra_public_values(builder);
+    let chain_constants = add_virtual_chain_constants(builder);
     PublicValuesTarget {
         trie_roots_before,
         trie_roots_after,
         block_metadata,
         block_hashes,
         extra_block_data,
+        block_metadata_extensions,
+        extra_public_values,
+        chain_constants,
     }
 }
 
@@ -754,6 +824,50 @@ pub(crate) fn add_virtual_extra_block_data<F: RichField + Extendable<D>, const D
     }
 }
 
+/// Adds the block metadata extensions as public inputs, constrained to be canonical so that they
+/// determine the `BlockMetadataExtensions` they encode.
+pub(crate) fn add_virtual_block_metadata_extensions<
+    F: RichField + Extendable<D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> BlockMetadataExtensionsTarget {
+    let present = builder.add_virtual_public_input_arr();
+    let values = builder.add_virtual_public_input_arr();
+    let extensions = BlockMetadataExtensionsTarget { present, values };
+    extensions.assert_canonical(builder);
+    extensions
+}
+
+/// Adds the extra public values as public inputs, constrained to be canonical so that they
+/// determine the `ExtraPublicValues` they encode.
+pub(crate) fn add_virtual_extra_public_values<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> ExtraPublicValuesTarget {
+    let declared = builder.add_virtual_public_input_arr();
+    let before = builder.add_virtual_public_input_arr();
+    let after = builder.add_virtual_public_input_arr();
+    let extra = ExtraPublicValuesTarget {
+        declared,
+        before,
+        after,
+    };
+    extra.assert_canonical(builder);
+    extra
+}
+
+/// Adds the chain constants as public inputs, constrained to be canonical so that they determine
+/// the `ChainConstants` they encode.
+pub(crate) fn add_virtual_chain_constants<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+) -> ChainConstantsTarget {
+    let overridden = builder.add_virtual_public_input_arr();
+    let values = builder.add_virtual_public_input_arr();
+    let constants = ChainConstantsTarget { overridden, values };
+    constants.assert_canonical(builder);
+    constants
+}
+
 pub(crate) fn add_virtual_stark_proof<
     F: RichField + Extendable<D>,
     S: Stark<F, D>,
@@ -768,10 +882,11 @@ pub(crate) fn add_virtual_stark_proof<
     let fri_params = config.fri_params(degree_bits);
     let cap_height = fri_params.config.cap_height;
 
+    let salt = salt_size(config.zero_knowledge);
     let num_leaves_per_oracle = vec![
-        S::COLUMNS,
-        stark.num_lookup_helper_columns(config) + num_ctl_zs,
-        stark.quotient_degree_factor() * config.num_challenges,
+        S::COLUMNS + salt,
+        stark.num_lookup_helper_columns(config) + num_ctl_zs + salt,
+        stark.num_quotient_polys(config) + salt,
     ];
 
     let auxiliary_polys_cap = builder.add_virtual_cap(cap_height);
@@ -791,7 +906,6 @@ fn add_virtual_stark_opening_set<F: RichField + Extendable<D>, S: Stark<F, D>, c
     num_ctl_zs: usize,
     config: &StarkConfig,
 ) -> StarkOpeningSetTarget<D> {
-    let num_challenges = config.num_challenges;
     StarkOpeningSetTarget {
         local_values: builder.add_virtual_extension_targets(S::COLUMNS),
         next_values: builder.add_virtual_extension_targets(S::COLUMNS),
@@ -800,8 +914,7 @@ fn add_virtual_stark_opening_set<F: RichField + Extendable<D>, S: Stark<F, D>, c
         auxiliary_polys_next: builder
             .add_virtual_extension_targets(stark.num_lookup_helper_columns(config) + num_ctl_zs),
         ctl_zs_first: builder.add_virtual_targets(num_ctl_zs),
-        quotient_polys: builder
-            .add_virtual_extension_targets(stark.quotient_degree_factor() * num_challenges),
+        quotient_polys: builder.add_virtual_extension_targets(stark.num_quotient_polys(config)),
     }
 }
 
@@ -840,207 +953,10 @@ where
     F: RichField + Extendable<D>,
     W: Witness<F>,
 {
-    set_trie_roots_target(
-        witness,
-        &public_values_target.trie_roots_before,
-        &public_values.trie_roots_before,
-    );
-    set_trie_roots_target(
-        witness,
-        &public_values_target.trie_roots_after,
-        &public_values.trie_roots_after,
-    );
-    set_block_metadata_target(
-        witness,
-        &public_values_target.block_metadata,
-        &public_values.block_metadata,
-    )?;
-    set_block_hashes_target(
-        witness,
-        &public_values_target.block_hashes,
-        &public_values.block_hashes,
-    );
-    set_extra_public_values_target(
-        witness,
-        &public_values_target.extra_block_data,
-        &public_values.extra_block_data,
-    )?;
-
-    Ok(())
-}
-
-pub(crate) fn set_trie_roots_target<F, W, const D: usize>(
-    witness: &mut W,
-    trie_roots_target: &TrieRootsTarget,
-    trie_roots: &TrieRoots,
-) where
-    F: RichField + Extendable<D>,
-    W: Witness<F>,
-{
-    for (i, limb) in trie_roots.state_root.into_uint().0.into_iter().enumerate() {
-        witness.set_target(
-            trie_roots_target.state_root[2 * i],
-            F::from_canonical_u32(limb as u32),
-        );
-        witness.set_target(
-            trie_roots_target.state_root[2 * i + 1],
-            F::from_canonical_u32((limb >> 32) as u32),
-        );
-    }
-
-    for (i, limb) in trie_roots
-        .transactions_root
-        .into_uint()
-        .0
-        .into_iter()
-        .enumerate()
-    {
-        witness.set_target(
-            trie_roots_target.transactions_root[2 * i],
-            F::from_canonical_u32(limb as u32),
-        );
-        witness.set_target(
-            trie_roots_target.transactions_root[2 * i + 1],
-            F::from_canonical_u32((limb >> 32) as u32),
-        );
-    }
-
-    for (i, limb) in trie_roots
-        .receipts_root
-        .into_uint()
-        .0
-        .into_iter()
-        .enumerate()
-    {
-        witness.set_target(
-            trie_roots_target.receipts_root[2 * i],
-            F::from_canonical_u32(limb as u32),
-        );
-        witness.set_target(
-            trie_roots_target.receipts_root[2 * i + 1],
-            F::from_canonical_u32((limb >> 32) as u32),
-        );
-    }
-}
-
-pub(crate) fn set_block_metadata_target<F, W, const D: usize>(
-    witness: &mut W,
-    block_metadata_target: &BlockMetadataTarget,
-    block_metadata: &BlockMetadata,
-) -> Result<(), ProgramError>
-where
-    F: RichField + Extendable<D>,
-    W: Witness<F>,
-{
-    let beneficiary_limbs: [F; 5] =
-        u256_limbs::<F>(U256::from_big_endian(&block_metadata.block_beneficiary.0))[..5]
-            .try_into()
-            .unwrap();
-    witness.set_target_arr(&block_metadata_target.block_beneficiary, &beneficiary_limbs);
-    witness.set_target(
-        block_metadata_target.block_timestamp,
-        u256_to_u32(block_metadata.block_timestamp)?,
-    );
-    witness.set_target(
-        block_metadata_target.block_number,
-        u256_to_u32(block_metadata.block_number)?,
-    );
-    witness.set_target(
-        block_metadata_target.block_difficulty,
-        u256_to_u32(block_metadata.block_difficulty)?,
-    );
     witness.set_target_arr(
-        &block_metadata_target.block_random,
-        &h256_limbs(block_metadata.block_random),
-    );
-    // Gaslimit fits in 2 limbs
-    let gaslimit = u256_to_u64(block_metadata.block_gaslimit)?;
-    witness.set_target(block_metadata_target.block_gaslimit[0], gaslimit.0);
-    witness.set_target(block_metadata_target.block_gaslimit[1], gaslimit.1);
-    witness.set_target(
-        block_metadata_target.block_chain_id,
-        u256_to_u32(block_metadata.block_chain_id)?,
+        &public_values_target.to_public_inputs(),
+        &public_values.to_public_inputs()?,
     );
-    // Basefee fits in 2 limbs
-    let basefee = u256_to_u64(block_metadata.block_base_fee)?;
-    witness.set_target(block_metadata_target.block_base_fee[0], basefee.0);
-    witness.set_target(block_metadata_target.block_base_fee[1], basefee.1);
-    // Gas used fits in 2 limbs
-    let gas_used = u256_to_u64(block_metadata.block_gas_used)?;
-    witness.set_target(block_metadata_target.block_gas_used[0], gas_used.0);
-    witness.set_target(block_metadata_target.block_gas_used[1], gas_used.1);
-    let mut block_bloom_limbs = [F::ZERO; 64];
-    for (i, limbs) in block_bloom_limbs.chunks_exact_mut(8).enumerate() {
-        limbs.copy_from_slice(&u256_limbs(block_metadata.block_bloom[i]));
-    }
-    witness.set_target_arr(&block_metadata_target.block_bloom, &block_bloom_limbs);
-
-    Ok(())
-}
-
-pub(crate) fn set_block_hashes_target<F, W, const D: usize>(
-    witness: &mut W,
-    block_hashes_target: &BlockHashesTarget,
-    block_hashes: &BlockHashes,
-) where
-    F: RichField + Extendable<D>,
-    W: Witness<F>,
-{
-    for i in 0..256 {
-        let block_hash_limbs: [F; 8] = h256_limbs::<F>(block_hashes.prev_hashes[i]);
-        witness.set_target_arr(
-            &block_hashes_target.prev_hashes[8 * i..8 * (i + 1)],
-            &block_hash_limbs,
-        );
-    }
-    let cur_block_hash_limbs: [F; 8] = h256_limbs::<F>(block_hashes.cur_hash);
-    witness.set_target_arr(&block_hashes_target.cur_hash, &cur_block_hash_limbs);
-}
-
-pub(crate) fn set_extra_public_values_target<F, W, const D: usize>(
-    witness: &mut W,
-    ed_target: &ExtraBlockDataTarget,
-    ed: &ExtraBlockData,
-) -> Result<(), ProgramError>
-where
-    F: RichField + Extendable<D>,
-    W: Witness<F>,
-{
-    witness.set_target_arr(
-        &ed_target.genesis_state_trie_root,
-        &h256_limbs::<F>(ed.genesis_state_trie_root),
-    );
-    witness.set_target(
-        ed_target.txn_number_before,
-        u256_to_u32(ed.txn_number_before)?,
-    );
-    witness.set_target(
-        ed_target.txn_number_after,
-        u256_to_u32(ed.txn_number_after)?,
-    );
-    // Gas used before/after fit in 2 limbs
-    let gas_used_before = u256_to_u64(ed.gas_used_before)?;
-    witness.set_target(ed_target.gas_used_before[0], gas_used_before.0);
-    witness.set_target(ed_target.gas_used_before[1], gas_used_before.1);
-    let gas_used_after = u256_to_u64(ed.gas_used_after)?;
-    witness.set_target(ed_target.gas_used_after[0], gas_used_after.0);
-    witness.set_target(ed_target.gas_used_after[1], gas_used_after.1);
-
-    let block_bloom_before = ed.block_bloom_before;
-    let mut block_bloom_limbs = [F::ZERO; 64];
-    for (i, limbs) in block_bloom_limbs.chunks_exact_mut(8).enumerate() {
-        limbs.copy_from_slice(&u256_limbs(block_bloom_before[i]));
-    }
-
-    witness.set_target_arr(&ed_target.block_bloom_before, &block_bloom_limbs);
-
-    let block_bloom_after = ed.block_bloom_after;
-    let mut block_bloom_limbs = [F::ZERO; 64];
-    for (i, limbs) in block_bloom_limbs.chunks_exact_mut(8).enumerate() {
-        limbs.copy_from_slice(&u256_limbs(block_bloom_after[i]));
-    }
-
-    witness.set_target_arr(&ed_target.block_bloom_after, &block_bloom_limbs);
 
     Ok(())
 }
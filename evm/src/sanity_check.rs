@@ -0,0 +1,417 @@
+//! A sanity check of generated traces, to run before proving.
+//!
+//! A trace which violates a constraint only surfaces during proving as a quotient polynomial of
+//! too high a degree, long after the traces were generated and without any indication of the
+//! offending row. This evaluates every constraint directly on the trace instead, including those
+//! of the lookup and CTL arguments, and reports the first violation.
+//!
+//! The lookup and CTL arguments are checked with fresh random challenges, rather than with the
+//! challenges of the proof, which are unknown before the traces are committed.
+
+use std::fmt::{self, Display, Formatter};
+use std::panic::Location;
+
+use itertools::Itertools;
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::{Field, Sample};
+use plonky2::hash::hash_types::RichField;
+use plonky2_maybe_rayon::*;
+
+use crate::all_stark::{AllStark, Table, NUM_TABLES};
+use crate::config::StarkConfig;
+use crate::constraint_consumer::ConstraintConsumer;
+use crate::cross_table_lookup::{
+    cross_table_lookup_data, CtlCheckVars, CtlData, GrandProductChallenge, GrandProductChallengeSet,
+};
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::lookup::{lookup_helper_columns, LookupCheckVars};
+use crate::proof::PublicValues;
+use crate::stark::Stark;
+use crate::vanishing_poly::eval_vanishing_poly;
+use crate::verifier::get_memory_extra_looking_products;
+
+/// The first violation found in a set of traces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceViolation {
+    /// A constraint of a table, or of its lookup or CTL arguments, doesn't vanish on a row.
+    Constraint {
+        table: Table,
+        row: usize,
+        /// The index of the constraint, in the order constraints are emitted. The constraints of
+        /// the STARK come first, then those of its lookups, then those of its CTLs.
+        index: usize,
+        /// Where the constraint is emitted.
+        location: &'static Location<'static>,
+    },
+    /// The filter of a table in a CTL is neither 0 nor 1 on a row.
+    NonBinaryFilter {
+        /// The index of the CTL in `AllStark::cross_table_lookups`.
+        ctl_index: usize,
+        table: Table,
+        row: usize,
+    },
+    /// The rows selected by the looking tables of a CTL differ from those of its looked table.
+    CrossTableLookup {
+        /// The index of the CTL in `AllStark::cross_table_lookups`.
+        ctl_index: usize,
+    },
+}
+
+impl Display for TraceViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Constraint {
+                table,
+                row,
+                index,
+                location,
+            } => write!(
+                f,
+                "{table:?} constraint {index} (emitted at {location}) fails on row {row}"
+            ),
+            Self::NonBinaryFilter {
+                ctl_index,
+                table,
+                row,
+            } => write!(
+                f,
+                "filter of {table:?} in CTL {ctl_index} is not binary on row {row}"
+            ),
+            Self::CrossTableLookup { ctl_index } => {
+                write!(f, "looking and looked rows of CTL {ctl_index} differ")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TraceViolation {}
+
+/// Checks that the traces satisfy all constraints, lookups and CTLs, and returns the first
+/// violation otherwise. Tables are checked in order, and rows in increasing order within a table.
+pub fn check_traces<F: RichField + Extendable<D>, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    traces: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+    public_values: &PublicValues,
+    config: &StarkConfig,
+) -> Result<(), TraceViolation> {
+    check_filters(all_stark, traces)?;
+
+    let ctl_challenges = GrandProductChallengeSet {
+        challenges: (0..config.num_challenges)
+            .map(|_| GrandProductChallenge {
+                beta: F::rand(),
+                gamma: F::rand(),
+            })
+            .collect(),
+    };
+    let ctl_data_per_table =
+        cross_table_lookup_data::<F, D>(traces, &all_stark.cross_table_lookups, &ctl_challenges);
+
+    let [arithmetic, byte_packing, cpu, keccak, keccak_sponge, logic, memory] = traces;
+    let [arithmetic_ctl, byte_packing_ctl, cpu_ctl, keccak_ctl, keccak_sponge_ctl, logic_ctl, memory_ctl] =
+        &ctl_data_per_table;
+    check_table(
+        &all_stark.arithmetic_stark,
+        Table::Arithmetic,
+        arithmetic,
+        arithmetic_ctl,
+        &ctl_challenges,
+    )?;
+    check_table(
+        &all_stark.byte_packing_stark,
+        Table::BytePacking,
+        byte_packing,
+        byte_packing_ctl,
+        &ctl_challenges,
+    )?;
+    check_table(
+        &all_stark.cpu_stark,
+        Table::Cpu,
+        cpu,
+        cpu_ctl,
+        &ctl_challenges,
+    )?;
+    check_table(
+        &all_stark.keccak_stark,
+        Table::Keccak,
+        keccak,
+        keccak_ctl,
+        &ctl_challenges,
+    )?;
+    check_table(
+        &all_stark.keccak_sponge_stark,
+        Table::KeccakSponge,
+        keccak_sponge,
+        keccak_sponge_ctl,
+        &ctl_challenges,
+    )?;
+    check_table(
+        &all_stark.logic_stark,
+        Table::Logic,
+        logic,
+        logic_ctl,
+        &ctl_challenges,
+    )?;
+    check_table(
+        &all_stark.memory_stark,
+        Table::Memory,
+        memory,
+        memory_ctl,
+        &ctl_challenges,
+    )?;
+
+    check_ctl_products::<F, D>(all_stark, &ctl_data_per_table, public_values, config)
+}
+
+/// Checks that every CTL filter is binary, as the CTL partial products assume it.
+fn check_filters<F: RichField + Extendable<D>, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    traces: &[Vec<PolynomialValues<F>>; NUM_TABLES],
+) -> Result<(), TraceViolation> {
+    for (ctl_index, ctl) in all_stark.cross_table_lookups.iter().enumerate() {
+        for twc in ctl.looking_tables.iter().chain([&ctl.looked_table]) {
+            let Some(filter) = &twc.filter_column else {
+                continue;
+            };
+            let trace = &traces[twc.table as usize];
+            let non_binary_row = (0..trace[0].len()).find(|&row| {
+                let value = filter.eval_table(trace, row);
+                value != F::ZERO && value != F::ONE
+            });
+            if let Some(row) = non_binary_row {
+                return Err(TraceViolation::NonBinaryFilter {
+                    ctl_index,
+                    table: twc.table,
+                    row,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates the constraints of a table, and those of its lookup and CTL arguments, on each row
+/// of its trace.
+fn check_table<F, S, const D: usize>(
+    stark: &S,
+    table: Table,
+    trace: &[PolynomialValues<F>],
+    ctl_data: &CtlData<F>,
+    ctl_challenges: &GrandProductChallengeSet<F>,
+) -> Result<(), TraceViolation>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let num_rows = trace[0].len();
+    let degree_bits = num_rows.trailing_zeros() as usize;
+    let subgroup = F::two_adic_subgroup(degree_bits);
+    let last = subgroup[num_rows - 1];
+
+    // The lookup arguments use the same challenges as the prover, i.e. the CTL betas.
+    let lookups = stark.lookups();
+    let lookup_challenges = stark.uses_lookups().then(|| {
+        ctl_challenges
+            .challenges
+            .iter()
+            .map(|challenge| challenge.beta)
+            .collect_vec()
+    });
+    let lookup_columns = lookup_challenges.as_ref().map_or(vec![], |challenges| {
+        lookups
+            .iter()
+            .flat_map(|lookup| {
+                challenges.iter().flat_map(move |&challenge| {
+                    lookup_helper_columns(lookup, trace, challenge, stark.constraint_degree())
+                })
+            })
+            .collect_vec()
+    });
+
+    let row = |columns: &[PolynomialValues<F>], i: usize| {
+        columns.iter().map(|column| column.values[i]).collect_vec()
+    };
+    let violation = (0..num_rows)
+        .into_par_iter()
+        .map(|i| {
+            let i_next = (i + 1) % num_rows;
+            let vars = S::EvaluationFrame::from_values(&row(trace, i), &row(trace, i_next));
+            let lookup_vars = lookup_challenges
+                .as_ref()
+                .map(|challenges| LookupCheckVars {
+                    local_values: row(&lookup_columns, i),
+                    next_values: row(&lookup_columns, i_next),
+                    challenges: challenges.clone(),
+                });
+            let ctl_vars = ctl_data
+                .zs_columns
+                .iter()
+                .map(|zs_columns| CtlCheckVars::<F, F, F, 1> {
+                    local_z: zs_columns.z.values[i],
+                    next_z: zs_columns.z.values[i_next],
+                    challenges: zs_columns.challenge,
+                    columns: &zs_columns.columns,
+                    filter_column: &zs_columns.filter_column,
+                })
+                .collect_vec();
+
+            let mut consumer = ConstraintConsumer::new_recording(
+                subgroup[i] - last,
+                F::from_bool(i == 0),
+                F::from_bool(i == num_rows - 1),
+            );
+            eval_vanishing_poly::<F, F, F, S, D, 1>(
+                stark,
+                &vars,
+                &lookups,
+                lookup_vars,
+                &ctl_vars,
+                &mut consumer,
+            );
+            consumer
+                .recorded()
+                .into_iter()
+                .enumerate()
+                .find(|(_, (value, _))| *value != F::ZERO)
+                .map(|(index, (_, location))| TraceViolation::Constraint {
+                    table,
+                    row: i,
+                    index,
+                    location,
+                })
+        })
+        .find_first(Option::is_some)
+        .flatten();
+
+    match violation {
+        Some(violation) => Err(violation),
+        None => Ok(()),
+    }
+}
+
+/// Checks that, for each CTL and challenge, the products of the looking and looked tables match,
+/// as the verifier does with the openings of the CTL `Z` polynomials on the first row.
+fn check_ctl_products<F: RichField + Extendable<D>, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    ctl_data_per_table: &[CtlData<F>; NUM_TABLES],
+    public_values: &PublicValues,
+    config: &StarkConfig,
+) -> Result<(), TraceViolation> {
+    let mut ctl_zs = ctl_data_per_table
+        .iter()
+        .map(|ctl_data| ctl_data.zs_columns.iter())
+        .collect_vec();
+    for (ctl_index, ctl) in all_stark.cross_table_lookups.iter().enumerate() {
+        for _ in 0..config.num_challenges {
+            let mut first_z = |table: Table| {
+                let zs_columns = ctl_zs[table as usize].next().unwrap();
+                (zs_columns.z.values[0], zs_columns.challenge)
+            };
+            let looking_product = ctl
+                .looking_tables
+                .iter()
+                .map(|twc| first_z(twc.table).0)
+                .product::<F>();
+            let (looked_z, challenge) = first_z(ctl.looked_table.table);
+
+            // The verifier adds the memory operations which don't appear in the CPU trace.
+            let extra_product = if ctl.looked_table.table == Table::Memory {
+                get_memory_extra_looking_products::<F, D>(public_values, challenge)
+            } else {
+                F::ONE
+            };
+            if looking_product * extra_product != looked_z {
+                return Err(TraceViolation::CrossTableLookup { ctl_index });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::U256;
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::util::timing::TimingTree;
+
+    use super::*;
+    use crate::byte_packing::byte_packing_stark::BytePackingStark;
+    use crate::logic::{columns, LogicStark, Op, Operation};
+
+    type F = GoldilocksField;
+
+    fn ctl_challenges() -> GrandProductChallengeSet<F> {
+        GrandProductChallengeSet {
+            challenges: vec![GrandProductChallenge {
+                beta: F::rand(),
+                gamma: F::rand(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_first_violation() {
+        type S = BytePackingStark<F, 2>;
+        let trace = vec![PolynomialValues::zero(8); S::COLUMNS];
+        let violation = check_table(
+            &S::default(),
+            Table::BytePacking,
+            &trace,
+            &CtlData::default(),
+            &ctl_challenges(),
+        )
+        .unwrap_err();
+
+        // The filter must start by one.
+        let TraceViolation::Constraint {
+            table,
+            row,
+            index,
+            location,
+        } = violation
+        else {
+            panic!("unexpected violation {violation:?}");
+        };
+        assert_eq!(table, Table::BytePacking);
+        assert_eq!(row, 0);
+        assert_eq!(index, 1);
+        assert!(location.file().ends_with("byte_packing_stark.rs"));
+    }
+    #[test]
+    fn test_corrupted_trace() {
+        let stark = LogicStark::<F, 2>::default();
+        let operations = vec![
+            Operation::new(Op::And, 0xf0f0.into(), 0xff00.into()),
+            Operation::new(Op::Xor, U256::MAX, 42.into()),
+            Operation::new(Op::Or, U256::one() << 200, 3.into()),
+        ];
+        let mut trace = stark.generate_trace(operations, 8, &mut TimingTree::default());
+        let ctl_challenges = ctl_challenges();
+        let check = |trace: &[PolynomialValues<F>]| {
+            check_table(
+                &stark,
+                Table::Logic,
+                trace,
+                &CtlData::default(),
+                &ctl_challenges,
+            )
+        };
+        assert_eq!(check(&trace), Ok(()));
+
+        // Corrupt the result of the XOR.
+        trace[columns::RESULT.start].values[1] += F::ONE;
+        let violation = check(&trace).unwrap_err();
+        assert!(
+            matches!(
+                violation,
+                TraceViolation::Constraint {
+                    table: Table::Logic,
+                    row: 1,
+                    ..
+                }
+            ),
+            "unexpected violation {violation:?}"
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! An estimate of the soundness of the whole EVM proving stack, for security reviews.
+//!
+//! The soundness of an EVM proof is bounded by each of its sub-protocols: the FRI proof of each
+//! table, the random combinations and out-of-domain evaluations of its constraints, its lookup
+//! arguments, the CTL arguments between tables, and each layer of recursion on top. A config
+//! with ample FRI security can still be let down by, e.g., CTL challenges drawn from the base
+//! field, so this estimates every component and reports the weakest one.
+//!
+//! Estimates are conjectured bits of security, following the usual `degree / |F|` bounds of
+//! Schwartz-Zippel and the ethSTARK conjecture for FRI queries. They are meant to compare configs
+//! and find bottlenecks, not as proofs of security.
+
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use serde::Serialize;
+
+use crate::all_stark::{AllStark, Table, NUM_TABLES};
+use crate::config::StarkConfig;
+use crate::constraint_consumer::ConstraintConsumer;
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::stark::Stark;
+
+/// The sub-protocol a soundness estimate applies to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundnessCategory {
+    /// The FRI query phase of a table's proof.
+    FriQueries,
+    /// The out-of-domain evaluation of a table's polynomials, and their batching in FRI.
+    OutOfDomain,
+    /// The random combination of a table's constraints.
+    ConstraintCombination,
+    /// The lookup arguments of a table.
+    Lookup,
+    /// A cross-table lookup.
+    CrossTableLookup,
+    /// A recursive or wrapping circuit.
+    Recursion,
+}
+
+/// The estimated soundness of one component of the proving stack.
+#[derive(Clone, Debug, Serialize)]
+pub struct ComponentSoundness {
+    pub category: SoundnessCategory,
+    /// What the component is, e.g. a table or a CTL.
+    pub name: String,
+    /// Conjectured bits of security.
+    pub bits: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SecurityReport {
+    /// The security targeted by the STARK config.
+    pub target_bits: usize,
+    pub components: Vec<ComponentSoundness>,
+}
+
+impl SecurityReport {
+    /// Returns the weakest component, whose soundness bounds that of the whole stack.
+    pub fn bottleneck(&self) -> &ComponentSoundness {
+        self.components
+            .iter()
+            .min_by(|a, b| a.bits.total_cmp(&b.bits))
+            .expect("A report has components")
+    }
+
+    /// The conjectured security of the whole stack, in bits.
+    pub fn security_bits(&self) -> f64 {
+        self.bottleneck().bits
+    }
+
+    /// Returns the components falling short of the targeted security.
+    pub fn shortfalls(&self) -> impl Iterator<Item = &ComponentSoundness> {
+        self.components
+            .iter()
+            .filter(|component| component.bits < self.target_bits as f64)
+    }
+}
+
+impl Display for SecurityReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bottleneck = self.bottleneck();
+        writeln!(
+            f,
+            "Conjectured security: {:.1} bits (target {}), bounded by {:?} of {}",
+            bottleneck.bits, self.target_bits, bottleneck.category, bottleneck.name
+        )?;
+        for component in &self.components {
+            writeln!(
+                f,
+                "{:>7.1} bits  {:?} of {}",
+                component.bits, component.category, component.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Estimates the soundness of each component of the proving stack, for tables of at most
+/// `2^(degree_bits_ranges[i].end - 1)` rows, as in `AllRecursiveCircuits::new`, and for the given
+/// named recursion configs.
+pub fn security_report<F: RichField + Extendable<D>, const D: usize>(
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+    degree_bits_ranges: &[Range<usize>; NUM_TABLES],
+    recursion_configs: &[(&str, &CircuitConfig)],
+) -> SecurityReport {
+    let max_degree_bits = degree_bits_ranges.clone().map(|range| range.end - 1);
+
+    let mut components = vec![];
+    let mut add_table = |table: Table, table_components: Vec<(SoundnessCategory, f64)>| {
+        for (category, bits) in table_components {
+            components.push(ComponentSoundness {
+                category,
+                name: format!("{table:?}"),
+                bits,
+            });
+        }
+    };
+    add_table(
+        Table::Arithmetic,
+        table_soundness(&all_stark.arithmetic_stark, max_degree_bits[0], config),
+    );
+    add_table(
+        Table::BytePacking,
+        table_soundness(&all_stark.byte_packing_stark, max_degree_bits[1], config),
+    );
+    add_table(
+        Table::Cpu,
+        table_soundness(&all_stark.cpu_stark, max_degree_bits[2], config),
+    );
+    add_table(
+        Table::Keccak,
+        table_soundness(&all_stark.keccak_stark, max_degree_bits[3], config),
+    );
+    add_table(
+        Table::KeccakSponge,
+        table_soundness(&all_stark.keccak_sponge_stark, max_degree_bits[4], config),
+    );
+    add_table(
+        Table::Logic,
+        table_soundness(&all_stark.logic_stark, max_degree_bits[5], config),
+    );
+    add_table(
+        Table::Memory,
+        table_soundness(&all_stark.memory_stark, max_degree_bits[6], config),
+    );
+
+    // A CTL is a grand product argument over the rows of all its tables, each combined from its
+    // columns, with challenges drawn from the base field.
+    let field_bits = F::order().bits() as f64;
+    for (ctl_index, ctl) in all_stark.cross_table_lookups.iter().enumerate() {
+        let twcs = ctl.looking_tables.iter().chain([&ctl.looked_table]);
+        let num_rows = twcs
+            .map(|twc| 2f64.powi(max_degree_bits[twc.table as usize] as i32))
+            .sum::<f64>();
+        let num_columns = ctl.looked_table.columns.len() as f64;
+        let bits_per_challenge = field_bits - (num_rows * (num_columns + 1.0)).log2();
+        components.push(ComponentSoundness {
+            category: SoundnessCategory::CrossTableLookup,
+            name: format!("CTL {ctl_index} into {:?}", ctl.looked_table.table),
+            bits: config.num_challenges as f64 * bits_per_challenge,
+        });
+    }
+
+    for &(name, circuit_config) in recursion_configs {
+        let extension_bits = F::Extension::order().bits() as f64;
+        let query_bits = circuit_config.fri_config.conjectured_query_security_bits() as f64;
+        components.push(ComponentSoundness {
+            category: SoundnessCategory::Recursion,
+            name: name.to_string(),
+            bits: query_bits.min(extension_bits),
+        });
+    }
+
+    SecurityReport {
+        target_bits: config.security_bits,
+        components,
+    }
+}
+
+/// Estimates the soundness of the FRI queries, out-of-domain evaluation, constraint combination
+/// and lookups of a table of `2^degree_bits` rows.
+fn table_soundness<F, S, const D: usize>(
+    stark: &S,
+    degree_bits: usize,
+    config: &StarkConfig,
+) -> Vec<(SoundnessCategory, f64)>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let field_bits = F::order().bits() as f64;
+    let extension_bits = F::Extension::order().bits() as f64;
+    let num_challenges = config.num_challenges as f64;
+
+    let fri_queries =
+        (config.fri_config.conjectured_query_security_bits() as f64).min(extension_bits);
+
+    // `zeta` and the FRI batching challenge are drawn from the extension field, and err with
+    // probability about `|LDE| / |F_ext|`.
+    let lde_bits = config.committed_degree_bits(degree_bits) + config.fri_config.rate_bits;
+    let out_of_domain = extension_bits - lde_bits as f64;
+
+    // Constraints are combined with the powers of each alpha, drawn from the base field.
+    let num_constraints = num_constraints::<F, S, D>(stark).max(1) as f64;
+    let constraint_combination = num_challenges * (field_bits - num_constraints.log2());
+
+    let mut components = vec![
+        (SoundnessCategory::FriQueries, fri_queries),
+        (SoundnessCategory::OutOfDomain, out_of_domain),
+        (
+            SoundnessCategory::ConstraintCombination,
+            constraint_combination,
+        ),
+    ];
+
+    // The logUp argument sums one fraction per looked up value, with a base field challenge.
+    let num_looked_up = stark
+        .lookups()
+        .iter()
+        .map(|lookup| lookup.columns.len() + 1)
+        .sum::<usize>();
+    if num_looked_up > 0 {
+        let lookup_bits =
+            num_challenges * (field_bits - degree_bits as f64 - (num_looked_up as f64).log2());
+        components.push((SoundnessCategory::Lookup, lookup_bits));
+    }
+    components
+}
+
+/// Counts the constraints of a STARK, by evaluating them on a zero frame.
+fn num_constraints<F, S, const D: usize>(stark: &S) -> usize
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let zeros = vec![F::ZERO; S::COLUMNS];
+    let vars = S::EvaluationFrame::from_values(&zeros, &zeros);
+    let mut consumer = ConstraintConsumer::new_recording(F::ZERO, F::ZERO, F::ZERO);
+    stark.eval_packed_base(&vars, &mut consumer);
+    consumer.recorded().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::goldilocks_field::GoldilocksField;
+
+    use super::*;
+    use crate::config::{EvmProverConfig, ProvingGoal};
+
+    type F = GoldilocksField;
+    const D: usize = 2;
+
+    #[test]
+    fn test_bottleneck() {
+        let all_stark = AllStark::<F, D>::default();
+        let config = StarkConfig::standard_fast_config();
+        let degree_bits_ranges = [16..25, 9..20, 12..25, 14..25, 9..20, 12..25, 17..28];
+        let recursion_config = CircuitConfig::standard_recursion_config();
+        let report = security_report(
+            &all_stark,
+            &config,
+            &degree_bits_ranges,
+            &[("recursion", &recursion_config)],
+        );
+
+        // Every table has a component of each category, except the lookups of tables without any.
+        assert_eq!(
+            report
+                .components
+                .iter()
+                .filter(|component| component.category == SoundnessCategory::FriQueries)
+                .count(),
+            NUM_TABLES
+        );
+        // With two challenges from a 64-bit field, the base field arguments are the bottleneck,
+        // well below the FRI queries.
+        let bottleneck = report.bottleneck();
+        assert!(bottleneck.bits < report.components[0].bits);
+        assert!(matches!(
+            bottleneck.category,
+            SoundnessCategory::CrossTableLookup
+                | SoundnessCategory::Lookup
+                | SoundnessCategory::ConstraintCombination
+        ));
+        assert!(report.shortfalls().count() > 0);
+    }
+
+    #[test]
+    fn test_prover_config_meets_target() {
+        let all_stark = AllStark::<F, D>::default();
+        let degree_bits_ranges = [16..25, 9..20, 12..25, 14..25, 9..20, 12..25, 17..28];
+        for goal in [ProvingGoal::ProverLatency, ProvingGoal::ProofSize] {
+            let config = EvmProverConfig::new(goal, 100, &all_stark, &degree_bits_ranges);
+            let report = security_report(
+                &all_stark,
+                &config.stark_config,
+                &degree_bits_ranges,
+                &config.recursion_configs(),
+            );
+
+            // Out-of-domain evaluations are bounded by the extension field, whatever the config.
+            assert!(report
+                .shortfalls()
+                .all(|component| component.category == SoundnessCategory::OutOfDomain));
+            assert!(config.stark_config.num_challenges > 2);
+        }
+    }
+}
@@ -83,8 +83,19 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         1.max(self.constraint_degree() - 1)
     }
 
+    /// The number of degree-`n` chunks the quotient polynomial of each challenge is split into.
+    /// Masking in zero-knowledge mode up to doubles the degree of committed polynomials but not
+    /// the degree of `Z_H`, so quotients then need twice as many chunks, plus one.
+    fn num_quotient_chunks(&self, config: &StarkConfig) -> usize {
+        if config.zero_knowledge {
+            2 * self.quotient_degree_factor() + 1
+        } else {
+            self.quotient_degree_factor()
+        }
+    }
+
     fn num_quotient_polys(&self, config: &StarkConfig) -> usize {
-        self.quotient_degree_factor() * config.num_challenges
+        self.num_quotient_chunks(config) * config.num_challenges
     }
 
     /// Computes the FRI instance used to prove this Stark.
@@ -97,7 +108,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
     ) -> FriInstanceInfo<F, D> {
         let trace_oracle = FriOracleInfo {
             num_polys: Self::COLUMNS,
-            blinding: false,
+            blinding: config.zero_knowledge,
         };
         let trace_info = FriPolynomialInfo::from_range(TRACE_ORACLE_INDEX, 0..Self::COLUMNS);
 
@@ -105,7 +116,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         let num_auxiliary_polys = num_lookup_columns + num_ctl_zs;
         let auxiliary_oracle = FriOracleInfo {
             num_polys: num_auxiliary_polys,
-            blinding: false,
+            blinding: config.zero_knowledge,
         };
         let auxiliary_polys_info =
             FriPolynomialInfo::from_range(AUXILIARY_ORACLE_INDEX, 0..num_auxiliary_polys);
@@ -118,7 +129,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         let num_quotient_polys = self.num_quotient_polys(config);
         let quotient_oracle = FriOracleInfo {
             num_polys: num_quotient_polys,
-            blinding: false,
+            blinding: config.zero_knowledge,
         };
         let quotient_info =
             FriPolynomialInfo::from_range(QUOTIENT_ORACLE_INDEX, 0..num_quotient_polys);
@@ -157,7 +168,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
     ) -> FriInstanceInfoTarget<D> {
         let trace_oracle = FriOracleInfo {
             num_polys: Self::COLUMNS,
-            blinding: false,
+            blinding: inner_config.zero_knowledge,
         };
         let trace_info = FriPolynomialInfo::from_range(TRACE_ORACLE_INDEX, 0..Self::COLUMNS);
 
@@ -165,7 +176,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         let num_auxiliary_polys = num_lookup_columns + num_ctl_zs;
         let auxiliary_oracle = FriOracleInfo {
             num_polys: num_auxiliary_polys,
-            blinding: false,
+            blinding: inner_config.zero_knowledge,
         };
         let auxiliary_polys_info =
             FriPolynomialInfo::from_range(AUXILIARY_ORACLE_INDEX, 0..num_auxiliary_polys);
@@ -178,7 +189,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         let num_quotient_polys = self.num_quotient_polys(inner_config);
         let quotient_oracle = FriOracleInfo {
             num_polys: num_quotient_polys,
-            blinding: false,
+            blinding: inner_config.zero_knowledge,
         };
         let quotient_info =
             FriPolynomialInfo::from_range(QUOTIENT_ORACLE_INDEX, 0..num_quotient_polys);
@@ -1,5 +1,6 @@
 use std::mem::{size_of, transmute_copy, ManuallyDrop};
 
+use anyhow::ensure;
 use ethereum_types::{H160, H256, U256};
 use itertools::Itertools;
 use num::BigUint;
@@ -10,6 +11,8 @@ use plonky2::field::types::Field;
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::util::transpose;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
 
 use crate::witness::errors::ProgramError;
 
@@ -213,3 +216,51 @@ pub(crate) fn biguint_to_mem_vec(x: BigUint) -> Vec<U256> {
 pub(crate) fn h2u(h: H256) -> U256 {
     U256::from_big_endian(&h.0)
 }
+
+/// Splits a bloom filter into the 32-byte words `BlockMetadata` holds.
+pub(crate) fn bloom_words(bloom: &[u8]) -> anyhow::Result<[U256; 8]> {
+    ensure!(
+        bloom.len() == 256,
+        "Invalid bloom filter length {}",
+        bloom.len()
+    );
+    Ok(core::array::from_fn(|i| {
+        U256::from_big_endian(&bloom[32 * i..32 * (i + 1)])
+    }))
+}
+
+/// A byte string, encoded in hex with an optional `0x` prefix.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct HexBytes(pub(crate) Vec<u8>);
+
+impl<'de> Deserialize<'de> for HexBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        decode_hex(&hex).map(HexBytes).map_err(D::Error::custom)
+    }
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.is_ascii() || hex.len() % 2 != 0 {
+        return Err(format!("Invalid hex string {hex:?}"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|err| err.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("0x"), Ok(vec![]));
+        assert_eq!(decode_hex("0x01ff"), Ok(vec![0x01, 0xff]));
+        assert_eq!(decode_hex("01ff"), Ok(vec![0x01, 0xff]));
+        assert!(decode_hex("0x1").is_err());
+        assert!(decode_hex("0xzz").is_err());
+    }
+}
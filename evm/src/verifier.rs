@@ -7,6 +7,8 @@ use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::types::Field;
 use plonky2::fri::verifier::verify_fri_proof;
 use plonky2::hash::hash_types::RichField;
+use plonky2::iop::challenger::Challenger;
+use plonky2::iop::transcript::Transcript;
 use plonky2::plonk::config::GenericConfig;
 use plonky2::plonk::plonk_common::reduce_with_powers;
 
@@ -23,6 +25,7 @@ use crate::memory::segments::Segment;
 use crate::memory::VALUE_LIMBS;
 use crate::proof::{
     AllProof, AllProofChallenges, PublicValues, StarkOpeningSet, StarkProof, StarkProofChallenges,
+    NUM_BLOCK_METADATA_EXTENSIONS, NUM_EXTRA_PUBLIC_VALUES,
 };
 use crate::stark::Stark;
 use crate::util::h2u;
@@ -35,11 +38,44 @@ pub fn verify_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, co
 ) -> Result<()>
 where
 {
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    verify_proof_with_challenger(all_stark, all_proof, &mut challenger, config)
+}
+
+/// Verifies a proof, and returns the Fiat-Shamir transcript recomputed by the verifier, intended
+/// for auditing. It should match the transcript recorded by `prove_with_transcript`.
+pub fn verify_proof_with_transcript<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    all_stark: &AllStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    config: &StarkConfig,
+) -> Result<Transcript<F>> {
+    let mut challenger = Challenger::<F, C::Hasher>::new_recording();
+    verify_proof_with_challenger(all_stark, all_proof, &mut challenger, config)?;
+    Ok(challenger
+        .take_transcript()
+        .expect("The challenger is recording"))
+}
+
+/// Verifies a proof, sampling its challenges with the given challenger, which must be fresh.
+fn verify_proof_with_challenger<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    all_stark: &AllStark<F, D>,
+    all_proof: AllProof<F, C, D>,
+    challenger: &mut Challenger<F, C::Hasher>,
+    config: &StarkConfig,
+) -> Result<()> {
     let AllProofChallenges {
         stark_challenges,
         ctl_challenges,
     } = all_proof
-        .get_challenges(config)
+        .get_challenges(challenger, config)
         .map_err(|_| anyhow::Error::msg("Invalid sampling of proof challenges."))?;
 
     let num_lookup_columns = all_stark.num_lookups_helper_columns(config);
@@ -263,9 +299,61 @@ where
         prod = add_data_write(challenge, block_hashes_segment, prod, index, val);
     }
 
+    // Add block metadata extensions writes.
+    let extensions_segment = F::from_canonical_u32(Segment::BlockMetadataExtensions as u32);
+    for (index, val) in block_metadata_extensions_memory(public_values)
+        .into_iter()
+        .enumerate()
+    {
+        prod = add_data_write(challenge, extensions_segment, prod, index, val);
+    }
+
+    // Add extra public values writes.
+    let extra_segment = F::from_canonical_u32(Segment::ExtraPublicValues as u32);
+    for (index, val) in extra_public_values_memory(public_values)
+        .into_iter()
+        .enumerate()
+    {
+        prod = add_data_write(challenge, extra_segment, prod, index, val);
+    }
+
+    // Add chain constants writes.
+    let constants_segment = F::from_canonical_u32(Segment::ChainConstants as u32);
+    for (index, val) in public_values
+        .chain_constants
+        .values()
+        .into_iter()
+        .enumerate()
+    {
+        prod = add_data_write(challenge, constants_segment, prod, index, val);
+    }
+
     prod
 }
 
+/// Returns the contents of the `BlockMetadataExtensions` segment: the presence flag of each
+/// extension, followed by their values.
+fn block_metadata_extensions_memory(
+    public_values: &PublicValues,
+) -> [U256; 2 * NUM_BLOCK_METADATA_EXTENSIONS] {
+    let extensions = &public_values.block_metadata.extensions.fields;
+    core::array::from_fn(|i| match i.checked_sub(NUM_BLOCK_METADATA_EXTENSIONS) {
+        None => U256::from(extensions[i].is_some() as u8),
+        Some(slot) => extensions[slot].unwrap_or_default(),
+    })
+}
+
+/// Returns the initial contents of the `ExtraPublicValues` segment: the value of each slot before
+/// the state transition, followed by their values after it.
+fn extra_public_values_memory(public_values: &PublicValues) -> [U256; 2 * NUM_EXTRA_PUBLIC_VALUES] {
+    let extra = &public_values.extra_public_values;
+    let (before, after) = (extra.before(), extra.after());
+    core::array::from_fn(|i| match i.checked_sub(NUM_EXTRA_PUBLIC_VALUES) {
+        None => before[i],
+        Some(slot) => after[slot],
+    })
+}
+
 fn add_data_write<F, const D: usize>(
     challenge: GrandProductChallenge<F>,
     segment: F,
@@ -356,13 +444,13 @@ pub(crate) fn verify_stark_proof_with_challenges<
     // Check each polynomial identity, of the form `vanishing(x) = Z_H(x) quotient(x)`, at zeta.
     let zeta_pow_deg = challenges.stark_zeta.exp_power_of_2(degree_bits);
     let z_h_zeta = zeta_pow_deg - F::Extension::ONE;
-    // `quotient_polys_zeta` holds `num_challenges * quotient_degree_factor` evaluations.
-    // Each chunk of `quotient_degree_factor` holds the evaluations of `t_0(zeta),...,t_{quotient_degree_factor-1}(zeta)`
+    // `quotient_polys_zeta` holds `num_challenges * num_quotient_chunks` evaluations.
+    // Each chunk of `num_quotient_chunks` holds the evaluations of `t_0(zeta),...,t_{num_quotient_chunks-1}(zeta)`
     // where the "real" quotient polynomial is `t(X) = t_0(X) + t_1(X)*X^n + t_2(X)*X^{2n} + ...`.
     // So to reconstruct `t(zeta)` we can compute `reduce_with_powers(chunk, zeta^n)` for each
-    // `quotient_degree_factor`-sized chunk of the original evaluations.
+    // `num_quotient_chunks`-sized chunk of the original evaluations.
     for (i, chunk) in quotient_polys
-        .chunks(stark.quotient_degree_factor())
+        .chunks(stark.num_quotient_chunks(config))
         .enumerate()
     {
         ensure!(
@@ -581,6 +669,35 @@ pub(crate) mod testutils {
             extra_looking_rows.push(add_extra_looking_row(block_hashes_segment, index, val));
         }
 
+        // Add block metadata extensions writes.
+        let extensions_segment = F::from_canonical_u32(Segment::BlockMetadataExtensions as u32);
+        for (index, val) in block_metadata_extensions_memory(public_values)
+            .into_iter()
+            .enumerate()
+        {
+            extra_looking_rows.push(add_extra_looking_row(extensions_segment, index, val));
+        }
+
+        // Add extra public values writes.
+        let extra_segment = F::from_canonical_u32(Segment::ExtraPublicValues as u32);
+        for (index, val) in extra_public_values_memory(public_values)
+            .into_iter()
+            .enumerate()
+        {
+            extra_looking_rows.push(add_extra_looking_row(extra_segment, index, val));
+        }
+
+        // Add chain constants writes.
+        let constants_segment = F::from_canonical_u32(Segment::ChainConstants as u32);
+        for (index, val) in public_values
+            .chain_constants
+            .values()
+            .into_iter()
+            .enumerate()
+        {
+            extra_looking_rows.push(add_extra_looking_row(constants_segment, index, val));
+        }
+
         extra_looking_rows
     }
 
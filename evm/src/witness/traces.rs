@@ -11,10 +11,9 @@ use crate::all_stark::{AllStark, NUM_TABLES};
 use crate::arithmetic::{BinaryOperator, Operation};
 use crate::byte_packing::byte_packing_stark::BytePackingOp;
 use crate::config::StarkConfig;
-use crate::cpu::columns::CpuColumnsView;
+use crate::cpu::columns::{CpuColumnsView, NUM_CPU_COLUMNS};
 use crate::keccak_sponge::columns::KECCAK_WIDTH_BYTES;
 use crate::keccak_sponge::keccak_sponge_stark::KeccakSpongeOp;
-use crate::util::trace_rows_to_poly_values;
 use crate::witness::memory::MemoryOp;
 use crate::{arithmetic, keccak, keccak_sponge, logic};
 
@@ -29,11 +28,45 @@ pub struct TraceCheckpoint {
     pub(self) memory_len: usize,
 }
 
+/// A trace stored in column-major form, so that it can be committed to without transposing it.
+#[derive(Clone, Debug)]
+pub(crate) struct ColumnMajorTrace<T, const COLUMNS: usize> {
+    columns: Vec<Vec<T>>,
+}
+
+impl<T: Copy, const COLUMNS: usize> ColumnMajorTrace<T, COLUMNS> {
+    pub(crate) fn new() -> Self {
+        Self {
+            columns: vec![vec![]; COLUMNS],
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.columns[0].len()
+    }
+
+    pub(crate) fn push_row(&mut self, row: [T; COLUMNS]) {
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        for column in &mut self.columns {
+            column.truncate(len);
+        }
+    }
+
+    pub(crate) fn into_columns(self) -> Vec<Vec<T>> {
+        self.columns
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Traces<T: Copy> {
     pub(crate) arithmetic_ops: Vec<arithmetic::Operation>,
     pub(crate) byte_packing_ops: Vec<BytePackingOp>,
-    pub(crate) cpu: Vec<CpuColumnsView<T>>,
+    pub(crate) cpu: ColumnMajorTrace<T, NUM_CPU_COLUMNS>,
     pub(crate) logic_ops: Vec<logic::Operation>,
     pub(crate) memory_ops: Vec<MemoryOp>,
     pub(crate) keccak_inputs: Vec<([u64; keccak::keccak_stark::NUM_INPUTS], usize)>,
@@ -45,7 +78,7 @@ impl<T: Copy> Traces<T> {
         Traces {
             arithmetic_ops: vec![],
             byte_packing_ops: vec![],
-            cpu: vec![],
+            cpu: ColumnMajorTrace::new(),
             logic_ops: vec![],
             memory_ops: vec![],
             keccak_inputs: vec![],
@@ -84,6 +117,28 @@ impl<T: Copy> Traces<T> {
         }
     }
 
+    /// Returns the actual trace lengths of each table, in the order of `Table`.
+    pub(crate) fn table_lengths(&self) -> [usize; NUM_TABLES] {
+        let TraceCheckpoint {
+            arithmetic_len,
+            byte_packing_len,
+            cpu_len,
+            keccak_len,
+            keccak_sponge_len,
+            logic_len,
+            memory_len,
+        } = self.get_lengths();
+        [
+            arithmetic_len,
+            byte_packing_len,
+            cpu_len,
+            keccak_len,
+            keccak_sponge_len,
+            logic_len,
+            memory_len,
+        ]
+    }
+
     /// Returns the number of operations for each STARK module.
     pub fn checkpoint(&self) -> TraceCheckpoint {
         TraceCheckpoint {
@@ -113,7 +168,7 @@ impl<T: Copy> Traces<T> {
     }
 
     pub fn push_cpu(&mut self, val: CpuColumnsView<T>) {
-        self.cpu.push(val);
+        self.cpu.push_row(val.into());
     }
 
     pub fn push_logic(&mut self, op: logic::Operation) {
@@ -186,8 +241,11 @@ impl<T: Copy> Traces<T> {
                 .byte_packing_stark
                 .generate_trace(byte_packing_ops, cap_elements, timing)
         );
-        let cpu_rows = cpu.into_iter().map(|x| x.into()).collect();
-        let cpu_trace = trace_rows_to_poly_values(cpu_rows);
+        let cpu_trace = cpu
+            .into_columns()
+            .into_iter()
+            .map(PolynomialValues::new)
+            .collect();
         let keccak_trace = timed!(
             timing,
             "generate Keccak trace",
@@ -89,6 +89,7 @@ fn add11_yml() -> anyhow::Result<()> {
         block_base_fee: 0xa.into(),
         block_gas_used: 0xa868u64.into(),
         block_bloom: [0.into(); 8],
+        ..BlockMetadata::default()
     };
 
     let mut contract_code = HashMap::new();
@@ -168,6 +169,7 @@ fn add11_yml() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
@@ -116,6 +116,7 @@ fn test_basic_smart_contract() -> anyhow::Result<()> {
         block_bloom: [0.into(); 8],
         block_base_fee: 0xa.into(),
         block_random: Default::default(),
+        ..BlockMetadata::default()
     };
 
     let mut contract_code = HashMap::new();
@@ -200,6 +201,7 @@ fn test_basic_smart_contract() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
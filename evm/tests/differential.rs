@@ -0,0 +1,96 @@
+#![cfg(feature = "differential")]
+
+use std::collections::HashMap;
+
+use eth_trie_utils::nibbles::Nibbles;
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::{Address, BigEndianHash, H256};
+use hex_literal::hex;
+use keccak_hash::keccak;
+use plonky2_evm::generation::differential::diff_against_revm;
+use plonky2_evm::generation::mpt::AccountRlp;
+use plonky2_evm::generation::{GenerationInputs, TrieInputs};
+use plonky2_evm::proof::{BlockHashes, BlockMetadata};
+use plonky2_evm::Node;
+
+/// The `add11_yml` test case from https://github.com/ethereum/tests, executed by both the kernel
+/// and revm. The expected roots are left out, as the executions are compared with each other.
+#[test]
+fn add11_yml_matches_revm() -> anyhow::Result<()> {
+    let beneficiary = hex!("2adc25665018aa1fe0e6bc666dac8fc2697ff9ba");
+    let sender = hex!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+    let to = hex!("095e7baea6a6c7c4c2dfeb977efac326af552d87");
+
+    let code = [0x60, 0x01, 0x60, 0x01, 0x01, 0x60, 0x00, 0x55, 0x00];
+    let code_hash = keccak(code);
+
+    let beneficiary_account = AccountRlp {
+        nonce: 1.into(),
+        ..AccountRlp::default()
+    };
+    let sender_account = AccountRlp {
+        balance: 0x0de0b6b3a7640000u64.into(),
+        ..AccountRlp::default()
+    };
+    let to_account = AccountRlp {
+        balance: 0x0de0b6b3a7640000u64.into(),
+        code_hash,
+        ..AccountRlp::default()
+    };
+
+    let mut state_trie = HashedPartialTrie::from(Node::Empty);
+    for (address, account) in [
+        (beneficiary, beneficiary_account),
+        (sender, sender_account),
+        (to, to_account),
+    ] {
+        let nibbles = Nibbles::from_bytes_be(keccak(address).as_bytes()).unwrap();
+        state_trie.insert(nibbles, rlp::encode(&account).to_vec());
+    }
+
+    let tries = TrieInputs {
+        state_trie,
+        transactions_trie: Node::Empty.into(),
+        receipts_trie: Node::Empty.into(),
+        storage_tries: vec![(keccak(to), Node::Empty.into())],
+    };
+
+    let txn = hex!("f863800a83061a8094095e7baea6a6c7c4c2dfeb977efac326af552d87830186a0801ba0ffb600e63115a7362e7811894a91d8ba4330e526f22121c994c4692035dfdfd5a06198379fcac8de3dbfac48b165df4bf88e2088f294b61efb9a65fe2281c76e16");
+
+    let block_metadata = BlockMetadata {
+        block_beneficiary: Address::from(beneficiary),
+        block_timestamp: 0x03e8.into(),
+        block_number: 1.into(),
+        block_difficulty: 0x020000.into(),
+        block_random: H256::from_uint(&0x020000.into()),
+        block_gaslimit: 0xff112233u32.into(),
+        block_chain_id: 1.into(),
+        block_base_fee: 0xa.into(),
+        block_gas_used: 0xa868u64.into(),
+        block_bloom: [0.into(); 8],
+        ..BlockMetadata::default()
+    };
+
+    let mut contract_code = HashMap::new();
+    contract_code.insert(keccak(vec![]), vec![]);
+    contract_code.insert(code_hash, code.to_vec());
+
+    let inputs = GenerationInputs {
+        signed_txns: vec![txn.to_vec()],
+        tries,
+        contract_code,
+        genesis_state_trie_root: HashedPartialTrie::from(Node::Empty).hash(),
+        block_metadata,
+        gas_used_after: 0xa868u64.into(),
+        block_hashes: BlockHashes {
+            prev_hashes: vec![H256::default(); 256],
+            cur_hash: H256::default(),
+        },
+        ..GenerationInputs::default()
+    };
+
+    if let Some(divergence) = diff_against_revm(&inputs)? {
+        panic!("{divergence}");
+    }
+    Ok(())
+}
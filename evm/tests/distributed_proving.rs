@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use env_logger::{try_init_from_env, Env, DEFAULT_FILTER_ENV};
+use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
+use ethereum_types::H256;
+use keccak_hash::keccak;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::plonk::config::PoseidonGoldilocksConfig;
+use plonky2::util::timing::TimingTree;
+use plonky2_evm::all_stark::{AllStark, Table};
+use plonky2_evm::config::StarkConfig;
+use plonky2_evm::coordinator::{
+    Coordinator, LocalWorker, ProverTask, ProverTaskResult, ProverTransport,
+};
+use plonky2_evm::fixed_recursive_verifier::AllRecursiveCircuits;
+use plonky2_evm::generation::{generate_traces, GenerationInputs, TrieInputs};
+use plonky2_evm::proof::{BlockHashes, BlockMetadata, TrieRoots};
+use plonky2_evm::prover_tasks::{
+    ctl_challenges, BlockRequest, RootRequest, TableProofRequest, TableProofResponse,
+    TableShrinkRequest, TableShrinkResponse, TraceCommitmentRequest, TraceCommitmentResponse,
+};
+use plonky2_evm::Node;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+type F = GoldilocksField;
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+
+/// Sends a value over the wire.
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+}
+
+/// Proves an empty list of transactions with prover tasks, each serialized as it would be to be
+/// sent to a worker.
+#[test]
+#[ignore] // Too slow to run on CI.
+fn test_distributed_proving() -> anyhow::Result<()> {
+    init_logger();
+
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+
+    let inputs = empty_txn_inputs();
+
+    let all_circuits = AllRecursiveCircuits::<F, C, D>::new(
+        &all_stark,
+        &[16..17, 10..11, 15..16, 14..15, 9..10, 12..13, 18..19],
+        &config,
+    );
+
+    let timing = &mut TimingTree::default();
+    let (traces, public_values, _outputs) = generate_traces(&all_stark, inputs, &config, timing)?;
+
+    let trace_commitments = Table::all()
+        .into_iter()
+        .zip(&traces)
+        .map(|(table, trace)| {
+            let request = round_trip(&TraceCommitmentRequest {
+                table,
+                trace: trace.clone(),
+            });
+            round_trip(&request.execute::<C, D>(&config, timing))
+        })
+        .collect::<Vec<TraceCommitmentResponse<F, _>>>();
+    let challenges = round_trip(&ctl_challenges::<F, C, D>(
+        &trace_commitments,
+        &public_values,
+        &config,
+    )?);
+
+    let mut challenger_state = challenges.challenger_state.clone();
+    let mut shrunk_proofs = vec![];
+    for ((table, trace), commitment) in Table::all().into_iter().zip(traces).zip(trace_commitments)
+    {
+        let request = round_trip(&TableProofRequest {
+            table,
+            trace,
+            trace_cap: commitment.trace_cap,
+            ctl_challenges: challenges.ctl_challenges.clone(),
+            challenger_state,
+        });
+        let response: TableProofResponse<F, C, D> =
+            round_trip(&request.execute(&all_stark, &config, timing)?);
+        challenger_state = response.challenger_state;
+
+        let request = round_trip(&TableShrinkRequest {
+            table,
+            proof: response.proof,
+            ctl_challenges: challenges.ctl_challenges.clone(),
+        });
+        let response: TableShrinkResponse<F, C, D> =
+            round_trip(&request.execute(&all_circuits, &config)?);
+        shrunk_proofs.push(response);
+    }
+
+    let root = round_trip(&RootRequest {
+        shrunk_proofs,
+        public_values,
+    })
+    .execute(&all_circuits)?;
+    all_circuits.verify_root(root.proof.clone())?;
+
+    let block = round_trip(&BlockRequest {
+        parent_block_proof: None,
+        agg_root_proof: root.proof,
+        public_values: root.public_values,
+    })
+    .execute(&all_circuits)?;
+    all_circuits.verify_block(&block.proof)
+}
+
+/// Proves the same block with a [`Coordinator`], through a transport serializing every task and
+/// result.
+#[test]
+#[ignore] // Too slow to run on CI.
+fn test_coordinator() -> anyhow::Result<()> {
+    init_logger();
+
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+    let all_circuits = AllRecursiveCircuits::<F, C, D>::new(
+        &all_stark,
+        &[16..17, 10..11, 15..16, 14..15, 9..10, 12..13, 18..19],
+        &config,
+    );
+
+    let worker = LocalWorker {
+        all_stark: &all_stark,
+        config: &config,
+        circuits: &all_circuits,
+    };
+    let coordinator = Coordinator {
+        all_stark: &all_stark,
+        config: &config,
+        circuits: &all_circuits,
+        transport: JsonTransport(worker),
+    };
+    let block =
+        coordinator.prove_block(vec![empty_txn_inputs()], None, &mut TimingTree::default())?;
+    all_circuits.verify_block(&block.proof)
+}
+
+/// Serializes tasks and results as a remote transport would.
+struct JsonTransport<'a>(LocalWorker<'a, F, C, D>);
+
+impl<'a> ProverTransport<F, C, D> for JsonTransport<'a> {
+    fn dispatch(
+        &self,
+        tasks: Vec<ProverTask<F, C, D>>,
+    ) -> anyhow::Result<Vec<ProverTaskResult<F, C, D>>> {
+        let results = self.0.dispatch(round_trip(&tasks))?;
+        Ok(round_trip(&results))
+    }
+}
+
+/// The inputs of a block without transactions, which doesn't change the state.
+fn empty_txn_inputs() -> GenerationInputs {
+    let state_trie = HashedPartialTrie::from(Node::Empty);
+    let transactions_trie = HashedPartialTrie::from(Node::Empty);
+    let receipts_trie = HashedPartialTrie::from(Node::Empty);
+    let mut contract_code = HashMap::new();
+    contract_code.insert(keccak(vec![]), vec![]);
+    let trie_roots_after = TrieRoots {
+        state_root: state_trie.hash(),
+        transactions_root: transactions_trie.hash(),
+        receipts_root: receipts_trie.hash(),
+    };
+    GenerationInputs {
+        signed_txns: vec![],
+        withdrawals: vec![],
+        tries: TrieInputs {
+            state_trie,
+            transactions_trie,
+            receipts_trie,
+            storage_tries: vec![],
+        },
+        trie_roots_after,
+        contract_code,
+        genesis_state_trie_root: HashedPartialTrie::from(Node::Empty).hash(),
+        block_metadata: BlockMetadata::default(),
+        txn_number_before: 0.into(),
+        gas_used_before: 0.into(),
+        gas_used_after: 0.into(),
+        block_bloom_before: [0.into(); 8],
+        block_bloom_after: [0.into(); 8],
+        block_hashes: BlockHashes {
+            prev_hashes: vec![H256::default(); 256],
+            cur_hash: H256::default(),
+        },
+        addresses: vec![],
+        ..GenerationInputs::default()
+    }
+}
+
+fn init_logger() {
+    let _ = try_init_from_env(Env::default().filter_or(DEFAULT_FILTER_ENV, "info"));
+}
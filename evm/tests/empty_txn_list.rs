@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::time::Duration;
 
 use env_logger::{try_init_from_env, Env, DEFAULT_FILTER_ENV};
@@ -8,20 +9,30 @@ use ethereum_types::H256;
 use keccak_hash::keccak;
 use log::info;
 use plonky2::field::goldilocks_field::GoldilocksField;
-use plonky2::plonk::config::PoseidonGoldilocksConfig;
-use plonky2::util::serialization::{DefaultGateSerializer, DefaultGeneratorSerializer};
+use plonky2::plonk::config::{KeccakGoldilocksConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::util::serialization::{
+    ArtifactKind, DefaultGateSerializer, DefaultGeneratorSerializer, IoError,
+    SERIALIZATION_HEADER_LEN,
+};
 use plonky2::util::timing::TimingTree;
-use plonky2_evm::all_stark::AllStark;
+use plonky2_evm::all_stark::{AllStark, Table, NUM_TABLES};
 use plonky2_evm::config::StarkConfig;
-use plonky2_evm::fixed_recursive_verifier::AllRecursiveCircuits;
+use plonky2_evm::fixed_recursive_verifier::{AllRecursiveCircuits, BlockWrapperCircuitData};
 use plonky2_evm::generation::{GenerationInputs, TrieInputs};
-use plonky2_evm::proof::{BlockHashes, BlockMetadata, TrieRoots};
+use plonky2_evm::proof::{BlockHashes, BlockMetadata, PublicValues, PublicValuesTarget, TrieRoots};
+use plonky2_evm::prover::prove;
+use plonky2_evm::verifier::verify_proof;
 use plonky2_evm::Node;
 
 type F = GoldilocksField;
 const D: usize = 2;
 type C = PoseidonGoldilocksConfig;
 
+/// Minimal ranges to prove an empty list.
+const DEGREE_BITS_RANGES: [Range<usize>; NUM_TABLES] =
+    [16..17, 10..11, 15..16, 14..15, 9..10, 12..13, 18..19];
+
 /// Execute the empty list of transactions, i.e. a no-op.
 #[test]
 #[ignore] // Too slow to run on CI.
@@ -30,7 +41,141 @@ fn test_empty_txn_list() -> anyhow::Result<()> {
 
     let all_stark = AllStark::<F, D>::default();
     let config = StarkConfig::standard_fast_config();
+    let all_circuits = empty_txn_list_circuits(&all_stark, &config);
+
+    {
+        let gate_serializer = DefaultGateSerializer;
+        let generator_serializer = DefaultGeneratorSerializer {
+            _phantom: PhantomData::<C>,
+        };
+
+        let timing = TimingTree::new("serialize AllRecursiveCircuits", log::Level::Info);
+        let all_circuits_bytes = all_circuits
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .map_err(|_| anyhow::Error::msg("AllRecursiveCircuits serialization failed."))?;
+        timing.filter(Duration::from_millis(100)).print();
+        info!(
+            "AllRecursiveCircuits length: {} bytes",
+            all_circuits_bytes.len()
+        );
+
+        let timing = TimingTree::new("deserialize AllRecursiveCircuits", log::Level::Info);
+        let all_circuits_from_bytes = AllRecursiveCircuits::<F, C, D>::from_bytes(
+            &all_circuits_bytes,
+            &gate_serializer,
+            &generator_serializer,
+        )
+        .map_err(|_| anyhow::Error::msg("AllRecursiveCircuits deserialization failed."))?;
+        timing.filter(Duration::from_millis(100)).print();
+
+        assert_eq!(all_circuits, all_circuits_from_bytes);
+
+        // The bytes start with the same versioned header as other artifacts.
+        let mut all_circuits_bytes = all_circuits_bytes;
+        all_circuits_bytes[SERIALIZATION_HEADER_LEN - 1] = ArtifactKind::CircuitData as u8;
+        assert!(matches!(
+            AllRecursiveCircuits::<F, C, D>::from_bytes(
+                &all_circuits_bytes,
+                &gate_serializer,
+                &generator_serializer,
+            ),
+            Err(IoError::UnexpectedArtifact { .. })
+        ));
+    }
+
+    prove_empty_block(&all_stark, &config, &all_circuits)?;
+    Ok(())
+}
+
+/// Traces larger than the circuits support are rejected before proving.
+#[test]
+#[ignore] // Too slow to run on CI.
+fn test_unsupported_degree_bits() -> anyhow::Result<()> {
+    init_logger();
+
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+    let all_circuits = empty_txn_list_circuits(&all_stark, &config);
+
+    let degree_bits = DEGREE_BITS_RANGES.map(|range| range.start);
+    all_circuits.check_degree_bits(&degree_bits)?;
+
+    let mut too_large = degree_bits;
+    too_large[Table::Cpu as usize] = DEGREE_BITS_RANGES[Table::Cpu as usize].end;
+    let err = all_circuits.check_degree_bits(&too_large).unwrap_err();
+    assert_eq!(err.tables.len(), 1);
+    assert_eq!(err.tables[0].table, Table::Cpu);
+    assert_eq!(
+        err.tables[0].supported_degree_bits,
+        DEGREE_BITS_RANGES[Table::Cpu as usize]
+            .clone()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
 
+/// Wrap the block proof of the empty list of transactions, so that it only exposes the hash of
+/// the public values.
+#[test]
+#[ignore] // Too slow to run on CI.
+fn test_block_wrapper() -> anyhow::Result<()> {
+    init_logger();
+
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+    let all_circuits = empty_txn_list_circuits(&all_stark, &config);
+    let (block_proof, public_values) = prove_empty_block(&all_stark, &config, &all_circuits)?;
+
+    // The block proof starts with the canonical encoding of its public values.
+    assert_eq!(
+        block_proof.public_inputs[..PublicValuesTarget::SIZE],
+        public_values.to_public_inputs::<F>().unwrap()
+    );
+    let block_wrapper = BlockWrapperCircuitData::<F, C, C, D>::new(&all_circuits.block);
+    let wrapped_block_proof = block_wrapper.prove(&block_proof)?;
+    assert_eq!(wrapped_block_proof.public_inputs.len(), 4);
+    block_wrapper.verify(&wrapped_block_proof, &public_values)
+}
+
+/// The wrapper may use another hasher than the block circuit, e.g. to be verified cheaply on-chain.
+#[test]
+#[ignore] // Too slow to run on CI.
+fn test_block_wrapper_with_keccak() -> anyhow::Result<()> {
+    init_logger();
+
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+    let all_circuits = empty_txn_list_circuits(&all_stark, &config);
+    let (block_proof, public_values) = prove_empty_block(&all_stark, &config, &all_circuits)?;
+
+    let block_wrapper =
+        BlockWrapperCircuitData::<F, KeccakGoldilocksConfig, C, D>::new(&all_circuits.block);
+    let wrapped_block_proof = block_wrapper.prove(&block_proof)?;
+    block_wrapper.verify(&wrapped_block_proof, &public_values)
+}
+
+/// Prove the empty list of transactions natively with Keccak transcripts, which the recursive
+/// circuits don't support.
+#[test]
+fn test_empty_txn_list_with_keccak() -> anyhow::Result<()> {
+    init_logger();
+
+    let all_stark = AllStark::<F, D>::default();
+    let config = StarkConfig::standard_fast_config();
+
+    let mut timing = TimingTree::new("prove", log::Level::Debug);
+    let proof = prove::<F, KeccakGoldilocksConfig, D>(
+        &all_stark,
+        &config,
+        empty_txn_list_inputs(),
+        &mut timing,
+    )?;
+    timing.filter(Duration::from_millis(100)).print();
+
+    verify_proof(&all_stark, proof, &config)
+}
+
+fn empty_txn_list_inputs() -> GenerationInputs {
     let block_metadata = BlockMetadata::default();
 
     let state_trie = HashedPartialTrie::from(Node::Empty);
@@ -47,7 +192,7 @@ fn test_empty_txn_list() -> anyhow::Result<()> {
         transactions_root: transactions_trie.hash(),
         receipts_root: receipts_trie.hash(),
     };
-    let inputs = GenerationInputs {
+    GenerationInputs {
         signed_txns: vec![],
         withdrawals: vec![],
         tries: TrieInputs {
@@ -70,45 +215,26 @@ fn test_empty_txn_list() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
-    };
-
-    let all_circuits = AllRecursiveCircuits::<F, C, D>::new(
-        &all_stark,
-        &[16..17, 10..11, 15..16, 14..15, 9..10, 12..13, 18..19], // Minimal ranges to prove an empty list
-        &config,
-    );
-
-    {
-        let gate_serializer = DefaultGateSerializer;
-        let generator_serializer = DefaultGeneratorSerializer {
-            _phantom: PhantomData::<C>,
-        };
-
-        let timing = TimingTree::new("serialize AllRecursiveCircuits", log::Level::Info);
-        let all_circuits_bytes = all_circuits
-            .to_bytes(&gate_serializer, &generator_serializer)
-            .map_err(|_| anyhow::Error::msg("AllRecursiveCircuits serialization failed."))?;
-        timing.filter(Duration::from_millis(100)).print();
-        info!(
-            "AllRecursiveCircuits length: {} bytes",
-            all_circuits_bytes.len()
-        );
-
-        let timing = TimingTree::new("deserialize AllRecursiveCircuits", log::Level::Info);
-        let all_circuits_from_bytes = AllRecursiveCircuits::<F, C, D>::from_bytes(
-            &all_circuits_bytes,
-            &gate_serializer,
-            &generator_serializer,
-        )
-        .map_err(|_| anyhow::Error::msg("AllRecursiveCircuits deserialization failed."))?;
-        timing.filter(Duration::from_millis(100)).print();
-
-        assert_eq!(all_circuits, all_circuits_from_bytes);
+        ..GenerationInputs::default()
     }
+}
+
+fn empty_txn_list_circuits(
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+) -> AllRecursiveCircuits<F, C, D> {
+    AllRecursiveCircuits::<F, C, D>::new(all_stark, &DEGREE_BITS_RANGES, config)
+}
 
+/// Proves the empty list of transactions up to a block proof, verifying each recursive proof.
+fn prove_empty_block(
+    all_stark: &AllStark<F, D>,
+    config: &StarkConfig,
+    all_circuits: &AllRecursiveCircuits<F, C, D>,
+) -> anyhow::Result<(ProofWithPublicInputs<F, C, D>, PublicValues)> {
     let mut timing = TimingTree::new("prove", log::Level::Info);
     let (root_proof, public_values) =
-        all_circuits.prove_root(&all_stark, &config, inputs, &mut timing)?;
+        all_circuits.prove_root(all_stark, config, empty_txn_list_inputs(), &mut timing)?;
     timing.filter(Duration::from_millis(100)).print();
     all_circuits.verify_root(root_proof.clone())?;
 
@@ -117,8 +243,9 @@ fn test_empty_txn_list() -> anyhow::Result<()> {
         all_circuits.prove_aggregation(false, &root_proof, false, &root_proof, public_values)?;
     all_circuits.verify_aggregation(&agg_proof)?;
 
-    let (block_proof, _) = all_circuits.prove_block(None, &agg_proof, public_values)?;
-    all_circuits.verify_block(&block_proof)
+    let (block_proof, public_values) = all_circuits.prove_block(None, &agg_proof, public_values)?;
+    all_circuits.verify_block(&block_proof)?;
+    Ok((block_proof, public_values))
 }
 
 fn init_logger() {
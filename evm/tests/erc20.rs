@@ -95,6 +95,7 @@ fn test_erc20() -> anyhow::Result<()> {
         block_base_fee: 0xa.into(),
         block_gas_used: gas_used,
         block_bloom: bloom,
+        ..BlockMetadata::default()
     };
 
     let contract_code = [giver_bytecode(), token_bytecode(), vec![]]
@@ -176,6 +177,7 @@ fn test_erc20() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
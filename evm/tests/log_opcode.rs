@@ -142,6 +142,7 @@ fn test_log_opcodes() -> anyhow::Result<()> {
         block_base_fee: 0xa.into(),
         block_gas_used: 0.into(),
         block_bloom: [0.into(); 8],
+        ..BlockMetadata::default()
     };
 
     let mut contract_code = HashMap::new();
@@ -247,6 +248,7 @@ fn test_log_opcodes() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
@@ -377,6 +379,7 @@ fn test_log_with_aggreg() -> anyhow::Result<()> {
             U256::from_dec_str("2722259584404615024560450425766186844160").unwrap(),
         ],
         block_random: Default::default(),
+        ..BlockMetadata::default()
     };
 
     let beneficiary_account_after = AccountRlp {
@@ -454,6 +457,7 @@ fn test_log_with_aggreg() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     // Preprocess all circuits.
@@ -599,6 +603,7 @@ fn test_log_with_aggreg() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove root second", log::Level::Info);
@@ -623,6 +628,10 @@ fn test_log_with_aggreg() -> anyhow::Result<()> {
         },
         block_metadata: public_values.block_metadata,
         block_hashes: public_values.block_hashes,
+        extra_public_values: first_public_values
+            .extra_public_values
+            .merge(&public_values.extra_public_values),
+        chain_constants: public_values.chain_constants,
     };
 
     // We can duplicate the proofs here because the state hasn't mutated.
@@ -822,6 +831,7 @@ fn test_two_txn() -> anyhow::Result<()> {
         block_base_fee: 0xa.into(),
         block_gas_used: 0.into(),
         block_bloom: [0.into(); 8],
+        ..BlockMetadata::default()
     };
 
     let mut contract_code = HashMap::new();
@@ -911,6 +921,7 @@ fn test_two_txn() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
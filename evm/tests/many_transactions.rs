@@ -228,6 +228,7 @@ fn test_four_transactions() -> anyhow::Result<()> {
             prev_hashes: vec![H256::default(); 256],
             cur_hash: H256::default(),
         },
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
@@ -105,6 +105,7 @@ fn self_balance_gas_cost() -> anyhow::Result<()> {
         block_bloom: [0.into(); 8],
         block_base_fee: 0xa.into(),
         block_random: Default::default(),
+        ..BlockMetadata::default()
     };
 
     let mut contract_code = HashMap::new();
@@ -187,6 +188,7 @@ fn self_balance_gas_cost() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
@@ -83,6 +83,7 @@ fn test_selfdestruct() -> anyhow::Result<()> {
         block_base_fee: 0xa.into(),
         block_gas_used: 26002.into(),
         block_bloom: [0.into(); 8],
+        ..BlockMetadata::default()
     };
 
     let contract_code = [(keccak(&code), code), (keccak([]), vec![])].into();
@@ -139,6 +140,7 @@ fn test_selfdestruct() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
+        ..GenerationInputs::default()
     };
 
     let mut timing = TimingTree::new("prove", log::Level::Debug);
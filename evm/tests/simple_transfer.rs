@@ -32,6 +32,14 @@ fn test_simple_transfer() -> anyhow::Result<()> {
     let all_stark = AllStark::<F, D>::default();
     let config = StarkConfig::standard_fast_config();
 
+    let mut timing = TimingTree::new("prove", log::Level::Debug);
+    let proof = prove::<F, C, D>(&all_stark, &config, simple_transfer_inputs(), &mut timing)?;
+    timing.filter(Duration::from_millis(100)).print();
+
+    verify_proof(&all_stark, proof, &config)
+}
+
+fn simple_transfer_inputs() -> GenerationInputs {
     let beneficiary = hex!("deadbeefdeadbeefdeadbeefdeadbeefdeadbeef");
     let sender = hex!("2c7536e3605d9c16a7a3d7b1898e529396a65c23");
     let to = hex!("a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0");
@@ -78,6 +86,7 @@ fn test_simple_transfer() -> anyhow::Result<()> {
         block_base_fee: 0xa.into(),
         block_gas_used: 21032.into(),
         block_bloom: [0.into(); 8],
+        ..BlockMetadata::default()
     };
 
     let mut contract_code = HashMap::new();
@@ -137,7 +146,7 @@ fn test_simple_transfer() -> anyhow::Result<()> {
         transactions_root: transactions_trie.hash(),
         receipts_root: receipts_trie.hash(),
     };
-    let inputs = GenerationInputs {
+    GenerationInputs {
         signed_txns: vec![txn.to_vec()],
         withdrawals: vec![],
         tries: tries_before,
@@ -155,13 +164,8 @@ fn test_simple_transfer() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
-    };
-
-    let mut timing = TimingTree::new("prove", log::Level::Debug);
-    let proof = prove::<F, C, D>(&all_stark, &config, inputs, &mut timing)?;
-    timing.filter(Duration::from_millis(100)).print();
-
-    verify_proof(&all_stark, proof, &config)
+        ..GenerationInputs::default()
+    }
 }
 
 fn eth_to_wei(eth: U256) -> U256 {
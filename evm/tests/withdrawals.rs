@@ -7,15 +7,15 @@ use eth_trie_utils::partial_trie::{HashedPartialTrie, PartialTrie};
 use ethereum_types::{H160, H256, U256};
 use keccak_hash::keccak;
 use plonky2::field::goldilocks_field::GoldilocksField;
-use plonky2::plonk::config::PoseidonGoldilocksConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
 use plonky2::util::timing::TimingTree;
 use plonky2_evm::all_stark::AllStark;
 use plonky2_evm::config::StarkConfig;
 use plonky2_evm::generation::mpt::AccountRlp;
 use plonky2_evm::generation::{GenerationInputs, TrieInputs};
 use plonky2_evm::proof::{BlockHashes, BlockMetadata, TrieRoots};
-use plonky2_evm::prover::prove;
-use plonky2_evm::verifier::verify_proof;
+use plonky2_evm::prover::prove_with_transcript;
+use plonky2_evm::verifier::verify_proof_with_transcript;
 use plonky2_evm::Node;
 use rand::random;
 
@@ -23,7 +23,7 @@ type F = GoldilocksField;
 const D: usize = 2;
 type C = PoseidonGoldilocksConfig;
 
-/// Execute 0 txns and 1 withdrawal.
+/// Execute 0 txns and 1 withdrawal, recording the Fiat-Shamir transcripts.
 #[test]
 fn test_withdrawals() -> anyhow::Result<()> {
     init_logger();
@@ -31,6 +31,22 @@ fn test_withdrawals() -> anyhow::Result<()> {
     let all_stark = AllStark::<F, D>::default();
     let config = StarkConfig::standard_fast_config();
 
+    let mut timing = TimingTree::new("prove", log::Level::Debug);
+    let (proof, transcript) =
+        prove_with_transcript::<F, C, D>(&all_stark, &config, withdrawals_inputs(), &mut timing)?;
+    timing.filter(Duration::from_millis(100)).print();
+
+    // The verifier must sample the same challenges from the same observations as the prover.
+    let verifier_transcript = verify_proof_with_transcript(&all_stark, proof, &config)?;
+    assert_eq!(verifier_transcript, transcript);
+    assert_eq!(
+        transcript.replay::<<C as GenericConfig<D>>::Hasher>(),
+        Ok(())
+    );
+    Ok(())
+}
+
+fn withdrawals_inputs() -> GenerationInputs {
     let block_metadata = BlockMetadata::default();
 
     let state_trie_before = HashedPartialTrie::from(Node::Empty);
@@ -62,7 +78,7 @@ fn test_withdrawals() -> anyhow::Result<()> {
         receipts_root: receipts_trie.hash(),
     };
 
-    let inputs = GenerationInputs {
+    GenerationInputs {
         signed_txns: vec![],
         withdrawals,
         tries: TrieInputs {
@@ -85,13 +101,8 @@ fn test_withdrawals() -> anyhow::Result<()> {
             cur_hash: H256::default(),
         },
         addresses: vec![],
-    };
-
-    let mut timing = TimingTree::new("prove", log::Level::Debug);
-    let proof = prove::<F, C, D>(&all_stark, &config, inputs, &mut timing)?;
-    timing.filter(Duration::from_millis(100)).print();
-
-    verify_proof(&all_stark, proof, &config)
+        ..GenerationInputs::default()
+    }
 }
 
 fn init_logger() {
@@ -0,0 +1,275 @@
+//! `wasm-bindgen` exports for checking EVM block proofs client-side, e.g. in light clients.
+//!
+//! This crate only depends on the `plonky2` verifier, not on the EVM prover, so it builds for
+//! `wasm32-unknown-unknown`:
+//! ```text
+//! cargo build -p plonky2_evm_wasm --release --target wasm32-unknown-unknown
+//! ```
+//! A [`BlockVerifier`] is created from the verifier data of the block circuit, i.e.
+//! `all_circuits.block.circuit.verifier_data()` serialized with [`VerifierCircuitData::to_bytes`]
+//! and the [`DefaultGateSerializer`], and checks block proofs serialized with
+//! [`ProofWithPublicInputs::to_bytes`]. Public values are returned in the Solidity ABI encoding of
+//! `plonky2_evm::abi`. Errors are returned as strings, which `wasm-bindgen` turns into thrown JS
+//! exceptions.
+
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::types::PrimeField64;
+use plonky2::plonk::circuit_data::VerifierCircuitData;
+use plonky2::plonk::config::PoseidonGoldilocksConfig;
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::recursion::cyclic_recursion::check_cyclic_proof_verifier_data;
+use plonky2::util::serialization::DefaultGateSerializer;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+type F = GoldilocksField;
+type C = PoseidonGoldilocksConfig;
+const D: usize = 2;
+
+/// The layout of the EVM public values in the public inputs of a block proof, as runs of
+/// `(count, limbs)`: `count` consecutive ABI words, each encoded as `limbs` little-endian 32-bit
+/// limbs. This mirrors `PublicValues::to_public_inputs` in `plonky2_evm`.
+const PUBLIC_VALUES_LAYOUT: [(usize, usize); 18] = [
+    // Trie roots before and after.
+    (6, 8),
+    // Block metadata: beneficiary, timestamp, number, difficulty, random, gas limit, chain ID,
+    // base fee, gas used and bloom.
+    (1, 5),
+    (3, 1),
+    (1, 8),
+    (1, 2),
+    (1, 1),
+    (2, 2),
+    (8, 8),
+    // Block hashes: the 256 previous hashes and the current one.
+    (257, 8),
+    // Extra block data: genesis state root, transaction numbers, gas used and blooms.
+    (1, 8),
+    (2, 1),
+    (2, 2),
+    (16, 8),
+    // Block metadata extensions: presence flags and values.
+    (8, 1),
+    (8, 8),
+    // Extra public values: declaration flags, and values before and after.
+    (4, 1),
+    (4, 8),
+    (4, 8),
+];
+
+/// The number of public inputs following the [`PUBLIC_VALUES_LAYOUT`] runs, which encode the chain
+/// constant overrides as 8 flags and 8 values of 8 limbs. They aren't part of the ABI encoding, so
+/// they must all be 0.
+const CHAIN_CONSTANTS_SIZE: usize = 9 * 8;
+
+/// The number of public inputs encoding the EVM public values.
+pub const PUBLIC_VALUES_SIZE: usize = {
+    let mut size = CHAIN_CONSTANTS_SIZE;
+    let mut i = 0;
+    while i < PUBLIC_VALUES_LAYOUT.len() {
+        size += PUBLIC_VALUES_LAYOUT[i].0 * PUBLIC_VALUES_LAYOUT[i].1;
+        i += 1;
+    }
+    size
+};
+
+/// The number of bytes of the ABI encoding of the EVM public values.
+pub const PUBLIC_VALUES_ABI_SIZE: usize = 32 * 329;
+
+/// Verifier data for the block circuit, deserialized once and reused across block proofs.
+#[wasm_bindgen]
+pub struct BlockVerifier {
+    data: VerifierCircuitData<F, C, D>,
+}
+
+#[wasm_bindgen]
+impl BlockVerifier {
+    #[wasm_bindgen(constructor)]
+    pub fn new(block_verifier_data: &[u8]) -> Result<BlockVerifier, String> {
+        VerifierCircuitData::from_bytes(block_verifier_data.to_vec(), &DefaultGateSerializer)
+            .map(|data| Self { data })
+            .map_err(|e| e.to_string())
+    }
+
+    /// Verifies a block proof, including that the verifier data it claims in its public inputs
+    /// is the block circuit's.
+    pub fn verify(&self, proof: &[u8]) -> Result<(), String> {
+        let proof = self.proof(proof)?;
+        let data = &self.data;
+        check_cyclic_proof_verifier_data(&proof, &data.verifier_only, &data.common)
+            .map_err(|e| e.to_string())?;
+        data.verify(proof).map_err(|e| e.to_string())
+    }
+
+    /// Returns the ABI encoding of the public values of a block proof. This does not verify the
+    /// proof.
+    #[wasm_bindgen(js_name = publicValues)]
+    pub fn public_values(&self, proof: &[u8]) -> Result<Vec<u8>, String> {
+        abi_encode(&self.proof(proof)?.public_inputs)
+    }
+
+    /// Returns the `keccak256` of the ABI encoding of the public values of a block proof, as
+    /// checked by settlement contracts. This does not verify the proof.
+    #[wasm_bindgen(js_name = publicValuesHash)]
+    pub fn public_values_hash(&self, proof: &[u8]) -> Result<Vec<u8>, String> {
+        Ok(keccak_hash::keccak(self.public_values(proof)?).0.to_vec())
+    }
+}
+
+impl BlockVerifier {
+    fn proof(&self, proof: &[u8]) -> Result<ProofWithPublicInputs<F, C, D>, String> {
+        ProofWithPublicInputs::from_bytes(proof.to_vec(), &self.data.common)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Encodes the public values at the start of `public_inputs` as ABI words. Each word is the
+/// big-endian encoding of the integer whose little-endian 32-bit limbs are the word's inputs.
+fn abi_encode(public_inputs: &[F]) -> Result<Vec<u8>, String> {
+    if public_inputs.len() < PUBLIC_VALUES_SIZE {
+        return Err(format!(
+            "Expected at least {PUBLIC_VALUES_SIZE} public inputs, found {}",
+            public_inputs.len()
+        ));
+    }
+
+    let mut limbs = public_inputs.iter().map(|x| x.to_canonical_u64());
+    let mut encoding = Vec::with_capacity(PUBLIC_VALUES_ABI_SIZE);
+    for (count, num_limbs) in PUBLIC_VALUES_LAYOUT {
+        for _ in 0..count {
+            let mut word = [0; 32];
+            for i in 0..num_limbs {
+                let limb = limbs.next().expect("Checked above");
+                let limb = u32::try_from(limb)
+                    .map_err(|_| format!("Public input {limb} is not a 32-bit limb"))?;
+                word[32 - 4 * (i + 1)..32 - 4 * i].copy_from_slice(&limb.to_be_bytes());
+            }
+            encoding.extend(word);
+        }
+    }
+    debug_assert_eq!(encoding.len(), PUBLIC_VALUES_ABI_SIZE);
+
+    if limbs.take(CHAIN_CONSTANTS_SIZE).any(|limb| limb != 0) {
+        return Err("Public values overriding chain constants can't be ABI encoded".to_string());
+    }
+    Ok(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use ethereum_types::{Address, H256};
+    use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2_evm::abi;
+    use plonky2_evm::proof::{
+        BlockHashes, BlockMetadata, ChainConstant, ChainConstants, ExtraBlockData,
+        ExtraPublicValue, ExtraPublicValues, PublicValues, TrieRoots,
+    };
+
+    use super::*;
+
+    fn public_values() -> PublicValues {
+        let hash = |i: u8| H256::repeat_byte(i);
+        PublicValues {
+            trie_roots_before: TrieRoots {
+                state_root: hash(1),
+                transactions_root: hash(2),
+                receipts_root: hash(3),
+            },
+            trie_roots_after: TrieRoots {
+                state_root: hash(4),
+                transactions_root: hash(5),
+                receipts_root: hash(6),
+            },
+            block_metadata: BlockMetadata {
+                block_beneficiary: Address::repeat_byte(7),
+                block_timestamp: 0x1000.into(),
+                block_number: 42.into(),
+                block_gaslimit: 30_000_000.into(),
+                block_chain_id: 1.into(),
+                block_base_fee: 7.into(),
+                block_bloom: [3.into(); 8],
+                ..BlockMetadata::default()
+            },
+            block_hashes: BlockHashes {
+                prev_hashes: (0..=255).map(hash).collect(),
+                cur_hash: hash(8),
+            },
+            extra_block_data: ExtraBlockData {
+                genesis_state_trie_root: hash(9),
+                txn_number_after: 2.into(),
+                gas_used_after: 21000.into(),
+                ..ExtraBlockData::default()
+            },
+            extra_public_values: ExtraPublicValues {
+                slots: [
+                    None,
+                    Some(ExtraPublicValue {
+                        before: 5.into(),
+                        after: 6.into(),
+                    }),
+                    None,
+                    None,
+                ],
+            },
+            chain_constants: ChainConstants::default(),
+        }
+    }
+
+    #[test]
+    fn test_layout_matches_evm() -> Result<()> {
+        assert_eq!(PUBLIC_VALUES_ABI_SIZE, abi::PUBLIC_VALUES_ABI_SIZE);
+        let public_values = public_values();
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        assert_eq!(pis.len(), PUBLIC_VALUES_SIZE);
+        assert_eq!(
+            abi_encode(&pis).map_err(anyhow::Error::msg)?,
+            public_values.abi_encode().unwrap()
+        );
+        assert!(abi_encode(&pis[1..]).is_err());
+
+        let mut public_values = public_values;
+        public_values
+            .chain_constants
+            .set(ChainConstant::MaxCodeSize, 0x8000.into());
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        assert!(public_values.abi_encode().is_err());
+        assert!(abi_encode(&pis).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_verifier() -> Result<()> {
+        // A circuit exposing EVM public values, but which isn't a cyclic block circuit.
+        let public_values = public_values();
+        let pis = public_values.to_public_inputs::<F>().unwrap();
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let targets = builder.add_virtual_public_input_arr::<PUBLIC_VALUES_SIZE>();
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&targets, &pis);
+        let proof = data.prove(pw)?;
+
+        let verifier_bytes = data
+            .verifier_data()
+            .to_bytes(&DefaultGateSerializer)
+            .map_err(anyhow::Error::msg)?;
+        let proof_bytes = proof.to_bytes();
+
+        let verifier = BlockVerifier::new(&verifier_bytes).map_err(anyhow::Error::msg)?;
+        assert_eq!(
+            verifier.public_values(&proof_bytes),
+            Ok(public_values.abi_encode().unwrap())
+        );
+        assert_eq!(
+            verifier.public_values_hash(&proof_bytes),
+            Ok(public_values.abi_hash().unwrap().0.to_vec())
+        );
+        // The proof is valid, but doesn't carry the verifier data of a cyclic proof.
+        assert!(verifier.verify(&proof_bytes).is_err());
+        assert!(BlockVerifier::new(&proof_bytes).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! A small C API for verifying Goldilocks/Poseidon proofs and EVM block proofs, for node software
+//! that links the library without building Rust code.
+//!
+//! Artifacts are passed as byte buffers in the formats produced by
+//! [`VerifierCircuitData::to_bytes`] (using the [`DefaultGateSerializer`]) and
+//! [`ProofWithPublicInputs::to_bytes`]. For EVM block proofs, the verifier data is that of the
+//! block circuit, i.e. `all_circuits.block.circuit.verifier_data()`.
+//!
+//! Every function returns a [`Plonky2Status`], and [`plonky2_last_error`] describes the last
+//! failure on the calling thread. The C header `include/plonky2_ffi.h` is generated by `cbindgen`
+//! when building this crate.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::plonk::circuit_data::VerifierCircuitData;
+use plonky2::plonk::config::PoseidonGoldilocksConfig;
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::recursion::cyclic_recursion::check_cyclic_proof_verifier_data;
+use plonky2::util::serialization::DefaultGateSerializer;
+use plonky2_evm::abi::PUBLIC_VALUES_ABI_SIZE;
+use plonky2_evm::proof::PublicValues;
+
+type F = GoldilocksField;
+type C = PoseidonGoldilocksConfig;
+const D: usize = 2;
+
+/// The number of bytes of the Solidity ABI encoding of EVM public values, as written by
+/// [`plonky2_evm_block_public_values`].
+pub const PLONKY2_EVM_PUBLIC_VALUES_SIZE: usize = 10528;
+
+const _: () = assert!(PLONKY2_EVM_PUBLIC_VALUES_SIZE == PUBLIC_VALUES_ABI_SIZE);
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Plonky2Status {
+    Ok = 0,
+    /// A pointer was null, or an output buffer was too small.
+    InvalidArgument = 1,
+    /// The verifier data or the proof could not be deserialized.
+    InvalidEncoding = 2,
+    /// The proof is invalid.
+    VerificationFailed = 3,
+    /// The library panicked, which is a bug.
+    Panic = 4,
+}
+
+/// Verifier data for a single circuit, deserialized once and reused across proofs.
+pub struct Plonky2Verifier {
+    data: VerifierCircuitData<F, C, D>,
+}
+
+struct Error {
+    status: Plonky2Status,
+    message: String,
+}
+
+impl Error {
+    fn new(status: Plonky2Status, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Runs `f`, recording its error or panic for `plonky2_last_error`.
+fn run(f: impl FnOnce() -> Result<(), Error>) -> Plonky2Status {
+    let error = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return Plonky2Status::Ok,
+        Ok(Err(error)) => error,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Unknown panic".to_string());
+            Error::new(Plonky2Status::Panic, message)
+        }
+    };
+    let message = CString::new(error.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    error.status
+}
+
+/// Borrows a byte buffer passed by the caller, which may be null if it is empty.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Error> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(Error::new(Plonky2Status::InvalidArgument, "Null buffer"))
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn verifier<'a>(verifier: *const Plonky2Verifier) -> Result<&'a Plonky2Verifier, Error> {
+    verifier
+        .as_ref()
+        .ok_or_else(|| Error::new(Plonky2Status::InvalidArgument, "Null verifier"))
+}
+
+impl Plonky2Verifier {
+    fn proof(&self, proof: &[u8]) -> Result<ProofWithPublicInputs<F, C, D>, Error> {
+        ProofWithPublicInputs::from_bytes(proof.to_vec(), &self.data.common)
+            .map_err(|e| Error::new(Plonky2Status::InvalidEncoding, e))
+    }
+
+    fn verify(&self, proof: ProofWithPublicInputs<F, C, D>) -> Result<(), Error> {
+        self.data
+            .verify(proof)
+            .map_err(|e| Error::new(Plonky2Status::VerificationFailed, e))
+    }
+}
+
+/// Deserializes verifier data into a new verifier, stored in `*out`. The verifier must be released
+/// with `plonky2_verifier_free`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn plonky2_verifier_new(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut Plonky2Verifier,
+) -> Plonky2Status {
+    run(|| {
+        if out.is_null() {
+            return Err(Error::new(Plonky2Status::InvalidArgument, "Null output"));
+        }
+        let bytes = bytes(data, len)?;
+        let data = VerifierCircuitData::from_bytes(bytes.to_vec(), &DefaultGateSerializer)
+            .map_err(|e| Error::new(Plonky2Status::InvalidEncoding, e))?;
+        *out = Box::into_raw(Box::new(Plonky2Verifier { data }));
+        Ok(())
+    })
+}
+
+/// Releases a verifier created by `plonky2_verifier_new`. Null is ignored.
+///
+/// # Safety
+///
+/// `verifier` must be null or a verifier which hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn plonky2_verifier_free(verifier: *mut Plonky2Verifier) {
+    if !verifier.is_null() {
+        drop(Box::from_raw(verifier));
+    }
+}
+
+/// Verifies a proof of the verifier's circuit.
+///
+/// # Safety
+///
+/// `verifier` must be a live verifier, and `proof` must point to `proof_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn plonky2_verify_proof(
+    verifier: *const Plonky2Verifier,
+    proof: *const u8,
+    proof_len: usize,
+) -> Plonky2Status {
+    run(|| {
+        let verifier = self::verifier(verifier)?;
+        let proof = verifier.proof(bytes(proof, proof_len)?)?;
+        verifier.verify(proof)
+    })
+}
+
+/// Verifies an EVM block proof, whose verifier must have been created from the verifier data of
+/// the block circuit. Unlike `plonky2_verify_proof`, this also checks that the verifier data the
+/// cyclic block proof claims in its public inputs is the block circuit's.
+///
+/// # Safety
+///
+/// `verifier` must be a live verifier, and `proof` must point to `proof_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn plonky2_evm_verify_block_proof(
+    verifier: *const Plonky2Verifier,
+    proof: *const u8,
+    proof_len: usize,
+) -> Plonky2Status {
+    run(|| {
+        let verifier = self::verifier(verifier)?;
+        let proof = verifier.proof(bytes(proof, proof_len)?)?;
+        let data = &verifier.data;
+        check_cyclic_proof_verifier_data(&proof, &data.verifier_only, &data.common)
+            .map_err(|e| Error::new(Plonky2Status::VerificationFailed, e))?;
+        verifier.verify(proof)
+    })
+}
+
+/// Writes the public values of an EVM block proof to `out`, in the Solidity ABI encoding of
+/// `plonky2_evm::abi`, which takes `PLONKY2_EVM_PUBLIC_VALUES_SIZE` bytes. This does not verify
+/// the proof.
+///
+/// # Safety
+///
+/// `verifier` must be a live verifier, `proof` must point to `proof_len` readable bytes, and `out`
+/// must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn plonky2_evm_block_public_values(
+    verifier: *const Plonky2Verifier,
+    proof: *const u8,
+    proof_len: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> Plonky2Status {
+    run(|| {
+        if out.is_null() || out_len < PLONKY2_EVM_PUBLIC_VALUES_SIZE {
+            return Err(Error::new(
+                Plonky2Status::InvalidArgument,
+                format!("Expected an output buffer of {PLONKY2_EVM_PUBLIC_VALUES_SIZE} bytes"),
+            ));
+        }
+        let verifier = self::verifier(verifier)?;
+        let proof = verifier.proof(bytes(proof, proof_len)?)?;
+        let encoding = PublicValues::from_public_inputs(&proof.public_inputs)
+            .map_err(|e| Error::new(Plonky2Status::InvalidEncoding, e))?
+            .abi_encode()
+            .map_err(|e| Error::new(Plonky2Status::InvalidEncoding, format!("{e:?}")))?;
+        ptr::copy_nonoverlapping(encoding.as_ptr(), out, encoding.len());
+        Ok(())
+    })
+}
+
+/// Returns a description of the last error on the calling thread, or null if no call has failed.
+/// The string is owned by the library, and stays valid until the next failing call on this
+/// thread.
+#[no_mangle]
+pub extern "C" fn plonky2_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use anyhow::Result;
+    use plonky2::field::types::Field;
+    use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_ffi_verifier() -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        let proof = data.prove(pw)?;
+
+        let verifier_bytes = data
+            .verifier_data()
+            .to_bytes(&DefaultGateSerializer)
+            .map_err(anyhow::Error::msg)?;
+        let proof_bytes = proof.to_bytes();
+        let mut tampered = proof;
+        tampered.public_inputs[0] = F::ONE;
+        let tampered_bytes = tampered.to_bytes();
+
+        unsafe {
+            let mut verifier = ptr::null_mut();
+            assert_eq!(
+                plonky2_verifier_new(verifier_bytes.as_ptr(), verifier_bytes.len(), &mut verifier),
+                Plonky2Status::Ok
+            );
+            assert_eq!(
+                plonky2_verify_proof(verifier, proof_bytes.as_ptr(), proof_bytes.len()),
+                Plonky2Status::Ok
+            );
+            assert_eq!(
+                plonky2_verify_proof(verifier, tampered_bytes.as_ptr(), tampered_bytes.len()),
+                Plonky2Status::VerificationFailed
+            );
+            assert!(!CStr::from_ptr(plonky2_last_error()).to_bytes().is_empty());
+
+            // A single public input isn't an encoding of EVM public values.
+            let mut public_values = vec![0; PLONKY2_EVM_PUBLIC_VALUES_SIZE];
+            assert_eq!(
+                plonky2_evm_block_public_values(
+                    verifier,
+                    proof_bytes.as_ptr(),
+                    proof_bytes.len(),
+                    public_values.as_mut_ptr(),
+                    public_values.len(),
+                ),
+                Plonky2Status::InvalidEncoding
+            );
+            assert_eq!(
+                plonky2_verify_proof(verifier, ptr::null(), 1),
+                Plonky2Status::InvalidArgument
+            );
+            plonky2_verifier_free(verifier);
+
+            let mut verifier = ptr::null_mut();
+            assert_eq!(
+                plonky2_verifier_new(proof_bytes.as_ptr(), proof_bytes.len(), &mut verifier),
+                Plonky2Status::InvalidEncoding
+            );
+            assert!(verifier.is_null());
+        }
+
+        Ok(())
+    }
+}
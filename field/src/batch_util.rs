@@ -63,3 +63,54 @@ pub fn batch_add_inplace<F: Field>(out: &mut [F], a: &[F]) {
         *x_out += *x_a;
     }
 }
+
+/// Elementwise inplace subtraction of two slices of field elements.
+/// Implementation be faster than the trivial for loop.
+pub fn batch_sub_inplace<F: Field>(out: &mut [F], a: &[F]) {
+    let n = out.len();
+    assert_eq!(n, a.len(), "both arrays must have the same length");
+
+    // Split out slice of vectors, leaving leftovers as scalars
+    let (out_packed, out_leftovers) =
+        pack_slice_with_leftovers_mut::<<F as Packable>::Packing>(out);
+    let (a_packed, a_leftovers) = pack_slice_with_leftovers::<<F as Packable>::Packing>(a);
+
+    // Subtract packed and the leftovers
+    for (x_out, x_a) in out_packed.iter_mut().zip(a_packed) {
+        *x_out -= *x_a;
+    }
+    for (x_out, x_a) in out_leftovers.iter_mut().zip(a_leftovers) {
+        *x_out -= *x_a;
+    }
+}
+
+/// Inplace multiplication of a slice of field elements by a scalar.
+/// Implementation be faster than the trivial for loop.
+pub fn batch_scale_inplace<F: Field>(out: &mut [F], c: F) {
+    let (out_packed, out_leftovers) =
+        pack_slice_with_leftovers_mut::<<F as Packable>::Packing>(out);
+    for x_out in out_packed {
+        *x_out *= c;
+    }
+    for x_out in out_leftovers {
+        *x_out *= c;
+    }
+}
+
+/// Elementwise inplace addition of a slice of field elements, scaled by `c`, to another.
+/// Implementation be faster than the trivial for loop.
+pub fn batch_add_scaled_inplace<F: Field>(out: &mut [F], a: &[F], c: F) {
+    let n = out.len();
+    assert_eq!(n, a.len(), "both arrays must have the same length");
+
+    let (out_packed, out_leftovers) =
+        pack_slice_with_leftovers_mut::<<F as Packable>::Packing>(out);
+    let (a_packed, a_leftovers) = pack_slice_with_leftovers::<<F as Packable>::Packing>(a);
+
+    for (x_out, x_a) in out_packed.iter_mut().zip(a_packed) {
+        *x_out += *x_a * c;
+    }
+    for (x_out, x_a) in out_leftovers.iter_mut().zip(a_leftovers) {
+        *x_out += *x_a * c;
+    }
+}
@@ -5,7 +5,8 @@ use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAss
 
 use num::{BigUint, Integer};
 use plonky2_util::{assume, branch_hint};
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::ops::Square;
 use crate::types::{Field, Field64, PrimeField, PrimeField64, Sample};
@@ -20,10 +21,28 @@ const EPSILON: u64 = (1 << 32) - 1;
 ///   = 2**64 - 2**32 + 1
 ///   = 2**32 * (2**32 - 1) + 1
 /// ```
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone)]
 #[repr(transparent)]
 pub struct GoldilocksField(pub u64);
 
+/// Elements are serialized as their canonical `u64` representative, and deserialization rejects
+/// integers which aren't reduced modulo the order.
+impl Serialize for GoldilocksField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.to_canonical_u64())
+    }
+}
+
+impl<'de> Deserialize<'de> for GoldilocksField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let n = u64::deserialize(deserializer)?;
+        if n >= Self::ORDER {
+            return Err(D::Error::custom("non-canonical Goldilocks field element"));
+        }
+        Ok(Self(n))
+    }
+}
+
 impl Default for GoldilocksField {
     fn default() -> Self {
         Self::ZERO
@@ -7,19 +7,23 @@ use core::iter::Sum;
 use core::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
 
 use anyhow::{ensure, Result};
-use itertools::Itertools;
 use plonky2_util::log2_strict;
 use serde::{Deserialize, Serialize};
 
+use crate::batch_util::{
+    batch_add_inplace, batch_add_scaled_inplace, batch_multiply_inplace, batch_scale_inplace,
+    batch_sub_inplace,
+};
 use crate::extension::{Extendable, FieldExtension};
-use crate::fft::{fft, fft_with_options, ifft, FftRootTable};
+use crate::fft::{fft, fft_dispatch, fft_with_options, ifft, FftRootTable};
 use crate::types::Field;
 
 /// A polynomial in point-value form.
 ///
 /// The points are implicitly `g^i`, where `g` generates the subgroup whose size equals the number
 /// of points.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct PolynomialValues<F: Field> {
     pub values: Vec<F>,
 }
@@ -99,10 +103,33 @@ impl<F: Field> PolynomialValues<F> {
 
     /// Adds `rhs * rhs_weight` to `self`. Assumes `self.len() == rhs.len()`.
     pub fn add_assign_scaled(&mut self, rhs: &Self, rhs_weight: F) {
-        self.values
-            .iter_mut()
-            .zip_eq(&rhs.values)
-            .for_each(|(self_v, rhs_v)| *self_v += *rhs_v * rhs_weight)
+        batch_add_scaled_inplace(&mut self.values, &rhs.values, rhs_weight);
+    }
+
+    /// Multiplies `self` by `rhs` pointwise, i.e. multiplies the polynomials modulo the vanishing
+    /// polynomial of the subgroup. Assumes `self.len() == rhs.len()`.
+    pub fn mul_assign_pointwise(&mut self, rhs: &Self) {
+        batch_multiply_inplace(&mut self.values, &rhs.values);
+    }
+}
+
+impl<F: Field> AddAssign<&Self> for PolynomialValues<F> {
+    /// Assumes `self.len() == rhs.len()`.
+    fn add_assign(&mut self, rhs: &Self) {
+        batch_add_inplace(&mut self.values, &rhs.values);
+    }
+}
+
+impl<F: Field> SubAssign<&Self> for PolynomialValues<F> {
+    /// Assumes `self.len() == rhs.len()`.
+    fn sub_assign(&mut self, rhs: &Self) {
+        batch_sub_inplace(&mut self.values, &rhs.values);
+    }
+}
+
+impl<F: Field> MulAssign<F> for PolynomialValues<F> {
+    fn mul_assign(&mut self, rhs: F) {
+        batch_scale_inplace(&mut self.values, rhs);
     }
 }
 
@@ -202,6 +229,13 @@ impl<F: Field> PolynomialCoeffs<F> {
         self.padded(self.len() << rate_bits)
     }
 
+    /// Adds `rhs * rhs_weight` to `self`, padding `self` if `rhs` is longer.
+    pub fn add_assign_scaled(&mut self, rhs: &Self, rhs_weight: F) {
+        let len = max(self.len(), rhs.len());
+        self.coeffs.resize(len, F::ZERO);
+        batch_add_scaled_inplace(&mut self.coeffs[..rhs.len()], &rhs.coeffs, rhs_weight);
+    }
+
     pub fn pad(&mut self, new_len: usize) -> Result<()> {
         ensure!(
             new_len >= self.len(),
@@ -278,6 +312,23 @@ impl<F: Field> PolynomialCoeffs<F> {
         self.coset_fft_with_options(shift, None, None)
     }
 
+    /// Writes into `out` the evaluation of the polynomial, padded to `self.len() << rate_bits`
+    /// coefficients, on the coset `shift*H`. This is the same as
+    /// `self.lde(rate_bits).coset_fft(shift)`, but reuses the allocation of `out`, e.g. to compute
+    /// the LDEs of many polynomials with a single buffer.
+    pub fn coset_lde_into(
+        &self,
+        shift: F,
+        rate_bits: usize,
+        root_table: Option<&FftRootTable<F>>,
+        out: &mut Vec<F>,
+    ) {
+        out.clear();
+        out.extend(shift.powers().zip(&self.coeffs).map(|(r, &c)| r * c));
+        out.resize(self.len() << rate_bits, F::ZERO);
+        fft_dispatch(out, Some(rate_bits), root_table);
+    }
+
     /// Returns the evaluation of the polynomial on the coset `shift*H`.
     pub fn coset_fft_with_options(
         &self,
@@ -364,11 +415,7 @@ impl<F: Field> Sub for &PolynomialCoeffs<F> {
 
 impl<F: Field> AddAssign for PolynomialCoeffs<F> {
     fn add_assign(&mut self, rhs: Self) {
-        let len = max(self.len(), rhs.len());
-        self.coeffs.resize(len, F::ZERO);
-        for (l, r) in self.coeffs.iter_mut().zip(rhs.coeffs) {
-            *l += r;
-        }
+        *self += &rhs;
     }
 }
 
@@ -376,19 +423,13 @@ impl<F: Field> AddAssign<&Self> for PolynomialCoeffs<F> {
     fn add_assign(&mut self, rhs: &Self) {
         let len = max(self.len(), rhs.len());
         self.coeffs.resize(len, F::ZERO);
-        for (l, &r) in self.coeffs.iter_mut().zip(&rhs.coeffs) {
-            *l += r;
-        }
+        batch_add_inplace(&mut self.coeffs[..rhs.len()], &rhs.coeffs);
     }
 }
 
 impl<F: Field> SubAssign for PolynomialCoeffs<F> {
     fn sub_assign(&mut self, rhs: Self) {
-        let len = max(self.len(), rhs.len());
-        self.coeffs.resize(len, F::ZERO);
-        for (l, r) in self.coeffs.iter_mut().zip(rhs.coeffs) {
-            *l -= r;
-        }
+        *self -= &rhs;
     }
 }
 
@@ -396,9 +437,7 @@ impl<F: Field> SubAssign<&Self> for PolynomialCoeffs<F> {
     fn sub_assign(&mut self, rhs: &Self) {
         let len = max(self.len(), rhs.len());
         self.coeffs.resize(len, F::ZERO);
-        for (l, &r) in self.coeffs.iter_mut().zip(&rhs.coeffs) {
-            *l -= r;
-        }
+        batch_sub_inplace(&mut self.coeffs[..rhs.len()], &rhs.coeffs);
     }
 }
 
@@ -413,7 +452,7 @@ impl<F: Field> Mul<F> for &PolynomialCoeffs<F> {
 
 impl<F: Field> MulAssign<F> for PolynomialCoeffs<F> {
     fn mul_assign(&mut self, rhs: F) {
-        self.coeffs.iter_mut().for_each(|x| *x *= rhs);
+        batch_scale_inplace(&mut self.coeffs, rhs);
     }
 }
 
@@ -517,6 +556,80 @@ mod tests {
         assert_eq!(evals, fft_evals);
     }
 
+    #[test]
+    fn test_coset_lde_into() {
+        type F = GoldilocksField;
+
+        let shift = F::rand();
+        let mut buffer = Vec::new();
+        for (k, rate_bits) in [(0, 1), (3, 2), (8, 3), (6, 0)] {
+            let poly = PolynomialCoeffs::new(F::rand_vec(1 << k));
+            poly.coset_lde_into(shift, rate_bits, None, &mut buffer);
+            assert_eq!(buffer, poly.lde(rate_bits).coset_fft(shift).values);
+        }
+    }
+
+    #[test]
+    fn test_values_inplace_ops() {
+        type F = GoldilocksField;
+
+        let n = 64;
+        let a = PolynomialValues::new(F::rand_vec(n));
+        let b = PolynomialValues::new(F::rand_vec(n));
+        let c = F::rand();
+        let pointwise = |f: fn(F, F) -> F| {
+            let values = a.values.iter().zip(&b.values).map(|(&x, &y)| f(x, y));
+            PolynomialValues::new(values.collect())
+        };
+
+        let mut sum = a.clone();
+        sum += &b;
+        assert_eq!(sum, pointwise(|x, y| x + y));
+
+        let mut difference = a.clone();
+        difference -= &b;
+        assert_eq!(difference, pointwise(|x, y| x - y));
+
+        let mut product = a.clone();
+        product.mul_assign_pointwise(&b);
+        assert_eq!(product, pointwise(|x, y| x * y));
+
+        let mut scaled = a.clone();
+        scaled *= c;
+        assert_eq!(
+            scaled,
+            PolynomialValues::new(a.values.iter().map(|&x| x * c).collect())
+        );
+
+        let mut scaled_sum = a.clone();
+        scaled_sum.add_assign_scaled(&b, c);
+        let expected = a.values.iter().zip(&b.values).map(|(&x, &y)| x + y * c);
+        assert_eq!(scaled_sum, PolynomialValues::new(expected.collect()));
+    }
+
+    #[test]
+    fn test_coeffs_inplace_ops() {
+        type F = GoldilocksField;
+
+        // Odd lengths exercise the leftovers of the packed loops.
+        let short = PolynomialCoeffs::new(F::rand_vec(13));
+        let long = PolynomialCoeffs::new(F::rand_vec(29));
+        let c = F::rand();
+        for (a, b) in [(&short, &long), (&long, &short)] {
+            let mut sum = a.clone();
+            sum += b;
+            assert_eq!(sum, a + b);
+
+            let mut difference = a.clone();
+            difference -= b;
+            assert_eq!(difference, a - b);
+
+            let mut scaled_sum = a.clone();
+            scaled_sum.add_assign_scaled(b, c);
+            assert_eq!(scaled_sum, a + &(b * c));
+        }
+    }
+
     #[test]
     fn test_polynomial_multiplication() {
         type F = GoldilocksField;
@@ -4,6 +4,7 @@ use crate::packed::PackedField;
 use crate::types::Field;
 
 /// Precomputations of the evaluation of `Z_H(X) = X^n - 1` on a coset `gK` with `H <= K`.
+#[derive(Debug)]
 pub struct ZeroPolyOnCoset<F: Field> {
     /// `n = |H|`.
     n: F,
@@ -1,3 +1,10 @@
+#![cfg_attr(not(feature = "parallel"), no_std)]
+
+#[cfg(not(feature = "parallel"))]
+extern crate alloc;
+
+#[cfg(not(feature = "parallel"))]
+use alloc::vec::Vec;
 #[cfg(not(feature = "parallel"))]
 use core::{
     iter::{FlatMap, IntoIterator, Iterator},
@@ -11,6 +18,7 @@ pub use rayon::{
         IndexedParallelIterator, ParallelDrainFull, ParallelDrainRange, ParallelExtend,
         ParallelIterator,
     },
+    ThreadPool,
 };
 #[cfg(feature = "parallel")]
 use rayon::{
@@ -53,7 +61,7 @@ where
 #[cfg(not(feature = "parallel"))]
 impl<'data, T: 'data> MaybeParIter<'data> for Vec<T> {
     type Item = &'data T;
-    type Iter = std::slice::Iter<'data, T>;
+    type Iter = core::slice::Iter<'data, T>;
 
     fn par_iter(&'data self) -> Self::Iter {
         self.iter()
@@ -63,7 +71,7 @@ impl<'data, T: 'data> MaybeParIter<'data> for Vec<T> {
 #[cfg(not(feature = "parallel"))]
 impl<'data, T: 'data> MaybeParIter<'data> for [T] {
     type Item = &'data T;
-    type Iter = std::slice::Iter<'data, T>;
+    type Iter = core::slice::Iter<'data, T>;
 
     fn par_iter(&'data self) -> Self::Iter {
         self.iter()
@@ -102,7 +110,7 @@ where
 #[cfg(not(feature = "parallel"))]
 impl<'data, T: 'data> MaybeParIterMut<'data> for Vec<T> {
     type Item = &'data mut T;
-    type Iter = std::slice::IterMut<'data, T>;
+    type Iter = core::slice::IterMut<'data, T>;
 
     fn par_iter_mut(&'data mut self) -> Self::Iter {
         self.iter_mut()
@@ -112,7 +120,7 @@ impl<'data, T: 'data> MaybeParIterMut<'data> for Vec<T> {
 #[cfg(not(feature = "parallel"))]
 impl<'data, T: 'data> MaybeParIterMut<'data> for [T] {
     type Item = &'data mut T;
-    type Iter = std::slice::IterMut<'data, T>;
+    type Iter = core::slice::IterMut<'data, T>;
 
     fn par_iter_mut(&'data mut self) -> Self::Iter {
         self.iter_mut()
@@ -233,6 +241,10 @@ pub trait ParallelIteratorMock {
     where
         P: Fn(&Self::Item) -> bool + Sync + Send;
 
+    fn find_first<P>(self, predicate: P) -> Option<Self::Item>
+    where
+        P: Fn(&Self::Item) -> bool + Sync + Send;
+
     fn flat_map_iter<U, F>(self, map_op: F) -> FlatMap<Self, U, F>
     where
         Self: Sized,
@@ -251,6 +263,13 @@ impl<T: Iterator> ParallelIteratorMock for T {
         self.find(predicate)
     }
 
+    fn find_first<P>(mut self, predicate: P) -> Option<Self::Item>
+    where
+        P: Fn(&Self::Item) -> bool + Sync + Send,
+    {
+        self.find(predicate)
+    }
+
     fn flat_map_iter<U, F>(self, map_op: F) -> FlatMap<Self, U, F>
     where
         Self: Sized,
@@ -280,3 +299,22 @@ where
 {
     (oper_a(), oper_b())
 }
+
+/// Stands in for a rayon thread pool, running everything on the calling thread.
+#[cfg(not(feature = "parallel"))]
+#[derive(Debug, Default)]
+pub struct ThreadPool;
+
+#[cfg(not(feature = "parallel"))]
+impl ThreadPool {
+    pub fn install<OP, R>(&self, op: OP) -> R
+    where
+        OP: FnOnce() -> R,
+    {
+        op()
+    }
+
+    pub fn current_num_threads(&self) -> usize {
+        1
+    }
+}
@@ -0,0 +1,55 @@
+mod allocator;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use plonky2::field::types::Field;
+use plonky2::iop::target::Target;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// A circuit of about `2^degree_bits` gates, computing a power of its input. Grinding is replaced
+/// by more queries, as its cost varies from one proof to another.
+fn circuit(degree_bits: usize) -> (CircuitData<F, C, D>, Target) {
+    let mut config = CircuitConfig::standard_recursion_config();
+    config.fri_config.proof_of_work_bits = 0;
+    config.fri_config.num_query_rounds = 34;
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let x = builder.add_virtual_target();
+    let mut y = x;
+    while builder.num_gates() < (1 << degree_bits) - 16 {
+        y = builder.mul(y, x);
+    }
+    builder.register_public_input(y);
+    (builder.build::<C>(), x)
+}
+
+fn bench_batch_prover(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prove");
+    group.sample_size(10);
+
+    for degree_bits in [12, 14] {
+        let (data, x) = circuit(degree_bits);
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::TWO);
+
+        group.bench_with_input(
+            BenchmarkId::new("single", degree_bits),
+            &degree_bits,
+            |b, _| b.iter(|| data.prove(inputs.clone()).unwrap()),
+        );
+        let batch_prover = data.batch_prover();
+        group.bench_with_input(
+            BenchmarkId::new("batch", degree_bits),
+            &degree_bits,
+            |b, _| b.iter(|| batch_prover.prove(inputs.clone()).unwrap()),
+        );
+    }
+}
+
+criterion_group!(benches, bench_batch_prover);
+criterion_main!(benches);
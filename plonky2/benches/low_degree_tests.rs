@@ -0,0 +1,191 @@
+mod allocator;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialCoeffs;
+use plonky2::field::types::Sample;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::fri::reduction_strategies::FriReductionStrategy;
+use plonky2::fri::stir::{verify_stir_proof, StirConfig};
+use plonky2::fri::structure::{
+    FriBatchInfo, FriInstanceInfo, FriOpeningBatch, FriOpenings, FriOracleInfo, FriPolynomialInfo,
+};
+use plonky2::fri::verifier::verify_fri_proof;
+use plonky2::fri::FriConfig;
+use plonky2::iop::challenger::Challenger;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::util::ceil_div_usize;
+use plonky2::util::timing::TimingTree;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+const RATE_BITS: usize = 3;
+const SECURITY_BITS: usize = 100;
+const NUM_POLYS: usize = 50;
+
+/// FRI and STIR configurations targeting the same conjectured security, without grinding.
+fn configs() -> (FriConfig, StirConfig) {
+    let fri_config = FriConfig {
+        rate_bits: RATE_BITS,
+        cap_height: 4,
+        proof_of_work_bits: 0,
+        reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+        num_query_rounds: ceil_div_usize(SECURITY_BITS, RATE_BITS),
+    };
+    let stir_config = StirConfig {
+        rate_bits: RATE_BITS,
+        cap_height: 4,
+        folding_arity_bits: 4,
+        final_poly_bits: 5,
+        security_bits: SECURITY_BITS,
+        num_ood_samples: 1,
+    };
+    (fri_config, stir_config)
+}
+
+fn batch_instance(
+    degree_bits: usize,
+) -> (
+    PolynomialBatch<F, C, D>,
+    FriInstanceInfo<F, D>,
+    FriOpenings<F, D>,
+) {
+    let polys = (0..NUM_POLYS)
+        .map(|_| PolynomialCoeffs::new(F::rand_vec(1 << degree_bits)))
+        .collect();
+    let batch =
+        PolynomialBatch::from_coeffs(polys, RATE_BITS, false, 4, &mut TimingTree::default(), None);
+
+    let zeta = <F as Extendable<D>>::Extension::rand();
+    let instance = FriInstanceInfo {
+        oracles: vec![FriOracleInfo {
+            num_polys: NUM_POLYS,
+            blinding: false,
+        }],
+        batches: vec![FriBatchInfo {
+            point: zeta,
+            polynomials: FriPolynomialInfo::from_range(0, 0..NUM_POLYS),
+        }],
+    };
+    let openings = FriOpenings {
+        batches: vec![FriOpeningBatch {
+            values: batch
+                .polynomials
+                .iter()
+                .map(|p| p.to_extension::<D>().eval(zeta))
+                .collect(),
+        }],
+    };
+    (batch, instance, openings)
+}
+
+fn bench_low_degree_tests(c: &mut Criterion) {
+    let (fri_config, stir_config) = configs();
+    let mut group = c.benchmark_group("low-degree-test");
+    group.sample_size(10);
+
+    for degree_bits in [14, 16] {
+        let (batch, instance, openings) = batch_instance(degree_bits);
+        let initial_caps = [batch.merkle_tree.cap.clone()];
+        let fri_params = fri_config.fri_params(degree_bits, false);
+        let stir_params = stir_config.stir_params(degree_bits, false);
+
+        let fri_proof = PolynomialBatch::prove_openings(
+            &instance,
+            &[&batch],
+            &mut Challenger::new(),
+            &fri_params,
+            &mut TimingTree::default(),
+        );
+        let stir_proof = PolynomialBatch::prove_openings_stir(
+            &instance,
+            &[&batch],
+            &mut Challenger::new(),
+            &stir_params,
+            &mut TimingTree::default(),
+        );
+        println!(
+            "degree 2^{degree_bits}: FRI proof {} bytes, STIR proof {} bytes",
+            serde_cbor::to_vec(&fri_proof).unwrap().len(),
+            serde_cbor::to_vec(&stir_proof).unwrap().len(),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("fri-prove", degree_bits),
+            &degree_bits,
+            |b, _| {
+                b.iter(|| {
+                    PolynomialBatch::prove_openings(
+                        &instance,
+                        &[&batch],
+                        &mut Challenger::new(),
+                        &fri_params,
+                        &mut TimingTree::default(),
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("stir-prove", degree_bits),
+            &degree_bits,
+            |b, _| {
+                b.iter(|| {
+                    PolynomialBatch::prove_openings_stir(
+                        &instance,
+                        &[&batch],
+                        &mut Challenger::new(),
+                        &stir_params,
+                        &mut TimingTree::default(),
+                    )
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("fri-verify", degree_bits),
+            &degree_bits,
+            |b, _| {
+                b.iter(|| {
+                    let challenges = Challenger::<F, <C as GenericConfig<D>>::Hasher>::new()
+                        .fri_challenges::<C, D>(
+                            &fri_proof.commit_phase_merkle_caps,
+                            &fri_proof.final_poly,
+                            fri_proof.pow_witness,
+                            degree_bits,
+                            &fri_config,
+                        );
+                    verify_fri_proof::<F, C, D>(
+                        &instance,
+                        &openings,
+                        &challenges,
+                        &initial_caps,
+                        &fri_proof,
+                        &fri_params,
+                    )
+                    .unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("stir-verify", degree_bits),
+            &degree_bits,
+            |b, _| {
+                b.iter(|| {
+                    verify_stir_proof::<F, C, D>(
+                        &instance,
+                        &openings,
+                        &initial_caps,
+                        &stir_proof,
+                        &mut Challenger::new(),
+                        &stir_params,
+                    )
+                    .unwrap()
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(benches, bench_low_degree_tests);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+mod allocator;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use num::bigint::RandBigInt;
+use num::BigUint;
+use plonky2::field::secp256k1_base::Secp256K1Base;
+use plonky2::field::types::Field;
+use plonky2::gadgets::biguint::{BigUintTarget, WitnessWriteBigUint};
+use plonky2::iop::witness::PartialWitness;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use rand::rngs::OsRng;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// The number of modular multiplications in each circuit.
+const NUM_PRODUCTS: usize = 16;
+
+type MulMod =
+    fn(&mut CircuitBuilder<F, D>, &BigUintTarget, &BigUintTarget, &BigUint) -> BigUintTarget;
+
+fn schoolbook(
+    builder: &mut CircuitBuilder<F, D>,
+    x: &BigUintTarget,
+    y: &BigUintTarget,
+    modulus: &BigUint,
+) -> BigUintTarget {
+    let product = builder.mul_biguint(x, y);
+    let modulus = builder.constant_biguint(modulus);
+    builder.rem_biguint(&product, &modulus)
+}
+
+fn montgomery(
+    builder: &mut CircuitBuilder<F, D>,
+    x: &BigUintTarget,
+    y: &BigUintTarget,
+    modulus: &BigUint,
+) -> BigUintTarget {
+    builder.mul_montgomery(x, y, modulus)
+}
+
+/// A circuit raising its input to the power `2^NUM_PRODUCTS` modulo `modulus`, and the number of
+/// gates of each multiplication.
+fn circuit(mul_mod: MulMod, modulus: &BigUint) -> (CircuitData<F, C, D>, BigUintTarget, usize) {
+    let config = CircuitConfig::standard_ecc_config();
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let x = builder.add_virtual_biguint_target(modulus.to_u32_digits().len());
+    let mut y = x.clone();
+    for _ in 0..NUM_PRODUCTS {
+        y = mul_mod(&mut builder, &y, &y, modulus);
+    }
+    let gates_per_product = builder.num_gates() / NUM_PRODUCTS;
+    (builder.build::<C>(), x, gates_per_product)
+}
+
+fn bench_mul_mod(c: &mut Criterion) {
+    // A 256-bit prime modulus.
+    let modulus = Secp256K1Base::order();
+    let x = OsRng.gen_biguint_below(&modulus);
+
+    let mut group = c.benchmark_group("mul-mod-256");
+    group.sample_size(10);
+
+    for (name, mul_mod) in [
+        ("schoolbook", schoolbook as MulMod),
+        ("montgomery", montgomery as MulMod),
+    ] {
+        let (data, x_target, gates_per_product) = circuit(mul_mod, &modulus);
+        println!("{name}: {gates_per_product} gates per multiplication");
+        let mut inputs = PartialWitness::new();
+        inputs.set_biguint_target(&x_target, &x);
+
+        group.bench_with_input(BenchmarkId::new("prove", name), &NUM_PRODUCTS, |b, _| {
+            b.iter(|| data.prove(inputs.clone()).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, bench_mul_mod);
+criterion_main!(benches);
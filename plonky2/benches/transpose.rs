@@ -5,9 +5,9 @@ use plonky2::field::goldilocks_field::GoldilocksField;
 use plonky2::field::types::Sample;
 use plonky2::util::transpose;
 
-fn criterion_benchmark(c: &mut Criterion) {
-    type F = GoldilocksField;
+type F = GoldilocksField;
 
+fn bench_polynomial_matrices(c: &mut Criterion) {
     // In practice, for the matrices we care about, each row is associated with a polynomial of
     // degree 2^13, and has been low-degree extended to a length of 2^16.
     const WIDTH: usize = 1 << 16;
@@ -24,5 +24,39 @@ fn criterion_benchmark(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, criterion_benchmark);
+fn bench_trace_matrices(c: &mut Criterion) {
+    // EVM tables have a few hundred columns and up to 2^23 rows. Their traces are generated as
+    // rows and transposed into columns, and transposed back into rows of column values when
+    // committed. The largest matrices take 16 GiB each.
+    const COLUMNS: usize = 256;
+
+    let mut group = c.benchmark_group("transpose trace");
+    group.sample_size(10);
+
+    for degree_bits in [16, 20, 23] {
+        let num_rows = 1 << degree_bits;
+        group.bench_with_input(
+            BenchmarkId::new("rows to columns", degree_bits),
+            &num_rows,
+            |b, &num_rows| {
+                let rows = (0..num_rows)
+                    .map(|_| F::rand_vec(COLUMNS))
+                    .collect::<Vec<_>>();
+                b.iter(|| transpose(&rows));
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("columns to rows", degree_bits),
+            &num_rows,
+            |b, &num_rows| {
+                let columns = (0..COLUMNS)
+                    .map(|_| F::rand_vec(num_rows))
+                    .collect::<Vec<_>>();
+                b.iter(|| transpose(&columns));
+            },
+        );
+    }
+}
+
+criterion_group!(benches, bench_polynomial_matrices, bench_trace_matrices);
 criterion_main!(benches);
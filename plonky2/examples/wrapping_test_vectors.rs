@@ -0,0 +1,54 @@
+use anyhow::Result;
+use plonky2::field::types::Field;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::verifier_spec::VerifierSpec;
+use serde_json::json;
+
+/// Prints test vectors for verifiers of wrapped plonky2 proofs in other proof systems, e.g. a
+/// halo2 circuit with KZG commitments. A proof of a small statement is wrapped in a recursive
+/// proof, and the output holds the [`VerifierSpec`] of the wrapper circuit, the wrapped proof, and
+/// its verifier's transcript, with all Fiat-Shamir challenges.
+fn main() -> Result<()> {
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    let config = CircuitConfig::standard_recursion_config();
+
+    // The inner circuit proves knowledge of x such that x^7 is public.
+    let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+    let x = builder.add_virtual_target();
+    let y = builder.exp_u64(x, 7);
+    builder.register_public_input(y);
+    let inner_data = builder.build::<C>();
+    let mut pw = PartialWitness::new();
+    pw.set_target(x, F::TWO);
+    let inner_proof = inner_data.prove(pw)?;
+
+    // The wrapper circuit verifies the inner proof, and exposes its public inputs.
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+    let verifier_target = builder.constant_verifier_data(&inner_data.verifier_only);
+    builder.verify_proof::<C>(&proof_target, &verifier_target, &inner_data.common);
+    builder.register_public_inputs(&proof_target.public_inputs);
+    let data = builder.build::<C>();
+    let mut pw = PartialWitness::new();
+    pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+    let proof = data.prove(pw)?;
+    data.verify(proof.clone())?;
+
+    let spec = VerifierSpec::new(&data.verifier_only, &data.common);
+    let transcript = proof.transcript(&data.verifier_only.circuit_digest, &data.common)?;
+    assert!(spec.matches(&transcript));
+
+    let vectors = json!({
+        "spec": spec,
+        "proof_with_public_inputs": proof,
+        "transcript": transcript,
+    });
+    println!("{vectors:#}");
+    Ok(())
+}
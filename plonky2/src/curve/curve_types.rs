@@ -0,0 +1,138 @@
+use core::fmt::Debug;
+use core::ops::{Add, Neg};
+
+use keccak_hash::keccak;
+use num::BigUint;
+
+use crate::field::ops::Square;
+use crate::field::types::{Field, PrimeField};
+
+/// A short Weierstrass curve `y^2 = x^3 + A x + B` of prime order.
+pub trait Curve: 'static + Sync + Sized + Copy + Debug {
+    type BaseField: PrimeField;
+    type ScalarField: PrimeField;
+
+    const A: Self::BaseField;
+    const B: Self::BaseField;
+
+    const GENERATOR_AFFINE: AffinePoint<Self>;
+}
+
+/// A point on a short Weierstrass curve, represented in affine coordinates.
+#[derive(Copy, Clone, Debug)]
+pub struct AffinePoint<C: Curve> {
+    pub x: C::BaseField,
+    pub y: C::BaseField,
+    /// Whether this is the point at infinity, in which case `x` and `y` are meaningless.
+    pub zero: bool,
+}
+
+impl<C: Curve> AffinePoint<C> {
+    pub const ZERO: Self = Self {
+        x: C::BaseField::ZERO,
+        y: C::BaseField::ZERO,
+        zero: true,
+    };
+
+    pub const fn nonzero(x: C::BaseField, y: C::BaseField) -> Self {
+        Self { x, y, zero: false }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let Self { x, y, zero } = *self;
+        zero || y.square() == x.cube() + C::A * x + C::B
+    }
+
+    pub fn double(&self) -> Self {
+        let Self { x, y, zero } = *self;
+        if zero || y.is_zero() {
+            return Self::ZERO;
+        }
+        let lambda = (x.square() * C::BaseField::from_canonical_u8(3) + C::A) / y.double();
+        let x3 = lambda.square() - x.double();
+        let y3 = lambda * (x - x3) - y;
+        Self::nonzero(x3, y3)
+    }
+
+    /// Returns `scalar * self`, by double-and-add.
+    pub fn scalar_mul(&self, scalar: C::ScalarField) -> Self {
+        self.biguint_mul(&scalar.to_canonical_biguint())
+    }
+
+    /// Returns `scalar * self` for an integer `scalar`, by double-and-add.
+    pub fn biguint_mul(&self, scalar: &BigUint) -> Self {
+        let mut result = Self::ZERO;
+        for i in (0..scalar.bits()).rev() {
+            result = result.double();
+            if scalar.bit(i) {
+                result = result + *self;
+            }
+        }
+        result
+    }
+}
+
+impl<C: Curve> PartialEq for AffinePoint<C> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.zero, other.zero) {
+            (true, true) => true,
+            (false, false) => self.x == other.x && self.y == other.y,
+            _ => false,
+        }
+    }
+}
+
+impl<C: Curve> Eq for AffinePoint<C> {}
+
+impl<C: Curve> Add for AffinePoint<C> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        if self.zero {
+            return rhs;
+        }
+        if rhs.zero {
+            return self;
+        }
+        if self.x == rhs.x {
+            return if self.y == rhs.y {
+                self.double()
+            } else {
+                Self::ZERO
+            };
+        }
+        let lambda = (rhs.y - self.y) / (rhs.x - self.x);
+        let x3 = lambda.square() - self.x - rhs.x;
+        let y3 = lambda * (self.x - x3) - self.y;
+        Self::nonzero(x3, y3)
+    }
+}
+
+impl<C: Curve> Neg for AffinePoint<C> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            x: self.x,
+            y: -self.y,
+            zero: self.zero,
+        }
+    }
+}
+
+/// Returns a point determined by `domain` and `index`, whose discrete logarithm relative to any
+/// other point is unknown, by hashing to `x` coordinates until one is on the curve. This isn't
+/// constant-time, so it's only meant for public constants such as offsets and generators.
+pub fn hash_to_curve<C: Curve>(domain: &[u8], index: u64) -> AffinePoint<C> {
+    for counter in 0u64.. {
+        let mut preimage = domain.to_vec();
+        preimage.extend(index.to_le_bytes());
+        preimage.extend(counter.to_le_bytes());
+        let x =
+            C::BaseField::from_noncanonical_biguint(BigUint::from_bytes_le(&keccak(preimage).0));
+        if let Some(y) = (x.cube() + C::A * x + C::B).sqrt() {
+            return AffinePoint::nonzero(x, y);
+        }
+    }
+    unreachable!()
+}
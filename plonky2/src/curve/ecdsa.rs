@@ -0,0 +1,83 @@
+//! Native ECDSA signing and verification, mostly to produce and check test vectors for the
+//! [in-circuit verifier](crate::gadgets::ecdsa).
+
+use crate::curve::curve_types::{AffinePoint, Curve};
+use crate::field::types::{Field, PrimeField, Sample};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ECDSASignature<C: Curve> {
+    pub r: C::ScalarField,
+    pub s: C::ScalarField,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ECDSASecretKey<C: Curve>(pub C::ScalarField);
+
+impl<C: Curve> ECDSASecretKey<C> {
+    pub fn to_public(&self) -> ECDSAPublicKey<C> {
+        ECDSAPublicKey(C::GENERATOR_AFFINE.scalar_mul(self.0))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ECDSAPublicKey<C: Curve>(pub AffinePoint<C>);
+
+/// Reduces an `x` coordinate modulo the group order, as ECDSA does for `r`.
+pub fn base_to_scalar<C: Curve>(x: C::BaseField) -> C::ScalarField {
+    C::ScalarField::from_noncanonical_biguint(x.to_canonical_biguint())
+}
+
+/// Signs the hash of a message, which the caller reduces modulo the group order, with a random
+/// nonce.
+pub fn sign_message<C: Curve>(
+    msg_hash: C::ScalarField,
+    sk: ECDSASecretKey<C>,
+) -> ECDSASignature<C> {
+    loop {
+        let k = C::ScalarField::rand();
+        let r = base_to_scalar::<C>(C::GENERATOR_AFFINE.scalar_mul(k).x);
+        if r.is_zero() {
+            continue;
+        }
+        let s = (msg_hash + r * sk.0) / k;
+        if !s.is_zero() {
+            return ECDSASignature { r, s };
+        }
+    }
+}
+
+pub fn verify_message<C: Curve>(
+    msg_hash: C::ScalarField,
+    sig: ECDSASignature<C>,
+    pk: ECDSAPublicKey<C>,
+) -> bool {
+    let ECDSASignature { r, s } = sig;
+    if r.is_zero() || s.is_zero() || pk.0.zero || !pk.0.is_valid() {
+        return false;
+    }
+    let s_inv = s.inverse();
+    let point = C::GENERATOR_AFFINE.scalar_mul(msg_hash * s_inv) + pk.0.scalar_mul(r * s_inv);
+    !point.zero && base_to_scalar::<C>(point.x) == r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::secp256k1::Secp256K1;
+    use crate::field::secp256k1_scalar::Secp256K1Scalar;
+
+    #[test]
+    fn test_ecdsa_native() {
+        type C = Secp256K1;
+
+        let msg_hash = Secp256K1Scalar::rand();
+        let sk = ECDSASecretKey::<C>(Secp256K1Scalar::rand());
+        let pk = sk.to_public();
+
+        let sig = sign_message(msg_hash, sk);
+        assert!(verify_message(msg_hash, sig, pk));
+        assert!(!verify_message(msg_hash + Secp256K1Scalar::ONE, sig, pk));
+        let other_pk = ECDSASecretKey::<C>(Secp256K1Scalar::rand()).to_public();
+        assert!(!verify_message(msg_hash, sig, other_pk));
+    }
+}
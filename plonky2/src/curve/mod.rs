@@ -0,0 +1,3 @@
+pub mod curve_types;
+pub mod ecdsa;
+pub mod secp256k1;
@@ -0,0 +1,72 @@
+use crate::curve::curve_types::{AffinePoint, Curve};
+use crate::field::secp256k1_base::Secp256K1Base;
+use crate::field::secp256k1_scalar::Secp256K1Scalar;
+use crate::field::types::Field;
+
+/// The secp256k1 curve `y^2 = x^3 + 7`, used by Ethereum signatures.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Secp256K1;
+
+impl Curve for Secp256K1 {
+    type BaseField = Secp256K1Base;
+    type ScalarField = Secp256K1Scalar;
+
+    const A: Secp256K1Base = Secp256K1Base::ZERO;
+    const B: Secp256K1Base = Secp256K1Base([7, 0, 0, 0]);
+
+    const GENERATOR_AFFINE: AffinePoint<Self> = AffinePoint::nonzero(
+        Secp256K1Base([
+            0x59F2815B16F81798,
+            0x029BFCDB2DCE28D9,
+            0x55A06295CE870B07,
+            0x79BE667EF9DCBBAC,
+        ]),
+        Secp256K1Base([
+            0x9C47D08FFB10D4B8,
+            0xFD17B448A6855419,
+            0x5DA4FBFC0E1108A8,
+            0x483ADA7726A3C465,
+        ]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use super::*;
+    use crate::curve::curve_types::hash_to_curve;
+    use crate::field::types::{PrimeField, Sample};
+
+    #[test]
+    fn test_generator() {
+        let g = Secp256K1::GENERATOR_AFFINE;
+        assert!(g.is_valid());
+        assert!(g.double().is_valid());
+        assert_eq!(g.biguint_mul(&Secp256K1Scalar::order()), AffinePoint::ZERO);
+        assert_eq!(g.biguint_mul(&BigUint::from(3u8)), g.double() + g);
+    }
+
+    #[test]
+    fn test_scalar_mul() {
+        let g = Secp256K1::GENERATOR_AFFINE;
+        let (a, b) = (Secp256K1Scalar::rand(), Secp256K1Scalar::rand());
+        let sum = g.scalar_mul(a) + g.scalar_mul(b);
+        assert!(sum.is_valid());
+        assert_eq!(sum, g.scalar_mul(a + b));
+        assert_eq!(g.scalar_mul(a) + (-g.scalar_mul(a)), AffinePoint::ZERO);
+        assert_eq!(
+            g.scalar_mul(a).scalar_mul(b),
+            g.biguint_mul(&(a * b).to_canonical_biguint())
+        );
+    }
+
+    #[test]
+    fn test_hash_to_curve() {
+        for i in 0..4 {
+            let p = hash_to_curve::<Secp256K1>(b"test", i);
+            assert!(p.is_valid() && !p.zero);
+            assert_ne!(p, hash_to_curve(b"test", i + 1));
+        }
+    }
+}
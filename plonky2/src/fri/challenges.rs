@@ -1,13 +1,15 @@
+use alloc::vec::Vec;
+
 use crate::field::extension::Extendable;
 use crate::field::polynomial::PolynomialCoeffs;
 use crate::fri::proof::{FriChallenges, FriChallengesTarget};
 use crate::fri::structure::{FriOpenings, FriOpeningsTarget};
-use crate::fri::FriConfig;
+use crate::fri::{FriConfig, FriParams};
 use crate::gadgets::polynomial::PolynomialCoeffsExtTarget;
 use crate::hash::hash_types::{MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::challenger::{Challenger, RecursiveChallenger};
-use crate::iop::target::Target;
+use crate::iop::target::{BoolTarget, Target};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
 
@@ -35,22 +37,28 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
         let num_fri_queries = config.num_query_rounds;
         let lde_size = 1 << (degree_bits + config.rate_bits);
         // Scaling factor to combine polynomials.
+        self.set_transcript_label("fri alpha");
         let fri_alpha = self.get_extension_challenge::<D>();
 
         // Recover the random betas used in the FRI reductions.
         let fri_betas = commit_phase_merkle_caps
             .iter()
             .map(|cap| {
+                self.set_transcript_label("fri commit phase cap");
                 self.observe_cap::<C::Hasher>(cap);
+                self.set_transcript_label("fri beta");
                 self.get_extension_challenge::<D>()
             })
             .collect();
 
+        self.set_transcript_label("fri final poly");
         self.observe_extension_elements(&final_poly.coeffs);
 
+        self.set_transcript_label("fri pow");
         self.observe_element(pow_witness);
         let fri_pow_response = self.get_challenge();
 
+        self.set_transcript_label("fri query indices");
         let fri_query_indices = (0..num_fri_queries)
             .map(|_| self.get_challenge().to_canonical_u64() as usize % lde_size)
             .collect();
@@ -110,4 +118,78 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
             fri_query_indices,
         }
     }
+
+    /// Like `fri_challenges`, for a proof target created with
+    /// `CircuitBuilder::add_virtual_fri_proof_with_variable_degree`, holding a proof with the
+    /// parameters `params[i]` for the only true `degree_flags[i]`. As the number of FRI reductions
+    /// depends on the degree, the transcript is replayed for each element of `params`, and the
+    /// challenges of the selected one are returned.
+    pub fn fri_challenges_with_variable_degree(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        commit_phase_merkle_caps: &[MerkleCapTarget],
+        final_poly: &PolynomialCoeffsExtTarget<D>,
+        pow_witness: Target,
+        params: &[FriParams],
+        degree_flags: &[BoolTarget],
+    ) -> FriChallengesTarget<D> {
+        if let [params] = params {
+            return self.fri_challenges(
+                builder,
+                commit_phase_merkle_caps,
+                final_poly,
+                pow_witness,
+                &params.config,
+            );
+        }
+        assert_eq!(params.len(), degree_flags.len());
+
+        let num_fri_queries = params[0].config.num_query_rounds;
+        let fri_alpha = self.get_extension_challenge(builder);
+        let candidates = params
+            .iter()
+            .map(|params| {
+                let mut challenger = self.clone();
+                let fri_betas = commit_phase_merkle_caps[..params.reduction_arity_bits.len()]
+                    .iter()
+                    .map(|cap| {
+                        challenger.observe_cap(cap);
+                        challenger.get_extension_challenge(builder)
+                    })
+                    .collect::<Vec<_>>();
+                challenger.observe_extension_elements(&final_poly.0[..params.final_poly_len()]);
+                challenger.observe_element(pow_witness);
+                let fri_pow_response = challenger.get_challenge(builder);
+                let fri_query_indices = challenger.get_n_challenges(builder, num_fri_queries);
+                (fri_betas, fri_pow_response, fri_query_indices)
+            })
+            .collect::<Vec<_>>();
+
+        let fri_betas = (0..commit_phase_merkle_caps.len())
+            .map(|i| {
+                // Only the degrees with at least `i + 1` reductions have an `i`th beta.
+                let (flags, betas): (Vec<_>, Vec<_>) = degree_flags
+                    .iter()
+                    .zip(&candidates)
+                    .filter_map(|(&flag, (betas, _, _))| Some((flag, *betas.get(i)?)))
+                    .unzip();
+                builder.select_one_hot_ext(&flags, &betas)
+            })
+            .collect();
+        let pow_responses = candidates.iter().map(|c| c.1).collect::<Vec<_>>();
+        let fri_pow_response = builder.select_one_hot(degree_flags, &pow_responses);
+        let fri_query_indices = (0..num_fri_queries)
+            .map(|i| {
+                let indices = candidates.iter().map(|c| c.2[i]).collect::<Vec<_>>();
+                builder.select_one_hot(degree_flags, &indices)
+            })
+            .collect();
+
+        FriChallengesTarget {
+            fri_alpha,
+            fri_betas,
+            fri_pow_response,
+            fri_query_indices,
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use alloc::vec::Vec;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::fri::reduction_strategies::FriReductionStrategy;
 
@@ -10,12 +10,13 @@ pub mod proof;
 pub mod prover;
 pub mod recursive_verifier;
 pub mod reduction_strategies;
+pub mod stir;
 pub mod structure;
 mod validate_shape;
 pub mod verifier;
 pub mod witness_util;
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct FriConfig {
     /// `rate = 2^{-rate_bits}`.
     pub rate_bits: usize,
@@ -54,11 +55,17 @@ impl FriConfig {
     pub fn num_cap_elements(&self) -> usize {
         1 << self.cap_height
     }
+
+    /// The conjectured security of the query phase, in bits: each query is conjectured to
+    /// contribute `rate_bits` bits, on top of the proof-of-work bits. See the ethSTARK paper.
+    pub fn conjectured_query_security_bits(&self) -> usize {
+        self.num_query_rounds * self.rate_bits + self.proof_of_work_bits as usize
+    }
 }
 
 /// FRI parameters, including generated parameters which are specific to an instance size, in
 /// contrast to `FriConfig` which is user-specified and independent of instance size.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct FriParams {
     /// User-specified FRI configuration.
     pub config: FriConfig,
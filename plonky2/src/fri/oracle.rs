@@ -1,16 +1,18 @@
 use alloc::format;
 use alloc::vec::Vec;
+use core::mem::{self, size_of};
 
 use itertools::Itertools;
 use plonky2_field::types::Field;
 use plonky2_maybe_rayon::*;
 
 use crate::field::extension::Extendable;
-use crate::field::fft::FftRootTable;
+use crate::field::fft::{ifft_with_options, FftRootTable};
 use crate::field::packed::PackedField;
 use crate::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use crate::fri::proof::FriProof;
 use crate::fri::prover::fri_proof;
+use crate::fri::stir::{stir_proof, StirParams, StirProof};
 use crate::fri::structure::{FriBatchInfo, FriInstanceInfo};
 use crate::fri::FriParams;
 use crate::hash::hash_types::RichField;
@@ -18,9 +20,12 @@ use crate::hash::merkle_tree::MerkleTree;
 use crate::iop::challenger::Challenger;
 use crate::plonk::config::GenericConfig;
 use crate::timed;
+use crate::util::randomness::ProverRandomness;
 use crate::util::reducing::ReducingFactor;
 use crate::util::timing::TimingTree;
-use crate::util::{log2_strict, reverse_bits, reverse_index_bits_in_place, transpose};
+use crate::util::{
+    domain_cache, log2_strict, reverse_bits, reverse_index_bits_in_place, transpose_into,
+};
 
 /// Four (~64 bit) field elements gives ~128 bit security.
 pub const SALT_SIZE: usize = 4;
@@ -62,19 +67,42 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         timing: &mut TimingTree,
         fft_root_table: Option<&FftRootTable<F>>,
     ) -> Self {
-        let coeffs = timed!(
+        Self::from_values_with_memory_budget(
+            values,
+            rate_bits,
+            blinding,
+            cap_height,
             timing,
-            "IFFT",
-            values.into_par_iter().map(|v| v.ifft()).collect::<Vec<_>>()
-        );
+            fft_root_table,
+            None,
+        )
+    }
 
-        Self::from_coeffs(
-            coeffs,
+    /// Like `from_values`, but with the LDE buffers bounded as in `from_coeffs_with_memory_budget`.
+    pub fn from_values_with_memory_budget(
+        values: Vec<PolynomialValues<F>>,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
+        memory_budget: Option<usize>,
+    ) -> Self {
+        // The values are interpolated in place, in the same pass that computes their LDEs.
+        let polynomials = values
+            .into_iter()
+            .map(|v| PolynomialCoeffs::new(v.values))
+            .collect();
+        Self::from_polynomials(
+            polynomials,
+            true,
             rate_bits,
             blinding,
             cap_height,
             timing,
             fft_root_table,
+            memory_budget,
+            ProverRandomness::Os,
         )
     }
 
@@ -86,15 +114,101 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         cap_height: usize,
         timing: &mut TimingTree,
         fft_root_table: Option<&FftRootTable<F>>,
+    ) -> Self {
+        Self::from_coeffs_with_memory_budget(
+            polynomials,
+            rate_bits,
+            blinding,
+            cap_height,
+            timing,
+            fft_root_table,
+            None,
+        )
+    }
+
+    /// Like `from_coeffs`, but computes the LDEs of as many polynomials at a time as fit in
+    /// `memory_budget` bytes, on top of the Merkle tree itself. This is slower, as the leaves are
+    /// filled in several passes, but bounds the prover's transient memory. Without a budget, all
+    /// LDEs are computed at once.
+    pub fn from_coeffs_with_memory_budget(
+        polynomials: Vec<PolynomialCoeffs<F>>,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
+        memory_budget: Option<usize>,
+    ) -> Self {
+        Self::from_polynomials(
+            polynomials,
+            false,
+            rate_bits,
+            blinding,
+            cap_height,
+            timing,
+            fft_root_table,
+            memory_budget,
+            ProverRandomness::Os,
+        )
+    }
+
+    /// Like `from_coeffs_with_memory_budget`, but draws the salts from `randomness`, e.g. to make
+    /// a proof reproducible.
+    pub fn from_coeffs_with_randomness(
+        polynomials: Vec<PolynomialCoeffs<F>>,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
+        memory_budget: Option<usize>,
+        randomness: ProverRandomness,
+    ) -> Self {
+        Self::from_polynomials(
+            polynomials,
+            false,
+            rate_bits,
+            blinding,
+            cap_height,
+            timing,
+            fft_root_table,
+            memory_budget,
+            randomness,
+        )
+    }
+
+    /// Commits to `polynomials`. If `interpolate` is set, they hold values over the subgroup
+    /// rather than coefficients, and are interpolated in place by `lde_leaves`.
+    fn from_polynomials(
+        mut polynomials: Vec<PolynomialCoeffs<F>>,
+        interpolate: bool,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
+        memory_budget: Option<usize>,
+        randomness: ProverRandomness,
     ) -> Self {
         let degree = polynomials[0].len();
-        let lde_values = timed!(
+        let mut leaves = timed!(
             timing,
-            "FFT + blinding",
-            Self::lde_values(&polynomials, rate_bits, blinding, fft_root_table)
+            if interpolate {
+                "IFFT + FFT + blinding"
+            } else {
+                "FFT + blinding"
+            },
+            Self::lde_leaves(
+                &mut polynomials,
+                interpolate,
+                rate_bits,
+                blinding,
+                fft_root_table,
+                memory_budget,
+                randomness
+            )
         );
 
-        let mut leaves = timed!(timing, "transpose LDEs", transpose(&lde_values));
         reverse_index_bits_in_place(&mut leaves);
         let merkle_tree = timed!(
             timing,
@@ -111,31 +225,69 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         }
     }
 
-    fn lde_values(
-        polynomials: &[PolynomialCoeffs<F>],
+    /// Computes the rows of the LDEs of `polynomials`, salted if blinding. The LDEs are computed
+    /// in chunks of polynomials, into buffers which are reused from one chunk to the next. If
+    /// `interpolate` is set, each polynomial is first interpolated from its values in place, while
+    /// it is still in cache for its LDE. The salt of each leaf is drawn from its own RNG, so that
+    /// salting is parallel yet reproducible from a seed.
+    fn lde_leaves(
+        polynomials: &mut [PolynomialCoeffs<F>],
+        interpolate: bool,
         rate_bits: usize,
         blinding: bool,
         fft_root_table: Option<&FftRootTable<F>>,
+        memory_budget: Option<usize>,
+        randomness: ProverRandomness,
     ) -> Vec<Vec<F>> {
         let degree = polynomials[0].len();
+        let lde_size = degree << rate_bits;
 
         // If blinding, salt with two random elements to each leaf vector.
         let salt_size = if blinding { SALT_SIZE } else { 0 };
 
-        polynomials
-            .par_iter()
-            .map(|p| {
-                assert_eq!(p.len(), degree, "Polynomial degrees inconsistent");
-                p.lde(rate_bits)
-                    .coset_fft_with_options(F::coset_shift(), Some(rate_bits), fft_root_table)
-                    .values
-            })
-            .chain(
-                (0..salt_size)
-                    .into_par_iter()
-                    .map(|_| F::rand_vec(degree << rate_bits)),
-            )
-            .collect()
+        let chunk_size = match memory_budget {
+            Some(budget) => (budget / (lde_size * size_of::<F>())).clamp(1, polynomials.len()),
+            None => polynomials.len(),
+        };
+
+        let cached_root_table;
+        let fft_root_table = match fft_root_table {
+            Some(table) => table,
+            None => {
+                cached_root_table = domain_cache::fft_root_table(log2_strict(lde_size));
+                &cached_root_table
+            }
+        };
+        // The rows of a root table only depend on the layer, so the table for the subgroup is a
+        // prefix of the table for the LDE.
+        let ifft_root_table = interpolate.then(|| fft_root_table[..log2_strict(degree)].to_vec());
+
+        let mut leaves = (0..lde_size)
+            .map(|_| Vec::with_capacity(polynomials.len() + salt_size))
+            .collect::<Vec<_>>();
+        let mut buffers: Vec<Vec<F>> = Vec::new();
+        for chunk in polynomials.chunks_mut(chunk_size) {
+            buffers.resize_with(chunk.len(), Vec::new);
+            buffers
+                .par_iter_mut()
+                .zip(chunk.par_iter_mut())
+                .for_each(|(buffer, p)| {
+                    assert_eq!(p.len(), degree, "Polynomial degrees inconsistent");
+                    if let Some(table) = &ifft_root_table {
+                        let values = PolynomialValues::new(mem::take(&mut p.coeffs));
+                        *p = ifft_with_options(values, None, Some(table));
+                    }
+                    p.coset_lde_into(F::coset_shift(), rate_bits, Some(fft_root_table), buffer);
+                });
+            transpose_into(&buffers, &mut leaves);
+        }
+        if salt_size > 0 {
+            leaves.par_iter_mut().enumerate().for_each(|(i, leaf)| {
+                let mut rng = randomness.rng(i as u64);
+                leaf.extend((0..salt_size).map(|_| F::sample(&mut rng)));
+            });
+        }
+        leaves
     }
 
     /// Fetches LDE values at the `index * step`th point.
@@ -180,7 +332,62 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         fri_params: &FriParams,
         timing: &mut TimingTree,
     ) -> FriProof<F, C::Hasher, D> {
+        let final_poly = Self::combine_openings(instance, oracles, challenger, timing);
+
+        let lde_final_poly = final_poly.lde(fri_params.config.rate_bits);
+        let lde_final_values = timed!(
+            timing,
+            &format!("perform final FFT {}", lde_final_poly.len()),
+            lde_final_poly.coset_fft(F::coset_shift().into())
+        );
+
+        let fri_proof = fri_proof::<F, C, D>(
+            &oracles
+                .par_iter()
+                .map(|c| &c.merkle_tree)
+                .collect::<Vec<_>>(),
+            lde_final_poly,
+            lde_final_values,
+            challenger,
+            fri_params,
+            timing,
+        );
+
+        fri_proof
+    }
+
+    /// Produces a batch opening proof using the experimental STIR low-degree test instead of FRI.
+    pub fn prove_openings_stir(
+        instance: &FriInstanceInfo<F, D>,
+        oracles: &[&Self],
+        challenger: &mut Challenger<F, C::Hasher>,
+        stir_params: &StirParams,
+        timing: &mut TimingTree,
+    ) -> StirProof<F, C::Hasher, D> {
+        let combined_poly = Self::combine_openings(instance, oracles, challenger, timing);
+
+        stir_proof::<F, C, D>(
+            &oracles
+                .par_iter()
+                .map(|c| &c.merkle_tree)
+                .collect::<Vec<_>>(),
+            combined_poly,
+            challenger,
+            stir_params,
+            timing,
+        )
+    }
+
+    /// Samples `alpha` and combines all the opened polynomials into the single low-degree
+    /// polynomial on which the LDT is performed.
+    fn combine_openings(
+        instance: &FriInstanceInfo<F, D>,
+        oracles: &[&Self],
+        challenger: &mut Challenger<F, C::Hasher>,
+        timing: &mut TimingTree,
+    ) -> PolynomialCoeffs<F::Extension> {
         assert!(D > 1, "Not implemented for D=1.");
+        challenger.set_transcript_label("fri alpha");
         let alpha = challenger.get_extension_challenge::<D>();
         let mut alpha = ReducingFactor::new(alpha);
 
@@ -210,25 +417,48 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
             final_poly += quotient;
         }
 
-        let lde_final_poly = final_poly.lde(fri_params.config.rate_bits);
-        let lde_final_values = timed!(
-            timing,
-            &format!("perform final FFT {}", lde_final_poly.len()),
-            lde_final_poly.coset_fft(F::coset_shift().into())
-        );
+        final_poly
+    }
+}
 
-        let fri_proof = fri_proof::<F, C, D>(
-            &oracles
-                .par_iter()
-                .map(|c| &c.merkle_tree)
-                .collect::<Vec<_>>(),
-            lde_final_poly,
-            lde_final_values,
-            challenger,
-            fri_params,
-            timing,
-        );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
 
-        fri_proof
+    #[test]
+    fn test_from_values_matches_from_coeffs() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let degree = 1 << 6;
+        let rate_bits = 2;
+        let values = (0..20)
+            .map(|_| PolynomialValues::new(F::rand_vec(degree)))
+            .collect::<Vec<_>>();
+        let coeffs = values.iter().map(|v| v.clone().ifft()).collect::<Vec<_>>();
+        let root_table = crate::field::fft::fft_root_table(degree << rate_bits);
+
+        let mut timing = TimingTree::default();
+        let expected =
+            PolynomialBatch::<F, C, D>::from_coeffs(coeffs, rate_bits, false, 2, &mut timing, None);
+        for (table, budget) in [
+            (None, None),
+            (Some(&root_table), None),
+            (None, Some(3 * (degree << rate_bits) * size_of::<F>())),
+        ] {
+            let batch = PolynomialBatch::<F, C, D>::from_values_with_memory_budget(
+                values.clone(),
+                rate_bits,
+                false,
+                2,
+                &mut timing,
+                table,
+                budget,
+            );
+            assert_eq!(batch, expected);
+        }
     }
 }
@@ -86,9 +86,11 @@ fn fri_committed_trees<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>,
             .collect();
         let tree = MerkleTree::<F, C::Hasher>::new(chunked_values, fri_params.config.cap_height);
 
+        challenger.set_transcript_label("fri commit phase cap");
         challenger.observe_cap(&tree.cap);
         trees.push(tree);
 
+        challenger.set_transcript_label("fri beta");
         let beta = challenger.get_extension_challenge::<D>();
         // P(x) = sum_{i<r} x^i * P_i(x^r) becomes sum_{i<r} beta^i * P_i(x).
         coeffs = PolynomialCoeffs::new(
@@ -107,10 +109,14 @@ fn fri_committed_trees<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>,
         .coeffs
         .truncate(coeffs.len() >> fri_params.config.rate_bits);
 
+    challenger.set_transcript_label("fri final poly");
     challenger.observe_extension_elements(&coeffs.coeffs);
     (trees, coeffs)
 }
 
+/// The number of proof-of-work candidates searched in parallel before checking for a witness.
+const POW_CHUNK_SIZE: u64 = 1 << 14;
+
 /// Performs the proof-of-work (a.k.a. grinding) step of the FRI protocol. Returns the PoW witness.
 fn fri_proof_of_work<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     challenger: &mut Challenger<F, C::Hasher>,
@@ -138,20 +144,28 @@ fn fri_proof_of_work<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, c
     let witness_input_pos = challenger.input_buffer.len();
     duplex_intermediate_state.set_from_iter(challenger.input_buffer.clone(), 0);
 
-    let pow_witness = (0..=F::NEG_ONE.to_canonical_u64())
-        .into_par_iter()
-        .find_any(|&candidate| {
-            let mut duplex_state = duplex_intermediate_state;
-            duplex_state.set_elt(F::from_canonical_u64(candidate), witness_input_pos);
-            duplex_state.permute();
-            let pow_response = duplex_state.squeeze().iter().last().unwrap();
-            let leading_zeros = pow_response.to_canonical_u64().leading_zeros();
-            leading_zeros >= min_leading_zeros
+    let is_pow_witness = |&candidate: &u64| {
+        let mut duplex_state = duplex_intermediate_state;
+        duplex_state.set_elt(F::from_canonical_u64(candidate), witness_input_pos);
+        duplex_state.permute();
+        let pow_response = duplex_state.squeeze().iter().last().unwrap();
+        let leading_zeros = pow_response.to_canonical_u64().leading_zeros();
+        leading_zeros >= min_leading_zeros
+    };
+    // Candidates are searched in parallel within chunks, and the smallest witness is kept, so that
+    // proofs don't depend on the number of threads or on their scheduling.
+    let num_candidates = F::ORDER;
+    let pow_witness = (0..num_candidates.div_ceil(POW_CHUNK_SIZE))
+        .find_map(|chunk| {
+            let start = chunk * POW_CHUNK_SIZE;
+            let end = (start + POW_CHUNK_SIZE).min(num_candidates);
+            (start..end).into_par_iter().find_first(is_pow_witness)
         })
         .map(F::from_canonical_u64)
         .expect("Proof of work failed. This is highly unlikely!");
 
     // Recompute pow_response using our normal Challenger code, and make sure it matches.
+    challenger.set_transcript_label("fri pow");
     challenger.observe_element(pow_witness);
     let pow_response = challenger.get_challenge();
     let leading_zeros = pow_response.to_canonical_u64().leading_zeros();
@@ -170,6 +184,7 @@ fn fri_prover_query_rounds<
     n: usize,
     fri_params: &FriParams,
 ) -> Vec<FriQueryRound<F, C::Hasher, D>> {
+    challenger.set_transcript_label("fri query indices");
     challenger
         .get_n_challenges(fri_params.config.num_query_rounds)
         .into_par_iter()
@@ -214,3 +229,37 @@ fn fri_prover_query_round<
         steps: query_steps,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Field;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_proof_of_work_is_deterministic() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = FriConfig {
+            proof_of_work_bits: 12,
+            ..CircuitConfig::standard_recursion_config().fri_config
+        };
+        let witnesses = [1, 2, 5].map(|num_threads| {
+            let thread_pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+            thread_pool.install(|| {
+                let mut challenger = Challenger::<F, <C as GenericConfig<D>>::Hasher>::new();
+                challenger.observe_element(F::ONE);
+                fri_proof_of_work::<F, C, D>(&mut challenger, &config)
+            })
+        });
+        assert_eq!(witnesses[0], witnesses[1]);
+        assert_eq!(witnesses[0], witnesses[2]);
+    }
+}
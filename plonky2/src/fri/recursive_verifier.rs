@@ -17,9 +17,10 @@ use crate::hash::hash_types::{MerkleCapTarget, RichField};
 use crate::iop::ext_target::{flatten_target, ExtensionTarget};
 use crate::iop::target::{BoolTarget, Target};
 use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CircuitConfig;
 use crate::plonk::config::{AlgebraicHasher, GenericConfig};
 use crate::util::reducing::ReducingFactorTarget;
-use crate::util::{log2_strict, reverse_index_bits_in_place};
+use crate::util::{domain_cache, log2_strict};
 use crate::with_context;
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
@@ -40,8 +41,10 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let g_inv = g.exp_u64((arity as u64) - 1);
 
         // The evaluation vector needs to be reordered first.
-        let mut evals = evals.to_vec();
-        reverse_index_bits_in_place(&mut evals);
+        let evals = domain_cache::bit_reversal_permutation(arity_bits)
+            .iter()
+            .map(|&i| evals[i])
+            .collect::<Vec<_>>();
         // Want `g^(arity - rev_x_index_within_coset)` as in the out-of-circuit version. Compute it
         // as `(g^-1)^rev_x_index_within_coset`.
         let start = self.exp_from_bits_const_base(g_inv, x_index_within_coset_bits.iter().rev());
@@ -59,22 +62,8 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// isn't required -- without it we'd get errors elsewhere in the stack -- but just gives more
     /// helpful errors.
     fn check_recursion_config(&self, max_fri_arity_bits: usize) {
-        let random_access = RandomAccessGate::<F, D>::new_from_config(
-            &self.config,
-            max_fri_arity_bits.max(self.config.fri_config.cap_height),
-        );
-        let interpolation_gate = CosetInterpolationGate::<F, D>::with_max_degree(
-            max_fri_arity_bits,
-            self.config.max_quotient_degree_factor,
-        );
-
-        let interpolation_wires = interpolation_gate.num_wires();
-        let interpolation_routed_wires = interpolation_gate.num_routed_wires();
-
-        let min_wires = random_access.num_wires().max(interpolation_wires);
-        let min_routed_wires = random_access
-            .num_routed_wires()
-            .max(interpolation_routed_wires);
+        let (min_wires, min_routed_wires) =
+            min_fri_recursion_wires::<F, D>(&self.config, max_fri_arity_bits);
 
         assert!(
             self.config.num_wires >= min_wires,
@@ -175,6 +164,114 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
+    /// Like `verify_fri_proof`, for polynomials whose degree is only known when proving.
+    /// `params` holds the parameters of each supported degree, sorted by degree, and the proof is
+    /// checked against `params[i]` for the only true `degree_flags[i]`. The proof target must be
+    /// created with `add_virtual_fri_proof_with_variable_degree`, and the challenges with
+    /// `RecursiveChallenger::fri_challenges_with_variable_degree`.
+    pub fn verify_fri_proof_with_variable_degree<C: GenericConfig<D, F = F>>(
+        &mut self,
+        instance: &FriInstanceInfoTarget<D>,
+        openings: &FriOpeningsTarget<D>,
+        challenges: &FriChallengesTarget<D>,
+        initial_merkle_caps: &[MerkleCapTarget],
+        proof: &FriProofTarget<D>,
+        params: &[FriParams],
+        degree_flags: &[BoolTarget],
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        if let [params] = params {
+            return self.verify_fri_proof::<C>(
+                instance,
+                openings,
+                challenges,
+                initial_merkle_caps,
+                proof,
+                params,
+            );
+        }
+
+        let max_params = check_variable_degree_params(params);
+        assert_eq!(params.len(), degree_flags.len());
+        let num_selected = self.add_many(degree_flags.iter().map(|flag| flag.target));
+        self.assert_one(num_selected);
+        if let Some(max_arity_bits) = max_params.max_arity_bits() {
+            self.check_recursion_config(max_arity_bits);
+        }
+
+        with_context!(
+            self,
+            "check PoW",
+            self.fri_verify_proof_of_work(challenges.fri_pow_response, &max_params.config)
+        );
+
+        // Coefficients beyond the selected final polynomial's length aren't observed by the
+        // challenger, so they must be zero.
+        for (i, &coeff) in proof.final_poly.0.iter().enumerate() {
+            let unused = degree_flags
+                .iter()
+                .zip(params)
+                .filter(|(_, params)| params.final_poly_len() <= i)
+                .map(|(flag, _)| flag.target)
+                .collect::<Vec<_>>();
+            if !unused.is_empty() {
+                let unused = self.add_many(unused);
+                let zero = self.zero_extension();
+                self.conditional_connect_extension(unused, coeff, zero);
+            }
+        }
+
+        let precomputed_reduced_evals = with_context!(
+            self,
+            "precompute reduced evaluations",
+            PrecomputedReducedOpeningsTarget::from_os_and_alpha(
+                openings,
+                challenges.fri_alpha,
+                self
+            )
+        );
+
+        let num_queries = proof.query_round_proofs.len();
+        for (i, round_proof) in proof.query_round_proofs.iter().enumerate() {
+            let level = if i == 1 {
+                log::Level::Debug
+            } else {
+                log::Level::Trace
+            };
+            with_context!(
+                self,
+                level,
+                &format!("verify one (of {num_queries}) query rounds"),
+                self.fri_verifier_query_round_with_variable_degree::<C>(
+                    instance,
+                    challenges,
+                    &precomputed_reduced_evals,
+                    initial_merkle_caps,
+                    proof,
+                    challenges.fri_query_indices[i],
+                    round_proof,
+                    params,
+                    degree_flags,
+                )
+            );
+        }
+    }
+
+    /// Connects `x` and `y` if `condition`, which must be zero or one, is one.
+    fn conditional_connect_extension(
+        &mut self,
+        condition: Target,
+        x: ExtensionTarget<D>,
+        y: ExtensionTarget<D>,
+    ) {
+        let diff = self.sub_extension(x, y);
+        let diff = self.scalar_mul_ext(condition, diff);
+        for t in diff.0 {
+            self.assert_zero(t);
+        }
+    }
+
     fn fri_verify_initial_proof<H: AlgebraicHasher<F>>(
         &mut self,
         x_index_bits: &[BoolTarget],
@@ -360,6 +457,140 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.connect_extension(eval, old_eval);
     }
 
+    fn fri_verifier_query_round_with_variable_degree<C: GenericConfig<D, F = F>>(
+        &mut self,
+        instance: &FriInstanceInfoTarget<D>,
+        challenges: &FriChallengesTarget<D>,
+        precomputed_reduced_evals: &PrecomputedReducedOpeningsTarget<D>,
+        initial_merkle_caps: &[MerkleCapTarget],
+        proof: &FriProofTarget<D>,
+        x_index: Target,
+        round_proof: &FriQueryRoundTarget<D>,
+        params: &[FriParams],
+        degree_flags: &[BoolTarget],
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let max_params = params.last().unwrap();
+        let cap_height = max_params.config.cap_height;
+
+        // The index is reduced modulo the selected LDE size by ignoring its higher bits.
+        Self::assert_noncanonical_indices_ok(&max_params.config);
+        let mut x_index_bits = self.low_bits(x_index, max_params.lde_bits(), F::BITS);
+
+        let initial_heights = params
+            .iter()
+            .map(|params| params.lde_bits() - cap_height)
+            .collect::<Vec<_>>();
+        with_context!(self, "check FRI initial proof", {
+            for ((evals, merkle_proof), cap) in round_proof
+                .initial_trees_proof
+                .evals_proofs
+                .iter()
+                .zip(initial_merkle_caps)
+            {
+                self.verify_merkle_proof_to_cap_with_variable_height::<C::Hasher>(
+                    evals.clone(),
+                    &x_index_bits,
+                    &initial_heights,
+                    degree_flags,
+                    cap,
+                    merkle_proof,
+                );
+            }
+        });
+
+        let mut subgroup_x = with_context!(self, "compute x from its index", {
+            let g = self.constant(F::coset_shift());
+            let phis = params
+                .iter()
+                .map(|params| {
+                    let n_log = params.lde_bits();
+                    let phi = F::primitive_root_of_unity(n_log);
+                    self.exp_from_bits_const_base(phi, x_index_bits[..n_log].iter().rev())
+                })
+                .collect::<Vec<_>>();
+            let phi = self.select_one_hot(degree_flags, &phis);
+            self.mul(g, phi)
+        });
+
+        let mut old_eval = with_context!(
+            self,
+            "combine initial oracles",
+            self.fri_combine_initial(
+                instance,
+                &round_proof.initial_trees_proof,
+                challenges.fri_alpha,
+                subgroup_x,
+                precomputed_reduced_evals,
+                max_params,
+            )
+        );
+
+        // Reductions are applied while the selected degree has any left, as the reductions of
+        // smaller degrees are a prefix of those of larger ones.
+        let mut reduced_bits = 0;
+        for (i, &arity_bits) in max_params.reduction_arity_bits.iter().enumerate() {
+            reduced_bits += arity_bits;
+            let (heights, flags): (Vec<_>, Vec<_>) = params
+                .iter()
+                .zip(degree_flags)
+                .filter(|(params, _)| params.reduction_arity_bits.len() > i)
+                .map(|(params, &flag)| (params.lde_bits() - reduced_bits - cap_height, flag))
+                .unzip();
+            let active = self.add_many(flags.iter().map(|flag| flag.target));
+            let active = BoolTarget::new_unsafe(active);
+            let evals = &round_proof.steps[i].evals;
+
+            let coset_index_bits = x_index_bits[arity_bits..].to_vec();
+            let x_index_within_coset_bits = &x_index_bits[..arity_bits];
+            let x_index_within_coset = self.le_sum(x_index_within_coset_bits.iter());
+
+            let new_eval = self.random_access_extension(x_index_within_coset, evals.clone());
+            self.conditional_connect_extension(active.target, new_eval, old_eval);
+
+            let reduced_eval = with_context!(
+                self,
+                "infer evaluation using interpolation",
+                self.compute_evaluation(
+                    subgroup_x,
+                    x_index_within_coset_bits,
+                    arity_bits,
+                    evals,
+                    challenges.fri_betas[i],
+                )
+            );
+
+            with_context!(
+                self,
+                "verify FRI round Merkle proof.",
+                self.verify_merkle_proof_to_cap_with_variable_height::<C::Hasher>(
+                    flatten_target(evals),
+                    &coset_index_bits,
+                    &heights,
+                    &flags,
+                    &proof.commit_phase_merkle_caps[i],
+                    &round_proof.steps[i].merkle_proof,
+                )
+            );
+
+            let reduced_x = self.exp_power_of_2(subgroup_x, arity_bits);
+            old_eval = self.select_ext(active, reduced_eval, old_eval);
+            subgroup_x = self.select(active, reduced_x, subgroup_x);
+            x_index_bits = coset_index_bits;
+        }
+
+        let eval = with_context!(
+            self,
+            &format!(
+                "evaluate final polynomial of length {}",
+                proof.final_poly.len()
+            ),
+            proof.final_poly.eval_scalar(self, subgroup_x)
+        );
+        self.connect_extension(eval, old_eval);
+    }
+
     /// We decompose FRI query indices into bits without verifying that the decomposition given by
     /// the prover is the canonical one. In particular, if `x_index < 2^field_bits - p`, then the
     /// prover could supply the binary encoding of either `x_index` or `x_index + p`, since the are
@@ -384,6 +615,35 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         &mut self,
         num_leaves_per_oracle: &[usize],
         params: &FriParams,
+    ) -> FriProofTarget<D> {
+        self.add_virtual_fri_proof_with_final_poly_len(
+            num_leaves_per_oracle,
+            params,
+            params.final_poly_len(),
+        )
+    }
+
+    /// Adds a FRI proof target which can hold proofs for any of `params`, sorted by degree, as
+    /// verified by `verify_fri_proof_with_variable_degree`.
+    pub fn add_virtual_fri_proof_with_variable_degree(
+        &mut self,
+        num_leaves_per_oracle: &[usize],
+        params: &[FriParams],
+    ) -> FriProofTarget<D> {
+        let max_params = check_variable_degree_params(params);
+        let final_poly_len = params.iter().map(FriParams::final_poly_len).max().unwrap();
+        self.add_virtual_fri_proof_with_final_poly_len(
+            num_leaves_per_oracle,
+            max_params,
+            final_poly_len,
+        )
+    }
+
+    fn add_virtual_fri_proof_with_final_poly_len(
+        &mut self,
+        num_leaves_per_oracle: &[usize],
+        params: &FriParams,
+        final_poly_len: usize,
     ) -> FriProofTarget<D> {
         let cap_height = params.config.cap_height;
         let num_queries = params.config.num_query_rounds;
@@ -393,7 +653,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let query_round_proofs = (0..num_queries)
             .map(|_| self.add_virtual_fri_query(num_leaves_per_oracle, params))
             .collect();
-        let final_poly = self.add_virtual_poly_coeff_ext(params.final_poly_len());
+        let final_poly = self.add_virtual_poly_coeff_ext(final_poly_len);
         let pow_witness = self.add_virtual_target();
         FriProofTarget {
             commit_phase_merkle_caps,
@@ -456,6 +716,56 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 }
 
+/// The numbers of wires and routed wires needed by a circuit with the given config to verify FRI
+/// proofs with arities up to `2^max_fri_arity_bits`.
+pub(crate) fn min_fri_recursion_wires<F: RichField + Extendable<D>, const D: usize>(
+    config: &CircuitConfig,
+    max_fri_arity_bits: usize,
+) -> (usize, usize) {
+    let random_access = RandomAccessGate::<F, D>::new_from_config(
+        config,
+        max_fri_arity_bits.max(config.fri_config.cap_height),
+    );
+    let interpolation_gate = CosetInterpolationGate::<F, D>::with_max_degree(
+        max_fri_arity_bits,
+        config.max_quotient_degree_factor,
+    );
+
+    let min_wires = random_access
+        .num_wires()
+        .max(interpolation_gate.num_wires());
+    let min_routed_wires = random_access
+        .num_routed_wires()
+        .max(interpolation_gate.num_routed_wires());
+    (min_wires, min_routed_wires)
+}
+
+/// Checks that `params` can be verified by a single circuit, and returns those of the largest
+/// degree, which determine the shape of proof targets.
+fn check_variable_degree_params(params: &[FriParams]) -> &FriParams {
+    let max_params = params.last().expect("No FRI parameters given");
+    for (p0, p1) in params.iter().tuple_windows() {
+        assert!(
+            p0.degree_bits < p1.degree_bits,
+            "FRI parameters must be sorted by increasing degree"
+        );
+    }
+    for p in params {
+        assert_eq!(p.config, max_params.config, "FRI configs must match");
+        assert_eq!(
+            p.hiding, max_params.hiding,
+            "FRI hiding settings must match"
+        );
+        assert!(
+            max_params
+                .reduction_arity_bits
+                .starts_with(&p.reduction_arity_bits),
+            "The FRI reduction arities of each degree must be a prefix of those of the largest degree"
+        );
+    }
+    max_params
+}
+
 /// For each opening point, holds the reduced (by `alpha`) evaluations of each polynomial that's
 /// opened at that point.
 #[derive(Clone)]
@@ -0,0 +1,712 @@
+//! An experimental implementation of the STIR low-degree test (Arnon, Chiesa, Fenzi, Yogev),
+//! which can be used in place of FRI to prove batch openings of `PolynomialBatch` oracles.
+//!
+//! Like FRI, each STIR round folds the codeword by a fixed arity. Unlike FRI, the folded
+//! polynomial is evaluated over a domain only half the size of the previous one, so the rate
+//! improves from round to round and later rounds need fewer queries. Consistency between rounds
+//! is enforced with out-of-domain samples and a degree-corrected quotient, which the verifier
+//! evaluates "virtually" from the committed codeword.
+//!
+//! Only a native prover and verifier are provided; there is no recursive verifier yet.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use hashbrown::HashSet;
+use itertools::Itertools;
+use plonky2_maybe_rayon::*;
+use serde::{Deserialize, Serialize};
+
+use crate::field::extension::{flatten, unflatten, Extendable};
+use crate::field::interpolation::{barycentric_weights, interpolant, interpolate};
+use crate::field::polynomial::PolynomialCoeffs;
+use crate::field::types::Field;
+use crate::fri::proof::{FriInitialTreeProof, FriQueryStep};
+use crate::fri::structure::{FriInstanceInfo, FriOpenings};
+use crate::fri::verifier::{compute_evaluation, fri_combine_initial, PrecomputedReducedOpenings};
+use crate::hash::hash_types::RichField;
+use crate::hash::merkle_proofs::verify_merkle_proof_to_cap;
+use crate::hash::merkle_tree::{MerkleCap, MerkleTree};
+use crate::iop::challenger::Challenger;
+use crate::plonk::config::{GenericConfig, Hasher};
+use crate::plonk::plonk_common::{reduce_with_powers, salt_size};
+use crate::timed;
+use crate::util::timing::TimingTree;
+use crate::util::{ceil_div_usize, reverse_bits, reverse_index_bits_in_place};
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct StirConfig {
+    /// `rate = 2^{-rate_bits}` of the initial codeword.
+    pub rate_bits: usize,
+
+    /// Height of Merkle tree caps.
+    pub cap_height: usize,
+
+    /// The log2 of the folding arity used in every round.
+    pub folding_arity_bits: usize,
+
+    /// Folding stops once at most `final_poly_bits` bits of degree would remain.
+    pub final_poly_bits: usize,
+
+    /// Conjectured security level, used to derive the number of queries of each round.
+    pub security_bits: usize,
+
+    /// Number of out-of-domain samples per round.
+    pub num_ood_samples: usize,
+}
+
+impl StirConfig {
+    pub fn stir_params(&self, degree_bits: usize, hiding: bool) -> StirParams {
+        assert!(self.rate_bits > 0, "STIR requires a rate below 1");
+        assert!(
+            self.folding_arity_bits > 0,
+            "STIR requires a folding arity above 1"
+        );
+        assert!(
+            degree_bits >= self.final_poly_bits + self.folding_arity_bits,
+            "Degree too small to perform any STIR folding"
+        );
+
+        let num_folds = (degree_bits - self.final_poly_bits) / self.folding_arity_bits;
+        // The degree shrinks by the folding arity each round while the domain only halves, so the
+        // rate of round `i` is `2^{-(rate_bits + i * (folding_arity_bits - 1))}`.
+        let num_queries = (0..num_folds)
+            .map(|i| {
+                let round_rate_bits = self.rate_bits + i * (self.folding_arity_bits - 1);
+                ceil_div_usize(self.security_bits, round_rate_bits)
+            })
+            .collect();
+
+        let params = StirParams {
+            config: self.clone(),
+            hiding,
+            degree_bits,
+            num_queries,
+        };
+        for round in 0..num_folds {
+            assert!(
+                params.domain_bits(round) - self.folding_arity_bits >= self.cap_height,
+                "Cap height too large for STIR round {round}"
+            );
+        }
+        for round in 0..params.num_rounds() {
+            assert!(
+                self.num_ood_samples + params.num_queries[round]
+                    < 1 << params.degree_bits(round + 1),
+                "Too many STIR queries in round {round} for the remaining degree"
+            );
+        }
+        params
+    }
+}
+
+/// STIR parameters, including generated parameters which are specific to an instance size.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct StirParams {
+    /// User-specified STIR configuration.
+    pub config: StirConfig,
+
+    /// Whether the initial oracles use salted Merkle leaves.
+    pub hiding: bool,
+
+    /// The degree of the purported codeword, measured in bits.
+    pub degree_bits: usize,
+
+    /// The number of queries of each folding. The last entry is for the final folding, which is
+    /// checked directly against the final polynomial.
+    pub num_queries: Vec<usize>,
+}
+
+impl StirParams {
+    /// The number of foldings, including the final one.
+    pub fn num_folds(&self) -> usize {
+        self.num_queries.len()
+    }
+
+    /// The number of rounds which commit to a new codeword.
+    pub fn num_rounds(&self) -> usize {
+        self.num_folds() - 1
+    }
+
+    /// The degree, in bits, of the polynomial folded in round `round`.
+    pub fn degree_bits(&self, round: usize) -> usize {
+        self.degree_bits - round * self.config.folding_arity_bits
+    }
+
+    /// The size, in bits, of the evaluation domain of round `round`.
+    pub fn domain_bits(&self, round: usize) -> usize {
+        self.degree_bits + self.config.rate_bits - round
+    }
+
+    pub fn final_poly_len(&self) -> usize {
+        1 << self.degree_bits(self.num_folds())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(bound = "")]
+pub struct StirProof<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize> {
+    /// A Merkle cap for the codeword committed in each round, starting with the initial one.
+    pub commit_phase_merkle_caps: Vec<MerkleCap<F, H>>,
+    /// The answers to the out-of-domain samples of each round.
+    pub ood_answers: Vec<Vec<F::Extension>>,
+    /// Openings of the initial oracles at each query of the first round.
+    pub initial_trees_proofs: Vec<FriInitialTreeProof<F, H>>,
+    /// For each round, the opened cosets of that round's codeword.
+    pub query_round_proofs: Vec<Vec<FriQueryStep<F, H, D>>>,
+    /// The final polynomial in coefficient form.
+    pub final_poly: PolynomialCoeffs<F::Extension>,
+}
+
+/// The point of the evaluation domain of size `2^domain_bits` at index `index` of the
+/// bit-reversed codeword.
+fn domain_point<F: Field>(domain_bits: usize, index: usize) -> F {
+    F::coset_shift()
+        * F::primitive_root_of_unity(domain_bits).exp_u64(reverse_bits(index, domain_bits) as u64)
+}
+
+fn query_indices<F: RichField, H: Hasher<F>>(
+    challenger: &mut Challenger<F, H>,
+    num_queries: usize,
+    domain_bits: usize,
+) -> Vec<usize> {
+    challenger
+        .get_n_challenges(num_queries)
+        .into_iter()
+        .map(|c| c.to_canonical_u64() as usize % (1 << domain_bits))
+        .collect()
+}
+
+/// The folded evaluation points of the given queries, without duplicates.
+fn shift_points<F: Field>(indices: &[usize], domain_bits: usize, arity_bits: usize) -> Vec<F> {
+    let mut seen = HashSet::new();
+    indices
+        .iter()
+        .map(|&i| i >> arity_bits)
+        .filter(|&coset| seen.insert(coset))
+        .map(|coset| domain_point::<F>(domain_bits, coset << arity_bits).exp_power_of_2(arity_bits))
+        .collect()
+}
+
+fn fold<F: Field>(coeffs: &PolynomialCoeffs<F>, arity_bits: usize, beta: F) -> PolynomialCoeffs<F> {
+    PolynomialCoeffs::new(
+        coeffs
+            .coeffs
+            .par_chunks_exact(1 << arity_bits)
+            .map(|chunk| reduce_with_powers(chunk, beta))
+            .collect(),
+    )
+}
+
+/// Commits to the evaluations of `coeffs` over the domain of size `2^domain_bits`, grouping the
+/// points of each coset of the folding into one leaf.
+fn commit_codeword<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    coeffs: &PolynomialCoeffs<F::Extension>,
+    domain_bits: usize,
+    params: &StirParams,
+) -> MerkleTree<F, C::Hasher> {
+    let mut values = coeffs
+        .padded(1 << domain_bits)
+        .coset_fft(F::coset_shift().into())
+        .values;
+    reverse_index_bits_in_place(&mut values);
+    let leaves = values
+        .par_chunks(1 << params.config.folding_arity_bits)
+        .map(flatten)
+        .collect();
+    MerkleTree::new(leaves, params.config.cap_height)
+}
+
+/// Computes `Quotient(poly, points, values)` followed by the degree correction with randomness
+/// `comb`, which brings the result back to a polynomial with `len` coefficients.
+fn quotient_with_degree_correction<F: Field>(
+    poly: &PolynomialCoeffs<F>,
+    points: &[F],
+    values: &[F],
+    comb: F,
+    len: usize,
+) -> PolynomialCoeffs<F> {
+    let answers = interpolant(
+        &points
+            .iter()
+            .copied()
+            .zip_eq(values.iter().copied())
+            .collect_vec(),
+    );
+    let mut vanishing = vec![F::ONE];
+    for &z in points {
+        // Multiply by `X - z`.
+        vanishing.insert(0, F::ZERO);
+        for i in 0..vanishing.len() - 1 {
+            let next = vanishing[i + 1];
+            vanishing[i] -= z * next;
+        }
+    }
+    let (quotient, remainder) = (poly - &answers).div_rem(&PolynomialCoeffs::new(vanishing));
+    debug_assert!(remainder.is_zero());
+    let correction = PolynomialCoeffs::new(comb.powers().take(points.len() + 1).collect());
+    let mut corrected = &quotient * &correction;
+    debug_assert!(corrected.coeffs.iter().skip(len).all(|c| c.is_zero()));
+    corrected.coeffs.resize(len, F::ZERO);
+    corrected
+}
+
+/// Builds a STIR proof that `polynomial` has degree less than `2^params.degree_bits`.
+pub fn stir_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    initial_merkle_trees: &[&MerkleTree<F, C::Hasher>],
+    polynomial: PolynomialCoeffs<F::Extension>,
+    challenger: &mut Challenger<F, C::Hasher>,
+    params: &StirParams,
+    timing: &mut TimingTree,
+) -> StirProof<F, C::Hasher, D> {
+    assert_eq!(polynomial.len(), 1 << params.degree_bits);
+    let arity_bits = params.config.folding_arity_bits;
+
+    let mut coeffs = polynomial;
+    let mut tree = timed!(
+        timing,
+        "commit to initial codeword",
+        commit_codeword::<F, C, D>(&coeffs, params.domain_bits(0), params)
+    );
+    challenger.observe_cap(&tree.cap);
+
+    let mut commit_phase_merkle_caps = vec![tree.cap.clone()];
+    let mut ood_answers = Vec::with_capacity(params.num_rounds());
+    let mut initial_trees_proofs = Vec::new();
+    let mut query_round_proofs = Vec::with_capacity(params.num_folds());
+
+    for round in 0..params.num_folds() {
+        let domain_bits = params.domain_bits(round);
+        let beta = challenger.get_extension_challenge::<D>();
+        let folded = fold(&coeffs, arity_bits, beta);
+
+        let next_tree = if round < params.num_rounds() {
+            let next_tree = timed!(
+                timing,
+                "commit to folded codeword",
+                commit_codeword::<F, C, D>(&folded, domain_bits - 1, params)
+            );
+            challenger.observe_cap(&next_tree.cap);
+            Some(next_tree)
+        } else {
+            challenger.observe_extension_elements(&folded.coeffs);
+            None
+        };
+
+        let ood_points = if next_tree.is_some() {
+            let points = challenger.get_n_extension_challenges::<D>(params.config.num_ood_samples);
+            let answers = points.iter().map(|&z| folded.eval(z)).collect_vec();
+            challenger.observe_extension_elements(&answers);
+            ood_answers.push(answers);
+            points
+        } else {
+            Vec::new()
+        };
+
+        let indices = query_indices(challenger, params.num_queries[round], domain_bits);
+        if round == 0 {
+            initial_trees_proofs = indices
+                .iter()
+                .map(|&i| FriInitialTreeProof {
+                    evals_proofs: initial_merkle_trees
+                        .iter()
+                        .map(|t| (t.get(i).to_vec(), t.prove(i)))
+                        .collect(),
+                })
+                .collect();
+        }
+        query_round_proofs.push(
+            indices
+                .iter()
+                .map(|&i| FriQueryStep {
+                    evals: unflatten(tree.get(i >> arity_bits)),
+                    merkle_proof: tree.prove(i >> arity_bits),
+                })
+                .collect(),
+        );
+
+        let Some(next_tree) = next_tree else {
+            // The coefficients being removed here should always be zero.
+            coeffs = folded;
+            coeffs.coeffs.truncate(params.final_poly_len());
+            break;
+        };
+
+        let comb = challenger.get_extension_challenge::<D>();
+        let mut points = ood_points;
+        points.extend(
+            shift_points::<F>(&indices, domain_bits, arity_bits)
+                .into_iter()
+                .map(F::Extension::from),
+        );
+        let values = points.iter().map(|&z| folded.eval(z)).collect_vec();
+        coeffs = timed!(
+            timing,
+            "compute quotient",
+            quotient_with_degree_correction(
+                &folded,
+                &points,
+                &values,
+                comb,
+                1 << params.degree_bits(round + 1),
+            )
+        );
+        commit_phase_merkle_caps.push(next_tree.cap.clone());
+        tree = next_tree;
+    }
+
+    StirProof {
+        commit_phase_merkle_caps,
+        ood_answers,
+        initial_trees_proofs,
+        query_round_proofs,
+        final_poly: coeffs,
+    }
+}
+
+fn validate_stir_proof_shape<F, C, const D: usize>(
+    proof: &StirProof<F, C::Hasher, D>,
+    instance: &FriInstanceInfo<F, D>,
+    params: &StirParams,
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let StirProof {
+        commit_phase_merkle_caps,
+        ood_answers,
+        initial_trees_proofs,
+        query_round_proofs,
+        final_poly,
+    } = proof;
+    let cap_height = params.config.cap_height;
+    let arity_bits = params.config.folding_arity_bits;
+
+    ensure!(commit_phase_merkle_caps.len() == params.num_folds());
+    for cap in commit_phase_merkle_caps {
+        ensure!(cap.height() == cap_height);
+    }
+
+    ensure!(ood_answers.len() == params.num_rounds());
+    for answers in ood_answers {
+        ensure!(answers.len() == params.config.num_ood_samples);
+    }
+
+    ensure!(initial_trees_proofs.len() == params.num_queries[0]);
+    for initial_trees_proof in initial_trees_proofs {
+        ensure!(initial_trees_proof.evals_proofs.len() == instance.oracles.len());
+        for ((leaf, merkle_proof), oracle) in initial_trees_proof
+            .evals_proofs
+            .iter()
+            .zip(&instance.oracles)
+        {
+            ensure!(leaf.len() == oracle.num_polys + salt_size(oracle.blinding && params.hiding));
+            ensure!(merkle_proof.len() + cap_height == params.domain_bits(0));
+        }
+    }
+
+    ensure!(query_round_proofs.len() == params.num_folds());
+    for (round, steps) in query_round_proofs.iter().enumerate() {
+        ensure!(steps.len() == params.num_queries[round]);
+        for step in steps {
+            ensure!(step.evals.len() == 1 << arity_bits);
+            ensure!(step.merkle_proof.len() + cap_height == params.domain_bits(round) - arity_bits);
+        }
+    }
+
+    ensure!(final_poly.len() == params.final_poly_len());
+
+    Ok(())
+}
+
+/// The data needed by the verifier to evaluate the degree-corrected quotient of a round from the
+/// committed codeword.
+struct VirtualQuotient<F: Field> {
+    points: Vec<(F, F)>,
+    barycentric_weights: Vec<F>,
+    comb: F,
+}
+
+impl<F: Field> VirtualQuotient<F> {
+    fn new(points: Vec<(F, F)>, comb: F) -> Self {
+        let barycentric_weights = barycentric_weights(&points);
+        Self {
+            points,
+            barycentric_weights,
+            comb,
+        }
+    }
+
+    /// Evaluates the quotient at `x`, given the committed codeword's value `value` at `x`.
+    fn eval(&self, x: F, value: F) -> F {
+        let answer = interpolate(&self.points, x, &self.barycentric_weights);
+        let vanishing = self.points.iter().map(|&(z, _)| x - z).product::<F>();
+        let correction = (self.comb * x)
+            .powers()
+            .take(self.points.len() + 1)
+            .sum::<F>();
+        (value - answer) / vanishing * correction
+    }
+}
+
+/// Verifies a STIR batch opening proof. `challenger` must be in the same state as the prover's
+/// when it called `PolynomialBatch::prove_openings_stir`.
+pub fn verify_stir_proof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    instance: &FriInstanceInfo<F, D>,
+    openings: &FriOpenings<F, D>,
+    initial_merkle_caps: &[MerkleCap<F, C::Hasher>],
+    proof: &StirProof<F, C::Hasher, D>,
+    challenger: &mut Challenger<F, C::Hasher>,
+    params: &StirParams,
+) -> Result<()> {
+    validate_stir_proof_shape::<F, C, D>(proof, instance, params)?;
+    let arity_bits = params.config.folding_arity_bits;
+
+    let alpha = challenger.get_extension_challenge::<D>();
+    let precomputed_reduced_evals = PrecomputedReducedOpenings::from_os_and_alpha(openings, alpha);
+    challenger.observe_cap(&proof.commit_phase_merkle_caps[0]);
+
+    // The quotient through which the codeword committed in the current round is read, if any.
+    let mut quotient: Option<VirtualQuotient<F::Extension>> = None;
+
+    for round in 0..params.num_folds() {
+        let domain_bits = params.domain_bits(round);
+        let beta = challenger.get_extension_challenge::<D>();
+
+        let is_final = round == params.num_rounds();
+        let ood_points = if is_final {
+            challenger.observe_extension_elements(&proof.final_poly.coeffs);
+            Vec::new()
+        } else {
+            challenger.observe_cap(&proof.commit_phase_merkle_caps[round + 1]);
+            let points = challenger.get_n_extension_challenges::<D>(params.config.num_ood_samples);
+            challenger.observe_extension_elements(&proof.ood_answers[round]);
+            points
+        };
+
+        let indices = query_indices(challenger, params.num_queries[round], domain_bits);
+        let mut folded_evals = Vec::with_capacity(indices.len());
+        for (j, &x_index) in indices.iter().enumerate() {
+            let step = &proof.query_round_proofs[round][j];
+            let coset_index = x_index >> arity_bits;
+            let x_index_within_coset = x_index & ((1 << arity_bits) - 1);
+            verify_merkle_proof_to_cap::<F, C::Hasher>(
+                flatten(&step.evals),
+                coset_index,
+                &proof.commit_phase_merkle_caps[round],
+                &step.merkle_proof,
+            )?;
+
+            let evals = match &quotient {
+                None => step.evals.clone(),
+                Some(quotient) => step
+                    .evals
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &value)| {
+                        let x = domain_point::<F>(domain_bits, (coset_index << arity_bits) + k);
+                        quotient.eval(x.into(), value)
+                    })
+                    .collect(),
+            };
+
+            let x = domain_point::<F>(domain_bits, x_index);
+            if round == 0 {
+                let initial_trees_proof = &proof.initial_trees_proofs[j];
+                for ((leaf, merkle_proof), cap) in initial_trees_proof
+                    .evals_proofs
+                    .iter()
+                    .zip(initial_merkle_caps)
+                {
+                    verify_merkle_proof_to_cap::<F, C::Hasher>(
+                        leaf.clone(),
+                        x_index,
+                        cap,
+                        merkle_proof,
+                    )?;
+                }
+                ensure!(
+                    evals[x_index_within_coset]
+                        == fri_combine_initial::<F, C, D>(
+                            instance,
+                            initial_trees_proof,
+                            alpha,
+                            x,
+                            &precomputed_reduced_evals,
+                            params.hiding,
+                        ),
+                    "Initial codeword is inconsistent with the initial oracles."
+                );
+            }
+
+            folded_evals.push(compute_evaluation(
+                x,
+                x_index_within_coset,
+                arity_bits,
+                &evals,
+                beta,
+            ));
+        }
+
+        if is_final {
+            for (&x_index, &folded_eval) in indices.iter().zip(&folded_evals) {
+                let y = domain_point::<F>(domain_bits, x_index).exp_power_of_2(arity_bits);
+                ensure!(
+                    proof.final_poly.eval(y.into()) == folded_eval,
+                    "Final polynomial evaluation is invalid."
+                );
+            }
+            break;
+        }
+
+        let comb = challenger.get_extension_challenge::<D>();
+        let mut seen = HashSet::new();
+        let mut points = ood_points
+            .into_iter()
+            .zip(proof.ood_answers[round].iter().copied())
+            .collect_vec();
+        points.extend(
+            indices
+                .iter()
+                .zip(folded_evals)
+                .filter(|&(&x_index, _)| seen.insert(x_index >> arity_bits))
+                .map(|(&x_index, folded_eval)| {
+                    let y = domain_point::<F>(domain_bits, x_index).exp_power_of_2(arity_bits);
+                    (y.into(), folded_eval)
+                }),
+        );
+        quotient = Some(VirtualQuotient::new(points, comb));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::fri::oracle::PolynomialBatch;
+    use crate::fri::structure::{FriBatchInfo, FriOpeningBatch, FriOracleInfo, FriPolynomialInfo};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type FE = <F as Extendable<D>>::Extension;
+
+    fn test_config() -> StirConfig {
+        StirConfig {
+            rate_bits: 2,
+            cap_height: 2,
+            folding_arity_bits: 2,
+            final_poly_bits: 2,
+            security_bits: 40,
+            num_ood_samples: 1,
+        }
+    }
+
+    /// Commits to random polynomials and opens them all at a random point.
+    fn random_batch(
+        params: &StirParams,
+    ) -> (
+        PolynomialBatch<F, C, D>,
+        FriInstanceInfo<F, D>,
+        FriOpenings<F, D>,
+    ) {
+        let num_polys = 5;
+        let degree = 1 << params.degree_bits;
+        let polys = (0..num_polys)
+            .map(|_| PolynomialCoeffs::new(F::rand_vec(degree)))
+            .collect_vec();
+        let batch = PolynomialBatch::<F, C, D>::from_coeffs(
+            polys,
+            params.config.rate_bits,
+            params.hiding,
+            params.config.cap_height,
+            &mut TimingTree::default(),
+            None,
+        );
+
+        let zeta = FE::rand();
+        let instance = FriInstanceInfo {
+            oracles: vec![FriOracleInfo {
+                num_polys,
+                blinding: params.hiding,
+            }],
+            batches: vec![FriBatchInfo {
+                point: zeta,
+                polynomials: FriPolynomialInfo::from_range(0, 0..num_polys),
+            }],
+        };
+        let openings = FriOpenings {
+            batches: vec![FriOpeningBatch {
+                values: batch
+                    .polynomials
+                    .iter()
+                    .map(|p| p.to_extension::<D>().eval(zeta))
+                    .collect(),
+            }],
+        };
+        (batch, instance, openings)
+    }
+
+    fn prove_and_verify(
+        params: &StirParams,
+        tamper: impl FnOnce(&mut FriOpenings<F, D>),
+    ) -> Result<()> {
+        let (batch, instance, mut openings) = random_batch(params);
+        let proof = PolynomialBatch::prove_openings_stir(
+            &instance,
+            &[&batch],
+            &mut Challenger::new(),
+            params,
+            &mut TimingTree::default(),
+        );
+        tamper(&mut openings);
+
+        let initial_merkle_caps = [batch.merkle_tree.cap];
+        verify_stir_proof::<F, C, D>(
+            &instance,
+            &openings,
+            &initial_merkle_caps,
+            &proof,
+            &mut Challenger::new(),
+            params,
+        )
+    }
+
+    #[test]
+    fn test_stir_batch_opening() -> Result<()> {
+        for hiding in [false, true] {
+            let params = test_config().stir_params(10, hiding);
+            prove_and_verify(&params, |_| ())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stir_rejects_wrong_opening() {
+        let params = test_config().stir_params(10, false);
+        let result = prove_and_verify(&params, |openings| {
+            openings.batches[0].values[0] += FE::ONE;
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_stir_num_queries_decrease() {
+        let params = test_config().stir_params(16, false);
+        assert_eq!(params.num_folds(), 7);
+        assert!(params.num_queries.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(params.num_queries[0], 20);
+        assert_eq!(params.final_poly_len(), 4);
+    }
+}
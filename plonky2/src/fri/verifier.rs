@@ -14,7 +14,7 @@ use crate::hash::merkle_proofs::verify_merkle_proof_to_cap;
 use crate::hash::merkle_tree::MerkleCap;
 use crate::plonk::config::{GenericConfig, Hasher};
 use crate::util::reducing::ReducingFactor;
-use crate::util::{log2_strict, reverse_bits, reverse_index_bits_in_place};
+use crate::util::{domain_cache, log2_strict, reverse_bits};
 
 /// Computes P'(x^arity) from {P(x*g^i)}_(i=0..arity), where g is a `arity`-th root of unity
 /// and P' is the FRI reduced polynomial.
@@ -29,17 +29,17 @@ pub(crate) fn compute_evaluation<F: Field + Extendable<D>, const D: usize>(
     debug_assert_eq!(evals.len(), arity);
 
     let g = F::primitive_root_of_unity(arity_bits);
+    let subgroup = domain_cache::two_adic_subgroup::<F>(arity_bits);
+    // The evaluations are given in bit-reversed order.
+    let permutation = domain_cache::bit_reversal_permutation(arity_bits);
 
-    // The evaluation vector needs to be reordered first.
-    let mut evals = evals.to_vec();
-    reverse_index_bits_in_place(&mut evals);
     let rev_x_index_within_coset = reverse_bits(x_index_within_coset, arity_bits);
     let coset_start = x * g.exp_u64((arity - rev_x_index_within_coset) as u64);
     // The answer is gotten by interpolating {(x*g^i, P(x*g^i))} and evaluating at beta.
-    let points = g
-        .powers()
-        .map(|y| (coset_start * y).into())
-        .zip(evals)
+    let points = subgroup
+        .iter()
+        .zip(permutation.iter())
+        .map(|(&y, &i)| ((coset_start * y).into(), evals[i]))
         .collect::<Vec<_>>();
     let barycentric_weights = barycentric_weights(&points);
     interpolate(&points, beta, &barycentric_weights)
@@ -129,7 +129,7 @@ pub(crate) fn fri_combine_initial<
     alpha: F::Extension,
     subgroup_x: F,
     precomputed_reduced_evals: &PrecomputedReducedOpenings<F, D>,
-    params: &FriParams,
+    hiding: bool,
 ) -> F::Extension {
     assert!(D > 1, "Not implemented for D=1.");
     let subgroup_x = F::Extension::from_basefield(subgroup_x);
@@ -146,7 +146,7 @@ pub(crate) fn fri_combine_initial<
             .iter()
             .map(|p| {
                 let poly_blinding = instance.oracles[p.oracle_index].blinding;
-                let salted = params.hiding && poly_blinding;
+                let salted = hiding && poly_blinding;
                 proof.unsalted_eval(p.oracle_index, p.polynomial_index, salted)
             })
             .map(F::Extension::from_basefield);
@@ -193,7 +193,7 @@ fn fri_verifier_query_round<
         challenges.fri_alpha,
         subgroup_x,
         precomputed_reduced_evals,
-        params,
+        params.hiding,
     );
 
     for (i, &arity_bits) in params.reduction_arity_bits.iter().enumerate() {
@@ -1,8 +1,9 @@
 use itertools::Itertools;
 
 use crate::field::extension::Extendable;
+use crate::field::types::Field;
 use crate::fri::proof::{FriProof, FriProofTarget};
-use crate::hash::hash_types::RichField;
+use crate::hash::hash_types::{HashOut, RichField};
 use crate::iop::witness::WitnessWrite;
 use crate::plonk::config::AlgebraicHasher;
 
@@ -69,3 +70,92 @@ pub fn set_fri_proof_target<F, W, H, const D: usize>(
         }
     }
 }
+
+/// Like `set_fri_proof_target`, for a target created with
+/// `CircuitBuilder::add_virtual_fri_proof_with_variable_degree`. Targets beyond the shape of
+/// `fri_proof` are set to zero.
+pub fn set_fri_proof_target_with_variable_degree<F, W, H, const D: usize>(
+    witness: &mut W,
+    fri_proof_target: &FriProofTarget<D>,
+    fri_proof: &FriProof<F, H, D>,
+) where
+    F: RichField + Extendable<D>,
+    W: WitnessWrite<F> + ?Sized,
+    H: AlgebraicHasher<F>,
+{
+    fn padded<T: Copy>(values: &[T], len: usize, zero: T) -> impl Iterator<Item = T> + '_ {
+        assert!(values.len() <= len, "Proof is larger than its target");
+        values
+            .iter()
+            .copied()
+            .chain(core::iter::repeat(zero))
+            .take(len)
+    }
+
+    witness.set_target(fri_proof_target.pow_witness, fri_proof.pow_witness);
+
+    let final_poly = &fri_proof_target.final_poly.0;
+    for (&t, x) in final_poly.iter().zip(padded(
+        &fri_proof.final_poly.coeffs,
+        final_poly.len(),
+        F::Extension::ZERO,
+    )) {
+        witness.set_extension_target(t, x);
+    }
+
+    let num_caps = fri_proof_target.commit_phase_merkle_caps.len();
+    assert!(fri_proof.commit_phase_merkle_caps.len() <= num_caps);
+    for (i, t) in fri_proof_target.commit_phase_merkle_caps.iter().enumerate() {
+        let cap = fri_proof
+            .commit_phase_merkle_caps
+            .get(i)
+            .map(|cap| &cap.0[..]);
+        for (&ht, h) in
+            t.0.iter()
+                .zip(padded(cap.unwrap_or_default(), t.0.len(), HashOut::ZERO))
+        {
+            witness.set_hash_target(ht, h);
+        }
+    }
+
+    for (qt, q) in fri_proof_target
+        .query_round_proofs
+        .iter()
+        .zip_eq(&fri_proof.query_round_proofs)
+    {
+        for (at, a) in qt
+            .initial_trees_proof
+            .evals_proofs
+            .iter()
+            .zip_eq(&q.initial_trees_proof.evals_proofs)
+        {
+            for (&t, &x) in at.0.iter().zip_eq(&a.0) {
+                witness.set_target(t, x);
+            }
+            let siblings = padded(&a.1.siblings, at.1.siblings.len(), HashOut::ZERO);
+            for (&t, x) in at.1.siblings.iter().zip(siblings) {
+                witness.set_hash_target(t, x);
+            }
+        }
+
+        assert!(q.steps.len() <= qt.steps.len());
+        for (i, st) in qt.steps.iter().enumerate() {
+            let step = q.steps.get(i);
+            let evals = step.map(|s| &s.evals[..]).unwrap_or_default();
+            for (&t, x) in st
+                .evals
+                .iter()
+                .zip(padded(evals, st.evals.len(), F::Extension::ZERO))
+            {
+                witness.set_extension_target(t, x);
+            }
+            let siblings = step
+                .map(|s| &s.merkle_proof.siblings[..])
+                .unwrap_or_default();
+            let siblings = padded(siblings, st.merkle_proof.siblings.len(), HashOut::ZERO);
+            for (&t, x) in st.merkle_proof.siblings.iter().zip(siblings) {
+                witness.set_hash_target(t, x);
+            }
+        }
+    }
+}
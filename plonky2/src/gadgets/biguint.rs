@@ -0,0 +1,369 @@
+//! Gadgets for arbitrary-precision unsigned integers, represented as little-endian 32-bit limbs.
+//!
+//! Like [`UintTarget`](crate::gadgets::uint::UintTarget)s, every limb is range-checked to 32 bits,
+//! and the limb arithmetic is exact, so the constraints hold over the integers rather than modulo
+//! the field order.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::max;
+
+use num::{BigUint, Integer, Zero};
+
+use crate::field::extension::Extendable;
+use crate::field::types::{Field, PrimeField64};
+use crate::hash::hash_types::RichField;
+use crate::iop::generator::{GeneratedValues, SimpleGenerator};
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CommonCircuitData;
+use crate::util::serialization::{Buffer, IoResult, Read, Write};
+
+/// An unsigned integer of any number of 32-bit limbs, in little-endian order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BigUintTarget {
+    /// The limbs of the integer, each range-checked to 32 bits.
+    pub(crate) limbs: Vec<Target>,
+}
+
+impl BigUintTarget {
+    pub fn num_limbs(&self) -> usize {
+        self.limbs.len()
+    }
+
+    /// Returns the little-endian 32-bit limbs of the integer.
+    pub fn limbs(&self) -> &[Target] {
+        &self.limbs
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds a new integer of `num_limbs` limbs, whose limbs are range-checked.
+    pub fn add_virtual_biguint_target(&mut self, num_limbs: usize) -> BigUintTarget {
+        let limbs = self.add_virtual_targets(num_limbs);
+        self.biguint_from_limbs(limbs)
+    }
+
+    /// Interprets little-endian 32-bit limbs as an integer, range-checking each limb.
+    pub fn biguint_from_limbs(&mut self, limbs: Vec<Target>) -> BigUintTarget {
+        for &limb in &limbs {
+            self.range_check(limb, 32);
+        }
+        BigUintTarget { limbs }
+    }
+
+    pub fn constant_biguint(&mut self, value: &BigUint) -> BigUintTarget {
+        let mut digits = value.to_u32_digits();
+        if digits.is_empty() {
+            digits.push(0);
+        }
+        let limbs = digits
+            .into_iter()
+            .map(|limb| self.constant(F::from_canonical_u32(limb)))
+            .collect();
+        BigUintTarget { limbs }
+    }
+
+    pub fn zero_biguint(&mut self) -> BigUintTarget {
+        self.constant_biguint(&BigUint::zero())
+    }
+
+    /// Returns `x` with zero limbs appended up to `num_limbs` limbs.
+    pub fn pad_biguint(&mut self, x: &BigUintTarget, num_limbs: usize) -> BigUintTarget {
+        let zero = self.zero();
+        let mut limbs = x.limbs.clone();
+        if limbs.len() < num_limbs {
+            limbs.resize(num_limbs, zero);
+        }
+        BigUintTarget { limbs }
+    }
+
+    /// Asserts that `x == y` as integers, which may have different numbers of limbs.
+    pub fn connect_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) {
+        let num_limbs = max(x.num_limbs(), y.num_limbs());
+        let x = self.pad_biguint(x, num_limbs);
+        let y = self.pad_biguint(y, num_limbs);
+        for (&x, &y) in x.limbs.iter().zip(&y.limbs) {
+            self.connect(x, y);
+        }
+    }
+
+    pub fn is_equal_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BoolTarget {
+        let num_limbs = max(x.num_limbs(), y.num_limbs());
+        let x = self.pad_biguint(x, num_limbs);
+        let y = self.pad_biguint(y, num_limbs);
+        let mut equal = self._true();
+        for (&x, &y) in x.limbs.iter().zip(&y.limbs) {
+            let limb_equal = self.is_equal(x, y);
+            equal = self.and(equal, limb_equal);
+        }
+        equal
+    }
+
+    /// Returns `x` if `b` is true, otherwise `y`.
+    pub fn select_biguint(
+        &mut self,
+        b: BoolTarget,
+        x: &BigUintTarget,
+        y: &BigUintTarget,
+    ) -> BigUintTarget {
+        let num_limbs = max(x.num_limbs(), y.num_limbs());
+        let x = self.pad_biguint(x, num_limbs);
+        let y = self.pad_biguint(y, num_limbs);
+        let limbs = x
+            .limbs
+            .iter()
+            .zip(&y.limbs)
+            .map(|(&x, &y)| self.select(b, x, y))
+            .collect();
+        BigUintTarget { limbs }
+    }
+
+    /// Returns `x < y`.
+    pub fn is_less_than_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BoolTarget {
+        let num_limbs = max(x.num_limbs(), y.num_limbs());
+        let x = self.pad_biguint(x, num_limbs);
+        let y = self.pad_biguint(y, num_limbs);
+        self.sub_limbs(&x.limbs, &y.limbs).1
+    }
+
+    /// Returns `x <= y`.
+    pub fn is_less_or_equal_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BoolTarget {
+        let greater = self.is_less_than_biguint(y, x);
+        self.not(greater)
+    }
+
+    /// Returns `x + y`, with one more limb than the longest input.
+    pub fn add_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BigUintTarget {
+        let num_limbs = max(x.num_limbs(), y.num_limbs());
+        let x = self.pad_biguint(x, num_limbs);
+        let y = self.pad_biguint(y, num_limbs);
+        let (mut limbs, carry) = self.add_limbs(&x.limbs, &y.limbs);
+        limbs.push(carry.target);
+        BigUintTarget { limbs }
+    }
+
+    /// Returns `x - y`, asserting that `x >= y`.
+    pub fn sub_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BigUintTarget {
+        let num_limbs = max(x.num_limbs(), y.num_limbs());
+        let x = self.pad_biguint(x, num_limbs);
+        let y = self.pad_biguint(y, num_limbs);
+        let (limbs, borrow) = self.sub_limbs(&x.limbs, &y.limbs);
+        self.assert_zero(borrow.target);
+        BigUintTarget { limbs }
+    }
+
+    /// Returns `x * y`, with as many limbs as both inputs together.
+    pub fn mul_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BigUintTarget {
+        let limbs = self.mul_limbs(&x.limbs, &y.limbs, x.num_limbs() + y.num_limbs());
+        BigUintTarget { limbs }
+    }
+
+    /// Returns `x` if `b` is true, otherwise zero.
+    pub fn mul_biguint_by_bool(&mut self, x: &BigUintTarget, b: BoolTarget) -> BigUintTarget {
+        let limbs = x
+            .limbs
+            .iter()
+            .map(|&limb| self.mul(limb, b.target))
+            .collect();
+        BigUintTarget { limbs }
+    }
+
+    /// Returns `(x / y, x % y)`. The constraints are unsatisfiable if `y` is zero.
+    ///
+    /// The quotient and remainder are witnessed, with `x.num_limbs() - y.num_limbs() + 1` and
+    /// `y.num_limbs()` range-checked limbs respectively, and checked by `q * y + r == x` and
+    /// `r < y`. The quotient only fits if the most significant limb of `y` is nonzero, as for a
+    /// modulus, so pad `y` no further than needed.
+    pub fn div_rem_biguint(
+        &mut self,
+        x: &BigUintTarget,
+        y: &BigUintTarget,
+    ) -> (BigUintTarget, BigUintTarget) {
+        let div_num_limbs = x.num_limbs().saturating_sub(y.num_limbs()) + 1;
+        self.div_rem_biguint_sized(x, y, div_num_limbs)
+    }
+
+    /// Like [`CircuitBuilder::div_rem_biguint`], with a quotient of `div_num_limbs` limbs, which
+    /// must fit it.
+    pub(crate) fn div_rem_biguint_sized(
+        &mut self,
+        x: &BigUintTarget,
+        y: &BigUintTarget,
+        div_num_limbs: usize,
+    ) -> (BigUintTarget, BigUintTarget) {
+        let div = self.add_virtual_biguint_target(div_num_limbs);
+        let rem = self.add_virtual_biguint_target(y.num_limbs());
+
+        self.add_simple_generator(BigUintDivRemGenerator {
+            x: x.clone(),
+            y: y.clone(),
+            div: div.clone(),
+            rem: rem.clone(),
+        });
+
+        let div_y = self.mul_biguint(&div, y);
+        let div_y_plus_rem = self.add_biguint(&div_y, &rem);
+        self.connect_biguint(x, &div_y_plus_rem);
+
+        let rem_less_than_y = self.is_less_than_biguint(&rem, y);
+        self.assert_one(rem_less_than_y.target);
+
+        (div, rem)
+    }
+
+    pub fn div_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BigUintTarget {
+        self.div_rem_biguint(x, y).0
+    }
+
+    pub fn rem_biguint(&mut self, x: &BigUintTarget, y: &BigUintTarget) -> BigUintTarget {
+        self.div_rem_biguint(x, y).1
+    }
+}
+
+pub trait WitnessBigUint<F: PrimeField64>: Witness<F> {
+    fn get_biguint_target(&self, target: &BigUintTarget) -> BigUint {
+        let digits = target
+            .limbs
+            .iter()
+            .map(|&limb| self.get_target(limb).to_canonical_u64() as u32)
+            .collect();
+        BigUint::new(digits)
+    }
+}
+
+impl<F: PrimeField64, W: Witness<F>> WitnessBigUint<F> for W {}
+
+pub trait WitnessWriteBigUint<F: Field>: WitnessWrite<F> {
+    /// Sets the limbs of `target` to those of `value`, which must fit in them.
+    fn set_biguint_target(&mut self, target: &BigUintTarget, value: &BigUint) {
+        let digits = value.to_u32_digits();
+        assert!(
+            digits.len() <= target.num_limbs(),
+            "{value} doesn't fit in {} limbs",
+            target.num_limbs()
+        );
+        for (i, &limb) in target.limbs.iter().enumerate() {
+            let digit = digits.get(i).copied().unwrap_or(0);
+            self.set_target(limb, F::from_canonical_u32(digit));
+        }
+    }
+}
+
+impl<F: Field, W: WitnessWrite<F> + ?Sized> WitnessWriteBigUint<F> for W {}
+
+#[derive(Debug, Default)]
+pub struct BigUintDivRemGenerator {
+    x: BigUintTarget,
+    y: BigUintTarget,
+    div: BigUintTarget,
+    rem: BigUintTarget,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
+    for BigUintDivRemGenerator
+{
+    fn id(&self) -> String {
+        "BigUintDivRemGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        self.x.limbs.iter().chain(&self.y.limbs).copied().collect()
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let x = witness.get_biguint_target(&self.x);
+        let y = witness.get_biguint_target(&self.y);
+        assert!(!y.is_zero(), "Division of {x} by zero");
+        let (div, rem) = x.div_rem(&y);
+
+        out_buffer.set_biguint_target(&self.div, &div);
+        out_buffer.set_biguint_target(&self.rem, &rem);
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target_vec(&self.x.limbs)?;
+        dst.write_target_vec(&self.y.limbs)?;
+        dst.write_target_vec(&self.div.limbs)?;
+        dst.write_target_vec(&self.rem.limbs)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let x = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        let y = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        let div = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        let rem = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        Ok(Self { x, y, div, rem })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use num::bigint::RandBigInt;
+    use num::One;
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_biguint_arithmetic() -> Result<()> {
+        let mut rng = OsRng;
+        let x = rng.gen_biguint(256);
+        // The quotient's size assumes that the top limb of `y` is nonzero.
+        let y = rng.gen_biguint(130) | (BigUint::one() << 129);
+        let (div, rem) = x.div_rem(&y);
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let xt = builder.add_virtual_biguint_target(8);
+        let yt = builder.add_virtual_biguint_target(5);
+        pw.set_biguint_target(&xt, &x);
+        pw.set_biguint_target(&yt, &y);
+
+        let sum = builder.add_biguint(&xt, &yt);
+        let diff = builder.sub_biguint(&xt, &yt);
+        let product = builder.mul_biguint(&xt, &yt);
+        let (divt, remt) = builder.div_rem_biguint(&xt, &yt);
+        for (target, value) in [
+            (sum, &x + &y),
+            (diff, &x - &y),
+            (product, &x * &y),
+            (divt, div),
+            (remt, rem),
+        ] {
+            let value = builder.constant_biguint(&value);
+            builder.connect_biguint(&target, &value);
+        }
+
+        let less = builder.is_less_than_biguint(&yt, &xt);
+        let less_or_equal = builder.is_less_or_equal_biguint(&xt, &yt);
+        let equal = builder.is_equal_biguint(&xt, &xt);
+        builder.assert_one(less.target);
+        builder.assert_zero(less_or_equal.target);
+        builder.assert_one(equal.target);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+}
@@ -0,0 +1,220 @@
+//! Gadgets for points of elliptic curves over non-native fields, see [`Curve`].
+
+use alloc::vec::Vec;
+
+use crate::curve::curve_types::{AffinePoint, Curve};
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::gadgets::biguint::BigUintTarget;
+use crate::gadgets::nonnative::{num_nonnative_limbs, NonNativeTarget, WitnessWriteNonNative};
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::WitnessWrite;
+use crate::plonk::circuit_builder::CircuitBuilder;
+
+/// A point of the curve `C` other than the point at infinity, which can't be represented.
+#[derive(Clone, Debug)]
+pub struct AffinePointTarget<C: Curve> {
+    pub x: NonNativeTarget<C::BaseField>,
+    pub y: NonNativeTarget<C::BaseField>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds a new point. Its coordinates are canonical, but it isn't checked to be on the curve,
+    /// see `curve_assert_valid`.
+    pub fn add_virtual_affine_point_target<C: Curve>(&mut self) -> AffinePointTarget<C> {
+        let x = self.add_virtual_nonnative_target();
+        let y = self.add_virtual_nonnative_target();
+        AffinePointTarget { x, y }
+    }
+
+    pub fn constant_affine_point<C: Curve>(
+        &mut self,
+        point: AffinePoint<C>,
+    ) -> AffinePointTarget<C> {
+        assert!(!point.zero, "The point at infinity can't be represented");
+        AffinePointTarget {
+            x: self.constant_nonnative(point.x),
+            y: self.constant_nonnative(point.y),
+        }
+    }
+
+    pub fn connect_affine_point<C: Curve>(
+        &mut self,
+        p1: &AffinePointTarget<C>,
+        p2: &AffinePointTarget<C>,
+    ) {
+        self.connect_nonnative(&p1.x, &p2.x);
+        self.connect_nonnative(&p1.y, &p2.y);
+    }
+
+    /// Asserts that `p` is on the curve.
+    pub fn curve_assert_valid<C: Curve>(&mut self, p: &AffinePointTarget<C>) {
+        let a = self.constant_nonnative(C::A);
+        let b = self.constant_nonnative(C::B);
+
+        let y_squared = self.mul_nonnative(&p.y, &p.y);
+        let x_squared = self.mul_nonnative(&p.x, &p.x);
+        let x_cubed = self.mul_nonnative(&x_squared, &p.x);
+        let a_x = self.mul_nonnative(&a, &p.x);
+        let rhs = self.add_many_nonnative(&[x_cubed, a_x, b]);
+
+        self.connect_nonnative(&y_squared, &rhs);
+    }
+
+    pub fn curve_neg<C: Curve>(&mut self, p: &AffinePointTarget<C>) -> AffinePointTarget<C> {
+        AffinePointTarget {
+            x: p.x.clone(),
+            y: self.neg_nonnative(&p.y),
+        }
+    }
+
+    /// Returns `p1` if `b` is true, otherwise `p2`.
+    pub fn select_affine_point<C: Curve>(
+        &mut self,
+        b: BoolTarget,
+        p1: &AffinePointTarget<C>,
+        p2: &AffinePointTarget<C>,
+    ) -> AffinePointTarget<C> {
+        AffinePointTarget {
+            x: self.select_nonnative(b, &p1.x, &p2.x),
+            y: self.select_nonnative(b, &p1.y, &p2.y),
+        }
+    }
+
+    /// Returns `points[index]`, for an `index` less than the number of points, which must be a
+    /// power of two.
+    pub fn random_access_affine_point<C: Curve>(
+        &mut self,
+        index: Target,
+        points: &[AffinePointTarget<C>],
+    ) -> AffinePointTarget<C> {
+        let mut coordinate = |coordinates: Vec<&NonNativeTarget<C::BaseField>>| {
+            let limbs = (0..num_nonnative_limbs::<C::BaseField>())
+                .map(|i| {
+                    let column = coordinates.iter().map(|c| c.value.limbs[i]).collect();
+                    self.random_access(index, column)
+                })
+                .collect();
+            // This is one of the canonical coordinates, so its limbs are range-checked already.
+            NonNativeTarget::from_canonical_biguint(BigUintTarget { limbs })
+        };
+        let x = coordinate(points.iter().map(|p| &p.x).collect());
+        let y = coordinate(points.iter().map(|p| &p.y).collect());
+        AffinePointTarget { x, y }
+    }
+
+    /// Returns `2 p`. The constraints are unsatisfiable if `p` has order 2.
+    pub fn curve_double<C: Curve>(&mut self, p: &AffinePointTarget<C>) -> AffinePointTarget<C> {
+        let AffinePointTarget { x, y } = p;
+
+        // lambda = (3 x^2 + a) / (2 y), where additions are much cheaper than multiplications.
+        let x_squared = self.mul_nonnative(x, x);
+        let mut numerator =
+            self.add_many_nonnative(&[x_squared.clone(), x_squared.clone(), x_squared]);
+        if !C::A.is_zero() {
+            let a = self.constant_nonnative(C::A);
+            numerator = self.add_nonnative(&numerator, &a);
+        }
+        let two_y = self.add_nonnative(y, y);
+        let lambda = self.div_nonnative(&numerator, &two_y);
+
+        self.curve_add_with_lambda(p, x, &lambda)
+    }
+
+    /// Returns `p1 + p2`, for points with different `x` coordinates. This incomplete addition is
+    /// cheaper than a complete one, and the constraints are unsatisfiable if `p1 = ±p2`, so it
+    /// can't produce a wrong sum.
+    pub fn curve_add<C: Curve>(
+        &mut self,
+        p1: &AffinePointTarget<C>,
+        p2: &AffinePointTarget<C>,
+    ) -> AffinePointTarget<C> {
+        // lambda = (y2 - y1) / (x2 - x1), where the division asserts that x1 != x2.
+        let dy = self.sub_nonnative(&p2.y, &p1.y);
+        let dx = self.sub_nonnative(&p2.x, &p1.x);
+        let lambda = self.div_nonnative(&dy, &dx);
+
+        self.curve_add_with_lambda(p1, &p2.x, &lambda)
+    }
+
+    /// Returns the sum of `p1` and the point with `x` coordinate `x2` on the line of slope
+    /// `lambda` through `p1`.
+    fn curve_add_with_lambda<C: Curve>(
+        &mut self,
+        p1: &AffinePointTarget<C>,
+        x2: &NonNativeTarget<C::BaseField>,
+        lambda: &NonNativeTarget<C::BaseField>,
+    ) -> AffinePointTarget<C> {
+        // x3 = lambda^2 - x1 - x2, y3 = lambda (x1 - x3) - y1
+        let lambda_squared = self.mul_nonnative(lambda, lambda);
+        let x1_plus_x2 = self.add_nonnative(&p1.x, x2);
+        let x3 = self.sub_nonnative(&lambda_squared, &x1_plus_x2);
+        let x1_minus_x3 = self.sub_nonnative(&p1.x, &x3);
+        let lambda_dx = self.mul_nonnative(lambda, &x1_minus_x3);
+        let y3 = self.sub_nonnative(&lambda_dx, &p1.y);
+        AffinePointTarget { x: x3, y: y3 }
+    }
+}
+
+pub trait WitnessWriteAffinePoint<F: Field>: WitnessWrite<F> {
+    fn set_affine_point_target<C: Curve>(
+        &mut self,
+        target: &AffinePointTarget<C>,
+        value: AffinePoint<C>,
+    ) {
+        assert!(!value.zero, "The point at infinity can't be represented");
+        self.set_nonnative_target(&target.x, value.x);
+        self.set_nonnative_target(&target.y, value.y);
+    }
+}
+
+impl<F: Field, W: WitnessWrite<F> + ?Sized> WitnessWriteAffinePoint<F> for W {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::curve::secp256k1::Secp256K1;
+    use crate::field::secp256k1_scalar::Secp256K1Scalar;
+    use crate::field::types::Sample;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_curve_add_double() -> Result<()> {
+        let g = Secp256K1::GENERATOR_AFFINE;
+        let p1 = g.scalar_mul(Secp256K1Scalar::rand());
+        let p2 = g.scalar_mul(Secp256K1Scalar::rand());
+
+        let config = CircuitConfig::standard_ecc_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let p1t = builder.add_virtual_affine_point_target::<Secp256K1>();
+        let p2t = builder.add_virtual_affine_point_target::<Secp256K1>();
+        pw.set_affine_point_target(&p1t, p1);
+        pw.set_affine_point_target(&p2t, p2);
+        builder.curve_assert_valid(&p1t);
+        builder.curve_assert_valid(&p2t);
+
+        let sum = builder.curve_add(&p1t, &p2t);
+        let double = builder.curve_double(&p1t);
+        let neg = builder.curve_neg(&p1t);
+        for (target, value) in [(sum, p1 + p2), (double, p1.double()), (neg, -p1)] {
+            let value = builder.constant_affine_point(value);
+            builder.connect_affine_point(&target, &value);
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+}
@@ -0,0 +1,147 @@
+//! Multi-scalar multiplication with fixed bases, by 4-bit windows over tables of constant points.
+//!
+//! For each base `P` and window `i`, the circuit picks `d P 16^i + R_i` from a table of 16
+//! constants with [`CircuitBuilder::random_access`], where `d` is the scalar's `i`-th digit and
+//! `R_i` is an offset point, then adds it to the accumulator. The offsets keep every table entry
+//! and partial sum away from infinity without any selection, and their sum is subtracted at the
+//! end. They are derived by hashing to the curve, so nobody knows their discrete logarithms
+//! relative to the bases, and the incomplete additions only fail to be satisfiable with
+//! negligible probability.
+//!
+//! Over secp256k1 with `CircuitConfig::standard_ecc_config`, an incomplete addition costs about 2.3k
+//! gates, so each scalar costs about 148k gates for its 64 windows, compared to about 950k for the
+//! doublings and additions of [`CircuitBuilder::curve_scalar_mul_windowed`].
+
+use alloc::vec::Vec;
+
+use crate::curve::curve_types::{hash_to_curve, AffinePoint, Curve};
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::gadgets::curve::AffinePointTarget;
+use crate::gadgets::nonnative::NonNativeTarget;
+use crate::hash::hash_types::RichField;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::ceil_div_usize;
+
+const WINDOW_BITS: usize = 4;
+const OFFSET_DOMAIN: &[u8] = b"plonky2 fixed-base MSM offset";
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Returns `scalar * base`. The constraints are unsatisfiable if the result is infinity.
+    pub fn fixed_base_scalar_mul<C: Curve>(
+        &mut self,
+        base: AffinePoint<C>,
+        scalar: &NonNativeTarget<C::ScalarField>,
+    ) -> AffinePointTarget<C> {
+        self.fixed_base_msm(&[(base, scalar.clone())])
+    }
+
+    /// Returns `sum_j s_j P_j` for the given pairs `(P_j, s_j)`, such as the bases and openings
+    /// of a Pedersen commitment. The constraints are unsatisfiable if the result is infinity.
+    pub fn fixed_base_msm<C: Curve>(
+        &mut self,
+        terms: &[(AffinePoint<C>, NonNativeTarget<C::ScalarField>)],
+    ) -> AffinePointTarget<C> {
+        assert!(!terms.is_empty(), "An MSM needs at least one term");
+        let num_windows = ceil_div_usize(C::ScalarField::BITS, WINDOW_BITS);
+
+        let mut acc: Option<AffinePointTarget<C>> = None;
+        let mut offset_sum = AffinePoint::ZERO;
+        for (j, (base, scalar)) in terms.iter().enumerate() {
+            assert!(!base.zero, "Bases can't be infinity");
+            // Digits past `num_windows` are zero, as the scalar is canonical.
+            let digits = self.split_nonnative_to_4_bit_limbs(scalar);
+
+            let mut window_base = *base;
+            for (i, &digit) in digits[..num_windows].iter().enumerate() {
+                let offset = hash_to_curve::<C>(OFFSET_DOMAIN, (j * num_windows + i) as u64);
+                offset_sum = offset_sum + offset;
+
+                let mut table = Vec::with_capacity(1 << WINDOW_BITS);
+                let mut entry = offset;
+                for _ in 0..1 << WINDOW_BITS {
+                    table.push(self.constant_affine_point(entry));
+                    entry = entry + window_base;
+                }
+                let selected = self.random_access_affine_point(digit, &table);
+
+                acc = Some(match acc {
+                    None => selected,
+                    Some(acc) => self.curve_add(&acc, &selected),
+                });
+                for _ in 0..WINDOW_BITS {
+                    window_base = window_base.double();
+                }
+            }
+        }
+
+        let neg_offset_sum = self.constant_affine_point(-offset_sum);
+        self.curve_add(&acc.unwrap(), &neg_offset_sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::curve::secp256k1::Secp256K1;
+    use crate::field::secp256k1_scalar::Secp256K1Scalar;
+    use crate::field::types::Sample;
+    use crate::gadgets::nonnative::WitnessWriteNonNative;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_fixed_base_scalar_mul() {
+        let g = Secp256K1::GENERATOR_AFFINE;
+        let a = Secp256K1Scalar::rand();
+
+        let config = CircuitConfig::standard_ecc_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let at = builder.add_virtual_nonnative_target();
+        pw.set_nonnative_target(&at, a);
+
+        let public_key = builder.fixed_base_scalar_mul(g, &at);
+        let expected = builder.constant_affine_point(g.scalar_mul(a));
+        builder.connect_affine_point(&public_key, &expected);
+
+        // Proving takes too much memory for a unit test, but witness generation checks the result
+        // against `expected`.
+        let data = builder.mock_build::<C>();
+        data.generate_witness(pw);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_fixed_base_msm() -> Result<()> {
+        let g = Secp256K1::GENERATOR_AFFINE;
+        let h = g.scalar_mul(Secp256K1Scalar::rand());
+        let (a, b) = (Secp256K1Scalar::rand(), Secp256K1Scalar::rand());
+
+        let config = CircuitConfig::standard_ecc_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let at = builder.add_virtual_nonnative_target();
+        let bt = builder.add_virtual_nonnative_target();
+        pw.set_nonnative_target(&at, a);
+        pw.set_nonnative_target(&bt, b);
+
+        let commitment = builder.fixed_base_msm(&[(g, at), (h, bt)]);
+        let expected = builder.constant_affine_point(g.scalar_mul(a) + h.scalar_mul(b));
+        builder.connect_affine_point(&commitment, &expected);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+}
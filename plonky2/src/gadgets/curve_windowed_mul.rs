@@ -0,0 +1,126 @@
+//! Scalar multiplication of points known only to the prover, by 4-bit windows.
+//!
+//! The circuit computes the multiples `d P` for `d < 16` once, then processes the scalar's digits
+//! from the most significant one, multiplying the accumulator by 16 with four doublings and adding
+//! `d P` when the digit `d` is nonzero. The accumulator starts at an offset point with an unknown
+//! discrete logarithm, which keeps the incomplete additions away from their exceptional cases,
+//! and whose multiple is subtracted at the end.
+
+use alloc::vec::Vec;
+
+use crate::curve::curve_types::{hash_to_curve, Curve};
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::gadgets::curve::AffinePointTarget;
+use crate::gadgets::nonnative::NonNativeTarget;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::Target;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::ceil_div_usize;
+
+const WINDOW_BITS: usize = 4;
+const OFFSET_DOMAIN: &[u8] = b"plonky2 windowed scalar multiplication offset";
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Returns `scalar * p`. The constraints are unsatisfiable if the result is infinity.
+    pub fn curve_scalar_mul_windowed<C: Curve>(
+        &mut self,
+        p: &AffinePointTarget<C>,
+        scalar: &NonNativeTarget<C::ScalarField>,
+    ) -> AffinePointTarget<C> {
+        let num_windows = ceil_div_usize(C::ScalarField::BITS, WINDOW_BITS);
+        // Digits past `num_windows` are zero, as the scalar is canonical.
+        let digits = self.split_nonnative_to_4_bit_limbs(scalar);
+        self.curve_mul_by_digits(p, &digits[..num_windows])
+    }
+
+    /// Returns `n * p` for the integer `n` with the given little-endian 4-bit digits.
+    fn curve_mul_by_digits<C: Curve>(
+        &mut self,
+        p: &AffinePointTarget<C>,
+        digits: &[Target],
+    ) -> AffinePointTarget<C> {
+        // `table[0]` is never added, but it has to be a valid summand for the unused sum.
+        let mut table = Vec::with_capacity(1 << WINDOW_BITS);
+        table.push(p.clone());
+        table.push(p.clone());
+        table.push(self.curve_double(p));
+        for d in 3..1 << WINDOW_BITS {
+            let multiple = self.curve_add(&table[d - 1], p);
+            table.push(multiple);
+        }
+
+        let offset = hash_to_curve::<C>(OFFSET_DOMAIN, 0);
+        let mut acc = self.constant_affine_point(offset);
+        let zero = self.zero();
+        for (i, &digit) in digits.iter().rev().enumerate() {
+            if i > 0 {
+                for _ in 0..WINDOW_BITS {
+                    acc = self.curve_double(&acc);
+                }
+            }
+            let summand = self.random_access_affine_point(digit, &table);
+            let sum = self.curve_add(&acc, &summand);
+            let digit_is_zero = self.is_equal(digit, zero);
+            acc = self.select_affine_point(digit_is_zero, &acc, &sum);
+        }
+
+        // The offset was doubled `4 (digits.len() - 1)` times.
+        let mut scaled_offset = offset;
+        for _ in 0..WINDOW_BITS * (digits.len() - 1) {
+            scaled_offset = scaled_offset.double();
+        }
+        let neg_scaled_offset = self.constant_affine_point(-scaled_offset);
+        self.curve_add(&acc, &neg_scaled_offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::secp256k1::Secp256K1;
+    use crate::field::secp256k1_scalar::Secp256K1Scalar;
+    use crate::field::types::Sample;
+    use crate::gadgets::curve::WitnessWriteAffinePoint;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_curve_mul_by_digits() {
+        // A full scalar takes too much memory for a unit test, but the windows don't depend on
+        // the scalar's size. The middle digit is zero, so that its sum is discarded.
+        let p = Secp256K1::GENERATOR_AFFINE.scalar_mul(Secp256K1Scalar::rand());
+        let digits = [
+            rand::random::<u32>() % 16,
+            0,
+            1 + rand::random::<u32>() % 15,
+        ];
+        let n = digits[0] + (digits[2] << 8);
+
+        let config = CircuitConfig::standard_ecc_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let pt = builder.add_virtual_affine_point_target::<Secp256K1>();
+        pw.set_affine_point_target(&pt, p);
+        let digit_targets = digits
+            .map(|digit| {
+                let target = builder.add_virtual_target();
+                pw.set_target(target, F::from_canonical_u32(digit));
+                target
+            })
+            .to_vec();
+
+        let product = builder.curve_mul_by_digits(&pt, &digit_targets);
+        let expected = builder.constant_affine_point(p.biguint_mul(&n.into()));
+        builder.connect_affine_point(&product, &expected);
+
+        let data = builder.mock_build::<C>();
+        data.generate_witness(pw);
+    }
+}
@@ -0,0 +1,251 @@
+//! Decompositions into limbs whose range checks are deferred to `build`.
+//!
+//! Rather than adding `BaseSumGate`s for each decomposition, the limbs are recorded as range
+//! assumptions on the builder. `build` then checks all of them at once with lookups into one shared
+//! table per limb width, which is much cheaper when a circuit decomposes many values.
+
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use hashbrown::HashMap;
+
+use crate::field::extension::Extendable;
+use crate::gates::lookup_table::LookupTable;
+use crate::hash::hash_types::RichField;
+use crate::iop::generator::{GeneratedValues, SimpleGenerator};
+use crate::iop::target::Target;
+use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CommonCircuitData;
+use crate::util::log_floor;
+use crate::util::serialization::{Buffer, IoResult, Read, Write};
+
+/// The widest range assumption checked with a lookup, as lookup tables hold `u16`s. Wider ones
+/// are checked with `BaseSumGate`s.
+pub const MAX_LOOKUP_RANGE_BITS: usize = 16;
+
+/// A target assumed to be less than `2^num_bits`, which `build` checks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LimbTarget {
+    pub target: Target,
+    num_bits: usize,
+}
+
+impl LimbTarget {
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Splits `x` into `num_limbs` little-endian limbs of `limb_bits` bits each. This asserts that
+    /// `x < 2^(limb_bits * num_limbs)`.
+    pub fn decompose(&mut self, x: Target, limb_bits: usize, num_limbs: usize) -> Vec<LimbTarget> {
+        self.decompose_with_widths(x, &vec![limb_bits; num_limbs])
+    }
+
+    /// Splits `x` into little-endian limbs, the `i`-th of which has `limb_bits[i]` bits. This
+    /// asserts that `x` is less than 2 to the total number of bits.
+    pub fn decompose_with_widths(&mut self, x: Target, limb_bits: &[usize]) -> Vec<LimbTarget> {
+        let num_bits = limb_bits.iter().sum::<usize>();
+        assert!(
+            num_bits <= log_floor(F::ORDER, 2),
+            "{} bits may overflow the field",
+            num_bits
+        );
+
+        let limbs = self.add_virtual_targets(limb_bits.len());
+        self.add_simple_generator(DecompositionGenerator {
+            x,
+            limbs: limbs.clone(),
+            limb_bits: limb_bits.to_vec(),
+        });
+
+        let mut sum = self.zero();
+        let mut offset = 0;
+        for (&limb, &bits) in limbs.iter().zip(limb_bits) {
+            sum = self.mul_const_add(F::from_canonical_u64(1 << offset), limb, sum);
+            offset += bits;
+        }
+        self.connect(x, sum);
+
+        limbs
+            .into_iter()
+            .zip(limb_bits)
+            .map(|(limb, &bits)| self.assume_range(limb, bits))
+            .collect()
+    }
+
+    /// Returns `x` as a limb of `num_bits` bits, recording the assumption that `x < 2^num_bits`
+    /// for `build` to check.
+    pub fn assume_range(&mut self, x: Target, num_bits: usize) -> LimbTarget {
+        self.range_assumptions.push((x, num_bits));
+        LimbTarget {
+            target: x,
+            num_bits,
+        }
+    }
+
+    /// Returns the number of range assumptions recorded and not yet checked.
+    pub fn num_range_assumptions(&self) -> usize {
+        self.range_assumptions.len()
+    }
+
+    /// Checks the range assumptions recorded so far, with one lookup each into a table shared by
+    /// all the assumptions of the same width. Only the tightest assumption on each target is
+    /// checked. `build` calls this, so it is only needed to control where the gates are placed.
+    pub fn discharge_range_assumptions(&mut self) {
+        let assumptions = mem::take(&mut self.range_assumptions);
+        let mut tightest = HashMap::new();
+        for &(target, num_bits) in &assumptions {
+            tightest
+                .entry(target)
+                .and_modify(|bits: &mut usize| *bits = (*bits).min(num_bits))
+                .or_insert(num_bits);
+        }
+
+        let mut luts = [None; MAX_LOOKUP_RANGE_BITS + 1];
+        for (target, num_bits) in assumptions {
+            // Keep the order of the assumptions, so that the circuit is deterministic.
+            if tightest.get(&target) != Some(&num_bits) {
+                continue;
+            }
+            tightest.remove(&target);
+
+            if num_bits == 0 {
+                self.assert_zero(target);
+            } else if num_bits <= MAX_LOOKUP_RANGE_BITS {
+                let lut = *luts[num_bits].get_or_insert_with(|| {
+                    let table: LookupTable = Arc::new(
+                        (0..1u32 << num_bits)
+                            .map(|i| (i as u16, i as u16))
+                            .collect(),
+                    );
+                    self.add_lookup_table_from_pairs(table)
+                });
+                self.add_lookup_from_index(target, lut);
+            } else {
+                self.range_check(target, num_bits);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DecompositionGenerator {
+    x: Target,
+    limbs: Vec<Target>,
+    limb_bits: Vec<usize>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
+    for DecompositionGenerator
+{
+    fn id(&self) -> String {
+        "DecompositionGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![self.x]
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let mut x = witness.get_target(self.x).to_canonical_u64();
+        for (&limb, &bits) in self.limbs.iter().zip(&self.limb_bits) {
+            out_buffer.set_target(limb, F::from_canonical_u64(x & ((1 << bits) - 1)));
+            x >>= bits;
+        }
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target(self.x)?;
+        dst.write_target_vec(&self.limbs)?;
+        dst.write_usize_vec(&self.limb_bits)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let x = src.read_target()?;
+        let limbs = src.read_target_vec()?;
+        let limb_bits = src.read_usize_vec()?;
+        Ok(Self {
+            x,
+            limbs,
+            limb_bits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_decompose() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        // Many 16-bit decompositions share one table, next to narrower and wider limbs.
+        let mut values = Vec::new();
+        for _ in 0..100 {
+            let value = rand::random::<u64>() >> 16;
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(value));
+            let limbs = builder.decompose(x, 16, 3);
+            values.push((value, limbs));
+        }
+        let value = rand::random::<u64>() >> 1;
+        let x = builder.add_virtual_target();
+        pw.set_target(x, F::from_canonical_u64(value));
+        let limbs = builder.decompose_with_widths(x, &[5, 0, 24, 34]);
+        values.push((value, limbs));
+
+        for (mut value, limbs) in values {
+            for limb in limbs {
+                let expected = value & ((1 << limb.num_bits()) - 1);
+                let expected = builder.constant(F::from_canonical_u64(expected));
+                builder.connect(limb.target, expected);
+                value >>= limb.num_bits();
+            }
+        }
+        assert_eq!(builder.num_range_assumptions(), 304);
+        // Only the 34-bit limb needs gates here, as the lookups are placed by `build`.
+        let num_gates = builder.num_gates();
+        builder.discharge_range_assumptions();
+        assert_eq!(builder.num_range_assumptions(), 0);
+        assert!(builder.num_gates() - num_gates <= 2);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_assume_range_out_of_range() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let x = builder.add_virtual_target();
+        pw.set_target(x, F::from_canonical_u64(1 << 16));
+        builder.assume_range(x, 16);
+
+        let data = builder.build::<C>();
+        data.prove(pw).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+//! Arithmetic expressions over targets.
+//!
+//! An [`Expr`] is built from targets and constants with the `+`, `-` and `*` operators, and is
+//! turned into gate operations by [`CircuitBuilder::lower_expr`]. Lowering fuses each
+//! multiplication with a pending addition and with constant factors, so that e.g.
+//! `x * y + z * 3 - w` takes two arithmetic operations, however it is parenthesized.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Add, Mul, Neg, Sub};
+
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::target::Target;
+use crate::plonk::circuit_builder::CircuitBuilder;
+
+/// A kind of target which expressions can be built over, i.e. [`Target`] or [`ExtensionTarget`].
+pub trait ExprTarget<F: RichField + Extendable<D>, const D: usize>: Copy {
+    fn constant(builder: &mut CircuitBuilder<F, D>, c: F) -> Self;
+
+    /// Computes `const_0 * multiplicand_0 * multiplicand_1 + const_1 * addend`.
+    fn arithmetic(
+        builder: &mut CircuitBuilder<F, D>,
+        const_0: F,
+        const_1: F,
+        multiplicand_0: Self,
+        multiplicand_1: Self,
+        addend: Self,
+    ) -> Self;
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> ExprTarget<F, D> for Target {
+    fn constant(builder: &mut CircuitBuilder<F, D>, c: F) -> Self {
+        builder.constant(c)
+    }
+
+    fn arithmetic(
+        builder: &mut CircuitBuilder<F, D>,
+        const_0: F,
+        const_1: F,
+        multiplicand_0: Self,
+        multiplicand_1: Self,
+        addend: Self,
+    ) -> Self {
+        builder.arithmetic(const_0, const_1, multiplicand_0, multiplicand_1, addend)
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> ExprTarget<F, D> for ExtensionTarget<D> {
+    fn constant(builder: &mut CircuitBuilder<F, D>, c: F) -> Self {
+        builder.constant_extension(c.into())
+    }
+
+    fn arithmetic(
+        builder: &mut CircuitBuilder<F, D>,
+        const_0: F,
+        const_1: F,
+        multiplicand_0: Self,
+        multiplicand_1: Self,
+        addend: Self,
+    ) -> Self {
+        builder.arithmetic_extension(const_0, const_1, multiplicand_0, multiplicand_1, addend)
+    }
+}
+
+/// An arithmetic expression over targets of type `T`, with coefficients in `F`.
+#[derive(Clone, Debug)]
+pub struct Expr<F: Field, T: Copy>(Node<F, T>);
+
+#[derive(Clone, Debug)]
+enum Node<F: Field, T: Copy> {
+    Constant(F),
+    Target(T),
+    /// A linear combination `sum c_i e_i`.
+    Sum(Vec<(F, Expr<F, T>)>),
+    Product(Box<Expr<F, T>>, Box<Expr<F, T>>),
+}
+
+impl<F: Field, T: Copy> Expr<F, T> {
+    pub fn constant(c: F) -> Self {
+        Self(Node::Constant(c))
+    }
+
+    pub fn target(t: T) -> Self {
+        Self(Node::Target(t))
+    }
+
+    /// Computes `c * self`.
+    pub fn scale(self, c: F) -> Self {
+        match self.0 {
+            Node::Constant(x) => Self::constant(c * x),
+            Node::Sum(mut terms) => {
+                terms.iter_mut().for_each(|(coeff, _)| *coeff *= c);
+                Self(Node::Sum(terms))
+            }
+            node => Self(Node::Sum(vec![(c, Self(node))])),
+        }
+    }
+
+    fn into_terms(self) -> Vec<(F, Self)> {
+        match self.0 {
+            Node::Sum(terms) => terms,
+            node => vec![(F::ONE, Self(node))],
+        }
+    }
+}
+
+impl<F: Field, T: Copy> From<T> for Expr<F, T> {
+    fn from(t: T) -> Self {
+        Self::target(t)
+    }
+}
+
+impl<F: Field, T: Copy, R: Into<Expr<F, T>>> Add<R> for Expr<F, T> {
+    type Output = Self;
+
+    fn add(self, rhs: R) -> Self {
+        let mut terms = self.into_terms();
+        terms.extend(rhs.into().into_terms());
+        Self(Node::Sum(terms))
+    }
+}
+
+impl<F: Field, T: Copy, R: Into<Expr<F, T>>> Sub<R> for Expr<F, T> {
+    type Output = Self;
+
+    fn sub(self, rhs: R) -> Self {
+        let mut terms = self.into_terms();
+        terms.extend(rhs.into().scale(F::NEG_ONE).into_terms());
+        Self(Node::Sum(terms))
+    }
+}
+
+impl<F: Field, T: Copy, R: Into<Expr<F, T>>> Mul<R> for Expr<F, T> {
+    type Output = Self;
+
+    fn mul(self, rhs: R) -> Self {
+        Self(Node::Product(Box::new(self), Box::new(rhs.into())))
+    }
+}
+
+impl<F: Field, T: Copy> Neg for Expr<F, T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.scale(F::NEG_ONE)
+    }
+}
+
+/// The terms of a flattened sum, awaiting lowering.
+struct Terms<F, T> {
+    constant: F,
+    /// Terms `c * a * b`.
+    products: Vec<(F, T, T)>,
+    /// Terms `c * a`.
+    linear: Vec<(F, T)>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds the gate operations computing `expr`, and returns the resulting target.
+    ///
+    /// The expression is flattened into a sum of products. Each product `c * a * b` is computed by
+    /// a single arithmetic operation which also adds one other term of the sum, and the remaining
+    /// terms are added two at a time.
+    pub fn lower_expr<T: ExprTarget<F, D>>(&mut self, expr: &Expr<F, T>) -> T {
+        let mut terms = Terms {
+            constant: F::ZERO,
+            products: Vec::new(),
+            linear: Vec::new(),
+        };
+        self.flatten_expr(expr, F::ONE, &mut terms);
+        let Terms {
+            constant,
+            products,
+            mut linear,
+        } = terms;
+
+        if constant != F::ZERO {
+            linear.push((F::ONE, T::constant(self, constant)));
+        }
+
+        for (c, a, b) in products {
+            let (addend_coeff, addend) = match linear.pop() {
+                Some(term) => term,
+                None => (F::ZERO, T::constant(self, F::ZERO)),
+            };
+            let product = T::arithmetic(self, c, addend_coeff, a, b, addend);
+            linear.push((F::ONE, product));
+        }
+        while linear.len() > 1 {
+            let (c_0, x) = linear.pop().unwrap();
+            let (c_1, y) = linear.pop().unwrap();
+            let one = T::constant(self, F::ONE);
+            let sum = T::arithmetic(self, c_0, c_1, x, one, y);
+            linear.push((F::ONE, sum));
+        }
+
+        match linear.pop() {
+            None => T::constant(self, F::ZERO),
+            Some((c, x)) if c == F::ONE => x,
+            Some((c, x)) => {
+                let zero = T::constant(self, F::ZERO);
+                let one = T::constant(self, F::ONE);
+                T::arithmetic(self, c, F::ZERO, x, one, zero)
+            }
+        }
+    }
+
+    fn flatten_expr<T: ExprTarget<F, D>>(
+        &mut self,
+        expr: &Expr<F, T>,
+        coeff: F,
+        terms: &mut Terms<F, T>,
+    ) {
+        match &expr.0 {
+            Node::Constant(c) => terms.constant += coeff * *c,
+            Node::Target(t) => terms.linear.push((coeff, *t)),
+            Node::Sum(summands) => {
+                for (c, e) in summands {
+                    self.flatten_expr(e, coeff * *c, terms);
+                }
+            }
+            Node::Product(l, r) => match (&l.0, &r.0) {
+                (Node::Constant(c), _) => self.flatten_expr(r, coeff * *c, terms),
+                (_, Node::Constant(c)) => self.flatten_expr(l, coeff * *c, terms),
+                _ => {
+                    let a = self.lower_expr(l);
+                    let b = self.lower_expr(r);
+                    terms.products.push((coeff, a, b));
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type FF = <C as GenericConfig<D>>::FE;
+
+    #[test]
+    fn test_lower_expr() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let [x, y, z, w] = [(); 4].map(|_| builder.add_virtual_target());
+
+        let three = Expr::constant(F::from_canonical_u64(3));
+        let expr = (Expr::from(x) * y + Expr::from(z) * three - w) * Expr::constant(F::TWO)
+            - Expr::from(x) * w
+            + Expr::constant(F::from_canonical_u64(5));
+        let result = builder.lower_expr(&expr);
+        // The expression is a sum of five terms, two of which are products. Each product absorbs
+        // one of the three other terms, and the last one is added separately.
+        assert_eq!(builder.base_arithmetic_results.len(), 4);
+
+        let [xv, yv, zv, wv] = [(); 4].map(|_| F::rand());
+        let expected = (xv * yv + zv * F::from_canonical_u64(3) - wv) * F::TWO - xv * wv
+            + F::from_canonical_u64(5);
+        let expected = builder.constant(expected);
+        builder.connect(result, expected);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, xv);
+        pw.set_target(y, yv);
+        pw.set_target(z, zv);
+        pw.set_target(w, wv);
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    fn test_lower_expr_extension() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let [x, y] = [(); 2].map(|_| builder.add_virtual_extension_target());
+
+        // `(x + 1) * (x - y) * y`, where the inner sums must be lowered separately.
+        let expr = (Expr::from(x) + Expr::constant(F::ONE)) * (Expr::from(x) - y) * y;
+        let result = builder.lower_expr(&expr);
+
+        let [xv, yv] = [(); 2].map(|_| FF::rand());
+        let expected = builder.constant_extension((xv + FF::ONE) * (xv - yv) * yv);
+        builder.connect_extension(result, expected);
+
+        let mut pw = PartialWitness::new();
+        pw.set_extension_target(x, xv);
+        pw.set_extension_target(y, yv);
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    fn test_lower_trivial_exprs() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+
+        assert_eq!(builder.lower_expr(&Expr::from(x)), x);
+        let c = builder.lower_expr(&Expr::<F, Target>::constant(F::TWO));
+        assert_eq!(builder.target_as_constant(c), Some(F::TWO));
+        let c = builder.lower_expr(&(Expr::from(x) * Expr::constant(F::ZERO)));
+        assert_eq!(builder.target_as_constant(c), Some(F::ZERO));
+        assert!(builder.base_arithmetic_results.is_empty());
+    }
+}
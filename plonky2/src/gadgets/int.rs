@@ -0,0 +1,276 @@
+//! Gadgets for signed integers in two's complement, on top of the
+//! [unsigned ones](crate::gadgets::uint).
+//!
+//! The semantics follow the EVM's signed opcodes, and Rust's wrapping operations: division
+//! truncates towards zero, remainders have the sign of the dividend, dividing the minimum value
+//! by `-1` wraps around to the minimum value, and dividing by zero gives zero.
+
+use crate::field::extension::Extendable;
+use crate::field::types::{Field, PrimeField64};
+use crate::gadgets::uint::{UintTarget, WitnessUint, WitnessWriteUint};
+use crate::hash::hash_types::RichField;
+use crate::iop::target::BoolTarget;
+use crate::plonk::circuit_builder::CircuitBuilder;
+
+/// A signed integer of `N` 32-bit limbs, in two's complement.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct IntTarget<const N: usize> {
+    value: UintTarget<N>,
+}
+
+/// A 64-bit signed integer.
+pub type I64Target = IntTarget<2>;
+
+/// A 128-bit signed integer.
+pub type I128Target = IntTarget<4>;
+
+impl<const N: usize> IntTarget<N> {
+    /// The number of bits of the integer, including the sign bit.
+    pub const BITS: usize = 32 * N;
+
+    /// Interprets the bits of an unsigned integer in two's complement.
+    pub fn from_uint(value: UintTarget<N>) -> Self {
+        Self { value }
+    }
+
+    /// Returns the two's complement encoding of the integer.
+    pub fn to_uint(self) -> UintTarget<N> {
+        self.value
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    pub fn add_virtual_int_target<const N: usize>(&mut self) -> IntTarget<N> {
+        IntTarget::from_uint(self.add_virtual_uint_target())
+    }
+
+    pub fn add_virtual_i64_target(&mut self) -> I64Target {
+        self.add_virtual_int_target()
+    }
+
+    pub fn add_virtual_i128_target(&mut self) -> I128Target {
+        self.add_virtual_int_target()
+    }
+
+    pub fn constant_i64(&mut self, x: i64) -> I64Target {
+        IntTarget::from_uint(self.constant_u64(x as u64))
+    }
+
+    pub fn constant_i128(&mut self, x: i128) -> I128Target {
+        IntTarget::from_uint(self.constant_u128(x as u128))
+    }
+
+    pub fn connect_int<const N: usize>(&mut self, x: IntTarget<N>, y: IntTarget<N>) {
+        self.connect_uint(x.value, y.value);
+    }
+
+    /// Returns `x < 0`, i.e. its sign bit.
+    pub fn is_negative_int<const N: usize>(&mut self, x: IntTarget<N>) -> BoolTarget {
+        let (_, sign) = self.split_low_high(x.value.limbs()[N - 1], 31, 32);
+        BoolTarget::new_unsafe(sign)
+    }
+
+    /// Returns `x < y`.
+    pub fn is_less_than_int<const N: usize>(
+        &mut self,
+        x: IntTarget<N>,
+        y: IntTarget<N>,
+    ) -> BoolTarget {
+        // With different signs, the unsigned comparison is reversed.
+        let unsigned_less = self.is_less_than_uint(x.value, y.value);
+        let x_sign = self.is_negative_int(x);
+        let y_sign = self.is_negative_int(y);
+        let different_signs = self.xor(x_sign, y_sign);
+        self.xor(unsigned_less, different_signs)
+    }
+
+    /// Returns `x <= y`.
+    pub fn is_less_or_equal_int<const N: usize>(
+        &mut self,
+        x: IntTarget<N>,
+        y: IntTarget<N>,
+    ) -> BoolTarget {
+        let greater = self.is_less_than_int(y, x);
+        self.not(greater)
+    }
+
+    /// Returns `-x`, wrapping around for the minimum value.
+    pub fn neg_int<const N: usize>(&mut self, x: IntTarget<N>) -> IntTarget<N> {
+        let zero = self.constant_uint([0; N]);
+        IntTarget::from_uint(self.sub_uint_wrapping(zero, x.value))
+    }
+
+    /// Returns `|x|`, which is unsigned so that it fits for the minimum value.
+    pub fn abs_int<const N: usize>(&mut self, x: IntTarget<N>) -> UintTarget<N> {
+        let sign = self.is_negative_int(x);
+        self.abs_int_with_sign(x, sign)
+    }
+
+    /// Returns `(x / y, x % y)`, with the quotient truncated towards zero and the remainder of
+    /// the sign of `x`, like the EVM's `SDIV` and `SMOD`. Both are zero if `y` is zero.
+    pub fn div_rem_int<const N: usize>(
+        &mut self,
+        x: IntTarget<N>,
+        y: IntTarget<N>,
+    ) -> (IntTarget<N>, IntTarget<N>) {
+        let x_sign = self.is_negative_int(x);
+        let y_sign = self.is_negative_int(y);
+        let x_abs = self.abs_int_with_sign(x, x_sign);
+        let y_abs = self.abs_int_with_sign(y, y_sign);
+
+        // Divide by one instead of zero, which leaves a zero remainder, then zero the quotient.
+        let zero = self.constant_uint([0; N]);
+        let mut one_limbs = [0; N];
+        one_limbs[0] = 1;
+        let one = self.constant_uint(one_limbs);
+        let y_is_zero = self.is_equal_uint(y_abs, zero);
+        let divisor = self.select_uint(y_is_zero, one, y_abs);
+        let (div, rem) = self.div_rem_uint(x_abs, divisor);
+        let div = self.select_uint(y_is_zero, zero, div);
+
+        let div_sign = self.xor(x_sign, y_sign);
+        let div = self.neg_uint_if(div_sign, div);
+        let rem = self.neg_uint_if(x_sign, rem);
+        (IntTarget::from_uint(div), IntTarget::from_uint(rem))
+    }
+
+    /// Returns the low `bits` bits of `x` as a signed integer of `bits` bits, sign-extended to
+    /// `N` limbs, like the EVM's `SIGNEXTEND` for `bits = 8 (b + 1)`.
+    pub fn sign_extend_int<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        bits: usize,
+    ) -> IntTarget<N> {
+        assert!(
+            0 < bits && bits <= IntTarget::<N>::BITS,
+            "Can't sign-extend {bits} bits"
+        );
+        let (sign_limb, sign_bit) = ((bits - 1) / 32, (bits - 1) % 32);
+        let mut limbs = x.limbs();
+        let limb_bits = self.split_le(limbs[sign_limb], 32);
+        let sign = limb_bits[sign_bit];
+        // The bits from the sign bit up are all set to the sign bit.
+        let low = self.le_sum(limb_bits[..sign_bit].iter());
+        let high = F::from_canonical_u64((1 << 32) - (1 << sign_bit));
+        limbs[sign_limb] = self.mul_const_add(high, sign.target, low);
+        let ones = F::from_canonical_u32(u32::MAX);
+        for limb in &mut limbs[sign_limb + 1..] {
+            *limb = self.mul_const(ones, sign.target);
+        }
+        // The limbs are 32-bit, as the new bits make up for the discarded ones.
+        IntTarget::from_uint(UintTarget { limbs })
+    }
+
+    fn abs_int_with_sign<const N: usize>(
+        &mut self,
+        x: IntTarget<N>,
+        sign: BoolTarget,
+    ) -> UintTarget<N> {
+        self.neg_uint_if(sign, x.value)
+    }
+
+    /// Returns `-x mod 2^BITS` if `b` is true, otherwise `x`.
+    fn neg_uint_if<const N: usize>(&mut self, b: BoolTarget, x: UintTarget<N>) -> UintTarget<N> {
+        let neg = self.neg_int(IntTarget::from_uint(x));
+        self.select_uint(b, neg.value, x)
+    }
+}
+
+pub trait WitnessInt<F: PrimeField64>: WitnessUint<F> {
+    fn get_i64_target(&self, target: I64Target) -> i64 {
+        self.get_u64_target(target.value) as i64
+    }
+
+    fn get_i128_target(&self, target: I128Target) -> i128 {
+        self.get_u128_target(target.value) as i128
+    }
+}
+
+impl<F: PrimeField64, W: WitnessUint<F>> WitnessInt<F> for W {}
+
+pub trait WitnessWriteInt<F: Field>: WitnessWriteUint<F> {
+    fn set_i64_target(&mut self, target: I64Target, value: i64) {
+        self.set_u64_target(target.value, value as u64);
+    }
+
+    fn set_i128_target(&mut self, target: I128Target, value: i128) {
+        self.set_u128_target(target.value, value as u128);
+    }
+}
+
+impl<F: Field, W: WitnessWriteUint<F>> WitnessWriteInt<F> for W {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    use super::*;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_i64_arithmetic() -> Result<()> {
+        let mut rng = OsRng;
+        // Include the overflowing division, division by zero, and every combination of signs.
+        for (x, y) in [
+            (rng.gen::<i64>(), rng.gen::<i32>() as i64),
+            (-7, 2),
+            (7, -2),
+            (-7, -2),
+            (i64::MIN, -1),
+            (rng.gen::<i64>(), 0),
+        ] {
+            let config = CircuitConfig::standard_recursion_config();
+            let mut pw = PartialWitness::<F>::new();
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+
+            let xt = builder.add_virtual_i64_target();
+            let yt = builder.add_virtual_i64_target();
+            pw.set_i64_target(xt, x);
+            pw.set_i64_target(yt, y);
+
+            let (div, rem) = builder.div_rem_int(xt, yt);
+            let neg = builder.neg_int(xt);
+            let abs = builder.abs_int(xt);
+            let less = builder.is_less_than_int(xt, yt);
+            let less_or_equal = builder.is_less_or_equal_int(yt, xt);
+            let negative = builder.is_negative_int(xt);
+            let byte = builder.sign_extend_int(xt.to_uint(), 8);
+            let word = builder.sign_extend_int(xt.to_uint(), 40);
+
+            let (expected_div, expected_rem) = if y == 0 {
+                (0, 0)
+            } else {
+                (x.wrapping_div(y), x.wrapping_rem(y))
+            };
+            for (target, value) in [
+                (div, expected_div),
+                (rem, expected_rem),
+                (neg, x.wrapping_neg()),
+                (IntTarget::from_uint(abs), x.unsigned_abs() as i64),
+                (byte, x as i8 as i64),
+                (word, (x << 24) >> 24),
+            ] {
+                let value = builder.constant_i64(value);
+                builder.connect_int(target, value);
+            }
+            for (target, value) in [(less, x < y), (less_or_equal, y <= x), (negative, x < 0)] {
+                let value = builder.constant_bool(value);
+                builder.connect(target.target, value.target);
+            }
+
+            let data = builder.build::<C>();
+            let proof = data.prove(pw)?;
+            verify(proof, &data.verifier_only, &data.common)?;
+        }
+        Ok(())
+    }
+}
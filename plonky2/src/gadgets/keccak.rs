@@ -0,0 +1,293 @@
+//! Keccak-256, the hash of Ethereum's tries and addresses, over byte targets.
+//!
+//! The state is kept as 1600 bit targets, so the rotations and lane permutations of each round
+//! are free, and each XOR costs two arithmetic operations. With
+//! `CircuitConfig::standard_recursion_config`, a permutation costs about 13k gates, and every
+//! 136-byte block of the padded input takes one permutation.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::log2_ceil;
+
+/// The number of bytes absorbed by each permutation.
+pub const KECCAK256_RATE_BYTES: usize = 136;
+const KECCAK256_DIGEST_BYTES: usize = 32;
+const LANE_BITS: usize = 64;
+const NUM_ROUNDS: usize = 24;
+
+const ROUND_CONSTANTS: [u64; NUM_ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// The rotation of the lane at `(x, y)`, as `ROTATIONS[x][y]`.
+const ROTATIONS: [[usize; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+/// The index of bit `z` of the lane at `(x, y)` in a state of 1600 bits, so that the state is
+/// the little-endian encoding of its lanes in the order of the byte encoding of the state.
+const fn bit_index(x: usize, y: usize, z: usize) -> usize {
+    ((x % 5) + 5 * (y % 5)) * LANE_BITS + z % LANE_BITS
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Returns the Keccak-256 hash of `data`, whose length is fixed by the circuit, as 32 bytes.
+    /// This asserts that the entries of `data` are bytes.
+    pub fn keccak256(&mut self, data: &[Target]) -> Vec<Target> {
+        let num_blocks = data.len() / KECCAK256_RATE_BYTES + 1;
+        let mut padding = vec![0u8; num_blocks * KECCAK256_RATE_BYTES - data.len()];
+        padding[0] |= 0x01;
+        *padding.last_mut().unwrap() |= 0x80;
+
+        let mut padded = data.to_vec();
+        padded.extend(
+            padding
+                .into_iter()
+                .map(|byte| self.constant(F::from_canonical_u8(byte))),
+        );
+        self.keccak256_blocks(&padded).pop().unwrap()
+    }
+
+    /// Returns the Keccak-256 hash of the first `len` bytes of `data`, as 32 bytes. This asserts
+    /// that `len` is at most `data.len()`, and that the first `len` entries of `data` are bytes;
+    /// the others are ignored. The cost is that of the longest input, `data.len()` bytes.
+    pub fn keccak256_variable(&mut self, data: &[Target], len: Target) -> Vec<Target> {
+        let max_len = self.constant(F::from_canonical_usize(data.len()));
+        let slack = self.sub(max_len, len);
+        self.range_check(slack, log2_ceil(data.len() + 1));
+
+        // The padding starts at `len` with `0x01` and ends with `0x80` at the end of the block
+        // holding `len`, which is last. `in_data[i]` is whether `i < len`.
+        let num_blocks = data.len() / KECCAK256_RATE_BYTES + 1;
+        let zero = self.zero();
+        let mut in_data = self._true();
+        let mut block_starts = Vec::with_capacity(num_blocks);
+        let mut is_last_block = Vec::with_capacity(num_blocks);
+        let mut padded = Vec::with_capacity(num_blocks * KECCAK256_RATE_BYTES);
+        for i in 0..num_blocks * KECCAK256_RATE_BYTES {
+            if i % KECCAK256_RATE_BYTES == 0 {
+                block_starts.push(in_data);
+            }
+            let i_target = self.constant(F::from_canonical_usize(i));
+            let at_len = self.is_equal(len, i_target);
+            in_data = BoolTarget::new_unsafe(self.sub(in_data.target, at_len.target));
+
+            let byte = data.get(i).copied().unwrap_or(zero);
+            let mut byte = self.mul_add(in_data.target, byte, at_len.target);
+            if i % KECCAK256_RATE_BYTES == KECCAK256_RATE_BYTES - 1 {
+                // The flags are monotonic, so this is whether `len` is in the current block.
+                let block_start = block_starts.last().unwrap().target;
+                let is_last = BoolTarget::new_unsafe(self.sub(block_start, in_data.target));
+                byte = self.mul_const_add(F::from_canonical_u8(0x80), is_last.target, byte);
+                is_last_block.push(is_last);
+            }
+            padded.push(byte);
+        }
+
+        let digests = self.keccak256_blocks(&padded);
+        (0..KECCAK256_DIGEST_BYTES)
+            .map(|i| {
+                digests
+                    .iter()
+                    .zip(&is_last_block)
+                    .fold(zero, |acc, (digest, is_last)| {
+                        self.mul_add(is_last.target, digest[i], acc)
+                    })
+            })
+            .collect()
+    }
+
+    /// Absorbs the padded input `padded`, and returns the digest squeezed after each block.
+    fn keccak256_blocks(&mut self, padded: &[Target]) -> Vec<Vec<Target>> {
+        debug_assert_eq!(padded.len() % KECCAK256_RATE_BYTES, 0);
+        let mut state = {
+            let zero = self._false();
+            vec![zero; 25 * LANE_BITS]
+        };
+        padded
+            .chunks(KECCAK256_RATE_BYTES)
+            .map(|block| {
+                for (i, &byte) in block.iter().enumerate() {
+                    let bits = self.split_le(byte, 8);
+                    for (j, bit) in bits.into_iter().enumerate() {
+                        state[8 * i + j] = self.xor(state[8 * i + j], bit);
+                    }
+                }
+                self.keccak_f(&mut state);
+                state[..8 * KECCAK256_DIGEST_BYTES]
+                    .chunks(8)
+                    .map(|bits| self.le_sum(bits.iter()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Applies the Keccak-f[1600] permutation to a state of 1600 bits.
+    fn keccak_f(&mut self, state: &mut [BoolTarget]) {
+        for round_constant in ROUND_CONSTANTS {
+            // θ
+            let parities = (0..5)
+                .flat_map(|x| (0..LANE_BITS).map(move |z| (x, z)))
+                .map(|(x, z)| {
+                    (1..5).fold(state[bit_index(x, 0, z)], |acc, y| {
+                        self.xor(acc, state[bit_index(x, y, z)])
+                    })
+                })
+                .collect::<Vec<_>>();
+            for x in 0..5 {
+                for z in 0..LANE_BITS {
+                    let left = parities[bit_index(x + 4, 0, z)];
+                    let right = parities[bit_index(x + 1, 0, z + LANE_BITS - 1)];
+                    let diff = self.xor(left, right);
+                    for y in 0..5 {
+                        let i = bit_index(x, y, z);
+                        state[i] = self.xor(state[i], diff);
+                    }
+                }
+            }
+
+            // ρ and π
+            let mut permuted = state.to_vec();
+            for x in 0..5 {
+                for y in 0..5 {
+                    let rotation = ROTATIONS[x][y];
+                    for z in 0..LANE_BITS {
+                        permuted[bit_index(y, 2 * x + 3 * y, z + rotation)] =
+                            state[bit_index(x, y, z)];
+                    }
+                }
+            }
+
+            // χ
+            for x in 0..5 {
+                for y in 0..5 {
+                    for z in 0..LANE_BITS {
+                        let b = permuted[bit_index(x + 1, y, z)];
+                        let c = permuted[bit_index(x + 2, y, z)];
+                        let not_b_and_c =
+                            self.arithmetic(-F::ONE, F::ONE, b.target, c.target, c.target);
+                        state[bit_index(x, y, z)] = self.xor(
+                            permuted[bit_index(x, y, z)],
+                            BoolTarget::new_unsafe(not_b_and_c),
+                        );
+                    }
+                }
+            }
+
+            // ι
+            for z in 0..LANE_BITS {
+                if (round_constant >> z) & 1 == 1 {
+                    state[z] = self.not(state[z]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn connect_digest(builder: &mut CircuitBuilder<F, D>, digest: &[Target], input: &[u8]) {
+        let expected = keccak_hash::keccak(input).0;
+        for (&target, &byte) in digest.iter().zip(&expected) {
+            let byte = builder.constant(F::from_canonical_u8(byte));
+            builder.connect(target, byte);
+        }
+    }
+
+    #[test]
+    fn test_keccak256() -> Result<()> {
+        let input = (0..200).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let data = builder.add_virtual_targets(input.len());
+        for (&target, &byte) in data.iter().zip(&input) {
+            pw.set_target(target, F::from_canonical_u8(byte));
+        }
+        let digest = builder.keccak256(&data);
+        connect_digest(&mut builder, &digest, &input);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    fn test_keccak256_variable() {
+        // The lengths cover empty inputs, both padding bytes merged into one, and a second block.
+        let input = (0..150).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        let lens = [
+            0,
+            1,
+            KECCAK256_RATE_BYTES - 1,
+            KECCAK256_RATE_BYTES,
+            input.len(),
+        ];
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let data = builder.add_virtual_targets(input.len());
+        for (&target, &byte) in data.iter().zip(&input) {
+            pw.set_target(target, F::from_canonical_u8(byte));
+        }
+        for len in lens {
+            let len_target = builder.add_virtual_target();
+            pw.set_target(len_target, F::from_canonical_usize(len));
+            let digest = builder.keccak256_variable(&data, len_target);
+            connect_digest(&mut builder, &digest, &input[..len]);
+        }
+
+        // Witness generation checks the digests against the expected ones.
+        let data = builder.mock_build::<C>();
+        data.generate_witness(pw);
+    }
+}
@@ -0,0 +1,285 @@
+//! Set membership and dictionary gadgets, on top of the lookup argument.
+//!
+//! Sets and dictionaries whose keys and values fit in 16 bits are a single lookup table from keys
+//! to values. Larger ones, with keys and values up to the field order, are split into tables from
+//! the index of each entry to the 16-bit chunks of its key and value: looking up every chunk at the
+//! same index proves that the chunks come from the same entry. Either way, they are limited to
+//! `2^16` entries.
+
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::gates::lookup_table::LookupTable;
+use crate::hash::hash_types::RichField;
+use crate::iop::generator::{GeneratedValues, SimpleGenerator};
+use crate::iop::target::Target;
+use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CommonCircuitData;
+use crate::util::ceil_div_usize;
+use crate::util::serialization::{Buffer, IoResult, Read, Write};
+
+const CHUNK_BITS: usize = 16;
+const MAX_ENTRIES: usize = 1 << CHUNK_BITS;
+
+/// A dictionary committed to in lookup tables, see [`CircuitBuilder::commit_dictionary`].
+#[derive(Clone, Debug)]
+pub struct CommittedDictionary {
+    layout: DictionaryLayout,
+}
+
+/// A set committed to in lookup tables, see [`CircuitBuilder::commit_set`].
+#[derive(Clone, Debug)]
+pub struct CommittedSet(CommittedDictionary);
+
+#[derive(Clone, Debug)]
+enum DictionaryLayout {
+    /// A table from keys to values.
+    Direct { lut: usize },
+    /// Tables from the index of each entry to each chunk of its key, then of its value.
+    Indexed {
+        keys: Vec<u64>,
+        key_luts: Vec<usize>,
+        value_luts: Vec<usize>,
+    },
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds lookup tables for the set of `values`, which must be less than the field order, and
+    /// number at most `2^16` once deduplicated. The tables are added to the circuit even if the
+    /// set is not used, and unused tables are rejected by `build`.
+    pub fn commit_set(&mut self, values: &[u64]) -> CommittedSet {
+        let mut values = values.to_vec();
+        values.sort_unstable();
+        values.dedup();
+        let entries = values
+            .into_iter()
+            .map(|value| (value, 0))
+            .collect::<Vec<_>>();
+        CommittedSet(self.commit_dictionary(&entries))
+    }
+
+    /// Asserts that `x` is in `set`.
+    pub fn assert_in_set(&mut self, x: Target, set: &CommittedSet) {
+        self.dictionary_get(x, &set.0);
+    }
+
+    /// Adds lookup tables for the dictionary of `(key, value)` entries, whose keys must be
+    /// distinct. There must be at most `2^16` entries, and keys and values must be less than the
+    /// field order. The tables are added to the circuit even if the dictionary is not used, and
+    /// unused tables are rejected by `build`.
+    pub fn commit_dictionary(&mut self, entries: &[(u64, u64)]) -> CommittedDictionary {
+        assert!(
+            !entries.is_empty() && entries.len() <= MAX_ENTRIES,
+            "Dictionaries must have between 1 and {} entries",
+            MAX_ENTRIES
+        );
+        assert!(
+            entries
+                .iter()
+                .all(|&(key, value)| key < F::ORDER && value < F::ORDER),
+            "Entries must be less than the field order"
+        );
+        let mut distinct_keys = entries.iter().map(|&(key, _)| key).collect::<Vec<_>>();
+        distinct_keys.sort_unstable();
+        distinct_keys.dedup();
+        assert_eq!(distinct_keys.len(), entries.len(), "Duplicate keys");
+
+        let max_key = entries.iter().map(|&(key, _)| key).max().unwrap();
+        let max_value = entries.iter().map(|&(_, value)| value).max().unwrap();
+        if max_key < 1 << CHUNK_BITS && max_value < 1 << CHUNK_BITS {
+            let table: LookupTable = Arc::new(
+                entries
+                    .iter()
+                    .map(|&(key, value)| (key as u16, value as u16))
+                    .collect(),
+            );
+            let lut = self.add_lookup_table_from_pairs(table);
+            return CommittedDictionary {
+                layout: DictionaryLayout::Direct { lut },
+            };
+        }
+
+        let mut chunk_luts = |values: Vec<u64>, max: u64| {
+            let num_chunks = ceil_div_usize(64 - max.leading_zeros() as usize, CHUNK_BITS);
+            (0..num_chunks)
+                .map(|c| {
+                    let table: LookupTable = Arc::new(
+                        values
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &value)| (i as u16, (value >> (CHUNK_BITS * c)) as u16))
+                            .collect(),
+                    );
+                    self.add_lookup_table_from_pairs(table)
+                })
+                .collect::<Vec<_>>()
+        };
+        let keys = entries.iter().map(|&(key, _)| key).collect::<Vec<_>>();
+        let key_luts = chunk_luts(keys.clone(), max_key);
+        let value_luts = chunk_luts(entries.iter().map(|&(_, value)| value).collect(), max_value);
+        CommittedDictionary {
+            layout: DictionaryLayout::Indexed {
+                keys,
+                key_luts,
+                value_luts,
+            },
+        }
+    }
+
+    /// Returns the value of `key` in `dictionary`, asserting that it is one of its keys.
+    pub fn dictionary_get(&mut self, key: Target, dictionary: &CommittedDictionary) -> Target {
+        match &dictionary.layout {
+            DictionaryLayout::Direct { lut } => self.add_lookup_from_index(key, *lut),
+            DictionaryLayout::Indexed {
+                keys,
+                key_luts,
+                value_luts,
+            } => {
+                let index = self.add_virtual_target();
+                self.add_simple_generator(DictionaryIndexGenerator {
+                    key,
+                    index,
+                    keys: keys.iter().map(|&key| F::from_canonical_u64(key)).collect(),
+                });
+                let key_chunks = key_luts
+                    .iter()
+                    .map(|&lut| self.add_lookup_from_index(index, lut))
+                    .collect::<Vec<_>>();
+                let key_sum = self.chunk_sum(&key_chunks);
+                self.connect(key, key_sum);
+                let value_chunks = value_luts
+                    .iter()
+                    .map(|&lut| self.add_lookup_from_index(index, lut))
+                    .collect::<Vec<_>>();
+                self.chunk_sum(&value_chunks)
+            }
+        }
+    }
+
+    /// Returns `sum_i chunks[i] * 2^(16 i)`.
+    fn chunk_sum(&mut self, chunks: &[Target]) -> Target {
+        let base = F::from_canonical_u64(1 << CHUNK_BITS);
+        let zero = self.zero();
+        chunks
+            .iter()
+            .rev()
+            .fold(zero, |acc, &chunk| self.mul_const_add(base, acc, chunk))
+    }
+}
+
+/// Finds the index of a key in an indexed dictionary.
+#[derive(Debug, Default)]
+pub struct DictionaryIndexGenerator<F: Field> {
+    key: Target,
+    index: Target,
+    keys: Vec<F>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
+    for DictionaryIndexGenerator<F>
+{
+    fn id(&self) -> String {
+        "DictionaryIndexGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![self.key]
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let key = witness.get_target(self.key);
+        let index = self
+            .keys
+            .iter()
+            .position(|&k| k == key)
+            .unwrap_or_else(|| panic!("Key {} is not in the dictionary", key));
+        out_buffer.set_target(self.index, F::from_canonical_usize(index));
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target(self.key)?;
+        dst.write_target(self.index)?;
+        dst.write_usize(self.keys.len())?;
+        dst.write_field_vec(&self.keys)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let key = src.read_target()?;
+        let index = src.read_target()?;
+        let num_keys = src.read_usize()?;
+        let keys = src.read_field_vec(num_keys)?;
+        Ok(Self { key, index, keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_set_and_dictionary() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        // An allow-list of small values, one of addresses, and a fee table with large keys.
+        let small_set = builder.commit_set(&[3, 1000, 40000]);
+        let addresses = [0x1234_5678_9abc, 0xdead_beef, 7];
+        let large_set = builder.commit_set(&addresses);
+        let fees = [(0xffff_0000_0000, 3), (12, 1 << 40), (1 << 20, 0)];
+        let fee_table = builder.commit_dictionary(&fees);
+
+        for value in [3, 40000] {
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(value));
+            builder.assert_in_set(x, &small_set);
+        }
+        for value in addresses {
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(value));
+            builder.assert_in_set(x, &large_set);
+        }
+        for (key, fee) in fees {
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(key));
+            let value = builder.dictionary_get(x, &fee_table);
+            let expected = builder.constant(F::from_canonical_u64(fee));
+            builder.connect(value, expected);
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_not_in_set() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let set = builder.commit_set(&[0xdead_beef, 7]);
+        let x = builder.add_virtual_target();
+        pw.set_target(x, F::from_canonical_u64(8));
+        builder.assert_in_set(x, &set);
+
+        let data = builder.mock_build::<C>();
+        data.generate_witness(pw);
+    }
+}
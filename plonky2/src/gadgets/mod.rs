@@ -1,11 +1,27 @@
 pub mod arithmetic;
 pub mod arithmetic_extension;
+pub mod biguint;
+pub mod curve;
+pub mod curve_fixed_base;
+pub mod curve_windowed_mul;
+pub mod decompose;
+pub mod ecdsa;
+pub mod expression;
 pub mod hash;
+pub mod int;
 pub mod interpolation;
+pub mod keccak;
 pub mod lookup;
+pub mod membership;
+pub mod montgomery;
+pub mod mpt;
+pub mod nonnative;
+pub mod permutation;
 pub mod polynomial;
 pub mod random_access;
 pub mod range_check;
+pub mod rlp;
 pub mod select;
 pub mod split_base;
 pub mod split_join;
+pub mod uint;
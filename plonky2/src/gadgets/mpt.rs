@@ -0,0 +1,783 @@
+//! Verification of Merkle-Patricia trie proofs, like those of `eth_getProof`, against the roots
+//! of Ethereum's state, storage, transaction and receipt tries.
+//!
+//! A proof lists the nodes on the path of a key from the root. Each node is checked against the
+//! hash referencing it with [`CircuitBuilder::keccak256_variable`] and decoded with the
+//! [RLP gadgets](crate::gadgets::rlp), then the key selects the next node: the child of a branch
+//! node at the key's next nibble, or the child of an extension node whose path matches the key.
+//! The path ends at a leaf, which holds the key's value if its path matches the rest of the key,
+//! or wherever the key diverges from the trie, which proves that the key is absent.
+//!
+//! Nodes whose encoding is shorter than 32 bytes are inlined in their parent rather than referenced
+//! by hash. They only appear in tries with short keys, and are checked against the bytes of their
+//! parent instead. `eth_getProof` doesn't list them, so [`WitnessWriteMpt::set_mpt_proof_target`]
+//! extracts them from their parent. The values of branch nodes are ignored, as Ethereum's keys
+//! are prefix-free. With
+//! `CircuitConfig::standard_recursion_config`, each node costs about 14k gates per 136 bytes of
+//! its maximum length for the hash, plus a few thousand gates to decode it, so for the usual
+//! maximum of 532 bytes, a level of the proof costs about 60k gates.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::WitnessWrite;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::log2_ceil;
+
+const HASH_LEN: usize = 32;
+const BRANCH_NODE_ITEMS: usize = 17;
+
+/// A proof of the path of a key in a trie, of at most `nodes.len()` nodes.
+#[derive(Clone, Debug)]
+pub struct MptProofTarget {
+    /// The encodings of the nodes from the root, padded with zeros to the same maximum length.
+    pub nodes: Vec<Vec<Target>>,
+    pub node_lens: Vec<Target>,
+    /// The number of nodes in the proof, between 1 and `nodes.len()`.
+    pub depth: Target,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    pub fn add_virtual_mpt_proof_target(
+        &mut self,
+        max_depth: usize,
+        max_node_len: usize,
+    ) -> MptProofTarget {
+        assert!(max_depth > 0, "A proof has at least one node");
+        MptProofTarget {
+            nodes: (0..max_depth)
+                .map(|_| self.add_virtual_targets(max_node_len))
+                .collect(),
+            node_lens: self.add_virtual_targets(max_depth),
+            depth: self.add_virtual_target(),
+        }
+    }
+
+    /// Asserts that `proof` shows that the trie with the root hash `root` maps the key `key` to
+    /// the first `value_len` bytes of `value`, whose other entries must be zero. The root and the
+    /// key are given as bytes.
+    pub fn verify_mpt_inclusion(
+        &mut self,
+        root: &[Target],
+        key: &[Target],
+        value: &[Target],
+        value_len: Target,
+        proof: &MptProofTarget,
+    ) {
+        let (found, leaf_value, leaf_value_len) = self.mpt_lookup(root, key, proof, value.len());
+        self.assert_one(found.target);
+        self.connect(leaf_value_len, value_len);
+        for (&x, &y) in leaf_value.iter().zip(value) {
+            self.connect(x, y);
+        }
+    }
+
+    /// Asserts that `proof` shows that the trie with the root hash `root` has no value for the
+    /// key `key`. The root and the key are given as bytes.
+    pub fn verify_mpt_exclusion(
+        &mut self,
+        root: &[Target],
+        key: &[Target],
+        proof: &MptProofTarget,
+    ) {
+        let (found, _, _) = self.mpt_lookup(root, key, proof, 0);
+        self.assert_zero(found.target);
+    }
+
+    /// Follows `key` down `proof` from `root`, and returns whether the trie has a value for the
+    /// key, with the value padded with zeros to `max_value_len` bytes and its length, or zeros.
+    fn mpt_lookup(
+        &mut self,
+        root: &[Target],
+        key: &[Target],
+        proof: &MptProofTarget,
+        max_value_len: usize,
+    ) -> (BoolTarget, Vec<Target>, Target) {
+        assert_eq!(root.len(), HASH_LEN, "The root is a Keccak-256 hash");
+        let max_depth = proof.nodes.len();
+        let num_nibbles = 2 * key.len();
+        let zero = self.zero();
+        let one = self.one();
+
+        // Paths are compared with the key up to their maximum length, past the end of the key.
+        let mut key_nibbles = Vec::with_capacity(2 * num_nibbles);
+        for &byte in key {
+            let bits = self.split_le(byte, 8);
+            key_nibbles.extend(self.nibbles(&bits));
+        }
+        key_nibbles.resize(2 * num_nibbles, zero);
+
+        // `active[i]` is whether the proof has a node at level `i`. Levels past the end of the
+        // proof are only decoded, so any valid node, like a copy of the last one, fills them.
+        let active = self.rlp_prefix_flags(proof.depth, max_depth + 1);
+        self.assert_one(active[0].target);
+        self.assert_zero(active[max_depth].target);
+
+        let mut expected_hash = root.to_vec();
+        let mut key_pos = zero;
+        let mut found = zero;
+        let mut last_node = vec![zero; proof.nodes[0].len()];
+        let mut value_offset = zero;
+        let mut value_len = zero;
+        let mut value_is_list = zero;
+        // Whether the node at the current level is inlined in the previous one, with its length
+        // and the bytes of the previous node from its start.
+        let mut is_inlined = zero;
+        let mut inlined_len = zero;
+        let mut inlined_bytes = vec![zero; HASH_LEN - 1];
+        for (i, (node, &node_len)) in proof.nodes.iter().zip(&proof.node_lens).enumerate() {
+            let is_active = active[i];
+            let is_last = BoolTarget::new_unsafe(self.sub(is_active.target, active[i + 1].target));
+            let continues = self.sub(is_active.target, is_last.target);
+
+            // Nodes are checked against the hash referencing them, or against their encoding in
+            // their parent if they are inlined.
+            let hash = self.keccak256_variable(node, node_len);
+            let check_hash = self.arithmetic(
+                -F::ONE,
+                F::ONE,
+                is_active.target,
+                is_inlined,
+                is_active.target,
+            );
+            for (&x, &y) in hash.iter().zip(&expected_hash) {
+                let diff = self.sub(x, y);
+                let diff = self.mul(check_hash, diff);
+                self.assert_zero(diff);
+            }
+            let len_diff = self.sub(node_len, inlined_len);
+            let len_diff = self.mul(is_inlined, len_diff);
+            self.assert_zero(len_diff);
+            // `inlined_len` is zero unless the node is inlined.
+            let in_inlined = self.rlp_prefix_flags(inlined_len, HASH_LEN - 1);
+            for ((&x, &y), in_inlined) in node.iter().zip(&inlined_bytes).zip(in_inlined) {
+                let diff = self.sub(x, y);
+                let diff = self.mul(in_inlined.target, diff);
+                self.assert_zero(diff);
+            }
+
+            // Decode two items, then the other items of branch nodes. For the other nodes, the
+            // remaining items are decoded from the start of the node, so they stay in bounds.
+            let list = self.decode_rlp_item(node, zero);
+            self.assert_one(list.is_list.target);
+            let end = self.rlp_item_end(&list);
+            self.connect(end, node_len);
+            let mut items = Vec::with_capacity(BRANCH_NODE_ITEMS);
+            let mut is_branch = self._false();
+            let mut pos = list.offset;
+            for j in 0..BRANCH_NODE_ITEMS {
+                let item = self.decode_rlp_item(node, pos);
+                let item_end = self.rlp_item_end(&item);
+                if j == 1 {
+                    let has_two_items = self.is_equal(item_end, end);
+                    is_branch = self.not(has_two_items);
+                }
+                pos = if j == 0 {
+                    item_end
+                } else {
+                    self.select(is_branch, item_end, zero)
+                };
+                items.push(item);
+            }
+            let branch_overrun = self.sub(pos, end);
+            let branch_overrun = self.mul(is_branch.target, branch_overrun);
+            self.assert_zero(branch_overrun);
+            let is_short = self.not(is_branch);
+
+            // For branch nodes, the child at the key's next nibble, which may be empty.
+            let nibble = self.random_access_padded(key_pos, &key_nibbles);
+            let children = &items[..16];
+            let child_offset =
+                self.random_access(nibble, children.iter().map(|c| c.offset).collect());
+            let child_len = self.random_access(nibble, children.iter().map(|c| c.len).collect());
+            let child_is_list =
+                self.random_access(nibble, children.iter().map(|c| c.is_list.target).collect());
+            let child_is_empty = self.is_equal(child_len, zero);
+            let not_child_is_list = self.not(BoolTarget::new_unsafe(child_is_list));
+            let child_is_empty = self.and(child_is_empty, not_child_is_list);
+
+            // For extension and leaf nodes, the hex-prefix encoding of the path. The high nibble
+            // of its first byte has a flag for leaves and one for odd lengths, and the low nibble
+            // is the first nibble of odd paths, or zero.
+            let [path, next] = [items[0], items[1]];
+            let path_bytes = self.mpt_read_bytes(node, path.offset, key.len() + 1);
+            let first_bits = self.split_le(path_bytes[0], 8);
+            let (is_odd, is_leaf) = (first_bits[4], first_bits[5]);
+            let mut path_nibbles = self.nibbles(&first_bits).to_vec();
+            for &byte in &path_bytes[1..] {
+                let bits = self.split_le(byte, 8);
+                path_nibbles.extend(self.nibbles(&bits));
+            }
+            let not_odd = self.not(is_odd);
+            let invalid = self.add(first_bits[6].target, first_bits[7].target);
+            let invalid = self.mul_add(not_odd.target, path_nibbles[1], invalid);
+            let invalid = self.add(invalid, path.is_list.target);
+            let path_is_empty = self.is_equal(path.len, zero);
+            let invalid = self.add(invalid, path_is_empty.target);
+            let invalid = self.mul(is_short.target, invalid);
+            self.assert_zero(invalid);
+
+            // `path_len = 2 (path.len - 1) + is_odd` nibbles, which have to fit in the key.
+            let path_len = self.mul_const_add(F::TWO, path.len, is_odd.target);
+            let path_len = self.add_const(path_len, -F::TWO);
+            let path_end = self.add(key_pos, path_len);
+            let key_end = self.constant(F::from_canonical_usize(num_nibbles));
+            let slack = self.sub(key_end, path_end);
+            let active_short = self.and(is_active, is_short);
+            let slack = self.mul(active_short.target, slack);
+            self.range_check(slack, log2_ceil(num_nibbles + 1));
+            let ends_at_key_end = self.is_equal(path_end, key_end);
+
+            let in_path = self.rlp_prefix_flags(path_len, num_nibbles);
+            let mut mismatches = zero;
+            for (j, in_path) in in_path.into_iter().enumerate() {
+                let path_nibble = self.select(is_odd, path_nibbles[j + 1], path_nibbles[j + 2]);
+                let key_index = self.add_const(key_pos, F::from_canonical_usize(j));
+                let key_nibble = self.random_access_padded(key_index, &key_nibbles);
+                let matches = self.is_equal(path_nibble, key_nibble);
+                let mismatch = self.sub(in_path.target, matches.target);
+                // This is `in_path (1 - matches)`, as paths match the padding past their end.
+                let mismatch = self.mul(in_path.target, mismatch);
+                mismatches = self.add(mismatches, mismatch);
+            }
+            let path_matches = self.is_equal(mismatches, zero);
+
+            let is_leaf = self.and(is_short, is_leaf);
+            let is_extension = BoolTarget::new_unsafe(self.sub(is_short.target, is_leaf.target));
+            let extension_continues = self.and(is_extension, path_matches);
+            let child_is_present = self.not(child_is_empty);
+            let branch_continues = self.and(is_branch, child_is_present);
+            let node_continues = self.add(extension_continues.target, branch_continues.target);
+            let leaf_matches = self.and(path_matches, ends_at_key_end);
+            let leaf_matches = self.and(is_leaf, leaf_matches);
+
+            // Every node of the proof but the last leads to the next one, by hash.
+            let node_continues = self.mul(is_active.target, node_continues);
+            self.connect(node_continues, continues);
+            let next_offset = self.select(is_branch, child_offset, next.offset);
+            let next_len = self.select(is_branch, child_len, next.len);
+            let next_is_list = self.select(is_branch, child_is_list, next.is_list.target);
+            let next_is_list = BoolTarget::new_unsafe(next_is_list);
+            // The next node is referenced by a hash, which is a 32-byte string, or inlined, as a
+            // list.
+            let hash_len = self.constant(F::from_canonical_usize(HASH_LEN));
+            let next_is_hash = self.is_equal(next_len, hash_len);
+            let next_is_string = self.not(next_is_list);
+            let next_is_hash = self.and(next_is_hash, next_is_string);
+            let invalid_ref = self.sub(one, next_is_hash.target);
+            let invalid_ref = self.sub(invalid_ref, next_is_list.target);
+            let invalid_ref = self.mul(continues, invalid_ref);
+            self.assert_zero(invalid_ref);
+            expected_hash = self.mpt_read_bytes(node, next_offset, HASH_LEN);
+            // Inlined nodes are shorter than a hash, so their header is a single byte.
+            is_inlined = self.mul(continues, next_is_list.target);
+            let inlined_start = self.add_const(next_offset, -F::ONE);
+            inlined_len = self.add_const(next_len, F::ONE);
+            inlined_len = self.mul(is_inlined, inlined_len);
+            inlined_bytes = self.mpt_read_bytes(node, inlined_start, HASH_LEN - 1);
+
+            let advance = self.select(is_branch, one, path_len);
+            key_pos = self.mul_add(continues, advance, key_pos);
+
+            found = self.mul_add(is_last.target, leaf_matches.target, found);
+            for (acc, &byte) in last_node.iter_mut().zip(node) {
+                *acc = self.mul_add(is_last.target, byte, *acc);
+            }
+            value_offset = self.mul_add(is_last.target, next.offset, value_offset);
+            value_len = self.mul_add(is_last.target, next.len, value_len);
+            value_is_list = self.mul_add(is_last.target, next.is_list.target, value_is_list);
+        }
+
+        // Values are strings, like the encodings of accounts in the state trie.
+        let found = BoolTarget::new_unsafe(found);
+        let value_is_list = self.mul(found.target, value_is_list);
+        self.assert_zero(value_is_list);
+        let value_len = self.mul(found.target, value_len);
+        let max_value_len_target = self.constant(F::from_canonical_usize(max_value_len));
+        let slack = self.sub(max_value_len_target, value_len);
+        self.range_check(slack, log2_ceil(max_value_len + 1));
+
+        let in_value = self.rlp_prefix_flags(value_len, max_value_len);
+        let value_bytes = self.mpt_read_bytes(&last_node, value_offset, max_value_len);
+        let value = in_value
+            .into_iter()
+            .zip(value_bytes)
+            .map(|(in_value, byte)| self.mul(in_value.target, byte))
+            .collect();
+        (found, value, value_len)
+    }
+
+    /// Returns the high and low nibbles of the byte with the given little-endian bits.
+    fn nibbles(&mut self, bits: &[BoolTarget]) -> [Target; 2] {
+        [self.le_sum(bits[4..].iter()), self.le_sum(bits[..4].iter())]
+    }
+
+    /// Returns the `count` bytes of `data` from `offset`, or zeros past its end, where `offset`
+    /// is at most `data.len()`.
+    fn mpt_read_bytes(&mut self, data: &[Target], offset: Target, count: usize) -> Vec<Target> {
+        let zero = self.zero();
+        let mut padded = data.to_vec();
+        padded.resize(data.len() + count, zero);
+        (0..count)
+            .map(|i| {
+                let index = self.add_const(offset, F::from_canonical_usize(i));
+                self.random_access_padded(index, &padded)
+            })
+            .collect()
+    }
+}
+
+pub trait WitnessWriteMpt<F: Field>: WitnessWrite<F> {
+    /// Sets `target` to the nodes of `proof`, the proof of `key` as returned by `eth_getProof`,
+    /// from the root. Nodes inlined in their parent, which `eth_getProof` doesn't list, are added
+    /// after it, and the levels past the end of the proof are filled with copies of its last node.
+    fn set_mpt_proof_target(&mut self, target: &MptProofTarget, key: &[u8], proof: &[Vec<u8>]) {
+        let proof = with_inlined_nodes(proof, key);
+        assert!(!proof.is_empty(), "A proof has at least one node");
+        assert!(proof.len() <= target.nodes.len(), "The proof is too deep");
+        self.set_target(target.depth, F::from_canonical_usize(proof.len()));
+        for (i, (node_target, &len_target)) in
+            target.nodes.iter().zip(&target.node_lens).enumerate()
+        {
+            let node = &proof[i.min(proof.len() - 1)];
+            assert!(node.len() <= node_target.len(), "The node is too long");
+            self.set_target(len_target, F::from_canonical_usize(node.len()));
+            for (j, &byte_target) in node_target.iter().enumerate() {
+                let byte = node.get(j).copied().unwrap_or(0);
+                self.set_target(byte_target, F::from_canonical_u8(byte));
+            }
+        }
+    }
+}
+
+impl<F: Field, W: WitnessWrite<F> + ?Sized> WitnessWriteMpt<F> for W {}
+
+/// Returns the nodes of `proof`, followed by the nodes on the path of `key` which are inlined in
+/// them. Inlined nodes can only reference inlined nodes, so they are at the end of the path.
+fn with_inlined_nodes(proof: &[Vec<u8>], key: &[u8]) -> Vec<Vec<u8>> {
+    let key_nibbles = key
+        .iter()
+        .flat_map(|&byte| [byte >> 4, byte & 0xf])
+        .collect::<Vec<_>>();
+    let mut key_pos = 0;
+    let mut nodes = Vec::with_capacity(proof.len());
+    let mut listed_nodes = proof.iter().cloned();
+    let mut next_node = listed_nodes.next();
+    while let Some(node) = next_node {
+        let items = rlp_list_items(&node);
+        let child = if items.len() == BRANCH_NODE_ITEMS {
+            key_nibbles.get(key_pos).map(|&nibble| {
+                key_pos += 1;
+                items[nibble as usize]
+            })
+        } else {
+            // The hex-prefix encoding of the path of an extension or leaf node.
+            let path = rlp_payload(items[0]);
+            let (is_odd, is_leaf) = (path[0] & 0x10 != 0, path[0] & 0x20 != 0);
+            let path_nibbles = path
+                .iter()
+                .flat_map(|&byte| [byte >> 4, byte & 0xf])
+                .skip(if is_odd { 1 } else { 2 })
+                .collect::<Vec<_>>();
+            let follows = !is_leaf && key_nibbles[key_pos..].starts_with(&path_nibbles);
+            follows.then(|| {
+                key_pos += path_nibbles.len();
+                items[1]
+            })
+        };
+        next_node = match child {
+            Some(child) if child[0] >= 0xc0 => Some(child.to_vec()),
+            _ => listed_nodes.next(),
+        };
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Returns the length of the header of the RLP item at the start of `data`, and the length of
+/// its payload.
+fn rlp_item_header(data: &[u8]) -> (usize, usize) {
+    let read_len = |len_of_len: usize| {
+        data[1..=len_of_len]
+            .iter()
+            .fold(0, |len, &byte| (len << 8) | byte as usize)
+    };
+    match data[0] {
+        0x00..=0x7f => (0, 1),
+        prefix @ 0x80..=0xb7 => (1, (prefix - 0x80) as usize),
+        prefix @ 0xb8..=0xbf => {
+            let len_of_len = (prefix - 0xb7) as usize;
+            (1 + len_of_len, read_len(len_of_len))
+        }
+        prefix @ 0xc0..=0xf7 => (1, (prefix - 0xc0) as usize),
+        prefix @ 0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            (1 + len_of_len, read_len(len_of_len))
+        }
+    }
+}
+
+fn rlp_payload(item: &[u8]) -> &[u8] {
+    let (header_len, len) = rlp_item_header(item);
+    &item[header_len..header_len + len]
+}
+
+/// Returns the encodings of the items of the RLP list `list`.
+fn rlp_list_items(list: &[u8]) -> Vec<&[u8]> {
+    let mut payload = rlp_payload(list);
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let (header_len, len) = rlp_item_header(payload);
+        let (item, rest) = payload.split_at(header_len + len);
+        items.push(item);
+        payload = rest;
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    const MAX_DEPTH: usize = 7;
+    const MAX_NODE_LEN: usize = 135;
+    const MAX_VALUE_LEN: usize = 48;
+
+    /// Entries of a trie, with keys as nibbles relative to the node holding them.
+    type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+    fn rlp_header(base: u8, len: usize) -> Vec<u8> {
+        if len <= 55 {
+            return vec![base + len as u8];
+        }
+        let len_bytes = (len as u32).to_be_bytes();
+        let len_bytes = &len_bytes[len_bytes.iter().position(|&b| b != 0).unwrap()..];
+        let mut header = vec![base + 55 + len_bytes.len() as u8];
+        header.extend(len_bytes);
+        header
+    }
+
+    fn rlp_string(bytes: &[u8]) -> Vec<u8> {
+        if let [byte] = bytes {
+            if *byte < 0x80 {
+                return vec![*byte];
+            }
+        }
+        let mut encoding = rlp_header(0x80, bytes.len());
+        encoding.extend(bytes);
+        encoding
+    }
+
+    fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+        let payload = items.concat();
+        let mut encoding = rlp_header(0xc0, payload.len());
+        encoding.extend(payload);
+        encoding
+    }
+
+    fn hex_prefix(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+        let is_odd = nibbles.len() % 2 == 1;
+        let mut all = vec![2 * is_leaf as u8 + is_odd as u8];
+        if !is_odd {
+            all.push(0);
+        }
+        all.extend(nibbles);
+        all.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect()
+    }
+
+    fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+        bytes.iter().flat_map(|&b| [b >> 4, b & 0xf]).collect()
+    }
+
+    fn common_prefix_len(entries: &Entries) -> usize {
+        let first = &entries[0].0;
+        entries
+            .iter()
+            .map(|(key, _)| first.iter().zip(key).take_while(|(a, b)| a == b).count())
+            .min()
+            .unwrap()
+    }
+
+    fn strip(entries: &Entries, len: usize) -> Entries {
+        entries
+            .iter()
+            .map(|(key, value)| (key[len..].to_vec(), value.clone()))
+            .collect()
+    }
+
+    fn branch_child(entries: &Entries, nibble: u8) -> Entries {
+        let child = entries
+            .iter()
+            .filter(|(key, _)| key.first() == Some(&nibble))
+            .cloned()
+            .collect();
+        strip(&child, 1)
+    }
+
+    fn node_ref(node: &[u8]) -> Vec<u8> {
+        if node.len() < HASH_LEN {
+            return node.to_vec();
+        }
+        rlp_string(&keccak_hash::keccak(node).0)
+    }
+
+    fn encode_node(entries: &Entries) -> Vec<u8> {
+        if let [(path, value)] = &entries[..] {
+            return rlp_list(&[rlp_string(&hex_prefix(path, true)), rlp_string(value)]);
+        }
+        let prefix_len = common_prefix_len(entries);
+        if prefix_len > 0 {
+            let child = encode_node(&strip(entries, prefix_len));
+            let path = hex_prefix(&entries[0].0[..prefix_len], false);
+            return rlp_list(&[rlp_string(&path), node_ref(&child)]);
+        }
+        let mut items = (0..16)
+            .map(|nibble| {
+                let child = branch_child(entries, nibble);
+                if child.is_empty() {
+                    rlp_string(&[])
+                } else {
+                    node_ref(&encode_node(&child))
+                }
+            })
+            .collect::<Vec<_>>();
+        let value = entries
+            .iter()
+            .find(|(key, _)| key.is_empty())
+            .map_or(vec![], |(_, value)| value.clone());
+        items.push(rlp_string(&value));
+        rlp_list(&items)
+    }
+
+    /// Returns the nodes on the path of `key`, given as nibbles, from the root, without the
+    /// inlined nodes, like `eth_getProof`.
+    fn prove(entries: &Entries, key: &[u8]) -> Vec<Vec<u8>> {
+        let mut proof = prove_path(entries, key);
+        let root = proof.remove(0);
+        proof.retain(|node| node.len() >= HASH_LEN);
+        proof.insert(0, root);
+        proof
+    }
+
+    fn prove_path(entries: &Entries, key: &[u8]) -> Vec<Vec<u8>> {
+        let mut proof = vec![encode_node(entries)];
+        if entries.len() == 1 {
+            return proof;
+        }
+        let prefix_len = common_prefix_len(entries);
+        if prefix_len > 0 {
+            if key[..prefix_len] == entries[0].0[..prefix_len] {
+                proof.extend(prove_path(&strip(entries, prefix_len), &key[prefix_len..]));
+            }
+        } else {
+            let child = branch_child(entries, key[0]);
+            if !child.is_empty() {
+                proof.extend(prove_path(&child, &key[1..]));
+            }
+        }
+        proof
+    }
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        (0..len).map(|_| rand::random::<u8>()).collect()
+    }
+
+    /// Keys whose paths go through a branch node at the root, an extension node, another branch
+    /// node and leaves.
+    fn test_keys() -> [Vec<u8>; 3] {
+        let mut keys = [random_bytes(32), random_bytes(32), random_bytes(32)];
+        keys[0][..2].copy_from_slice(&[0x12, 0x3a]);
+        keys[1][..2].copy_from_slice(&[0x12, 0x3b]);
+        keys[2][0] = 0x50;
+        keys
+    }
+
+    fn test_trie(keys: &[Vec<u8>]) -> Entries {
+        keys.iter()
+            .map(|key| (to_nibbles(key), random_bytes(40)))
+            .collect()
+    }
+
+    fn add_bytes(
+        builder: &mut CircuitBuilder<F, D>,
+        pw: &mut PartialWitness<F>,
+        bytes: &[u8],
+    ) -> Vec<Target> {
+        let targets = builder.add_virtual_targets(bytes.len());
+        for (&target, &byte) in targets.iter().zip(bytes) {
+            pw.set_target(target, F::from_canonical_u8(byte));
+        }
+        targets
+    }
+
+    /// Adds a proof of the path of `key` in the trie with the given entries, and returns the
+    /// targets of the root, the key and the proof.
+    fn add_proof(
+        builder: &mut CircuitBuilder<F, D>,
+        pw: &mut PartialWitness<F>,
+        entries: &Entries,
+        key: &[u8],
+    ) -> (Vec<Target>, Vec<Target>, MptProofTarget) {
+        let root = keccak_hash::keccak(encode_node(entries)).0;
+        let root_target = add_bytes(builder, pw, &root);
+        let key_target = add_bytes(builder, pw, key);
+        let proof_target = builder.add_virtual_mpt_proof_target(MAX_DEPTH, MAX_NODE_LEN);
+        pw.set_mpt_proof_target(&proof_target, key, &prove(entries, &to_nibbles(key)));
+        (root_target, key_target, proof_target)
+    }
+
+    fn verify_inclusion(
+        builder: &mut CircuitBuilder<F, D>,
+        pw: &mut PartialWitness<F>,
+        entries: &Entries,
+        key: &[u8],
+        value: &[u8],
+    ) {
+        let (root, key, proof) = add_proof(builder, pw, entries, key);
+        let value_len = builder.constant(F::from_canonical_usize(value.len()));
+        let mut padded_value = value.to_vec();
+        padded_value.resize(MAX_VALUE_LEN, 0);
+        let value = add_bytes(builder, pw, &padded_value);
+        builder.verify_mpt_inclusion(&root, &key, &value, value_len, &proof);
+    }
+
+    fn verify_exclusion(
+        builder: &mut CircuitBuilder<F, D>,
+        pw: &mut PartialWitness<F>,
+        entries: &Entries,
+        key: &[u8],
+    ) {
+        let (root, key, proof) = add_proof(builder, pw, entries, key);
+        builder.verify_mpt_exclusion(&root, &key, &proof);
+    }
+
+    // Proving these circuits takes too much memory for unit tests, but witness generation checks
+    // every constraint of the gadget that could fail. Each proof gets its own circuit, to bound
+    // the memory used by a test.
+    fn generate_witness(add_proof: impl FnOnce(&mut CircuitBuilder<F, D>, &mut PartialWitness<F>)) {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        add_proof(&mut builder, &mut pw);
+
+        let data = builder.mock_build::<C>();
+        data.generate_witness(pw);
+    }
+
+    #[test]
+    fn test_mpt_inclusion() {
+        let keys = test_keys();
+        let entries = test_trie(&keys);
+
+        // The first key's proof has all four kinds of nodes, and the last one's only two.
+        for i in [0, 2] {
+            generate_witness(|builder, pw| {
+                verify_inclusion(builder, pw, &entries, &keys[i], &entries[i].1)
+            });
+        }
+    }
+
+    #[test]
+    fn test_mpt_exclusion() {
+        let keys = test_keys();
+        let entries = test_trie(&keys);
+        // The paths of these keys end at an empty child of the root, at the extension node, and
+        // at the leaf of the last key.
+        let mut absent_keys = [random_bytes(32), keys[0].clone(), keys[2].clone()];
+        absent_keys[0][0] = 0x70;
+        absent_keys[1][0] = 0x14;
+        absent_keys[2][31] ^= 1;
+
+        for key in &absent_keys {
+            generate_witness(|builder, pw| verify_exclusion(builder, pw, &entries, key));
+        }
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Tries of the trie tests of the Ethereum test suite, whose short keys make inlined nodes.
+    fn ethereum_test_tries() -> [(Entries, Vec<u8>); 2] {
+        let trie = |entries: &[(&str, &str)]| -> Entries {
+            entries
+                .iter()
+                .map(|(key, value)| (to_nibbles(key.as_bytes()), value.as_bytes().to_vec()))
+                .collect()
+        };
+        [
+            (
+                trie(&[
+                    ("do", "verb"),
+                    ("horse", "stallion"),
+                    ("doge", "coin"),
+                    ("dog", "puppy"),
+                ]),
+                from_hex("5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"),
+            ),
+            (
+                trie(&[
+                    ("doe", "reindeer"),
+                    ("dog", "puppy"),
+                    ("dogglesworth", "cat"),
+                ]),
+                from_hex("8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_mpt_inlined_nodes() {
+        let [(puppy, puppy_root), (dogs, dogs_root)] = ethereum_test_tries();
+        assert_eq!(
+            keccak_hash::keccak(encode_node(&puppy)).0.to_vec(),
+            puppy_root
+        );
+        assert_eq!(
+            keccak_hash::keccak(encode_node(&dogs)).0.to_vec(),
+            dogs_root
+        );
+
+        // `doge` and `doe` are in inlined leaves, below an inlined branch node for `doge`.
+        let included: [(&Entries, &[u8], &[u8]); 4] = [
+            (&puppy, b"doge", b"coin"),
+            (&puppy, b"horse", b"stallion"),
+            (&dogs, b"doe", b"reindeer"),
+            (&dogs, b"dogglesworth", b"cat"),
+        ];
+        for (entries, key, value) in included {
+            generate_witness(|builder, pw| verify_inclusion(builder, pw, entries, key, value));
+        }
+        let excluded: [(&Entries, &[u8]); 2] = [(&puppy, b"dogs"), (&dogs, b"dot")];
+        for (entries, key) in excluded {
+            generate_witness(|builder, pw| verify_exclusion(builder, pw, entries, key));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mpt_inclusion_of_absent_key() {
+        let keys = test_keys();
+        let entries = test_trie(&keys);
+        let mut key = keys[2].clone();
+        key[31] ^= 1;
+
+        generate_witness(|builder, pw| {
+            verify_inclusion(builder, pw, &entries, &key, &entries[2].1)
+        });
+    }
+}
@@ -0,0 +1,374 @@
+//! Gadgets for arithmetic in a prime field other than the circuit's, such as the base and scalar
+//! fields of secp256k1.
+//!
+//! Elements are [`BigUintTarget`]s kept in canonical form, so they can be compared limb by limb:
+//! sums and differences are reduced by a conditional subtraction, products with
+//! [`CircuitBuilder::rem_biguint`], and quotients are witnessed and checked by a multiplication.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use num::{BigUint, Zero};
+
+use crate::field::extension::Extendable;
+use crate::field::types::{Field, PrimeField};
+use crate::gadgets::biguint::{BigUintTarget, WitnessBigUint, WitnessWriteBigUint};
+use crate::hash::hash_types::RichField;
+use crate::iop::generator::{GeneratedValues, SimpleGenerator};
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::{PartitionWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CommonCircuitData;
+use crate::util::ceil_div_usize;
+use crate::util::serialization::{Buffer, IoResult, Read, Write};
+
+/// An element of the field `FF`, in canonical form.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NonNativeTarget<FF: Field> {
+    pub(crate) value: BigUintTarget,
+    _phantom: PhantomData<FF>,
+}
+
+impl<FF: Field> NonNativeTarget<FF> {
+    /// Wraps an integer which the caller knows to be canonical, with range-checked limbs.
+    pub(crate) fn from_canonical_biguint(value: BigUintTarget) -> Self {
+        Self {
+            value,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the canonical representative of the element.
+    pub fn value(&self) -> &BigUintTarget {
+        &self.value
+    }
+}
+
+/// The number of 32-bit limbs of elements of `FF`.
+pub fn num_nonnative_limbs<FF: Field>() -> usize {
+    ceil_div_usize(FF::BITS, 32)
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds a new element of `FF`, whose limbs are range-checked and whose value is checked to be
+    /// canonical.
+    pub fn add_virtual_nonnative_target<FF: PrimeField>(&mut self) -> NonNativeTarget<FF> {
+        let value = self.add_virtual_biguint_target(num_nonnative_limbs::<FF>());
+        self.assert_canonical_nonnative::<FF>(&value);
+        NonNativeTarget {
+            value,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn constant_nonnative<FF: PrimeField>(&mut self, x: FF) -> NonNativeTarget<FF> {
+        let value = self.constant_biguint(&x.to_canonical_biguint());
+        let value = self.pad_biguint(&value, num_nonnative_limbs::<FF>());
+        NonNativeTarget {
+            value,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn zero_nonnative<FF: PrimeField>(&mut self) -> NonNativeTarget<FF> {
+        self.constant_nonnative(FF::ZERO)
+    }
+
+    /// Reduces an integer modulo the order of `FF`.
+    pub fn reduce_biguint<FF: PrimeField>(&mut self, x: &BigUintTarget) -> NonNativeTarget<FF> {
+        let modulus = self.constant_biguint(&FF::order());
+        let value = self.rem_biguint(x, &modulus);
+        let value = self.pad_biguint(&value, num_nonnative_limbs::<FF>());
+        NonNativeTarget {
+            value,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn connect_nonnative<FF: Field>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) {
+        self.connect_biguint(&x.value, &y.value);
+    }
+
+    pub fn is_equal_nonnative<FF: Field>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) -> BoolTarget {
+        self.is_equal_biguint(&x.value, &y.value)
+    }
+
+    /// Returns `x` if `b` is true, otherwise `y`.
+    pub fn select_nonnative<FF: Field>(
+        &mut self,
+        b: BoolTarget,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        NonNativeTarget {
+            value: self.select_biguint(b, &x.value, &y.value),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn add_nonnative<FF: PrimeField>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        // `x + y` is in `[0, 2p)`, so subtracting `p` once is enough, which is much cheaper than a
+        // division.
+        let num_limbs = num_nonnative_limbs::<FF>();
+        let sum = self.add_biguint(&x.value, &y.value);
+        let modulus = self.constant_biguint(&FF::order());
+        let modulus = self.pad_biguint(&modulus, sum.num_limbs());
+        let (diff, less_than_modulus) = self.sub_limbs(&sum.limbs, &modulus.limbs);
+        let value = self.select_biguint(less_than_modulus, &sum, &BigUintTarget { limbs: diff });
+        // The result is less than `p`, so its top limb is zero.
+        NonNativeTarget::from_canonical_biguint(BigUintTarget {
+            limbs: value.limbs[..num_limbs].to_vec(),
+        })
+    }
+
+    pub fn add_many_nonnative<FF: PrimeField>(
+        &mut self,
+        terms: &[NonNativeTarget<FF>],
+    ) -> NonNativeTarget<FF> {
+        let (first, rest) = terms.split_first().expect("No terms to add");
+        let mut sum = first.clone();
+        for term in rest {
+            sum = self.add_nonnative(&sum, term);
+        }
+        sum
+    }
+
+    pub fn neg_nonnative<FF: PrimeField>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        let zero = self.zero_nonnative();
+        self.sub_nonnative(&zero, x)
+    }
+
+    pub fn sub_nonnative<FF: PrimeField>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        // If `x < y`, the difference wrapped around to `x - y + 2^(32 n)`, and adding `p` wraps it
+        // back to `x - y + p`.
+        let (diff, borrow) = self.sub_limbs(&x.value.limbs, &y.value.limbs);
+        let modulus = self.constant_biguint(&FF::order());
+        let modulus = self.pad_biguint(&modulus, diff.len());
+        let correction = self.mul_biguint_by_bool(&modulus, borrow);
+        let (limbs, _) = self.add_limbs(&diff, &correction.limbs);
+        NonNativeTarget::from_canonical_biguint(BigUintTarget { limbs })
+    }
+
+    pub fn mul_nonnative<FF: PrimeField>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        let product = self.mul_biguint(&x.value, &y.value);
+        self.reduce_biguint(&product)
+    }
+
+    pub fn mul_const_nonnative<FF: PrimeField>(
+        &mut self,
+        c: FF,
+        x: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        let c = self.constant_biguint(&c.to_canonical_biguint());
+        let product = self.mul_biguint(&c, &x.value);
+        self.reduce_biguint(&product)
+    }
+
+    /// Returns `x^-1`. The constraints are unsatisfiable if `x` is zero.
+    pub fn inv_nonnative<FF: PrimeField>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        let one = self.constant_nonnative(FF::ONE);
+        self.div_nonnative(&one, x)
+    }
+
+    /// Returns `x / y`. The constraints are unsatisfiable if `y` is zero.
+    pub fn div_nonnative<FF: PrimeField>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+        y: &NonNativeTarget<FF>,
+    ) -> NonNativeTarget<FF> {
+        // `y` is canonical, so it is zero iff all of its limbs are, and a nonzero `y` has a unique
+        // quotient.
+        let zero = self.zero_nonnative();
+        let y_is_zero = self.is_equal_nonnative(y, &zero);
+        self.assert_zero(y_is_zero.target);
+
+        let div = self.add_virtual_nonnative_target::<FF>();
+        self.add_simple_generator(NonNativeDivisionGenerator {
+            x: x.value.clone(),
+            y: y.value.clone(),
+            div: div.value.clone(),
+            modulus: FF::order(),
+        });
+
+        let product = self.mul_nonnative(&div, y);
+        self.connect_nonnative(&product, x);
+        div
+    }
+
+    /// Returns the little-endian 4-bit digits of the canonical representative of `x`.
+    pub fn split_nonnative_to_4_bit_limbs<FF: Field>(
+        &mut self,
+        x: &NonNativeTarget<FF>,
+    ) -> Vec<Target> {
+        let mut digits = Vec::with_capacity(8 * x.value.num_limbs());
+        for &limb in &x.value.limbs {
+            let bits = self.split_le(limb, 32);
+            for chunk in bits.chunks(4) {
+                digits.push(self.le_sum(chunk.iter()));
+            }
+        }
+        digits
+    }
+
+    /// Asserts that `x` is less than the order of `FF`.
+    fn assert_canonical_nonnative<FF: PrimeField>(&mut self, x: &BigUintTarget) {
+        let modulus = self.constant_biguint(&FF::order());
+        let canonical = self.is_less_than_biguint(x, &modulus);
+        self.assert_one(canonical.target);
+    }
+}
+
+pub trait WitnessWriteNonNative<F: Field>: WitnessWrite<F> {
+    fn set_nonnative_target<FF: PrimeField>(&mut self, target: &NonNativeTarget<FF>, value: FF) {
+        self.set_biguint_target(&target.value, &value.to_canonical_biguint());
+    }
+}
+
+impl<F: Field, W: WitnessWrite<F> + ?Sized> WitnessWriteNonNative<F> for W {}
+
+#[derive(Debug, Default)]
+pub struct NonNativeDivisionGenerator {
+    x: BigUintTarget,
+    y: BigUintTarget,
+    div: BigUintTarget,
+    modulus: BigUint,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
+    for NonNativeDivisionGenerator
+{
+    fn id(&self) -> String {
+        "NonNativeDivisionGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        self.x.limbs.iter().chain(&self.y.limbs).copied().collect()
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let x = witness.get_biguint_target(&self.x);
+        let y = witness.get_biguint_target(&self.y);
+        assert!(!y.is_zero(), "Division by zero");
+        // The modulus is prime, so this is Fermat's little theorem.
+        let y_inv = y.modpow(&(&self.modulus - 2u32), &self.modulus);
+        let div = x * y_inv % &self.modulus;
+
+        out_buffer.set_biguint_target(&self.div, &div);
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target_vec(&self.x.limbs)?;
+        dst.write_target_vec(&self.y.limbs)?;
+        dst.write_target_vec(&self.div.limbs)?;
+        let modulus = self.modulus.to_u32_digits();
+        dst.write_usize(modulus.len())?;
+        for digit in modulus {
+            dst.write_u32(digit)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let x = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        let y = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        let div = BigUintTarget {
+            limbs: src.read_target_vec()?,
+        };
+        let num_digits = src.read_usize()?;
+        let digits = (0..num_digits)
+            .map(|_| src.read_u32())
+            .collect::<IoResult<Vec<_>>>()?;
+        Ok(Self {
+            x,
+            y,
+            div,
+            modulus: BigUint::new(digits),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::secp256k1_base::Secp256K1Base;
+    use crate::field::types::Sample;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type FF = Secp256K1Base;
+
+    #[test]
+    fn test_nonnative_arithmetic() -> Result<()> {
+        let (x, y) = (FF::rand(), FF::rand());
+
+        let config = CircuitConfig::standard_ecc_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let xt = builder.add_virtual_nonnative_target::<FF>();
+        let yt = builder.add_virtual_nonnative_target::<FF>();
+        pw.set_nonnative_target(&xt, x);
+        pw.set_nonnative_target(&yt, y);
+
+        let sum = builder.add_nonnative(&xt, &yt);
+        let diff = builder.sub_nonnative(&xt, &yt);
+        let neg = builder.neg_nonnative(&xt);
+        let product = builder.mul_nonnative(&xt, &yt);
+        let quotient = builder.div_nonnative(&xt, &yt);
+        let zero = builder.zero_nonnative::<FF>();
+        let neg_zero = builder.neg_nonnative(&zero);
+        for (target, value) in [
+            (sum, x + y),
+            (diff, x - y),
+            (neg, -x),
+            (product, x * y),
+            (quotient, x / y),
+            (neg_zero, FF::ZERO),
+        ] {
+            let value = builder.constant_nonnative(value);
+            builder.connect_nonnative(&target, &value);
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+}
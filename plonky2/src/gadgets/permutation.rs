@@ -0,0 +1,163 @@
+//! Permutation and sorting checks, the building blocks of memory arguments and deduplication.
+//!
+//! Permutations are checked with a grand product: `a` is a permutation of `b` if and only if
+//! `prod_i (gamma - a_i) = prod_i (gamma - b_i)` as polynomials in `gamma`, so it suffices to check
+//! the equality at a random point. The challenges are derived by hashing both lists in the
+//! circuit, so they are fixed by the values being checked, as with Fiat-Shamir. They live in the
+//! extension field, so a false claim passes with probability about `n / |F^D|`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::hash::poseidon::PoseidonHash;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::target::Target;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::log_floor;
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Asserts that `b` is a permutation of `a`.
+    pub fn assert_permutation(&mut self, a: &[Target], b: &[Target]) {
+        let a = a.iter().map(|&x| vec![x]).collect::<Vec<_>>();
+        let b = b.iter().map(|&x| vec![x]).collect::<Vec<_>>();
+        self.assert_permutation_of_tuples(&a, &b);
+    }
+
+    /// Asserts that the rows of `b` are a permutation of the rows of `a`, where rows are compared
+    /// as tuples, e.g. the `(address, timestamp, value)` entries of a memory trace.
+    pub fn assert_permutation_of_tuples(&mut self, a: &[Vec<Target>], b: &[Vec<Target>]) {
+        assert_eq!(a.len(), b.len(), "Lists of different lengths");
+        let width = a.first().map_or(0, Vec::len);
+        assert!(
+            a.iter().chain(b).all(|row| row.len() == width),
+            "Rows of different widths"
+        );
+        if a.is_empty() {
+            return;
+        }
+
+        let inputs = a.iter().chain(b).flatten().copied().collect();
+        let challenges = self.hash_n_to_m_no_pad::<PoseidonHash>(inputs, 2 * D);
+        let alpha = ExtensionTarget(challenges[..D].try_into().unwrap());
+        let gamma = ExtensionTarget(challenges[D..].try_into().unwrap());
+
+        let a_product = self.permutation_grand_product(a, alpha, gamma);
+        let b_product = self.permutation_grand_product(b, alpha, gamma);
+        self.connect_extension(a_product, b_product);
+    }
+
+    /// Asserts that `xs` is sorted in non-decreasing order. The entries must be known to be less
+    /// than `2^num_bits`, e.g. from a decomposition; the differences between neighbours are then
+    /// range-checked with [`CircuitBuilder::assume_range`].
+    pub fn assert_sorted(&mut self, xs: &[Target], num_bits: usize) {
+        // A decreasing pair has a difference of at least `p - 2^num_bits`, which must not fit.
+        assert!(
+            num_bits < log_floor(F::ORDER, 2),
+            "{} bits may overflow the field",
+            num_bits
+        );
+        for pair in xs.windows(2) {
+            let diff = self.sub(pair[1], pair[0]);
+            self.assume_range(diff, num_bits);
+        }
+    }
+
+    /// Returns `prod_i (gamma - sum_j alpha^j rows[i][j])`.
+    fn permutation_grand_product(
+        &mut self,
+        rows: &[Vec<Target>],
+        alpha: ExtensionTarget<D>,
+        gamma: ExtensionTarget<D>,
+    ) -> ExtensionTarget<D> {
+        let mut product = self.one_extension();
+        for row in rows {
+            let mut compressed = self.zero_extension();
+            for &x in row.iter().rev() {
+                let x = self.convert_to_ext(x);
+                compressed = self.mul_add_extension(compressed, alpha, x);
+            }
+            let factor = self.sub_extension(gamma, compressed);
+            product = self.mul_extension(product, factor);
+        }
+        product
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rand::rngs::OsRng;
+    use rand::seq::SliceRandom;
+
+    use super::*;
+    use crate::field::types::{Field, Sample};
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_permutation() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        // A memory trace of `(address, value)` pairs, sorted by address.
+        let mut rows = (0..50)
+            .map(|_| (rand::random::<u16>() as u64, F::rand()))
+            .collect::<Vec<_>>();
+        let mut sorted = rows.clone();
+        sorted.sort_by_key(|&(address, _)| address);
+        rows.shuffle(&mut OsRng);
+
+        let mut add_rows = |rows: &[(u64, F)]| {
+            rows.iter()
+                .map(|&(address, value)| {
+                    let row = builder.add_virtual_targets(2);
+                    pw.set_target(row[0], F::from_canonical_u64(address));
+                    pw.set_target(row[1], value);
+                    row
+                })
+                .collect::<Vec<_>>()
+        };
+        let rows = add_rows(&rows);
+        let sorted = add_rows(&sorted);
+
+        builder.assert_permutation_of_tuples(&rows, &sorted);
+        let addresses = sorted.iter().map(|row| row[0]).collect::<Vec<_>>();
+        builder.assert_sorted(&addresses, 16);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_not_permutation() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        // The lists have the same sum, but are not permutations of each other.
+        let [a, b] = [[1, 5, 6], [2, 3, 7]].map(|values| {
+            values
+                .map(|value| {
+                    let target = builder.add_virtual_target();
+                    pw.set_target(target, F::from_canonical_u64(value));
+                    target
+                })
+                .to_vec()
+        });
+        builder.assert_permutation(&a, &b);
+
+        let data = builder.mock_build::<C>();
+        data.generate_witness(pw);
+    }
+}
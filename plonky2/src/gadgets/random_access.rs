@@ -38,6 +38,35 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         claimed_element
     }
 
+    /// Like `random_access`, but for a vector of any length, which may be too long for a single
+    /// `RandomAccessGate`. The vector is padded with zeros up to a power of two, so indices past
+    /// its end read zero, up to that power of two.
+    pub fn random_access_padded(&mut self, access_index: Target, v: &[Target]) -> Target {
+        let zero = self.zero();
+        let mut v = v.to_vec();
+        v.resize(v.len().next_power_of_two(), zero);
+        let bits = log2_strict(v.len());
+
+        // The largest list that fits in a single gate.
+        let fits_in_gate = |bits: usize| {
+            let vec_size = 1 << bits;
+            2 + vec_size <= self.config.num_routed_wires
+                && 2 + vec_size + bits <= self.config.num_wires
+        };
+        let max_gate_bits = (1..=bits).take_while(|&b| fits_in_gate(b)).count();
+        if bits <= max_gate_bits {
+            return self.random_access(access_index, v);
+        }
+
+        // Select within each chunk by the low bits of the index, then among the chunks.
+        let (low, high) = self.split_low_high(access_index, max_gate_bits, bits);
+        let chunks = v
+            .chunks(1 << max_gate_bits)
+            .map(|chunk| self.random_access(low, chunk.to_vec()))
+            .collect::<Vec<_>>();
+        self.random_access_padded(high, &chunks)
+    }
+
     /// Like `random_access`, but with `ExtensionTarget`s rather than simple `Target`s.
     pub fn random_access_extension(
         &mut self,
@@ -105,7 +134,7 @@ mod tests {
 
     use super::*;
     use crate::field::types::{Field, Sample};
-    use crate::iop::witness::PartialWitness;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_data::CircuitConfig;
     use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use crate::plonk::verifier::verify;
@@ -135,6 +164,32 @@ mod tests {
         verify(proof, &data.verifier_only, &data.common)
     }
 
+    #[test]
+    fn test_random_access_padded() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let len = 300;
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let vec = F::rand_vec(len);
+        let v: Vec<_> = vec.iter().map(|&x| builder.constant(x)).collect();
+
+        for i in [0, 1, 63, 64, 200, len - 1, 511] {
+            let it = builder.add_virtual_target();
+            pw.set_target(it, F::from_canonical_usize(i));
+            let elem = builder.constant(vec.get(i).copied().unwrap_or(F::ZERO));
+            let res = builder.random_access_padded(it, &v);
+            builder.connect(elem, res);
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
     #[test]
     fn test_random_access() -> Result<()> {
         for len_log in 1..3 {
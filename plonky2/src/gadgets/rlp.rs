@@ -0,0 +1,339 @@
+//! Gadgets for decoding RLP, the encoding of Ethereum headers, transactions and trie nodes.
+//!
+//! Encodings are given as slices of byte targets, which the caller range-checks to 8 bits, and
+//! may be padded with zeros past their actual length. Items are located by their offset in the
+//! encoding, which is read with [`CircuitBuilder::random_access_padded`], so each byte read costs
+//! about one gate per 64 bytes of encoding. Decoding enforces the canonical encoding, so every
+//! value has a unique encoding: single bytes below `0x80` are encoded as themselves, and long
+//! forms are only used for payloads of more than 55 bytes, with lengths without leading zeros.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::gadgets::biguint::BigUintTarget;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::log2_ceil;
+
+/// The maximum size of the length of a long form, so payloads are limited to `2^24 - 1` bytes.
+pub const MAX_RLP_LEN_OF_LEN: usize = 3;
+
+/// A decoded RLP item, whose payload is `data[offset..offset + len]` in its encoding `data`.
+#[derive(Copy, Clone, Debug)]
+pub struct RlpItemTarget {
+    pub offset: Target,
+    pub len: Target,
+    pub is_list: BoolTarget,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Decodes the header of the item starting at `pos` in `data`.
+    pub fn decode_rlp_item(&mut self, data: &[Target], pos: Target) -> RlpItemTarget {
+        let prefix = self.rlp_byte(data, pos, 0);
+        let bits = self.split_le(prefix, 8);
+        let is_list = self.and(bits[7], bits[6]);
+        let is_single_byte = self.not(bits[7]);
+        // The prefix minus `0x80` for strings, or minus `0xc0` for lists.
+        let short_len = self.le_sum(bits[..6].iter());
+        // Long forms have prefixes from `0xb8` for strings, or from `0xf8` for lists.
+        let long_bits = self.and(bits[5], bits[4]);
+        let long_bits = self.and(long_bits, bits[3]);
+        let is_long = self.and(bits[7], long_bits);
+        let not_long = self.not(is_long);
+        let is_short = self.and(bits[7], not_long);
+
+        // For long forms, `short_len - 55` is the length of the length, which has to be at most
+        // `MAX_RLP_LEN_OF_LEN = 3`, so bit 2 is unset and `bits[0] + 2 bits[1]` is 0, 1 or 2.
+        let too_long = self.mul(is_long.target, bits[2].target);
+        self.assert_zero(too_long);
+        let len_of_len_is_3 = self.and(bits[0], bits[1]);
+        let len_of_len_is_3 = self.and(is_long, len_of_len_is_3);
+        self.assert_zero(len_of_len_is_3.target);
+        let len_of_len = self.mul_const_add(F::TWO, bits[1].target, bits[0].target);
+        let len_of_len = self.add_const(len_of_len, F::ONE);
+
+        let b1 = self.rlp_byte(data, pos, 1);
+        let b2 = self.rlp_byte(data, pos, 2);
+        let b3 = self.rlp_byte(data, pos, 3);
+        let b1_b2 = self.mul_const_add(F::from_canonical_u32(1 << 8), b1, b2);
+        let b1_b2_b3 = self.mul_const_add(F::from_canonical_u32(1 << 8), b1_b2, b3);
+        let long_len = self.select(bits[0], b1_b2, b1);
+        let long_len = self.select(bits[1], b1_b2_b3, long_len);
+
+        // Long lengths have no leading zeros, and exceed 55.
+        let zero = self.zero();
+        let b1_is_zero = self.is_equal(b1, zero);
+        let leading_zero = self.and(is_long, b1_is_zero);
+        self.assert_zero(leading_zero.target);
+        let long_len_excess = self.add_const(long_len, -F::from_canonical_u32(56));
+        let long_len_excess = self.mul(is_long.target, long_len_excess);
+        self.range_check(long_len_excess, 8 * MAX_RLP_LEN_OF_LEN);
+
+        // A string of a single byte below `0x80` is encoded as that byte.
+        let b1_bits = self.split_le(b1, 8);
+        let one = self.one();
+        let short_len_is_one = self.is_equal(short_len, one);
+        let single_byte_string = self.and(is_short, short_len_is_one);
+        let not_list = self.not(is_list);
+        let single_byte_string = self.and(single_byte_string, not_list);
+        let non_canonical = self.and(single_byte_string, b1_bits[7]);
+        let non_canonical = self.sub(single_byte_string.target, non_canonical.target);
+        self.assert_zero(non_canonical);
+
+        let short_offset = self.add_const(pos, F::ONE);
+        let long_offset = self.add(short_offset, len_of_len);
+        let offset = self.select(is_long, long_offset, short_offset);
+        let offset = self.select(is_single_byte, pos, offset);
+        let len = self.select(is_long, long_len, short_len);
+        let len = self.select(is_single_byte, one, len);
+
+        RlpItemTarget {
+            offset,
+            len,
+            is_list,
+        }
+    }
+
+    /// Decodes an encoding of `data_len` bytes as a list of exactly `num_items` items.
+    pub fn decode_rlp_list(
+        &mut self,
+        data: &[Target],
+        data_len: Target,
+        num_items: usize,
+    ) -> Vec<RlpItemTarget> {
+        let zero = self.zero();
+        let list = self.decode_rlp_item(data, zero);
+        let end = self.rlp_item_end(&list);
+        self.connect(end, data_len);
+        self.decode_rlp_list_items(data, &list, num_items)
+    }
+
+    /// Decodes the payload of `list`, such as a nested list, as exactly `num_items` items.
+    pub fn decode_rlp_list_items(
+        &mut self,
+        data: &[Target],
+        list: &RlpItemTarget,
+        num_items: usize,
+    ) -> Vec<RlpItemTarget> {
+        self.assert_one(list.is_list.target);
+        let mut pos = list.offset;
+        let items = (0..num_items)
+            .map(|_| {
+                let item = self.decode_rlp_item(data, pos);
+                pos = self.rlp_item_end(&item);
+                item
+            })
+            .collect();
+        // Each item takes at least a byte, so the items can't overshoot the end and come back.
+        let end = self.rlp_item_end(list);
+        self.connect(pos, end);
+        items
+    }
+
+    /// Returns the payload of the string `item`, padded with zeros to `max_len` bytes, asserting
+    /// that it has at most `max_len` bytes.
+    pub fn rlp_item_bytes(
+        &mut self,
+        data: &[Target],
+        item: &RlpItemTarget,
+        max_len: usize,
+    ) -> Vec<Target> {
+        self.assert_zero(item.is_list.target);
+        self.assert_rlp_len_at_most(item.len, max_len);
+        let in_payload = self.rlp_prefix_flags(item.len, max_len);
+        (0..max_len)
+            .map(|i| {
+                let byte = self.rlp_byte(data, item.offset, i);
+                self.mul(in_payload[i].target, byte)
+            })
+            .collect()
+    }
+
+    /// Returns the payload of the string `item`, asserting that it has exactly `len` bytes, like
+    /// a hash or an address.
+    pub fn rlp_item_fixed_bytes(
+        &mut self,
+        data: &[Target],
+        item: &RlpItemTarget,
+        len: usize,
+    ) -> Vec<Target> {
+        self.assert_zero(item.is_list.target);
+        let expected_len = self.constant(F::from_canonical_usize(len));
+        self.connect(item.len, expected_len);
+        (0..len)
+            .map(|i| self.rlp_byte(data, item.offset, i))
+            .collect()
+    }
+
+    /// Returns the big-endian integer encoded by the string `item`, of at most `max_len` bytes,
+    /// asserting that it has no leading zeros, as in Ethereum's encoding of integers.
+    pub fn rlp_item_to_biguint(
+        &mut self,
+        data: &[Target],
+        item: &RlpItemTarget,
+        max_len: usize,
+    ) -> BigUintTarget {
+        let bytes = self.rlp_item_bytes(data, item, max_len);
+        let zero = self.zero();
+        let empty = self.is_equal(item.len, zero);
+        let first_is_zero = self.is_equal(bytes[0], zero);
+        let leading_zero = self.sub(first_is_zero.target, empty.target);
+        self.assert_zero(leading_zero);
+
+        // The least significant byte is `bytes[len - 1]`, so read the bytes in reverse.
+        let in_payload = self.rlp_prefix_flags(item.len, max_len);
+        let last = self.add_const(item.offset, -F::ONE);
+        let last = self.add(last, item.len);
+        let le_bytes = (0..max_len)
+            .map(|i| {
+                let index = self.add_const(last, -F::from_canonical_usize(i));
+                // Past the payload, the index may underflow, so read from the start instead.
+                let index = self.select(in_payload[i], index, item.offset);
+                let byte = self.random_access_padded(index, data);
+                self.mul(in_payload[i].target, byte)
+            })
+            .collect::<Vec<_>>();
+        let limbs = le_bytes
+            .chunks(4)
+            .map(|chunk| {
+                chunk.iter().rev().fold(zero, |acc, &byte| {
+                    self.mul_const_add(F::from_canonical_u32(1 << 8), acc, byte)
+                })
+            })
+            .collect();
+        BigUintTarget { limbs }
+    }
+
+    /// Returns the position right after the payload of `item`.
+    pub fn rlp_item_end(&mut self, item: &RlpItemTarget) -> Target {
+        self.add(item.offset, item.len)
+    }
+
+    /// Returns the byte at `pos + i`, or zero past the end of the encoding.
+    fn rlp_byte(&mut self, data: &[Target], pos: Target, i: usize) -> Target {
+        // Headers read a few bytes ahead, so pad the encoding beyond them.
+        let zero = self.zero();
+        let mut data = data.to_vec();
+        data.extend(vec![zero; MAX_RLP_LEN_OF_LEN + 1]);
+        let index = self.add_const(pos, F::from_canonical_usize(i));
+        self.random_access_padded(index, &data)
+    }
+
+    /// Returns flags for whether each of `0..max_len` is less than `len`.
+    pub(crate) fn rlp_prefix_flags(&mut self, len: Target, max_len: usize) -> Vec<BoolTarget> {
+        let mut in_prefix = self._true();
+        (0..max_len)
+            .map(|i| {
+                let i = self.constant(F::from_canonical_usize(i));
+                let at_end = self.is_equal(len, i);
+                in_prefix = BoolTarget::new_unsafe(self.sub(in_prefix.target, at_end.target));
+                in_prefix
+            })
+            .collect()
+    }
+
+    fn assert_rlp_len_at_most(&mut self, len: Target, max_len: usize) {
+        let slack = self.constant(F::from_canonical_usize(max_len));
+        let slack = self.sub(slack, len);
+        self.range_check(slack, log2_ceil(max_len + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use num::BigUint;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Proves the decoding of `encoding`, padded to `padded_len` bytes, as a list of items whose
+    /// payloads are checked by `check`.
+    fn prove_rlp_list(
+        encoding: &[u8],
+        padded_len: usize,
+        num_items: usize,
+        check: impl FnOnce(&mut CircuitBuilder<F, D>, &[Target], &[RlpItemTarget]),
+    ) -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let data = builder.add_virtual_targets(padded_len);
+        for (i, &byte) in data.iter().enumerate() {
+            builder.range_check(byte, 8);
+            let value = encoding.get(i).copied().unwrap_or(0);
+            pw.set_target(byte, F::from_canonical_u8(value));
+        }
+        let data_len = builder.constant(F::from_canonical_usize(encoding.len()));
+        let items = builder.decode_rlp_list(&data, data_len, num_items);
+        check(&mut builder, &data, &items);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    fn connect_bytes(builder: &mut CircuitBuilder<F, D>, targets: &[Target], bytes: &[u8]) {
+        for (&target, &byte) in targets.iter().zip(bytes) {
+            let byte = builder.constant(F::from_canonical_u8(byte));
+            builder.connect(target, byte);
+        }
+    }
+
+    #[test]
+    fn test_decode_rlp_list() -> Result<()> {
+        // ["dog", 1024, "", ["cat"], "a" * 60, 0x0f]
+        let mut encoding = vec![0xf8, 76, 0x83, b'd', b'o', b'g', 0x82, 0x04, 0x00, 0x80];
+        encoding.extend([0xc4, 0x83, b'c', b'a', b't', 0xb8, 60]);
+        encoding.extend([b'a'; 60]);
+        encoding.push(0x0f);
+
+        prove_rlp_list(&encoding, 96, 6, |builder, data, items| {
+            let dog = builder.rlp_item_bytes(data, &items[0], 4);
+            connect_bytes(builder, &dog, b"dog\0");
+
+            let n = builder.rlp_item_to_biguint(data, &items[1], 8);
+            let expected = builder.constant_biguint(&BigUint::from(1024u32));
+            builder.connect_biguint(&n, &expected);
+
+            let empty = builder.rlp_item_to_biguint(data, &items[2], 8);
+            let zero = builder.zero_biguint();
+            builder.connect_biguint(&empty, &zero);
+
+            let nested = builder.decode_rlp_list_items(data, &items[3], 1);
+            let cat = builder.rlp_item_fixed_bytes(data, &nested[0], 3);
+            connect_bytes(builder, &cat, b"cat");
+
+            let a = builder.rlp_item_fixed_bytes(data, &items[4], 60);
+            connect_bytes(builder, &a, &[b'a'; 60]);
+
+            let single = builder.rlp_item_bytes(data, &items[5], 1);
+            connect_bytes(builder, &single, &[0x0f]);
+        })
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_non_canonical_single_byte() {
+        // [0x05], with the byte wrapped in a string header.
+        prove_rlp_list(&[0xc2, 0x81, 0x05], 8, 1, |_, _, _| {}).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_non_canonical_long_string() {
+        // ["abc"], with the string's length in the long form.
+        prove_rlp_list(&[0xc5, 0xb8, 3, b'a', b'b', b'c'], 8, 1, |_, _, _| {}).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
+use alloc::vec::Vec;
+
 use crate::field::extension::Extendable;
-use crate::hash::hash_types::RichField;
+use crate::hash::hash_types::{HashOutTarget, RichField};
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::target::{BoolTarget, Target};
 use crate::plonk::circuit_builder::CircuitBuilder;
@@ -34,13 +36,50 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let tmp = self.mul_sub(b.target, y, y);
         self.mul_sub(b.target, x, tmp)
     }
+
+    /// Selects `xs[i]` where `flags[i]` is true. At most one flag may be true, and if none is, this
+    /// returns zero.
+    pub fn select_one_hot(&mut self, flags: &[BoolTarget], xs: &[Target]) -> Target {
+        assert_eq!(flags.len(), xs.len());
+        let zero = self.zero();
+        flags
+            .iter()
+            .zip(xs)
+            .fold(zero, |acc, (flag, &x)| self.mul_add(flag.target, x, acc))
+    }
+
+    /// See `select_one_hot`.
+    pub fn select_one_hot_ext(
+        &mut self,
+        flags: &[BoolTarget],
+        xs: &[ExtensionTarget<D>],
+    ) -> ExtensionTarget<D> {
+        assert_eq!(flags.len(), xs.len());
+        let zero = self.zero_extension();
+        flags.iter().zip(xs).fold(zero, |acc, (flag, &x)| {
+            self.scalar_mul_add_extension(flag.target, x, acc)
+        })
+    }
+
+    /// See `select_one_hot`.
+    pub fn select_one_hot_hash(
+        &mut self,
+        flags: &[BoolTarget],
+        xs: &[HashOutTarget],
+    ) -> HashOutTarget {
+        let elements = core::array::from_fn(|i| {
+            let xs = xs.iter().map(|x| x.elements[i]).collect::<Vec<_>>();
+            self.select_one_hot(flags, &xs)
+        });
+        HashOutTarget { elements }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
 
-    use crate::field::types::Sample;
+    use crate::field::types::{Field, Sample};
     use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::CircuitConfig;
@@ -77,4 +116,26 @@ mod tests {
 
         verify(proof, &data.verifier_only, &data.common)
     }
+
+    #[test]
+    fn test_select_one_hot() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+        let pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let xs = [3, 5, 7].map(|x| builder.constant(F::from_canonical_u64(x)));
+        let flags = [builder._false(), builder._true(), builder._false()];
+        let selected = builder.select_one_hot(&flags, &xs);
+        builder.connect(selected, xs[1]);
+        let none_selected = builder.select_one_hot(&[flags[0], flags[2]], &[xs[0], xs[2]]);
+        builder.assert_zero(none_selected);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+
+        verify(proof, &data.verifier_only, &data.common)
+    }
 }
@@ -0,0 +1,552 @@
+//! Gadgets for unsigned integers of 64 and 128 bits, represented as little-endian 32-bit limbs.
+//!
+//! Every limb of a [`UintTarget`] is range-checked to 32 bits when the target is created, and all
+//! intermediate values are kept below the Goldilocks order, so the arithmetic is exact: products
+//! of two limbs plus a limb are split into canonical 32-bit halves before anything else is added.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::field::types::{Field, PrimeField64};
+use crate::gadgets::biguint::BigUintTarget;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::{Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+
+/// An unsigned integer of `N` 32-bit limbs, in little-endian order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UintTarget<const N: usize> {
+    /// The limbs of the integer, each range-checked to 32 bits.
+    pub(crate) limbs: [Target; N],
+}
+
+/// A 64-bit unsigned integer.
+pub type U64Target = UintTarget<2>;
+
+/// A 128-bit unsigned integer.
+pub type U128Target = UintTarget<4>;
+
+impl<const N: usize> UintTarget<N> {
+    /// The number of bits of the integer.
+    pub const BITS: usize = 32 * N;
+
+    /// Returns the little-endian 32-bit limbs of the integer.
+    pub fn limbs(&self) -> [Target; N] {
+        self.limbs
+    }
+}
+
+/// The order of the Goldilocks field, the only field in which these gadgets are sound.
+const GOLDILOCKS_ORDER: u64 = 0xFFFF_FFFF_0000_0001;
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds a new integer, whose limbs are range-checked.
+    pub fn add_virtual_uint_target<const N: usize>(&mut self) -> UintTarget<N> {
+        let limbs = self.add_virtual_target_arr::<N>();
+        self.uint_from_limbs(limbs)
+    }
+
+    pub fn add_virtual_u64_target(&mut self) -> U64Target {
+        self.add_virtual_uint_target()
+    }
+
+    pub fn add_virtual_u128_target(&mut self) -> U128Target {
+        self.add_virtual_uint_target()
+    }
+
+    /// Interprets little-endian 32-bit limbs as an integer, range-checking each limb.
+    pub fn uint_from_limbs<const N: usize>(&mut self, limbs: [Target; N]) -> UintTarget<N> {
+        for limb in limbs {
+            self.range_check(limb, 32);
+        }
+        UintTarget { limbs }
+    }
+
+    /// Returns a constant integer with the given little-endian 32-bit limbs.
+    pub fn constant_uint<const N: usize>(&mut self, limbs: [u32; N]) -> UintTarget<N> {
+        UintTarget {
+            limbs: limbs.map(|limb| self.constant(F::from_canonical_u32(limb))),
+        }
+    }
+
+    pub fn constant_u64(&mut self, x: u64) -> U64Target {
+        self.constant_uint([x as u32, (x >> 32) as u32])
+    }
+
+    pub fn constant_u128(&mut self, x: u128) -> U128Target {
+        self.constant_uint(core::array::from_fn(|i| (x >> (32 * i)) as u32))
+    }
+
+    pub fn connect_uint<const N: usize>(&mut self, x: UintTarget<N>, y: UintTarget<N>) {
+        for (x, y) in x.limbs.into_iter().zip(y.limbs) {
+            self.connect(x, y);
+        }
+    }
+
+    pub fn is_equal_uint<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> BoolTarget {
+        let mut equal = self._true();
+        for (x, y) in x.limbs.into_iter().zip(y.limbs) {
+            let limb_equal = self.is_equal(x, y);
+            equal = self.and(equal, limb_equal);
+        }
+        equal
+    }
+
+    /// Returns `x` if `b` is true, otherwise `y`.
+    pub fn select_uint<const N: usize>(
+        &mut self,
+        b: BoolTarget,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        let mut limbs = x.limbs;
+        for (limb, y) in limbs.iter_mut().zip(y.limbs) {
+            *limb = self.select(b, *limb, y);
+        }
+        UintTarget { limbs }
+    }
+
+    /// Returns `(x + y) mod 2^BITS` and whether the addition overflowed.
+    pub fn add_uint_with_carry<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> (UintTarget<N>, BoolTarget) {
+        let (limbs, carry) = self.add_limbs(&x.limbs, &y.limbs);
+        let limbs = to_limbs(limbs);
+        (UintTarget { limbs }, carry)
+    }
+
+    /// Returns `(x + y) mod 2^BITS`.
+    pub fn add_uint_wrapping<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        self.add_uint_with_carry(x, y).0
+    }
+
+    /// Returns `x + y`, asserting that it doesn't overflow.
+    pub fn add_uint_checked<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        let (sum, carry) = self.add_uint_with_carry(x, y);
+        self.assert_zero(carry.target);
+        sum
+    }
+
+    /// Returns `(x - y) mod 2^BITS` and whether the subtraction underflowed, i.e. whether `x < y`.
+    pub fn sub_uint_with_borrow<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> (UintTarget<N>, BoolTarget) {
+        let (limbs, borrow) = self.sub_limbs(&x.limbs, &y.limbs);
+        let limbs = to_limbs(limbs);
+        (UintTarget { limbs }, borrow)
+    }
+
+    /// Returns `(x - y) mod 2^BITS`.
+    pub fn sub_uint_wrapping<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        self.sub_uint_with_borrow(x, y).0
+    }
+
+    /// Returns `x - y`, asserting that `x >= y`.
+    pub fn sub_uint_checked<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        let (diff, borrow) = self.sub_uint_with_borrow(x, y);
+        self.assert_zero(borrow.target);
+        diff
+    }
+
+    /// Returns `(x * y) mod 2^BITS`.
+    pub fn mul_uint_wrapping<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        let product = self.mul_limbs(&x.limbs, &y.limbs, N);
+        let limbs = to_limbs(product);
+        UintTarget { limbs }
+    }
+
+    /// Returns `x * y`, asserting that it doesn't overflow.
+    pub fn mul_uint_checked<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> UintTarget<N> {
+        let mut product = self.mul_limbs(&x.limbs, &y.limbs, 2 * N);
+        for high_limb in product.drain(N..) {
+            self.assert_zero(high_limb);
+        }
+        let limbs = to_limbs(product);
+        UintTarget { limbs }
+    }
+
+    /// Returns `(x / y, x % y)`. The constraints are unsatisfiable if `y` is zero.
+    pub fn div_rem_uint<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> (UintTarget<N>, UintTarget<N>) {
+        let x = BigUintTarget {
+            limbs: x.limbs.to_vec(),
+        };
+        let y = BigUintTarget {
+            limbs: y.limbs.to_vec(),
+        };
+        // The divisor's top limbs may be zero, so the quotient may take all `N` limbs.
+        let (div, rem) = self.div_rem_biguint_sized(&x, &y, N);
+        (
+            UintTarget {
+                limbs: to_limbs(div.limbs),
+            },
+            UintTarget {
+                limbs: to_limbs(rem.limbs),
+            },
+        )
+    }
+
+    /// Returns `x < y`.
+    pub fn is_less_than_uint<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> BoolTarget {
+        self.sub_uint_with_borrow(x, y).1
+    }
+
+    /// Returns `x <= y`.
+    pub fn is_less_or_equal_uint<const N: usize>(
+        &mut self,
+        x: UintTarget<N>,
+        y: UintTarget<N>,
+    ) -> BoolTarget {
+        let greater = self.is_less_than_uint(y, x);
+        self.not(greater)
+    }
+
+    /// Returns `(x << shift) mod 2^BITS`, for a constant `shift`.
+    pub fn shl_uint<const N: usize>(&mut self, x: UintTarget<N>, shift: usize) -> UintTarget<N> {
+        let (limb_shift, bit_shift) = (shift / 32, shift % 32);
+        let zero = self.zero();
+        let mut limbs = vec![zero; N];
+        if bit_shift == 0 {
+            if limb_shift < N {
+                limbs[limb_shift..].copy_from_slice(&x.limbs[..N - limb_shift]);
+            }
+        } else {
+            // Each limb is split into its low `32 - bit_shift` bits, which stay in the same limb,
+            // and its high `bit_shift` bits, which move to the next limb.
+            let pow = self.constant(F::from_canonical_u64(1 << bit_shift));
+            let mut carry = zero;
+            for i in limb_shift..N {
+                let (low, high) = self.split_low_high(x.limbs[i - limb_shift], 32 - bit_shift, 32);
+                limbs[i] = self.mul_add(low, pow, carry);
+                carry = high;
+            }
+        }
+        let limbs = to_limbs(limbs);
+        UintTarget { limbs }
+    }
+
+    /// Returns `x >> shift`, for a constant `shift`.
+    pub fn shr_uint<const N: usize>(&mut self, x: UintTarget<N>, shift: usize) -> UintTarget<N> {
+        let (limb_shift, bit_shift) = (shift / 32, shift % 32);
+        let zero = self.zero();
+        let mut limbs = vec![zero; N];
+        if bit_shift == 0 {
+            if limb_shift < N {
+                limbs[..N - limb_shift].copy_from_slice(&x.limbs[limb_shift..]);
+            }
+        } else {
+            // Each limb is split into its low `bit_shift` bits, which move to the previous limb,
+            // and its high `32 - bit_shift` bits, which stay in the same limb.
+            let pow = self.constant(F::from_canonical_u64(1 << (32 - bit_shift)));
+            let mut carry = zero;
+            for i in (limb_shift..N).rev() {
+                let (low, high) = self.split_low_high(x.limbs[i], bit_shift, 32);
+                limbs[i - limb_shift] = self.mul_add(carry, pow, high);
+                carry = low;
+            }
+        }
+        let limbs = to_limbs(limbs);
+        UintTarget { limbs }
+    }
+
+    /// Adds two little-endian lists of 32-bit limbs of the same length, returning the limbs of the
+    /// sum modulo `2^(32 * len)` and the carry out.
+    pub(crate) fn add_limbs(&mut self, x: &[Target], y: &[Target]) -> (Vec<Target>, BoolTarget) {
+        assert_eq!(x.len(), y.len());
+        let mut carry = self.zero();
+        let mut limbs = Vec::with_capacity(x.len());
+        for (&x, &y) in x.iter().zip(y) {
+            let sum = self.add_many([x, y, carry]);
+            let (limb, limb_carry) = self.split_low_high(sum, 32, 33);
+            limbs.push(limb);
+            carry = limb_carry;
+        }
+        // The carry was range-checked to a single bit by `split_low_high`.
+        (limbs, BoolTarget::new_unsafe(carry))
+    }
+
+    /// Subtracts two little-endian lists of 32-bit limbs of the same length, returning the limbs
+    /// of the difference modulo `2^(32 * len)` and the borrow out, i.e. whether `x < y`.
+    pub(crate) fn sub_limbs(&mut self, x: &[Target], y: &[Target]) -> (Vec<Target>, BoolTarget) {
+        assert_eq!(x.len(), y.len());
+        let mut borrow = self._false();
+        let mut limbs = Vec::with_capacity(x.len());
+        for (&x, &y) in x.iter().zip(y) {
+            // `x - y - borrow + 2^32` is in `[0, 2^33)`, and its high bit is set iff there's no
+            // borrow.
+            let diff = self.sub(x, y);
+            let diff = self.sub(diff, borrow.target);
+            let diff = self.add_const(diff, F::from_canonical_u64(1 << 32));
+            let (limb, no_borrow) = self.split_low_high(diff, 32, 33);
+            limbs.push(limb);
+            borrow = self.not(BoolTarget::new_unsafe(no_borrow));
+        }
+        (limbs, borrow)
+    }
+
+    /// Returns the `num_limbs` low limbs of `x * y`, by schoolbook multiplication.
+    pub(crate) fn mul_limbs(
+        &mut self,
+        x: &[Target],
+        y: &[Target],
+        num_limbs: usize,
+    ) -> Vec<Target> {
+        assert_eq!(
+            F::ORDER,
+            GOLDILOCKS_ORDER,
+            "Limb products only fit in the Goldilocks field"
+        );
+        let zero = self.zero();
+        let mut product = vec![zero; num_limbs];
+        for (i, &x) in x.iter().enumerate() {
+            let mut carry = zero;
+            for (j, &y) in y.iter().enumerate() {
+                if i + j >= num_limbs {
+                    break;
+                }
+                // `x * y + product[i + j] + carry < 2^64` may exceed the field order, so the
+                // carry is added after splitting `x * y + product[i + j] <= 2^64 - 2^32`.
+                let partial = self.mul_add(x, y, product[i + j]);
+                let (low, high) = self.split_u64_canonical(partial);
+                let sum = self.add(low, carry);
+                let (limb, sum_carry) = self.split_low_high(sum, 32, 33);
+                product[i + j] = limb;
+                // This is the high limb of `x * y + product[i + j] + carry`, so it fits in 32
+                // bits without a range check.
+                carry = self.add(high, sum_carry);
+            }
+            if i + y.len() < num_limbs {
+                product[i + y.len()] = carry;
+            }
+        }
+        product
+    }
+
+    /// Returns 32-bit limbs `(low, high)` such that `x = low + 2^32 * high` as integers.
+    pub(crate) fn split_u64_canonical(&mut self, x: Target) -> (Target, Target) {
+        let (low, high) = self.split_low_high(x, 32, 64);
+        // For `x < 2^32 - 1`, `x + p` has 32-bit limbs too, with `high = 2^32 - 1` and `low > 0`.
+        // A canonical decomposition with `high = 2^32 - 1` must have `low = 0`.
+        let max = self.constant(F::from_canonical_u32(u32::MAX));
+        let high_is_max = self.is_equal(high, max);
+        let non_canonical = self.mul(high_is_max.target, low);
+        self.assert_zero(non_canonical);
+        (low, high)
+    }
+}
+
+fn to_limbs<const N: usize>(limbs: Vec<Target>) -> [Target; N] {
+    limbs.try_into().expect("Wrong number of limbs")
+}
+
+pub trait WitnessUint<F: PrimeField64>: Witness<F> {
+    fn get_uint_target<const N: usize>(&self, target: UintTarget<N>) -> [u32; N] {
+        target
+            .limbs
+            .map(|limb| self.get_target(limb).to_canonical_u64() as u32)
+    }
+
+    fn get_u64_target(&self, target: U64Target) -> u64 {
+        let [lo, hi] = self.get_uint_target(target);
+        u64::from(lo) | (u64::from(hi) << 32)
+    }
+
+    fn get_u128_target(&self, target: U128Target) -> u128 {
+        self.get_uint_target(target)
+            .into_iter()
+            .rev()
+            .fold(0, |acc, limb| (acc << 32) | u128::from(limb))
+    }
+}
+
+impl<F: PrimeField64, W: Witness<F>> WitnessUint<F> for W {}
+
+pub trait WitnessWriteUint<F: Field>: WitnessWrite<F> {
+    fn set_uint_target<const N: usize>(&mut self, target: UintTarget<N>, limbs: [u32; N]) {
+        for (t, limb) in target.limbs.into_iter().zip(limbs) {
+            self.set_target(t, F::from_canonical_u32(limb));
+        }
+    }
+
+    fn set_u64_target(&mut self, target: U64Target, value: u64) {
+        self.set_uint_target(target, [value as u32, (value >> 32) as u32]);
+    }
+
+    fn set_u128_target(&mut self, target: U128Target, value: u128) {
+        self.set_uint_target(target, core::array::from_fn(|i| (value >> (32 * i)) as u32));
+    }
+}
+
+impl<F: Field, W: WitnessWrite<F>> WitnessWriteUint<F> for W {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    use super::*;
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::verifier::verify;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_u64_arithmetic() -> Result<()> {
+        let mut rng = OsRng;
+        // Include values whose limb products approach the field order.
+        for (x, y) in [
+            (rng.gen::<u64>(), rng.gen::<u64>()),
+            (u64::MAX, u64::MAX),
+            (u64::MAX, 1),
+            (0, u64::MAX),
+        ] {
+            let config = CircuitConfig::standard_recursion_config();
+            let mut pw = PartialWitness::<F>::new();
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+
+            let xt = builder.add_virtual_u64_target();
+            let yt = builder.add_virtual_u64_target();
+            pw.set_u64_target(xt, x);
+            pw.set_u64_target(yt, y);
+
+            let (sum, carry) = builder.add_uint_with_carry(xt, yt);
+            let (diff, borrow) = builder.sub_uint_with_borrow(xt, yt);
+            let product = builder.mul_uint_wrapping(xt, yt);
+            let less = builder.is_less_than_uint(xt, yt);
+            let less_or_equal = builder.is_less_or_equal_uint(xt, yt);
+            let equal = builder.is_equal_uint(xt, xt);
+            let shl = builder.shl_uint(xt, 13);
+            let shr = builder.shr_uint(xt, 45);
+            let shl_limb = builder.shl_uint(xt, 32);
+
+            let (expected_sum, expected_carry) = x.overflowing_add(y);
+            let (expected_diff, expected_borrow) = x.overflowing_sub(y);
+            let expected = [
+                (sum, expected_sum),
+                (diff, expected_diff),
+                (product, x.wrapping_mul(y)),
+                (shl, x << 13),
+                (shr, x >> 45),
+                (shl_limb, x << 32),
+            ];
+            for (target, value) in expected {
+                let value = builder.constant_u64(value);
+                builder.connect_uint(target, value);
+            }
+            for (target, value) in [
+                (carry, expected_carry),
+                (borrow, expected_borrow),
+                (less, x < y),
+                (less_or_equal, x <= y),
+                (equal, true),
+            ] {
+                let value = builder.constant_bool(value);
+                builder.connect(target.target, value.target);
+            }
+
+            let data = builder.build::<C>();
+            let proof = data.prove(pw)?;
+            verify(proof, &data.verifier_only, &data.common)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_u128_arithmetic() -> Result<()> {
+        let mut rng = OsRng;
+        // A product which doesn't overflow, for the checked multiplication.
+        let (x, y) = (rng.gen::<u64>() as u128, rng.gen::<u64>() as u128);
+        let z = rng.gen::<u128>();
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let xt = builder.add_virtual_u128_target();
+        let yt = builder.add_virtual_u128_target();
+        let zt = builder.add_virtual_u128_target();
+        pw.set_u128_target(xt, x);
+        pw.set_u128_target(yt, y);
+        pw.set_u128_target(zt, z);
+
+        let product = builder.mul_uint_checked(xt, yt);
+        let wrapped = builder.mul_uint_wrapping(zt, zt);
+        let sum = builder.add_uint_checked(xt, yt);
+        let diff = builder.sub_uint_wrapping(xt, zt);
+        let shl = builder.shl_uint(zt, 71);
+        let shr = builder.shr_uint(zt, 100);
+        let t = builder._true();
+        let selected = builder.select_uint(t, zt, xt);
+
+        for (target, value) in [
+            (product, x * y),
+            (wrapped, z.wrapping_mul(z)),
+            (sum, x + y),
+            (diff, x.wrapping_sub(z)),
+            (shl, z << 71),
+            (shr, z >> 100),
+            (selected, z),
+        ] {
+            let value = builder.constant_u128(value);
+            builder.connect_uint(target, value);
+        }
+
+        assert_eq!(pw.get_u128_target(zt), z);
+        assert_eq!(
+            pw.get_u64_target(U64Target {
+                limbs: [xt.limbs[0], xt.limbs[1]]
+            }),
+            x as u64
+        );
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
+}
@@ -157,6 +157,18 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ArithmeticGate
     fn num_constraints(&self) -> usize {
         self.num_ops
     }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        (Self::wire_ith_multiplicand_0(op)..=Self::wire_ith_output(op)).collect()
+    }
+
+    fn wire_name(&self, column: usize) -> String {
+        if column >= 4 * self.num_ops {
+            return format!("unused wire {column}");
+        }
+        let role = ["multiplicand_0", "multiplicand_1", "addend", "output"][column % 4];
+        format!("{} of operation {}", role, column / 4)
+    }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> PackedEvaluableBase<F, D> for ArithmeticGate {
@@ -111,6 +111,14 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ConstantGate {
         self.num_consts
     }
 
+    fn wire_name(&self, column: usize) -> String {
+        if column < self.num_consts {
+            format!("output {column}")
+        } else {
+            format!("unused wire {column}")
+        }
+    }
+
     fn extra_constant_wires(&self) -> Vec<(usize, usize)> {
         (0..self.num_consts)
             .map(|i| (self.const_input(i), self.wire_output(i)))
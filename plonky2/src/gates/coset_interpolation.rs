@@ -1,4 +1,5 @@
 use alloc::string::{String, ToString};
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use alloc::{format, vec};
 use core::marker::PhantomData;
@@ -19,6 +20,7 @@ use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
+use crate::util::domain_cache;
 use crate::util::serialization::{Buffer, IoResult, Read, Write};
 
 /// One of the instantiations of `InterpolationGate`: allows constraints of variable
@@ -200,7 +202,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for CosetInterpola
             (evaluation_point - shifted_evaluation_point.scalar_mul(shift)).to_basefield_array(),
         );
 
-        let domain = F::two_adic_subgroup(self.subgroup_bits);
+        let domain = domain_cache::two_adic_subgroup::<F>(self.subgroup_bits);
         let values = (0..self.num_points())
             .map(|i| vars.get_local_ext_algebra(self.wires_value(i)))
             .collect::<Vec<_>>();
@@ -251,7 +253,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for CosetInterpola
             (evaluation_point - shifted_evaluation_point.scalar_mul(shift)).to_basefield_array(),
         );
 
-        let domain = F::two_adic_subgroup(self.subgroup_bits);
+        let domain = domain_cache::two_adic_subgroup::<F>(self.subgroup_bits);
         let values = (0..self.num_points())
             .map(|i| vars.get_local_ext(self.wires_value(i)))
             .collect::<Vec<_>>();
@@ -308,7 +310,7 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for CosetInterpola
                 .to_ext_target_array(),
         );
 
-        let domain = F::two_adic_subgroup(self.subgroup_bits);
+        let domain = domain_cache::two_adic_subgroup::<F>(self.subgroup_bits);
         let values = (0..self.num_points())
             .map(|i| vars.get_local_ext_algebra(self.wires_value(i)))
             .collect::<Vec<_>>();
@@ -391,13 +393,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for CosetInterpola
 pub struct InterpolationGenerator<F: RichField + Extendable<D>, const D: usize> {
     row: usize,
     gate: CosetInterpolationGate<F, D>,
-    interpolation_domain: Vec<F>,
+    interpolation_domain: Arc<Vec<F>>,
     _phantom: PhantomData<F>,
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> InterpolationGenerator<F, D> {
     fn new(row: usize, gate: CosetInterpolationGate<F, D>) -> Self {
-        let interpolation_domain = F::two_adic_subgroup(gate.subgroup_bits);
+        let interpolation_domain = domain_cache::two_adic_subgroup(gate.subgroup_bits);
         InterpolationGenerator {
             row,
             gate,
@@ -1,7 +1,7 @@
 use alloc::string::String;
 use alloc::sync::Arc;
-use alloc::vec;
 use alloc::vec::Vec;
+use alloc::{format, vec};
 use core::any::Any;
 use core::fmt::{Debug, Error, Formatter};
 use core::hash::{Hash, Hasher};
@@ -195,6 +195,19 @@ pub trait Gate<F: RichField + Extendable<D>, const D: usize>: 'static + Send + S
             .len()
     }
 
+    /// The wires used by operation `op`, for gates whose operations don't share any wires or
+    /// constraints. Operations of such gates can be moved to another instance with the same
+    /// constants by `CircuitBuilder`'s gate packing pass. The default empty list marks the gate's
+    /// operations as not movable.
+    fn op_wires(&self, _op: usize) -> Vec<usize> {
+        vec![]
+    }
+
+    /// A short description of the role of the wire at `column`, used in error messages.
+    fn wire_name(&self, column: usize) -> String {
+        format!("wire {column}")
+    }
+
     /// Enables gates to store some "routed constants", if they have both unused constants and
     /// unused routed wires.
     ///
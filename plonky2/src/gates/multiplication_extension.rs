@@ -149,6 +149,18 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MulExtensionGa
     fn num_constraints(&self) -> usize {
         self.num_ops * D
     }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        (Self::wires_ith_multiplicand_0(op).start..Self::wires_ith_output(op).end).collect()
+    }
+
+    fn wire_name(&self, column: usize) -> String {
+        if column >= 3 * D * self.num_ops {
+            return format!("unused wire {column}");
+        }
+        let role = ["multiplicand_0", "multiplicand_1", "output"][column % (3 * D) / D];
+        format!("{}[{}] of operation {}", role, column % D, column / (3 * D))
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1,3 +1,4 @@
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::ops::Range;
@@ -96,6 +97,14 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for PublicInputGat
     fn num_constraints(&self) -> usize {
         4
     }
+
+    fn wire_name(&self, column: usize) -> String {
+        if Self::wires_public_inputs_hash().contains(&column) {
+            format!("public_inputs_hash[{column}]")
+        } else {
+            format!("unused wire {column}")
+        }
+    }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> PackedEvaluableBase<F, D> for PublicInputGate {
@@ -284,6 +284,12 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for RandomAccessGa
         self.num_copies * constraints_per_copy + self.num_extra_constants
     }
 
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        let routed = self.wire_access_index(op)..=self.wire_list_item(self.vec_size() - 1, op);
+        let bits = (0..self.bits).map(|i| self.wire_bit(i, op));
+        routed.chain(bits).collect()
+    }
+
     fn extra_constant_wires(&self) -> Vec<(usize, usize)> {
         (0..self.num_extra_constants)
             .map(|i| (i, self.wire_extra_constant(i)))
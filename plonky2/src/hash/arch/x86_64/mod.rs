@@ -3,3 +3,6 @@
 // // - BMI2 (for MULX and SHRX)
 // #[cfg(all(target_feature = "avx2", target_feature = "bmi2"))]
 // pub(crate) mod poseidon_goldilocks_avx2_bmi2;
+
+// Requires AVX-512F (and optionally AVX-512 IFMA), detected at runtime.
+pub(crate) mod poseidon_goldilocks_avx512;
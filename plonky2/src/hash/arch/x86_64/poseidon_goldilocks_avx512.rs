@@ -0,0 +1,320 @@
+//! AVX-512 implementation of the Poseidon permutation over Goldilocks.
+//!
+//! Unlike the packed field, which is chosen at compile time, this path is selected at runtime so
+//! that portable binaries still use AVX-512 on machines that support it. The full rounds keep the
+//! state in two `__m512i` registers (lanes 0..8 and 8..12, the upper four lanes of the second
+//! register being unused). The partial rounds only apply a single S-box per round, so they stay on
+//! the scalar path. When AVX-512 IFMA is available, the MDS accumulation uses fused 52-bit
+//! multiply-adds.
+
+use core::arch::x86_64::*;
+use core::mem::transmute;
+
+use crate::field::goldilocks_field::GoldilocksField;
+use crate::hash::poseidon::{
+    Poseidon, ALL_ROUND_CONSTANTS, HALF_N_FULL_ROUNDS, N_ROUNDS, SPONGE_WIDTH,
+};
+
+const WIDTH: usize = SPONGE_WIDTH;
+
+const EPSILON: __m512i = unsafe { transmute([0xffffffffu64; 8]) };
+
+const LO_32_BITS_MASK: __mmask16 = 0b0101010101010101;
+
+/// Mask selecting the four meaningful lanes of the upper half of the state.
+const HI_LANES_MASK: __mmask8 = 0b00001111;
+
+/// Columns of the MDS matrix `C + D`, each split into the rows held by the lower and upper
+/// registers. Row `r` of column `j` is `MDS_MATRIX_CIRC[(j - r) % 12]`, plus `MDS_MATRIX_DIAG[r]`
+/// on the diagonal.
+const MDS_COLUMNS: [[__m512i; 2]; WIDTH] = unsafe { transmute(make_mds_columns()) };
+
+const fn make_mds_columns() -> [[u64; 16]; WIDTH] {
+    let circ = <GoldilocksField as Poseidon>::MDS_MATRIX_CIRC;
+    let diag = <GoldilocksField as Poseidon>::MDS_MATRIX_DIAG;
+    let mut res = [[0; 16]; WIDTH];
+    let mut j = 0;
+    while j < WIDTH {
+        let mut r = 0;
+        while r < WIDTH {
+            res[j][r] = circ[(j + WIDTH - r) % WIDTH];
+            if r == j {
+                res[j][r] += diag[r];
+            }
+            r += 1;
+        }
+        j += 1;
+    }
+    res
+}
+
+/// Whether the AVX-512 permutation can be used on this machine.
+#[inline]
+pub(crate) fn is_available() -> bool {
+    #[cfg(feature = "std")]
+    {
+        std::is_x86_feature_detected!("avx512f")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cpu_features().0
+    }
+}
+
+#[inline]
+fn has_ifma() -> bool {
+    #[cfg(feature = "std")]
+    {
+        std::is_x86_feature_detected!("avx512ifma")
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        cpu_features().1
+    }
+}
+
+/// Whether the CPU and the OS support AVX-512F and AVX-512 IFMA, for builds without
+/// `is_x86_feature_detected!`. The result is queried once with `cpuid` and cached.
+#[cfg(any(not(feature = "std"), test))]
+fn cpu_features() -> (bool, bool) {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    const DETECTED: u8 = 1;
+    const AVX512F: u8 = 2;
+    const AVX512IFMA: u8 = 4;
+    static FEATURES: AtomicU8 = AtomicU8::new(0);
+
+    let mut features = FEATURES.load(Ordering::Relaxed);
+    if features == 0 {
+        features = DETECTED;
+        // SAFETY: `cpuid` is available on all x86_64 CPUs, and `xgetbv` is only executed when
+        // `cpuid` reports OSXSAVE.
+        unsafe {
+            let osxsave = __cpuid(1).ecx & (1 << 27) != 0;
+            // The OS must save the SSE, AVX, opmask and ZMM registers.
+            let zmm_enabled = osxsave && _xgetbv(0) & 0xe6 == 0xe6;
+            if zmm_enabled && __get_cpuid_max(0).0 >= 7 {
+                let ebx = __cpuid_count(7, 0).ebx;
+                if ebx & (1 << 16) != 0 {
+                    features |= AVX512F;
+                    if ebx & (1 << 21) != 0 {
+                        features |= AVX512IFMA;
+                    }
+                }
+            }
+        }
+        FEATURES.store(features, Ordering::Relaxed);
+    }
+    (features & AVX512F != 0, features & AVX512IFMA != 0)
+}
+
+/// Addition modulo FIELD_ORDER. `y` must be canonical; `x` may be any `u64`.
+#[inline(always)]
+unsafe fn add_canonical(x: __m512i, y: __m512i) -> __m512i {
+    let res = _mm512_add_epi64(x, y);
+    // On overflow, 2^64 = EPSILON (mod FIELD_ORDER). Since y < FIELD_ORDER, adding EPSILON back
+    // cannot overflow again.
+    let overflow = _mm512_cmplt_epu64_mask(res, y);
+    _mm512_mask_add_epi64(res, overflow, res, EPSILON)
+}
+
+/// Full 64-bit by 64-bit multiplication, returning the (high, low) halves of the product.
+#[inline(always)]
+unsafe fn mul64_64(x: __m512i, y: __m512i) -> (__m512i, __m512i) {
+    let x_hi = _mm512_srli_epi64::<32>(x);
+    let y_hi = _mm512_srli_epi64::<32>(y);
+
+    let mul_ll = _mm512_mul_epu32(x, y);
+    let mul_lh = _mm512_mul_epu32(x, y_hi);
+    let mul_hl = _mm512_mul_epu32(x_hi, y);
+    let mul_hh = _mm512_mul_epu32(x_hi, y_hi);
+
+    // None of these additions can overflow.
+    let t0 = _mm512_add_epi64(mul_hl, _mm512_srli_epi64::<32>(mul_ll));
+    let t1 = _mm512_add_epi64(mul_lh, _mm512_and_si512(t0, EPSILON));
+    let t2 = _mm512_add_epi64(mul_hh, _mm512_srli_epi64::<32>(t0));
+    let res_hi = _mm512_add_epi64(t2, _mm512_srli_epi64::<32>(t1));
+
+    let t1_lo = _mm512_slli_epi64::<32>(t1);
+    let res_lo = _mm512_mask_blend_epi32(LO_32_BITS_MASK, t1_lo, mul_ll);
+
+    (res_hi, res_lo)
+}
+
+#[inline(always)]
+unsafe fn reduce128((hi, lo): (__m512i, __m512i)) -> __m512i {
+    // 2^96 = -1 (mod FIELD_ORDER).
+    let hi_hi = _mm512_srli_epi64::<32>(hi);
+    let borrow = _mm512_cmplt_epu64_mask(lo, hi_hi);
+    let t0 = _mm512_sub_epi64(lo, hi_hi);
+    let t0 = _mm512_mask_sub_epi64(t0, borrow, t0, EPSILON);
+    // 2^64 = EPSILON (mod FIELD_ORDER); the low 32 bits of hi times EPSILON fit in 64 bits.
+    let t1 = _mm512_mul_epu32(hi, EPSILON);
+    add_canonical(t0, t1)
+}
+
+#[inline(always)]
+unsafe fn mul(x: __m512i, y: __m512i) -> __m512i {
+    reduce128(mul64_64(x, y))
+}
+
+#[inline(always)]
+unsafe fn sbox(x: __m512i) -> __m512i {
+    let x2 = mul(x, x);
+    let x3 = mul(x, x2);
+    let x4 = mul(x2, x2);
+    mul(x3, x4)
+}
+
+/// `acc + x * y`, where `x < 2^32` and `y` is a small MDS coefficient.
+#[inline(always)]
+unsafe fn mul_add<const IFMA: bool>(acc: __m512i, x: __m512i, y: __m512i) -> __m512i {
+    if IFMA {
+        // x * y < 2^52, so the low half of the 52-bit product is exact.
+        _mm512_madd52lo_epu64(acc, x, y)
+    } else {
+        _mm512_add_epi64(acc, _mm512_mul_epu32(x, y))
+    }
+}
+
+/// Reduces `lo + 2^32 hi`, where `lo, hi < 2^41`.
+#[inline(always)]
+unsafe fn reduce_mds(lo: __m512i, hi: __m512i) -> __m512i {
+    let res = _mm512_add_epi64(lo, _mm512_slli_epi64::<32>(hi));
+    // If the addition wrapped, res < lo < 2^41, so adding EPSILON cannot overflow.
+    let carry = _mm512_cmplt_epu64_mask(res, lo);
+    let res = _mm512_mask_add_epi64(res, carry, res, EPSILON);
+    let t = _mm512_mul_epu32(_mm512_srli_epi64::<32>(hi), EPSILON);
+    add_canonical(res, t)
+}
+
+#[inline(always)]
+unsafe fn mds_layer<const IFMA: bool>(state: [__m512i; 2]) -> [__m512i; 2] {
+    let mut s = [0u64; 16];
+    _mm512_storeu_si512(s.as_mut_ptr().cast(), state[0]);
+    _mm512_storeu_si512(s.as_mut_ptr().add(8).cast(), state[1]);
+
+    // The MDS coefficients sum to less than 2^9, so accumulating the 32-bit halves of the state
+    // separately cannot exceed 2^41.
+    let mut acc_lo = [_mm512_setzero_si512(); 2];
+    let mut acc_hi = [_mm512_setzero_si512(); 2];
+    for j in 0..WIDTH {
+        let s_lo = _mm512_set1_epi64((s[j] & 0xffffffff) as i64);
+        let s_hi = _mm512_set1_epi64((s[j] >> 32) as i64);
+        for k in 0..2 {
+            acc_lo[k] = mul_add::<IFMA>(acc_lo[k], s_lo, MDS_COLUMNS[j][k]);
+            acc_hi[k] = mul_add::<IFMA>(acc_hi[k], s_hi, MDS_COLUMNS[j][k]);
+        }
+    }
+
+    [
+        reduce_mds(acc_lo[0], acc_hi[0]),
+        reduce_mds(acc_lo[1], acc_hi[1]),
+    ]
+}
+
+#[inline(always)]
+unsafe fn full_rounds<const IFMA: bool>(
+    state: &mut [GoldilocksField; WIDTH],
+    round_ctr: &mut usize,
+) {
+    let ptr = state.as_mut_ptr().cast::<u64>();
+    let mut regs = [
+        _mm512_loadu_si512(ptr.cast()),
+        _mm512_maskz_loadu_epi64(HI_LANES_MASK, ptr.add(8).cast()),
+    ];
+    for _ in 0..HALF_N_FULL_ROUNDS {
+        let rc = ALL_ROUND_CONSTANTS[WIDTH * *round_ctr..].as_ptr();
+        regs[0] = add_canonical(regs[0], _mm512_loadu_si512(rc.cast()));
+        regs[1] = add_canonical(
+            regs[1],
+            _mm512_maskz_loadu_epi64(HI_LANES_MASK, rc.add(8).cast()),
+        );
+        regs = [sbox(regs[0]), sbox(regs[1])];
+        regs = mds_layer::<IFMA>(regs);
+        *round_ctr += 1;
+    }
+    _mm512_storeu_si512(ptr.cast(), regs[0]);
+    _mm512_mask_storeu_epi64(ptr.add(8).cast(), HI_LANES_MASK, regs[1]);
+}
+
+#[inline(always)]
+unsafe fn permute<const IFMA: bool>(input: [GoldilocksField; WIDTH]) -> [GoldilocksField; WIDTH] {
+    let mut state = input;
+    let mut round_ctr = 0;
+
+    full_rounds::<IFMA>(&mut state, &mut round_ctr);
+    <GoldilocksField as Poseidon>::partial_rounds(&mut state, &mut round_ctr);
+    full_rounds::<IFMA>(&mut state, &mut round_ctr);
+    debug_assert_eq!(round_ctr, N_ROUNDS);
+
+    state
+}
+
+#[target_feature(enable = "avx512f")]
+unsafe fn poseidon_avx512f(input: [GoldilocksField; WIDTH]) -> [GoldilocksField; WIDTH] {
+    permute::<false>(input)
+}
+
+#[target_feature(enable = "avx512f,avx512ifma")]
+unsafe fn poseidon_avx512ifma(input: [GoldilocksField; WIDTH]) -> [GoldilocksField; WIDTH] {
+    permute::<true>(input)
+}
+
+/// Computes the Poseidon permutation, using IFMA if the CPU supports it.
+///
+/// # Safety
+/// The caller must check [`is_available`] first.
+#[inline]
+pub(crate) unsafe fn poseidon(input: [GoldilocksField; WIDTH]) -> [GoldilocksField; WIDTH] {
+    if has_ifma() {
+        poseidon_avx512ifma(input)
+    } else {
+        poseidon_avx512f(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::{Field, Sample};
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_cpu_features() {
+        assert_eq!(
+            cpu_features(),
+            (
+                std::is_x86_feature_detected!("avx512f"),
+                std::is_x86_feature_detected!("avx512ifma")
+            )
+        );
+    }
+
+    #[test]
+    fn test_poseidon_avx512_matches_naive() {
+        if !is_available() {
+            return;
+        }
+
+        let mut inputs = vec![
+            [GoldilocksField::ZERO; WIDTH],
+            [GoldilocksField::NEG_ONE; WIDTH],
+            // Non-canonical representatives.
+            [GoldilocksField(u64::MAX); WIDTH],
+        ];
+        inputs.extend((0..100).map(|_| GoldilocksField::rand_array()));
+
+        for input in inputs {
+            let expected = GoldilocksField::poseidon_naive(input);
+            unsafe {
+                assert_eq!(poseidon_avx512f(input), expected);
+                if has_ifma() {
+                    assert_eq!(poseidon_avx512ifma(input), expected);
+                }
+            }
+        }
+    }
+}
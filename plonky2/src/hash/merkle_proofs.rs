@@ -13,6 +13,7 @@ use crate::iop::target::{BoolTarget, Target};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::VerifierCircuitTarget;
 use crate::plonk::config::{AlgebraicHasher, Hasher};
+use crate::util::log2_strict;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(bound = "")]
@@ -151,6 +152,58 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
+    /// Like `verify_merkle_proof_to_cap`, for a tree whose height is `heights[i]` for the only
+    /// true `height_flags[i]`. If no flag is true, nothing is checked. `proof` must have as many
+    /// siblings as the largest height; those beyond the selected height are ignored.
+    pub(crate) fn verify_merkle_proof_to_cap_with_variable_height<H: AlgebraicHasher<F>>(
+        &mut self,
+        leaf_data: Vec<Target>,
+        leaf_index_bits: &[BoolTarget],
+        heights: &[usize],
+        height_flags: &[BoolTarget],
+        merkle_cap: &MerkleCapTarget,
+        proof: &MerkleProofTarget,
+    ) {
+        debug_assert!(H::AlgebraicPermutation::RATE >= NUM_HASH_OUT_ELTS);
+        let cap_height = log2_strict(merkle_cap.0.len());
+
+        // The digest of the leaf's subtree of each height.
+        let zero = self.zero();
+        let mut state: HashOutTarget = self.hash_or_noop::<H>(leaf_data);
+        let mut states = vec![state];
+        for (&bit, &sibling) in leaf_index_bits.iter().zip(&proof.siblings) {
+            let mut perm_inputs = H::AlgebraicPermutation::default();
+            perm_inputs.set_from_slice(&state.elements, 0);
+            perm_inputs.set_from_slice(&sibling.elements, NUM_HASH_OUT_ELTS);
+            perm_inputs.set_from_iter(core::iter::repeat(zero), 2 * NUM_HASH_OUT_ELTS);
+            let perm_outs = self.permute_swapped::<H>(perm_inputs, bit);
+            state = HashOutTarget {
+                elements: perm_outs.squeeze()[0..NUM_HASH_OUT_ELTS]
+                    .try_into()
+                    .unwrap(),
+            };
+            states.push(state);
+        }
+
+        let roots = heights.iter().map(|&h| states[h]).collect::<Vec<_>>();
+        let root = self.select_one_hot_hash(height_flags, &roots);
+        let cap_indices = heights
+            .iter()
+            .map(|&h| self.le_sum(leaf_index_bits[h..h + cap_height].iter()))
+            .collect::<Vec<_>>();
+        let cap_index = self.select_one_hot(height_flags, &cap_indices);
+        let condition = self.add_many(height_flags.iter().map(|flag| flag.target));
+        for i in 0..NUM_HASH_OUT_ELTS {
+            let result = self.random_access(
+                cap_index,
+                merkle_cap.0.iter().map(|h| h.elements[i]).collect(),
+            );
+            let diff = self.sub(result, root.elements[i]);
+            let diff = self.mul(condition, diff);
+            self.assert_zero(diff);
+        }
+    }
+
     pub fn connect_hashes(&mut self, x: HashOutTarget, y: HashOutTarget) {
         for i in 0..NUM_HASH_OUT_ELTS {
             self.connect(x.elements[i], y.elements[i]);
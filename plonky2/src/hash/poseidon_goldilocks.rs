@@ -279,6 +279,22 @@ impl Poseidon for GoldilocksField {
     //     }
     // }
 
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    fn poseidon(input: [Self; 12]) -> [Self; 12] {
+        use crate::hash::arch::x86_64::poseidon_goldilocks_avx512 as avx512;
+        if avx512::is_available() {
+            return unsafe { avx512::poseidon(input) };
+        }
+
+        let mut state = input;
+        let mut round_ctr = 0;
+        Self::full_rounds(&mut state, &mut round_ctr);
+        Self::partial_rounds(&mut state, &mut round_ctr);
+        Self::full_rounds(&mut state, &mut round_ctr);
+        state
+    }
+
     // #[cfg(all(target_arch="aarch64", target_feature="neon"))]
     // #[inline]
     // fn poseidon(input: [Self; 12]) -> [Self; 12] {
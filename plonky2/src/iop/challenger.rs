@@ -8,6 +8,7 @@ use crate::hash::hashing::PlonkyPermutation;
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::target::Target;
+use crate::iop::transcript::{Transcript, TranscriptOp};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::config::{AlgebraicHasher, GenericHashOut, Hasher};
 
@@ -16,7 +17,9 @@ use crate::plonk::config::{AlgebraicHasher, GenericHashOut, Hasher};
 pub struct Challenger<F: RichField, H: Hasher<F>> {
     pub(crate) sponge_state: H::Permutation,
     pub(crate) input_buffer: Vec<F>,
-    output_buffer: Vec<F>,
+    pub(crate) output_buffer: Vec<F>,
+    /// If recording, the transcript of all observations and challenges so far.
+    pub(crate) transcript: Option<Transcript<F>>,
 }
 
 /// Observes prover messages, and generates verifier challenges based on the transcript.
@@ -33,10 +36,53 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
             sponge_state: H::Permutation::new(core::iter::repeat(F::ZERO)),
             input_buffer: Vec::with_capacity(H::Permutation::RATE),
             output_buffer: Vec::with_capacity(H::Permutation::RATE),
+            transcript: None,
         }
     }
 
+    /// Creates a challenger which records its transcript, see [`Transcript`].
+    pub fn new_recording() -> Challenger<F, H> {
+        Challenger {
+            transcript: Some(Transcript::default()),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a challenger whose sponge has the given state, such as one returned by
+    /// [`compact`](Self::compact).
+    pub fn from_state(sponge_state: H::Permutation) -> Challenger<F, H> {
+        Challenger {
+            sponge_state,
+            ..Self::new()
+        }
+    }
+
+    /// Sets the scope of the following transcript entries, and clears their label. Does nothing
+    /// if the challenger isn't recording.
+    pub fn set_transcript_scope(&mut self, scope: &str) {
+        if let Some(transcript) = &mut self.transcript {
+            transcript.set_scope(scope);
+        }
+    }
+
+    /// Sets the label of the following transcript entries. Does nothing if the challenger isn't
+    /// recording.
+    pub fn set_transcript_label(&mut self, label: &str) {
+        if let Some(transcript) = &mut self.transcript {
+            transcript.set_label(label);
+        }
+    }
+
+    /// Returns the transcript recorded so far, if recording, and stops recording.
+    pub fn take_transcript(&mut self) -> Option<Transcript<F>> {
+        self.transcript.take()
+    }
+
     pub fn observe_element(&mut self, element: F) {
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(TranscriptOp::Observe, Some(element));
+        }
+
         // Any buffered outputs are now invalid, since they wouldn't reflect this input.
         self.output_buffer.clear();
 
@@ -86,9 +132,14 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
             self.duplexing();
         }
 
-        self.output_buffer
+        let challenge = self
+            .output_buffer
             .pop()
-            .expect("Output buffer should be non-empty")
+            .expect("Output buffer should be non-empty");
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(TranscriptOp::Challenge, Some(challenge));
+        }
+        challenge
     }
 
     pub fn get_n_challenges(&mut self, n: usize) -> Vec<F> {
@@ -144,6 +195,9 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
     }
 
     pub fn compact(&mut self) -> H::Permutation {
+        if let Some(transcript) = &mut self.transcript {
+            transcript.record(TranscriptOp::Compact, None);
+        }
         if !self.input_buffer.is_empty() {
             self.duplexing();
         }
@@ -169,6 +223,20 @@ pub struct RecursiveChallenger<F: RichField + Extendable<D>, H: AlgebraicHasher<
     __: PhantomData<(F, H)>,
 }
 
+// Not derived, as that would require `F: Clone` and `H: Clone`.
+impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize> Clone
+    for RecursiveChallenger<F, H, D>
+{
+    fn clone(&self) -> Self {
+        Self {
+            sponge_state: self.sponge_state,
+            input_buffer: self.input_buffer.clone(),
+            output_buffer: self.output_buffer.clone(),
+            __: PhantomData,
+        }
+    }
+}
+
 impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
     RecursiveChallenger<F, H, D>
 {
@@ -1,9 +1,15 @@
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
+use core::cmp::Reverse;
 use core::fmt::Debug;
 use core::marker::PhantomData;
+use core::time::Duration;
+
+use hashbrown::HashMap;
+use log::info;
 
 use crate::field::extension::Extendable;
 use crate::field::types::Field;
@@ -14,6 +20,7 @@ use crate::iop::wire::Wire;
 use crate::iop::witness::{PartialWitness, PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
 use crate::plonk::config::GenericConfig;
+use crate::util::randomness::ProverRandomness;
 use crate::util::serialization::{Buffer, IoResult, Read, Write};
 
 /// Given a `PartitionWitness` that has only inputs set, populates the rest of the witness using the
@@ -27,6 +34,61 @@ pub fn generate_partial_witness<
     inputs: PartialWitness<F>,
     prover_data: &'a ProverOnlyCircuitData<F, C, D>,
     common_data: &'a CommonCircuitData<F, D>,
+) -> PartitionWitness<'a, F> {
+    run_generators(inputs, prover_data, common_data, ProverRandomness::Os, None)
+}
+
+/// Like [`generate_partial_witness`], but draws the values of random targets from `randomness`.
+pub fn generate_partial_witness_with_randomness<
+    'a,
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    inputs: PartialWitness<F>,
+    prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+    common_data: &'a CommonCircuitData<F, D>,
+    randomness: ProverRandomness,
+) -> PartitionWitness<'a, F> {
+    run_generators(inputs, prover_data, common_data, randomness, None)
+}
+
+/// Like [`generate_partial_witness`], but also records how often each generator was run and, with
+/// the `timing` feature, how long its runs took.
+pub fn generate_partial_witness_with_profile<
+    'a,
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    inputs: PartialWitness<F>,
+    prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+    common_data: &'a CommonCircuitData<F, D>,
+) -> (PartitionWitness<'a, F>, WitnessGenerationProfile) {
+    let mut runs = vec![GeneratorRuns::default(); prover_data.generators.len()];
+    let witness = run_generators(
+        inputs,
+        prover_data,
+        common_data,
+        ProverRandomness::Os,
+        Some(&mut runs),
+    );
+    (witness, WitnessGenerationProfile::new(prover_data, &runs))
+}
+
+/// The number of runs of a generator and their total duration.
+#[derive(Copy, Clone, Debug, Default)]
+struct GeneratorRuns {
+    count: usize,
+    time: Duration,
+}
+
+fn run_generators<'a, F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    inputs: PartialWitness<F>,
+    prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+    common_data: &'a CommonCircuitData<F, D>,
+    randomness: ProverRandomness,
+    mut runs: Option<&mut [GeneratorRuns]>,
 ) -> PartitionWitness<'a, F> {
     let config = &common_data.config;
     let generators = &prover_data.generators;
@@ -34,12 +96,40 @@ pub fn generate_partial_witness<
 
     let mut witness = PartitionWitness::new(
         config.num_wires,
-        common_data.degree(),
+        common_data.degree() + prover_data.merged_rows.len(),
         &prover_data.representative_map,
     );
+    witness.randomness = randomness;
+
+    // Map the representatives of watched targets to their labels, so that their values can be
+    // logged as soon as they are set.
+    let mut watches_by_rep = HashMap::<_, Vec<_>>::new();
+    for (label, target) in &prover_data.watches {
+        let rep = witness.representative_map[witness.target_index(*target)];
+        watches_by_rep.entry(rep).or_default().push(label.as_str());
+    }
+    let log_watches = |rep: usize, value: F| {
+        for label in watches_by_rep.get(&rep).into_iter().flatten() {
+            info!("Watched target `{}` = {}", label, value);
+        }
+    };
+
+    let conflict = |t: Target, v: F, old: F, source: &str| -> ! {
+        panic!(
+            "{} set {} to {}, but its partition was already set to {}",
+            source,
+            prover_data.diagnostics.describe_target(t, common_data),
+            v,
+            old
+        )
+    };
 
     for (t, v) in inputs.target_values.into_iter() {
-        witness.set_target(t, v);
+        match witness.try_set_target_returning_rep(t, v) {
+            Ok(Some(rep)) => log_watches(rep, v),
+            Ok(None) => {}
+            Err(old) => conflict(t, v, old, "The inputs"),
+        }
     }
 
     // Build a list of "pending" generators which are queued to be run. Initially, all generators
@@ -61,7 +151,21 @@ pub fn generate_partial_witness<
                 continue;
             }
 
-            let finished = generators[generator_idx].0.run(&witness, &mut buffer);
+            let finished = match runs.as_deref_mut() {
+                Some(runs) => {
+                    #[cfg(feature = "timing")]
+                    let start = std::time::Instant::now();
+                    let finished = generators[generator_idx].0.run(&witness, &mut buffer);
+                    let runs = &mut runs[generator_idx];
+                    runs.count += 1;
+                    #[cfg(feature = "timing")]
+                    {
+                        runs.time += start.elapsed();
+                    }
+                    finished
+                }
+                None => generators[generator_idx].0.run(&witness, &mut buffer),
+            };
             if finished {
                 generator_is_expired[generator_idx] = true;
                 remaining_generators -= 1;
@@ -69,10 +173,14 @@ pub fn generate_partial_witness<
 
             // Merge any generated values into our witness, and get a list of newly-populated
             // targets' representatives.
-            let new_target_reps = buffer
-                .target_values
-                .drain(..)
-                .flat_map(|(t, v)| witness.set_target_returning_rep(t, v));
+            let new_target_reps = buffer.target_values.drain(..).flat_map(|(t, v)| {
+                let rep = match witness.try_set_target_returning_rep(t, v) {
+                    Ok(rep) => rep?,
+                    Err(old) => conflict(t, v, old, &generators[generator_idx].0.id()),
+                };
+                log_watches(rep, v);
+                Some(rep)
+            });
 
             // Enqueue unfinished generators that were watching one of the newly populated targets.
             for watch in new_target_reps {
@@ -99,6 +207,89 @@ pub fn generate_partial_witness<
     witness
 }
 
+/// The cost of a group of generators during witness generation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneratorProfile {
+    /// The generator ID or context stack shared by the group.
+    pub name: String,
+    pub num_generators: usize,
+    /// The total number of times the group's generators were run, including runs which didn't
+    /// finish because some of their inputs were missing.
+    pub num_runs: usize,
+    /// The total duration of the group's runs. This is only measured with the `timing` feature,
+    /// and is zero otherwise.
+    pub time: Duration,
+}
+
+/// The cost of witness generation, broken down by generator type and by the context in which
+/// generators were added. Both lists are ranked from most to least expensive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WitnessGenerationProfile {
+    pub by_generator: Vec<GeneratorProfile>,
+    pub by_context: Vec<GeneratorProfile>,
+}
+
+impl WitnessGenerationProfile {
+    fn new<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+        prover_data: &ProverOnlyCircuitData<F, C, D>,
+        runs: &[GeneratorRuns],
+    ) -> Self {
+        let mut by_generator = BTreeMap::new();
+        let mut by_context = BTreeMap::new();
+        for (i, (generator, runs)) in prover_data.generators.iter().zip(runs).enumerate() {
+            let context = prover_data
+                .diagnostics
+                .generator_context(i)
+                .unwrap_or("unknown context");
+            for (groups, name) in [
+                (&mut by_generator, generator.0.id()),
+                (&mut by_context, context.to_string()),
+            ] {
+                let profile = groups
+                    .entry(name.clone())
+                    .or_insert_with(|| GeneratorProfile {
+                        name,
+                        num_generators: 0,
+                        num_runs: 0,
+                        time: Duration::ZERO,
+                    });
+                profile.num_generators += 1;
+                profile.num_runs += runs.count;
+                profile.time += runs.time;
+            }
+        }
+        Self {
+            by_generator: Self::rank(by_generator),
+            by_context: Self::rank(by_context),
+        }
+    }
+
+    fn rank(groups: BTreeMap<String, GeneratorProfile>) -> Vec<GeneratorProfile> {
+        let mut profiles = groups.into_values().collect::<Vec<_>>();
+        profiles.sort_by_key(|p| Reverse((p.time, p.num_runs)));
+        profiles
+    }
+
+    /// Logs both rankings.
+    pub fn print(&self) {
+        for (title, profiles) in [
+            ("generator", &self.by_generator),
+            ("context", &self.by_context),
+        ] {
+            info!("Witness generation cost by {}:", title);
+            for profile in profiles {
+                info!(
+                    "{:.4}s, {} runs of {} generators: {}",
+                    profile.time.as_secs_f64(),
+                    profile.num_runs,
+                    profile.num_generators,
+                    profile.name
+                );
+            }
+        }
+    }
+}
+
 /// A generator participates in the generation of the witness.
 pub trait WitnessGenerator<F: RichField + Extendable<D>, const D: usize>:
     'static + Send + Sync + Debug
@@ -312,8 +503,11 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Ran
         Vec::new()
     }
 
-    fn run_once(&self, _witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
-        let random_value = F::rand();
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let mut rng = witness
+            .randomness
+            .rng(witness.target_index(self.target) as u64);
+        let random_value = F::sample(&mut rng);
         out_buffer.set_target(self.target, random_value);
     }
 
@@ -415,3 +609,51 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Con
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+    use crate::with_context;
+
+    #[test]
+    fn test_witness_generation_profile() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = with_context!(builder, "square", builder.mul(x, x));
+        let z = with_context!(builder, "split", builder.split_le(y, 64));
+        builder.register_public_inputs(&[y, z[0].target]);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u64(3));
+        let profile = data.profile_witness_generation(pw);
+
+        let num_generators = data.prover_only.generators.len();
+        for profiles in [&profile.by_generator, &profile.by_context] {
+            assert_eq!(
+                profiles.iter().map(|p| p.num_generators).sum::<usize>(),
+                num_generators
+            );
+            assert!(profiles.iter().all(|p| p.num_runs >= p.num_generators));
+            assert!(profiles
+                .windows(2)
+                .all(|w| (w[0].time, w[0].num_runs) >= (w[1].time, w[1].num_runs)));
+        }
+        let by_name = |profiles: &[GeneratorProfile], name: &str| {
+            profiles.iter().find(|p| p.name == name).unwrap().clone()
+        };
+        assert!(by_name(&profile.by_generator, "ArithmeticBaseGenerator").num_runs > 0);
+        assert_eq!(
+            by_name(&profile.by_context, "root > square").num_generators,
+            1
+        );
+        assert!(by_name(&profile.by_context, "root > split").num_generators > 0);
+    }
+}
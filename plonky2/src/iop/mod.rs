@@ -4,5 +4,6 @@ pub mod challenger;
 pub mod ext_target;
 pub mod generator;
 pub mod target;
+pub mod transcript;
 pub mod wire;
 pub mod witness;
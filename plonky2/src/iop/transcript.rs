@@ -0,0 +1,157 @@
+//! A record of the Fiat-Shamir transcript of a [`Challenger`], for auditing.
+//!
+//! A recording challenger logs every element it observes and every challenge it samples, under
+//! the scope and label last given to it. Since a prover and a verifier drive their challengers
+//! identically, their transcripts should match entry for entry, and [`Transcript::replay`]
+//! recomputes every challenge from the observations alone. An auditor can thus check that every
+//! public value is observed before the challenges which should depend on it.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash_types::RichField;
+use crate::iop::challenger::Challenger;
+use crate::plonk::config::Hasher;
+
+/// An operation on a challenger.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptOp {
+    /// Elements absorbed into the sponge.
+    Observe,
+    /// Challenges squeezed from the sponge.
+    Challenge,
+    /// A call to [`Challenger::compact`], which absorbs buffered elements and discards buffered
+    /// challenges.
+    Compact,
+}
+
+/// Consecutive operations of the same kind, under the same scope and label.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry<F> {
+    /// What the operations belong to, e.g. a table of a multi-STARK proof.
+    pub scope: String,
+    /// What the operations are, e.g. `"trace cap"` or `"fri alpha"`.
+    pub label: String,
+    pub op: TranscriptOp,
+    /// The observed elements or sampled challenges, in order.
+    pub values: Vec<F>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Transcript<F> {
+    pub entries: Vec<TranscriptEntry<F>>,
+    #[serde(skip)]
+    scope: String,
+    #[serde(skip)]
+    label: String,
+}
+
+impl<F: RichField> Transcript<F> {
+    pub(crate) fn set_scope(&mut self, scope: &str) {
+        self.scope = scope.to_string();
+        self.label.clear();
+    }
+
+    pub(crate) fn set_label(&mut self, label: &str) {
+        self.label = label.to_string();
+    }
+
+    /// Records an operation, merging it into the last entry if it has the same kind, scope and
+    /// label.
+    pub(crate) fn record(&mut self, op: TranscriptOp, value: Option<F>) {
+        let extend_last = self.entries.last().is_some_and(|entry| {
+            entry.op == op
+                && op != TranscriptOp::Compact
+                && entry.scope == self.scope
+                && entry.label == self.label
+        });
+        if !extend_last {
+            self.entries.push(TranscriptEntry {
+                scope: self.scope.clone(),
+                label: self.label.clone(),
+                op,
+                values: Vec::new(),
+            });
+        }
+        if let Some(value) = value {
+            self.entries.last_mut().unwrap().values.push(value);
+        }
+    }
+
+    /// Replays the observations of this transcript into a fresh challenger, and checks that it
+    /// samples the recorded challenges. Returns the index of the first entry with a mismatching
+    /// challenge otherwise.
+    pub fn replay<H: Hasher<F>>(&self) -> Result<(), usize> {
+        let mut challenger = Challenger::<F, H>::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            match entry.op {
+                TranscriptOp::Observe => challenger.observe_elements(&entry.values),
+                TranscriptOp::Challenge => {
+                    if entry
+                        .values
+                        .iter()
+                        .any(|&value| challenger.get_challenge() != value)
+                    {
+                        return Err(i);
+                    }
+                }
+                TranscriptOp::Compact => {
+                    challenger.compact();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::goldilocks_field::GoldilocksField;
+    use crate::field::types::{Field, Sample};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    type F = GoldilocksField;
+    type H = <PoseidonGoldilocksConfig as GenericConfig<2>>::Hasher;
+
+    #[test]
+    fn test_replay() {
+        let mut challenger = Challenger::<F, H>::new_recording();
+        challenger.set_transcript_scope("test");
+        challenger.set_transcript_label("inputs");
+        challenger.observe_elements(&F::rand_vec(20));
+        challenger.set_transcript_label("alphas");
+        challenger.get_n_challenges(3);
+        challenger.compact();
+        challenger.set_transcript_label("more inputs");
+        challenger.observe_elements(&F::rand_vec(3));
+        challenger.set_transcript_label("betas");
+        challenger.get_n_challenges(10);
+
+        let transcript = challenger.take_transcript().unwrap();
+        let ops = transcript
+            .entries
+            .iter()
+            .map(|entry| (entry.label.as_str(), entry.op, entry.values.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                ("inputs", TranscriptOp::Observe, 20),
+                ("alphas", TranscriptOp::Challenge, 3),
+                ("alphas", TranscriptOp::Compact, 0),
+                ("more inputs", TranscriptOp::Observe, 3),
+                ("betas", TranscriptOp::Challenge, 10),
+            ]
+        );
+        assert_eq!(transcript.replay::<H>(), Ok(()));
+
+        // Tampering with an observation changes all later challenges.
+        let mut tampered = transcript;
+        tampered.entries[3].values[0] += F::ONE;
+        assert_eq!(tampered.replay::<H>(), Err(4));
+    }
+}
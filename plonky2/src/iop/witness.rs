@@ -16,6 +16,9 @@ use crate::iop::wire::Wire;
 use crate::plonk::circuit_data::{VerifierCircuitTarget, VerifierOnlyCircuitData};
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
 use crate::plonk::proof::{Proof, ProofTarget, ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use crate::recursion::cyclic_recursion::CyclicVerifierSetTarget;
+use crate::recursion::verifier_allow_list::{AllowedVerifierDataTarget, VerifierAllowList};
+use crate::util::randomness::ProverRandomness;
 
 pub trait WitnessWrite<F: Field> {
     fn set_target(&mut self, target: Target, value: F);
@@ -146,6 +149,38 @@ pub trait WitnessWrite<F: Field> {
         self.set_hash_target(vdt.circuit_digest, vd.circuit_digest);
     }
 
+    fn set_verifier_set_target<C: GenericConfig<D, F = F>, const D: usize>(
+        &mut self,
+        vst: &CyclicVerifierSetTarget,
+        verifier_set: &[VerifierOnlyCircuitData<C, D>],
+    ) where
+        F: RichField + Extendable<D>,
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        for (vdt, vd) in zip_eq(&vst.verifier_data, verifier_set) {
+            self.set_verifier_data_target(vdt, vd);
+        }
+    }
+
+    fn set_allowed_verifier_data_target<C: GenericConfig<D, F = F>, const D: usize>(
+        &mut self,
+        avdt: &AllowedVerifierDataTarget,
+        allow_list: &VerifierAllowList<F, C, D>,
+        index: usize,
+    ) where
+        F: RichField + Extendable<D>,
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        self.set_verifier_data_target(&avdt.verifier_data, &allow_list.verifier_data()[index]);
+        for (i, &bit) in avdt.index_bits.iter().enumerate() {
+            self.set_bool_target(bit, (index >> i) & 1 == 1);
+        }
+        let proof = allow_list.prove(index);
+        for (&ht, &h) in zip_eq(&avdt.merkle_proof.siblings, &proof.siblings) {
+            self.set_hash_target(ht, h);
+        }
+    }
+
     fn set_wire(&mut self, wire: Wire, value: F) {
         self.set_target(Target::Wire(wire), value)
     }
@@ -303,6 +338,8 @@ pub struct PartitionWitness<'a, F: Field> {
     pub representative_map: &'a [usize],
     pub num_wires: usize,
     pub degree: usize,
+    /// The source of the values of random targets, such as blinding wires.
+    pub randomness: ProverRandomness,
 }
 
 impl<'a, F: Field> PartitionWitness<'a, F> {
@@ -312,24 +349,38 @@ impl<'a, F: Field> PartitionWitness<'a, F> {
             representative_map,
             num_wires,
             degree,
+            randomness: ProverRandomness::Os,
         }
     }
 
     /// Set a `Target`. On success, returns the representative index of the newly-set target. If the
     /// target was already set, returns `None`.
     pub fn set_target_returning_rep(&mut self, target: Target, value: F) -> Option<usize> {
+        self.try_set_target_returning_rep(target, value)
+            .unwrap_or_else(|old_value| {
+                panic!(
+                    "Partition containing {:?} was set twice with different values: {} != {}",
+                    target, old_value, value
+                )
+            })
+    }
+
+    /// Like `set_target_returning_rep`, but if the target's partition was already set to a
+    /// different value, returns that value instead of panicking.
+    pub fn try_set_target_returning_rep(
+        &mut self,
+        target: Target,
+        value: F,
+    ) -> Result<Option<usize>, F> {
         let rep_index = self.representative_map[self.target_index(target)];
         let rep_value = &mut self.values[rep_index];
-        if let Some(old_value) = *rep_value {
-            assert_eq!(
-                value, old_value,
-                "Partition containing {:?} was set twice with different values: {} != {}",
-                target, old_value, value
-            );
-            None
-        } else {
-            *rep_value = Some(value);
-            Some(rep_index)
+        match *rep_value {
+            Some(old_value) if old_value != value => Err(old_value),
+            Some(_) => Ok(None),
+            None => {
+                *rep_value = Some(value);
+                Ok(Some(rep_index))
+            }
         }
     }
 
@@ -338,14 +389,28 @@ impl<'a, F: Field> PartitionWitness<'a, F> {
     }
 
     pub fn full_witness(self) -> MatrixWitness<F> {
-        let mut wire_values = vec![vec![F::ZERO; self.degree]; self.num_wires];
-        for i in 0..self.degree {
+        self.trace_witness(&[])
+    }
+
+    /// Like `full_witness`, but leaves out the given rows, which must be sorted. This is used for
+    /// circuits whose duplicate gates were merged when building, and whose targets include rows
+    /// which are not part of the trace.
+    pub fn trace_witness(self, merged_rows: &[usize]) -> MatrixWitness<F> {
+        let trace_degree = self.degree - merged_rows.len();
+        let mut wire_values = vec![vec![F::ZERO; trace_degree]; self.num_wires];
+        let mut merged_rows = merged_rows.iter().peekable();
+        let mut i = 0;
+        for row in 0..self.degree {
+            if merged_rows.next_if_eq(&&row).is_some() {
+                continue;
+            }
             for j in 0..self.num_wires {
-                let t = Target::Wire(Wire { row: i, column: j });
+                let t = Target::Wire(Wire { row, column: j });
                 if let Some(x) = self.try_get_target(t) {
                     wire_values[j][i] = x;
                 }
             }
+            i += 1;
         }
 
         MatrixWitness { wire_values }
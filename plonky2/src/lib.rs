@@ -4,9 +4,16 @@
 
 pub extern crate alloc;
 
+#[cfg(all(feature = "timing", target_arch = "wasm32", target_os = "unknown"))]
+compile_error!(
+    "the `timing` feature relies on `std::time::Instant`, which is unavailable on \
+     wasm32-unknown-unknown"
+);
+
 #[doc(inline)]
 pub use plonky2_field as field;
 
+pub mod curve;
 pub mod fri;
 pub mod gadgets;
 pub mod gates;
@@ -15,6 +22,8 @@ pub mod iop;
 pub mod plonk;
 pub mod recursion;
 pub mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[cfg(test)]
 mod lookup_test;
@@ -0,0 +1,117 @@
+//! Proving many instances of a single circuit.
+//!
+//! Besides the circuit's preprocessed data, every proof needs some data which only depends on the
+//! circuit's shape, such as FFT root tables. A [`BatchProver`] computes it once, and shares it
+//! between all its proofs.
+
+use alloc::vec::Vec;
+
+use anyhow::Result;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::witness::PartialWitness;
+use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
+use crate::plonk::config::GenericConfig;
+use crate::plonk::proof::ProofWithPublicInputs;
+use crate::plonk::prover::{prove_with_precomputation, ProverOptions, ProverPrecomputation};
+use crate::util::timing::TimingTree;
+
+/// A prover for many proofs of the same circuit.
+#[derive(Debug)]
+pub struct BatchProver<'a, F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+    common_data: &'a CommonCircuitData<F, D>,
+    options: ProverOptions,
+    precomputation: ProverPrecomputation<F>,
+}
+
+impl<'a, F, C, const D: usize> BatchProver<'a, F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    pub fn new(
+        prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+        common_data: &'a CommonCircuitData<F, D>,
+    ) -> Self {
+        Self::with_options(prover_data, common_data, ProverOptions::default())
+    }
+
+    pub fn with_options(
+        prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+        common_data: &'a CommonCircuitData<F, D>,
+        options: ProverOptions,
+    ) -> Self {
+        Self {
+            prover_data,
+            common_data,
+            options,
+            precomputation: ProverPrecomputation::new(common_data),
+        }
+    }
+
+    pub fn prove(&self, inputs: PartialWitness<F>) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_precomputation(
+            self.prover_data,
+            self.common_data,
+            inputs,
+            &self.options,
+            &self.precomputation,
+            &mut TimingTree::default(),
+        )
+    }
+
+    /// Proves each of `inputs` in turn. Each proof is itself parallelized, so this doesn't prove
+    /// several instances at once, which would only increase the peak memory usage.
+    pub fn prove_batch(
+        &self,
+        inputs: Vec<PartialWitness<F>>,
+    ) -> Result<Vec<ProofWithPublicInputs<F, C, D>>> {
+        inputs
+            .into_iter()
+            .map(|inputs| self.prove(inputs))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::WitnessWrite;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_prove_batch() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let inputs = (0..3)
+            .map(|i| {
+                let mut pw = PartialWitness::new();
+                pw.set_target(x, F::from_canonical_u64(i + 2));
+                pw
+            })
+            .collect();
+        let proofs = data.batch_prover().prove_batch(inputs)?;
+        for (i, proof) in (0..3).zip(proofs) {
+            assert_eq!(
+                proof.public_inputs,
+                [F::from_canonical_u64((i + 2u64).pow(7))]
+            );
+            data.verify(proof)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Checkpointing of long-running proofs.
+//!
+//! [`prove_with_checkpoints`] saves the state of a proof to a directory after its commit and
+//! challenge phases, and resumes from the latest state found there. A prover restarted after a
+//! crash thus doesn't generate the witness and commit to it again.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::Result;
+use log::warn;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::witness::PartialWitness;
+use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
+use crate::plonk::config::{GenericConfig, Hasher};
+use crate::plonk::proof::ProofWithPublicInputs;
+use crate::plonk::prover::{
+    prove_challenge_phase, prove_commit_phase, prove_open_phase, ChallengedProof, CommittedWitness,
+    ProverOptions,
+};
+use crate::util::timing::TimingTree;
+
+/// The file holding the state of a proof after its commit phase.
+pub const COMMITTED_WITNESS_FILE: &str = "committed_witness.bin";
+/// The file holding the state of a proof after its challenge phase.
+pub const CHALLENGED_PROOF_FILE: &str = "challenged_proof.bin";
+
+/// Like [`prove_with_options`](crate::plonk::prover::prove_with_options), but saves checkpoints to
+/// `checkpoint_dir`, and resumes from them if they exist. Checkpoints of another circuit, or which
+/// can't be read, are ignored. They are removed once the proof is done.
+///
+/// Since a resumed proof skips witness generation, `inputs` aren't checked against the
+/// checkpoints: each proof must have its own directory.
+pub fn prove_with_checkpoints<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: PartialWitness<F>,
+    options: &ProverOptions,
+    checkpoint_dir: &Path,
+    timing: &mut TimingTree,
+) -> Result<ProofWithPublicInputs<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    fs::create_dir_all(checkpoint_dir)?;
+    let committed_path = checkpoint_dir.join(COMMITTED_WITNESS_FILE);
+    let challenged_path = checkpoint_dir.join(CHALLENGED_PROOF_FILE);
+
+    let challenged = read_checkpoint(&challenged_path, |bytes| {
+        ChallengedProof::<F, C, D>::from_bytes(bytes).map(|challenged| {
+            (challenged.circuit_digest() == prover_data.circuit_digest).then_some(challenged)
+        })
+    });
+    let challenged = match challenged {
+        Some(challenged) => challenged,
+        None => {
+            let committed = read_checkpoint(&committed_path, |bytes| {
+                CommittedWitness::<F, C, D>::from_bytes(bytes).map(|committed| {
+                    (committed.circuit_digest() == prover_data.circuit_digest).then_some(committed)
+                })
+            });
+            let committed = match committed {
+                Some(mut committed) => {
+                    committed.memory_budget = options.memory_budget;
+                    committed.randomness = options.randomness;
+                    committed
+                }
+                None => {
+                    let committed =
+                        prove_commit_phase(prover_data, common_data, inputs, options, timing);
+                    write_checkpoint(&committed_path, &committed.to_bytes())?;
+                    committed
+                }
+            };
+            let challenged = prove_challenge_phase(prover_data, common_data, committed, timing)?;
+            write_checkpoint(&challenged_path, &challenged.to_bytes())?;
+            challenged
+        }
+    };
+
+    let proof = prove_open_phase(prover_data, common_data, challenged, timing);
+    for path in [&committed_path, &challenged_path] {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(proof)
+}
+
+/// Reads the checkpoint at `path`, if it exists and `parse` accepts it.
+fn read_checkpoint<T>(path: &Path, parse: impl FnOnce(&[u8]) -> Result<Option<T>>) -> Option<T> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                warn!("Ignoring unreadable checkpoint {}: {}", path.display(), e);
+            }
+            return None;
+        }
+    };
+    match parse(&bytes) {
+        Ok(Some(checkpoint)) => Some(checkpoint),
+        Ok(None) => {
+            warn!("Ignoring checkpoint {} of another circuit", path.display());
+            None
+        }
+        Err(e) => {
+            warn!("Ignoring invalid checkpoint {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Writes a checkpoint to a temporary file first, so that a crash can't leave a partial one at
+/// `path`.
+fn write_checkpoint(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::WitnessWrite;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_prove_with_checkpoints() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let (prover_data, common_data) = (&data.prover_only, &data.common);
+        let inputs = |x_value: u64| {
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u64(x_value));
+            pw
+        };
+        let dir = env::temp_dir().join(format!("plonky2-checkpoints-{}", std::process::id()));
+        let timing = &mut TimingTree::default();
+        let options = ProverOptions::default();
+
+        // Simulate a crash after the commit phase of a proof with x = 2.
+        fs::create_dir_all(&dir)?;
+        let committed = prove_commit_phase(prover_data, common_data, inputs(2), &options, timing);
+        fs::write(dir.join(COMMITTED_WITNESS_FILE), committed.to_bytes())?;
+
+        // The proof resumes from the checkpoint, whatever the inputs.
+        let proof =
+            prove_with_checkpoints(prover_data, common_data, inputs(3), &options, &dir, timing)?;
+        assert_eq!(proof.public_inputs, [F::from_canonical_u64(128)]);
+        data.verify(proof)?;
+        assert!(!dir.join(COMMITTED_WITNESS_FILE).exists());
+        assert!(!dir.join(CHALLENGED_PROOF_FILE).exists());
+
+        // An invalid checkpoint is ignored.
+        fs::write(dir.join(CHALLENGED_PROOF_FILE), [0; 8])?;
+        let proof =
+            prove_with_checkpoints(prover_data, common_data, inputs(3), &options, &dir, timing)?;
+        assert_eq!(proof.public_inputs, [F::from_canonical_u64(2187)]);
+        data.verify(proof)?;
+
+        // Likewise after the challenge phase.
+        let committed = prove_commit_phase(prover_data, common_data, inputs(4), &options, timing);
+        let challenged = prove_challenge_phase(prover_data, common_data, committed, timing)?;
+        fs::write(dir.join(CHALLENGED_PROOF_FILE), challenged.to_bytes())?;
+        let proof =
+            prove_with_checkpoints(prover_data, common_data, inputs(3), &options, &dir, timing)?;
+        assert_eq!(proof.public_inputs, [F::from_canonical_u64(16384)]);
+        data.verify(proof)?;
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
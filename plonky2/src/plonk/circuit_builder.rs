@@ -1,9 +1,13 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cmp::max;
-#[cfg(feature = "std")]
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
 use std::time::Instant;
 
 use hashbrown::{HashMap, HashSet};
@@ -17,7 +21,7 @@ use crate::field::fft::fft_root_table;
 use crate::field::polynomial::PolynomialValues;
 use crate::field::types::Field;
 use crate::fri::oracle::PolynomialBatch;
-use crate::fri::{FriConfig, FriParams};
+use crate::fri::FriParams;
 use crate::gadgets::arithmetic::BaseArithmeticOperation;
 use crate::gadgets::arithmetic_extension::ExtensionArithmeticOperation;
 use crate::gadgets::polynomial::PolynomialCoeffsExtTarget;
@@ -45,8 +49,11 @@ use crate::plonk::circuit_data::{
 };
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, GenericHashOut, Hasher};
 use crate::plonk::copy_constraint::CopyConstraint;
+use crate::plonk::diagnostics::CircuitDiagnostics;
 use crate::plonk::permutation_argument::Forest;
 use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::public_input_layout::{NamedPublicInput, PublicInputLayout, PublicInputType};
+use crate::recursion::cyclic_recursion::CyclicVerifierSetTarget;
 use crate::timed;
 use crate::util::context_tree::ContextTree;
 use crate::util::partial_products::num_partial_products;
@@ -83,6 +90,29 @@ pub struct LookupWire {
     /// Index of the first lookup table row (i.e. the last `LookupTableGate`).
     pub first_lut_gate: usize,
 }
+/// The usage of a gate type's operation slots, as reported by
+/// [`CircuitBuilder::gate_fill_report`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GateFill {
+    pub gate_id: String,
+    pub num_instances: usize,
+    /// The number of operations performed by all instances.
+    pub used_ops: usize,
+    /// The number of operations that all instances could perform, if they were full.
+    pub total_ops: usize,
+}
+
+impl GateFill {
+    /// The fraction of operation slots in use, or 1 if there are none.
+    pub fn fill_ratio(&self) -> f64 {
+        if self.total_ops == 0 {
+            1.0
+        } else {
+            self.used_ops as f64 / self.total_ops as f64
+        }
+    }
+}
+
 pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     pub config: CircuitConfig,
 
@@ -100,6 +130,9 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     /// Targets to be made public.
     public_inputs: Vec<Target>,
 
+    /// Names and types of the public inputs registered with a name.
+    public_input_layout: PublicInputLayout,
+
     /// The next available index for a `VirtualTarget`.
     virtual_target_index: usize,
 
@@ -135,6 +168,10 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     // Lookup tables in the form of `Vec<(input_value, output_value)>`.
     luts: Vec<LookupTable>,
 
+    /// Pairs `(target, num_bits)` of range assumptions made by decompositions, which `build`
+    /// checks in batches with lookups.
+    pub(crate) range_assumptions: Vec<(Target, usize)>,
+
     /// Optional common data. When it is `Some(goal_data)`, the `build` function panics if the resulting
     /// common data doesn't equal `goal_data`.
     /// This is used in cyclic recursion.
@@ -143,6 +180,30 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     /// Optional verifier data that is registered as public inputs.
     /// This is used in cyclic recursion to hold the circuit's own verifier key.
     pub(crate) verifier_data_public_input: Option<VerifierCircuitTarget>,
+
+    /// Optional verifier data of a set of mutually recursive circuits, whose digest is registered
+    /// as public inputs.
+    pub(crate) verifier_set_public_input: Option<CyclicVerifierSetTarget>,
+
+    /// Whether `build` should merge gates which duplicate an earlier gate.
+    deduplicate_gates: bool,
+
+    /// Whether `build` should move operations between partially filled gates to free up rows.
+    pack_gates: bool,
+
+    /// Rows of the gates merged into other gates by `deduplicate_gates` or `pack_gates`. Their
+    /// wires are still valid targets, but they are not part of the trace.
+    merged_rows: BTreeSet<usize>,
+
+    /// Targets whose values are logged during witness generation, along with their labels.
+    watches: Vec<(String, Target)>,
+
+    /// The context stack of each gate, used to explain failures at proving time.
+    diagnostics: CircuitDiagnostics,
+
+    /// Pairs of wires identified with each other by merging gates. Unlike copy constraints, these
+    /// may involve wires which are not routed, or not part of the trace.
+    wire_aliases: Vec<(Target, Target)>,
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
@@ -153,6 +214,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             gates: HashSet::new(),
             gate_instances: Vec::new(),
             public_inputs: Vec::new(),
+            public_input_layout: PublicInputLayout::default(),
             virtual_target_index: 0,
             copy_constraints: Vec::new(),
             context_log: ContextTree::new(),
@@ -166,32 +228,34 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             lookup_rows: Vec::new(),
             lut_to_lookups: Vec::new(),
             luts: Vec::new(),
+            range_assumptions: Vec::new(),
             goal_common_data: None,
             verifier_data_public_input: None,
+            verifier_set_public_input: None,
+            deduplicate_gates: false,
+            pack_gates: false,
+            merged_rows: BTreeSet::new(),
+            wire_aliases: Vec::new(),
+            watches: Vec::new(),
+            diagnostics: CircuitDiagnostics::default(),
         };
         builder.check_config();
         builder
     }
 
     fn check_config(&self) {
-        let &CircuitConfig {
+        let CircuitConfig {
             security_bits,
-            fri_config:
-                FriConfig {
-                    rate_bits,
-                    proof_of_work_bits,
-                    num_query_rounds,
-                    ..
-                },
+            fri_config,
             ..
         } = &self.config;
 
         // Conjectured FRI security; see the ethSTARK paper.
         let fri_field_bits = F::Extension::order().bits() as usize;
-        let fri_query_security_bits = num_query_rounds * rate_bits + proof_of_work_bits as usize;
+        let fri_query_security_bits = fri_config.conjectured_query_security_bits();
         let fri_security_bits = fri_field_bits.min(fri_query_security_bits);
         assert!(
-            fri_security_bits >= security_bits,
+            fri_security_bits >= *security_bits,
             "FRI params fall short of target security"
         );
     }
@@ -205,6 +269,27 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.gate_instances.len()
     }
 
+    /// Enables or disables gate deduplication in `build`. When enabled, each gate which is
+    /// identical to an earlier gate, i.e. has the same type and constants and reads its inputs from
+    /// the same targets, is merged into that gate before routing, so that it doesn't take up a row.
+    pub fn set_gate_deduplication(&mut self, enabled: bool) {
+        self.deduplicate_gates = enabled;
+    }
+
+    /// Enables or disables gate packing in `build`. Gadgets which don't share slot bookkeeping,
+    /// i.e. call `find_slot` with different parameters, can leave several instances of the same gate
+    /// with the same constants partially filled. When enabled, operations are moved from the last of
+    /// these instances into the free slots of the first ones, and emptied instances are dropped.
+    /// Only gates which report their operations' wires through [`Gate::op_wires`] are packed.
+    pub fn set_gate_packing(&mut self, enabled: bool) {
+        self.pack_gates = enabled;
+    }
+
+    /// The number of rows taken up by the gates added so far.
+    fn num_trace_rows(&self) -> usize {
+        self.gate_instances.len() - self.merged_rows.len()
+    }
+
     /// Registers the given target as a public input.
     pub fn register_public_input(&mut self, target: Target) {
         self.public_inputs.push(target);
@@ -219,6 +304,50 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.public_inputs.len()
     }
 
+    /// Registers the given targets as public inputs, under the given name and type. Panics if the
+    /// name is already taken.
+    fn register_named_public_inputs(
+        &mut self,
+        name: &str,
+        ty: PublicInputType,
+        targets: &[Target],
+    ) {
+        debug_assert_eq!(ty.len(), targets.len());
+        let entry = NamedPublicInput {
+            name: name.to_string(),
+            ty,
+            start: self.public_inputs.len(),
+        };
+        if let Err(e) = self.public_input_layout.push(entry) {
+            panic!("{e}");
+        }
+        self.register_public_inputs(targets);
+    }
+
+    /// Registers the given target as a public input named `name`.
+    pub fn register_named_public_input(&mut self, name: &str, target: Target) {
+        self.register_named_public_inputs(name, PublicInputType::Field, &[target]);
+    }
+
+    /// Registers the given hash as a public input named `name`.
+    pub fn register_named_public_hash(&mut self, name: &str, hash: HashOutTarget) {
+        self.register_named_public_inputs(name, PublicInputType::Hash, &hash.elements);
+    }
+
+    /// Range-checks the given targets to 32 bits, then registers them as a public input named
+    /// `name`.
+    pub fn register_named_public_u32s(&mut self, name: &str, targets: &[Target]) {
+        for &t in targets {
+            self.range_check(t, 32);
+        }
+        self.register_named_public_inputs(name, PublicInputType::U32Array(targets.len()), targets);
+    }
+
+    /// The layout of the public inputs registered so far with a name.
+    pub fn public_input_layout(&self) -> &PublicInputLayout {
+        &self.public_input_layout
+    }
+
     /// Adds lookup rows for a lookup table.
     pub fn add_lookup_rows(
         &mut self,
@@ -355,6 +484,47 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         verifier_data
     }
 
+    /// Add virtual verifier data for each of the `num_circuits` circuits of a mutually recursive
+    /// set, register their digest as public inputs and set them to
+    /// `self.verifier_set_public_input`.
+    /// WARNING: Do not register any public input after calling this!
+    pub fn add_verifier_set_public_inputs<C: GenericConfig<D, F = F>>(
+        &mut self,
+        num_circuits: usize,
+    ) -> CyclicVerifierSetTarget
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        assert!(
+            self.verifier_set_public_input.is_none(),
+            "add_verifier_set_public_inputs only needs to be called once"
+        );
+        assert!(num_circuits > 0, "The verifier set can't be empty");
+
+        let verifier_data = (0..num_circuits)
+            .map(|_| self.add_virtual_verifier_data(self.config.fri_config.cap_height))
+            .collect::<Vec<_>>();
+        let elements = verifier_data
+            .iter()
+            .flat_map(|vd| {
+                vd.circuit_digest
+                    .elements
+                    .into_iter()
+                    .chain(vd.constants_sigmas_cap.0.iter().flat_map(|h| h.elements))
+            })
+            .collect();
+        let digest = self.hash_n_to_hash_no_pad::<C::Hasher>(elements);
+        // Only the digest of the verifier data is public.
+        self.register_public_inputs(&digest.elements);
+
+        let verifier_set = CyclicVerifierSetTarget {
+            digest,
+            verifier_data,
+        };
+        self.verifier_set_public_input = Some(verifier_set.clone());
+        verifier_set
+    }
+
     /// Adds a gate to the circuit, and returns its index.
     pub fn add_gate<G: Gate<F, D>>(&mut self, gate_type: G, mut constants: Vec<F>) -> usize {
         self.check_gate_compatibility(&gate_type);
@@ -389,6 +559,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             gate_ref,
             constants,
         });
+        self.diagnostics.push_row(self.context_log.open_stack());
 
         row
     }
@@ -451,14 +622,30 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 
     pub fn add_generators(&mut self, generators: Vec<WitnessGeneratorRef<F, D>>) {
+        self.diagnostics
+            .push_generators(self.context_log.open_stack(), generators.len());
         self.generators.extend(generators);
     }
 
     pub fn add_simple_generator<G: SimpleGenerator<F, D>>(&mut self, generator: G) {
+        self.diagnostics
+            .push_generators(self.context_log.open_stack(), 1);
         self.generators
             .push(WitnessGeneratorRef::new(generator.adapter()));
     }
 
+    /// Registers `target` to be watched during witness generation, for debugging. Its value is
+    /// logged under `label` as soon as it is known, and can be looked up afterwards with
+    /// [`ProverOnlyCircuitData::watched_values`].
+    pub fn register_watch(&mut self, target: Target, label: &str) {
+        assert!(
+            self.watches.iter().all(|(other, _)| other != label),
+            "Watch `{}` is registered twice",
+            label
+        );
+        self.watches.push((label.to_string(), target));
+    }
+
     /// Returns a routable target with a value of 0.
     pub fn zero(&mut self) -> Target {
         self.constant(F::ZERO)
@@ -746,7 +933,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// polynomials (which are opened at only one location) and for the Z polynomials (which are
     /// opened at two).
     fn blinding_counts(&self) -> (usize, usize) {
-        let num_gates = self.gate_instances.len();
+        let num_gates = self.num_trace_rows();
         let mut degree_estimate = 1 << log2_ceil(num_gates);
 
         loop {
@@ -771,7 +958,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             self.blind();
         }
 
-        while !self.gate_instances.len().is_power_of_two() {
+        while !self.num_trace_rows().is_power_of_two() {
             self.add_gate(NoopGate, vec![]);
         }
     }
@@ -825,7 +1012,10 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
-    fn constant_polys(&self) -> Vec<PolynomialValues<F>> {
+    fn constant_polys(
+        &self,
+        trace_gate_instances: &[GateInstance<F, D>],
+    ) -> Vec<PolynomialValues<F>> {
         let max_constants = self
             .gates
             .iter()
@@ -833,8 +1023,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             .max()
             .unwrap();
         transpose(
-            &self
-                .gate_instances
+            &trace_gate_instances
                 .iter()
                 .map(|g| {
                     let mut consts = g.constants.clone();
@@ -848,18 +1037,19 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         .collect()
     }
 
-    fn sigma_vecs(&self, k_is: &[F], subgroup: &[F]) -> (Vec<PolynomialValues<F>>, Forest) {
-        let degree = self.gate_instances.len();
-        let degree_log = log2_strict(degree);
+    /// Builds the disjoint-set forest of all targets, with the partitions induced by copy
+    /// constraints and by merged gates.
+    fn copy_forest(&self) -> Forest {
+        let num_rows = self.gate_instances.len();
         let config = &self.config;
         let mut forest = Forest::new(
             config.num_wires,
             config.num_routed_wires,
-            degree,
+            num_rows,
             self.virtual_target_index,
         );
 
-        for gate in 0..degree {
+        for gate in 0..num_rows {
             for input in 0..config.num_wires {
                 forest.add(Target::Wire(Wire {
                     row: gate,
@@ -876,15 +1066,223 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             forest.merge(a, b);
         }
 
+        for &(a, b) in &self.wire_aliases {
+            forest.merge(a, b);
+        }
+
+        forest
+    }
+
+    fn sigma_vecs(&self, k_is: &[F], subgroup: &[F]) -> (Vec<PolynomialValues<F>>, Forest) {
+        let trace_rows = (0..self.gate_instances.len())
+            .filter(|row| !self.merged_rows.contains(row))
+            .collect::<Vec<_>>();
+        let degree_log = log2_strict(trace_rows.len());
+        let mut forest = self.copy_forest();
         forest.compress_paths();
 
-        let wire_partition = forest.wire_partition();
+        let wire_partition = forest.wire_partition(&trace_rows);
         (
             wire_partition.get_sigma_polys(degree_log, k_is, subgroup),
             forest,
         )
     }
 
+    /// Maps each gate which only uses some of its slots to its number of used slots.
+    fn incomplete_gates(&self) -> HashMap<usize, usize> {
+        self.current_slots
+            .values()
+            .flat_map(|current_slot| current_slot.current_slot.values().copied())
+            .collect()
+    }
+
+    /// Merges each gate which duplicates an earlier gate into it, and returns the number of merged
+    /// gates.
+    ///
+    /// Two gates are duplicates if they have the same type, constants and number of used slots,
+    /// and if their inputs are in the same partitions. Their outputs are then equal, so the wires of
+    /// the later gate can be merged into those of the earlier one, and the later gate's row dropped
+    /// from the trace. The inputs of a gate are taken to be the wires watched by its generators;
+    /// gates without generators, or whose generators watch targets outside of their row, are left
+    /// alone. Since merging outputs can make later gates' inputs equal, we repeat until no more
+    /// gates are merged.
+    fn deduplicate_gates(&mut self) -> usize {
+        let num_wires = self.config.num_wires;
+        let incomplete_gates = self.incomplete_gates();
+        let inputs = self
+            .gate_instances
+            .iter()
+            .enumerate()
+            .map(|(row, gate)| {
+                let mut gens = gate.gate_ref.0.generators(row, &gate.constants);
+                if let Some(&op) = incomplete_gates.get(&row) {
+                    gens.drain(op..);
+                }
+                if gens.is_empty() {
+                    return None;
+                }
+                let mut columns = Vec::new();
+                for watch in gens.iter().flat_map(|g| g.0.watch_list()) {
+                    match watch {
+                        Target::Wire(wire) if wire.row == row => columns.push(wire.column),
+                        _ => return None,
+                    }
+                }
+                columns.sort_unstable();
+                columns.dedup();
+                Some(columns)
+            })
+            .collect::<Vec<_>>();
+
+        let mut forest = self.copy_forest();
+        let mut num_merged = 0;
+        loop {
+            let mut first_rows = HashMap::new();
+            let mut merged_any = false;
+            for (row, columns) in inputs.iter().enumerate() {
+                let Some(columns) = columns else {
+                    continue;
+                };
+                if self.merged_rows.contains(&row) {
+                    continue;
+                }
+                let input_reps = columns
+                    .iter()
+                    .map(|&column| forest.find(forest.target_index(Target::wire(row, column))))
+                    .collect::<Vec<_>>();
+                let gate = &self.gate_instances[row];
+                let key = (
+                    gate.gate_ref.clone(),
+                    gate.constants.clone(),
+                    incomplete_gates.get(&row).copied(),
+                    input_reps,
+                );
+                match first_rows.get(&key) {
+                    None => {
+                        first_rows.insert(key, row);
+                    }
+                    Some(&original) => {
+                        for column in 0..num_wires {
+                            let (a, b) =
+                                (Target::wire(original, column), Target::wire(row, column));
+                            forest.merge(a, b);
+                            self.wire_aliases.push((a, b));
+                        }
+                        self.merged_rows.insert(row);
+                        num_merged += 1;
+                        merged_any = true;
+                    }
+                }
+            }
+            if !merged_any {
+                break;
+            }
+        }
+
+        num_merged
+    }
+
+    /// Moves operations between partially filled instances of the same gate with the same
+    /// constants, so that all but at most one of them are full, and returns the number of instances
+    /// which were emptied and dropped.
+    ///
+    /// A moved operation's wires are aliased to those of the slot it moves to, so targets which
+    /// refer to the old wires remain valid.
+    fn pack_gates(&mut self) -> usize {
+        let mut groups = HashMap::<_, Vec<_>>::new();
+        for (gate_ref, slots) in &self.current_slots {
+            for (params, &(row, num_used)) in &slots.current_slot {
+                let constants = self.gate_instances[row].constants.clone();
+                groups
+                    .entry((gate_ref.clone(), constants))
+                    .or_default()
+                    .push((params.clone(), row, num_used));
+            }
+        }
+
+        let mut num_packed = 0;
+        // Sort the groups so that packing is deterministic.
+        for ((gate_ref, _), mut partials) in groups
+            .into_iter()
+            .sorted_by_key(|(_, partials)| partials.iter().map(|&(_, row, _)| row).min())
+        {
+            let gate = &gate_ref.0;
+            if partials.len() < 2 || gate.op_wires(0).is_empty() {
+                continue;
+            }
+            let num_ops = gate.num_ops();
+            partials.sort_by_key(|&(_, row, _)| row);
+
+            // Move operations from the last instances to the first ones, taking each instance's
+            // last operation so that the used slots stay contiguous.
+            let (mut dst, mut src) = (0, partials.len() - 1);
+            while dst < src {
+                let (_, dst_row, dst_used) = partials[dst];
+                let (_, src_row, src_used) = partials[src];
+                if dst_used == num_ops {
+                    dst += 1;
+                } else if src_used == 0 {
+                    self.merged_rows.insert(src_row);
+                    num_packed += 1;
+                    src -= 1;
+                } else {
+                    let src_wires = gate.op_wires(src_used - 1);
+                    let dst_wires = gate.op_wires(dst_used);
+                    self.wire_aliases.extend(
+                        src_wires
+                            .into_iter()
+                            .zip(dst_wires)
+                            .map(|(s, d)| (Target::wire(dst_row, d), Target::wire(src_row, s))),
+                    );
+                    partials[dst].2 += 1;
+                    partials[src].2 -= 1;
+                }
+            }
+
+            let current_slot = &mut self.current_slots.get_mut(&gate_ref).unwrap().current_slot;
+            for (params, row, num_used) in partials {
+                if self.merged_rows.contains(&row) || num_used == num_ops {
+                    current_slot.remove(&params);
+                } else {
+                    current_slot.insert(params, (row, num_used));
+                }
+            }
+        }
+
+        // Emptied gates no longer hold constants.
+        self.constant_generators
+            .retain(|generator| !self.merged_rows.contains(&generator.row));
+
+        num_packed
+    }
+
+    /// Reports, for each gate type, how many of its instances' operation slots are in use.
+    /// Instances which were merged into other gates are not counted.
+    pub fn gate_fill_report(&self) -> Vec<GateFill> {
+        let incomplete_gates = self.incomplete_gates();
+        self.gates
+            .iter()
+            .map(|gate| {
+                let num_ops = gate.0.num_ops();
+                let rows = (0..self.gate_instances.len()).filter(|row| {
+                    self.gate_instances[*row].gate_ref == *gate && !self.merged_rows.contains(row)
+                });
+                let (mut num_instances, mut used_ops) = (0, 0);
+                for row in rows {
+                    num_instances += 1;
+                    used_ops += incomplete_gates.get(&row).copied().unwrap_or(num_ops);
+                }
+                GateFill {
+                    gate_id: gate.0.id(),
+                    num_instances,
+                    used_ops,
+                    total_ops: num_instances * num_ops,
+                }
+            })
+            .sorted_by(|a, b| a.gate_id.cmp(&b.gate_id))
+            .collect()
+    }
+
     pub fn print_gate_counts(&self, min_delta: usize) {
         // Print gate counts for each context.
         self.context_log
@@ -893,13 +1291,13 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         // Print total count of each gate type.
         debug!("Total gate counts:");
-        for gate in self.gates.iter().cloned() {
-            let count = self
-                .gate_instances
-                .iter()
-                .filter(|inst| inst.gate_ref == gate)
-                .count();
-            debug!("- {} instances of {}", count, gate.0.id());
+        for fill in self.gate_fill_report() {
+            debug!(
+                "- {} instances of {} ({:.1}% of operation slots used)",
+                fill.num_instances,
+                fill.gate_id,
+                100.0 * fill.fill_ratio()
+            );
         }
     }
 
@@ -922,9 +1320,15 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     ) -> CircuitData<F, C, D> {
         let mut timing = TimingTree::new("preprocess", Level::Trace);
 
-        #[cfg(feature = "std")]
+        #[cfg(all(
+            feature = "std",
+            not(all(target_arch = "wasm32", target_os = "unknown"))
+        ))]
         let start = Instant::now();
 
+        // This adds lookups, so it must come before the LUTs are counted.
+        self.discharge_range_assumptions();
+
         let rate_bits = self.config.fri_config.rate_bits;
         let cap_height = self.config.fri_config.cap_height;
         // Total number of LUTs.
@@ -947,6 +1351,21 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         // Place LUT-related gates.
         self.add_all_lookups();
 
+        if self.pack_gates {
+            if num_luts == 0 {
+                let num_packed = self.pack_gates();
+                info!("Gate packing saved {} gates", num_packed);
+                for fill in self.gate_fill_report() {
+                    debug!(
+                        "- {} instances of {}, {}/{} operation slots used",
+                        fill.num_instances, fill.gate_id, fill.used_ops, fill.total_ops
+                    );
+                }
+            } else {
+                info!("Skipping gate packing, which doesn't support lookups");
+            }
+        }
+
         // Make sure we have enough constant generators. If not, add a `ConstantGate`.
         while self.constants_to_targets.len() > self.constant_generators.len() {
             self.add_gate(
@@ -976,12 +1395,21 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             self.add_simple_generator(const_gen);
         }
 
+        if self.deduplicate_gates {
+            if num_luts == 0 {
+                let num_merged = self.deduplicate_gates();
+                info!("Gate deduplication saved {} gates", num_merged);
+            } else {
+                info!("Skipping gate deduplication, which doesn't support lookups");
+            }
+        }
+
         debug!(
             "Degree before blinding & padding: {}",
-            self.gate_instances.len()
+            self.num_trace_rows()
         );
         self.blind_and_pad();
-        let degree = self.gate_instances.len();
+        let degree = self.num_trace_rows();
         debug!("Degree after blinding & padding: {}", degree);
         let degree_bits = log2_strict(degree);
         let fri_params = self.fri_params(degree_bits);
@@ -994,14 +1422,31 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let mut gates = self.gates.iter().cloned().collect::<Vec<_>>();
         // Gates need to be sorted by their degrees (and ID to make the ordering deterministic) to compute the selector polynomials.
         gates.sort_unstable_by_key(|g| (g.0.degree(), g.0.id()));
+        let gate_indices = gates
+            .iter()
+            .enumerate()
+            .map(|(i, gate)| (gate.clone(), i))
+            .collect::<HashMap<_, _>>();
+        self.diagnostics.row_gates = self
+            .gate_instances
+            .iter()
+            .map(|inst| gate_indices[&inst.gate_ref])
+            .collect();
+        let trace_gate_instances = self
+            .gate_instances
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| !self.merged_rows.contains(row))
+            .map(|(_, gate)| gate.clone())
+            .collect::<Vec<_>>();
         let (mut constant_vecs, selectors_info) =
-            selector_polynomials(&gates, &self.gate_instances, quotient_degree_factor + 1);
+            selector_polynomials(&gates, &trace_gate_instances, quotient_degree_factor + 1);
 
         // Get the lookup selectors.
         let num_lookup_selectors = if num_luts != 0 {
             let selector_lookups =
-                selectors_lookup(&gates, &self.gate_instances, &self.lookup_rows);
-            let selector_ends = selector_ends_lookups(&self.lookup_rows, &self.gate_instances);
+                selectors_lookup(&gates, &trace_gate_instances, &self.lookup_rows);
+            let selector_ends = selector_ends_lookups(&self.lookup_rows, &trace_gate_instances);
             let all_lookup_selectors = [selector_lookups, selector_ends].concat();
             let num_lookup_selectors = all_lookup_selectors.len();
             constant_vecs.extend(all_lookup_selectors);
@@ -1010,7 +1455,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             0
         };
 
-        constant_vecs.extend(self.constant_polys());
+        constant_vecs.extend(self.constant_polys(&trace_gate_instances));
         let num_constants = constant_vecs.len();
 
         let subgroup = F::two_adic_subgroup(degree_bits);
@@ -1041,27 +1486,22 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         };
 
         // Map between gates where not all generators are used and the gate's number of used generators.
-        let incomplete_gates = self
-            .current_slots
-            .values()
-            .flat_map(|current_slot| current_slot.current_slot.values().copied())
-            .collect::<HashMap<_, _>>();
+        let incomplete_gates = self.incomplete_gates();
 
-        // Add gate generators.
-        self.add_generators(
-            self.gate_instances
-                .iter()
-                .enumerate()
-                .flat_map(|(index, gate)| {
-                    let mut gens = gate.gate_ref.0.generators(index, &gate.constants);
-                    // Remove unused generators, if any.
-                    if let Some(&op) = incomplete_gates.get(&index) {
-                        gens.drain(op..);
-                    }
-                    gens
-                })
-                .collect(),
-        );
+        // Add gate generators. Merged gates don't need any, since their wires are copies of those
+        // of the gates they were merged into. They are attributed to the context of their gate.
+        for (index, gate) in self.gate_instances.iter().enumerate() {
+            if self.merged_rows.contains(&index) {
+                continue;
+            }
+            let mut gens = gate.gate_ref.0.generators(index, &gate.constants);
+            // Remove unused generators, if any.
+            if let Some(&op) = incomplete_gates.get(&index) {
+                gens.drain(op..);
+            }
+            self.diagnostics.push_row_generators(index, gens.len());
+            self.generators.extend(gens);
+        }
 
         // Index generator indices by their watched targets.
         let mut generator_indices_by_watches = BTreeMap::new();
@@ -1100,6 +1540,8 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let domain_separator = self.domain_separator.unwrap_or_default();
         let domain_separator_digest = C::Hasher::hash_pad(&domain_separator);
         // TODO: This should also include an encoding of gate constraints.
+        // The public input layout is only included if there is one, so that circuits without
+        // named public inputs keep their digest.
         let circuit_digest_parts = [
             constants_sigmas_cap.flatten(),
             domain_separator_digest.to_vec(),
@@ -1107,6 +1549,11 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                 F::from_canonical_usize(degree_bits),
                 /* Add other circuit data here */
             ],
+            if self.public_input_layout.is_empty() {
+                vec![]
+            } else {
+                self.public_input_layout.fingerprint_elements()
+            },
         ];
         let circuit_digest = C::Hasher::hash_no_pad(&circuit_digest_parts.concat());
 
@@ -1141,15 +1588,22 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             circuit_digest,
             lookup_rows: self.lookup_rows.clone(),
             lut_to_lookups: self.lut_to_lookups.clone(),
+            merged_rows: self.merged_rows.iter().copied().collect(),
+            watches: self.watches,
+            diagnostics: self.diagnostics,
         };
 
         let verifier_only = VerifierOnlyCircuitData::<C, D> {
             constants_sigmas_cap,
             circuit_digest,
+            public_input_layout: self.public_input_layout,
         };
 
         timing.print();
-        #[cfg(feature = "std")]
+        #[cfg(all(
+            feature = "std",
+            not(all(target_arch = "wasm32", target_os = "unknown"))
+        ))]
         debug!("Building circuit took {}s", start.elapsed().as_secs_f32());
         CircuitData {
             prover_only,
@@ -1183,3 +1637,168 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         circuit_data.verifier_data()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::marker::PhantomData;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::hash::poseidon::PoseidonHash;
+    use crate::iop::generator::generate_partial_witness;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::serialization::DefaultGeneratorSerializer;
+
+    #[test]
+    fn test_gate_deduplication() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let build = |deduplicate: bool| {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            builder.set_gate_deduplication(deduplicate);
+            let inputs = builder.add_virtual_targets(4);
+            // The second chain of hashes duplicates the first. Its first gate is merged right away,
+            // and the second one once the outputs of the first gates are merged.
+            for _ in 0..2 {
+                let h = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs.clone());
+                let h = builder.hash_n_to_hash_no_pad::<PoseidonHash>(h.elements.to_vec());
+                builder.register_public_inputs(&h.elements);
+            }
+            (inputs, builder.build::<C>())
+        };
+
+        let (_, data) = build(false);
+        assert!(data.prover_only.merged_rows.is_empty());
+        let (inputs, data) = build(true);
+        assert_eq!(data.prover_only.merged_rows.len(), 2);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&inputs, &[F::ONE, F::TWO, F::ZERO, F::NEG_ONE]);
+        let proof = data.prove(pw)?;
+        assert_eq!(proof.public_inputs[..4], proof.public_inputs[4..]);
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_gate_packing() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        // A multiplication which keeps its own slot bookkeeping, keyed by `tag`, like a gadget which
+        // doesn't coordinate with `CircuitBuilder::mul`.
+        fn tagged_mul(
+            builder: &mut CircuitBuilder<F, D>,
+            tag: u64,
+            x: Target,
+            y: Target,
+        ) -> Target {
+            let gate = ArithmeticGate::new_from_config(&builder.config);
+            let params = [F::from_canonical_u64(tag)];
+            let (row, i) = builder.find_slot(gate, &params, &[F::ONE, F::ZERO]);
+            let zero = builder.zero();
+            builder.connect(
+                x,
+                Target::wire(row, ArithmeticGate::wire_ith_multiplicand_0(i)),
+            );
+            builder.connect(
+                y,
+                Target::wire(row, ArithmeticGate::wire_ith_multiplicand_1(i)),
+            );
+            builder.connect(zero, Target::wire(row, ArithmeticGate::wire_ith_addend(i)));
+            Target::wire(row, ArithmeticGate::wire_ith_output(i))
+        }
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        builder.set_gate_packing(true);
+        let inputs = builder.add_virtual_targets(3);
+        let mut outputs = Vec::new();
+        for (i, &x) in inputs.iter().enumerate() {
+            outputs.push(tagged_mul(&mut builder, 1, x, x));
+            outputs.push(tagged_mul(&mut builder, 2, x, inputs[(i + 1) % 3]));
+            outputs.push(builder.mul(x, inputs[(i + 2) % 3]));
+        }
+        // Chain a multiplication onto a moved operation's output.
+        let product = builder.mul(outputs[7], outputs[8]);
+        builder.register_public_inputs(&outputs);
+        builder.register_public_input(product);
+
+        let arithmetic_id = Gate::<F, D>::id(&ArithmeticGate::new_from_config(&builder.config));
+        let fill = builder
+            .gate_fill_report()
+            .into_iter()
+            .find(|fill| fill.gate_id == arithmetic_id)
+            .unwrap();
+        assert_eq!((fill.num_instances, fill.used_ops), (3, 10));
+        assert_eq!(
+            fill.total_ops,
+            3 * builder.num_base_arithmetic_ops_per_gate()
+        );
+
+        let data = builder.build::<C>();
+        assert_eq!(data.prover_only.merged_rows.len(), 2);
+
+        let values = [F::TWO, F::from_canonical_u64(3), F::NEG_ONE];
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&inputs, &values);
+        let proof = data.prove(pw)?;
+        let expected = (0..3).flat_map(|i| {
+            let x = values[i];
+            [x * x, x * values[(i + 1) % 3], x * values[(i + 2) % 3]]
+        });
+        assert!(proof.public_inputs[..9].iter().copied().eq(expected));
+        assert_eq!(
+            proof.public_inputs[9],
+            proof.public_inputs[7] * proof.public_inputs[8]
+        );
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_watches() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let unset = builder.add_virtual_target();
+        let square = builder.mul(x, x);
+        let cube = builder.mul(square, x);
+        builder.register_public_input(cube);
+        builder.register_watch(x, "x");
+        builder.register_watch(square, "x^2");
+        builder.register_watch(unset, "unset");
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u64(3));
+        let witness = generate_partial_witness(pw, &data.prover_only, &data.common);
+        let values = data.prover_only.watched_values(&witness);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["x"], F::from_canonical_u64(3));
+        assert_eq!(values["x^2"], F::from_canonical_u64(9));
+
+        // Watches survive serialization.
+        let generator_serializer = DefaultGeneratorSerializer::<C, D> {
+            _phantom: PhantomData,
+        };
+        let bytes = data
+            .prover_only
+            .to_bytes(&generator_serializer, &data.common)
+            .unwrap();
+        let prover_only = ProverOnlyCircuitData::<F, C, D>::from_bytes(
+            &bytes,
+            &generator_serializer,
+            &data.common,
+        )
+        .unwrap();
+        assert_eq!(prover_only.watches, data.prover_only.watches);
+        Ok(())
+    }
+}
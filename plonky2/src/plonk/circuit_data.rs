@@ -1,10 +1,13 @@
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::ops::{Range, RangeFrom};
 
 use anyhow::Result;
-use serde::Serialize;
+use keccak_hash::keccak;
+use plonky2_maybe_rayon::ThreadPool;
+use serde::{Deserialize, Serialize};
 
 use super::circuit_builder::LookupWire;
 use crate::field::extension::Extendable;
@@ -24,21 +27,29 @@ use crate::gates::selectors::SelectorsInfo;
 use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::ext_target::ExtensionTarget;
-use crate::iop::generator::{generate_partial_witness, WitnessGeneratorRef};
+use crate::iop::generator::{
+    generate_partial_witness, generate_partial_witness_with_profile, WitnessGenerationProfile,
+    WitnessGeneratorRef,
+};
 use crate::iop::target::Target;
-use crate::iop::witness::{PartialWitness, PartitionWitness};
+use crate::iop::witness::{PartialWitness, PartitionWitness, Witness};
+use crate::plonk::batch_prover::BatchProver;
 use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_stats::CircuitStats;
 use crate::plonk::config::{GenericConfig, Hasher};
+use crate::plonk::diagnostics::CircuitDiagnostics;
 use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs};
-use crate::plonk::prover::prove;
+use crate::plonk::prover::{prove, prove_with_options, ProverOptions};
+use crate::plonk::public_input_layout::PublicInputLayout;
 use crate::plonk::verifier::verify;
 use crate::util::serialization::{
-    Buffer, GateSerializer, IoResult, Read, WitnessGeneratorSerializer, Write,
+    ArtifactKind, Buffer, GateSerializer, IoError, IoResult, Read, Remaining,
+    WitnessGeneratorSerializer, Write,
 };
 use crate::util::timing::TimingTree;
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CircuitConfig {
     pub num_wires: usize,
     pub num_routed_wires: usize,
@@ -109,6 +120,21 @@ impl CircuitConfig {
             ..Self::standard_recursion_config()
         }
     }
+
+    /// A config for the final wrapping of a proof, targeting ~100 bit security like
+    /// `standard_recursion_config`. Its high FRI rate yields small proofs, at the cost of slower
+    /// proving.
+    pub fn wrap_config() -> Self {
+        let standard = Self::standard_recursion_config();
+        Self {
+            fri_config: FriConfig {
+                rate_bits: 7,
+                num_query_rounds: 12,
+                ..standard.fri_config
+            },
+            ..standard
+        }
+    }
 }
 
 /// Mock circuit data to only do witness generation without generating a proof.
@@ -144,6 +170,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::CircuitData)?;
         buffer.write_circuit_data(self, gate_serializer, generator_serializer)?;
         Ok(buffer)
     }
@@ -154,7 +181,10 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<Self> {
         let mut buffer = Buffer::new(bytes);
-        buffer.read_circuit_data(gate_serializer, generator_serializer)
+        buffer.read_header(ArtifactKind::CircuitData)?;
+        let data = buffer.read_circuit_data(gate_serializer, generator_serializer)?;
+        buffer.ensure_empty()?;
+        Ok(data)
     }
 
     pub fn prove(&self, inputs: PartialWitness<F>) -> Result<ProofWithPublicInputs<F, C, D>> {
@@ -166,6 +196,53 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         )
     }
 
+    /// Returns a prover for many proofs of this circuit, which shares their common setup.
+    pub fn batch_prover(&self) -> BatchProver<'_, F, C, D> {
+        BatchProver::new(&self.prover_only, &self.common)
+    }
+
+    /// Like `prove`, but with the given [`ProverOptions`], e.g. to bound its memory usage.
+    pub fn prove_with_options(
+        &self,
+        inputs: PartialWitness<F>,
+        options: &ProverOptions,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_options::<F, C, D>(
+            &self.prover_only,
+            &self.common,
+            inputs,
+            options,
+            &mut TimingTree::default(),
+        )
+    }
+
+    /// Like `prove`, but runs on `thread_pool` rather than on the global rayon pool.
+    pub fn prove_in_thread_pool(
+        &self,
+        inputs: PartialWitness<F>,
+        thread_pool: &ThreadPool,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        thread_pool.install(|| self.prove(inputs))
+    }
+
+    /// Returns the [`circuit_fingerprint`] of this circuit.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        circuit_fingerprint::<F, C, D>(&self.common, &self.verifier_only.circuit_digest)
+    }
+
+    /// Returns statistics about the size and shape of this circuit.
+    pub fn stats(&self) -> CircuitStats {
+        CircuitStats::new(&self.prover_only, &self.common)
+    }
+
+    /// Generates a witness from `inputs`, and returns which generators it spent its time on.
+    pub fn profile_witness_generation(
+        &self,
+        inputs: PartialWitness<F>,
+    ) -> WitnessGenerationProfile {
+        generate_partial_witness_with_profile(inputs, &self.prover_only, &self.common).1
+    }
+
     pub fn verify(&self, proof_with_pis: ProofWithPublicInputs<F, C, D>) -> Result<()> {
         verify::<F, C, D>(proof_with_pis, &self.verifier_only, &self.common)
     }
@@ -241,6 +318,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::ProverCircuitData)?;
         buffer.write_prover_circuit_data(self, gate_serializer, generator_serializer)?;
         Ok(buffer)
     }
@@ -251,7 +329,10 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<Self> {
         let mut buffer = Buffer::new(bytes);
-        buffer.read_prover_circuit_data(gate_serializer, generator_serializer)
+        buffer.read_header(ArtifactKind::ProverCircuitData)?;
+        let data = buffer.read_prover_circuit_data(gate_serializer, generator_serializer)?;
+        buffer.ensure_empty()?;
+        Ok(data)
     }
 
     pub fn prove(&self, inputs: PartialWitness<F>) -> Result<ProofWithPublicInputs<F, C, D>> {
@@ -262,6 +343,40 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
             &mut TimingTree::default(),
         )
     }
+
+    /// Returns a prover for many proofs of this circuit, which shares their common setup.
+    pub fn batch_prover(&self) -> BatchProver<'_, F, C, D> {
+        BatchProver::new(&self.prover_only, &self.common)
+    }
+
+    /// Like `prove`, but with the given [`ProverOptions`], e.g. to bound its memory usage.
+    pub fn prove_with_options(
+        &self,
+        inputs: PartialWitness<F>,
+        options: &ProverOptions,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_options::<F, C, D>(
+            &self.prover_only,
+            &self.common,
+            inputs,
+            options,
+            &mut TimingTree::default(),
+        )
+    }
+
+    /// Like `prove`, but runs on `thread_pool` rather than on the global rayon pool.
+    pub fn prove_in_thread_pool(
+        &self,
+        inputs: PartialWitness<F>,
+        thread_pool: &ThreadPool,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        thread_pool.install(|| self.prove(inputs))
+    }
+
+    /// Returns the [`circuit_fingerprint`] of this circuit.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        circuit_fingerprint::<F, C, D>(&self.common, &self.prover_only.circuit_digest)
+    }
 }
 
 /// Circuit data required by the prover.
@@ -280,6 +395,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
 {
     pub fn to_bytes(&self, gate_serializer: &dyn GateSerializer<F, D>) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::VerifierCircuitData)?;
         buffer.write_verifier_circuit_data(self, gate_serializer)?;
         Ok(buffer)
     }
@@ -289,7 +405,10 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         gate_serializer: &dyn GateSerializer<F, D>,
     ) -> IoResult<Self> {
         let mut buffer = Buffer::new(&bytes);
-        buffer.read_verifier_circuit_data(gate_serializer)
+        buffer.read_header(ArtifactKind::VerifierCircuitData)?;
+        let data = buffer.read_verifier_circuit_data(gate_serializer)?;
+        buffer.ensure_empty()?;
+        Ok(data)
     }
 
     pub fn verify(&self, proof_with_pis: ProofWithPublicInputs<F, C, D>) -> Result<()> {
@@ -302,6 +421,57 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     ) -> Result<()> {
         compressed_proof_with_pis.verify(&self.verifier_only, &self.common)
     }
+
+    /// Returns the [`circuit_fingerprint`] of this circuit.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        circuit_fingerprint::<F, C, D>(&self.common, &self.verifier_only.circuit_digest)
+    }
+}
+
+/// Returns a canonical Keccak hash of a circuit, covering its config, its gate types, and, through
+/// `circuit_digest`, the placement of its gates, its constants and its copy constraints.
+///
+/// It only depends on the circuit, and not on e.g. the order in which generators were added, so
+/// circuits built by the same code on different machines have the same fingerprint. Comparing
+/// fingerprints checks that a prover and a verifier were built from the same circuit, and they can
+/// be used as cache keys for circuit data.
+pub fn circuit_fingerprint<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    common_data: &CommonCircuitData<F, D>,
+    circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<F>>::Hash,
+) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    write_fingerprint_data::<F, C, D>(&mut bytes, common_data, circuit_digest)
+        .expect("Writing to a byte-vector cannot fail.");
+    keccak(bytes).0
+}
+
+fn write_fingerprint_data<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    bytes: &mut Vec<u8>,
+    common_data: &CommonCircuitData<F, D>,
+    circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<F>>::Hash,
+) -> IoResult<()> {
+    bytes.write_circuit_config(&common_data.config)?;
+    bytes.write_fri_params(&common_data.fri_params)?;
+    bytes.write_selectors_info(&common_data.selectors_info)?;
+    bytes.write_usize(common_data.num_public_inputs)?;
+    bytes.write_usize(common_data.luts.len())?;
+    for lut in &common_data.luts {
+        bytes.write_lut(lut)?;
+    }
+    // Gates are identified by their IDs, which include their parameters.
+    bytes.write_usize(common_data.gates.len())?;
+    for gate in &common_data.gates {
+        bytes.write_string(&gate.0.id())?;
+    }
+    bytes.write_hash::<F, C::Hasher>(*circuit_digest)
 }
 
 /// Circuit data required by the prover, but not the verifier.
@@ -335,6 +505,14 @@ pub struct ProverOnlyCircuitData<
     pub lookup_rows: Vec<LookupWire>,
     /// A vector of (looking_in, looking_out) pairs for for each lookup table index.
     pub lut_to_lookups: Vec<Lookup>,
+    /// The sorted rows of gates which were merged into other gates when building the circuit. They
+    /// are still part of the target space, but not of the trace.
+    pub merged_rows: Vec<usize>,
+    /// Targets registered with [`CircuitBuilder::register_watch`], along with their labels.
+    pub watches: Vec<(String, Target)>,
+    /// The gate and context stack of each row, used to explain witness generation and proving
+    /// failures.
+    pub diagnostics: CircuitDiagnostics,
 }
 
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
@@ -346,6 +524,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         common_data: &CommonCircuitData<F, D>,
     ) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::ProverOnlyCircuitData)?;
         buffer.write_prover_only_circuit_data(self, generator_serializer, common_data)?;
         Ok(buffer)
     }
@@ -356,33 +535,82 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         common_data: &CommonCircuitData<F, D>,
     ) -> IoResult<Self> {
         let mut buffer = Buffer::new(bytes);
-        buffer.read_prover_only_circuit_data(generator_serializer, common_data)
+        buffer.read_header(ArtifactKind::ProverOnlyCircuitData)?;
+        let data = buffer.read_prover_only_circuit_data(generator_serializer, common_data)?;
+        buffer.ensure_empty()?;
+        Ok(data)
+    }
+
+    /// Returns the values of the watched targets which are set in `witness`, by label.
+    pub fn watched_values(&self, witness: &PartitionWitness<F>) -> BTreeMap<String, F> {
+        self.watches
+            .iter()
+            .filter_map(|(label, target)| Some((label.clone(), witness.try_get_target(*target)?)))
+            .collect()
     }
 }
 
 /// Circuit data required by the verifier, but not the prover.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct VerifierOnlyCircuitData<C: GenericConfig<D>, const D: usize> {
     /// A commitment to each constant polynomial and each permutation polynomial.
     pub constants_sigmas_cap: MerkleCap<C::F, C::Hasher>,
     /// A digest of the "circuit" (i.e. the instance, minus public inputs), which can be used to
-    /// seed Fiat-Shamir.
+    /// seed Fiat-Shamir. It covers the public input layout, if there is one.
     pub circuit_digest: <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+    /// The names and types of the public inputs registered with a name.
+    #[serde(default)]
+    pub public_input_layout: PublicInputLayout,
 }
 
 impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
     pub fn to_bytes(&self) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::VerifierOnlyCircuitData)?;
         buffer.write_verifier_only_circuit_data(self)?;
         Ok(buffer)
     }
 
     pub fn from_bytes(bytes: Vec<u8>) -> IoResult<Self> {
         let mut buffer = Buffer::new(&bytes);
-        buffer.read_verifier_only_circuit_data()
+        buffer.read_header(ArtifactKind::VerifierOnlyCircuitData)?;
+        let data = buffer.read_verifier_only_circuit_data()?;
+        buffer.ensure_empty()?;
+        Ok(data)
+    }
+
+    /// Encodes this data as a JSON object with fields `constants_sigmas_cap`, an array of hashes,
+    /// `circuit_digest`, and `public_input_layout`, holding the named public inputs under
+    /// `entries`. See [`ProofWithPublicInputs::to_json`] for the encoding of hashes.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Verifier data is always serializable to JSON.")
+    }
+
+    pub fn from_json(json: &str) -> IoResult<Self> {
+        serde_json::from_str(json).map_err(|_| IoError::InvalidData)
     }
 }
 
+/// The JSON encoding of [`CommonCircuitData`], with gates replaced by their binary encodings.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct CommonCircuitDataJson<F: RichField> {
+    config: CircuitConfig,
+    fri_params: FriParams,
+    gates: Vec<Vec<u8>>,
+    selectors_info: SelectorsInfo,
+    quotient_degree_factor: usize,
+    num_gate_constraints: usize,
+    num_constants: usize,
+    num_public_inputs: usize,
+    k_is: Vec<F>,
+    num_partial_products: usize,
+    num_lookup_polys: usize,
+    num_lookup_selectors: usize,
+    luts: Vec<LookupTable>,
+}
+
 /// Circuit data required by both the prover and the verifier.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize)]
 pub struct CommonCircuitData<F: RichField + Extendable<D>, const D: usize> {
@@ -426,6 +654,7 @@ pub struct CommonCircuitData<F: RichField + Extendable<D>, const D: usize> {
 impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
     pub fn to_bytes(&self, gate_serializer: &dyn GateSerializer<F, D>) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::CommonCircuitData)?;
         buffer.write_common_circuit_data(self, gate_serializer)?;
         Ok(buffer)
     }
@@ -435,7 +664,75 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
         gate_serializer: &dyn GateSerializer<F, D>,
     ) -> IoResult<Self> {
         let mut buffer = Buffer::new(&bytes);
-        buffer.read_common_circuit_data(gate_serializer)
+        buffer.read_header(ArtifactKind::CommonCircuitData)?;
+        let data = buffer.read_common_circuit_data(gate_serializer)?;
+        buffer.ensure_empty()?;
+        Ok(data)
+    }
+
+    /// Encodes this data as a JSON object with one field per struct field. Gates can't be
+    /// reconstructed from their names, so `gates` holds each gate's binary encoding under
+    /// `gate_serializer`, as an array of bytes.
+    pub fn to_json(&self, gate_serializer: &dyn GateSerializer<F, D>) -> IoResult<String> {
+        let gates = self
+            .gates
+            .iter()
+            .map(|gate| {
+                let mut bytes = Vec::new();
+                bytes.write_gate(gate, gate_serializer, self)?;
+                Ok(bytes)
+            })
+            .collect::<IoResult<Vec<_>>>()?;
+        let json = CommonCircuitDataJson {
+            config: self.config.clone(),
+            fri_params: self.fri_params.clone(),
+            gates,
+            selectors_info: self.selectors_info.clone(),
+            quotient_degree_factor: self.quotient_degree_factor,
+            num_gate_constraints: self.num_gate_constraints,
+            num_constants: self.num_constants,
+            num_public_inputs: self.num_public_inputs,
+            k_is: self.k_is.clone(),
+            num_partial_products: self.num_partial_products,
+            num_lookup_polys: self.num_lookup_polys,
+            num_lookup_selectors: self.num_lookup_selectors,
+            luts: self.luts.clone(),
+        };
+        serde_json::to_string(&json).map_err(|_| IoError::InvalidData)
+    }
+
+    pub fn from_json(json: &str, gate_serializer: &dyn GateSerializer<F, D>) -> IoResult<Self> {
+        let json: CommonCircuitDataJson<F> =
+            serde_json::from_str(json).map_err(|_| IoError::InvalidData)?;
+
+        // As in `read_common_circuit_data`, the gates are decoded against the common data
+        // without gates.
+        let mut common_data = CommonCircuitData {
+            config: json.config,
+            fri_params: json.fri_params,
+            gates: vec![],
+            selectors_info: json.selectors_info,
+            quotient_degree_factor: json.quotient_degree_factor,
+            num_gate_constraints: json.num_gate_constraints,
+            num_constants: json.num_constants,
+            num_public_inputs: json.num_public_inputs,
+            k_is: json.k_is,
+            num_partial_products: json.num_partial_products,
+            num_lookup_polys: json.num_lookup_polys,
+            num_lookup_selectors: json.num_lookup_selectors,
+            luts: json.luts,
+        };
+        common_data.gates = json
+            .gates
+            .iter()
+            .map(|bytes| {
+                let mut buffer = Buffer::new(bytes);
+                let gate = buffer.read_gate::<F, D>(gate_serializer, &common_data)?;
+                buffer.ensure_empty()?;
+                Ok(gate)
+            })
+            .collect::<IoResult<_>>()?;
+        Ok(common_data)
     }
 
     pub const fn degree_bits(&self) -> usize {
@@ -646,3 +943,48 @@ pub struct VerifierCircuitTarget {
     /// seed Fiat-Shamir.
     pub circuit_digest: HashOutTarget,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn build_circuit(config: CircuitConfig, exponent: u64) -> CircuitData<F, C, D> {
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, exponent);
+        builder.register_public_input(y);
+        builder.build::<C>()
+    }
+
+    #[test]
+    fn test_circuit_fingerprint() {
+        let config = CircuitConfig::standard_recursion_config();
+        let data = build_circuit(config.clone(), 5);
+        assert_eq!(
+            data.fingerprint(),
+            build_circuit(config.clone(), 5).fingerprint()
+        );
+        assert_ne!(
+            data.fingerprint(),
+            build_circuit(config.clone(), 6).fingerprint()
+        );
+        let zk_config = CircuitConfig {
+            zero_knowledge: true,
+            ..config
+        };
+        assert_ne!(
+            data.fingerprint(),
+            build_circuit(zk_config, 5).fingerprint()
+        );
+
+        // The prover and verifier halves agree with the full circuit data.
+        let fingerprint = data.fingerprint();
+        assert_eq!(data.verifier_data().fingerprint(), fingerprint);
+        assert_eq!(data.prover_data().fingerprint(), fingerprint);
+    }
+}
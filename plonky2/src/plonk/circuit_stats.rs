@@ -0,0 +1,169 @@
+//! Statistics about built circuits, e.g. to track circuit growth in CI.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
+use crate::plonk::config::GenericConfig;
+
+/// A summary of the size and shape of a built circuit. It can be serialized, e.g. to JSON, so that
+/// it can be compared across versions.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CircuitStats {
+    pub degree_bits: usize,
+    /// The number of rows of the trace, including blinding and padding rows.
+    pub num_rows: usize,
+    /// The number of rows taken up by each gate type, keyed by gate ID.
+    pub gate_counts: BTreeMap<String, usize>,
+    /// The number of rows whose gate has constraints of each degree.
+    pub rows_by_constraint_degree: BTreeMap<usize, usize>,
+    pub quotient_degree_factor: usize,
+    pub num_public_inputs: usize,
+    pub luts: Vec<LutStats>,
+    pub routed_wires: RoutedWireStats,
+}
+
+/// The usage of a lookup table.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct LutStats {
+    /// The number of entries in the table.
+    pub table_size: usize,
+    /// The number of lookups into the table.
+    pub num_lookups: usize,
+}
+
+/// How many routed wires are involved in copy constraints. A routed wire is used if it is copied
+/// to any other wire.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RoutedWireStats {
+    pub num_routed_wires: usize,
+    /// The total number of used routed wires, over all rows.
+    pub num_used: usize,
+    /// The largest number of used routed wires in any row. If this is well below
+    /// `num_routed_wires`, a config with fewer routed wires may suffice.
+    pub max_used_per_row: usize,
+    /// The fraction of the trace's routed wires which are used.
+    pub utilization: f64,
+}
+
+impl CircuitStats {
+    pub fn new<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+        prover_data: &ProverOnlyCircuitData<F, C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Self {
+        let mut gate_counts = BTreeMap::new();
+        let mut rows_by_constraint_degree = BTreeMap::new();
+        let trace_gates = prover_data
+            .diagnostics
+            .row_gates
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| prover_data.merged_rows.binary_search(row).is_err());
+        for (_, &gate) in trace_gates {
+            let gate = &common_data.gates[gate].0;
+            *gate_counts.entry(gate.id()).or_default() += 1;
+            *rows_by_constraint_degree.entry(gate.degree()).or_default() += 1;
+        }
+
+        let luts = common_data
+            .luts
+            .iter()
+            .zip(&prover_data.lut_to_lookups)
+            .map(|(lut, lookups)| LutStats {
+                table_size: lut.len(),
+                num_lookups: lookups.len(),
+            })
+            .collect();
+
+        // A routed wire is unused if its sigma value maps it to itself.
+        let num_routed_wires = common_data.config.num_routed_wires;
+        let used_per_row = prover_data
+            .sigmas
+            .iter()
+            .zip(&prover_data.subgroup)
+            .map(|(sigmas, &x)| {
+                sigmas
+                    .iter()
+                    .zip(&common_data.k_is)
+                    .filter(|&(&sigma, &k)| sigma != k * x)
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        let num_used = used_per_row.iter().sum();
+        let num_rows = common_data.degree();
+        let routed_wires = RoutedWireStats {
+            num_routed_wires,
+            num_used,
+            max_used_per_row: used_per_row.into_iter().max().unwrap_or(0),
+            utilization: num_used as f64 / (num_rows * num_routed_wires) as f64,
+        };
+
+        Self {
+            degree_bits: common_data.degree_bits(),
+            num_rows,
+            gate_counts,
+            rows_by_constraint_degree,
+            quotient_degree_factor: common_data.quotient_degree_factor,
+            num_public_inputs: common_data.num_public_inputs,
+            luts,
+            routed_wires,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use alloc::vec;
+
+    use super::*;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_circuit_stats() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.mul(x, x);
+        builder.register_public_input(y);
+        let lut_index = builder.add_lookup_table_from_pairs(Arc::new(vec![(0, 1), (1, 0)]));
+        let looked_up = builder.add_lookup_from_index(x, lut_index);
+        builder.register_public_input(looked_up);
+        let data = builder.build::<C>();
+        let stats = data.stats();
+
+        assert_eq!(stats.num_rows, data.common.degree());
+        assert_eq!(stats.gate_counts.values().sum::<usize>(), stats.num_rows);
+        assert_eq!(
+            stats.rows_by_constraint_degree.values().sum::<usize>(),
+            stats.num_rows
+        );
+        assert_eq!(stats.gate_counts["ArithmeticGate { num_ops: 20 }"], 1);
+        assert_eq!(stats.gate_counts["PublicInputGate"], 1);
+        assert!(stats.gate_counts["NoopGate"] > 0);
+        assert_eq!(stats.num_public_inputs, 2);
+        assert_eq!(
+            stats.luts,
+            vec![LutStats {
+                table_size: 2,
+                num_lookups: 1
+            }]
+        );
+
+        // `x` is copied to two multiplicands and a lookup, and `y` to the public input hashing.
+        let routed = &stats.routed_wires;
+        assert!(routed.num_used >= 4);
+        assert!(routed.max_used_per_row <= routed.num_routed_wires);
+        assert!(routed.utilization > 0.0 && routed.utilization < 1.0);
+    }
+}
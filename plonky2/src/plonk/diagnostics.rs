@@ -0,0 +1,218 @@
+//! Information about where a circuit's gates come from, used to explain witness generation and
+//! proving failures in terms of the code which built the circuit.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::hash::hash_types::{HashOut, RichField};
+use crate::iop::target::Target;
+use crate::iop::wire::Wire;
+use crate::iop::witness::MatrixWitness;
+use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
+use crate::plonk::config::GenericConfig;
+use crate::plonk::vanishing_poly::evaluate_gate_constraints;
+use crate::plonk::vars::EvaluationVars;
+
+/// For each row of a circuit, the gate placed there and the contexts which were open when it was
+/// added. Rows are numbered as in `CircuitBuilder`, i.e. including rows of merged gates.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CircuitDiagnostics {
+    /// The index of each row's gate in [`CommonCircuitData::gates`].
+    pub row_gates: Vec<usize>,
+    /// The stacks of contexts which were open when gates were added, e.g. `root > verify proof`.
+    pub contexts: Vec<String>,
+    /// The index in `contexts` of each row's context stack.
+    pub row_contexts: Vec<usize>,
+    /// The index in `contexts` of the context stack each generator was added in. Generators of
+    /// gates are attributed to their gate's row. Empty for circuits serialized before generator
+    /// contexts were recorded.
+    pub generator_contexts: Vec<usize>,
+}
+
+impl CircuitDiagnostics {
+    /// Records the context stack of a newly added row.
+    pub(crate) fn push_row(&mut self, context: String) {
+        let index = self.context_index(context);
+        self.row_contexts.push(index);
+    }
+
+    /// Records the context stack of `num_generators` newly added generators.
+    pub(crate) fn push_generators(&mut self, context: String, num_generators: usize) {
+        let index = self.context_index(context);
+        let len = self.generator_contexts.len() + num_generators;
+        self.generator_contexts.resize(len, index);
+    }
+
+    /// Records `num_generators` newly added generators of the gate at `row`.
+    pub(crate) fn push_row_generators(&mut self, row: usize, num_generators: usize) {
+        let index = self.row_contexts[row];
+        let len = self.generator_contexts.len() + num_generators;
+        self.generator_contexts.resize(len, index);
+    }
+
+    fn context_index(&mut self, context: String) -> usize {
+        if self.contexts.last() != Some(&context) {
+            self.contexts.push(context);
+        }
+        self.contexts.len() - 1
+    }
+
+    /// The context stack which the generator at `index` was added in, if known.
+    pub fn generator_context(&self, index: usize) -> Option<&str> {
+        let &context = self.generator_contexts.get(index)?;
+        Some(&self.contexts[context])
+    }
+
+    /// Describes `target` for error messages, e.g. as `addend of operation 3 of ArithmeticGate {
+    /// num_ops: 20 } at row 12, added in root > verify proof`.
+    pub fn describe_target<F: RichField + Extendable<D>, const D: usize>(
+        &self,
+        target: Target,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> String {
+        match target {
+            Target::Wire(Wire { row, column }) => match self.row_gates.get(row) {
+                Some(&gate) => {
+                    let gate = &common_data.gates[gate].0;
+                    format!(
+                        "{} of {} at row {}{}",
+                        gate.wire_name(column),
+                        gate.id(),
+                        row,
+                        self.describe_context(row)
+                    )
+                }
+                None => format!("wire {column} at row {row}"),
+            },
+            Target::VirtualTarget { index } => format!("virtual target {index}"),
+        }
+    }
+
+    fn describe_context(&self, row: usize) -> String {
+        match self.row_contexts.get(row) {
+            Some(&context) => format!(", added in {}", self.contexts[context]),
+            None => String::new(),
+        }
+    }
+}
+
+/// Evaluates each gate's constraints on the trace, and describes the first one which isn't
+/// satisfied. This is slow, so it's only meant to be called once proving has failed.
+pub(crate) fn describe_unsatisfied_constraint<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    witness: &MatrixWitness<F>,
+    public_inputs_hash: &HashOut<F>,
+) -> String {
+    let constants = prover_data.constants_sigmas_commitment.polynomials
+        [common_data.constants_range()]
+    .iter()
+    .map(|poly| poly.clone().fft().values)
+    .collect::<Vec<_>>();
+    let diagnostics = &prover_data.diagnostics;
+    let target_rows = (0..diagnostics.row_gates.len())
+        .filter(|row| prover_data.merged_rows.binary_search(row).is_err());
+
+    for (trace_row, row) in target_rows.enumerate() {
+        let local_constants = constants
+            .iter()
+            .map(|values| values[trace_row].into())
+            .collect::<Vec<_>>();
+        let local_wires = witness
+            .wire_values
+            .iter()
+            .map(|values| values[trace_row].into())
+            .collect::<Vec<_>>();
+        let vars = EvaluationVars {
+            local_constants: &local_constants,
+            local_wires: &local_wires,
+            public_inputs_hash,
+        };
+        let constraints = evaluate_gate_constraints::<F, D>(common_data, vars);
+        if let Some(i) = constraints.iter().position(|c| !c.is_zero()) {
+            let gate = &common_data.gates[diagnostics.row_gates[row]].0;
+            return format!(
+                "Constraint {} of {} is not satisfied at row {}{}.",
+                i,
+                gate.id(),
+                row,
+                diagnostics.describe_context(row)
+            );
+        }
+    }
+    "All gate constraints are satisfied, so a lookup constraint must be violated.".into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iop::generator::generate_partial_witness;
+    use crate::iop::witness::{PartialWitness, Witness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+    use crate::plonk::config::{Hasher, PoseidonGoldilocksConfig};
+    use crate::with_context;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Builds a circuit squaring `x` within a `square` context.
+    fn square_circuit() -> (Target, Target, CircuitData<F, C, D>) {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = with_context!(builder, "square", builder.mul(x, x));
+        builder.register_public_input(y);
+        (x, y, builder.build::<C>())
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "set output of operation 0 of ArithmeticGate { num_ops: 20 } at row 0, added in root > square to 4, but its partition was already set to 5"
+    )]
+    fn test_conflicting_witness() {
+        let (x, y, data) = square_circuit();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        pw.set_target(y, F::from_canonical_u64(5));
+        generate_partial_witness(pw, &data.prover_only, &data.common);
+    }
+
+    #[test]
+    fn test_unsatisfied_constraint() {
+        let (x, _, data) = square_circuit();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        let partition_witness = generate_partial_witness(pw, &data.prover_only, &data.common);
+        let public_inputs = partition_witness.get_targets(&data.prover_only.public_inputs);
+        let public_inputs_hash = <C as GenericConfig<D>>::InnerHasher::hash_no_pad(&public_inputs);
+        let mut witness = partition_witness.full_witness();
+
+        let description = describe_unsatisfied_constraint(
+            &data.prover_only,
+            &data.common,
+            &witness,
+            &public_inputs_hash,
+        );
+        assert!(description.starts_with("All gate constraints are satisfied"));
+
+        witness.wire_values[3][0] = F::from_canonical_u64(5);
+        let description = describe_unsatisfied_constraint(
+            &data.prover_only,
+            &data.common,
+            &witness,
+            &public_inputs_hash,
+        );
+        assert_eq!(
+            description,
+            "Constraint 0 of ArithmeticGate { num_ops: 20 } is not satisfied at row 0, added in root > square."
+        );
+    }
+}
@@ -13,6 +13,7 @@ use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::challenger::{Challenger, RecursiveChallenger};
 use crate::iop::target::Target;
+use crate::iop::transcript::Transcript;
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
@@ -24,6 +25,7 @@ use crate::plonk::proof::{
 use crate::util::reverse_bits;
 
 fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    challenger: &mut Challenger<F, C::Hasher>,
     public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
     wires_cap: &MerkleCap<F, C::Hasher>,
     plonk_zs_partial_products_cap: &MerkleCap<F, C::Hasher>,
@@ -38,15 +40,19 @@ fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, cons
     let config = &common_data.config;
     let num_challenges = config.num_challenges;
 
-    let mut challenger = Challenger::<F, C::Hasher>::new();
     let has_lookup = common_data.num_lookup_polys != 0;
 
     // Observe the instance.
+    challenger.set_transcript_label("circuit digest");
     challenger.observe_hash::<C::Hasher>(*circuit_digest);
+    challenger.set_transcript_label("public inputs hash");
     challenger.observe_hash::<C::InnerHasher>(public_inputs_hash);
 
+    challenger.set_transcript_label("wires cap");
     challenger.observe_cap::<C::Hasher>(wires_cap);
+    challenger.set_transcript_label("plonk betas");
     let plonk_betas = challenger.get_n_challenges(num_challenges);
+    challenger.set_transcript_label("plonk gammas");
     let plonk_gammas = challenger.get_n_challenges(num_challenges);
 
     // If there are lookups in the circuit, we should get delta challenges as well.
@@ -55,6 +61,7 @@ fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, cons
         let num_lookup_challenges = NUM_COINS_LOOKUP * num_challenges;
         let mut deltas = Vec::with_capacity(num_lookup_challenges);
         let num_additional_challenges = num_lookup_challenges - 2 * num_challenges;
+        challenger.set_transcript_label("plonk deltas");
         let additional = challenger.get_n_challenges(num_additional_challenges);
         deltas.extend(&plonk_betas);
         deltas.extend(&plonk_gammas);
@@ -65,12 +72,17 @@ fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, cons
     };
 
     // `plonk_zs_partial_products_cap` also contains the commitment to lookup polynomials.
+    challenger.set_transcript_label("zs partial products cap");
     challenger.observe_cap::<C::Hasher>(plonk_zs_partial_products_cap);
+    challenger.set_transcript_label("plonk alphas");
     let plonk_alphas = challenger.get_n_challenges(num_challenges);
 
+    challenger.set_transcript_label("quotient polys cap");
     challenger.observe_cap::<C::Hasher>(quotient_polys_cap);
+    challenger.set_transcript_label("plonk zeta");
     let plonk_zeta = challenger.get_extension_challenge::<D>();
 
+    challenger.set_transcript_label("openings");
     challenger.observe_openings(&openings.to_fri_openings());
 
     Ok(ProofChallenges {
@@ -109,6 +121,39 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
         circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
         common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<ProofChallenges<F, D>> {
+        self.get_challenges_with(
+            &mut Challenger::new(),
+            public_inputs_hash,
+            circuit_digest,
+            common_data,
+        )
+    }
+
+    /// Records the Fiat-Shamir transcript of the verifier of this proof, see [`Transcript`].
+    pub fn transcript(
+        &self,
+        circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<Transcript<F>> {
+        let mut challenger = Challenger::new_recording();
+        self.get_challenges_with(
+            &mut challenger,
+            self.get_public_inputs_hash(),
+            circuit_digest,
+            common_data,
+        )?;
+        Ok(challenger
+            .take_transcript()
+            .expect("The challenger is recording."))
+    }
+
+    fn get_challenges_with(
+        &self,
+        challenger: &mut Challenger<F, C::Hasher>,
+        public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
+        circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+        common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<ProofChallenges<F, D>> {
         let Proof {
             wires_cap,
@@ -125,6 +170,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         } = &self.proof;
 
         get_challenges::<F, C, D>(
+            challenger,
             public_inputs_hash,
             wires_cap,
             plonk_zs_partial_products_cap,
@@ -164,6 +210,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         } = &self.proof;
 
         get_challenges::<F, C, D>(
+            &mut Challenger::new(),
             public_inputs_hash,
             wires_cap,
             plonk_zs_partial_products_cap,
@@ -218,7 +265,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
                 *fri_alpha,
                 subgroup_x,
                 &precomputed_reduced_evals,
-                &common_data.fri_params,
+                common_data.fri_params.hiding,
             );
             for (i, &arity_bits) in common_data
                 .fri_params
@@ -0,0 +1,206 @@
+//! Export of a final proof for the gnark plonky2 verifier circuits.
+//!
+//! The gnark verifier reads three JSON files, holding the serde encodings of the circuit's
+//! [`CommonCircuitData`], its [`VerifierOnlyCircuitData`] and the [`ProofWithPublicInputs`]. Field
+//! elements are written as the integer given by their canonical representative, and hashes as
+//! objects `{"elements": [..]}` of four such integers. Only Goldilocks proofs with `D = 2` and
+//! Poseidon hashes can be verified there, which the bounds of [`GnarkExport::new`] enforce.
+//!
+//! A [`GnarkManifest`] describes the export, so that a consumer can check which circuit a proof
+//! targets without parsing the other files.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::field::goldilocks_field::GoldilocksField;
+use crate::field::types::PrimeField64;
+use crate::hash::hash_types::HashOut;
+use crate::hash::merkle_tree::MerkleCap;
+use crate::hash::poseidon::PoseidonHash;
+use crate::plonk::circuit_data::{CommonCircuitData, VerifierOnlyCircuitData};
+use crate::plonk::config::GenericConfig;
+use crate::plonk::proof::ProofWithPublicInputs;
+
+/// The version of the export format, written in the manifest.
+pub const GNARK_EXPORT_VERSION: u16 = 1;
+
+/// The file holding the common circuit data.
+pub const COMMON_CIRCUIT_DATA_FILE: &str = "common_circuit_data.json";
+/// The file holding the verifier-only circuit data.
+pub const VERIFIER_ONLY_CIRCUIT_DATA_FILE: &str = "verifier_only_circuit_data.json";
+/// The file holding the proof and its public inputs.
+pub const PROOF_WITH_PUBLIC_INPUTS_FILE: &str = "proof_with_public_inputs.json";
+/// The file holding the [`GnarkManifest`].
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// The verifier-only data as read by gnark, i.e. without the public input layout.
+#[derive(Serialize)]
+struct GnarkVerifierOnlyCircuitData<'a> {
+    constants_sigmas_cap: &'a MerkleCap<GoldilocksField, PoseidonHash>,
+    circuit_digest: &'a HashOut<GoldilocksField>,
+}
+
+/// A description of a [`GnarkExport`]. Its fields are written in a fixed order, so that
+/// manifests of the same proof are byte-for-byte identical.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GnarkManifest {
+    /// The [`GNARK_EXPORT_VERSION`] the export was written with.
+    pub version: u16,
+    /// The field and hash of the proof, always `"goldilocks_poseidon"`.
+    pub hasher: String,
+    pub degree_bits: usize,
+    pub num_public_inputs: usize,
+    /// The canonical representatives of the public inputs.
+    pub public_inputs: Vec<u64>,
+    /// The canonical representatives of the elements of the circuit digest.
+    pub circuit_digest: Vec<u64>,
+    pub common_circuit_data_file: String,
+    pub verifier_only_circuit_data_file: String,
+    pub proof_with_public_inputs_file: String,
+}
+
+/// The files read by the gnark plonky2 verifier, along with their manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GnarkExport {
+    pub common_circuit_data: String,
+    pub verifier_only_circuit_data: String,
+    pub proof_with_public_inputs: String,
+    pub manifest: GnarkManifest,
+}
+
+impl GnarkExport {
+    pub fn new<C>(
+        proof: &ProofWithPublicInputs<GoldilocksField, C, 2>,
+        verifier_only: &VerifierOnlyCircuitData<C, 2>,
+        common: &CommonCircuitData<GoldilocksField, 2>,
+    ) -> Result<Self>
+    where
+        C: GenericConfig<2, F = GoldilocksField, Hasher = PoseidonHash>,
+    {
+        ensure!(
+            proof.public_inputs.len() == common.num_public_inputs,
+            "Expected {} public inputs, found {}",
+            common.num_public_inputs,
+            proof.public_inputs.len()
+        );
+
+        let verifier_only_json = GnarkVerifierOnlyCircuitData {
+            constants_sigmas_cap: &verifier_only.constants_sigmas_cap,
+            circuit_digest: &verifier_only.circuit_digest,
+        };
+        let manifest = GnarkManifest {
+            version: GNARK_EXPORT_VERSION,
+            hasher: "goldilocks_poseidon".to_string(),
+            degree_bits: common.degree_bits(),
+            num_public_inputs: common.num_public_inputs,
+            public_inputs: proof
+                .public_inputs
+                .iter()
+                .map(|x| x.to_canonical_u64())
+                .collect(),
+            circuit_digest: verifier_only
+                .circuit_digest
+                .elements
+                .iter()
+                .map(|x| x.to_canonical_u64())
+                .collect(),
+            common_circuit_data_file: COMMON_CIRCUIT_DATA_FILE.to_string(),
+            verifier_only_circuit_data_file: VERIFIER_ONLY_CIRCUIT_DATA_FILE.to_string(),
+            proof_with_public_inputs_file: PROOF_WITH_PUBLIC_INPUTS_FILE.to_string(),
+        };
+
+        Ok(Self {
+            common_circuit_data: serde_json::to_string(common)
+                .expect("Common circuit data is always serializable to JSON."),
+            verifier_only_circuit_data: serde_json::to_string(&verifier_only_json)
+                .expect("Verifier data is always serializable to JSON."),
+            proof_with_public_inputs: proof.to_json(),
+            manifest,
+        })
+    }
+
+    pub fn manifest_json(&self) -> String {
+        serde_json::to_string_pretty(&self.manifest).expect("Manifests are serializable to JSON.")
+    }
+
+    /// Writes the export to `dir`, which is created if needed, under the file names of the
+    /// manifest.
+    #[cfg(feature = "std")]
+    pub fn write_to_dir(&self, dir: &std::path::Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(
+            dir.join(COMMON_CIRCUIT_DATA_FILE),
+            &self.common_circuit_data,
+        )?;
+        std::fs::write(
+            dir.join(VERIFIER_ONLY_CIRCUIT_DATA_FILE),
+            &self.verifier_only_circuit_data,
+        )?;
+        std::fs::write(
+            dir.join(PROOF_WITH_PUBLIC_INPUTS_FILE),
+            &self.proof_with_public_inputs,
+        )?;
+        std::fs::write(dir.join(MANIFEST_FILE), self.manifest_json())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_gnark_export() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(x);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        let proof = data.prove(pw)?;
+
+        let export = GnarkExport::new(&proof, &data.verifier_only, &data.common)?;
+        assert_eq!(export.manifest.public_inputs, [2, 128]);
+        assert_eq!(export.manifest.degree_bits, data.common.degree_bits());
+
+        let proof_json: Value = serde_json::from_str(&export.proof_with_public_inputs).unwrap();
+        assert_eq!(proof_json["public_inputs"], serde_json::json!([2, 128]));
+        assert!(proof_json["proof"]["wires_cap"].is_array());
+
+        let verifier_json: Value =
+            serde_json::from_str(&export.verifier_only_circuit_data).unwrap();
+        let verifier_fields = verifier_json.as_object().unwrap();
+        assert_eq!(verifier_fields.len(), 2);
+        assert_eq!(
+            verifier_json["circuit_digest"]["elements"],
+            serde_json::json!(export.manifest.circuit_digest)
+        );
+
+        let common_json: Value = serde_json::from_str(&export.common_circuit_data).unwrap();
+        assert!(common_json["gates"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(Value::is_string));
+
+        let manifest: GnarkManifest = serde_json::from_str(&export.manifest_json()).unwrap();
+        assert_eq!(manifest, export.manifest);
+        Ok(())
+    }
+}
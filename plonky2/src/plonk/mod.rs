@@ -1,13 +1,25 @@
+pub mod batch_prover;
+#[cfg(feature = "std")]
+pub mod checkpoint;
 pub mod circuit_builder;
 pub mod circuit_data;
+pub mod circuit_stats;
 pub mod config;
 pub(crate) mod copy_constraint;
+pub mod diagnostics;
 mod get_challenges;
+pub mod gnark;
 pub(crate) mod permutation_argument;
 pub mod plonk_common;
 pub mod proof;
+#[cfg(feature = "cbor")]
+pub mod proof_container;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
 pub mod prover;
+pub mod public_input_layout;
 mod validate_shape;
 pub(crate) mod vanishing_poly;
 pub mod vars;
 pub mod verifier;
+pub mod verifier_spec;
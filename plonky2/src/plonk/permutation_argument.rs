@@ -85,17 +85,20 @@ impl Forest {
         }
     }
 
-    /// Assumes `compress_paths` has already been called.
-    pub fn wire_partition(&mut self) -> WirePartition {
+    /// Returns the partition of the routed wires of the trace, whose `i`th row holds the wires of
+    /// target row `trace_rows[i]`. Assumes `compress_paths` has already been called.
+    pub fn wire_partition(&mut self, trace_rows: &[usize]) -> WirePartition {
         let mut partition = HashMap::<_, Vec<_>>::new();
 
         // Here we keep just the Wire targets, filtering out everything else.
-        for row in 0..self.degree {
+        for (row, &target_row) in trace_rows.iter().enumerate() {
             for column in 0..self.num_routed_wires {
-                let w = Wire { row, column };
-                let t = Target::Wire(w);
+                let t = Target::wire(target_row, column);
                 let x_parent = self.parents[self.target_index(t)];
-                partition.entry(x_parent).or_default().push(w);
+                partition
+                    .entry(x_parent)
+                    .or_default()
+                    .push(Wire { row, column });
             }
         }
 
@@ -1,3 +1,4 @@
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
@@ -21,7 +22,7 @@ use crate::iop::target::Target;
 use crate::plonk::circuit_data::{CommonCircuitData, VerifierOnlyCircuitData};
 use crate::plonk::config::{GenericConfig, Hasher};
 use crate::plonk::verifier::verify_with_challenges;
-use crate::util::serialization::{Buffer, Read, Write};
+use crate::util::serialization::{ArtifactKind, Buffer, Read, Remaining, Write};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(bound = "")]
@@ -104,7 +105,8 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
         buffer
-            .write_proof_with_public_inputs(self)
+            .write_header(ArtifactKind::Proof)
+            .and_then(|_| buffer.write_proof_with_public_inputs(self))
             .expect("Writing to a byte-vector cannot fail.");
         buffer
     }
@@ -114,9 +116,33 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<Self> {
         let mut buffer = Buffer::new(&bytes);
+        buffer
+            .read_header(ArtifactKind::Proof)
+            .map_err(anyhow::Error::msg)?;
         let proof = buffer
             .read_proof_with_public_inputs(common_data)
             .map_err(anyhow::Error::msg)?;
+        buffer.ensure_empty().map_err(anyhow::Error::msg)?;
+        Ok(proof)
+    }
+
+    /// Encodes this proof as JSON, following its serde derive: structs are objects with one
+    /// field per struct field, and vectors and Merkle caps are arrays. A field element is the
+    /// integer given by its canonical representative, which may exceed 2^53, so JavaScript
+    /// consumers should parse it as a `BigInt`. Extension field elements are arrays of `D` such
+    /// integers, and Poseidon hashes are objects `{"elements": [..]}` of four.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Proofs are always serializable to JSON.")
+    }
+
+    pub fn from_json(json: &str, common_data: &CommonCircuitData<F, D>) -> anyhow::Result<Self> {
+        let proof: Self = serde_json::from_str(json)?;
+        ensure!(
+            proof.public_inputs.len() == common_data.num_public_inputs,
+            "Expected {} public inputs, found {}",
+            common_data.num_public_inputs,
+            proof.public_inputs.len()
+        );
         Ok(proof)
     }
 }
@@ -233,7 +259,8 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
         buffer
-            .write_compressed_proof_with_public_inputs(self)
+            .write_header(ArtifactKind::CompressedProof)
+            .and_then(|_| buffer.write_compressed_proof_with_public_inputs(self))
             .expect("Writing to a byte-vector cannot fail.");
         buffer
     }
@@ -243,9 +270,13 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<Self> {
         let mut buffer = Buffer::new(&bytes);
+        buffer
+            .read_header(ArtifactKind::CompressedProof)
+            .map_err(anyhow::Error::msg)?;
         let proof = buffer
             .read_compressed_proof_with_public_inputs(common_data)
             .map_err(anyhow::Error::msg)?;
+        buffer.ensure_empty().map_err(anyhow::Error::msg)?;
         Ok(proof)
     }
 }
@@ -443,6 +474,54 @@ impl<const D: usize> OpeningSetTarget<D> {
     }
 }
 
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use anyhow::Result;
+
+    use crate::field::types::Sample;
+    use crate::iop::target::Target;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::proof::ProofWithPublicInputs;
+
+    pub(crate) const D: usize = 2;
+    pub(crate) type C = PoseidonGoldilocksConfig;
+    pub(crate) type F = <C as GenericConfig<D>>::F;
+
+    /// A circuit squaring its public input `num_squarings` times, and exposing the result as a
+    /// public input, named `output_name` if given. Returns the input target and the circuit.
+    pub(crate) fn square_circuit(
+        num_squarings: usize,
+        output_name: Option<&str>,
+    ) -> (Target, CircuitData<F, C, D>) {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let mut y = x;
+        for _ in 0..num_squarings {
+            y = builder.square(y);
+        }
+        match output_name {
+            Some(name) => builder.register_named_public_input(name, y),
+            None => builder.register_public_input(y),
+        }
+        (x, builder.build::<C>())
+    }
+
+    /// A [`square_circuit`], with a proof for a random input.
+    pub(crate) fn square_circuit_proof(
+        num_squarings: usize,
+        output_name: Option<&str>,
+    ) -> Result<(CircuitData<F, C, D>, ProofWithPublicInputs<F, C, D>)> {
+        let (x, data) = square_circuit(num_squarings, output_name);
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = data.prove(pw)?;
+        Ok((data, proof))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::sync::Arc;
@@ -450,15 +529,164 @@ mod tests {
     use anyhow::Result;
     use itertools::Itertools;
 
-    use crate::field::types::Sample;
+    use crate::field::types::{Field64, PrimeField64, Sample};
     use crate::fri::reduction_strategies::FriReductionStrategy;
     use crate::gates::lookup_table::LookupTable;
     use crate::gates::noop::NoopGate;
     use crate::iop::witness::PartialWitness;
     use crate::plonk::circuit_builder::CircuitBuilder;
-    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData, VerifierOnlyCircuitData};
     use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::proof::test_helpers::{square_circuit_proof, C, D, F};
+    use crate::plonk::proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs};
     use crate::plonk::verifier::verify;
+    use crate::util::serialization::{
+        DefaultGateSerializer, IoError, SERIALIZATION_HEADER_LEN, SERIALIZATION_MAGIC,
+        SERIALIZATION_VERSION,
+    };
+
+    #[test]
+    fn test_proof_deserialization_is_canonical() -> Result<()> {
+        let (data, proof) = square_circuit_proof(1, None)?;
+        let bytes = proof.to_bytes();
+        assert_eq!(
+            ProofWithPublicInputs::from_bytes(bytes.clone(), &data.common)?,
+            proof
+        );
+
+        let io_error = |result: Result<ProofWithPublicInputs<F, C, D>>| {
+            *result.unwrap_err().downcast_ref::<IoError>().unwrap()
+        };
+
+        // Trailing bytes are rejected.
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert_eq!(
+            io_error(ProofWithPublicInputs::from_bytes(padded, &data.common)),
+            IoError::TrailingBytes
+        );
+
+        // So are truncated proofs.
+        assert_eq!(
+            io_error(ProofWithPublicInputs::from_bytes(
+                bytes[..bytes.len() - 1].to_vec(),
+                &data.common
+            )),
+            IoError::UnexpectedEof
+        );
+
+        // Adding the field order to the last public input gives the same value modulo the order,
+        // but isn't a canonical encoding.
+        let mut non_canonical = bytes.clone();
+        let start = non_canonical.len() - 8;
+        let last_pi = u64::from_le_bytes(non_canonical[start..].try_into().unwrap());
+        if let Some(shifted) = last_pi.checked_add(F::ORDER) {
+            non_canonical[start..].copy_from_slice(&shifted.to_le_bytes());
+            assert_eq!(
+                io_error(ProofWithPublicInputs::from_bytes(
+                    non_canonical,
+                    &data.common
+                )),
+                IoError::NonCanonicalField
+            );
+        }
+
+        // The same holds for hashes, e.g. the first element of the wires cap.
+        let mut non_canonical_hash = bytes.clone();
+        non_canonical_hash[SERIALIZATION_HEADER_LEN..][..8]
+            .copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            io_error(ProofWithPublicInputs::from_bytes(
+                non_canonical_hash,
+                &data.common
+            )),
+            IoError::NonCanonicalField
+        );
+
+        // Compressed proofs have no length prefix for public inputs, so trailing bytes must be
+        // rejected too.
+        let compressed_bytes = data.compress(proof)?.to_bytes();
+        let mut padded = compressed_bytes.clone();
+        padded.extend([0; 8]);
+        assert!(CompressedProofWithPublicInputs::<F, C, D>::from_bytes(
+            compressed_bytes,
+            &data.common
+        )
+        .is_ok());
+        assert!(
+            CompressedProofWithPublicInputs::<F, C, D>::from_bytes(padded, &data.common).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_serialization_header() -> Result<()> {
+        let (data, proof) = square_circuit_proof(1, None)?;
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes[..SERIALIZATION_MAGIC.len()], SERIALIZATION_MAGIC);
+
+        let io_error = |bytes: Vec<u8>| {
+            *ProofWithPublicInputs::<F, C, D>::from_bytes(bytes, &data.common)
+                .unwrap_err()
+                .downcast_ref::<IoError>()
+                .unwrap()
+        };
+
+        // Headerless proofs, as written by older versions, are detected.
+        assert_eq!(
+            io_error(bytes[SERIALIZATION_HEADER_LEN..].to_vec()),
+            IoError::BadMagic
+        );
+
+        // So are proofs written with a future format version.
+        let mut future = bytes.clone();
+        let version_start = SERIALIZATION_MAGIC.len();
+        future[version_start..version_start + 2]
+            .copy_from_slice(&(SERIALIZATION_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            io_error(future),
+            IoError::UnsupportedVersion(SERIALIZATION_VERSION + 1)
+        );
+
+        // A compressed proof isn't silently misparsed as an uncompressed one.
+        let compressed_bytes = data.compress(proof)?.to_bytes();
+        assert!(matches!(
+            io_error(compressed_bytes),
+            IoError::UnexpectedArtifact { .. }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_json_roundtrip() -> Result<()> {
+        let (data, proof) = square_circuit_proof(1, None)?;
+
+        let gate_serializer = DefaultGateSerializer;
+        let common_json = data.common.to_json(&gate_serializer).unwrap();
+        let common = CommonCircuitData::<F, D>::from_json(&common_json, &gate_serializer).unwrap();
+        assert_eq!(common, data.common);
+        let verifier_only =
+            VerifierOnlyCircuitData::<C, D>::from_json(&data.verifier_only.to_json()).unwrap();
+        assert_eq!(verifier_only, data.verifier_only);
+        let proof_from_json = ProofWithPublicInputs::from_json(&proof.to_json(), &common)?;
+        assert_eq!(proof_from_json, proof);
+        verify(proof_from_json, &verifier_only, &common)?;
+
+        // Field elements must be given by their canonical representative.
+        let public_input = proof.public_inputs[0].to_canonical_u64();
+        if let Some(shifted) = public_input.checked_add(F::ORDER) {
+            let json = proof.to_json().replacen(
+                &format!("\"public_inputs\":[{public_input}"),
+                &format!("\"public_inputs\":[{shifted}"),
+                1,
+            );
+            assert!(ProofWithPublicInputs::<F, C, D>::from_json(&json, &common).is_err());
+        }
+
+        Ok(())
+    }
 
     #[test]
     fn test_proof_compression() -> Result<()> {
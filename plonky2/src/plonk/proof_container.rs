@@ -0,0 +1,198 @@
+//! A self-describing CBOR container for proofs.
+//!
+//! The container is a CBOR map with the following entries:
+//! - `version`: the [`SERIALIZATION_VERSION`] the container was written with,
+//! - `circuit_digest`: the `circuit_digest` of the circuit's verifier data, as a byte string,
+//! - `config_hash`: the Keccak hash of the circuit's binary-encoded [`CircuitConfig`], as a byte
+//!   string, see [`config_hash`],
+//! - `public_inputs`: an array with the canonical representative of each public input,
+//! - `proof`: the binary encoding of the proof, without public inputs, as a byte string.
+
+use alloc::vec::Vec;
+
+use anyhow::ensure;
+use keccak_hash::keccak;
+use serde::{Deserialize, Serialize};
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData, VerifierOnlyCircuitData};
+use crate::plonk::config::{GenericConfig, GenericHashOut, Hasher};
+use crate::plonk::proof::ProofWithPublicInputs;
+use crate::plonk::verifier::verify;
+use crate::util::serialization::{
+    Buffer, IoError, IoResult, Read, Remaining, Write, MIN_SERIALIZATION_VERSION,
+    SERIALIZATION_VERSION,
+};
+
+/// A proof bundled with the metadata needed to check that it targets a given circuit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProofContainer<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    /// A fingerprint of the circuit the proof was generated for, namely its `circuit_digest`.
+    pub circuit_digest: <<C as GenericConfig<D>>::Hasher as Hasher<F>>::Hash,
+    /// The hash of the circuit's configuration, see [`config_hash`].
+    pub config_hash: [u8; 32],
+    pub proof: ProofWithPublicInputs<F, C, D>,
+}
+
+/// The CBOR representation of a [`ProofContainer`].
+#[derive(Serialize, Deserialize)]
+struct ProofContainerCbor {
+    version: u16,
+    #[serde(with = "serde_bytes")]
+    circuit_digest: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    config_hash: Vec<u8>,
+    public_inputs: Vec<u64>,
+    #[serde(with = "serde_bytes")]
+    proof: Vec<u8>,
+}
+
+/// Returns the Keccak hash of the binary encoding of `config`.
+pub fn config_hash(config: &CircuitConfig) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    bytes
+        .write_circuit_config(config)
+        .expect("Writing to a byte-vector cannot fail.");
+    keccak(bytes).0
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    ProofContainer<F, C, D>
+{
+    pub fn new(
+        proof: ProofWithPublicInputs<F, C, D>,
+        verifier_data: &VerifierOnlyCircuitData<C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Self {
+        Self {
+            circuit_digest: verifier_data.circuit_digest,
+            config_hash: config_hash(&common_data.config),
+            proof,
+        }
+    }
+
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut proof = Vec::new();
+        proof
+            .write_proof(&self.proof.proof)
+            .expect("Writing to a byte-vector cannot fail.");
+        let container = ProofContainerCbor {
+            version: SERIALIZATION_VERSION,
+            circuit_digest: self.circuit_digest.to_bytes(),
+            config_hash: self.config_hash.to_vec(),
+            public_inputs: self
+                .proof
+                .public_inputs
+                .iter()
+                .map(|x| x.to_canonical_u64())
+                .collect(),
+            proof,
+        };
+        serde_cbor::to_vec(&container).expect("Writing to a byte-vector cannot fail.")
+    }
+
+    pub fn from_cbor(bytes: &[u8], common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let container: ProofContainerCbor =
+            serde_cbor::from_slice(bytes).map_err(|_| IoError::InvalidData)?;
+        if !(MIN_SERIALIZATION_VERSION..=SERIALIZATION_VERSION).contains(&container.version) {
+            return Err(IoError::UnsupportedVersion(container.version));
+        }
+
+        if container.circuit_digest.len() != C::Hasher::HASH_SIZE {
+            return Err(IoError::InvalidData);
+        }
+        let circuit_digest = <C::Hasher as Hasher<F>>::Hash::from_bytes(&container.circuit_digest);
+        if circuit_digest.to_bytes() != container.circuit_digest {
+            return Err(IoError::NonCanonicalField);
+        }
+        let config_hash = container
+            .config_hash
+            .try_into()
+            .map_err(|_| IoError::InvalidData)?;
+
+        if container.public_inputs.len() != common_data.num_public_inputs {
+            return Err(IoError::InvalidData);
+        }
+        let public_inputs = container
+            .public_inputs
+            .into_iter()
+            .map(|x| {
+                if x < F::ORDER {
+                    Ok(F::from_canonical_u64(x))
+                } else {
+                    Err(IoError::NonCanonicalField)
+                }
+            })
+            .collect::<IoResult<_>>()?;
+
+        let mut buffer = Buffer::new(&container.proof);
+        let proof = buffer.read_proof(common_data)?;
+        buffer.ensure_empty()?;
+
+        Ok(Self {
+            circuit_digest,
+            config_hash,
+            proof: ProofWithPublicInputs {
+                proof,
+                public_inputs,
+            },
+        })
+    }
+
+    /// Checks that the container's metadata matches the given circuit.
+    pub fn check_circuit(
+        &self,
+        verifier_data: &VerifierOnlyCircuitData<C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            self.circuit_digest == verifier_data.circuit_digest,
+            "Proof was generated for a different circuit"
+        );
+        ensure!(
+            self.config_hash == config_hash(&common_data.config),
+            "Proof was generated with a different circuit config"
+        );
+        Ok(())
+    }
+
+    /// Checks the container's metadata against the given circuit, then verifies the proof.
+    pub fn verify(
+        self,
+        verifier_data: &VerifierOnlyCircuitData<C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<()> {
+        self.check_circuit(verifier_data, common_data)?;
+        verify::<F, C, D>(self.proof, verifier_data, common_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::plonk::proof::test_helpers::{square_circuit, square_circuit_proof, C, D, F};
+
+    #[test]
+    fn test_proof_container_roundtrip() -> Result<()> {
+        let (data, proof) = square_circuit_proof(1, None)?;
+
+        let container = ProofContainer::new(proof, &data.verifier_only, &data.common);
+        let bytes = container.to_cbor();
+        let decoded = ProofContainer::<F, C, D>::from_cbor(&bytes, &data.common).unwrap();
+        assert_eq!(decoded, container);
+        assert!(bytes.len() < serde_cbor::to_vec(&container.proof)?.len());
+        decoded.verify(&data.verifier_only, &data.common)?;
+
+        // The metadata identifies proofs for a different circuit, even with the same config.
+        let (_, other_data) = square_circuit(2, None);
+        assert!(container
+            .check_circuit(&other_data.verifier_only, &other_data.common)
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,520 @@
+//! Protobuf messages for proofs and verifier data, for gRPC services.
+//!
+//! The messages mirror the native types field by field, and are described for other languages by
+//! `proto/plonky2.proto`. Field elements are their canonical `uint64` representatives, extension
+//! field elements are flattened into `D` consecutive base field elements, and hashes are their
+//! byte encodings. Decoding rejects non-canonical field elements and hashes of the wrong size, but
+//! the shape of a proof is only checked against a circuit when the proof is verified.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use anyhow::{anyhow, ensure, Result};
+
+use crate::field::extension::{Extendable, FieldExtension};
+use crate::field::polynomial::PolynomialCoeffs;
+use crate::field::types::PrimeField64;
+use crate::fri::proof as fri_proof;
+use crate::hash::hash_types::RichField;
+use crate::hash::{merkle_proofs, merkle_tree};
+use crate::plonk::config::{GenericConfig, GenericHashOut, Hasher};
+use crate::plonk::public_input_layout::{self, PublicInputType};
+use crate::plonk::{circuit_data, proof};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Hash {
+    #[prost(bytes = "vec", tag = "1")]
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleCap {
+    #[prost(message, repeated, tag = "1")]
+    pub hashes: Vec<Hash>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleProof {
+    #[prost(message, repeated, tag = "1")]
+    pub siblings: Vec<Hash>,
+}
+
+/// The openings of all polynomials at the challenge point. Each field holds extension field
+/// elements, flattened.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OpeningSet {
+    #[prost(uint64, repeated, tag = "1")]
+    pub constants: Vec<u64>,
+    #[prost(uint64, repeated, tag = "2")]
+    pub plonk_sigmas: Vec<u64>,
+    #[prost(uint64, repeated, tag = "3")]
+    pub wires: Vec<u64>,
+    #[prost(uint64, repeated, tag = "4")]
+    pub plonk_zs: Vec<u64>,
+    #[prost(uint64, repeated, tag = "5")]
+    pub plonk_zs_next: Vec<u64>,
+    #[prost(uint64, repeated, tag = "6")]
+    pub partial_products: Vec<u64>,
+    #[prost(uint64, repeated, tag = "7")]
+    pub quotient_polys: Vec<u64>,
+    #[prost(uint64, repeated, tag = "8")]
+    pub lookup_zs: Vec<u64>,
+    #[prost(uint64, repeated, tag = "9")]
+    pub lookup_zs_next: Vec<u64>,
+}
+
+/// The evaluations of the polynomials of an initial tree at a query point, with their Merkle proof.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FriEvalsProof {
+    #[prost(uint64, repeated, tag = "1")]
+    pub evals: Vec<u64>,
+    #[prost(message, optional, tag = "2")]
+    pub merkle_proof: Option<MerkleProof>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FriQueryStep {
+    /// Extension field elements, flattened.
+    #[prost(uint64, repeated, tag = "1")]
+    pub evals: Vec<u64>,
+    #[prost(message, optional, tag = "2")]
+    pub merkle_proof: Option<MerkleProof>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FriQueryRound {
+    #[prost(message, repeated, tag = "1")]
+    pub initial_trees_proof: Vec<FriEvalsProof>,
+    #[prost(message, repeated, tag = "2")]
+    pub steps: Vec<FriQueryStep>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FriProof {
+    #[prost(message, repeated, tag = "1")]
+    pub commit_phase_merkle_caps: Vec<MerkleCap>,
+    #[prost(message, repeated, tag = "2")]
+    pub query_round_proofs: Vec<FriQueryRound>,
+    /// The coefficients of the final polynomial, extension field elements flattened.
+    #[prost(uint64, repeated, tag = "3")]
+    pub final_poly: Vec<u64>,
+    #[prost(uint64, tag = "4")]
+    pub pow_witness: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Proof {
+    #[prost(message, optional, tag = "1")]
+    pub wires_cap: Option<MerkleCap>,
+    #[prost(message, optional, tag = "2")]
+    pub plonk_zs_partial_products_cap: Option<MerkleCap>,
+    #[prost(message, optional, tag = "3")]
+    pub quotient_polys_cap: Option<MerkleCap>,
+    #[prost(message, optional, tag = "4")]
+    pub openings: Option<OpeningSet>,
+    #[prost(message, optional, tag = "5")]
+    pub opening_proof: Option<FriProof>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProofWithPublicInputs {
+    #[prost(message, optional, tag = "1")]
+    pub proof: Option<Proof>,
+    #[prost(uint64, repeated, tag = "2")]
+    pub public_inputs: Vec<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum PublicInputKind {
+    Field = 0,
+    Hash = 1,
+    U32Array = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NamedPublicInput {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(enumeration = "PublicInputKind", tag = "2")]
+    pub kind: i32,
+    /// The length of a `U32Array`.
+    #[prost(uint64, tag = "3")]
+    pub len: u64,
+    #[prost(uint64, tag = "4")]
+    pub start: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VerifierOnlyCircuitData {
+    #[prost(message, optional, tag = "1")]
+    pub constants_sigmas_cap: Option<MerkleCap>,
+    #[prost(message, optional, tag = "2")]
+    pub circuit_digest: Option<Hash>,
+    #[prost(message, repeated, tag = "3")]
+    pub public_input_layout: Vec<NamedPublicInput>,
+}
+
+impl<F, C, const D: usize> From<&proof::ProofWithPublicInputs<F, C, D>> for ProofWithPublicInputs
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    fn from(proof: &proof::ProofWithPublicInputs<F, C, D>) -> Self {
+        let proof::Proof {
+            wires_cap,
+            plonk_zs_partial_products_cap,
+            quotient_polys_cap,
+            openings,
+            opening_proof,
+        } = &proof.proof;
+        Self {
+            proof: Some(Proof {
+                wires_cap: Some(cap_to_proto(wires_cap)),
+                plonk_zs_partial_products_cap: Some(cap_to_proto(plonk_zs_partial_products_cap)),
+                quotient_polys_cap: Some(cap_to_proto(quotient_polys_cap)),
+                openings: Some(OpeningSet {
+                    constants: extension_to_proto::<F, D>(&openings.constants),
+                    plonk_sigmas: extension_to_proto::<F, D>(&openings.plonk_sigmas),
+                    wires: extension_to_proto::<F, D>(&openings.wires),
+                    plonk_zs: extension_to_proto::<F, D>(&openings.plonk_zs),
+                    plonk_zs_next: extension_to_proto::<F, D>(&openings.plonk_zs_next),
+                    partial_products: extension_to_proto::<F, D>(&openings.partial_products),
+                    quotient_polys: extension_to_proto::<F, D>(&openings.quotient_polys),
+                    lookup_zs: extension_to_proto::<F, D>(&openings.lookup_zs),
+                    lookup_zs_next: extension_to_proto::<F, D>(&openings.lookup_zs_next),
+                }),
+                opening_proof: Some(fri_proof_to_proto(opening_proof)),
+            }),
+            public_inputs: field_to_proto(&proof.public_inputs),
+        }
+    }
+}
+
+impl<F, C, const D: usize> TryFrom<ProofWithPublicInputs> for proof::ProofWithPublicInputs<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(proof: ProofWithPublicInputs) -> Result<Self> {
+        let Proof {
+            wires_cap,
+            plonk_zs_partial_products_cap,
+            quotient_polys_cap,
+            openings,
+            opening_proof,
+        } = required(proof.proof, "proof")?;
+        let openings = required(openings, "openings")?;
+        Ok(Self {
+            proof: proof::Proof {
+                wires_cap: cap_from_proto(required(wires_cap, "wires_cap")?)?,
+                plonk_zs_partial_products_cap: cap_from_proto(required(
+                    plonk_zs_partial_products_cap,
+                    "plonk_zs_partial_products_cap",
+                )?)?,
+                quotient_polys_cap: cap_from_proto(required(
+                    quotient_polys_cap,
+                    "quotient_polys_cap",
+                )?)?,
+                openings: proof::OpeningSet {
+                    constants: extension_from_proto::<F, D>(&openings.constants)?,
+                    plonk_sigmas: extension_from_proto::<F, D>(&openings.plonk_sigmas)?,
+                    wires: extension_from_proto::<F, D>(&openings.wires)?,
+                    plonk_zs: extension_from_proto::<F, D>(&openings.plonk_zs)?,
+                    plonk_zs_next: extension_from_proto::<F, D>(&openings.plonk_zs_next)?,
+                    partial_products: extension_from_proto::<F, D>(&openings.partial_products)?,
+                    quotient_polys: extension_from_proto::<F, D>(&openings.quotient_polys)?,
+                    lookup_zs: extension_from_proto::<F, D>(&openings.lookup_zs)?,
+                    lookup_zs_next: extension_from_proto::<F, D>(&openings.lookup_zs_next)?,
+                },
+                opening_proof: fri_proof_from_proto(required(opening_proof, "opening_proof")?)?,
+            },
+            public_inputs: field_from_proto(&proof.public_inputs)?,
+        })
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> From<&circuit_data::VerifierOnlyCircuitData<C, D>>
+    for VerifierOnlyCircuitData
+{
+    fn from(data: &circuit_data::VerifierOnlyCircuitData<C, D>) -> Self {
+        Self {
+            constants_sigmas_cap: Some(cap_to_proto(&data.constants_sigmas_cap)),
+            circuit_digest: Some(hash_to_proto::<C::F, C::Hasher>(&data.circuit_digest)),
+            public_input_layout: data
+                .public_input_layout
+                .entries()
+                .iter()
+                .map(|entry| {
+                    let (kind, len) = match entry.ty {
+                        PublicInputType::Field => (PublicInputKind::Field, 0),
+                        PublicInputType::Hash => (PublicInputKind::Hash, 0),
+                        PublicInputType::U32Array(len) => (PublicInputKind::U32Array, len),
+                    };
+                    NamedPublicInput {
+                        name: entry.name.clone(),
+                        kind: kind as i32,
+                        len: len as u64,
+                        start: entry.start as u64,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> TryFrom<VerifierOnlyCircuitData>
+    for circuit_data::VerifierOnlyCircuitData<C, D>
+{
+    type Error = anyhow::Error;
+
+    fn try_from(data: VerifierOnlyCircuitData) -> Result<Self> {
+        let entries = data
+            .public_input_layout
+            .into_iter()
+            .map(|entry| {
+                let kind = PublicInputKind::try_from(entry.kind)
+                    .map_err(|_| anyhow!("Invalid public input kind {}", entry.kind))?;
+                let ty = match kind {
+                    PublicInputKind::Field => PublicInputType::Field,
+                    PublicInputKind::Hash => PublicInputType::Hash,
+                    PublicInputKind::U32Array => PublicInputType::U32Array(entry.len as usize),
+                };
+                Ok(public_input_layout::NamedPublicInput {
+                    name: entry.name,
+                    ty,
+                    start: entry.start as usize,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            constants_sigmas_cap: cap_from_proto(required(
+                data.constants_sigmas_cap,
+                "constants_sigmas_cap",
+            )?)?,
+            circuit_digest: hash_from_proto::<C::F, C::Hasher>(&required(
+                data.circuit_digest,
+                "circuit_digest",
+            )?)?,
+            public_input_layout: public_input_layout::PublicInputLayout::new(entries)?,
+        })
+    }
+}
+
+fn required<T>(message: Option<T>, name: &str) -> Result<T> {
+    message.ok_or_else(|| anyhow!("Missing field `{name}`"))
+}
+
+fn field_to_proto<F: PrimeField64>(elements: &[F]) -> Vec<u64> {
+    elements.iter().map(F::to_canonical_u64).collect()
+}
+
+fn field_element_from_proto<F: RichField>(x: u64) -> Result<F> {
+    ensure!(x < F::ORDER, "Non-canonical field element {x}");
+    Ok(F::from_canonical_u64(x))
+}
+
+fn field_from_proto<F: RichField>(elements: &[u64]) -> Result<Vec<F>> {
+    elements
+        .iter()
+        .map(|&x| field_element_from_proto(x))
+        .collect()
+}
+
+fn extension_to_proto<F: RichField + Extendable<D>, const D: usize>(
+    elements: &[F::Extension],
+) -> Vec<u64> {
+    elements
+        .iter()
+        .flat_map(|x| x.to_basefield_array())
+        .map(|x| x.to_canonical_u64())
+        .collect()
+}
+
+fn extension_from_proto<F: RichField + Extendable<D>, const D: usize>(
+    elements: &[u64],
+) -> Result<Vec<F::Extension>> {
+    ensure!(
+        elements.len() % D == 0,
+        "{} base field elements don't make extension field elements of degree {D}",
+        elements.len()
+    );
+    let elements = field_from_proto::<F>(elements)?;
+    Ok(elements
+        .chunks_exact(D)
+        .map(|limbs| F::Extension::from_basefield_array(limbs.try_into().unwrap()))
+        .collect())
+}
+
+fn hash_to_proto<F: RichField, H: Hasher<F>>(hash: &H::Hash) -> Hash {
+    Hash {
+        bytes: hash.to_bytes(),
+    }
+}
+
+fn hash_from_proto<F: RichField, H: Hasher<F>>(hash: &Hash) -> Result<H::Hash> {
+    ensure!(
+        hash.bytes.len() == H::HASH_SIZE,
+        "Expected a hash of {} bytes, found {}",
+        H::HASH_SIZE,
+        hash.bytes.len()
+    );
+    let decoded = H::Hash::from_bytes(&hash.bytes);
+    ensure!(decoded.to_bytes() == hash.bytes, "Non-canonical hash");
+    Ok(decoded)
+}
+
+fn cap_to_proto<F: RichField, H: Hasher<F>>(cap: &merkle_tree::MerkleCap<F, H>) -> MerkleCap {
+    MerkleCap {
+        hashes: cap.0.iter().map(hash_to_proto::<F, H>).collect(),
+    }
+}
+
+fn cap_from_proto<F: RichField, H: Hasher<F>>(
+    cap: MerkleCap,
+) -> Result<merkle_tree::MerkleCap<F, H>> {
+    let hashes = cap
+        .hashes
+        .iter()
+        .map(hash_from_proto::<F, H>)
+        .collect::<Result<_>>()?;
+    Ok(merkle_tree::MerkleCap(hashes))
+}
+
+fn merkle_proof_to_proto<F: RichField, H: Hasher<F>>(
+    proof: &merkle_proofs::MerkleProof<F, H>,
+) -> MerkleProof {
+    MerkleProof {
+        siblings: proof.siblings.iter().map(hash_to_proto::<F, H>).collect(),
+    }
+}
+
+fn merkle_proof_from_proto<F: RichField, H: Hasher<F>>(
+    proof: Option<MerkleProof>,
+) -> Result<merkle_proofs::MerkleProof<F, H>> {
+    let siblings = required(proof, "merkle_proof")?
+        .siblings
+        .iter()
+        .map(hash_from_proto::<F, H>)
+        .collect::<Result<_>>()?;
+    Ok(merkle_proofs::MerkleProof { siblings })
+}
+
+fn fri_proof_to_proto<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize>(
+    proof: &fri_proof::FriProof<F, H, D>,
+) -> FriProof {
+    FriProof {
+        commit_phase_merkle_caps: proof
+            .commit_phase_merkle_caps
+            .iter()
+            .map(cap_to_proto)
+            .collect(),
+        query_round_proofs: proof
+            .query_round_proofs
+            .iter()
+            .map(|round| FriQueryRound {
+                initial_trees_proof: round
+                    .initial_trees_proof
+                    .evals_proofs
+                    .iter()
+                    .map(|(evals, merkle_proof)| FriEvalsProof {
+                        evals: field_to_proto(evals),
+                        merkle_proof: Some(merkle_proof_to_proto(merkle_proof)),
+                    })
+                    .collect(),
+                steps: round
+                    .steps
+                    .iter()
+                    .map(|step| FriQueryStep {
+                        evals: extension_to_proto::<F, D>(&step.evals),
+                        merkle_proof: Some(merkle_proof_to_proto(&step.merkle_proof)),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        final_poly: extension_to_proto::<F, D>(&proof.final_poly.coeffs),
+        pow_witness: proof.pow_witness.to_canonical_u64(),
+    }
+}
+
+fn fri_proof_from_proto<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize>(
+    proof: FriProof,
+) -> Result<fri_proof::FriProof<F, H, D>> {
+    let query_round_proofs = proof
+        .query_round_proofs
+        .into_iter()
+        .map(|round| {
+            let evals_proofs = round
+                .initial_trees_proof
+                .into_iter()
+                .map(|evals_proof| {
+                    Ok((
+                        field_from_proto(&evals_proof.evals)?,
+                        merkle_proof_from_proto(evals_proof.merkle_proof)?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            let steps = round
+                .steps
+                .into_iter()
+                .map(|step| {
+                    Ok(fri_proof::FriQueryStep {
+                        evals: extension_from_proto::<F, D>(&step.evals)?,
+                        merkle_proof: merkle_proof_from_proto(step.merkle_proof)?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            Ok(fri_proof::FriQueryRound {
+                initial_trees_proof: fri_proof::FriInitialTreeProof { evals_proofs },
+                steps,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(fri_proof::FriProof {
+        commit_phase_merkle_caps: proof
+            .commit_phase_merkle_caps
+            .into_iter()
+            .map(cap_from_proto)
+            .collect::<Result<_>>()?,
+        query_round_proofs,
+        final_poly: PolynomialCoeffs::new(extension_from_proto::<F, D>(&proof.final_poly)?),
+        pow_witness: field_element_from_proto(proof.pow_witness)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+    use crate::field::types::Field64;
+    use crate::plonk::proof::test_helpers::{square_circuit_proof, C, D, F};
+
+    #[test]
+    fn test_protobuf_round_trip() -> Result<()> {
+        let (data, proof) = square_circuit_proof(1, Some("y"))?;
+
+        let bytes = ProofWithPublicInputs::from(&proof).encode_to_vec();
+        let decoded: proof::ProofWithPublicInputs<F, C, D> =
+            ProofWithPublicInputs::decode(bytes.as_slice())
+                .unwrap()
+                .try_into()?;
+        assert_eq!(decoded, proof);
+        data.verify(decoded)?;
+
+        let bytes = VerifierOnlyCircuitData::from(&data.verifier_only).encode_to_vec();
+        let decoded: circuit_data::VerifierOnlyCircuitData<C, D> =
+            VerifierOnlyCircuitData::decode(bytes.as_slice())
+                .unwrap()
+                .try_into()?;
+        assert_eq!(decoded, data.verifier_only);
+
+        // A non-canonical public input is rejected.
+        let mut message = ProofWithPublicInputs::from(&proof);
+        message.public_inputs[0] = F::ORDER;
+        assert!(proof::ProofWithPublicInputs::<F, C, D>::try_from(message).is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use alloc::{format, vec};
 use core::cmp::min;
-use core::mem::swap;
+use core::mem::{size_of, swap};
 
 use anyhow::{ensure, Result};
 use hashbrown::HashMap;
@@ -9,6 +10,7 @@ use plonky2_maybe_rayon::*;
 
 use super::circuit_builder::{LookupChallenges, LookupWire};
 use crate::field::extension::Extendable;
+use crate::field::fft::{ifft_with_options, FftRootTable};
 use crate::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use crate::field::types::Field;
 use crate::field::zero_poly_coset::ZeroPolyOnCoset;
@@ -18,20 +20,23 @@ use crate::gates::lookup_table::LookupTableGate;
 use crate::gates::selectors::LookupSelectors;
 use crate::hash::hash_types::RichField;
 use crate::iop::challenger::Challenger;
-use crate::iop::generator::generate_partial_witness;
+use crate::iop::generator::generate_partial_witness_with_randomness;
 use crate::iop::target::Target;
 use crate::iop::witness::{MatrixWitness, PartialWitness, PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::NUM_COINS_LOOKUP;
 use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
 use crate::plonk::config::{GenericConfig, Hasher};
+use crate::plonk::diagnostics::describe_unsatisfied_constraint;
 use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{OpeningSet, Proof, ProofWithPublicInputs};
 use crate::plonk::vanishing_poly::{eval_vanishing_poly_base_batch, get_lut_poly};
 use crate::plonk::vars::EvaluationVarsBaseBatch;
 use crate::timed;
 use crate::util::partial_products::{partial_products_and_z_gx, quotient_chunk_products};
+use crate::util::randomness::ProverRandomness;
+use crate::util::serialization::{ArtifactKind, Buffer, IoResult, Read, Remaining, Write};
 use crate::util::timing::TimingTree;
-use crate::util::{ceil_div_usize, log2_ceil, transpose};
+use crate::util::{ceil_div_usize, domain_cache, log2_ceil, transpose};
 
 /// Set all the lookup gate wires (including multiplicities) and pad unused LU slots.
 /// Warning: rows are in descending order: the first gate to appear is the last LU gate, and
@@ -107,12 +112,81 @@ pub fn set_lookup_wires<
     }
 }
 
+/// Options tuning how a proof is computed, which don't affect its validity.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProverOptions {
+    /// A bound, in bytes, on the prover's largest transient buffers, i.e. those holding the LDEs
+    /// of committed polynomials before they are Merklized, and the quotient polynomials' values.
+    /// These are then computed in chunks, which is slower but keeps the peak memory usage bounded
+    /// by about the size of the commitments themselves. With `None`, everything is computed at
+    /// once.
+    pub memory_budget: Option<usize>,
+    /// The source of the blinding values of the witness and of the salts of the commitments. A
+    /// seeded one makes proofs reproducible, e.g. to debug the prover.
+    pub randomness: ProverRandomness,
+}
+
+/// The purpose, in the sense of [`ProverRandomness::derive`], of the randomness of the witness.
+/// The randomness of each commitment is derived from the index of its oracle.
+const WITNESS_RANDOMNESS: u64 = u64::MAX;
+
 pub fn prove<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     prover_data: &ProverOnlyCircuitData<F, C, D>,
     common_data: &CommonCircuitData<F, D>,
     inputs: PartialWitness<F>,
     timing: &mut TimingTree,
 ) -> Result<ProofWithPublicInputs<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    prove_with_options(
+        prover_data,
+        common_data,
+        inputs,
+        &ProverOptions::default(),
+        timing,
+    )
+}
+
+pub fn prove_with_options<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: PartialWitness<F>,
+    options: &ProverOptions,
+    timing: &mut TimingTree,
+) -> Result<ProofWithPublicInputs<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    let precomputation = ProverPrecomputation::new(common_data);
+    prove_with_precomputation(
+        prover_data,
+        common_data,
+        inputs,
+        options,
+        &precomputation,
+        timing,
+    )
+}
+
+pub(crate) fn prove_with_precomputation<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: PartialWitness<F>,
+    options: &ProverOptions,
+    precomputation: &ProverPrecomputation<F>,
+    timing: &mut TimingTree,
+) -> Result<ProofWithPublicInputs<F, C, D>>
 where
     C::Hasher: Hasher<F>,
     C::InnerHasher: Hasher<F>,
@@ -120,10 +194,63 @@ where
     let partition_witness = timed!(
         timing,
         &format!("run {} generators", prover_data.generators.len()),
-        generate_partial_witness(inputs, prover_data, common_data)
+        generate_partial_witness_with_randomness(
+            inputs,
+            prover_data,
+            common_data,
+            options.randomness.derive(WITNESS_RANDOMNESS)
+        )
     );
+    let committed = commit_wires(
+        prover_data,
+        common_data,
+        partition_witness,
+        options,
+        precomputation,
+        timing,
+    );
+    let challenged = challenge_phase(prover_data, common_data, committed, precomputation, timing)?;
+    Ok(prove_open_phase(
+        prover_data,
+        common_data,
+        challenged,
+        timing,
+    ))
+}
 
-    prove_with_partition_witness(prover_data, common_data, partition_witness, timing)
+/// Data which depends only on the shape of a circuit, and which every proof of it needs: the FFT
+/// root tables of the sizes not covered by `ProverOnlyCircuitData::fft_root_table`, and the points
+/// at which the quotient polynomials are evaluated. A `BatchProver` computes it once for all its
+/// proofs, and the tables themselves are shared through the [`domain_cache`].
+#[derive(Debug)]
+pub(crate) struct ProverPrecomputation<F: Field> {
+    /// The root table of FFTs over the subgroup, used to interpolate the committed polynomials.
+    subgroup_root_table: Arc<FftRootTable<F>>,
+    /// The root table of FFTs over the subgroup on which the quotient polynomials are evaluated.
+    quotient_root_table: Arc<FftRootTable<F>>,
+    quotient_points: Arc<Vec<F>>,
+    z_h_on_coset: ZeroPolyOnCoset<F>,
+}
+
+impl<F: RichField> ProverPrecomputation<F> {
+    pub(crate) fn new<const D: usize>(common_data: &CommonCircuitData<F, D>) -> Self
+    where
+        F: Extendable<D>,
+    {
+        let degree_bits = common_data.degree_bits();
+        let quotient_degree_bits = log2_ceil(common_data.quotient_degree_factor);
+        Self {
+            subgroup_root_table: domain_cache::fft_root_table(degree_bits),
+            quotient_root_table: domain_cache::fft_root_table(degree_bits + quotient_degree_bits),
+            quotient_points: domain_cache::two_adic_subgroup(degree_bits + quotient_degree_bits),
+            z_h_on_coset: ZeroPolyOnCoset::new(degree_bits, quotient_degree_bits),
+        }
+    }
+
+    /// Interpolates values over the subgroup.
+    fn interpolate(&self, values: PolynomialValues<F>) -> PolynomialCoeffs<F> {
+        ifft_with_options(values, None, Some(self.subgroup_root_table.as_ref()))
+    }
 }
 
 pub fn prove_with_partition_witness<
@@ -133,18 +260,232 @@ pub fn prove_with_partition_witness<
 >(
     prover_data: &ProverOnlyCircuitData<F, C, D>,
     common_data: &CommonCircuitData<F, D>,
-    mut partition_witness: PartitionWitness<F>,
+    partition_witness: PartitionWitness<F>,
     timing: &mut TimingTree,
 ) -> Result<ProofWithPublicInputs<F, C, D>>
 where
     C::Hasher: Hasher<F>,
     C::InnerHasher: Hasher<F>,
 {
-    let has_lookup = !common_data.luts.is_empty();
+    let precomputation = ProverPrecomputation::new(common_data);
+    let committed = commit_wires(
+        prover_data,
+        common_data,
+        partition_witness,
+        &ProverOptions::default(),
+        &precomputation,
+        timing,
+    );
+    let challenged = challenge_phase(prover_data, common_data, committed, &precomputation, timing)?;
+    Ok(prove_open_phase(
+        prover_data,
+        common_data,
+        challenged,
+        timing,
+    ))
+}
+
+/// The state of a proof after [`prove_commit_phase`], in which the witness is generated and its
+/// wires are committed to.
+pub struct CommittedWitness<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    circuit_digest: <C::Hasher as Hasher<F>>::Hash,
+    public_inputs: Vec<F>,
+    public_inputs_hash: <C::InnerHasher as Hasher<F>>::Hash,
+    witness: MatrixWitness<F>,
+    wires_commitment: PolynomialBatch<F, C, D>,
+    challenger: Challenger<F, C::Hasher>,
+    pub(crate) memory_budget: Option<usize>,
+    pub(crate) randomness: ProverRandomness,
+}
+
+/// The state of a proof after [`prove_challenge_phase`], in which the permutation and quotient
+/// polynomials are committed to and the opening point is drawn.
+pub struct ChallengedProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    circuit_digest: <C::Hasher as Hasher<F>>::Hash,
+    public_inputs: Vec<F>,
+    wires_commitment: PolynomialBatch<F, C, D>,
+    partial_products_zs_and_lookup_commitment: PolynomialBatch<F, C, D>,
+    quotient_polys_commitment: PolynomialBatch<F, C, D>,
+    zeta: F::Extension,
+    challenger: Challenger<F, C::Hasher>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    CommittedWitness<F, C, D>
+{
+    /// The digest of the circuit this witness belongs to.
+    pub fn circuit_digest(&self) -> <C::Hasher as Hasher<F>>::Hash {
+        self.circuit_digest
+    }
+
+    /// Serializes this state, e.g. to resume the proof after a crash. The memory budget of the
+    /// proof isn't part of it, and a deserialized state has none.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer
+            .write_header(ArtifactKind::CommittedWitness)
+            .and_then(|_| buffer.write_hash::<F, C::Hasher>(self.circuit_digest))
+            .and_then(|_| buffer.write_usize(self.public_inputs.len()))
+            .and_then(|_| buffer.write_field_vec(&self.public_inputs))
+            .and_then(|_| buffer.write_hash::<F, C::InnerHasher>(self.public_inputs_hash))
+            .and_then(|_| buffer.write_usize(self.witness.wire_values.len()))
+            .and_then(|_| {
+                self.witness.wire_values.iter().try_for_each(|column| {
+                    buffer.write_usize(column.len())?;
+                    buffer.write_field_vec(column)
+                })
+            })
+            .and_then(|_| buffer.write_polynomial_batch(&self.wires_commitment))
+            .and_then(|_| buffer.write_challenger(&self.challenger))
+            .expect("Writing to a byte-vector cannot fail.");
+        buffer
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut buffer = Buffer::new(bytes);
+        let read = |buffer: &mut Buffer| -> IoResult<Self> {
+            buffer.read_header(ArtifactKind::CommittedWitness)?;
+            let circuit_digest = buffer.read_hash::<F, C::Hasher>()?;
+            let num_public_inputs = buffer.read_usize()?;
+            let public_inputs = buffer.read_field_vec(num_public_inputs)?;
+            let public_inputs_hash = buffer.read_hash::<F, C::InnerHasher>()?;
+            let num_wires = buffer.read_usize()?;
+            let wire_values = (0..num_wires)
+                .map(|_| {
+                    let len = buffer.read_usize()?;
+                    buffer.read_field_vec(len)
+                })
+                .collect::<IoResult<Vec<_>>>()?;
+            let wires_commitment = buffer.read_polynomial_batch()?;
+            let challenger = buffer.read_challenger()?;
+            buffer.ensure_empty()?;
+            Ok(Self {
+                circuit_digest,
+                public_inputs,
+                public_inputs_hash,
+                witness: MatrixWitness { wire_values },
+                wires_commitment,
+                challenger,
+                memory_budget: None,
+                randomness: ProverRandomness::Os,
+            })
+        };
+        read(&mut buffer).map_err(anyhow::Error::msg)
+    }
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    ChallengedProof<F, C, D>
+{
+    /// The digest of the circuit this proof belongs to.
+    pub fn circuit_digest(&self) -> <C::Hasher as Hasher<F>>::Hash {
+        self.circuit_digest
+    }
+
+    /// Serializes this state, e.g. to resume the proof after a crash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer
+            .write_header(ArtifactKind::ChallengedProof)
+            .and_then(|_| buffer.write_hash::<F, C::Hasher>(self.circuit_digest))
+            .and_then(|_| buffer.write_usize(self.public_inputs.len()))
+            .and_then(|_| buffer.write_field_vec(&self.public_inputs))
+            .and_then(|_| buffer.write_polynomial_batch(&self.wires_commitment))
+            .and_then(|_| {
+                buffer.write_polynomial_batch(&self.partial_products_zs_and_lookup_commitment)
+            })
+            .and_then(|_| buffer.write_polynomial_batch(&self.quotient_polys_commitment))
+            .and_then(|_| buffer.write_field_ext::<F, D>(self.zeta))
+            .and_then(|_| buffer.write_challenger(&self.challenger))
+            .expect("Writing to a byte-vector cannot fail.");
+        buffer
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut buffer = Buffer::new(bytes);
+        let read = |buffer: &mut Buffer| -> IoResult<Self> {
+            buffer.read_header(ArtifactKind::ChallengedProof)?;
+            let circuit_digest = buffer.read_hash::<F, C::Hasher>()?;
+            let num_public_inputs = buffer.read_usize()?;
+            let public_inputs = buffer.read_field_vec(num_public_inputs)?;
+            let wires_commitment = buffer.read_polynomial_batch()?;
+            let partial_products_zs_and_lookup_commitment = buffer.read_polynomial_batch()?;
+            let quotient_polys_commitment = buffer.read_polynomial_batch()?;
+            let zeta = buffer.read_field_ext::<F, D>()?;
+            let challenger = buffer.read_challenger()?;
+            buffer.ensure_empty()?;
+            Ok(Self {
+                circuit_digest,
+                public_inputs,
+                wires_commitment,
+                partial_products_zs_and_lookup_commitment,
+                quotient_polys_commitment,
+                zeta,
+                challenger,
+            })
+        };
+        read(&mut buffer).map_err(anyhow::Error::msg)
+    }
+}
+
+/// The first phase of proving: generates the witness and commits to its wires. Together with
+/// [`prove_challenge_phase`] and [`prove_open_phase`], this is equivalent to [`prove`], but lets
+/// a pipeline of provers interleave the phases of different proofs, e.g. to commit to the next
+/// proof's trace while the previous proof's openings are being computed.
+pub fn prove_commit_phase<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: PartialWitness<F>,
+    options: &ProverOptions,
+    timing: &mut TimingTree,
+) -> CommittedWitness<F, C, D>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    let partition_witness = timed!(
+        timing,
+        &format!("run {} generators", prover_data.generators.len()),
+        generate_partial_witness_with_randomness(
+            inputs,
+            prover_data,
+            common_data,
+            options.randomness.derive(WITNESS_RANDOMNESS)
+        )
+    );
+
+    commit_wires(
+        prover_data,
+        common_data,
+        partition_witness,
+        options,
+        &ProverPrecomputation::new(common_data),
+        timing,
+    )
+}
+
+fn commit_wires<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    mut partition_witness: PartitionWitness<F>,
+    options: &ProverOptions,
+    precomputation: &ProverPrecomputation<F>,
+    timing: &mut TimingTree,
+) -> CommittedWitness<F, C, D>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
     let config = &common_data.config;
-    let num_challenges = config.num_challenges;
-    let quotient_degree = common_data.quotient_degree();
-    let degree = common_data.degree();
 
     set_lookup_wires(prover_data, common_data, &mut partition_witness);
 
@@ -154,29 +495,31 @@ where
     let witness = timed!(
         timing,
         "compute full witness",
-        partition_witness.full_witness()
+        partition_witness.trace_witness(&prover_data.merged_rows)
     );
 
-    let wires_values: Vec<PolynomialValues<F>> = timed!(
+    let wires_polys: Vec<PolynomialCoeffs<F>> = timed!(
         timing,
         "compute wire polynomials",
         witness
             .wire_values
             .par_iter()
-            .map(|column| PolynomialValues::new(column.clone()))
+            .map(|column| precomputation.interpolate(PolynomialValues::new(column.clone())))
             .collect()
     );
 
     let wires_commitment = timed!(
         timing,
         "compute wires commitment",
-        PolynomialBatch::<F, C, D>::from_values(
-            wires_values,
+        PolynomialBatch::<F, C, D>::from_coeffs_with_randomness(
+            wires_polys,
             config.fri_config.rate_bits,
             config.zero_knowledge && PlonkOracle::WIRES.blinding,
             config.fri_config.cap_height,
             timing,
             prover_data.fft_root_table.as_ref(),
+            options.memory_budget,
+            options.randomness.derive(PlonkOracle::WIRES.index as u64),
         )
     );
 
@@ -188,6 +531,74 @@ where
 
     challenger.observe_cap::<C::Hasher>(&wires_commitment.merkle_tree.cap);
 
+    CommittedWitness {
+        circuit_digest: prover_data.circuit_digest,
+        public_inputs,
+        public_inputs_hash,
+        witness,
+        wires_commitment,
+        challenger,
+        memory_budget: options.memory_budget,
+        randomness: options.randomness,
+    }
+}
+
+/// The second phase of proving: commits to the permutation and quotient polynomials of a proof
+/// whose wires were committed to by [`prove_commit_phase`].
+pub fn prove_challenge_phase<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    committed: CommittedWitness<F, C, D>,
+    timing: &mut TimingTree,
+) -> Result<ChallengedProof<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    challenge_phase(
+        prover_data,
+        common_data,
+        committed,
+        &ProverPrecomputation::new(common_data),
+        timing,
+    )
+}
+
+fn challenge_phase<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    committed: CommittedWitness<F, C, D>,
+    precomputation: &ProverPrecomputation<F>,
+    timing: &mut TimingTree,
+) -> Result<ChallengedProof<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    ensure!(
+        committed.circuit_digest == prover_data.circuit_digest,
+        "The witness was committed to for another circuit."
+    );
+    let CommittedWitness {
+        circuit_digest,
+        public_inputs,
+        public_inputs_hash,
+        witness,
+        wires_commitment,
+        mut challenger,
+        memory_budget,
+        randomness,
+    } = committed;
+    let has_lookup = !common_data.luts.is_empty();
+    let config = &common_data.config;
+    let num_challenges = config.num_challenges;
+    let quotient_degree = common_data.quotient_degree();
+    let degree = common_data.degree();
+
     // We need 4 values per challenge: 2 for the combos, 1 for (X-combo) in the accumulators and 1 to prove that the lookup table was computed correctly.
     // We can reuse betas and gammas for two of them.
     let num_lookup_challenges = NUM_COINS_LOOKUP * num_challenges;
@@ -237,13 +648,18 @@ where
     let partial_products_zs_and_lookup_commitment = timed!(
         timing,
         "commit to partial products, Z's and, if any, lookup polynomials",
-        PolynomialBatch::from_values(
-            zs_partial_products_lookups,
+        PolynomialBatch::from_coeffs_with_randomness(
+            zs_partial_products_lookups
+                .into_par_iter()
+                .map(|values| precomputation.interpolate(values))
+                .collect(),
             config.fri_config.rate_bits,
             config.zero_knowledge && PlonkOracle::ZS_PARTIAL_PRODUCTS.blinding,
             config.fri_config.cap_height,
             timing,
             prover_data.fft_root_table.as_ref(),
+            memory_budget,
+            randomness.derive(PlonkOracle::ZS_PARTIAL_PRODUCTS.index as u64),
         )
     );
 
@@ -264,6 +680,8 @@ where
             &gammas,
             &deltas,
             &alphas,
+            precomputation,
+            memory_budget,
         )
     );
 
@@ -272,26 +690,40 @@ where
         "split up quotient polys",
         quotient_polys
             .into_par_iter()
-            .flat_map(|mut quotient_poly| {
-                quotient_poly.trim_to_len(quotient_degree).expect(
-                    "Quotient has failed, the vanishing polynomial is not divisible by Z_H",
-                );
+            .map(|mut quotient_poly| {
+                quotient_poly.trim_to_len(quotient_degree).ok()?;
                 // Split quotient into degree-n chunks.
-                quotient_poly.chunks(degree)
+                Some(quotient_poly.chunks(degree))
             })
-            .collect()
-    );
+            .collect::<Option<Vec<_>>>()
+    )
+    .unwrap_or_else(|| {
+        panic!(
+            "Quotient has failed, the vanishing polynomial is not divisible by Z_H. {}",
+            describe_unsatisfied_constraint(
+                prover_data,
+                common_data,
+                &witness,
+                &public_inputs_hash
+            )
+        )
+    })
+    .into_iter()
+    .flatten()
+    .collect();
 
     let quotient_polys_commitment = timed!(
         timing,
         "commit to quotient polys",
-        PolynomialBatch::<F, C, D>::from_coeffs(
+        PolynomialBatch::<F, C, D>::from_coeffs_with_randomness(
             all_quotient_poly_chunks,
             config.fri_config.rate_bits,
             config.zero_knowledge && PlonkOracle::QUOTIENT.blinding,
             config.fri_config.cap_height,
             timing,
             prover_data.fft_root_table.as_ref(),
+            memory_budget,
+            randomness.derive(PlonkOracle::QUOTIENT.index as u64),
         )
     );
 
@@ -301,12 +733,45 @@ where
     // To avoid leaking witness data, we want to ensure that our opening locations, `zeta` and
     // `g * zeta`, are not in our subgroup `H`. It suffices to check `zeta` only, since
     // `(g * zeta)^n = zeta^n`, where `n` is the order of `g`.
-    let g = F::Extension::primitive_root_of_unity(common_data.degree_bits());
     ensure!(
         zeta.exp_power_of_2(common_data.degree_bits()) != F::Extension::ONE,
         "Opening point is in the subgroup."
     );
 
+    Ok(ChallengedProof {
+        circuit_digest,
+        public_inputs,
+        wires_commitment,
+        partial_products_zs_and_lookup_commitment,
+        quotient_polys_commitment,
+        zeta,
+        challenger,
+    })
+}
+
+/// The last phase of proving: computes the openings of a proof at the point drawn by
+/// [`prove_challenge_phase`], and proves them with FRI.
+pub fn prove_open_phase<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    challenged: ChallengedProof<F, C, D>,
+    timing: &mut TimingTree,
+) -> ProofWithPublicInputs<F, C, D>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    let ChallengedProof {
+        circuit_digest: _,
+        public_inputs,
+        wires_commitment,
+        partial_products_zs_and_lookup_commitment,
+        quotient_polys_commitment,
+        zeta,
+        mut challenger,
+    } = challenged;
+    let g = F::Extension::primitive_root_of_unity(common_data.degree_bits());
+
     let openings = timed!(
         timing,
         "construct the opening set, including lookups",
@@ -347,10 +812,10 @@ where
         openings,
         opening_proof,
     };
-    Ok(ProofWithPublicInputs::<F, C, D> {
+    ProofWithPublicInputs::<F, C, D> {
         proof,
         public_inputs,
-    })
+    }
 }
 
 /// Compute the partial products used in the `Z` polynomials.
@@ -613,6 +1078,8 @@ fn compute_quotient_polys<
     gammas: &[F],
     deltas: &[F],
     alphas: &[F],
+    precomputation: &ProverPrecomputation<F>,
+    memory_budget: Option<usize>,
 ) -> Vec<PolynomialCoeffs<F>> {
     let num_challenges = common_data.config.num_challenges;
 
@@ -632,10 +1099,10 @@ fn compute_quotient_polys<
     // steps away since we work on an LDE of degree `max_filtered_constraint_degree`.
     let next_step = 1 << quotient_degree_bits;
 
-    let points = F::two_adic_subgroup(common_data.degree_bits() + quotient_degree_bits);
+    let points = &precomputation.quotient_points;
     let lde_size = points.len();
 
-    let z_h_on_coset = ZeroPolyOnCoset::new(common_data.degree_bits(), quotient_degree_bits);
+    let z_h_on_coset = &precomputation.z_h_on_coset;
 
     // Precompute the lookup table evals on the challenges in delta
     // These values are used to produce the final RE constraints for each lut,
@@ -673,135 +1140,307 @@ fn compute_quotient_polys<
     let lut_re_poly_evals_refs: Vec<&[F]> =
         lut_re_poly_evals.iter().map(|v| v.as_slice()).collect();
 
-    let points_batches = points.par_chunks(BATCH_SIZE);
-    let num_batches = ceil_div_usize(points.len(), BATCH_SIZE);
-
-    let quotient_values: Vec<Vec<F>> = points_batches
-        .enumerate()
-        .flat_map(|(batch_i, xs_batch)| {
-            // Each batch must be the same size, except the last one, which may be smaller.
-            debug_assert!(
-                xs_batch.len() == BATCH_SIZE
-                    || (batch_i == num_batches - 1 && xs_batch.len() <= BATCH_SIZE)
-            );
+    // The values are computed by windows of points, each holding about `memory_budget` bytes
+    // of quotient values, which are then moved to the per-challenge columns.
+    let window_size = match memory_budget {
+        Some(budget) => {
+            let row_size = size_of::<Vec<F>>() + num_challenges * size_of::<F>();
+            (budget / row_size / BATCH_SIZE).max(1) * BATCH_SIZE
+        }
+        None => lde_size,
+    };
+    let mut quotient_columns = (0..num_challenges)
+        .map(|_| Vec::with_capacity(lde_size))
+        .collect::<Vec<_>>();
+    for (window_i, xs_window) in points.chunks(window_size).enumerate() {
+        let window_start = window_i * window_size;
+        let num_batches = ceil_div_usize(xs_window.len(), BATCH_SIZE);
+        let quotient_values: Vec<Vec<F>> = xs_window
+            .par_chunks(BATCH_SIZE)
+            .enumerate()
+            .flat_map(|(batch_i, xs_batch)| {
+                // Each batch must be the same size, except the last one, which may be smaller.
+                debug_assert!(
+                    xs_batch.len() == BATCH_SIZE
+                        || (batch_i == num_batches - 1 && xs_batch.len() <= BATCH_SIZE)
+                );
 
-            let indices_batch: Vec<usize> =
-                (BATCH_SIZE * batch_i..BATCH_SIZE * batch_i + xs_batch.len()).collect();
+                let batch_start = window_start + BATCH_SIZE * batch_i;
+                let indices_batch: Vec<usize> =
+                    (batch_start..batch_start + xs_batch.len()).collect();
 
-            let mut shifted_xs_batch = Vec::with_capacity(xs_batch.len());
-            let mut local_zs_batch = Vec::with_capacity(xs_batch.len());
-            let mut next_zs_batch = Vec::with_capacity(xs_batch.len());
+                let mut shifted_xs_batch = Vec::with_capacity(xs_batch.len());
+                let mut local_zs_batch = Vec::with_capacity(xs_batch.len());
+                let mut next_zs_batch = Vec::with_capacity(xs_batch.len());
 
-            let mut local_lookup_batch = Vec::with_capacity(xs_batch.len());
-            let mut next_lookup_batch = Vec::with_capacity(xs_batch.len());
+                let mut local_lookup_batch = Vec::with_capacity(xs_batch.len());
+                let mut next_lookup_batch = Vec::with_capacity(xs_batch.len());
 
-            let mut partial_products_batch = Vec::with_capacity(xs_batch.len());
-            let mut s_sigmas_batch = Vec::with_capacity(xs_batch.len());
+                let mut partial_products_batch = Vec::with_capacity(xs_batch.len());
+                let mut s_sigmas_batch = Vec::with_capacity(xs_batch.len());
 
-            let mut local_constants_batch_refs = Vec::with_capacity(xs_batch.len());
-            let mut local_wires_batch_refs = Vec::with_capacity(xs_batch.len());
+                let mut local_constants_batch_refs = Vec::with_capacity(xs_batch.len());
+                let mut local_wires_batch_refs = Vec::with_capacity(xs_batch.len());
 
-            for (&i, &x) in indices_batch.iter().zip(xs_batch) {
-                let shifted_x = F::coset_shift() * x;
-                let i_next = (i + next_step) % lde_size;
-                let local_constants_sigmas = prover_data
-                    .constants_sigmas_commitment
-                    .get_lde_values(i, step);
-                let local_constants = &local_constants_sigmas[common_data.constants_range()];
-                let s_sigmas = &local_constants_sigmas[common_data.sigmas_range()];
-                let local_wires = wires_commitment.get_lde_values(i, step);
-                let local_zs_partial_and_lookup =
-                    zs_partial_products_and_lookup_commitment.get_lde_values(i, step);
-                let next_zs_partial_and_lookup =
-                    zs_partial_products_and_lookup_commitment.get_lde_values(i_next, step);
+                for (&i, &x) in indices_batch.iter().zip(xs_batch) {
+                    let shifted_x = F::coset_shift() * x;
+                    let i_next = (i + next_step) % lde_size;
+                    let local_constants_sigmas = prover_data
+                        .constants_sigmas_commitment
+                        .get_lde_values(i, step);
+                    let local_constants = &local_constants_sigmas[common_data.constants_range()];
+                    let s_sigmas = &local_constants_sigmas[common_data.sigmas_range()];
+                    let local_wires = wires_commitment.get_lde_values(i, step);
+                    let local_zs_partial_and_lookup =
+                        zs_partial_products_and_lookup_commitment.get_lde_values(i, step);
+                    let next_zs_partial_and_lookup =
+                        zs_partial_products_and_lookup_commitment.get_lde_values(i_next, step);
 
-                let local_zs = &local_zs_partial_and_lookup[common_data.zs_range()];
+                    let local_zs = &local_zs_partial_and_lookup[common_data.zs_range()];
 
-                let next_zs = &next_zs_partial_and_lookup[common_data.zs_range()];
+                    let next_zs = &next_zs_partial_and_lookup[common_data.zs_range()];
 
-                let partial_products =
-                    &local_zs_partial_and_lookup[common_data.partial_products_range()];
+                    let partial_products =
+                        &local_zs_partial_and_lookup[common_data.partial_products_range()];
 
-                if has_lookup {
-                    let local_lookup_zs = &local_zs_partial_and_lookup[common_data.lookup_range()];
+                    if has_lookup {
+                        let local_lookup_zs =
+                            &local_zs_partial_and_lookup[common_data.lookup_range()];
 
-                    let next_lookup_zs = &next_zs_partial_and_lookup[common_data.lookup_range()];
-                    debug_assert_eq!(local_lookup_zs.len(), common_data.num_all_lookup_polys());
+                        let next_lookup_zs =
+                            &next_zs_partial_and_lookup[common_data.lookup_range()];
+                        debug_assert_eq!(local_lookup_zs.len(), common_data.num_all_lookup_polys());
 
-                    local_lookup_batch.push(local_lookup_zs);
-                    next_lookup_batch.push(next_lookup_zs);
-                }
+                        local_lookup_batch.push(local_lookup_zs);
+                        next_lookup_batch.push(next_lookup_zs);
+                    }
 
-                debug_assert_eq!(local_wires.len(), common_data.config.num_wires);
-                debug_assert_eq!(local_zs.len(), num_challenges);
+                    debug_assert_eq!(local_wires.len(), common_data.config.num_wires);
+                    debug_assert_eq!(local_zs.len(), num_challenges);
 
-                local_constants_batch_refs.push(local_constants);
-                local_wires_batch_refs.push(local_wires);
+                    local_constants_batch_refs.push(local_constants);
+                    local_wires_batch_refs.push(local_wires);
 
-                shifted_xs_batch.push(shifted_x);
-                local_zs_batch.push(local_zs);
-                next_zs_batch.push(next_zs);
-                partial_products_batch.push(partial_products);
-                s_sigmas_batch.push(s_sigmas);
-            }
+                    shifted_xs_batch.push(shifted_x);
+                    local_zs_batch.push(local_zs);
+                    next_zs_batch.push(next_zs);
+                    partial_products_batch.push(partial_products);
+                    s_sigmas_batch.push(s_sigmas);
+                }
 
-            // NB (JN): I'm not sure how (in)efficient the below is. It needs measuring.
-            let mut local_constants_batch =
-                vec![F::ZERO; xs_batch.len() * local_constants_batch_refs[0].len()];
-            for i in 0..local_constants_batch_refs[0].len() {
-                for (j, constants) in local_constants_batch_refs.iter().enumerate() {
-                    local_constants_batch[i * xs_batch.len() + j] = constants[i];
+                // NB (JN): I'm not sure how (in)efficient the below is. It needs measuring.
+                let mut local_constants_batch =
+                    vec![F::ZERO; xs_batch.len() * local_constants_batch_refs[0].len()];
+                for i in 0..local_constants_batch_refs[0].len() {
+                    for (j, constants) in local_constants_batch_refs.iter().enumerate() {
+                        local_constants_batch[i * xs_batch.len() + j] = constants[i];
+                    }
                 }
-            }
 
-            let mut local_wires_batch =
-                vec![F::ZERO; xs_batch.len() * local_wires_batch_refs[0].len()];
-            for i in 0..local_wires_batch_refs[0].len() {
-                for (j, wires) in local_wires_batch_refs.iter().enumerate() {
-                    local_wires_batch[i * xs_batch.len() + j] = wires[i];
+                let mut local_wires_batch =
+                    vec![F::ZERO; xs_batch.len() * local_wires_batch_refs[0].len()];
+                for i in 0..local_wires_batch_refs[0].len() {
+                    for (j, wires) in local_wires_batch_refs.iter().enumerate() {
+                        local_wires_batch[i * xs_batch.len() + j] = wires[i];
+                    }
                 }
-            }
 
-            let vars_batch = EvaluationVarsBaseBatch::new(
-                xs_batch.len(),
-                &local_constants_batch,
-                &local_wires_batch,
-                public_inputs_hash,
-            );
+                let vars_batch = EvaluationVarsBaseBatch::new(
+                    xs_batch.len(),
+                    &local_constants_batch,
+                    &local_wires_batch,
+                    public_inputs_hash,
+                );
 
-            let mut quotient_values_batch = eval_vanishing_poly_base_batch::<F, D>(
-                common_data,
-                &indices_batch,
-                &shifted_xs_batch,
-                vars_batch,
-                &local_zs_batch,
-                &next_zs_batch,
-                &local_lookup_batch,
-                &next_lookup_batch,
-                &partial_products_batch,
-                &s_sigmas_batch,
-                betas,
-                gammas,
-                deltas,
-                alphas,
-                &z_h_on_coset,
-                &lut_re_poly_evals_refs,
-            );
+                let mut quotient_values_batch = eval_vanishing_poly_base_batch::<F, D>(
+                    common_data,
+                    &indices_batch,
+                    &shifted_xs_batch,
+                    vars_batch,
+                    &local_zs_batch,
+                    &next_zs_batch,
+                    &local_lookup_batch,
+                    &next_lookup_batch,
+                    &partial_products_batch,
+                    &s_sigmas_batch,
+                    betas,
+                    gammas,
+                    deltas,
+                    alphas,
+                    z_h_on_coset,
+                    &lut_re_poly_evals_refs,
+                );
 
-            for (&i, quotient_values) in indices_batch.iter().zip(quotient_values_batch.iter_mut())
-            {
-                let denominator_inv = z_h_on_coset.eval_inverse(i);
-                quotient_values
-                    .iter_mut()
-                    .for_each(|v| *v *= denominator_inv);
-            }
-            quotient_values_batch
-        })
-        .collect();
+                for (&i, quotient_values) in
+                    indices_batch.iter().zip(quotient_values_batch.iter_mut())
+                {
+                    let denominator_inv = z_h_on_coset.eval_inverse(i);
+                    quotient_values
+                        .iter_mut()
+                        .for_each(|v| *v *= denominator_inv);
+                }
+                quotient_values_batch
+            })
+            .collect();
+        for (j, column) in quotient_columns.iter_mut().enumerate() {
+            column.extend(quotient_values.iter().map(|row| row[j]));
+        }
+    }
 
-    transpose(&quotient_values)
+    quotient_columns
         .into_par_iter()
-        .map(PolynomialValues::new)
-        .map(|values| values.coset_ifft(F::coset_shift()))
+        .map(|values| {
+            let mut coeffs = ifft_with_options(
+                values.into(),
+                None,
+                Some(precomputation.quotient_root_table.as_ref()),
+            );
+            // Turn the interpolant on the coset into the quotient polynomial.
+            coeffs
+                .coeffs
+                .iter_mut()
+                .zip(F::coset_shift().inverse().powers())
+                .for_each(|(c, r)| *c *= r);
+            coeffs
+        })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_prove_in_phases() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let (prover_data, common_data) = (&data.prover_only, &data.common);
+        let inputs = |x_value: u64| {
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u64(x_value));
+            pw
+        };
+
+        // Interleave the phases of two proofs.
+        let timing = &mut TimingTree::default();
+        let options = ProverOptions::default();
+        let committed_0 = prove_commit_phase(prover_data, common_data, inputs(2), &options, timing);
+        let challenged_0 = prove_challenge_phase(prover_data, common_data, committed_0, timing)?;
+        let committed_1 = prove_commit_phase(prover_data, common_data, inputs(3), &options, timing);
+        let proof_0 = prove_open_phase(prover_data, common_data, challenged_0, timing);
+        let challenged_1 = prove_challenge_phase(prover_data, common_data, committed_1, timing)?;
+        let proof_1 = prove_open_phase(prover_data, common_data, challenged_1, timing);
+
+        assert_eq!(proof_0.public_inputs, [F::from_canonical_u64(128)]);
+        assert_eq!(proof_1.public_inputs, [F::from_canonical_u64(2187)]);
+        data.verify(proof_0)?;
+        data.verify(proof_1)
+    }
+
+    #[test]
+    fn test_prove_with_memory_budget() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        // Computing the LDEs one polynomial at a time gives the same commitment.
+        let polys = (0..5)
+            .map(|_| PolynomialCoeffs::new(F::rand_vec(1 << 6)))
+            .collect::<Vec<_>>();
+        let commit = |memory_budget| {
+            PolynomialBatch::<F, C, D>::from_coeffs_with_memory_budget(
+                polys.clone(),
+                3,
+                false,
+                2,
+                &mut TimingTree::default(),
+                None,
+                memory_budget,
+            )
+            .merkle_tree
+        };
+        assert_eq!(commit(Some(1)), commit(None));
+
+        let mut config = CircuitConfig::standard_recursion_zk_config();
+        config.num_challenges = 3;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+
+        // A budget smaller than any buffer falls back to the smallest chunks.
+        let options = ProverOptions {
+            memory_budget: Some(1),
+            ..Default::default()
+        };
+        let proof = data.prove_with_options(pw, &options)?;
+        assert_eq!(proof.public_inputs, [F::from_canonical_u64(128)]);
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_prove_with_seeded_randomness() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_zk_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let prove = |randomness| {
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::TWO);
+            let options = ProverOptions {
+                randomness,
+                ..Default::default()
+            };
+            data.prove_with_options(pw, &options)
+        };
+
+        // A zero-knowledge proof is reproducible from its seed, down to its blinding values.
+        let proof = prove(ProverRandomness::Seeded(1))?;
+        assert_eq!(proof, prove(ProverRandomness::Seeded(1))?);
+        assert_ne!(proof, prove(ProverRandomness::Seeded(2))?);
+        assert_ne!(proof, prove(ProverRandomness::Os)?);
+        data.verify(proof)
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_prove_in_thread_pool() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+
+        let thread_pool = plonky2_maybe_rayon::rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()?;
+        let proof = data.prove_in_thread_pool(pw, &thread_pool)?;
+        data.verify(proof)
+    }
+}
@@ -0,0 +1,343 @@
+//! Named, typed public inputs.
+//!
+//! Public inputs registered through [`CircuitBuilder::register_named_public_input`] and its
+//! siblings are recorded in a [`PublicInputLayout`], which ends up in the circuit's
+//! [`VerifierOnlyCircuitData`]. Consumers of proofs can then look public inputs up by name instead
+//! of by raw index, and check that a circuit exposes the layout they were written against. A
+//! non-empty layout is part of the circuit digest, so proofs are bound to it.
+//!
+//! [`CircuitBuilder::register_named_public_input`]: crate::plonk::circuit_builder::CircuitBuilder::register_named_public_input
+//! [`VerifierOnlyCircuitData`]: crate::plonk::circuit_data::VerifierOnlyCircuitData
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use anyhow::{anyhow, ensure, Result};
+use keccak_hash::keccak;
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash_types::{HashOut, RichField, NUM_HASH_OUT_ELTS};
+
+/// The type of a named public input, which determines how many public input elements it spans.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum PublicInputType {
+    /// A single field element.
+    Field,
+    /// A hash, spanning `NUM_HASH_OUT_ELTS` elements.
+    Hash,
+    /// An array of the given length, with each element range-checked to 32 bits.
+    U32Array(usize),
+}
+
+impl PublicInputType {
+    /// The number of public input elements spanned by a value of this type.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Field => 1,
+            Self::Hash => NUM_HASH_OUT_ELTS,
+            Self::U32Array(len) => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn descriptor(&self) -> String {
+        match self {
+            Self::Field => "field".to_string(),
+            Self::Hash => "hash".to_string(),
+            Self::U32Array(len) => format!("u32[{len}]"),
+        }
+    }
+}
+
+/// A named public input, spanning the public input elements `start..start + ty.len()`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NamedPublicInput {
+    pub name: String,
+    pub ty: PublicInputType,
+    pub start: usize,
+}
+
+impl NamedPublicInput {
+    /// The indices of the public input elements holding this input.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.ty.len()
+    }
+}
+
+/// The named public inputs of a circuit, in registration order. Public inputs registered without a
+/// name do not appear here, but still take up indices.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PublicInputLayout {
+    entries: Vec<NamedPublicInput>,
+}
+
+impl PublicInputLayout {
+    /// Builds a layout from its entries, checking that names are unique and that entries don't
+    /// overlap.
+    pub fn new(entries: Vec<NamedPublicInput>) -> Result<Self> {
+        let mut layout = Self::default();
+        for entry in entries {
+            layout.push(entry)?;
+        }
+        Ok(layout)
+    }
+
+    pub(crate) fn push(&mut self, entry: NamedPublicInput) -> Result<()> {
+        ensure!(
+            !entry.name.is_empty() && !entry.name.contains([':', ';', '@']),
+            "Invalid public input name `{}`",
+            entry.name
+        );
+        ensure!(
+            self.get(&entry.name).is_none(),
+            "Public input `{}` is registered twice",
+            entry.name
+        );
+        if let Some(other) = self.entries.iter().find(|other| {
+            let (a, b) = (other.range(), entry.range());
+            a.start < b.end && b.start < a.end
+        }) {
+            return Err(anyhow!(
+                "Public inputs `{}` and `{}` overlap",
+                other.name,
+                entry.name
+            ));
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[NamedPublicInput] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NamedPublicInput> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Returns the public input elements holding the input `name`, if it exists and lies within
+    /// `public_inputs`.
+    pub fn values<'a, T>(&self, name: &str, public_inputs: &'a [T]) -> Option<&'a [T]> {
+        public_inputs.get(self.get(name)?.range())
+    }
+
+    /// Returns the input `name`, if it was registered as a field element.
+    pub fn field<F: RichField>(&self, name: &str, public_inputs: &[F]) -> Option<F> {
+        self.typed_values(name, PublicInputType::Field, public_inputs)
+            .map(|values| values[0])
+    }
+
+    /// Returns the input `name`, if it was registered as a hash.
+    pub fn hash<F: RichField>(&self, name: &str, public_inputs: &[F]) -> Option<HashOut<F>> {
+        self.typed_values(name, PublicInputType::Hash, public_inputs)
+            .map(HashOut::from_vec)
+    }
+
+    /// Returns the input `name`, if it was registered as an array of `u32`s.
+    pub fn u32s<F: RichField>(&self, name: &str, public_inputs: &[F]) -> Option<Vec<u32>> {
+        let entry = self.get(name)?;
+        let PublicInputType::U32Array(_) = entry.ty else {
+            return None;
+        };
+        public_inputs
+            .get(entry.range())?
+            .iter()
+            .map(|x| u32::try_from(x.to_canonical_u64()).ok())
+            .collect()
+    }
+
+    fn typed_values<F: RichField>(
+        &self,
+        name: &str,
+        ty: PublicInputType,
+        public_inputs: &[F],
+    ) -> Option<Vec<F>> {
+        let entry = self.get(name)?;
+        if entry.ty != ty {
+            return None;
+        }
+        public_inputs
+            .get(entry.range())
+            .map(|values| values.to_vec())
+    }
+
+    /// A canonical textual description of the layout, e.g. `root:hash@0;amounts:u32[2]@4`. Two
+    /// layouts are equal if and only if their descriptors are.
+    pub fn descriptor(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| format!("{}:{}@{}", entry.name, entry.ty.descriptor(), entry.start))
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    /// The Keccak hash of the [`descriptor`](Self::descriptor), which can be pinned by downstream
+    /// code to detect layout changes.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        keccak(self.descriptor().as_bytes()).0
+    }
+
+    /// The [`fingerprint`](Self::fingerprint) as field elements, one per 32-bit limb, to be
+    /// hashed into the circuit digest.
+    pub(crate) fn fingerprint_elements<F: RichField>(&self) -> Vec<F> {
+        self.fingerprint()
+            .chunks(4)
+            .map(|limb| F::from_canonical_u32(u32::from_le_bytes(limb.try_into().unwrap())))
+            .collect()
+    }
+
+    /// Checks that this layout contains each input of `expected`, with the same type and position.
+    /// Inputs not present in `expected` are allowed, so that circuits can expose new inputs
+    /// without breaking existing consumers.
+    pub fn validate(&self, expected: &PublicInputLayout) -> Result<()> {
+        for entry in &expected.entries {
+            let actual = self
+                .get(&entry.name)
+                .ok_or_else(|| anyhow!("Missing public input `{}`", entry.name))?;
+            ensure!(
+                actual == entry,
+                "Public input `{}` is {} at index {}, expected {} at index {}",
+                entry.name,
+                actual.ty.descriptor(),
+                actual.start,
+                entry.ty.descriptor(),
+                entry.start
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::{Field, Sample};
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, VerifierOnlyCircuitData};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::serialization::{SERIALIZATION_HEADER_LEN, SERIALIZATION_MAGIC};
+
+    #[test]
+    fn test_named_public_inputs() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let unnamed = builder.add_virtual_target();
+        builder.register_public_input(unnamed);
+        let root = builder.add_virtual_hash();
+        builder.register_named_public_hash("root", root);
+        let amounts = builder.add_virtual_targets(2);
+        builder.register_named_public_u32s("amounts", &amounts);
+        let total = builder.add(amounts[0], amounts[1]);
+        builder.register_named_public_input("total", total);
+        let data = builder.build::<C>();
+
+        let layout = &data.verifier_only.public_input_layout;
+        assert_eq!(
+            layout.descriptor(),
+            "root:hash@1;amounts:u32[2]@5;total:field@7"
+        );
+        assert_eq!(layout.get("total").unwrap().range(), 7..8);
+        assert!(layout.get("unnamed").is_none());
+
+        let root_value = HashOut::<F>::rand();
+        let mut pw = PartialWitness::new();
+        pw.set_target(unnamed, F::ZERO);
+        pw.set_hash_target(root, root_value);
+        pw.set_target(amounts[0], F::from_canonical_u32(3));
+        pw.set_target(amounts[1], F::from_canonical_u32(u32::MAX));
+        let proof = data.prove(pw)?;
+        let pis = &proof.public_inputs;
+        assert_eq!(layout.hash("root", pis), Some(root_value));
+        assert_eq!(layout.u32s("amounts", pis), Some(vec![3, u32::MAX]));
+        assert_eq!(
+            layout.field("total", pis),
+            Some(F::from_canonical_u64(3 + u32::MAX as u64))
+        );
+        // Lookups with the wrong type fail.
+        assert_eq!(layout.field("root", pis), None);
+        data.verify(proof)?;
+
+        // The layout survives serialization, and a subset of it validates.
+        let bytes = data.verifier_only.to_bytes().unwrap();
+        let decoded = VerifierOnlyCircuitData::<C, D>::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(
+            decoded.public_input_layout.fingerprint(),
+            layout.fingerprint()
+        );
+        let expected = PublicInputLayout::new(vec![NamedPublicInput {
+            name: "amounts".into(),
+            ty: PublicInputType::U32Array(2),
+            start: 5,
+        }])?;
+        decoded.public_input_layout.validate(&expected)?;
+        let moved = PublicInputLayout::new(vec![NamedPublicInput {
+            name: "total".into(),
+            ty: PublicInputType::Field,
+            start: 0,
+        }])?;
+        assert!(decoded.public_input_layout.validate(&moved).is_err());
+
+        // Version 1 data, which has no layout, still decodes.
+        let mut v1_bytes = SERIALIZATION_MAGIC.to_vec();
+        v1_bytes.extend(1u16.to_le_bytes());
+        v1_bytes.extend(&bytes[SERIALIZATION_MAGIC.len() + 2..SERIALIZATION_HEADER_LEN]);
+        let mut v1_data = data.verifier_only.clone();
+        v1_data.public_input_layout = PublicInputLayout::default();
+        let v2_bytes = v1_data.to_bytes().unwrap();
+        // Drop the encoding of the empty layout, i.e. its length.
+        v1_bytes.extend(&v2_bytes[SERIALIZATION_HEADER_LEN..v2_bytes.len() - 8]);
+        assert_eq!(
+            VerifierOnlyCircuitData::<C, D>::from_bytes(v1_bytes).unwrap(),
+            v1_data
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layout_in_circuit_digest() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let circuit_digest = |name: Option<&str>| {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let x = builder.add_virtual_target();
+            match name {
+                Some(name) => builder.register_named_public_input(name, x),
+                None => builder.register_public_input(x),
+            }
+            builder.build::<C>().verifier_only.circuit_digest
+        };
+
+        // Renaming an input changes the digest, as does naming it.
+        assert_ne!(circuit_digest(Some("x")), circuit_digest(Some("y")));
+        assert_ne!(circuit_digest(Some("x")), circuit_digest(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "registered twice")]
+    fn test_duplicate_public_input_name() {
+        const D: usize = 2;
+        type F = <PoseidonGoldilocksConfig as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        builder.register_named_public_input("x", x);
+        builder.register_named_public_input("x", x);
+    }
+}
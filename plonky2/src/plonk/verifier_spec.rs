@@ -0,0 +1,252 @@
+//! A description of a plonky2 verifier as plain data, for re-verifying proofs in other proof
+//! systems.
+//!
+//! Wrapping a plonky2 proof in, e.g., a halo2 circuit with KZG commitments, for cheaper settlement
+//! on chain, means reimplementing the plonky2 verifier in that circuit. A [`VerifierSpec`] gathers
+//! everything such a verifier depends on: the shape of the circuit, its constant and permutation
+//! commitment, its FRI parameters, and the sequence of Fiat-Shamir operations. The transcript
+//! steps only depend on the circuit, and match entry for entry the [`Transcript`] recorded by
+//! [`transcript`](crate::plonk::proof::ProofWithPublicInputs::transcript), whose challenges can
+//! serve as test vectors.
+//!
+//! Like the gnark export, this targets Goldilocks proofs with `D = 2` and Poseidon hashes. The
+//! challenger is a duplex sponge over the Poseidon permutation with width [`SPONGE_WIDTH`] and rate
+//! [`SPONGE_RATE`], see [`Challenger`](crate::iop::challenger::Challenger).
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::field::extension::Extendable;
+use crate::field::goldilocks_field::GoldilocksField;
+use crate::field::types::{Field, PrimeField64};
+use crate::fri::FriParams;
+use crate::hash::hash_types::NUM_HASH_OUT_ELTS;
+use crate::hash::poseidon::{PoseidonHash, SPONGE_RATE, SPONGE_WIDTH};
+use crate::iop::transcript::{Transcript, TranscriptOp};
+use crate::plonk::circuit_builder::NUM_COINS_LOOKUP;
+use crate::plonk::circuit_data::{CommonCircuitData, VerifierOnlyCircuitData};
+use crate::plonk::config::GenericConfig;
+
+const D: usize = 2;
+
+/// Consecutive challenger operations of the same kind, as in a [`Transcript`] but without values.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptStep {
+    pub label: String,
+    pub op: TranscriptOp,
+    /// The number of field elements observed or sampled. An extension field element counts as
+    /// `D` elements.
+    pub len: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VerifierSpec {
+    pub sponge_width: usize,
+    pub sponge_rate: usize,
+    pub degree_bits: usize,
+    pub num_wires: usize,
+    pub num_routed_wires: usize,
+    pub num_constants: usize,
+    pub num_public_inputs: usize,
+    pub num_challenges: usize,
+    pub quotient_degree_factor: usize,
+    pub num_partial_products: usize,
+    pub num_lookup_polys: usize,
+    pub num_lookup_selectors: usize,
+    /// The coset shifts of the permutation argument.
+    pub k_is: Vec<u64>,
+    /// The ids of the gates, in the order of their selectors.
+    pub gates: Vec<String>,
+    /// For each gate, the index of its selector polynomial.
+    pub selector_indices: Vec<usize>,
+    /// For each selector polynomial, the range of gates it selects, as `[start, end)`.
+    pub selector_groups: Vec<(usize, usize)>,
+    pub fri_params: FriParams,
+    pub constants_sigmas_cap: Vec<[u64; NUM_HASH_OUT_ELTS]>,
+    pub circuit_digest: [u64; NUM_HASH_OUT_ELTS],
+    /// The operations of the verifier's challenger, in order.
+    pub transcript: Vec<TranscriptStep>,
+}
+
+impl VerifierSpec {
+    pub fn new<C>(
+        verifier_only: &VerifierOnlyCircuitData<C, D>,
+        common: &CommonCircuitData<GoldilocksField, D>,
+    ) -> Self
+    where
+        C: GenericConfig<D, F = GoldilocksField, Hasher = PoseidonHash>,
+    {
+        let hash_u64s =
+            |elements: [GoldilocksField; NUM_HASH_OUT_ELTS]| elements.map(|x| x.to_canonical_u64());
+        Self {
+            sponge_width: SPONGE_WIDTH,
+            sponge_rate: SPONGE_RATE,
+            degree_bits: common.degree_bits(),
+            num_wires: common.config.num_wires,
+            num_routed_wires: common.config.num_routed_wires,
+            num_constants: common.num_constants,
+            num_public_inputs: common.num_public_inputs,
+            num_challenges: common.config.num_challenges,
+            quotient_degree_factor: common.quotient_degree_factor,
+            num_partial_products: common.num_partial_products,
+            num_lookup_polys: common.num_lookup_polys,
+            num_lookup_selectors: common.num_lookup_selectors,
+            k_is: common.k_is.iter().map(|k| k.to_canonical_u64()).collect(),
+            gates: common.gates.iter().map(|gate| gate.0.id()).collect(),
+            selector_indices: common.selectors_info.selector_indices.clone(),
+            selector_groups: common
+                .selectors_info
+                .groups
+                .iter()
+                .map(|group| (group.start, group.end))
+                .collect(),
+            fri_params: common.fri_params.clone(),
+            constants_sigmas_cap: verifier_only
+                .constants_sigmas_cap
+                .0
+                .iter()
+                .map(|hash| hash_u64s(hash.elements))
+                .collect(),
+            circuit_digest: hash_u64s(verifier_only.circuit_digest.elements),
+            transcript: transcript_steps(common),
+        }
+    }
+
+    /// Checks that a recorded transcript has the shape of this verifier's transcript.
+    pub fn matches(&self, transcript: &Transcript<GoldilocksField>) -> bool {
+        self.transcript.len() == transcript.entries.len()
+            && self
+                .transcript
+                .iter()
+                .zip(&transcript.entries)
+                .all(|(step, entry)| {
+                    step.label == entry.label
+                        && step.op == entry.op
+                        && step.len == entry.values.len()
+                })
+    }
+}
+
+/// The challenger operations of the verifier of a circuit, following `get_challenges`.
+fn transcript_steps(common: &CommonCircuitData<GoldilocksField, D>) -> Vec<TranscriptStep> {
+    let num_challenges = common.config.num_challenges;
+    let fri_params = &common.fri_params;
+    let cap_len = NUM_HASH_OUT_ELTS << fri_params.config.cap_height;
+    let num_openings: usize = common
+        .get_fri_instance(<GoldilocksField as Extendable<D>>::Extension::ONE)
+        .batches
+        .iter()
+        .map(|batch| batch.polynomials.len())
+        .sum();
+
+    let mut steps = Vec::new();
+    let mut push = |label: &str, op: TranscriptOp, len: usize| {
+        steps.push(TranscriptStep {
+            label: label.to_string(),
+            op,
+            len,
+        })
+    };
+    push("circuit digest", TranscriptOp::Observe, NUM_HASH_OUT_ELTS);
+    push(
+        "public inputs hash",
+        TranscriptOp::Observe,
+        NUM_HASH_OUT_ELTS,
+    );
+    push("wires cap", TranscriptOp::Observe, cap_len);
+    push("plonk betas", TranscriptOp::Challenge, num_challenges);
+    push("plonk gammas", TranscriptOp::Challenge, num_challenges);
+    if common.num_lookup_polys != 0 {
+        push(
+            "plonk deltas",
+            TranscriptOp::Challenge,
+            (NUM_COINS_LOOKUP - 2) * num_challenges,
+        );
+    }
+    push("zs partial products cap", TranscriptOp::Observe, cap_len);
+    push("plonk alphas", TranscriptOp::Challenge, num_challenges);
+    push("quotient polys cap", TranscriptOp::Observe, cap_len);
+    push("plonk zeta", TranscriptOp::Challenge, D);
+    push("openings", TranscriptOp::Observe, num_openings * D);
+
+    push("fri alpha", TranscriptOp::Challenge, D);
+    for _ in &fri_params.reduction_arity_bits {
+        push("fri commit phase cap", TranscriptOp::Observe, cap_len);
+        push("fri beta", TranscriptOp::Challenge, D);
+    }
+    push(
+        "fri final poly",
+        TranscriptOp::Observe,
+        fri_params.final_poly_len() * D,
+    );
+    push("fri pow", TranscriptOp::Observe, 1);
+    push("fri pow", TranscriptOp::Challenge, 1);
+    push(
+        "fri query indices",
+        TranscriptOp::Challenge,
+        fri_params.config.num_query_rounds,
+    );
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_verifier_spec_matches_transcript() -> Result<()> {
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        let proof = data.prove(pw)?;
+
+        let spec = VerifierSpec::new(&data.verifier_only, &data.common);
+        assert_eq!(spec.num_public_inputs, 1);
+        assert_eq!(spec.gates.len(), data.common.gates.len());
+        assert_eq!(
+            spec.constants_sigmas_cap.len(),
+            1 << spec.fri_params.config.cap_height
+        );
+
+        // The recorded transcript of a proof has the shape of the spec, and its challenges are
+        // those the verifier uses.
+        let transcript = proof.transcript(&data.verifier_only.circuit_digest, &data.common)?;
+        assert!(spec.matches(&transcript));
+        assert_eq!(transcript.replay::<PoseidonHash>(), Ok(()));
+        let challenges = proof.get_challenges(
+            proof.get_public_inputs_hash(),
+            &data.verifier_only.circuit_digest,
+            &data.common,
+        )?;
+        let query_indices = transcript.entries.last().unwrap();
+        assert_eq!(query_indices.label, "fri query indices");
+        assert_eq!(
+            query_indices
+                .values
+                .iter()
+                .map(|x| x.to_canonical_u64() as usize
+                    % (1 << (spec.degree_bits + spec.fri_params.config.rate_bits)))
+                .collect::<Vec<_>>(),
+            challenges.fri_challenges.fri_query_indices
+        );
+
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(serde_json::from_str::<VerifierSpec>(&json).unwrap(), spec);
+        Ok(())
+    }
+}
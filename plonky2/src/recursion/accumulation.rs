@@ -0,0 +1,444 @@
+//! An experimental accumulation scheme for long chains of identical steps, as an alternative to
+//! recursively verifying the previous proof at each step like [`PcdCircuit`] does.
+//!
+//! Unlike folding schemes based on homomorphic commitments, hash-based proofs can't be combined
+//! cheaply, so accumulation here defers verification instead. Steps are accumulated in batches of
+//! `steps_per_batch`, each proven without any recursion, and once the chain ends the batch proofs
+//! are folded pairwise in a tree, each fold checking that the two chains connect. Recursive
+//! verification is thus done about once per batch rather than once per step, at the cost of
+//! storing the batch proofs until the chain ends.
+//!
+//! Proofs have the same public inputs as those of a [`PcdCircuit`], minus the verifier data:
+//! the initial state, the final state and the number of steps.
+//!
+//! [`PcdCircuit`]: crate::recursion::pcd::PcdCircuit
+
+use alloc::vec::Vec;
+
+use anyhow::{anyhow, ensure, Result};
+use plonky2_maybe_rayon::*;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use crate::recursion::pcd::{PcdChain, PcdStep};
+
+/// How steps are accumulated.
+#[derive(Clone, Debug)]
+pub struct AccumulationConfig {
+    /// The number of steps proven by each batch proof.
+    pub steps_per_batch: usize,
+    /// The config of the batch and fold circuits.
+    pub config: CircuitConfig,
+}
+
+impl Default for AccumulationConfig {
+    fn default() -> Self {
+        Self {
+            steps_per_batch: 8,
+            config: CircuitConfig::standard_recursion_config(),
+        }
+    }
+}
+
+/// A chain being accumulated.
+#[derive(Debug)]
+pub struct Accumulator<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, W, const D: usize>
+{
+    state: Vec<F>,
+    /// The witnesses of the steps which aren't part of a batch proof yet.
+    pending: Vec<W>,
+    batches: Vec<ProofWithPublicInputs<F, C, D>>,
+    num_steps: usize,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, W, const D: usize>
+    Accumulator<F, C, W, D>
+{
+    pub fn num_steps(&self) -> usize {
+        self.num_steps
+    }
+}
+
+/// The proof of an accumulated chain.
+#[derive(Clone, Debug)]
+pub struct AccumulatedProof<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub proof: ProofWithPublicInputs<F, C, D>,
+    /// The number of fold levels above the batch proofs, which determines the circuit of `proof`.
+    pub depth: usize,
+}
+
+/// A circuit proving up to `steps_per_batch` steps. Slots past the number of actual steps are
+/// disabled, and replay the last enabled step without updating the state, so that they are
+/// satisfiable with its witness.
+#[derive(Debug)]
+struct BatchCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, W, const D: usize> {
+    data: CircuitData<F, C, D>,
+    initial_state: Vec<Target>,
+    /// Whether each slot but the first one, which is always enabled, holds a step.
+    enabled: Vec<BoolTarget>,
+    steps: Vec<W>,
+}
+
+/// A circuit verifying two proofs of the level below, and checking that the second chain starts
+/// where the first one ends. If `use_right` is false, the second proof is ignored.
+#[derive(Debug)]
+struct FoldCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    data: CircuitData<F, C, D>,
+    left: ProofWithPublicInputsTarget<D>,
+    right: ProofWithPublicInputsTarget<D>,
+    use_right: BoolTarget,
+}
+
+/// Accumulates chains of `step`. Fold circuits are built the first time they are needed, and
+/// reused afterwards.
+#[derive(Debug)]
+pub struct AccumulationScheme<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: PcdStep<F, D>,
+    const D: usize,
+> {
+    step: S,
+    config: AccumulationConfig,
+    batch: BatchCircuit<F, C, S::WitnessTarget, D>,
+    levels: Vec<FoldCircuit<F, C, D>>,
+}
+
+impl<F, C, S, const D: usize> AccumulationScheme<F, C, S, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+    S: PcdStep<F, D>,
+    S::Witness: Clone,
+{
+    pub fn new(step: S, config: AccumulationConfig) -> Result<Self> {
+        ensure!(
+            config.steps_per_batch >= 1,
+            "A batch must hold at least one step"
+        );
+        let batch = Self::build_batch_circuit(&step, &config)?;
+        Ok(Self {
+            step,
+            config,
+            batch,
+            levels: Vec::new(),
+        })
+    }
+
+    fn build_batch_circuit(
+        step: &S,
+        config: &AccumulationConfig,
+    ) -> Result<BatchCircuit<F, C, S::WitnessTarget, D>> {
+        let n = S::STATE_LEN;
+        let mut builder = CircuitBuilder::<F, D>::new(config.config.clone());
+        let initial_state = builder.add_virtual_targets(n);
+        builder.register_public_inputs(&initial_state);
+
+        let mut state = initial_state.clone();
+        let mut input = initial_state.clone();
+        let mut num_steps = builder.one();
+        let mut enabled = Vec::new();
+        let mut steps = Vec::new();
+        for i in 0..config.steps_per_batch {
+            let is_enabled = if i == 0 {
+                builder._true()
+            } else {
+                let is_enabled = builder.add_virtual_bool_target_safe();
+                // Enabled slots must come first.
+                let prev_enabled = enabled.last().copied().unwrap_or(builder._true());
+                let both = builder.and(is_enabled, prev_enabled);
+                builder.connect(both.target, is_enabled.target);
+                num_steps = builder.add(num_steps, is_enabled.target);
+                enabled.push(is_enabled);
+                is_enabled
+            };
+            input = select_all(&mut builder, is_enabled, &state, &input);
+            let (next_state, step_target) = step.build_step(&mut builder, &input);
+            ensure!(
+                next_state.len() == n,
+                "Expected a next state of length {}, got {}",
+                n,
+                next_state.len()
+            );
+            state = select_all(&mut builder, is_enabled, &next_state, &state);
+            steps.push(step_target);
+        }
+        ensure!(
+            builder.num_public_inputs() == n,
+            "An accumulated step must not register public inputs"
+        );
+        builder.register_public_inputs(&state);
+        builder.register_public_input(num_steps);
+
+        Ok(BatchCircuit {
+            data: builder.build::<C>(),
+            initial_state,
+            enabled,
+            steps,
+        })
+    }
+
+    fn build_levels(&mut self, depth: usize) {
+        let n = S::STATE_LEN;
+        while self.levels.len() < depth {
+            let inner = match self.levels.last() {
+                Some(level) => &level.data,
+                None => &self.batch.data,
+            };
+
+            let mut builder = CircuitBuilder::<F, D>::new(self.config.config.clone());
+            let verifier_data = builder.constant_verifier_data(&inner.verifier_only);
+            let left = builder.add_virtual_proof_with_pis(&inner.common);
+            builder.verify_proof::<C>(&left, &verifier_data, &inner.common);
+            let right = builder.add_virtual_proof_with_pis(&inner.common);
+            builder.verify_proof::<C>(&right, &verifier_data, &inner.common);
+            let use_right = builder.add_virtual_bool_target_safe();
+
+            let (left_pis, right_pis) = (&left.public_inputs, &right.public_inputs);
+            for i in 0..n {
+                let diff = builder.sub(right_pis[i], left_pis[n + i]);
+                let diff = builder.mul(use_right.target, diff);
+                builder.assert_zero(diff);
+            }
+            builder.register_public_inputs(&left_pis[..n]);
+            let state = select_all(
+                &mut builder,
+                use_right,
+                &right_pis[n..2 * n],
+                &left_pis[n..2 * n],
+            );
+            builder.register_public_inputs(&state);
+            let num_steps = builder.mul_add(use_right.target, right_pis[2 * n], left_pis[2 * n]);
+            builder.register_public_input(num_steps);
+
+            self.levels.push(FoldCircuit {
+                data: builder.build::<C>(),
+                left,
+                right,
+                use_right,
+            });
+        }
+    }
+
+    pub fn step(&self) -> &S {
+        &self.step
+    }
+
+    /// Starts a chain from `initial_state`.
+    pub fn start(&self, initial_state: &S::State) -> Result<Accumulator<F, C, S::Witness, D>> {
+        let initial_state = S::encode_state(initial_state);
+        ensure!(
+            initial_state.len() == S::STATE_LEN,
+            "Expected a state of length {}, got {}",
+            S::STATE_LEN,
+            initial_state.len()
+        );
+        Ok(Accumulator {
+            state: initial_state,
+            pending: Vec::new(),
+            batches: Vec::new(),
+            num_steps: 0,
+        })
+    }
+
+    /// Adds a step to `accumulator`, proving a batch once enough steps are pending.
+    pub fn accumulate(
+        &self,
+        accumulator: &mut Accumulator<F, C, S::Witness, D>,
+        witness: S::Witness,
+    ) -> Result<()> {
+        accumulator.pending.push(witness);
+        accumulator.num_steps += 1;
+        if accumulator.pending.len() == self.config.steps_per_batch {
+            self.prove_batch(accumulator)?;
+        }
+        Ok(())
+    }
+
+    fn prove_batch(&self, accumulator: &mut Accumulator<F, C, S::Witness, D>) -> Result<()> {
+        let pending = core::mem::take(&mut accumulator.pending);
+        let last = &pending[pending.len() - 1];
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&self.batch.initial_state, &accumulator.state);
+        for (i, target) in self.batch.steps.iter().enumerate() {
+            self.step
+                .set_witness(&mut pw, target, pending.get(i).unwrap_or(last));
+        }
+        for (i, &enabled) in self.batch.enabled.iter().enumerate() {
+            pw.set_bool_target(enabled, i + 1 < pending.len());
+        }
+
+        let proof = self.batch.data.prove(pw)?;
+        accumulator.state = proof.public_inputs[S::STATE_LEN..2 * S::STATE_LEN].to_vec();
+        accumulator.batches.push(proof);
+        Ok(())
+    }
+
+    /// Proves the pending steps of `accumulator`, and folds all its batch proofs into a single
+    /// proof, generating the proofs of each level of the tree in parallel.
+    pub fn finalize(
+        &mut self,
+        mut accumulator: Accumulator<F, C, S::Witness, D>,
+    ) -> Result<AccumulatedProof<F, C, D>> {
+        if !accumulator.pending.is_empty() {
+            self.prove_batch(&mut accumulator)?;
+        }
+        ensure!(!accumulator.batches.is_empty(), "No steps were accumulated");
+
+        let mut layer = accumulator.batches;
+        let mut depth = 0;
+        while layer.len() > 1 {
+            self.build_levels(depth + 1);
+            let level = &self.levels[depth];
+            layer = layer
+                .par_chunks(2)
+                .map(|pair| {
+                    let mut pw = PartialWitness::new();
+                    pw.set_proof_with_pis_target(&level.left, &pair[0]);
+                    pw.set_proof_with_pis_target(&level.right, &pair[pair.len() - 1]);
+                    pw.set_bool_target(level.use_right, pair.len() == 2);
+                    level.data.prove(pw)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            depth += 1;
+        }
+
+        Ok(AccumulatedProof {
+            proof: layer.remove(0),
+            depth,
+        })
+    }
+
+    /// Verifies the proof of an accumulated chain, and returns its initial and final states.
+    pub fn verify(&self, proof: &AccumulatedProof<F, C, D>) -> Result<PcdChain<S::State>> {
+        let data = match proof.depth {
+            0 => &self.batch.data,
+            depth => {
+                &self
+                    .levels
+                    .get(depth - 1)
+                    .ok_or_else(|| anyhow!("No fold circuit at depth {}", depth))?
+                    .data
+            }
+        };
+        data.verify(proof.proof.clone())?;
+
+        let n = S::STATE_LEN;
+        let pis = &proof.proof.public_inputs;
+        Ok(PcdChain {
+            initial_state: S::decode_state(&pis[..n]),
+            state: S::decode_state(&pis[n..2 * n]),
+            num_steps: pis[2 * n].to_canonical_u64(),
+        })
+    }
+}
+
+/// Selects `xs` if `b` is true, and `ys` otherwise, element-wise.
+fn select_all<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    b: BoolTarget,
+    xs: &[Target],
+    ys: &[Target],
+) -> Vec<Target> {
+    xs.iter()
+        .zip(ys)
+        .map(|(&x, &y)| builder.select(b, x, y))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::{Field, PrimeField64};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Multiplies a running product by a private factor.
+    struct Multiply;
+
+    impl PcdStep<F, D> for Multiply {
+        type State = u64;
+        type Witness = u64;
+        type WitnessTarget = Target;
+
+        const STATE_LEN: usize = 1;
+
+        fn encode_state(state: &u64) -> Vec<F> {
+            vec![F::from_canonical_u64(*state)]
+        }
+
+        fn decode_state(elements: &[F]) -> u64 {
+            elements[0].to_canonical_u64()
+        }
+
+        fn build_step(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            prev_state: &[Target],
+        ) -> (Vec<Target>, Target) {
+            let factor = builder.add_virtual_target();
+            (vec![builder.mul(prev_state[0], factor)], factor)
+        }
+
+        fn set_witness(&self, pw: &mut PartialWitness<F>, target: &Target, witness: &u64) {
+            pw.set_target(*target, F::from_canonical_u64(*witness));
+        }
+    }
+
+    #[test]
+    fn test_accumulation() -> Result<()> {
+        let mut scheme = AccumulationScheme::<F, C, _, D>::new(
+            Multiply,
+            AccumulationConfig {
+                steps_per_batch: 2,
+                ..Default::default()
+            },
+        )?;
+
+        // Five steps make three batches, the last one partial, folded in two levels.
+        let mut accumulator = scheme.start(&1)?;
+        for factor in 2..7 {
+            scheme.accumulate(&mut accumulator, factor)?;
+        }
+        assert_eq!(accumulator.num_steps(), 5);
+        let proof = scheme.finalize(accumulator)?;
+        assert_eq!(proof.depth, 2);
+        assert_eq!(
+            scheme.verify(&proof)?,
+            PcdChain {
+                initial_state: 1,
+                state: 720,
+                num_steps: 5
+            }
+        );
+
+        // A single step needs no folding.
+        let mut accumulator = scheme.start(&3)?;
+        scheme.accumulate(&mut accumulator, 5)?;
+        let proof = scheme.finalize(accumulator)?;
+        assert_eq!(proof.depth, 0);
+        assert_eq!(scheme.verify(&proof)?.state, 15);
+
+        // Claiming another state is rejected.
+        let mut tampered = proof;
+        tampered.proof.public_inputs[1] = F::from_canonical_u64(16);
+        assert!(scheme.verify(&tampered).is_err());
+        Ok(())
+    }
+}
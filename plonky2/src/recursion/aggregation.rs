@@ -0,0 +1,240 @@
+//! Aggregation of many proofs of the same circuit into a single proof, using a balanced tree of
+//! recursive proofs.
+//!
+//! Each node of the tree verifies `arity` proofs of the level below and exposes their public inputs,
+//! in order, as its own. The aggregated proof's public inputs are thus the concatenation of those
+//! of the aggregated proofs. The tree is made complete by repeating the last proof.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use plonky2_maybe_rayon::*;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{
+    CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData, VerifierOnlyCircuitData,
+};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use crate::plonk::verifier::verify;
+
+/// How proofs are aggregated.
+#[derive(Clone, Debug)]
+pub struct AggregationStrategy {
+    /// The number of proofs verified by each aggregation circuit.
+    pub arity: usize,
+    /// The config of the aggregation circuits.
+    pub config: CircuitConfig,
+}
+
+impl Default for AggregationStrategy {
+    fn default() -> Self {
+        Self {
+            arity: 2,
+            config: CircuitConfig::standard_recursion_config(),
+        }
+    }
+}
+
+/// A proof aggregating several proofs of the same circuit.
+#[derive(Debug)]
+pub struct AggregatedProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub proof: ProofWithPublicInputs<F, C, D>,
+    /// The public inputs of each aggregated proof, in order. Unlike the public inputs of `proof`,
+    /// these don't include those of the proofs repeated to complete the tree.
+    pub public_inputs: Vec<Vec<F>>,
+    /// The verifier data of the aggregation circuit which generated `proof`.
+    pub verifier_data: VerifierCircuitData<F, C, D>,
+}
+
+/// An aggregation circuit, along with the targets of the proofs it verifies.
+#[derive(Debug)]
+struct AggregationCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    data: CircuitData<F, C, D>,
+    proofs: Vec<ProofWithPublicInputsTarget<D>>,
+}
+
+/// Aggregates proofs of a given circuit. The aggregation circuit of each level of the tree is
+/// built the first time it is needed, and reused afterwards.
+#[derive(Debug)]
+pub struct ProofAggregator<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    strategy: AggregationStrategy,
+    inner_verifier_data: VerifierOnlyCircuitData<C, D>,
+    inner_common_data: CommonCircuitData<F, D>,
+    levels: Vec<AggregationCircuit<F, C, D>>,
+}
+
+impl<F, C, const D: usize> ProofAggregator<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    pub fn new(inner: &VerifierCircuitData<F, C, D>, strategy: AggregationStrategy) -> Self {
+        assert!(strategy.arity >= 2, "The arity must be at least 2");
+        Self {
+            strategy,
+            inner_verifier_data: inner.verifier_only.clone(),
+            inner_common_data: inner.common.clone(),
+            levels: Vec::new(),
+        }
+    }
+
+    /// The number of levels of the tree aggregating `num_proofs` proofs.
+    pub fn depth(&self, num_proofs: usize) -> usize {
+        let mut depth = 1;
+        let mut capacity = self.strategy.arity;
+        while capacity < num_proofs {
+            depth += 1;
+            capacity *= self.strategy.arity;
+        }
+        depth
+    }
+
+    /// The aggregation circuit of the given level, where level 0 verifies proofs of the inner
+    /// circuit.
+    pub fn circuit(&mut self, level: usize) -> &CircuitData<F, C, D> {
+        self.build_levels(level + 1);
+        &self.levels[level].data
+    }
+
+    fn build_levels(&mut self, depth: usize) {
+        while self.levels.len() < depth {
+            let (verifier_data, common_data) = match self.levels.last() {
+                Some(level) => (&level.data.verifier_only, &level.data.common),
+                None => (&self.inner_verifier_data, &self.inner_common_data),
+            };
+
+            let mut builder = CircuitBuilder::<F, D>::new(self.strategy.config.clone());
+            let verifier_data = builder.constant_verifier_data(verifier_data);
+            let proofs = (0..self.strategy.arity)
+                .map(|_| {
+                    let proof = builder.add_virtual_proof_with_pis(common_data);
+                    builder.verify_proof::<C>(&proof, &verifier_data, common_data);
+                    builder.register_public_inputs(&proof.public_inputs);
+                    proof
+                })
+                .collect();
+            let data = builder.build::<C>();
+            self.levels.push(AggregationCircuit { data, proofs });
+        }
+    }
+
+    /// Aggregates `proofs` of the inner circuit into a single proof, generating the proofs of each
+    /// level of the tree in parallel.
+    pub fn aggregate(
+        &mut self,
+        proofs: &[ProofWithPublicInputs<F, C, D>],
+    ) -> Result<AggregatedProof<F, C, D>> {
+        ensure!(!proofs.is_empty(), "No proofs to aggregate");
+        for (i, proof) in proofs.iter().enumerate() {
+            verify(
+                proof.clone(),
+                &self.inner_verifier_data,
+                &self.inner_common_data,
+            )
+            .map_err(|e| e.context(format!("Proof {i} is invalid")))?;
+        }
+
+        let depth = self.depth(proofs.len());
+        self.build_levels(depth);
+        let arity = self.strategy.arity;
+        let mut layer = proofs.to_vec();
+        layer.resize(arity.pow(depth as u32), proofs[proofs.len() - 1].clone());
+        for level in &self.levels[..depth] {
+            layer = layer
+                .par_chunks(arity)
+                .map(|children| {
+                    let mut pw = PartialWitness::new();
+                    for (target, proof) in level.proofs.iter().zip(children) {
+                        pw.set_proof_with_pis_target(target, proof);
+                    }
+                    level.data.prove(pw)
+                })
+                .collect::<Result<Vec<_>>>()?;
+        }
+
+        Ok(AggregatedProof {
+            proof: layer.remove(0),
+            public_inputs: proofs.iter().map(|p| p.public_inputs.clone()).collect(),
+            verifier_data: self.levels[depth - 1].data.verifier_data(),
+        })
+    }
+}
+
+/// Aggregates `proofs` of the circuit `inner` into a single proof. To aggregate several batches
+/// of proofs of the same circuit, use a [`ProofAggregator`], which reuses aggregation circuits.
+pub fn aggregate_proofs<F, C, const D: usize>(
+    proofs: &[ProofWithPublicInputs<F, C, D>],
+    inner: &VerifierCircuitData<F, C, D>,
+    strategy: AggregationStrategy,
+) -> Result<AggregatedProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ProofAggregator::new(inner, strategy).aggregate(proofs)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_aggregate_proofs() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let inner = builder.build::<C>();
+        let proofs = (0..3)
+            .map(|i| {
+                let mut pw = PartialWitness::new();
+                pw.set_target(x, F::from_canonical_u64(i));
+                inner.prove(pw)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut aggregator = ProofAggregator::new(&inner.verifier_data(), Default::default());
+        assert_eq!(aggregator.depth(3), 2);
+        let aggregated = aggregator.aggregate(&proofs)?;
+        let squares = [0, 1, 4].map(F::from_canonical_u64);
+        assert_eq!(aggregated.public_inputs, squares.map(|y| vec![y]).to_vec());
+        // The last proof is repeated to complete the tree.
+        assert_eq!(
+            aggregated.proof.public_inputs,
+            [0, 1, 4, 4].map(F::from_canonical_u64)
+        );
+        aggregated.verifier_data.verify(aggregated.proof)?;
+
+        // Aggregating fewer proofs reuses the first level's circuit.
+        let aggregated = aggregator.aggregate(&proofs[..2])?;
+        assert_eq!(
+            aggregated.verifier_data.verifier_only,
+            aggregator.circuit(0).verifier_only
+        );
+        aggregated.verifier_data.verify(aggregated.proof)?;
+
+        // Invalid proofs are rejected.
+        let mut invalid = proofs[0].clone();
+        invalid.public_inputs[0] = F::TWO;
+        assert!(aggregator.aggregate(&[invalid]).is_err());
+        Ok(())
+    }
+}
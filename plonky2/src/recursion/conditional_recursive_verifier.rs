@@ -15,6 +15,7 @@ use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::{CommonCircuitData, VerifierCircuitTarget};
 use crate::plonk::config::{AlgebraicHasher, GenericConfig};
 use crate::plonk::proof::{OpeningSetTarget, ProofTarget, ProofWithPublicInputsTarget};
+use crate::recursion::dummy_circuit::DummyProofData;
 use crate::with_context;
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
@@ -59,9 +60,35 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     ) -> anyhow::Result<()>
     where
         C::Hasher: AlgebraicHasher<F>,
+    {
+        let dummy = DummyProofData::<F, C, D>::new(inner_common_data)?;
+        self.conditionally_verify_proof_or_given_dummy(
+            condition,
+            proof_with_pis,
+            inner_verifier_data,
+            &dummy,
+            inner_common_data,
+        );
+        Ok(())
+    }
+
+    /// Verify `proof_with_pis` if `condition`, else verify the proof of `dummy`, which must have
+    /// been generated for `inner_common_data`.
+    ///
+    /// `proof_with_pis` and `inner_verifier_data` must be set in the witness even if `condition`
+    /// is false, e.g. to the proof and verifier data of `dummy`.
+    pub fn conditionally_verify_proof_or_given_dummy<C: GenericConfig<D, F = F> + 'static>(
+        &mut self,
+        condition: BoolTarget,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        inner_verifier_data: &VerifierCircuitTarget,
+        dummy: &DummyProofData<F, C, D>,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
     {
         let (dummy_proof_with_pis_target, dummy_verifier_data_target) =
-            self.dummy_proof_and_vk::<C>(inner_common_data)?;
+            self.add_dummy_proof_and_vk(dummy, inner_common_data);
         self.conditionally_verify_proof::<C>(
             condition,
             proof_with_pis,
@@ -70,7 +97,6 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             &dummy_verifier_data_target,
             inner_common_data,
         );
-        Ok(())
     }
 
     /// Computes `if b { proof_with_pis0 } else { proof_with_pis1 }`.
@@ -402,6 +428,53 @@ mod tests {
         data.verify(proof)
     }
 
+    #[test]
+    fn test_conditionally_verify_proof_or_given_dummy() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let mut pw = PartialWitness::new();
+        let t = builder.add_virtual_target();
+        pw.set_target(t, F::rand());
+        builder.register_public_input(t);
+        for _ in 0..64 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+
+        // Verify a real proof and a missing one, which is replaced by the shared dummy proof.
+        let dummy = DummyProofData::<F, C, D>::new(&data.common)?;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let mut pw = PartialWitness::new();
+        let cap_height = data.common.config.fri_config.cap_height;
+        for (present, proof, verifier_data) in [
+            (true, &proof, &data.verifier_only),
+            (false, &dummy.proof_with_pis, &dummy.verifier_data),
+        ] {
+            let pt = builder.add_virtual_proof_with_pis(&data.common);
+            pw.set_proof_with_pis_target(&pt, proof);
+            let inner_data = builder.add_virtual_verifier_data(cap_height);
+            pw.set_verifier_data_target(&inner_data, verifier_data);
+            let condition = builder.constant_bool(present);
+            builder.conditionally_verify_proof_or_given_dummy(
+                condition,
+                &pt,
+                &inner_data,
+                &dummy,
+                &data.common,
+            );
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
     fn init_logger() {
         let _ = env_logger::builder().format_timestamp(None).try_init();
     }
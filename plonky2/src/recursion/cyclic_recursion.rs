@@ -12,9 +12,10 @@ use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::{
     CommonCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
 };
-use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
 use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::plonk::public_input_layout::PublicInputLayout;
+use crate::util::serialization::{ArtifactKind, Buffer, IoResult, Read, Write};
 
 impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
     fn from_slice(slice: &[C::F], common_data: &CommonCircuitData<C::F, D>) -> Result<Self>
@@ -38,6 +39,8 @@ impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
         Ok(Self {
             circuit_digest,
             constants_sigmas_cap,
+            // The layout is not part of the public inputs.
+            public_input_layout: PublicInputLayout::default(),
         })
     }
 }
@@ -45,6 +48,7 @@ impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
 impl VerifierCircuitTarget {
     pub fn to_bytes(&self) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
+        buffer.write_header(ArtifactKind::VerifierCircuitTarget)?;
         buffer.write_target_merkle_cap(&self.constants_sigmas_cap)?;
         buffer.write_target_hash(&self.circuit_digest)?;
         Ok(buffer)
@@ -52,6 +56,7 @@ impl VerifierCircuitTarget {
 
     pub fn from_bytes(bytes: Vec<u8>) -> IoResult<Self> {
         let mut buffer = Buffer::new(&bytes);
+        buffer.read_header(ArtifactKind::VerifierCircuitTarget)?;
         let constants_sigmas_cap = buffer.read_target_merkle_cap()?;
         let circuit_digest = buffer.read_target_hash()?;
         Ok(Self {
@@ -85,6 +90,40 @@ impl VerifierCircuitTarget {
     }
 }
 
+/// The verifier data of a set of mutually recursive circuits, e.g. the step types of an IVC scheme,
+/// which share the same `CommonCircuitData`. Each circuit of the set registers the digest of the
+/// set as its last public inputs, so that it can verify proofs of any circuit of the set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CyclicVerifierSetTarget {
+    /// The hash of the verifier data of all circuits of the set, see [`verifier_set_digest`].
+    pub digest: HashOutTarget,
+    pub verifier_data: Vec<VerifierCircuitTarget>,
+}
+
+/// Returns the hash of the verifier data of a set of mutually recursive circuits, which they
+/// register as public inputs.
+pub fn verifier_set_digest<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    verifier_set: &[VerifierOnlyCircuitData<C, D>],
+) -> HashOut<F>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let elements = verifier_set
+        .iter()
+        .flat_map(|vd| {
+            vd.circuit_digest
+                .elements
+                .into_iter()
+                .chain(vd.constants_sigmas_cap.0.iter().flat_map(|h| h.elements))
+        })
+        .collect::<Vec<_>>();
+    C::Hasher::hash_no_pad(&elements)
+}
+
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// If `condition` is true, recursively verify a proof for the same circuit as the one we're
     /// currently building. Otherwise, verify `other_proof_with_pis`.
@@ -114,12 +153,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             .verifier_data_public_input
             .clone()
             .expect("Must call add_verifier_data_public_inputs before cyclic recursion");
-
-        if let Some(existing_common_data) = self.goal_common_data.as_ref() {
-            assert_eq!(existing_common_data, common_data);
-        } else {
-            self.goal_common_data = Some(common_data.clone());
-        }
+        self.set_goal_common_data(common_data);
 
         let inner_cyclic_pis = VerifierCircuitTarget::from_slice::<F, D>(
             &cyclic_proof_with_pis.public_inputs,
@@ -153,6 +187,97 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         Ok(())
     }
 
+    fn set_goal_common_data(&mut self, common_data: &CommonCircuitData<F, D>) {
+        if let Some(existing_common_data) = self.goal_common_data.as_ref() {
+            assert_eq!(existing_common_data, common_data);
+        } else {
+            self.goal_common_data = Some(common_data.clone());
+        }
+    }
+
+    /// Like `conditionally_verify_cyclic_proof`, but for a circuit belonging to a set of mutually
+    /// recursive circuits. If `condition` is true, recursively verify a proof for the circuit at
+    /// index `circuit_index` of the set, which may be the one we're currently building. Otherwise,
+    /// verify `other_proof_with_pis`.
+    ///
+    /// Every proof in a chain is checked to commit to the same verifier set. As for
+    /// `conditionally_verify_cyclic_proof`, verifiers must separately call
+    /// `check_cyclic_proof_verifier_set` to check that the set is the expected one.
+    ///
+    /// WARNING: Do not register any public input after calling this!
+    pub fn conditionally_verify_cyclic_proof_in_set<C: GenericConfig<D, F = F>>(
+        &mut self,
+        condition: BoolTarget,
+        circuit_index: Target,
+        cyclic_proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        other_proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        other_verifier_data: &VerifierCircuitTarget,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Result<()>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let verifier_set = self
+            .verifier_set_public_input
+            .clone()
+            .expect("Must call add_verifier_set_public_inputs before cyclic recursion in a set");
+        self.set_goal_common_data(common_data);
+
+        // Connect the previous verifier set to the current one, so that every proof in the chain
+        // commits to the same set.
+        let inner_pis = &cyclic_proof_with_pis.public_inputs;
+        ensure!(inner_pis.len() >= 4, "Not enough public inputs");
+        let inner_digest = HashOutTarget {
+            elements: core::array::from_fn(|i| inner_pis[inner_pis.len() - 4 + i]),
+        };
+        self.connect_hashes(inner_digest, verifier_set.digest);
+
+        // Random access needs a power-of-two number of items, so pad the set with copies of its
+        // last element.
+        let mut candidates = verifier_set.verifier_data;
+        let padded_len = candidates.len().next_power_of_two();
+        candidates.resize(padded_len, candidates[candidates.len() - 1].clone());
+        let verifier_data = self.random_access_verifier_data(circuit_index, candidates);
+
+        self.conditionally_verify_proof::<C>(
+            condition,
+            cyclic_proof_with_pis,
+            &verifier_data,
+            other_proof_with_pis,
+            other_verifier_data,
+            common_data,
+        );
+
+        // Make sure we have every gate to match `common_data`.
+        for g in &common_data.gates {
+            self.add_gate_to_gate_set(g.clone());
+        }
+
+        Ok(())
+    }
+
+    pub fn conditionally_verify_cyclic_proof_in_set_or_dummy<C: GenericConfig<D, F = F> + 'static>(
+        &mut self,
+        condition: BoolTarget,
+        circuit_index: Target,
+        cyclic_proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Result<()>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let (dummy_proof_with_pis_target, dummy_verifier_data_target) =
+            self.dummy_proof_and_vk::<C>(common_data)?;
+        self.conditionally_verify_cyclic_proof_in_set::<C>(
+            condition,
+            circuit_index,
+            cyclic_proof_with_pis,
+            &dummy_proof_with_pis_target,
+            &dummy_verifier_data_target,
+            common_data,
+        )
+    }
+
     pub fn conditionally_verify_cyclic_proof_or_dummy<C: GenericConfig<D, F = F> + 'static>(
         &mut self,
         condition: BoolTarget,
@@ -196,6 +321,38 @@ where
     Ok(())
 }
 
+/// Additional checks to be performed on a proof of a set of mutually recursive circuits, in
+/// addition to verifying it with `verifier_data`. Checks that `verifier_data` belongs to
+/// `verifier_set`, and that the proof commits to that set.
+pub fn check_cyclic_proof_verifier_set<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    proof: &ProofWithPublicInputs<F, C, D>,
+    verifier_data: &VerifierOnlyCircuitData<C, D>,
+    verifier_set: &[VerifierOnlyCircuitData<C, D>],
+) -> Result<()>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    ensure!(
+        verifier_set
+            .iter()
+            .any(|vd| vd.circuit_digest == verifier_data.circuit_digest
+                && vd.constants_sigmas_cap == verifier_data.constants_sigmas_cap),
+        "The verifier data isn't part of the verifier set"
+    );
+    let pis = &proof.public_inputs;
+    ensure!(pis.len() >= 4, "Not enough public inputs");
+    ensure!(
+        pis[pis.len() - 4..] == verifier_set_digest::<F, C, D>(verifier_set).elements,
+        "The proof commits to a different verifier set"
+    );
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -206,12 +363,16 @@ mod tests {
     use crate::hash::hash_types::{HashOutTarget, RichField};
     use crate::hash::hashing::hash_n_to_hash_no_pad;
     use crate::hash::poseidon::{PoseidonHash, PoseidonPermutation};
+    use crate::iop::target::{BoolTarget, Target};
     use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
     use crate::plonk::config::{AlgebraicHasher, GenericConfig, PoseidonGoldilocksConfig};
-    use crate::recursion::cyclic_recursion::check_cyclic_proof_verifier_data;
-    use crate::recursion::dummy_circuit::cyclic_base_proof;
+    use crate::plonk::proof::ProofWithPublicInputsTarget;
+    use crate::recursion::cyclic_recursion::{
+        check_cyclic_proof_verifier_data, check_cyclic_proof_verifier_set, CyclicVerifierSetTarget,
+    };
+    use crate::recursion::dummy_circuit::{cyclic_base_proof, cyclic_set_base_proof};
 
     // Generates `CommonCircuitData` usable for recursion.
     fn common_data_for_recursion<
@@ -219,6 +380,20 @@ mod tests {
         C: GenericConfig<D, F = F>,
         const D: usize,
     >() -> CommonCircuitData<F, D>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        common_data_for_recursion_in_set::<F, C, D>(1)
+    }
+
+    // Generates `CommonCircuitData` usable for recursion within a set of `num_circuits` circuits.
+    fn common_data_for_recursion_in_set<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        num_circuits: usize,
+    ) -> CommonCircuitData<F, D>
     where
         C::Hasher: AlgebraicHasher<F>,
     {
@@ -239,6 +414,15 @@ mod tests {
         let verifier_data =
             builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height);
         builder.verify_proof::<C>(&proof, &verifier_data, &data.common);
+        if num_circuits > 1 {
+            let index = builder.add_virtual_target();
+            let verifier_set = (0..num_circuits.next_power_of_two())
+                .map(|_| {
+                    builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height)
+                })
+                .collect();
+            builder.random_access_verifier_data(index, verifier_set);
+        }
         while builder.num_gates() < 1 << 12 {
             builder.add_gate(NoopGate, vec![]);
         }
@@ -364,6 +548,112 @@ mod tests {
         cyclic_circuit_data.verify(proof)
     }
 
+    /// Uses cyclic recursion over a set of two circuits, which respectively increment and double a
+    /// counter, so that a chain can apply them in any order.
+    /// Each circuit has the following public input structure:
+    /// - Initial value (1)
+    /// - Current value (1)
+    /// - Digest of the verifier set (4)
+    #[test]
+    fn test_cyclic_recursion_in_set() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        struct StepTargets {
+            initial: Target,
+            condition: BoolTarget,
+            circuit_index: Target,
+            inner_proof: ProofWithPublicInputsTarget<D>,
+            verifier_set: CyclicVerifierSetTarget,
+        }
+
+        let common_data_for_set = common_data_for_recursion_in_set::<F, C, D>(2);
+        let build_step = |step: fn(&mut CircuitBuilder<F, D>, Target) -> Target| {
+            let config = CircuitConfig::standard_recursion_config();
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let initial = builder.add_virtual_public_input();
+            let current = builder.add_virtual_public_input();
+            let verifier_set = builder.add_verifier_set_public_inputs::<C>(2);
+            let mut common_data = common_data_for_set.clone();
+            common_data.num_public_inputs = builder.num_public_inputs();
+
+            let condition = builder.add_virtual_bool_target_safe();
+            let circuit_index = builder.add_virtual_target();
+            let inner_proof = builder.add_virtual_proof_with_pis(&common_data);
+            let inner_initial = inner_proof.public_inputs[0];
+            let inner_current = inner_proof.public_inputs[1];
+            builder.connect(initial, inner_initial);
+            let input = builder.select(condition, inner_current, initial);
+            let output = step(&mut builder, input);
+            builder.connect(current, output);
+
+            builder
+                .conditionally_verify_cyclic_proof_in_set_or_dummy::<C>(
+                    condition,
+                    circuit_index,
+                    &inner_proof,
+                    &common_data,
+                )
+                .unwrap();
+            let targets = StepTargets {
+                initial,
+                condition,
+                circuit_index,
+                inner_proof,
+                verifier_set,
+            };
+            (builder.build::<C>(), targets)
+        };
+        let circuits = [
+            build_step(|builder, x| builder.add_const(x, F::ONE)),
+            build_step(|builder, x| builder.mul_const(F::TWO, x)),
+        ];
+        let verifier_set = circuits
+            .iter()
+            .map(|(data, _)| data.verifier_only.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(circuits[0].0.common, circuits[1].0.common);
+        let common_data = &circuits[0].0.common;
+
+        let initial = F::from_canonical_u64(3);
+        let base_proof = cyclic_set_base_proof(
+            common_data,
+            &verifier_set,
+            [(0, initial)].into_iter().collect(),
+        );
+        // Compute ((3 + 1) * 2) + 1, where each step verifies a proof of the previous one.
+        let mut proof = base_proof;
+        let mut previous_step = None;
+        for step in [0, 1, 0] {
+            let (data, targets) = &circuits[step];
+            let mut pw = PartialWitness::new();
+            pw.set_target(targets.initial, initial);
+            pw.set_bool_target(targets.condition, previous_step.is_some());
+            pw.set_target(
+                targets.circuit_index,
+                F::from_canonical_usize(previous_step.unwrap_or(0)),
+            );
+            pw.set_proof_with_pis_target(&targets.inner_proof, &proof);
+            pw.set_verifier_set_target(&targets.verifier_set, &verifier_set);
+            proof = data.prove(pw)?;
+            check_cyclic_proof_verifier_set(&proof, &data.verifier_only, &verifier_set)?;
+            data.verify(proof.clone())?;
+            previous_step = Some(step);
+        }
+        assert_eq!(proof.public_inputs[1], F::from_canonical_u64(9));
+
+        // The proof doesn't commit to a set with a different order.
+        let reversed_set = verifier_set.iter().rev().cloned().collect::<Vec<_>>();
+        assert!(check_cyclic_proof_verifier_set(
+            &proof,
+            &circuits[0].0.verifier_only,
+            &reversed_set
+        )
+        .is_err());
+        Ok(())
+    }
+
     fn iterate_poseidon<F: RichField>(initial_state: [F; 4], n: usize) -> [F; 4] {
         let mut current = initial_state;
         for _ in 0..n {
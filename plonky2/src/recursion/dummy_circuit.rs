@@ -24,6 +24,8 @@ use crate::plonk::proof::{
     OpeningSet, OpeningSetTarget, Proof, ProofTarget, ProofWithPublicInputs,
     ProofWithPublicInputsTarget,
 };
+use crate::plonk::public_input_layout::PublicInputLayout;
+use crate::recursion::cyclic_recursion::verifier_set_digest;
 use crate::util::serialization::{Buffer, IoResult, Read, Write};
 
 /// Creates a dummy proof which is suitable for use as a base proof in a cyclic recursion tree.
@@ -62,14 +64,33 @@ where
     .unwrap()
 }
 
+/// Like [`cyclic_base_proof`], but for a circuit belonging to a set of mutually recursive circuits,
+/// whose public inputs end with the digest of the set's verifier data.
+pub fn cyclic_set_base_proof<F, C, const D: usize>(
+    common_data: &CommonCircuitData<F, D>,
+    verifier_set: &[VerifierOnlyCircuitData<C, D>],
+    mut nonzero_public_inputs: HashMap<usize, F>,
+) -> ProofWithPublicInputs<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<C::F>,
+{
+    let start_digest_pis = common_data.num_public_inputs - 4;
+    nonzero_public_inputs
+        .extend((start_digest_pis..).zip(verifier_set_digest::<F, C, D>(verifier_set).elements));
+
+    dummy_proof::<F, C, D>(
+        &dummy_circuit::<F, C, D>(common_data),
+        nonzero_public_inputs,
+    )
+    .unwrap()
+}
+
 /// Generate a proof for a dummy circuit. The `public_inputs` parameter let the caller specify
 /// certain public inputs (identified by their indices) which should be given specific values.
 /// The rest will default to zero.
-pub(crate) fn dummy_proof<
-    F: RichField + Extendable<D>,
-    C: GenericConfig<D, F = F>,
-    const D: usize,
->(
+pub fn dummy_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     circuit: &CircuitData<F, C, D>,
     nonzero_public_inputs: HashMap<usize, F>,
 ) -> anyhow::Result<ProofWithPublicInputs<F, C, D>>
@@ -84,11 +105,7 @@ where
 }
 
 /// Generate a circuit matching a given `CommonCircuitData`.
-pub(crate) fn dummy_circuit<
-    F: RichField + Extendable<D>,
-    C: GenericConfig<D, F = F>,
-    const D: usize,
->(
+pub fn dummy_circuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     common_data: &CommonCircuitData<F, D>,
 ) -> CircuitData<F, C, D> {
     let config = common_data.config.clone();
@@ -118,6 +135,31 @@ pub(crate) fn dummy_circuit<
     circuit
 }
 
+/// The canonical dummy proof for circuits with a given `CommonCircuitData`, i.e. a proof of the
+/// dummy circuit with all public inputs set to zero, along with the dummy circuit's verifier data.
+///
+/// Generating it requires building and proving the dummy circuit, so when several proofs with the
+/// same `CommonCircuitData` are conditionally verified, it should be generated once and shared.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DummyProofData<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
+    pub verifier_data: VerifierOnlyCircuitData<C, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    DummyProofData<F, C, D>
+{
+    pub fn new(common_data: &CommonCircuitData<F, D>) -> anyhow::Result<Self> {
+        let circuit = dummy_circuit::<F, C, D>(common_data);
+        let proof_with_pis = dummy_proof::<F, C, D>(&circuit, HashMap::new())?;
+        Ok(Self {
+            proof_with_pis,
+            verifier_data: circuit.verifier_only,
+        })
+    }
+}
+
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     pub(crate) fn dummy_proof_and_vk<C: GenericConfig<D, F = F> + 'static>(
         &mut self,
@@ -126,20 +168,32 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     where
         C::Hasher: AlgebraicHasher<F>,
     {
-        let dummy_circuit = dummy_circuit::<F, C, D>(common_data);
-        let dummy_proof_with_pis = dummy_proof::<F, C, D>(&dummy_circuit, HashMap::new())?;
+        let dummy = DummyProofData::<F, C, D>::new(common_data)?;
+        Ok(self.add_dummy_proof_and_vk(&dummy, common_data))
+    }
+
+    /// Adds targets for a proof and verifier data, which are set to those of `dummy` during
+    /// witness generation.
+    pub fn add_dummy_proof_and_vk<C: GenericConfig<D, F = F> + 'static>(
+        &mut self,
+        dummy: &DummyProofData<F, C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> (ProofWithPublicInputsTarget<D>, VerifierCircuitTarget)
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
         let dummy_proof_with_pis_target = self.add_virtual_proof_with_pis(common_data);
         let dummy_verifier_data_target =
             self.add_virtual_verifier_data(self.config.fri_config.cap_height);
 
         self.add_simple_generator(DummyProofGenerator {
             proof_with_pis_target: dummy_proof_with_pis_target.clone(),
-            proof_with_pis: dummy_proof_with_pis,
+            proof_with_pis: dummy.proof_with_pis.clone(),
             verifier_data_target: dummy_verifier_data_target.clone(),
-            verifier_data: dummy_circuit.verifier_only,
+            verifier_data: dummy.verifier_data.clone(),
         });
 
-        Ok((dummy_proof_with_pis_target, dummy_verifier_data_target))
+        (dummy_proof_with_pis_target, dummy_verifier_data_target)
     }
 }
 
@@ -205,6 +259,7 @@ where
             circuit_digest: <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash::from_bytes(
                 &vec![0; <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::HASH_SIZE],
             ),
+            public_input_layout: PublicInputLayout::default(),
         };
 
         Self {
@@ -0,0 +1,207 @@
+//! Selection of an inner circuit's FRI parameters to minimize the size of the circuit verifying
+//! its proofs.
+//!
+//! The rate, number of queries and proof of work bits of the inner config determine its security,
+//! so they are kept as is. Only the cap height and the reduction arities are tuned: higher arities
+//! mean fewer Merkle proofs but larger interpolations, a larger final polynomial means fewer
+//! reductions but more coefficients to evaluate, and a larger cap means shorter Merkle proofs but
+//! more verifier data to hash.
+
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::fri::recursive_verifier::min_fri_recursion_wires;
+use crate::fri::reduction_strategies::FriReductionStrategy;
+use crate::fri::FriConfig;
+use crate::hash::hash_types::RichField;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+
+/// The inner FRI parameters considered by [`tune_inner_fri_config`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FriTuningSpace {
+    /// The largest reduction arity, in bits.
+    pub max_arity_bits: usize,
+    /// The largest degree of the final polynomial, in bits.
+    pub max_final_poly_bits: usize,
+    pub max_cap_height: usize,
+}
+
+impl Default for FriTuningSpace {
+    fn default() -> Self {
+        Self {
+            max_arity_bits: 4,
+            max_final_poly_bits: 5,
+            max_cap_height: 4,
+        }
+    }
+}
+
+/// The result of [`tune_inner_fri_config`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FriTuning {
+    /// The FRI config the inner circuit should be built with.
+    pub fri_config: FriConfig,
+    /// The number of gates of the circuit verifying a proof of the inner circuit, before padding.
+    pub num_gates: usize,
+}
+
+/// The number of gates of a circuit with config `outer_config` verifying a proof of a circuit with
+/// common data `inner_common_data`, before padding.
+pub fn recursive_verifier_num_gates<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    inner_common_data: &CommonCircuitData<F, D>,
+    outer_config: &CircuitConfig,
+) -> usize
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut builder = CircuitBuilder::<F, D>::new(outer_config.clone());
+    let proof = builder.add_virtual_proof_with_pis(inner_common_data);
+    let verifier_data =
+        builder.add_virtual_verifier_data(inner_common_data.config.fri_config.cap_height);
+    builder.verify_proof::<C>(&proof, &verifier_data, inner_common_data);
+    builder.num_gates()
+}
+
+/// Searches `space` for the inner FRI config minimizing the number of gates needed to verify a
+/// proof of the inner circuit in a circuit with config `outer_config`. Configs whose arities need
+/// more wires than `outer_config` has are skipped. The returned config has fixed arities, which
+/// are specific to the inner circuit's degree. The inner circuit must then be rebuilt with it,
+/// which leaves its other common data unchanged.
+pub fn tune_inner_fri_config<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    inner_common_data: &CommonCircuitData<F, D>,
+    outer_config: &CircuitConfig,
+    space: &FriTuningSpace,
+) -> FriTuning
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let degree_bits = inner_common_data.degree_bits();
+    let hiding = inner_common_data.fri_params.hiding;
+    let base_config = &inner_common_data.config.fri_config;
+
+    let mut tried = Vec::new();
+    let mut best: Option<FriTuning> = None;
+    for cap_height in 0..=space
+        .max_cap_height
+        .min(degree_bits + base_config.rate_bits)
+    {
+        for arity_bits in 1..=space.max_arity_bits {
+            for final_poly_bits in 0..=space.max_final_poly_bits {
+                // Like `ConstantArityBits`, but without overshooting the final polynomial.
+                let mut reduction_arity_bits = Vec::new();
+                let mut bits = degree_bits;
+                while bits >= final_poly_bits + arity_bits
+                    && bits + base_config.rate_bits - arity_bits >= cap_height
+                {
+                    reduction_arity_bits.push(arity_bits);
+                    bits -= arity_bits;
+                }
+                let fri_config = FriConfig {
+                    cap_height,
+                    reduction_strategy: FriReductionStrategy::Fixed(reduction_arity_bits),
+                    ..base_config.clone()
+                };
+                let fri_params = fri_config.fri_params(degree_bits, hiding);
+                // Several strategies may lead to the same arities.
+                let key = (cap_height, fri_params.reduction_arity_bits.clone());
+                if tried.contains(&key) {
+                    continue;
+                }
+                tried.push(key);
+
+                let max_arity_bits = fri_params.max_arity_bits().unwrap_or(0);
+                let (min_wires, min_routed_wires) =
+                    min_fri_recursion_wires::<F, D>(outer_config, max_arity_bits);
+                if outer_config.num_wires < min_wires
+                    || outer_config.num_routed_wires < min_routed_wires
+                {
+                    continue;
+                }
+
+                let mut common_data = inner_common_data.clone();
+                common_data.config.fri_config = fri_config.clone();
+                common_data.fri_params = fri_params;
+                let num_gates = recursive_verifier_num_gates::<F, C, D>(&common_data, outer_config);
+                let is_better = match &best {
+                    Some(best) => num_gates < best.num_gates,
+                    None => true,
+                };
+                if is_better {
+                    best = Some(FriTuning {
+                        fri_config,
+                        num_gates,
+                    });
+                }
+            }
+        }
+    }
+    best.expect("No FRI config in the search space can be verified with the outer config")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::gates::noop::NoopGate;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_tune_inner_fri_config() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let build_inner = |config: CircuitConfig| {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_target();
+            let y = builder.exp_u64(x, 1 << 10);
+            builder.register_public_input(y);
+            while builder.num_gates() < 1 << 10 {
+                builder.add_gate(NoopGate, vec![]);
+            }
+            (builder.build::<C>(), x)
+        };
+        let outer_config = CircuitConfig::standard_recursion_config();
+        let (inner, _) = build_inner(outer_config.clone());
+        let default_num_gates =
+            recursive_verifier_num_gates::<F, C, D>(&inner.common, &outer_config);
+
+        let tuning = tune_inner_fri_config::<F, C, D>(
+            &inner.common,
+            &outer_config,
+            &FriTuningSpace::default(),
+        );
+        assert!(tuning.num_gates <= default_num_gates);
+        assert_eq!(
+            tuning.fri_config.rate_bits,
+            outer_config.fri_config.rate_bits
+        );
+
+        // The inner circuit rebuilt with the tuned config has the expected verifier size.
+        let mut config = outer_config.clone();
+        config.fri_config = tuning.fri_config;
+        let (inner, x) = build_inner(config);
+        assert_eq!(
+            recursive_verifier_num_gates::<F, C, D>(&inner.common, &outer_config),
+            tuning.num_gates
+        );
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        inner.verify(inner.prove(pw)?)
+    }
+}
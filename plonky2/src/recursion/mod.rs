@@ -1,4 +1,10 @@
+pub mod accumulation;
+pub mod aggregation;
 pub mod conditional_recursive_verifier;
 pub mod cyclic_recursion;
 pub mod dummy_circuit;
+pub mod fri_tuning;
+pub mod pcd;
 pub mod recursive_verifier;
+pub mod verifier_allow_list;
+pub mod wrap;
@@ -0,0 +1,352 @@
+//! Proof-carrying data (PCD) on top of cyclic recursion.
+//!
+//! A [`PcdStep`] describes a relation between a state and the next one. A [`PcdCircuit`] proves a
+//! chain of such steps starting from some initial state, each proof verifying the previous one.
+//! The public inputs of its proofs are laid out as follows:
+//! - the initial state (`STATE_LEN`),
+//! - the current state (`STATE_LEN`),
+//! - the number of steps applied so far (1),
+//! - the verifier data of the circuit, used for cyclic recursion.
+
+use alloc::vec::Vec;
+
+use anyhow::{anyhow, ensure, Result};
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{
+    CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitTarget,
+};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use crate::recursion::cyclic_recursion::check_cyclic_proof_verifier_data;
+use crate::recursion::dummy_circuit::cyclic_base_proof;
+
+/// The maximum number of circuits built while searching for the common data of a PCD circuit.
+const MAX_COMMON_DATA_ITERATIONS: usize = 8;
+
+/// A step of a PCD chain, i.e. a relation between a state and the next one.
+pub trait PcdStep<F: RichField + Extendable<D>, const D: usize> {
+    /// The state carried along the chain.
+    type State;
+    /// The private inputs of a step.
+    type Witness;
+    /// The targets of the private inputs of a step.
+    type WitnessTarget;
+
+    /// The number of field elements encoding a state.
+    const STATE_LEN: usize;
+
+    /// Encodes a state as `STATE_LEN` field elements.
+    fn encode_state(state: &Self::State) -> Vec<F>;
+
+    /// Decodes a state from `STATE_LEN` field elements.
+    fn decode_state(elements: &[F]) -> Self::State;
+
+    /// Adds the constraints of a step from `prev_state`, and returns the next state along with the
+    /// targets of the step's private inputs. This must not register any public input.
+    fn build_step(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        prev_state: &[Target],
+    ) -> (Vec<Target>, Self::WitnessTarget);
+
+    /// Sets the private inputs of a step.
+    fn set_witness(
+        &self,
+        pw: &mut PartialWitness<F>,
+        target: &Self::WitnessTarget,
+        witness: &Self::Witness,
+    );
+}
+
+/// The claim of a verified PCD proof.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PcdChain<T> {
+    pub initial_state: T,
+    pub state: T,
+    pub num_steps: u64,
+}
+
+/// A circuit proving a step of a PCD chain, and recursively verifying the proof of the previous
+/// step unless it is the first one.
+#[derive(Debug)]
+pub struct PcdCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: PcdStep<F, D>,
+    const D: usize,
+> {
+    pub data: CircuitData<F, C, D>,
+    step: S,
+    targets: PcdTargets<S::WitnessTarget, D>,
+}
+
+#[derive(Debug)]
+struct PcdTargets<W, const D: usize> {
+    condition: BoolTarget,
+    inner_proof: ProofWithPublicInputsTarget<D>,
+    verifier_data: VerifierCircuitTarget,
+    step: W,
+}
+
+impl<F, C, S, const D: usize> PcdCircuit<F, C, S, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    C::Hasher: AlgebraicHasher<F>,
+    S: PcdStep<F, D>,
+{
+    /// Builds the circuit of `step`. Since a cyclic circuit must know its own common data before
+    /// being built, this builds it repeatedly until its common data matches the one it assumed.
+    pub fn new(mut step: S, config: CircuitConfig) -> Result<Self> {
+        let mut common_data = Self::initial_common_data(&config);
+        for _ in 0..MAX_COMMON_DATA_ITERATIONS {
+            let pcd = Self::build(step, &config, &common_data)?;
+            if pcd.data.common == common_data {
+                return Ok(pcd);
+            }
+            common_data = pcd.data.common;
+            step = pcd.step;
+        }
+        Err(anyhow!(
+            "The common data of the PCD circuit didn't converge after {} iterations",
+            MAX_COMMON_DATA_ITERATIONS
+        ))
+    }
+
+    /// A first guess of the common data, that of a circuit verifying a recursive proof.
+    fn initial_common_data(config: &CircuitConfig) -> CommonCircuitData<F, D> {
+        let mut common_data = CircuitBuilder::<F, D>::new(config.clone())
+            .build::<C>()
+            .common;
+        for _ in 0..2 {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            let proof = builder.add_virtual_proof_with_pis(&common_data);
+            let verifier_data = builder.add_virtual_verifier_data(config.fri_config.cap_height);
+            builder.verify_proof::<C>(&proof, &verifier_data, &common_data);
+            common_data = builder.build::<C>().common;
+        }
+        common_data.num_public_inputs =
+            2 * S::STATE_LEN + 1 + 4 + 4 * config.fri_config.num_cap_elements();
+        common_data
+    }
+
+    /// Builds the circuit, assuming that its own common data is `common_data`.
+    fn build(
+        step: S,
+        config: &CircuitConfig,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Result<Self> {
+        let n = S::STATE_LEN;
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let one = builder.one();
+
+        let initial_state = builder.add_virtual_targets(n);
+        builder.register_public_inputs(&initial_state);
+        let condition = builder.add_virtual_bool_target_safe();
+        let inner_proof = builder.add_virtual_proof_with_pis(common_data);
+        let inner_pis = inner_proof.public_inputs.clone();
+
+        // If there is no inner proof, the initial state is only constrained by the base proof's
+        // public inputs, which the prover sets to it.
+        for (&x, &y) in initial_state.iter().zip(&inner_pis[..n]) {
+            builder.connect(x, y);
+        }
+        let prev_state = inner_pis[n..2 * n]
+            .iter()
+            .zip(&initial_state)
+            .map(|(&inner, &initial)| builder.select(condition, inner, initial))
+            .collect::<Vec<_>>();
+
+        let (next_state, step_target) = step.build_step(&mut builder, &prev_state);
+        ensure!(
+            next_state.len() == n,
+            "Expected a next state of length {}, got {}",
+            n,
+            next_state.len()
+        );
+        ensure!(
+            builder.num_public_inputs() == n,
+            "A PCD step must not register public inputs"
+        );
+        builder.register_public_inputs(&next_state);
+        let num_steps = builder.mul_add(condition.target, inner_pis[2 * n], one);
+        builder.register_public_input(num_steps);
+
+        let verifier_data = builder.add_verifier_data_public_inputs();
+        builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
+            condition,
+            &inner_proof,
+            common_data,
+        )?;
+        // `common_data` is only a guess, which the caller compares with the actual common data.
+        builder.goal_common_data = None;
+
+        Ok(Self {
+            data: builder.build::<C>(),
+            step,
+            targets: PcdTargets {
+                condition,
+                inner_proof,
+                verifier_data,
+                step: step_target,
+            },
+        })
+    }
+
+    pub fn step(&self) -> &S {
+        &self.step
+    }
+
+    /// Proves the first step of a chain, from `initial_state`.
+    pub fn prove_base_step(
+        &self,
+        initial_state: &S::State,
+        witness: &S::Witness,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let initial_state = S::encode_state(initial_state);
+        ensure!(
+            initial_state.len() == S::STATE_LEN,
+            "Expected a state of length {}, got {}",
+            S::STATE_LEN,
+            initial_state.len()
+        );
+        let base_proof = cyclic_base_proof(
+            &self.data.common,
+            &self.data.verifier_only,
+            initial_state.into_iter().enumerate().collect(),
+        );
+        self.prove(false, &base_proof, witness)
+    }
+
+    /// Proves the step following the one proven by `prev_proof`.
+    pub fn prove_step(
+        &self,
+        prev_proof: &ProofWithPublicInputs<F, C, D>,
+        witness: &S::Witness,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            prev_proof.public_inputs.len() == self.data.common.num_public_inputs,
+            "Expected {} public inputs, got {}",
+            self.data.common.num_public_inputs,
+            prev_proof.public_inputs.len()
+        );
+        self.prove(true, prev_proof, witness)
+    }
+
+    fn prove(
+        &self,
+        condition: bool,
+        inner_proof: &ProofWithPublicInputs<F, C, D>,
+        witness: &S::Witness,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let mut pw = PartialWitness::new();
+        pw.set_bool_target(self.targets.condition, condition);
+        pw.set_proof_with_pis_target(&self.targets.inner_proof, inner_proof);
+        pw.set_verifier_data_target(&self.targets.verifier_data, &self.data.verifier_only);
+        self.step.set_witness(&mut pw, &self.targets.step, witness);
+        self.data.prove(pw)
+    }
+
+    /// Verifies a proof of a chain, and returns its initial and final states.
+    pub fn verify_chain(
+        &self,
+        proof: &ProofWithPublicInputs<F, C, D>,
+    ) -> Result<PcdChain<S::State>> {
+        check_cyclic_proof_verifier_data(proof, &self.data.verifier_only, &self.data.common)?;
+        self.data
+            .verify(proof.clone())
+            .map_err(|e| e.context("Invalid PCD proof"))?;
+
+        let n = S::STATE_LEN;
+        let pis = &proof.public_inputs;
+        Ok(PcdChain {
+            initial_state: S::decode_state(&pis[..n]),
+            state: S::decode_state(&pis[n..2 * n]),
+            num_steps: pis[2 * n].to_canonical_u64(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::{Field, PrimeField64};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Adds a private amount to a running total.
+    struct Accumulate;
+
+    impl PcdStep<F, D> for Accumulate {
+        type State = u64;
+        type Witness = u64;
+        type WitnessTarget = Target;
+
+        const STATE_LEN: usize = 1;
+
+        fn encode_state(state: &u64) -> Vec<F> {
+            vec![F::from_canonical_u64(*state)]
+        }
+
+        fn decode_state(elements: &[F]) -> u64 {
+            elements[0].to_canonical_u64()
+        }
+
+        fn build_step(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            prev_state: &[Target],
+        ) -> (Vec<Target>, Target) {
+            let amount = builder.add_virtual_target();
+            builder.range_check(amount, 32);
+            (vec![builder.add(prev_state[0], amount)], amount)
+        }
+
+        fn set_witness(&self, pw: &mut PartialWitness<F>, target: &Target, witness: &u64) {
+            pw.set_target(*target, F::from_canonical_u64(*witness));
+        }
+    }
+
+    #[test]
+    fn test_pcd() -> Result<()> {
+        let pcd =
+            PcdCircuit::<F, C, _, D>::new(Accumulate, CircuitConfig::standard_recursion_config())?;
+
+        let mut proof = pcd.prove_base_step(&10, &1)?;
+        assert_eq!(
+            pcd.verify_chain(&proof)?,
+            PcdChain {
+                initial_state: 10,
+                state: 11,
+                num_steps: 1
+            }
+        );
+        for amount in [2, 3] {
+            proof = pcd.prove_step(&proof, &amount)?;
+        }
+        assert_eq!(
+            pcd.verify_chain(&proof)?,
+            PcdChain {
+                initial_state: 10,
+                state: 16,
+                num_steps: 3
+            }
+        );
+
+        // Claiming another state is rejected.
+        let mut tampered = proof;
+        tampered.public_inputs[1] = F::from_canonical_u64(17);
+        assert!(pcd.verify_chain(&tampered).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+//! Verification of proofs of different circuits within a single recursive circuit.
+//!
+//! Instead of being a constant, the verifier data of the inner proof is a witness, which is checked
+//! to be a leaf of a Merkle tree of allowed verifier data. The root of that tree is typically a
+//! public input, so that verifiers can check which circuits were accepted. All allowed circuits
+//! must share the same `CommonCircuitData`, since it determines the shape of the verifier circuit.
+
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::{HashOut, HashOutTarget, RichField};
+use crate::hash::merkle_proofs::{MerkleProof, MerkleProofTarget};
+use crate::hash::merkle_tree::MerkleTree;
+use crate::iop::target::BoolTarget;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{
+    CommonCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::ProofWithPublicInputsTarget;
+use crate::util::log2_strict;
+
+/// A set of verifier data, committed to by the root of a Merkle tree with one leaf per entry.
+#[derive(Debug)]
+pub struct VerifierAllowList<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    verifier_data: Vec<VerifierOnlyCircuitData<C, D>>,
+    tree: MerkleTree<F, C::Hasher>,
+}
+
+impl<F, C, const D: usize> VerifierAllowList<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    /// Commits to `verifier_data`. The tree is padded to a power-of-two number of leaves by
+    /// repeating the last entry.
+    pub fn new(verifier_data: Vec<VerifierOnlyCircuitData<C, D>>) -> Self {
+        assert!(!verifier_data.is_empty(), "The allow-list can't be empty");
+        let mut leaves = verifier_data
+            .iter()
+            .map(verifier_data_leaf)
+            .collect::<Vec<_>>();
+        leaves.resize(
+            leaves.len().next_power_of_two(),
+            leaves[leaves.len() - 1].clone(),
+        );
+        Self {
+            verifier_data,
+            tree: MerkleTree::new(leaves, 0),
+        }
+    }
+
+    pub fn verifier_data(&self) -> &[VerifierOnlyCircuitData<C, D>] {
+        &self.verifier_data
+    }
+
+    /// The root of the Merkle tree, which commits to the allowed verifier data.
+    pub fn root(&self) -> HashOut<F> {
+        self.tree.cap.0[0]
+    }
+
+    /// The height of the Merkle tree, i.e. the length of membership proofs.
+    pub fn height(&self) -> usize {
+        log2_strict(self.tree.leaves.len())
+    }
+
+    /// The index of `verifier_data` in the allow-list, if it is allowed.
+    pub fn index_of(&self, verifier_data: &VerifierOnlyCircuitData<C, D>) -> Option<usize> {
+        self.verifier_data.iter().position(|vd| {
+            vd.circuit_digest == verifier_data.circuit_digest
+                && vd.constants_sigmas_cap == verifier_data.constants_sigmas_cap
+        })
+    }
+
+    /// A proof that the verifier data at `index` is allowed.
+    pub fn prove(&self, index: usize) -> MerkleProof<F, C::Hasher> {
+        self.tree.prove(index)
+    }
+}
+
+/// The leaf of a [`VerifierAllowList`] holding `verifier_data`.
+fn verifier_data_leaf<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    verifier_data: &VerifierOnlyCircuitData<C, D>,
+) -> Vec<F>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut leaf = verifier_data.circuit_digest.elements.to_vec();
+    leaf.extend(verifier_data.constants_sigmas_cap.flatten());
+    leaf
+}
+
+/// Verifier data which is checked to belong to a [`VerifierAllowList`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllowedVerifierDataTarget {
+    pub verifier_data: VerifierCircuitTarget,
+    /// The little-endian bits of the index of `verifier_data` in the allow-list.
+    pub index_bits: Vec<BoolTarget>,
+    pub merkle_proof: MerkleProofTarget,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds verifier data targets, and checks that they belong to the allow-list with the given
+    /// root and height. `inner_cap_height` is the cap height of the allowed circuits, which may
+    /// differ from that of this circuit. The targets can be set with
+    /// `WitnessWrite::set_allowed_verifier_data_target`.
+    pub fn add_allowed_verifier_data<H: AlgebraicHasher<F>>(
+        &mut self,
+        allow_list_root: HashOutTarget,
+        allow_list_height: usize,
+        inner_cap_height: usize,
+    ) -> AllowedVerifierDataTarget {
+        let verifier_data = self.add_virtual_verifier_data(inner_cap_height);
+        let index_bits = (0..allow_list_height)
+            .map(|_| self.add_virtual_bool_target_safe())
+            .collect::<Vec<_>>();
+        let merkle_proof = MerkleProofTarget {
+            siblings: self.add_virtual_hashes(allow_list_height),
+        };
+
+        let mut leaf = verifier_data.circuit_digest.elements.to_vec();
+        for h in &verifier_data.constants_sigmas_cap.0 {
+            leaf.extend(h.elements);
+        }
+        self.verify_merkle_proof::<H>(leaf, &index_bits, allow_list_root, &merkle_proof);
+
+        AllowedVerifierDataTarget {
+            verifier_data,
+            index_bits,
+            merkle_proof,
+        }
+    }
+
+    /// Verifies a proof of any circuit whose verifier data belongs to the allow-list with the
+    /// given root and height. All circuits of the allow-list must have `inner_common_data` as
+    /// their common data.
+    pub fn verify_proof_with_allow_list<C: GenericConfig<D, F = F>>(
+        &mut self,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        allow_list_root: HashOutTarget,
+        allow_list_height: usize,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) -> AllowedVerifierDataTarget
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let allowed = self.add_allowed_verifier_data::<C::Hasher>(
+            allow_list_root,
+            allow_list_height,
+            inner_common_data.config.fri_config.cap_height,
+        );
+        self.verify_proof::<C>(proof_with_pis, &allowed.verifier_data, inner_common_data);
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::target::Target;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Builds a circuit exposing `x^exponent`, and returns it with its `x` target.
+    fn power_circuit(exponent: u64) -> (CircuitData<F, C, D>, Target) {
+        power_circuit_with_config(exponent, CircuitConfig::standard_recursion_config())
+    }
+
+    fn power_circuit_with_config(
+        exponent: u64,
+        config: CircuitConfig,
+    ) -> (CircuitData<F, C, D>, Target) {
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, exponent);
+        builder.register_public_input(y);
+        (builder.build::<C>(), x)
+    }
+
+    #[test]
+    fn test_verify_proof_with_allow_list() -> Result<()> {
+        let circuits = [power_circuit(2), power_circuit(3)];
+        assert_eq!(circuits[0].0.common, circuits[1].0.common);
+        let common_data = &circuits[0].0.common;
+        let allow_list = VerifierAllowList::<F, C, D>::new(
+            circuits
+                .iter()
+                .map(|(data, _)| data.verifier_only.clone())
+                .collect(),
+        );
+        assert_eq!(allow_list.height(), 1);
+        assert_eq!(allow_list.index_of(&power_circuit(4).0.verifier_only), None);
+
+        // A single universal circuit verifies proofs of both circuits.
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let root = builder.add_virtual_hash();
+        builder.register_public_inputs(&root.elements);
+        let proof_target = builder.add_virtual_proof_with_pis(common_data);
+        builder.register_public_inputs(&proof_target.public_inputs);
+        let allowed = builder.verify_proof_with_allow_list::<C>(
+            &proof_target,
+            root,
+            allow_list.height(),
+            common_data,
+        );
+        let universal = builder.build::<C>();
+
+        for (i, (data, x)) in circuits.iter().enumerate() {
+            let mut pw = PartialWitness::new();
+            pw.set_target(*x, F::TWO);
+            let inner_proof = data.prove(pw)?;
+
+            let mut pw = PartialWitness::new();
+            pw.set_hash_target(root, allow_list.root());
+            pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+            let index = allow_list.index_of(&data.verifier_only).unwrap();
+            assert_eq!(index, i);
+            pw.set_allowed_verifier_data_target(&allowed, &allow_list, index);
+            let proof = universal.prove(pw)?;
+            assert_eq!(proof.public_inputs[..4], allow_list.root().elements);
+            assert_eq!(proof.public_inputs[4], F::from_canonical_u64(1 << (i + 2)));
+            universal.verify(proof)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_allow_list_with_different_cap_height() -> Result<()> {
+        let mut inner_config = CircuitConfig::standard_recursion_config();
+        inner_config.fri_config.cap_height = 2;
+        let outer_config = CircuitConfig::standard_recursion_config();
+        assert_ne!(
+            inner_config.fri_config.cap_height,
+            outer_config.fri_config.cap_height
+        );
+        let circuits = [
+            power_circuit_with_config(2, inner_config.clone()),
+            power_circuit_with_config(3, inner_config),
+        ];
+        let common_data = &circuits[0].0.common;
+        let allow_list = VerifierAllowList::<F, C, D>::new(
+            circuits
+                .iter()
+                .map(|(data, _)| data.verifier_only.clone())
+                .collect(),
+        );
+
+        let mut builder = CircuitBuilder::<F, D>::new(outer_config);
+        let root = builder.add_virtual_hash();
+        let proof_target = builder.add_virtual_proof_with_pis(common_data);
+        let allowed = builder.verify_proof_with_allow_list::<C>(
+            &proof_target,
+            root,
+            allow_list.height(),
+            common_data,
+        );
+        let universal = builder.build::<C>();
+
+        let (data, x) = &circuits[1];
+        let mut pw = PartialWitness::new();
+        pw.set_target(*x, F::TWO);
+        let inner_proof = data.prove(pw)?;
+
+        let mut pw = PartialWitness::new();
+        pw.set_hash_target(root, allow_list.root());
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        pw.set_allowed_verifier_data_target(&allowed, &allow_list, 1);
+        universal.verify(universal.prove(pw)?)
+    }
+}
@@ -0,0 +1,128 @@
+//! Wrapping of a proof into a small final proof.
+//!
+//! A wrap circuit verifies a proof of a fixed inner circuit, whose verifier data is a constant of
+//! the wrap circuit rather than a witness. Its only public inputs are the hash of the inner
+//! proof's public inputs, and it uses a high-rate FRI config by default, so that its proofs are
+//! small and cheap to verify.
+
+use anyhow::{ensure, Result};
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::{HashOut, RichField};
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{
+    CircuitConfig, CircuitData, CommonCircuitData, VerifierOnlyCircuitData,
+};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
+use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+
+/// A circuit verifying proofs of a fixed inner circuit, and exposing the hash of their public
+/// inputs.
+#[derive(Debug)]
+pub struct WrapCircuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub data: CircuitData<F, C, D>,
+    proof_with_pis: ProofWithPublicInputsTarget<D>,
+}
+
+impl<F, C, const D: usize> WrapCircuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    /// Builds a circuit wrapping proofs of `inner`, with `CircuitConfig::wrap_config`.
+    pub fn new(inner: &CircuitData<F, C, D>) -> Self {
+        Self::with_config(
+            &inner.verifier_only,
+            &inner.common,
+            CircuitConfig::wrap_config(),
+        )
+    }
+
+    pub fn with_config(
+        inner_verifier_data: &VerifierOnlyCircuitData<C, D>,
+        inner_common_data: &CommonCircuitData<F, D>,
+        config: CircuitConfig,
+    ) -> Self {
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_with_pis = builder.add_virtual_proof_with_pis(inner_common_data);
+        // The verifier data must be a constant, as a witness would let the prover pick any circuit.
+        let verifier_data = builder.constant_verifier_data(inner_verifier_data);
+        builder.verify_proof::<C>(&proof_with_pis, &verifier_data, inner_common_data);
+        let public_inputs_hash =
+            builder.hash_n_to_hash_no_pad::<C::InnerHasher>(proof_with_pis.public_inputs.clone());
+        builder.register_public_inputs(&public_inputs_hash.elements);
+
+        Self {
+            data: builder.build::<C>(),
+            proof_with_pis,
+        }
+    }
+
+    /// The public inputs of a wrapped proof, given those of the inner proof.
+    pub fn public_inputs_hash(inner_public_inputs: &[F]) -> HashOut<F> {
+        C::InnerHasher::hash_no_pad(inner_public_inputs)
+    }
+
+    pub fn prove(
+        &self,
+        inner_proof: &ProofWithPublicInputs<F, C, D>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            inner_proof.public_inputs.len() == self.proof_with_pis.public_inputs.len(),
+            "Expected {} public inputs, got {}",
+            self.proof_with_pis.public_inputs.len(),
+            inner_proof.public_inputs.len()
+        );
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&self.proof_with_pis, inner_proof);
+        self.data.prove(pw)
+    }
+
+    /// Verifies a wrapped proof, and checks that it commits to `inner_public_inputs`.
+    pub fn verify(
+        &self,
+        proof: ProofWithPublicInputs<F, C, D>,
+        inner_public_inputs: &[F],
+    ) -> Result<()> {
+        ensure!(
+            proof.public_inputs == Self::public_inputs_hash(inner_public_inputs).elements,
+            "The proof doesn't commit to the given public inputs"
+        );
+        self.data.verify(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Field;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[test]
+    fn test_wrap_circuit() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.square(x);
+        builder.register_public_inputs(&[x, y]);
+        let inner = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u64(3));
+        let inner_proof = inner.prove(pw)?;
+
+        let wrap = WrapCircuit::new(&inner);
+        assert_eq!(wrap.data.common.config, CircuitConfig::wrap_config());
+        assert_eq!(wrap.data.common.num_public_inputs, 4);
+        let proof = wrap.prove(&inner_proof)?;
+        let inner_public_inputs = [3, 9].map(F::from_canonical_u64);
+        assert!(wrap
+            .verify(proof.clone(), &[F::from_canonical_u64(3), F::ZERO])
+            .is_err());
+        wrap.verify(proof, &inner_public_inputs)
+    }
+}
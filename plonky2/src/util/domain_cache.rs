@@ -0,0 +1,311 @@
+//! A global cache of the tables which only depend on the size of an evaluation domain: FFT root
+//! tables, two-adic subgroups and bit-reversal permutations.
+//!
+//! Provers and verifiers of circuits and Starks of the same sizes keep needing the same tables,
+//! e.g. every proof of a recursion chain, or every table of a multi-Stark. With the `std` feature,
+//! tables are kept in a cache shared by all threads, bounded by [`DomainCacheConfig::max_bytes`]
+//! and evicting the least recently used tables first. Without it, tables are computed on each call.
+
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use crate::field::fft::FftRootTable;
+use crate::field::types::Field;
+use crate::util::reverse_bits;
+
+/// The configuration of the domain cache.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DomainCacheConfig {
+    /// The total size of the cached tables, beyond which the least recently used ones are evicted.
+    /// A table larger than this is never cached.
+    pub max_bytes: usize,
+}
+
+impl Default for DomainCacheConfig {
+    fn default() -> Self {
+        Self { max_bytes: 1 << 28 }
+    }
+}
+
+/// Returns the root table of FFTs of size `2^lg_n`.
+pub fn fft_root_table<F: Field>(lg_n: usize) -> Arc<FftRootTable<F>> {
+    cache::get_or_compute::<F, _>(Table::FftRoots, lg_n, table_size::<F>(lg_n), || {
+        crate::field::fft::fft_root_table(1 << lg_n)
+    })
+}
+
+/// Returns the subgroup of order `2^lg_n`, in the order of the powers of its generator
+/// `F::primitive_root_of_unity(lg_n)`.
+pub fn two_adic_subgroup<F: Field>(lg_n: usize) -> Arc<Vec<F>> {
+    cache::get_or_compute::<F, _>(Table::Subgroup, lg_n, table_size::<F>(lg_n), || {
+        F::two_adic_subgroup(lg_n)
+    })
+}
+
+/// Returns the bit-reversal permutation of `2^lg_n` indices, i.e. `reverse_bits(i, lg_n)` at
+/// index `i`.
+pub fn bit_reversal_permutation(lg_n: usize) -> Arc<Vec<usize>> {
+    cache::get_or_compute::<(), _>(Table::BitReversal, lg_n, table_size::<usize>(lg_n), || {
+        (0..1 << lg_n).map(|i| reverse_bits(i, lg_n)).collect()
+    })
+}
+
+/// Fills the cache with the tables of the domains of size `2^lg_n` for each `lg_n` in `lg_ns`,
+/// e.g. before proving, so that the first proofs don't pay for them.
+pub fn warm_domain_cache<F: Field>(lg_ns: impl IntoIterator<Item = usize>) {
+    for lg_n in lg_ns {
+        fft_root_table::<F>(lg_n);
+        two_adic_subgroup::<F>(lg_n);
+        bit_reversal_permutation(lg_n);
+    }
+}
+
+/// Sets the configuration of the domain cache, evicting tables if it shrank.
+pub fn set_domain_cache_config(config: DomainCacheConfig) {
+    cache::set_config(config);
+}
+
+/// Returns the configuration of the domain cache.
+pub fn domain_cache_config() -> DomainCacheConfig {
+    cache::config()
+}
+
+/// Removes all tables from the domain cache.
+pub fn clear_domain_cache() {
+    cache::clear();
+}
+
+/// The kinds of cached tables.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+enum Table {
+    FftRoots,
+    Subgroup,
+    BitReversal,
+}
+
+/// The approximate size in bytes of a table of `2^lg_n` elements of type `T`, used to bound the
+/// size of the cache. A root table holds about as many elements as the domain.
+fn table_size<T>(lg_n: usize) -> usize {
+    (1 << lg_n) * size_of::<T>()
+}
+
+#[cfg(feature = "std")]
+mod cache {
+    use core::any::{Any, TypeId};
+    use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+    use hashbrown::HashMap;
+
+    use super::{DomainCacheConfig, Table};
+
+    type Key = (TypeId, Table, usize);
+
+    struct Entry {
+        table: Arc<dyn Any + Send + Sync>,
+        size: usize,
+        last_used: u64,
+    }
+
+    #[derive(Default)]
+    pub(super) struct DomainCache {
+        config: DomainCacheConfig,
+        entries: HashMap<Key, Entry>,
+        pub(super) size: usize,
+        clock: u64,
+    }
+
+    impl DomainCache {
+        fn get(&mut self, key: &Key) -> Option<Arc<dyn Any + Send + Sync>> {
+            self.clock += 1;
+            let entry = self.entries.get_mut(key)?;
+            entry.last_used = self.clock;
+            Some(entry.table.clone())
+        }
+
+        fn insert(&mut self, key: Key, table: Arc<dyn Any + Send + Sync>, size: usize) {
+            if size > self.config.max_bytes || self.entries.contains_key(&key) {
+                return;
+            }
+            self.clock += 1;
+            let entry = Entry {
+                table,
+                size,
+                last_used: self.clock,
+            };
+            self.entries.insert(key, entry);
+            self.size += size;
+            self.evict();
+        }
+
+        pub(super) fn set_config(&mut self, config: DomainCacheConfig) {
+            self.config = config;
+            self.evict();
+        }
+
+        fn clear(&mut self) {
+            self.entries.clear();
+            self.size = 0;
+        }
+
+        /// Evicts the least recently used tables until the cache fits in its budget.
+        fn evict(&mut self) {
+            while self.size > self.config.max_bytes {
+                let (&key, _) = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .expect("A non-empty cache has entries");
+                let entry = self.entries.remove(&key).unwrap();
+                self.size -= entry.size;
+            }
+        }
+    }
+
+    fn lock(cache: &Mutex<DomainCache>) -> MutexGuard<'_, DomainCache> {
+        // The cache is consistent between operations, so a panic elsewhere doesn't corrupt it.
+        cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn global() -> &'static Mutex<DomainCache> {
+        static CACHE: OnceLock<Mutex<DomainCache>> = OnceLock::new();
+        CACHE.get_or_init(Default::default)
+    }
+
+    /// Returns the table of kind `table` for the domain of size `2^lg_n` over `F`, computing it
+    /// with `compute` if it isn't in `cache`, and caching it as taking `size` bytes. The cache
+    /// isn't locked while computing, so that tables of different sizes can be computed
+    /// concurrently.
+    pub(super) fn get_or_compute_in<F: 'static, T: Send + Sync + 'static>(
+        cache: &Mutex<DomainCache>,
+        table: Table,
+        lg_n: usize,
+        size: usize,
+        compute: impl FnOnce() -> T,
+    ) -> Arc<T> {
+        let key = (TypeId::of::<F>(), table, lg_n);
+        if let Some(table) = lock(cache).get(&key) {
+            return table.downcast().expect("Tables are keyed by type");
+        }
+        let computed = Arc::new(compute());
+        lock(cache).insert(key, computed.clone(), size);
+        computed
+    }
+
+    pub(super) fn get_or_compute<F: 'static, T: Send + Sync + 'static>(
+        table: Table,
+        lg_n: usize,
+        size: usize,
+        compute: impl FnOnce() -> T,
+    ) -> Arc<T> {
+        get_or_compute_in::<F, T>(global(), table, lg_n, size, compute)
+    }
+
+    pub(super) fn set_config(config: DomainCacheConfig) {
+        lock(global()).set_config(config);
+    }
+
+    pub(super) fn config() -> DomainCacheConfig {
+        lock(global()).config
+    }
+
+    pub(super) fn clear() {
+        lock(global()).clear();
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod cache {
+    use alloc::sync::Arc;
+
+    use super::{DomainCacheConfig, Table};
+
+    pub(super) fn get_or_compute<F, T>(
+        _table: Table,
+        _lg_n: usize,
+        _size: usize,
+        compute: impl FnOnce() -> T,
+    ) -> Arc<T> {
+        Arc::new(compute())
+    }
+
+    pub(super) fn set_config(_config: DomainCacheConfig) {}
+
+    pub(super) fn config() -> DomainCacheConfig {
+        DomainCacheConfig { max_bytes: 0 }
+    }
+
+    pub(super) fn clear() {}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::cache::{get_or_compute_in, DomainCache};
+    use super::*;
+    use crate::field::goldilocks_field::GoldilocksField;
+    use crate::util::reverse_index_bits;
+
+    type F = GoldilocksField;
+
+    #[test]
+    fn test_tables() {
+        warm_domain_cache::<F>(0..6);
+        for lg_n in 0..6 {
+            assert_eq!(
+                *fft_root_table::<F>(lg_n),
+                crate::field::fft::fft_root_table::<F>(1 << lg_n)
+            );
+            assert_eq!(*two_adic_subgroup::<F>(lg_n), F::two_adic_subgroup(lg_n));
+            let indices = (0..1 << lg_n).collect::<Vec<usize>>();
+            assert_eq!(
+                *bit_reversal_permutation(lg_n),
+                reverse_index_bits(&indices)
+            );
+        }
+    }
+
+    #[test]
+    fn test_eviction() {
+        // A local cache, as other tests use the global one concurrently.
+        let cache = Mutex::new(DomainCache::default());
+        let subgroup = |lg_n| {
+            get_or_compute_in::<F, _>(&cache, Table::Subgroup, lg_n, table_size::<F>(lg_n), || {
+                F::two_adic_subgroup(lg_n)
+            })
+        };
+        let permutation = |lg_n| {
+            get_or_compute_in::<(), _>(
+                &cache,
+                Table::BitReversal,
+                lg_n,
+                table_size::<usize>(lg_n),
+                || vec![0usize; 1 << lg_n],
+            )
+        };
+        let first = subgroup(4);
+        assert!(Arc::ptr_eq(&first, &subgroup(4)));
+
+        // Tables of 2^7 elements take 1 KiB, so only the most recently used one fits.
+        cache
+            .lock()
+            .unwrap()
+            .set_config(DomainCacheConfig { max_bytes: 1 << 10 });
+        let first = subgroup(7);
+        assert!(Arc::ptr_eq(&first, &subgroup(7)));
+        let second = permutation(7);
+        assert_eq!(cache.lock().unwrap().size, 1 << 10);
+        assert!(Arc::ptr_eq(&second, &permutation(7)));
+        assert!(!Arc::ptr_eq(&first, &subgroup(7)));
+        assert!(!Arc::ptr_eq(&second, &permutation(7)));
+
+        // Tables exceeding the budget are computed but not cached.
+        assert!(!Arc::ptr_eq(&subgroup(8), &subgroup(8)));
+    }
+}
@@ -8,7 +8,9 @@ use crate::field::polynomial::PolynomialValues;
 use crate::field::types::Field;
 
 pub(crate) mod context_tree;
+pub mod domain_cache;
 pub(crate) mod partial_products;
+pub mod randomness;
 pub mod reducing;
 pub mod serialization;
 pub mod strided_view;
@@ -19,12 +21,35 @@ pub(crate) fn transpose_poly_values<F: Field>(polys: Vec<PolynomialValues<F>>) -
     transpose(&poly_values)
 }
 
+/// The side of the square tiles [`transpose`] works on, small enough for a tile to stay in cache
+/// while it is copied.
+const TRANSPOSE_BLOCK_SIZE: usize = 32;
+
+/// Transposes `matrix`, whose rows must all have the same length. Blocks of output rows are built
+/// in parallel, one tile at a time, so that each cache line read from an input row is fully used
+/// rather than evicted between accesses to its elements.
 pub fn transpose<T: Send + Sync + Copy>(matrix: &[Vec<T>]) -> Vec<Vec<T>> {
-    let len = matrix[0].len();
-    (0..len)
-        .into_par_iter()
-        .map(|i| matrix.iter().map(|row| row[i]).collect())
-        .collect()
+    let mut transposed = (0..matrix[0].len())
+        .map(|_| Vec::with_capacity(matrix.len()))
+        .collect::<Vec<_>>();
+    transpose_into(matrix, &mut transposed);
+    transposed
+}
+
+/// Like [`transpose`], but appends the columns of `matrix` to the rows of `transposed`, e.g. to
+/// transpose a matrix in several batches of rows.
+pub fn transpose_into<T: Send + Sync + Copy>(matrix: &[Vec<T>], transposed: &mut [Vec<T>]) {
+    transposed
+        .par_chunks_mut(TRANSPOSE_BLOCK_SIZE)
+        .enumerate()
+        .for_each(|(block, block_rows)| {
+            let start = block * TRANSPOSE_BLOCK_SIZE;
+            for rows in matrix.chunks(TRANSPOSE_BLOCK_SIZE) {
+                for (i, transposed_row) in block_rows.iter_mut().enumerate() {
+                    transposed_row.extend(rows.iter().map(|row| row[start + i]));
+                }
+            }
+        });
 }
 
 pub(crate) fn reverse_bits(n: usize, num_bits: usize) -> usize {
@@ -50,6 +75,20 @@ mod tests {
         assert_eq!(reverse_bits(0b01011, 5), 0b11010);
     }
 
+    #[test]
+    fn test_transpose() {
+        // Neither dimension is a multiple of the block size.
+        let (height, width) = (3, 2 * TRANSPOSE_BLOCK_SIZE + 5);
+        let matrix = (0..height)
+            .map(|i| (0..width).map(|j| (i, j)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let transposed = transpose(&matrix);
+        assert_eq!(transposed.len(), width);
+        for (j, row) in transposed.iter().enumerate() {
+            assert_eq!(*row, (0..height).map(|i| (i, j)).collect::<Vec<_>>());
+        }
+    }
+
     #[test]
     fn test_reverse_index_bits() {
         assert_eq!(reverse_index_bits(&[10, 20, 30, 40]), vec![10, 30, 20, 40]);
@@ -0,0 +1,109 @@
+//! The source of a prover's randomness, i.e. of the salts of its commitments and of the random
+//! values of its witness.
+//!
+//! By default, the prover samples from the operating system, so that proofs are zero-knowledge
+//! but can't be reproduced. A seeded [`ProverRandomness`] makes a proof reproducible bit for bit,
+//! e.g. to debug a prover failure.
+
+use alloc::boxed::Box;
+
+use rand::rngs::{OsRng, StdRng};
+use rand::{Error, RngCore, SeedableRng};
+
+/// The source of a prover's randomness.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ProverRandomness {
+    /// Randomness from the operating system.
+    #[default]
+    Os,
+    /// Randomness derived deterministically from a seed. A proof is only zero-knowledge if its
+    /// seed is secret and uniformly random, so this is meant for testing and debugging.
+    Seeded(u64),
+}
+
+impl ProverRandomness {
+    /// Returns the randomness used for `purpose`, independent of the randomness of other purposes.
+    pub fn derive(&self, purpose: u64) -> Self {
+        match *self {
+            Self::Os => Self::Os,
+            Self::Seeded(seed) => Self::Seeded(seeded_rng(seed, purpose, 1).next_u64()),
+        }
+    }
+
+    /// Returns the RNG of the `index`th draw, e.g. of the `index`th leaf of a commitment. Draws of
+    /// different indices are independent, so that they can be made in parallel while remaining
+    /// reproducible.
+    pub fn rng(&self, index: u64) -> ProverRng {
+        match *self {
+            Self::Os => ProverRng::Os(OsRng),
+            Self::Seeded(seed) => ProverRng::Seeded(Box::new(seeded_rng(seed, index, 0))),
+        }
+    }
+}
+
+fn seeded_rng(seed: u64, index: u64, domain: u64) -> StdRng {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    bytes[8..16].copy_from_slice(&index.to_le_bytes());
+    bytes[16..24].copy_from_slice(&domain.to_le_bytes());
+    StdRng::from_seed(bytes)
+}
+
+/// An RNG returned by [`ProverRandomness::rng`].
+#[derive(Clone, Debug)]
+pub enum ProverRng {
+    Os(OsRng),
+    Seeded(Box<StdRng>),
+}
+
+impl RngCore for ProverRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Self::Os(rng) => rng.next_u32(),
+            Self::Seeded(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Self::Os(rng) => rng.next_u64(),
+            Self::Seeded(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            Self::Os(rng) => rng.fill_bytes(dest),
+            Self::Seeded(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            Self::Os(rng) => rng.try_fill_bytes(dest),
+            Self::Seeded(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_randomness() {
+        let randomness = ProverRandomness::Seeded(42);
+        assert_eq!(
+            randomness.rng(3).next_u64(),
+            ProverRandomness::Seeded(42).rng(3).next_u64()
+        );
+        assert_ne!(randomness.rng(3).next_u64(), randomness.rng(4).next_u64());
+        assert_ne!(
+            randomness.rng(3).next_u64(),
+            ProverRandomness::Seeded(43).rng(3).next_u64()
+        );
+        assert_eq!(randomness.derive(1), randomness.derive(1));
+        assert_ne!(randomness.derive(1), randomness.derive(2));
+        assert_eq!(ProverRandomness::Os.derive(1), ProverRandomness::Os);
+    }
+}
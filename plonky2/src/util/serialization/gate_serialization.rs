@@ -34,7 +34,7 @@ macro_rules! read_gate_impl {
             Ok($crate::gates::gate::GateRef::<F, D>::new(gate))
         } else)*
         {
-            Err($crate::util::serialization::IoError)
+            Err($crate::util::serialization::IoError::InvalidData)
         }
     }}
 }
@@ -53,7 +53,7 @@ macro_rules! get_gate_tag_impl {
                 "attempted to serialize gate with id `{}` which is unsupported by this gate serializer",
                 $gate.0.id()
             );
-            Err($crate::util::serialization::IoError)
+            Err($crate::util::serialization::IoError::InvalidData)
         }
     }};
 }
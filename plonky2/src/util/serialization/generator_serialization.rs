@@ -39,7 +39,7 @@ macro_rules! read_generator_impl {
         ))
         } else)*
         {
-            Err($crate::util::serialization::IoError)
+            Err($crate::util::serialization::IoError::InvalidData)
         }
     }};
 }
@@ -57,7 +57,7 @@ macro_rules! get_generator_tag_impl {
                 "attempted to serialize generator with id {} which is unsupported by this generator serializer",
                 $generator.0.id()
             );
-            Err($crate::util::serialization::IoError)
+            Err($crate::util::serialization::IoError::InvalidData)
         }
     }};
 }
@@ -100,6 +100,11 @@ pub mod default {
 
     use crate::gadgets::arithmetic::EqualityGenerator;
     use crate::gadgets::arithmetic_extension::QuotientGeneratorExtension;
+    use crate::gadgets::biguint::BigUintDivRemGenerator;
+    use crate::gadgets::decompose::DecompositionGenerator;
+    use crate::gadgets::membership::DictionaryIndexGenerator;
+    use crate::gadgets::montgomery::MontgomeryMultiplicationGenerator;
+    use crate::gadgets::nonnative::NonNativeDivisionGenerator;
     use crate::gadgets::range_check::LowHighGenerator;
     use crate::gadgets::split_base::BaseSumGenerator;
     use crate::gadgets::split_join::{SplitGenerator, WireSplitGenerator};
@@ -159,7 +164,13 @@ pub mod default {
             ReducingGenerator<D>,
             ReducingExtensionGenerator<D>,
             SplitGenerator,
-            WireSplitGenerator
+            WireSplitGenerator,
+            // Appended rather than sorted, so that the tags of existing generators don't change.
+            BigUintDivRemGenerator,
+            NonNativeDivisionGenerator,
+            MontgomeryMultiplicationGenerator,
+            DecompositionGenerator,
+            DictionaryIndexGenerator<F>
         }
     }
 }
@@ -5,6 +5,7 @@ pub mod generator_serialization;
 pub mod gate_serialization;
 
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -34,8 +35,10 @@ use crate::gates::gate::GateRef;
 use crate::gates::lookup::Lookup;
 use crate::gates::selectors::SelectorsInfo;
 use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
+use crate::hash::hashing::PlonkyPermutation;
 use crate::hash::merkle_proofs::{MerkleProof, MerkleProofTarget};
 use crate::hash::merkle_tree::{MerkleCap, MerkleTree};
+use crate::iop::challenger::Challenger;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::generator::WitnessGeneratorRef;
 use crate::iop::target::{BoolTarget, Target};
@@ -46,25 +49,98 @@ use crate::plonk::circuit_data::{
     VerifierCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
 };
 use crate::plonk::config::{GenericConfig, GenericHashOut, Hasher};
+use crate::plonk::diagnostics::CircuitDiagnostics;
 use crate::plonk::plonk_common::salt_size;
 use crate::plonk::proof::{
     CompressedProof, CompressedProofWithPublicInputs, OpeningSet, OpeningSetTarget, Proof,
     ProofTarget, ProofWithPublicInputs, ProofWithPublicInputsTarget,
 };
+use crate::plonk::public_input_layout::{NamedPublicInput, PublicInputLayout, PublicInputType};
 
 /// A no_std compatible variant of `std::io::Error`
-#[derive(Debug)]
-pub struct IoError;
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IoError {
+    /// The input ended before the value could be fully read.
+    UnexpectedEof,
+    /// A field element was encoded as an integer greater than or equal to the field order.
+    NonCanonicalField,
+    /// Bytes were left over after reading a value which should span the whole input.
+    TrailingBytes,
+    /// The input doesn't encode any valid value, e.g. an unknown enum tag or an unexpected length.
+    InvalidData,
+    /// The input doesn't start with [`SERIALIZATION_MAGIC`].
+    BadMagic,
+    /// The input was written with a format version which this crate can't read.
+    UnsupportedVersion(u16),
+    /// The input holds a different kind of artifact than the one being read.
+    UnexpectedArtifact { expected: ArtifactKind, found: u8 },
+}
 
 impl Display for IoError {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        Debug::fmt(self, f)
+        match self {
+            IoError::UnexpectedEof => write!(f, "unexpected end of input"),
+            IoError::NonCanonicalField => write!(f, "non-canonical field element encoding"),
+            IoError::TrailingBytes => write!(f, "trailing bytes after the encoded value"),
+            IoError::InvalidData => write!(f, "invalid encoding"),
+            IoError::BadMagic => write!(f, "missing serialization header"),
+            IoError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported serialization version {version}, expected \
+                 {MIN_SERIALIZATION_VERSION}..={SERIALIZATION_VERSION}"
+            ),
+            IoError::UnexpectedArtifact { expected, found } => {
+                write!(
+                    f,
+                    "expected a serialized {expected:?}, found artifact tag {found}"
+                )
+            }
+        }
     }
 }
 
 /// A no_std compatible variant of `std::io::Result`
 pub type IoResult<T> = Result<T, IoError>;
 
+/// Magic bytes at the start of every artifact produced by a `to_bytes` method.
+pub const SERIALIZATION_MAGIC: [u8; 4] = *b"PLK2";
+
+/// Version of the binary format written by this crate. It must be bumped whenever the encoding
+/// of any artifact changes, so that stale artifacts are rejected rather than misparsed.
+///
+/// Version 2 added the public input layout to [`VerifierOnlyCircuitData`].
+/// Version 3 added the rows of merged gates to [`ProverOnlyCircuitData`].
+/// Version 4 added watched targets to [`ProverOnlyCircuitData`].
+/// Version 5 added [`CircuitDiagnostics`] to [`ProverOnlyCircuitData`].
+/// Version 6 added the contexts of generators to [`CircuitDiagnostics`].
+pub const SERIALIZATION_VERSION: u16 = 6;
+
+/// Oldest binary format version which this crate can still read.
+pub const MIN_SERIALIZATION_VERSION: u16 = 1;
+
+/// Length in bytes of the header written by [`Write::write_header`].
+pub const SERIALIZATION_HEADER_LEN: usize = SERIALIZATION_MAGIC.len() + size_of::<u16>() + 1;
+
+/// The kind of artifact following a serialization header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ArtifactKind {
+    Proof = 0,
+    CompressedProof = 1,
+    CommonCircuitData = 2,
+    VerifierOnlyCircuitData = 3,
+    VerifierCircuitData = 4,
+    ProverOnlyCircuitData = 5,
+    ProverCircuitData = 6,
+    CircuitData = 7,
+    VerifierCircuitTarget = 8,
+    CommittedWitness = 9,
+    ChallengedProof = 10,
+    StarkProof = 11,
+    MultiStarkProof = 12,
+    AllRecursiveCircuits = 13,
+}
+
 /// A `Read` which is able to report how many bytes are remaining.
 pub trait Remaining: Read {
     /// Returns the number of bytes remaining in the buffer.
@@ -74,6 +150,16 @@ pub trait Remaining: Read {
     fn is_empty(&self) -> bool {
         self.remaining() == 0
     }
+
+    /// Returns an error if any bytes are remaining, so that a value which should span the whole
+    /// input can't be followed by arbitrary padding.
+    fn ensure_empty(&self) -> IoResult<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(IoError::TrailingBytes)
+        }
+    }
 }
 
 /// Similar to `std::io::Read`, but works with no_std.
@@ -81,6 +167,15 @@ pub trait Read {
     /// Reads exactly the length of `bytes` from `self` and writes it to `bytes`.
     fn read_exact(&mut self, bytes: &mut [u8]) -> IoResult<()>;
 
+    /// Returns the format version of the data being read, as recorded by [`Read::read_header`].
+    /// Readers which don't keep track of it assume the current [`SERIALIZATION_VERSION`].
+    fn format_version(&self) -> u16 {
+        SERIALIZATION_VERSION
+    }
+
+    /// Records the format version of the data being read.
+    fn set_format_version(&mut self, _version: u16) {}
+
     /// Reads a `bool` value from `self`.
     #[inline]
     fn read_bool(&mut self) -> IoResult<bool> {
@@ -88,8 +183,35 @@ pub trait Read {
         match i {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(IoError),
+            _ => Err(IoError::InvalidData),
+        }
+    }
+
+    /// Reads the header written by [`Write::write_header`], checking that it precedes an artifact
+    /// of the given kind in a supported format version. Returns that version, so that callers can
+    /// decode older layouts once the format evolves.
+    fn read_header(&mut self, kind: ArtifactKind) -> IoResult<u16> {
+        let mut magic = [0; SERIALIZATION_MAGIC.len()];
+        self.read_exact(&mut magic)?;
+        if magic != SERIALIZATION_MAGIC {
+            return Err(IoError::BadMagic);
+        }
+
+        let version = self.read_u16()?;
+        if !(MIN_SERIALIZATION_VERSION..=SERIALIZATION_VERSION).contains(&version) {
+            return Err(IoError::UnsupportedVersion(version));
         }
+        self.set_format_version(version);
+
+        let found = self.read_u8()?;
+        if found != kind as u8 {
+            return Err(IoError::UnexpectedArtifact {
+                expected: kind,
+                found,
+            });
+        }
+
+        Ok(version)
     }
 
     /// Reads a `BoolTarget` value from `self`.
@@ -136,7 +258,7 @@ pub trait Read {
     fn read_usize(&mut self) -> IoResult<usize> {
         let mut buf = [0; core::mem::size_of::<u64>()];
         self.read_exact(&mut buf)?;
-        Ok(u64::from_le_bytes(buf) as usize)
+        usize::try_from(u64::from_le_bytes(buf)).map_err(|_| IoError::InvalidData)
     }
 
     /// Reads a vector of `usize` value from `self`.
@@ -151,7 +273,20 @@ pub trait Read {
         Ok(res)
     }
 
+    /// Reads a length-prefixed UTF-8 string from `self`.
+    #[inline]
+    fn read_string(&mut self) -> IoResult<String> {
+        let len = self.read_usize()?;
+        let mut bytes = Vec::new();
+        for _ in 0..len {
+            bytes.push(self.read_u8()?);
+        }
+        String::from_utf8(bytes).map_err(|_| IoError::InvalidData)
+    }
+
     /// Reads a element from the field `F` with size less than `2^64` from `self.`
+    ///
+    /// The element must be encoded canonically, i.e. as an integer less than the field order.
     #[inline]
     fn read_field<F>(&mut self) -> IoResult<F>
     where
@@ -159,7 +294,11 @@ pub trait Read {
     {
         let mut buf = [0; size_of::<u64>()];
         self.read_exact(&mut buf)?;
-        Ok(F::from_canonical_u64(u64::from_le_bytes(buf)))
+        let n = u64::from_le_bytes(buf);
+        if n >= F::ORDER {
+            return Err(IoError::NonCanonicalField);
+        }
+        Ok(F::from_canonical_u64(n))
     }
 
     /// Reads a vector of elements from the field `F` from `self`.
@@ -261,7 +400,12 @@ pub trait Read {
     {
         let mut buf = vec![0; H::HASH_SIZE];
         self.read_exact(&mut buf)?;
-        Ok(H::Hash::from_bytes(&buf))
+        let hash = H::Hash::from_bytes(&buf);
+        // Hashes made of field elements must be encoded canonically.
+        if hash.to_bytes() != buf {
+            return Err(IoError::NonCanonicalField);
+        }
+        Ok(hash)
     }
 
     /// Reads a HashOutTarget value from `self`.
@@ -338,6 +482,29 @@ pub trait Read {
         })
     }
 
+    /// Reads a value of type [`Challenger`] from `self`.
+    #[inline]
+    fn read_challenger<F, H>(&mut self) -> IoResult<Challenger<F, H>>
+    where
+        F: RichField,
+        H: Hasher<F>,
+    {
+        let sponge_state = H::Permutation::new(self.read_field_vec(H::Permutation::WIDTH)?);
+        let input_buffer_len = self.read_usize()?;
+        let input_buffer = self.read_field_vec(input_buffer_len)?;
+        let output_buffer_len = self.read_usize()?;
+        let output_buffer = self.read_field_vec(output_buffer_len)?;
+        if input_buffer.len() > H::Permutation::RATE || output_buffer.len() > H::Permutation::RATE {
+            return Err(IoError::InvalidData);
+        }
+        Ok(Challenger {
+            sponge_state,
+            input_buffer,
+            output_buffer,
+            transcript: None,
+        })
+    }
+
     /// Reads a value of type [`OpeningSet`] from `self` with the given `common_data`.
     #[inline]
     fn read_opening_set<F, C, const D: usize>(
@@ -629,10 +796,10 @@ pub trait Read {
                         let max = self.read_usize()?;
                         Ok(FriReductionStrategy::MinSize(Some(max)))
                     }
-                    _ => Err(IoError),
+                    _ => Err(IoError::InvalidData),
                 }
             }
-            _ => Err(IoError),
+            _ => Err(IoError::InvalidData),
         }
     }
 
@@ -823,6 +990,37 @@ pub trait Read {
         })
     }
 
+    fn read_circuit_diagnostics(&mut self, num_gates: usize) -> IoResult<CircuitDiagnostics> {
+        let row_gates = self.read_usize_vec()?;
+        if row_gates.iter().any(|&gate| gate >= num_gates) {
+            return Err(IoError::InvalidData);
+        }
+        let len = self.read_usize()?;
+        let mut contexts = Vec::with_capacity(len);
+        for _ in 0..len {
+            contexts.push(self.read_string()?);
+        }
+        let row_contexts = self.read_usize_vec()?;
+        let generator_contexts = if self.format_version() >= 6 {
+            self.read_usize_vec()?
+        } else {
+            Vec::new()
+        };
+        if row_contexts
+            .iter()
+            .chain(&generator_contexts)
+            .any(|&context| context >= contexts.len())
+        {
+            return Err(IoError::InvalidData);
+        }
+        Ok(CircuitDiagnostics {
+            row_gates,
+            contexts,
+            row_contexts,
+            generator_contexts,
+        })
+    }
+
     fn read_prover_only_circuit_data<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -891,6 +1089,25 @@ pub trait Read {
             lut_to_lookups.push(self.read_target_lut()?);
         }
 
+        let merged_rows = if self.format_version() >= 3 {
+            self.read_usize_vec()?
+        } else {
+            Vec::new()
+        };
+
+        let mut watches = Vec::new();
+        if self.format_version() >= 4 {
+            for _ in 0..self.read_usize()? {
+                watches.push((self.read_string()?, self.read_target()?));
+            }
+        }
+
+        let diagnostics = if self.format_version() >= 5 {
+            self.read_circuit_diagnostics(common_data.gates.len())?
+        } else {
+            CircuitDiagnostics::default()
+        };
+
         Ok(ProverOnlyCircuitData {
             generators,
             generator_indices_by_watches,
@@ -903,6 +1120,9 @@ pub trait Read {
             circuit_digest,
             lookup_rows,
             lut_to_lookups,
+            merged_rows,
+            watches,
+            diagnostics,
         })
     }
 
@@ -933,12 +1153,36 @@ pub trait Read {
         let height = self.read_usize()?;
         let constants_sigmas_cap = self.read_merkle_cap(height)?;
         let circuit_digest = self.read_hash::<F, <C as GenericConfig<D>>::Hasher>()?;
+        // Version 1 predates named public inputs.
+        let public_input_layout = if self.format_version() >= 2 {
+            self.read_public_input_layout()?
+        } else {
+            PublicInputLayout::default()
+        };
         Ok(VerifierOnlyCircuitData {
             constants_sigmas_cap,
             circuit_digest,
+            public_input_layout,
         })
     }
 
+    fn read_public_input_layout(&mut self) -> IoResult<PublicInputLayout> {
+        let len = self.read_usize()?;
+        let mut entries = Vec::with_capacity(len);
+        for _ in 0..len {
+            let name = self.read_string()?;
+            let ty = match self.read_u8()? {
+                0 => PublicInputType::Field,
+                1 => PublicInputType::Hash,
+                2 => PublicInputType::U32Array(self.read_usize()?),
+                _ => return Err(IoError::InvalidData),
+            };
+            let start = self.read_usize()?;
+            entries.push(NamedPublicInput { name, ty, start });
+        }
+        PublicInputLayout::new(entries).map_err(|_| IoError::InvalidData)
+    }
+
     fn read_verifier_circuit_data<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -1019,6 +1263,9 @@ pub trait Read {
     {
         let proof = self.read_proof(common_data)?;
         let pi_len = self.read_usize()?;
+        if pi_len != common_data.num_public_inputs {
+            return Err(IoError::InvalidData);
+        }
         let public_inputs = self.read_field_vec(pi_len)?;
         Ok(ProofWithPublicInputs {
             proof,
@@ -1051,7 +1298,10 @@ pub trait Read {
     {
         let config = &common_data.config;
         let original_indices = (0..config.fri_config.num_query_rounds)
-            .map(|_| self.read_u32().map(|i| i as usize))
+            .map(|_| match self.read_u32()? as usize {
+                i if i < common_data.lde_size() => Ok(i),
+                _ => Err(IoError::InvalidData),
+            })
             .collect::<Result<Vec<_>, _>>()?;
         let mut indices = original_indices.clone();
         indices.sort_unstable();
@@ -1151,7 +1401,7 @@ pub trait Read {
         C: GenericConfig<D, F = F>,
     {
         let proof = self.read_compressed_proof(common_data)?;
-        let public_inputs = self.read_field_vec(self.remaining() / size_of::<u64>())?;
+        let public_inputs = self.read_field_vec(common_data.num_public_inputs)?;
         Ok(CompressedProofWithPublicInputs {
             proof,
             public_inputs,
@@ -1197,6 +1447,14 @@ pub trait Write {
         self.write_u8(u8::from(x))
     }
 
+    /// Writes a header identifying an artifact of the given kind, encoded with the current
+    /// [`SERIALIZATION_VERSION`].
+    fn write_header(&mut self, kind: ArtifactKind) -> IoResult<()> {
+        self.write_all(&SERIALIZATION_MAGIC)?;
+        self.write_u16(SERIALIZATION_VERSION)?;
+        self.write_u8(kind as u8)
+    }
+
     /// Writes a target bool `x` to `self`.
     #[inline]
     fn write_target_bool(&mut self, x: BoolTarget) -> IoResult<()> {
@@ -1249,6 +1507,13 @@ pub trait Write {
         Ok(())
     }
 
+    /// Writes a string `s` to `self`, prefixed by its length in bytes.
+    #[inline]
+    fn write_string(&mut self, s: &str) -> IoResult<()> {
+        self.write_usize(s.len())?;
+        self.write_all(s.as_bytes())
+    }
+
     /// Writes an element `x` from the field `F` to `self`.
     #[inline]
     fn write_field<F>(&mut self, x: F) -> IoResult<()>
@@ -1431,6 +1696,22 @@ pub trait Write {
         Ok(())
     }
 
+    /// Writes `challenger`, a value of type [`Challenger`], to `self`.
+    #[inline]
+    fn write_challenger<F, H>(&mut self, challenger: &Challenger<F, H>) -> IoResult<()>
+    where
+        F: RichField,
+        H: Hasher<F>,
+    {
+        self.write_field_vec(challenger.sponge_state.as_ref())?;
+        self.write_usize(challenger.input_buffer.len())?;
+        self.write_field_vec(&challenger.input_buffer)?;
+        self.write_usize(challenger.output_buffer.len())?;
+        self.write_field_vec(&challenger.output_buffer)?;
+
+        Ok(())
+    }
+
     /// Writes a value `os` of type [`OpeningSet`] to `self.`
     #[inline]
     fn write_opening_set<F, const D: usize>(&mut self, os: &OpeningSet<F, D>) -> IoResult<()>
@@ -1831,6 +2112,16 @@ pub trait Write {
         self.write_verifier_only_circuit_data(&circuit_data.verifier_only)
     }
 
+    fn write_circuit_diagnostics(&mut self, diagnostics: &CircuitDiagnostics) -> IoResult<()> {
+        self.write_usize_vec(&diagnostics.row_gates)?;
+        self.write_usize(diagnostics.contexts.len())?;
+        for context in &diagnostics.contexts {
+            self.write_string(context)?;
+        }
+        self.write_usize_vec(&diagnostics.row_contexts)?;
+        self.write_usize_vec(&diagnostics.generator_contexts)
+    }
+
     fn write_prover_only_circuit_data<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -1853,6 +2144,9 @@ pub trait Write {
             circuit_digest,
             lookup_rows,
             lut_to_lookups,
+            merged_rows,
+            watches,
+            diagnostics,
         } = prover_only_circuit_data;
 
         self.write_usize(generators.len())?;
@@ -1903,6 +2197,16 @@ pub trait Write {
             self.write_target_lut(tlut)?;
         }
 
+        self.write_usize_vec(merged_rows)?;
+
+        self.write_usize(watches.len())?;
+        for (label, target) in watches {
+            self.write_string(label)?;
+            self.write_target(*target)?;
+        }
+
+        self.write_circuit_diagnostics(diagnostics)?;
+
         Ok(())
     }
 
@@ -1935,11 +2239,31 @@ pub trait Write {
         let VerifierOnlyCircuitData {
             constants_sigmas_cap,
             circuit_digest,
+            public_input_layout,
         } = verifier_only_circuit_data;
 
         self.write_usize(constants_sigmas_cap.height())?;
         self.write_merkle_cap(constants_sigmas_cap)?;
         self.write_hash::<F, <C as GenericConfig<D>>::Hasher>(*circuit_digest)?;
+        self.write_public_input_layout(public_input_layout)?;
+
+        Ok(())
+    }
+
+    fn write_public_input_layout(&mut self, layout: &PublicInputLayout) -> IoResult<()> {
+        self.write_usize(layout.entries().len())?;
+        for entry in layout.entries() {
+            self.write_string(&entry.name)?;
+            match entry.ty {
+                PublicInputType::Field => self.write_u8(0)?,
+                PublicInputType::Hash => self.write_u8(1)?,
+                PublicInputType::U32Array(len) => {
+                    self.write_u8(2)?;
+                    self.write_usize(len)?;
+                }
+            }
+            self.write_usize(entry.start)?;
+        }
 
         Ok(())
     }
@@ -2168,13 +2492,18 @@ impl Write for Vec<u8> {
 pub struct Buffer<'a> {
     bytes: &'a [u8],
     pos: usize,
+    version: u16,
 }
 
 impl<'a> Buffer<'a> {
     /// Builds a new [`Buffer`] over `buffer`.
     #[inline]
     pub fn new(bytes: &'a [u8]) -> Self {
-        Self { bytes, pos: 0 }
+        Self {
+            bytes,
+            pos: 0,
+            version: SERIALIZATION_VERSION,
+        }
     }
 
     /// Returns the inner position.
@@ -2207,7 +2536,7 @@ impl<'a> Read for Buffer<'a> {
     fn read_exact(&mut self, bytes: &mut [u8]) -> IoResult<()> {
         let n = bytes.len();
         if self.remaining() < n {
-            Err(IoError)
+            Err(IoError::UnexpectedEof)
         } else {
             bytes.copy_from_slice(&self.bytes[self.pos..][..n]);
             self.pos += n;
@@ -2215,6 +2544,14 @@ impl<'a> Read for Buffer<'a> {
         }
     }
 
+    fn format_version(&self) -> u16 {
+        self.version
+    }
+
+    fn set_format_version(&mut self, version: u16) {
+        self.version = version;
+    }
+
     fn read_gate<F: RichField + Extendable<D>, const D: usize>(
         &mut self,
         gate_serializer: &dyn GateSerializer<F, D>,
@@ -16,6 +16,9 @@ pub struct TimingTree {
     exit_time: Option<Instant>,
     /// Any child scopes.
     children: Vec<TimingTree>,
+    /// The `tracing` span mirroring this scope.
+    #[cfg(feature = "tracing")]
+    span: ScopeSpan,
 }
 
 #[cfg(not(feature = "timing"))]
@@ -44,6 +47,10 @@ impl TimingTree {
             enter_time: Instant::now(),
             exit_time: None,
             children: vec![],
+            // The root scope is never popped, so its span is not entered, lest it stay entered
+            // on this thread.
+            #[cfg(feature = "tracing")]
+            span: ScopeSpan::new(tracing::Span::current(), root_name, level),
         }
     }
 
@@ -90,12 +97,20 @@ impl TimingTree {
             }
         }
 
+        #[cfg(feature = "tracing")]
+        let span = {
+            let mut span = ScopeSpan::new(self.span.span.clone(), ctx, level);
+            span.enter();
+            span
+        };
         self.children.push(TimingTree {
             name: ctx.to_string(),
             level,
             enter_time: Instant::now(),
             exit_time: None,
             children: vec![],
+            #[cfg(feature = "tracing")]
+            span,
         })
     }
 
@@ -115,6 +130,8 @@ impl TimingTree {
         }
 
         self.exit_time = Some(Instant::now());
+        #[cfg(feature = "tracing")]
+        self.span.exit();
     }
 
     #[cfg(not(feature = "timing"))]
@@ -141,6 +158,8 @@ impl TimingTree {
                 .filter(|c| c.duration() >= min_delta)
                 .map(|c| c.filter(min_delta))
                 .collect(),
+            #[cfg(feature = "tracing")]
+            span: self.span.clone(),
         }
     }
 
@@ -157,6 +176,26 @@ impl TimingTree {
         );
     }
 
+    /// Exports this scope and its children as JSON, each scope being an object with its `name`,
+    /// its `duration` in seconds, up to now if it is still open, and its `children`.
+    #[cfg(feature = "timing")]
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "duration": self.duration().as_secs_f64(),
+            "children": self.children.iter().map(Self::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    #[cfg(not(feature = "timing"))]
+    pub fn to_json(&self) -> serde_json::Value {
+        log!(
+            self.0,
+            "TimingTree is not supported without the 'timing' feature enabled"
+        );
+        serde_json::Value::Null
+    }
+
     #[cfg(feature = "timing")]
     fn print_helper(&self, depth: usize) {
         let prefix = "| ".repeat(depth);
@@ -173,6 +212,63 @@ impl TimingTree {
     }
 }
 
+/// A `tracing` span mirroring a scope of a [`TimingTree`], so that subscribers can record the
+/// durations of scopes, along with events emitted within them. It is entered on the thread which
+/// pushes the scope, until the scope is popped or, if it never is, dropped.
+#[cfg(feature = "tracing")]
+struct ScopeSpan {
+    span: tracing::Span,
+    entered: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl ScopeSpan {
+    fn new(parent: tracing::Span, name: &str, level: Level) -> Self {
+        let span = match level {
+            Level::Error => tracing::error_span!(parent: parent, "timed", scope = name),
+            Level::Warn => tracing::warn_span!(parent: parent, "timed", scope = name),
+            Level::Info => tracing::info_span!(parent: parent, "timed", scope = name),
+            Level::Debug => tracing::debug_span!(parent: parent, "timed", scope = name),
+            Level::Trace => tracing::trace_span!(parent: parent, "timed", scope = name),
+        };
+        Self {
+            span,
+            entered: false,
+        }
+    }
+
+    fn enter(&mut self) {
+        self.span
+            .with_subscriber(|(id, dispatch)| dispatch.enter(id));
+        self.entered = true;
+    }
+
+    fn exit(&mut self) {
+        if self.entered {
+            self.span
+                .with_subscriber(|(id, dispatch)| dispatch.exit(id));
+            self.entered = false;
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Clone for ScopeSpan {
+    fn clone(&self) -> Self {
+        Self {
+            span: self.span.clone(),
+            entered: false,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for ScopeSpan {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
+
 /// Creates a named scope; useful for debugging.
 #[macro_export]
 macro_rules! timed {
@@ -190,3 +286,81 @@ macro_rules! timed {
         res
     }};
 }
+
+#[cfg(all(test, feature = "timing"))]
+mod tests {
+    use log::Level;
+
+    use super::TimingTree;
+
+    #[test]
+    fn test_to_json() {
+        let mut timing = TimingTree::new("prove", Level::Debug);
+        timed!(timing, "commit", {
+            timed!(timing, "compute LDEs", ());
+        });
+        timed!(timing, "open", ());
+
+        let json = timing.to_json();
+        assert_eq!(json["name"], "prove");
+        let children = json["children"].as_array().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0]["name"], "commit");
+        assert_eq!(children[0]["children"][0]["name"], "compute LDEs");
+        assert_eq!(children[1]["name"], "open");
+        assert!(children[1]["children"].as_array().unwrap().is_empty());
+        assert!(json["duration"].as_f64().unwrap() >= children[0]["duration"].as_f64().unwrap());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the nesting of the spans entered so far.
+        #[derive(Default)]
+        struct SpanStack {
+            next_id: AtomicU64,
+            stack: Mutex<Vec<u64>>,
+            max_depth: AtomicUsize,
+        }
+
+        struct StackSubscriber(Arc<SpanStack>);
+
+        impl Subscriber for StackSubscriber {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(self.0.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, span: &Id) {
+                let mut stack = self.0.stack.lock().unwrap();
+                stack.push(span.into_u64());
+                self.0.max_depth.fetch_max(stack.len(), Ordering::Relaxed);
+            }
+            fn exit(&self, span: &Id) {
+                assert_eq!(self.0.stack.lock().unwrap().pop(), Some(span.into_u64()));
+            }
+        }
+
+        let spans = Arc::new(SpanStack::default());
+        tracing::subscriber::with_default(StackSubscriber(spans.clone()), || {
+            let mut timing = TimingTree::new("prove", Level::Debug);
+            timed!(timing, "commit", {
+                timed!(timing, "compute LDEs", ());
+            });
+            // A scope left by an early return is exited when the tree is dropped.
+            timing.push("open", Level::Debug);
+        });
+        assert!(spans.stack.lock().unwrap().is_empty());
+        assert_eq!(spans.max_depth.load(Ordering::Relaxed), 2);
+    }
+}
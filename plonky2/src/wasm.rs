@@ -0,0 +1,123 @@
+//! A small `wasm-bindgen` façade for verifying Goldilocks/Poseidon proofs in the browser.
+//!
+//! For `wasm32-unknown-unknown`, build with `--no-default-features --features wasm`; without the
+//! `parallel` feature everything runs on the calling thread. Artifacts are passed in the byte
+//! formats produced by [`VerifierCircuitData::to_bytes`] (using the [`DefaultGateSerializer`]),
+//! [`ProofWithPublicInputs::to_bytes`] and [`CompressedProofWithPublicInputs::to_bytes`]. Errors
+//! are returned as strings, which `wasm-bindgen` turns into thrown JS exceptions.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::field::goldilocks_field::GoldilocksField;
+use crate::field::types::PrimeField64;
+use crate::plonk::circuit_data::VerifierCircuitData;
+use crate::plonk::config::PoseidonGoldilocksConfig;
+use crate::plonk::proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs};
+use crate::util::serialization::DefaultGateSerializer;
+
+type F = GoldilocksField;
+type C = PoseidonGoldilocksConfig;
+const D: usize = 2;
+
+/// Verifier data for a single circuit, deserialized once and reused across proofs.
+#[wasm_bindgen]
+pub struct Verifier {
+    data: VerifierCircuitData<F, C, D>,
+}
+
+#[wasm_bindgen]
+impl Verifier {
+    #[wasm_bindgen(constructor)]
+    pub fn new(verifier_circuit_data: &[u8]) -> Result<Verifier, String> {
+        VerifierCircuitData::from_bytes(verifier_circuit_data.to_vec(), &DefaultGateSerializer)
+            .map(|data| Self { data })
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn verify(&self, proof: &[u8]) -> Result<(), String> {
+        let proof = ProofWithPublicInputs::from_bytes(proof.to_vec(), &self.data.common)
+            .map_err(|e| e.to_string())?;
+        self.data.verify(proof).map_err(|e| e.to_string())
+    }
+
+    #[wasm_bindgen(js_name = verifyCompressed)]
+    pub fn verify_compressed(&self, compressed_proof: &[u8]) -> Result<(), String> {
+        let proof = CompressedProofWithPublicInputs::from_bytes(
+            compressed_proof.to_vec(),
+            &self.data.common,
+        )
+        .map_err(|e| e.to_string())?;
+        self.data
+            .verify_compressed(proof)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Returns the public inputs of `proof` as canonical `u64`s. This does not verify the proof.
+    #[wasm_bindgen(js_name = publicInputs)]
+    pub fn public_inputs(&self, proof: &[u8]) -> Result<Vec<u64>, String> {
+        let proof = ProofWithPublicInputs::<F, C, D>::from_bytes(proof.to_vec(), &self.data.common)
+            .map_err(|e| e.to_string())?;
+        Ok(proof
+            .public_inputs
+            .iter()
+            .map(|x| x.to_canonical_u64())
+            .collect())
+    }
+}
+
+/// Verifies a single proof against serialized verifier data.
+#[wasm_bindgen(js_name = verifyProof)]
+pub fn verify_proof(verifier_circuit_data: &[u8], proof: &[u8]) -> Result<(), String> {
+    Verifier::new(verifier_circuit_data)?.verify(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+
+    #[test]
+    fn test_wasm_verifier() -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.exp_u64(x, 7);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        let proof = data.prove(pw)?;
+
+        let verifier_bytes = data
+            .verifier_data()
+            .to_bytes(&DefaultGateSerializer)
+            .map_err(anyhow::Error::msg)?;
+        let proof_bytes = proof.to_bytes();
+        let compressed_bytes = proof
+            .clone()
+            .compress(&data.verifier_only.circuit_digest, &data.common)?
+            .to_bytes();
+
+        let verifier = Verifier::new(&verifier_bytes).map_err(anyhow::Error::msg)?;
+        verifier.verify(&proof_bytes).map_err(anyhow::Error::msg)?;
+        verifier
+            .verify_compressed(&compressed_bytes)
+            .map_err(anyhow::Error::msg)?;
+        assert_eq!(verifier.public_inputs(&proof_bytes), Ok(vec![128]));
+        verify_proof(&verifier_bytes, &proof_bytes).map_err(anyhow::Error::msg)?;
+
+        let mut tampered = proof;
+        tampered.public_inputs[0] = F::ONE;
+        assert!(verifier.verify(&tampered.to_bytes()).is_err());
+        assert!(Verifier::new(&proof_bytes).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+//! Aggregation of STARK proofs, possibly of different Starks, into a single plonky2 proof.
+//!
+//! Each STARK proof is first wrapped into a plonky2 proof by a [`StarkWrapperCircuit`], which
+//! only depends on the Stark, its config and the trace length, so it can be built once and reused
+//! for any number of proofs. An [`AggregationCircuit`] then verifies the wrapped proofs, and
+//! exposes their public inputs, concatenated in order, as its own.
+
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use itertools::Itertools;
+use plonky2::field::extension::Extendable;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData, VerifierCircuitData};
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+
+use crate::config::StarkConfig;
+use crate::proof::{StarkProofWithPublicInputs, StarkProofWithPublicInputsTarget};
+use crate::recursive_verifier::{
+    add_virtual_stark_proof_with_pis, set_stark_proof_with_pis_target, verify_stark_proof_circuit,
+};
+use crate::stark::Stark;
+
+/// A circuit verifying proofs of a Stark for traces of a fixed length. Its public inputs are the
+/// public inputs of the verified proof.
+pub struct StarkWrapperCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub circuit: CircuitData<F, C, D>,
+    proof_target: StarkProofWithPublicInputsTarget<D>,
+    stark_config: StarkConfig,
+    degree_bits: usize,
+}
+
+impl<F, C, const D: usize> StarkWrapperCircuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    /// Builds a circuit verifying proofs of `stark`, generated with `stark_config` for traces of
+    /// length `2^degree_bits`.
+    pub fn new<S: Stark<F, D> + Copy>(
+        stark: S,
+        stark_config: &StarkConfig,
+        degree_bits: usize,
+        circuit_config: CircuitConfig,
+    ) -> Self {
+        let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
+        let proof_target =
+            add_virtual_stark_proof_with_pis(&mut builder, stark, stark_config, degree_bits);
+        builder.register_public_inputs(&proof_target.public_inputs);
+        verify_stark_proof_circuit::<F, C, S, D>(
+            &mut builder,
+            stark,
+            proof_target.clone(),
+            stark_config,
+        );
+        Self {
+            circuit: builder.build::<C>(),
+            proof_target,
+            stark_config: stark_config.clone(),
+            degree_bits,
+        }
+    }
+
+    /// Wraps `proof` into a plonky2 proof.
+    pub fn prove(
+        &self,
+        proof: &StarkProofWithPublicInputs<F, C, D>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let degree_bits = proof.proof.recover_degree_bits(&self.stark_config);
+        ensure!(
+            degree_bits == self.degree_bits,
+            "This wrapper verifies traces of length 2^{}, not 2^{}.",
+            self.degree_bits,
+            degree_bits
+        );
+        let mut pw = PartialWitness::new();
+        set_stark_proof_with_pis_target(&mut pw, &self.proof_target, proof);
+        self.circuit.prove(pw)
+    }
+
+    pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
+        self.circuit.verifier_data()
+    }
+}
+
+/// A circuit verifying one proof of each of several circuits, typically [`StarkWrapperCircuit`]s.
+/// Its public inputs are the concatenation of the public inputs of the verified proofs.
+pub struct AggregationCircuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub circuit: CircuitData<F, C, D>,
+    proof_targets: Vec<ProofWithPublicInputsTarget<D>>,
+}
+
+impl<F, C, const D: usize> AggregationCircuit<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    /// Builds a circuit verifying one proof of each of the `inner` circuits, in order.
+    pub fn new(inner: &[VerifierCircuitData<F, C, D>], circuit_config: CircuitConfig) -> Self {
+        let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
+        let proof_targets = inner
+            .iter()
+            .map(|data| {
+                let proof_target = builder.add_virtual_proof_with_pis(&data.common);
+                let verifier_target = builder.constant_verifier_data(&data.verifier_only);
+                builder.verify_proof::<C>(&proof_target, &verifier_target, &data.common);
+                builder.register_public_inputs(&proof_target.public_inputs);
+                proof_target
+            })
+            .collect();
+        Self {
+            circuit: builder.build::<C>(),
+            proof_targets,
+        }
+    }
+
+    /// Aggregates `proofs`, which must be proofs of the inner circuits, in order.
+    pub fn prove(
+        &self,
+        proofs: &[ProofWithPublicInputs<F, C, D>],
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        ensure!(
+            proofs.len() == self.proof_targets.len(),
+            "Expected {} proofs, got {}.",
+            self.proof_targets.len(),
+            proofs.len()
+        );
+        let mut pw = PartialWitness::new();
+        for (target, proof) in self.proof_targets.iter().zip_eq(proofs) {
+            pw.set_proof_with_pis_target(target, proof);
+        }
+        self.circuit.prove(pw)
+    }
+}
+
+/// Wraps each STARK proof with its wrapper circuit, then aggregates the wrapped proofs into a
+/// single plonky2 proof, whose public inputs are the public inputs of the STARK proofs,
+/// concatenated in order. Also returns the aggregation circuit, needed to verify the proof and
+/// reusable for further proofs of the same wrappers.
+pub fn aggregate_stark_proofs<F, C, const D: usize>(
+    proofs: &[(
+        &StarkWrapperCircuit<F, C, D>,
+        &StarkProofWithPublicInputs<F, C, D>,
+    )],
+    circuit_config: CircuitConfig,
+) -> Result<(AggregationCircuit<F, C, D>, ProofWithPublicInputs<F, C, D>)>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let wrapped_proofs = proofs
+        .iter()
+        .map(|(wrapper, proof)| wrapper.prove(proof))
+        .collect::<Result<Vec<_>>>()?;
+    let inner = proofs
+        .iter()
+        .map(|(wrapper, _)| wrapper.verifier_data())
+        .collect_vec();
+    let aggregation_circuit = AggregationCircuit::new(&inner, circuit_config);
+    let proof = aggregation_circuit.prove(&wrapped_proofs)?;
+    Ok((aggregation_circuit, proof))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use anyhow::Result;
+    use plonky2::field::types::Field;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use crate::aggregation::{aggregate_stark_proofs, StarkWrapperCircuit};
+    use crate::config::StarkConfig;
+    use crate::fibonacci_stark::FibonacciStark;
+    use crate::proof::StarkProofWithPublicInputs;
+    use crate::prover::prove;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type S = FibonacciStark<F, D>;
+
+    fn fibonacci_proof(
+        degree_bits: usize,
+        x1: u64,
+        config: &StarkConfig,
+    ) -> Result<StarkProofWithPublicInputs<F, C, D>> {
+        let stark = S::new(1 << degree_bits);
+        let (x0, x1) = (F::ZERO, F::from_canonical_u64(x1));
+        let trace = stark.generate_trace(x0, x1);
+        let result = *trace[1].values.last().unwrap();
+        prove::<F, C, S, D>(
+            stark,
+            config,
+            trace,
+            &[x0, x1, result],
+            &mut TimingTree::default(),
+        )
+    }
+
+    #[test]
+    fn test_aggregate_stark_proofs() -> Result<()> {
+        let config = StarkConfig::standard_fast_config();
+        let circuit_config = CircuitConfig::standard_recursion_config();
+        let small =
+            StarkWrapperCircuit::<F, C, D>::new(S::new(1 << 5), &config, 5, circuit_config.clone());
+        let large =
+            StarkWrapperCircuit::<F, C, D>::new(S::new(1 << 6), &config, 6, circuit_config.clone());
+
+        let proofs = [
+            fibonacci_proof(5, 1, &config)?,
+            fibonacci_proof(6, 2, &config)?,
+            fibonacci_proof(5, 3, &config)?,
+        ];
+        // A wrapper only accepts proofs of its trace length.
+        assert!(small.prove(&proofs[1]).is_err());
+
+        let (aggregation_circuit, proof) = aggregate_stark_proofs(
+            &[
+                (&small, &proofs[0]),
+                (&large, &proofs[1]),
+                (&small, &proofs[2]),
+            ],
+            circuit_config,
+        )?;
+        let expected_public_inputs = proofs
+            .iter()
+            .flat_map(|proof| proof.public_inputs.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(proof.public_inputs, expected_public_inputs);
+        aggregation_circuit.circuit.verify(proof)
+    }
+}
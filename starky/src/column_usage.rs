@@ -0,0 +1,214 @@
+//! Detection of trace columns which are not, or only partly, constrained.
+//!
+//! After refactoring a table, a column may silently lose its constraints, leaving the prover free
+//! to choose its values. [`column_report`] finds such columns by evaluating the constraints of a
+//! Stark at a random frame, then perturbing each column and observing which constraints change.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use plonky2::field::extension::Extendable;
+use plonky2::hash::hash_types::RichField;
+
+use crate::stark::Stark;
+use crate::stark_testing::eval_recorded_constraints;
+
+/// The trace columns of a Stark which are not fully constrained.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ColumnReport {
+    /// Columns which no constraint, permutation argument, lookup or cross-table lookup refers to.
+    pub unused_columns: Vec<usize>,
+    /// Columns which only appear in constraints restricted to the first or last row, and in no
+    /// argument, so that their values on other rows are free.
+    pub boundary_only_columns: Vec<usize>,
+}
+
+impl ColumnReport {
+    /// Whether every column is constrained on all rows.
+    pub fn is_empty(&self) -> bool {
+        self.unused_columns.is_empty() && self.boundary_only_columns.is_empty()
+    }
+
+    /// Removes `columns` from the report, as they are used by an argument.
+    pub(crate) fn mark_used(&mut self, columns: impl IntoIterator<Item = usize>) {
+        for column in columns {
+            self.unused_columns.retain(|&c| c != column);
+            self.boundary_only_columns.retain(|&c| c != column);
+        }
+    }
+}
+
+/// Reports the trace columns of `stark` which are unused or only constrained on the first or last
+/// row, taking its permutation pairs and lookups into account. Cross-table lookups are taken into
+/// account by [`MultiStark::column_reports`](crate::multi_stark::MultiStark::column_reports).
+///
+/// The analysis is probabilistic, but only misses a dependency if a random perturbation happens to
+/// leave a constraint unchanged, which is negligibly likely for constraints of low degree.
+pub fn column_report<F, S, const D: usize>(stark: &S) -> ColumnReport
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let mut rows = (0..S::FRAME_ROWS)
+        .map(|_| F::rand_vec(S::COLUMNS))
+        .collect::<Vec<_>>();
+    let preprocessed_rows = if S::PREPROCESSED_COLUMNS > 0 {
+        (0..S::FRAME_ROWS)
+            .map(|_| F::rand_vec(S::PREPROCESSED_COLUMNS))
+            .collect()
+    } else {
+        vec![]
+    };
+    let public_inputs = F::rand_vec(S::PUBLIC_INPUTS);
+    let filters = [F::rand(), F::rand(), F::rand(), F::rand()];
+    let eval = |rows: &[Vec<F>], filters: [F; 4]| {
+        eval_recorded_constraints::<F, F, F, S, D, 1>(
+            rows,
+            &preprocessed_rows,
+            &public_inputs,
+            filters,
+            |vars, consumer| stark.eval_packed_base(vars, consumer),
+        )
+    };
+
+    // Boundary constraints are the ones vanishing once the first and last row selectors are zero.
+    let constraints = eval(&rows, filters);
+    let is_boundary = eval(&rows, [filters[0], filters[1], F::ZERO, F::ZERO])
+        .into_iter()
+        .zip(&constraints)
+        .map(|(filtered, &constraint)| filtered.is_zero() && constraint.is_nonzero())
+        .collect::<Vec<_>>();
+
+    let mut used = vec![false; S::COLUMNS];
+    let mut constrained_on_all_rows = vec![false; S::COLUMNS];
+    for row in 0..S::FRAME_ROWS {
+        for column in 0..S::COLUMNS {
+            let value = rows[row][column];
+            rows[row][column] = F::rand();
+            let perturbed = eval(&rows, filters);
+            rows[row][column] = value;
+            for (i, (perturbed, constraint)) in perturbed.into_iter().zip(&constraints).enumerate()
+            {
+                if perturbed != *constraint {
+                    used[column] = true;
+                    constrained_on_all_rows[column] |= !is_boundary[i];
+                }
+            }
+        }
+    }
+
+    let mut report = ColumnReport {
+        unused_columns: (0..S::COLUMNS).filter(|&c| !used[c]).collect(),
+        boundary_only_columns: (0..S::COLUMNS)
+            .filter(|&c| used[c] && !constrained_on_all_rows[c])
+            .collect(),
+    };
+    report.mark_used(
+        stark
+            .permutation_pairs()
+            .into_iter()
+            .flat_map(|pair| pair.column_pairs)
+            .flat_map(|(lhs, rhs)| [lhs, rhs]),
+    );
+    report.mark_used(stark.lookups().into_iter().flat_map(|lookup| {
+        lookup
+            .columns
+            .into_iter()
+            .chain([lookup.table_column, lookup.frequencies_column])
+    }));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use core::marker::PhantomData;
+
+    use plonky2::field::extension::{Extendable, FieldExtension};
+    use plonky2::field::packed::PackedField;
+    use plonky2::hash::hash_types::RichField;
+    use plonky2::iop::ext_target::ExtensionTarget;
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use crate::column_usage::{column_report, ColumnReport};
+    use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+    use crate::evaluation_frame::{StarkEvaluationFrame, StarkFrame};
+    use crate::fibonacci_stark::FibonacciStark;
+    use crate::stark::Stark;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// A counter `i' <- i + 1`, a column `x` only fixed on the first row to the public input, and a
+    /// column `y` left unconstrained.
+    #[derive(Copy, Clone)]
+    struct LooseStark<F: RichField + Extendable<D>, const D: usize> {
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for LooseStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, 3, 1>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget = StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, 3, 1>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            yield_constr.constraint_transition(next_values[0] - local_values[0] - P::ONES);
+            yield_constr.constraint_first_row(local_values[1] - vars.get_public_inputs()[0]);
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            let one = builder.one_extension();
+            let constraint = builder.sub_extension(next_values[0], local_values[0]);
+            let constraint = builder.sub_extension(constraint, one);
+            yield_constr.constraint_transition(builder, constraint);
+            let constraint = builder.sub_extension(local_values[1], vars.get_public_inputs()[0]);
+            yield_constr.constraint_first_row(builder, constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn test_fully_constrained_columns() {
+        // The last two columns are only used by the permutation argument.
+        assert!(column_report(&FibonacciStark::<F, D>::new(8)).is_empty());
+    }
+
+    #[test]
+    fn test_loose_columns() {
+        let stark = LooseStark::<F, D> {
+            _phantom: PhantomData,
+        };
+        assert_eq!(
+            column_report(&stark),
+            ColumnReport {
+                unused_columns: vec![2],
+                boundary_only_columns: vec![1],
+            }
+        );
+    }
+}
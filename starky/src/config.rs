@@ -1,6 +1,17 @@
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension::Extendable;
+use plonky2::field::types::Field;
 use plonky2::fri::reduction_strategies::FriReductionStrategy;
 use plonky2::fri::{FriConfig, FriParams};
+use plonky2::hash::hash_types::RichField;
+use plonky2::util::serialization::{Buffer, IoResult, Read, Write};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StarkConfig {
     pub security_bits: usize,
 
@@ -9,12 +20,19 @@ pub struct StarkConfig {
     pub num_challenges: usize,
 
     pub fri_config: FriConfig,
+
+    /// Whether proofs should hide the trace. If so, Merkle leaves are salted, each committed
+    /// trace or auxiliary polynomial `p` is masked as `p + Z_H r` for a random `r` with
+    /// [`Self::num_mask_coeffs`] coefficients, and quotient chunks are masked similarly. This
+    /// doubles the degree bound of committed polynomials, and requires traces at least as long as
+    /// the masks.
+    pub zero_knowledge: bool,
 }
 
 impl StarkConfig {
-    /// A typical configuration with a rate of 2, resulting in fast but large proofs.
-    /// Targets ~100 bit conjectured security.
-    pub fn standard_fast_config() -> Self {
+    /// A configuration with a rate of 2, resulting in fast but large proofs. Targets ~100 bits of
+    /// conjectured security. Supports constraints of degree up to 3, or 2 in zero-knowledge mode.
+    pub fn fast_prover_100_bits() -> Self {
         Self {
             security_bits: 100,
             num_challenges: 2,
@@ -25,10 +43,233 @@ impl StarkConfig {
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 84,
             },
+            zero_knowledge: false,
+        }
+    }
+
+    /// Same as `fast_prover_100_bits`, but targeting ~128 bits of conjectured security.
+    pub fn fast_prover_128_bits() -> Self {
+        let config = Self::fast_prover_100_bits();
+        Self {
+            security_bits: 128,
+            fri_config: FriConfig {
+                num_query_rounds: 112,
+                ..config.fri_config
+            },
+            ..config
+        }
+    }
+
+    /// A configuration with a rate of 8, resulting in proofs about three times smaller than with
+    /// `fast_prover_100_bits`, at the cost of a four times larger LDE. Targets ~100 bits of
+    /// conjectured security. Supports constraints of degree up to 9, or 8 in zero-knowledge mode.
+    pub fn small_proof_100_bits() -> Self {
+        Self {
+            security_bits: 100,
+            num_challenges: 2,
+            fri_config: FriConfig {
+                rate_bits: 3,
+                cap_height: 4,
+                proof_of_work_bits: 16,
+                reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+                num_query_rounds: 28,
+            },
+            zero_knowledge: false,
+        }
+    }
+
+    /// Same as `small_proof_100_bits`, but targeting ~128 bits of conjectured security.
+    pub fn small_proof_128_bits() -> Self {
+        let config = Self::small_proof_100_bits();
+        Self {
+            security_bits: 128,
+            fri_config: FriConfig {
+                proof_of_work_bits: 20,
+                num_query_rounds: 36,
+                ..config.fri_config
+            },
+            ..config
+        }
+    }
+
+    /// Same as `fast_prover_100_bits`.
+    pub fn standard_fast_config() -> Self {
+        Self::fast_prover_100_bits()
+    }
+
+    /// Same as `standard_fast_config`, but with zero-knowledge enabled. Note that since masking
+    /// doubles the degree bound of committed polynomials, only constraints of degree at most 2 are
+    /// supported at this rate.
+    pub fn standard_fast_zk_config() -> Self {
+        Self {
+            zero_knowledge: true,
+            ..Self::standard_fast_config()
+        }
+    }
+
+    /// The conjectured security of proofs over `F` generated with this config, in bits. It is
+    /// the conjectured security of FRI queries, capped by the size of the extension field from
+    /// which FRI challenges are drawn.
+    pub fn conjectured_security_bits<F: RichField + Extendable<D>, const D: usize>(&self) -> usize {
+        let field_bits = F::Extension::order().bits() as usize;
+        field_bits.min(self.fri_config.conjectured_query_security_bits())
+    }
+
+    /// Checks that this config achieves its targeted `security_bits` over `F`.
+    pub fn check_security<F: RichField + Extendable<D>, const D: usize>(&self) -> Result<()> {
+        let achieved = self.conjectured_security_bits::<F, D>();
+        ensure!(
+            achieved >= self.security_bits,
+            "Config achieves {} bits of conjectured security, short of the targeted {}.",
+            achieved,
+            self.security_bits
+        );
+        Ok(())
+    }
+
+    /// The number of random coefficients of the masks of committed polynomials in zero-knowledge
+    /// mode, for traces of length `2^degree_bits` and a STARK whose constraints span `frame_rows`
+    /// rows. This is the number of base field values revealed about each polynomial, as in
+    /// plonky2's blinding: `D` per opening outside the trace domain, and per FRI query, one value
+    /// of the polynomial plus `D` per folding point and per coefficient of the final polynomial.
+    /// This many random coefficients make all revealed values uniformly random.
+    pub fn num_mask_coeffs<const D: usize>(&self, degree_bits: usize, frame_rows: usize) -> usize {
+        let fri_params = self.fri_params(degree_bits);
+        let num_folding_points = fri_params
+            .reduction_arity_bits
+            .iter()
+            .map(|&arity_bits| (1 << arity_bits) - 1)
+            .sum::<usize>();
+        let fri_openings = self.fri_config.num_query_rounds
+            * (1 + D * num_folding_points + D * fri_params.final_poly_len());
+        D * frame_rows + fri_openings
+    }
+
+    /// Checks that traces of length `2^degree_bits` can be masked in zero-knowledge mode, which
+    /// needs masks of degree below the trace length.
+    pub fn check_zk_degree_bits<const D: usize>(
+        &self,
+        degree_bits: usize,
+        frame_rows: usize,
+    ) -> Result<()> {
+        if !self.zero_knowledge {
+            return Ok(());
         }
+        let num_mask_coeffs = self.num_mask_coeffs::<D>(degree_bits, frame_rows);
+        ensure!(
+            1 << degree_bits >= num_mask_coeffs,
+            "Zero-knowledge proofs of traces of length 2^{} need masks of {} coefficients, more \
+             than the trace length.",
+            degree_bits,
+            num_mask_coeffs
+        );
+        Ok(())
+    }
+
+    /// The smallest `degree_bits` such that traces of length `2^degree_bits` can be masked in
+    /// zero-knowledge mode.
+    pub fn min_zk_degree_bits<const D: usize>(&self, frame_rows: usize) -> usize {
+        (0..)
+            .find(|&degree_bits| {
+                self.check_zk_degree_bits::<D>(degree_bits, frame_rows)
+                    .is_ok()
+            })
+            .unwrap()
+    }
+
+    /// The log of the degree bound of committed polynomials, for a trace of length
+    /// `2^degree_bits`.
+    pub fn committed_degree_bits(&self, degree_bits: usize) -> usize {
+        degree_bits + usize::from(self.zero_knowledge)
+    }
+
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        buffer.write_usize(self.security_bits)?;
+        buffer.write_usize(self.num_challenges)?;
+        buffer.write_fri_config(&self.fri_config)?;
+        buffer.write_bool(self.zero_knowledge)
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer) -> IoResult<Self> {
+        let security_bits = buffer.read_usize()?;
+        let num_challenges = buffer.read_usize()?;
+        let fri_config = buffer.read_fri_config()?;
+        let zero_knowledge = buffer.read_bool()?;
+        Ok(Self {
+            security_bits,
+            num_challenges,
+            fri_config,
+            zero_knowledge,
+        })
     }
 
     pub(crate) fn fri_params(&self, degree_bits: usize) -> FriParams {
-        self.fri_config.fri_params(degree_bits, false)
+        self.fri_config
+            .fri_params(self.committed_degree_bits(degree_bits), self.zero_knowledge)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use crate::config::StarkConfig;
+
+    const D: usize = 2;
+    type F = <PoseidonGoldilocksConfig as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_presets_meet_their_targets() {
+        for (config, bits) in [
+            (StarkConfig::fast_prover_100_bits(), 100),
+            (StarkConfig::fast_prover_128_bits(), 128),
+            (StarkConfig::small_proof_100_bits(), 100),
+            (StarkConfig::small_proof_128_bits(), 128),
+            (StarkConfig::standard_fast_zk_config(), 100),
+        ] {
+            assert_eq!(config.security_bits, bits);
+            assert!(config.conjectured_security_bits::<F, D>() >= bits);
+            config.check_security::<F, D>().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_zk_degree_bits() {
+        let config = StarkConfig::standard_fast_zk_config();
+        // Traces of 2^14 rows are committed with degree 2^15, and FRI folds them with arities
+        // 16, 16 and 16 down to a final polynomial of 8 coefficients.
+        assert_eq!(
+            config.num_mask_coeffs::<D>(14, 2),
+            2 * 2 + 84 * (1 + 2 * 3 * 15 + 2 * 8)
+        );
+        assert!(config.check_zk_degree_bits::<D>(13, 2).is_err());
+        config.check_zk_degree_bits::<D>(14, 2).unwrap();
+        assert_eq!(config.min_zk_degree_bits::<D>(2), 14);
+
+        // Fewer queries need smaller masks.
+        let small_proof_config = StarkConfig {
+            zero_knowledge: true,
+            ..StarkConfig::small_proof_100_bits()
+        };
+        assert_eq!(small_proof_config.min_zk_degree_bits::<D>(2), 12);
+
+        // Without zero-knowledge, traces of any length are fine.
+        StarkConfig::standard_fast_config()
+            .check_zk_degree_bits::<D>(1, 2)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_weakened_config() {
+        let mut config = StarkConfig::fast_prover_100_bits();
+        config.fri_config.num_query_rounds = 20;
+        assert_eq!(config.conjectured_security_bits::<F, D>(), 36);
+        assert!(config.check_security::<F, D>().is_err());
+
+        // Security is capped by the size of the extension field.
+        config.fri_config.num_query_rounds = 1000;
+        config.security_bits = 200;
+        assert_eq!(config.conjectured_security_bits::<F, D>(), 128);
+        assert!(config.check_security::<F, D>().is_err());
     }
 }
@@ -21,6 +21,9 @@ pub struct ConstraintConsumer<P: PackedField> {
     /// The evaluation of `X - g^(n-1)`.
     z_last: P,
 
+    /// The evaluation of `(X - g^(n-1)) ... (X - g^(n-r+1))`, for frames of `r` rows.
+    z_window: P,
+
     /// The evaluation of the Lagrange basis polynomial which is nonzero at the point associated
     /// with the first trace row, and zero at other points in the subgroup.
     lagrange_basis_first: P,
@@ -28,12 +31,16 @@ pub struct ConstraintConsumer<P: PackedField> {
     /// The evaluation of the Lagrange basis polynomial which is nonzero at the point associated
     /// with the last trace row, and zero at other points in the subgroup.
     lagrange_basis_last: P,
+
+    /// The constraints emitted so far, each with its filter, if recording was requested.
+    recorded_constraints: Option<Vec<P>>,
 }
 
 impl<P: PackedField> ConstraintConsumer<P> {
     pub fn new(
         alphas: Vec<P::Scalar>,
         z_last: P,
+        z_window: P,
         lagrange_basis_first: P,
         lagrange_basis_last: P,
     ) -> Self {
@@ -41,11 +48,25 @@ impl<P: PackedField> ConstraintConsumer<P> {
             constraint_accs: vec![P::ZEROS; alphas.len()],
             alphas,
             z_last,
+            z_window,
             lagrange_basis_first,
             lagrange_basis_last,
+            recorded_constraints: None,
         }
     }
 
+    /// Makes the consumer keep every constraint it is given, in order, in addition to combining
+    /// them.
+    pub(crate) fn recording(mut self) -> Self {
+        self.recorded_constraints = Some(Vec::new());
+        self
+    }
+
+    /// The constraints given so far, if recording was requested.
+    pub(crate) fn recorded_constraints(&self) -> Option<&[P]> {
+        self.recorded_constraints.as_deref()
+    }
+
     pub fn accumulators(self) -> Vec<P> {
         self.constraint_accs
     }
@@ -55,12 +76,23 @@ impl<P: PackedField> ConstraintConsumer<P> {
         self.constraint(constraint * self.z_last);
     }
 
+    /// Add one constraint valid on all rows whose whole frame lies within the trace, i.e. all
+    /// rows except the last `r - 1` for frames of `r` rows. Unlike `constraint_transition`, it
+    /// may involve any row of the frame. Since it is filtered by `r - 1` linear factors, a
+    /// constraint of the maximum degree needs `r` to be at most the Stark's constraint degree.
+    pub fn constraint_window(&mut self, constraint: P) {
+        self.constraint(constraint * self.z_window);
+    }
+
     /// Add one constraint on all rows.
     pub fn constraint(&mut self, constraint: P) {
         for (&alpha, acc) in self.alphas.iter().zip(&mut self.constraint_accs) {
             *acc *= alpha;
             *acc += constraint;
         }
+        if let Some(recorded_constraints) = &mut self.recorded_constraints {
+            recorded_constraints.push(constraint);
+        }
     }
 
     /// Add one constraint, but first multiply it by a filter such that it will only apply to the
@@ -86,6 +118,9 @@ pub struct RecursiveConstraintConsumer<F: RichField + Extendable<D>, const D: us
     /// The evaluation of `X - g^(n-1)`.
     z_last: ExtensionTarget<D>,
 
+    /// The evaluation of `(X - g^(n-1)) ... (X - g^(n-r+1))`, for frames of `r` rows.
+    z_window: ExtensionTarget<D>,
+
     /// The evaluation of the Lagrange basis polynomial which is nonzero at the point associated
     /// with the first trace row, and zero at other points in the subgroup.
     lagrange_basis_first: ExtensionTarget<D>,
@@ -102,6 +137,7 @@ impl<F: RichField + Extendable<D>, const D: usize> RecursiveConstraintConsumer<F
         zero: ExtensionTarget<D>,
         alphas: Vec<Target>,
         z_last: ExtensionTarget<D>,
+        z_window: ExtensionTarget<D>,
         lagrange_basis_first: ExtensionTarget<D>,
         lagrange_basis_last: ExtensionTarget<D>,
     ) -> Self {
@@ -109,6 +145,7 @@ impl<F: RichField + Extendable<D>, const D: usize> RecursiveConstraintConsumer<F
             constraint_accs: vec![zero; alphas.len()],
             alphas,
             z_last,
+            z_window,
             lagrange_basis_first,
             lagrange_basis_last,
             _phantom: Default::default(),
@@ -129,6 +166,16 @@ impl<F: RichField + Extendable<D>, const D: usize> RecursiveConstraintConsumer<F
         self.constraint(builder, filtered_constraint);
     }
 
+    /// Add one constraint valid on all rows whose whole frame lies within the trace.
+    pub fn constraint_window(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        constraint: ExtensionTarget<D>,
+    ) {
+        let filtered_constraint = builder.mul_extension(constraint, self.z_window);
+        self.constraint(builder, filtered_constraint);
+    }
+
     /// Add one constraint valid on all rows.
     pub fn constraint(
         &mut self,
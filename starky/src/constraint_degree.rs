@@ -0,0 +1,375 @@
+//! Symbolic checking of the degrees of a Stark's constraints.
+//!
+//! A constraint whose degree exceeds [`Stark::constraint_degree`] yields a quotient polynomial
+//! which does not fit in the committed quotient chunks, so proofs silently fail to verify.
+//! [`check_constraint_degrees`] catches this by evaluating the constraints over [`Degree`]s, which
+//! track degrees of polynomials instead of values, and pinpoints the offending constraint.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::{Product, Sum};
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+use core::slice;
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension::Extendable;
+use plonky2::field::goldilocks_field::GoldilocksField;
+use plonky2::field::packed::PackedField;
+use plonky2::field::types::PrimeField64;
+use plonky2::hash::hash_types::RichField;
+
+use crate::constraint_consumer::ConstraintConsumer;
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::stark::Stark;
+
+/// The symbolic trace length used by [`check_constraint_degrees`]. It only needs to exceed the
+/// degrees of the filters, so that they don't add up to a full trace column.
+const SYMBOLIC_DEGREE_BITS: usize = 16;
+
+/// An upper bound on the degree of a polynomial, used in place of its values to evaluate
+/// constraints symbolically. Sums take the maximum of the degrees, products add them up, and
+/// scalars are constants of degree 0. Cancellations are not detected, so the bound may be loose.
+///
+/// The degree is stored as a canonical element of `F`, so that `Degree<F>` has the layout of `F`
+/// required by [`PackedField`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct Degree<F: PrimeField64>(F);
+
+impl<F: PrimeField64> Degree<F> {
+    /// The degree of constants.
+    const CONSTANT: Self = Self(F::ZERO);
+
+    pub fn new(degree: u64) -> Self {
+        Self(F::from_canonical_u64(degree.min(F::ORDER - 1)))
+    }
+
+    pub fn degree(&self) -> u64 {
+        self.0.to_canonical_u64()
+    }
+
+    fn max(self, other: Self) -> Self {
+        Self::new(self.degree().max(other.degree()))
+    }
+}
+
+impl<F: PrimeField64> From<F> for Degree<F> {
+    fn from(_: F) -> Self {
+        Self::CONSTANT
+    }
+}
+
+impl<F: PrimeField64> Add<Self> for Degree<F> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        self.max(rhs)
+    }
+}
+impl<F: PrimeField64> Add<F> for Degree<F> {
+    type Output = Self;
+    fn add(self, _: F) -> Self {
+        self
+    }
+}
+impl<F: PrimeField64> AddAssign<Self> for Degree<F> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+impl<F: PrimeField64> AddAssign<F> for Degree<F> {
+    fn add_assign(&mut self, rhs: F) {
+        *self = *self + rhs;
+    }
+}
+
+impl<F: PrimeField64> Sub<Self> for Degree<F> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self.max(rhs)
+    }
+}
+impl<F: PrimeField64> Sub<F> for Degree<F> {
+    type Output = Self;
+    fn sub(self, _: F) -> Self {
+        self
+    }
+}
+impl<F: PrimeField64> SubAssign<Self> for Degree<F> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+impl<F: PrimeField64> SubAssign<F> for Degree<F> {
+    fn sub_assign(&mut self, rhs: F) {
+        *self = *self - rhs;
+    }
+}
+
+impl<F: PrimeField64> Mul<Self> for Degree<F> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.degree().saturating_add(rhs.degree()))
+    }
+}
+impl<F: PrimeField64> Mul<F> for Degree<F> {
+    type Output = Self;
+    fn mul(self, _: F) -> Self {
+        self
+    }
+}
+impl<F: PrimeField64> MulAssign<Self> for Degree<F> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+impl<F: PrimeField64> MulAssign<F> for Degree<F> {
+    fn mul_assign(&mut self, rhs: F) {
+        *self = *self * rhs;
+    }
+}
+
+impl<F: PrimeField64> Div<F> for Degree<F> {
+    type Output = Self;
+    fn div(self, _: F) -> Self {
+        self
+    }
+}
+
+impl<F: PrimeField64> Neg for Degree<F> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        self
+    }
+}
+
+impl<F: PrimeField64> Sum for Degree<F> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::CONSTANT, |acc, x| acc + x)
+    }
+}
+
+impl<F: PrimeField64> Product for Degree<F> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::CONSTANT, |acc, x| acc * x)
+    }
+}
+
+/// Implements the operations with a scalar on the left, which coherence rules prevent from
+/// implementing for all fields at once.
+macro_rules! impl_scalar_ops {
+    ($field:ty) => {
+        impl Add<Degree<$field>> for $field {
+            type Output = Degree<$field>;
+            fn add(self, rhs: Degree<$field>) -> Degree<$field> {
+                rhs + self
+            }
+        }
+        impl Sub<Degree<$field>> for $field {
+            type Output = Degree<$field>;
+            fn sub(self, rhs: Degree<$field>) -> Degree<$field> {
+                rhs - self
+            }
+        }
+        impl Mul<Degree<$field>> for $field {
+            type Output = Degree<$field>;
+            fn mul(self, rhs: Degree<$field>) -> Degree<$field> {
+                rhs * self
+            }
+        }
+    };
+}
+
+impl_scalar_ops!(GoldilocksField);
+
+unsafe impl<F: PrimeField64> PackedField for Degree<F>
+where
+    F: Add<Self, Output = Self> + Mul<Self, Output = Self> + Sub<Self, Output = Self>,
+{
+    type Scalar = F;
+
+    const WIDTH: usize = 1;
+    const ZEROS: Self = Self::CONSTANT;
+    const ONES: Self = Self::CONSTANT;
+
+    fn from_slice(slice: &[F]) -> &Self {
+        assert_eq!(slice.len(), 1);
+        unsafe { &*slice.as_ptr().cast::<Self>() }
+    }
+    fn from_slice_mut(slice: &mut [F]) -> &mut Self {
+        assert_eq!(slice.len(), 1);
+        unsafe { &mut *slice.as_mut_ptr().cast::<Self>() }
+    }
+    fn as_slice(&self) -> &[F] {
+        slice::from_ref(&self.0)
+    }
+    fn as_slice_mut(&mut self) -> &mut [F] {
+        slice::from_mut(&mut self.0)
+    }
+
+    fn interleave(&self, other: Self, block_len: usize) -> (Self, Self) {
+        match block_len {
+            1 => (*self, other),
+            _ => panic!("unsupported block length"),
+        }
+    }
+}
+
+/// Checks that none of the constraints of `stark` exceeds its
+/// [`constraint_degree`](Stark::constraint_degree), counting the degree of their filters, and
+/// returns an error naming the first one which does. Constraints are indexed in the order they
+/// are given to the [`ConstraintConsumer`] by [`Stark::eval_packed_generic`]. Permutation and
+/// lookup constraints, which starky generates itself, are not checked.
+pub fn check_constraint_degrees<F, S, const D: usize>(stark: &S) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    Degree<F>: PackedField<Scalar = F>,
+{
+    // Trace polynomials have degree `n - 1`, and so do the Lagrange selectors, while the
+    // transition filters only have one linear factor per excluded row.
+    let n = 1u64 << SYMBOLIC_DEGREE_BITS;
+    let column = Degree::<F>::new(n - 1);
+    let rows = vec![vec![column; S::COLUMNS]; S::FRAME_ROWS];
+    let preprocessed_rows = if S::PREPROCESSED_COLUMNS > 0 {
+        vec![vec![column; S::PREPROCESSED_COLUMNS]; S::FRAME_ROWS]
+    } else {
+        vec![]
+    };
+    let public_inputs = vec![F::ZERO; S::PUBLIC_INPUTS];
+    let vars = S::EvaluationFrame::<F, Degree<F>, 1>::from_values(
+        &rows.iter().map(|row| &row[..]).collect::<Vec<_>>(),
+        &preprocessed_rows
+            .iter()
+            .map(|row| &row[..])
+            .collect::<Vec<_>>(),
+        &public_inputs,
+    );
+
+    let mut consumer = ConstraintConsumer::new(
+        vec![F::ONE],
+        Degree::new(1),
+        Degree::new(S::FRAME_ROWS as u64 - 1),
+        column,
+        column,
+    )
+    .recording();
+    stark.eval_packed_generic(&vars, &mut consumer);
+
+    // A constraint of degree `d` has degree at most `d * n - 1` once filtered.
+    let max_degree = stark.constraint_degree();
+    for (i, constraint) in consumer
+        .recorded_constraints()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+    {
+        let degree = (constraint.degree() / n + 1) as usize;
+        ensure!(
+            degree <= max_degree,
+            "Constraint {} has degree {}, exceeding the constraint degree {} of the Stark.",
+            i,
+            degree,
+            max_degree
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::marker::PhantomData;
+
+    use anyhow::Result;
+    use plonky2::field::extension::{Extendable, FieldExtension};
+    use plonky2::field::packed::PackedField;
+    use plonky2::field::types::Field;
+    use plonky2::hash::hash_types::RichField;
+    use plonky2::iop::ext_target::ExtensionTarget;
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+    use crate::constraint_degree::check_constraint_degrees;
+    use crate::evaluation_frame::{StarkEvaluationFrame, StarkFrame};
+    use crate::fibonacci_stark::FibonacciStark;
+    use crate::stark::Stark;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Iterates `x <- x^3` from `x = 2`, for a given claimed constraint degree.
+    #[derive(Copy, Clone)]
+    struct CubeStark<F: RichField + Extendable<D>, const D: usize> {
+        constraint_degree: usize,
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for CubeStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, 1, 0>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget = StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, 1, 0>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            yield_constr.constraint_first_row(local_values[0] - FE::TWO);
+            yield_constr
+                .constraint_transition(next_values[0] - local_values[0].square() * local_values[0]);
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            let two = builder.constant_extension(F::Extension::TWO);
+            let first_row_constraint = builder.sub_extension(local_values[0], two);
+            yield_constr.constraint_first_row(builder, first_row_constraint);
+            let cube = builder.cube_extension(local_values[0]);
+            let transition_constraint = builder.sub_extension(next_values[0], cube);
+            yield_constr.constraint_transition(builder, transition_constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            self.constraint_degree
+        }
+    }
+
+    fn cube_stark(constraint_degree: usize) -> CubeStark<F, D> {
+        CubeStark {
+            constraint_degree,
+            _phantom: PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_valid_constraint_degrees() -> Result<()> {
+        check_constraint_degrees(&FibonacciStark::<F, D>::new(8))?;
+        check_constraint_degrees(&cube_stark(3))
+    }
+
+    #[test]
+    fn test_excessive_constraint_degree() {
+        let err = check_constraint_degrees(&cube_stark(2)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Constraint 1 has degree 3, exceeding the constraint degree 2 of the Stark."
+        );
+    }
+}
@@ -0,0 +1,449 @@
+//! Cross-table lookups between the STARKs of a [`MultiStark`](crate::multi_stark::MultiStark).
+//!
+//! A cross-table lookup (CTL) checks that the rows selected from some "looking" tables, once
+//! concatenated, are a permutation of the rows selected from a "looked" table. Rows are selected
+//! with an optional binary filter, and each CTL row is a list of linear combinations of the
+//! columns of its table, possibly spanning the current and next rows.
+//!
+//! For each table taking part in a CTL, and each of the `num_challenges` challenges, the prover
+//! commits to a running product `Z(x)` of the combined CTL rows. Partial products are computed
+//! upside down, i.e. the complete product is on the first row, which allows the constraints
+//! - `Z(g^(n-1)) = combine(g^(n-1))`,
+//! - `Z(w) = Z(gw) * combine(w)`,
+//!
+//! where `combine` only reads the local row. The verifier then checks that the product of the
+//! looking tables' `Z(1)` openings equals the looked table's `Z(1)` opening.
+
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::config::GenericConfig;
+
+use crate::config::StarkConfig;
+use crate::constraint_consumer::ConstraintConsumer;
+use crate::permutation::{PermutationChallenge, PermutationChallengeSet};
+use crate::proof::StarkProofWithPublicInputs;
+
+/// A linear combination of columns of the current and next rows, plus a constant.
+#[derive(Clone, Debug)]
+pub struct Column<F: Field> {
+    linear_combination: Vec<(usize, F)>,
+    next_row_linear_combination: Vec<(usize, F)>,
+    constant: F,
+}
+
+impl<F: Field> Column<F> {
+    /// A single column of the current row.
+    pub fn single(c: usize) -> Self {
+        Self {
+            linear_combination: vec![(c, F::ONE)],
+            next_row_linear_combination: vec![],
+            constant: F::ZERO,
+        }
+    }
+
+    /// Multiple single columns of the current row.
+    pub fn singles<I: IntoIterator<Item = impl Borrow<usize>>>(
+        cs: I,
+    ) -> impl Iterator<Item = Self> {
+        cs.into_iter().map(|c| Self::single(*c.borrow()))
+    }
+
+    /// A single column of the next row.
+    pub fn single_next_row(c: usize) -> Self {
+        Self {
+            linear_combination: vec![],
+            next_row_linear_combination: vec![(c, F::ONE)],
+            constant: F::ZERO,
+        }
+    }
+
+    /// A constant, independent of the trace.
+    pub fn constant(constant: F) -> Self {
+        Self {
+            linear_combination: vec![],
+            next_row_linear_combination: vec![],
+            constant,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self::constant(F::ZERO)
+    }
+
+    pub fn one() -> Self {
+        Self::constant(F::ONE)
+    }
+
+    /// A linear combination of columns of the current row, plus a constant.
+    pub fn linear_combination_with_constant<I: IntoIterator<Item = (usize, F)>>(
+        iter: I,
+        constant: F,
+    ) -> Self {
+        let v = iter.into_iter().collect::<Vec<_>>();
+        assert!(!v.is_empty());
+        debug_assert_eq!(
+            v.iter().map(|(c, _)| c).collect::<BTreeSet<_>>().len(),
+            v.len(),
+            "Duplicate columns."
+        );
+        Self {
+            linear_combination: v,
+            next_row_linear_combination: vec![],
+            constant,
+        }
+    }
+
+    /// A linear combination of columns of the current row.
+    pub fn linear_combination<I: IntoIterator<Item = (usize, F)>>(iter: I) -> Self {
+        Self::linear_combination_with_constant(iter, F::ZERO)
+    }
+
+    /// Evaluates the combination on the given current and next rows.
+    /// The indices of the columns this combination refers to, in the current or next row.
+    pub(crate) fn column_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.linear_combination
+            .iter()
+            .chain(&self.next_row_linear_combination)
+            .map(|&(c, _)| c)
+    }
+
+    pub fn eval_with_next<FE, P, const D: usize>(&self, v: &[P], next_v: &[P]) -> P
+    where
+        FE: FieldExtension<D, BaseField = F>,
+        P: PackedField<Scalar = FE>,
+    {
+        self.linear_combination
+            .iter()
+            .map(|&(c, f)| v[c] * FE::from_basefield(f))
+            .sum::<P>()
+            + self
+                .next_row_linear_combination
+                .iter()
+                .map(|&(c, f)| next_v[c] * FE::from_basefield(f))
+                .sum::<P>()
+            + FE::from_basefield(self.constant)
+    }
+
+    /// Evaluates the combination on a row of a table given in column-major form.
+    pub fn eval_table(&self, table: &[PolynomialValues<F>], row: usize) -> F {
+        let mut res = self
+            .linear_combination
+            .iter()
+            .map(|&(c, f)| table[c].values[row] * f)
+            .sum::<F>()
+            + self.constant;
+
+        // The next row of the last row is taken to be zero. Filters of CTLs reading the next row
+        // should be zero on the last row anyway.
+        if !self.next_row_linear_combination.is_empty() && row < table[0].values.len() - 1 {
+            res += self
+                .next_row_linear_combination
+                .iter()
+                .map(|&(c, f)| table[c].values[row + 1] * f)
+                .sum::<F>();
+        }
+
+        res
+    }
+}
+
+/// Linear combinations of the columns of a table, along with a filter selecting the rows taking
+/// part in a CTL. Tables are identified by their index in the [`MultiStark`](crate::multi_stark::MultiStark).
+#[derive(Clone, Debug)]
+pub struct TableWithColumns<F: Field> {
+    pub(crate) table: usize,
+    columns: Vec<Column<F>>,
+    filter_column: Option<Column<F>>,
+}
+
+impl<F: Field> TableWithColumns<F> {
+    /// `filter_column` must evaluate to 0 or 1 on every row; if it is `None`, all rows are
+    /// selected.
+    pub fn new(table: usize, columns: Vec<Column<F>>, filter_column: Option<Column<F>>) -> Self {
+        Self {
+            table,
+            columns,
+            filter_column,
+        }
+    }
+
+    /// The indices of the columns this table's combinations and filter refer to.
+    pub(crate) fn column_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.columns
+            .iter()
+            .chain(&self.filter_column)
+            .flat_map(|column| column.column_indices())
+    }
+
+    /// The degree of the constraints checking this table's `Z` polynomials.
+    pub(crate) fn constraint_degree(&self) -> usize {
+        if self.filter_column.is_some() {
+            3
+        } else {
+            2
+        }
+    }
+}
+
+/// A cross-table lookup: the concatenation of the rows selected in `looking_tables` should be a
+/// permutation of the rows selected in `looked_table`.
+#[derive(Clone, Debug)]
+pub struct CrossTableLookup<F: Field> {
+    pub(crate) looking_tables: Vec<TableWithColumns<F>>,
+    pub(crate) looked_table: TableWithColumns<F>,
+}
+
+impl<F: Field> CrossTableLookup<F> {
+    /// All tables should have the same number of column combinations.
+    pub fn new(
+        looking_tables: Vec<TableWithColumns<F>>,
+        looked_table: TableWithColumns<F>,
+    ) -> Self {
+        assert!(looking_tables
+            .iter()
+            .all(|twc| twc.columns.len() == looked_table.columns.len()));
+        Self {
+            looking_tables,
+            looked_table,
+        }
+    }
+
+    pub(crate) fn tables(&self) -> impl Iterator<Item = &TableWithColumns<F>> {
+        self.looking_tables.iter().chain(Some(&self.looked_table))
+    }
+
+    /// The number of CTL `Z` polynomials of `table`, i.e. the number of times it appears in the
+    /// given CTLs, times `num_challenges`.
+    pub fn num_ctl_zs(ctls: &[Self], table: usize, num_challenges: usize) -> usize {
+        ctls.iter()
+            .flat_map(|ctl| ctl.tables())
+            .filter(|twc| twc.table == table)
+            .count()
+            * num_challenges
+    }
+}
+
+/// Cross-table lookup data of one table.
+#[derive(Clone, Default)]
+pub(crate) struct CtlData<F: Field> {
+    pub(crate) zs_columns: Vec<CtlZData<F>>,
+}
+
+/// Cross-table lookup data associated with one `Z` polynomial.
+#[derive(Clone)]
+pub(crate) struct CtlZData<F: Field> {
+    pub(crate) z: PolynomialValues<F>,
+    pub(crate) challenge: PermutationChallenge<F>,
+    pub(crate) columns: Vec<Column<F>>,
+    pub(crate) filter_column: Option<Column<F>>,
+}
+
+impl<F: Field> CtlData<F> {
+    pub(crate) fn len(&self) -> usize {
+        self.zs_columns.len()
+    }
+
+    pub(crate) fn z_polys(&self) -> Vec<PolynomialValues<F>> {
+        self.zs_columns.iter().map(|zs| zs.z.clone()).collect()
+    }
+}
+
+/// Computes the CTL `Z` polynomials of every table. For each CTL and each challenge, `Z`s are
+/// ordered with the looking tables first, then the looked table.
+pub(crate) fn cross_table_lookup_data<F: RichField>(
+    trace_poly_values: &[Vec<PolynomialValues<F>>],
+    cross_table_lookups: &[CrossTableLookup<F>],
+    ctl_challenges: &PermutationChallengeSet<F>,
+) -> Vec<CtlData<F>> {
+    let mut ctl_data_per_table = vec![CtlData::default(); trace_poly_values.len()];
+    for ctl in cross_table_lookups {
+        for &challenge in &ctl_challenges.challenges {
+            for table in ctl.tables() {
+                let z = partial_products(
+                    &trace_poly_values[table.table],
+                    &table.columns,
+                    &table.filter_column,
+                    challenge,
+                );
+                ctl_data_per_table[table.table].zs_columns.push(CtlZData {
+                    z,
+                    challenge,
+                    columns: table.columns.clone(),
+                    filter_column: table.filter_column.clone(),
+                });
+            }
+        }
+    }
+    ctl_data_per_table
+}
+
+/// Computes the upside-down partial products of the combined rows selected by `filter_column`.
+fn partial_products<F: Field>(
+    trace: &[PolynomialValues<F>],
+    columns: &[Column<F>],
+    filter_column: &Option<Column<F>>,
+    challenge: PermutationChallenge<F>,
+) -> PolynomialValues<F> {
+    let mut partial_prod = F::ONE;
+    let degree = trace[0].len();
+    let mut res = Vec::with_capacity(degree);
+    for i in (0..degree).rev() {
+        let filter = match filter_column {
+            Some(column) => column.eval_table(trace, i),
+            None => F::ONE,
+        };
+        if filter.is_one() {
+            let evals = columns
+                .iter()
+                .map(|c| c.eval_table(trace, i))
+                .collect::<Vec<_>>();
+            partial_prod *= challenge.combine(evals.iter());
+        } else {
+            assert_eq!(filter, F::ZERO, "Non-binary filter?")
+        };
+        res.push(partial_prod);
+    }
+    res.reverse();
+    res.into()
+}
+
+/// Data needed to check one CTL `Z` polynomial of a table.
+#[derive(Clone)]
+pub struct CtlCheckVars<'a, F, FE, P, const D2: usize>
+where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+{
+    pub(crate) local_z: P,
+    pub(crate) next_z: P,
+    pub(crate) challenges: PermutationChallenge<F>,
+    pub(crate) columns: &'a [Column<F>],
+    pub(crate) filter_column: &'a Option<Column<F>>,
+}
+
+impl<'a, F: RichField + Extendable<D>, const D: usize>
+    CtlCheckVars<'a, F, F::Extension, F::Extension, D>
+{
+    /// Extracts the `CtlCheckVars` of each table from the openings of its proof. CTL `Z`s come
+    /// last among the auxiliary polynomials, after the first `num_auxiliary_polys[i]` of table `i`.
+    pub(crate) fn from_proofs<C: GenericConfig<D, F = F>>(
+        proofs: &[StarkProofWithPublicInputs<F, C, D>],
+        cross_table_lookups: &'a [CrossTableLookup<F>],
+        ctl_challenges: &'a PermutationChallengeSet<F>,
+        num_auxiliary_polys: &[usize],
+    ) -> Vec<Vec<Self>> {
+        let mut ctl_zs = proofs
+            .iter()
+            .zip(num_auxiliary_polys)
+            .map(|(p, &num_auxiliary)| {
+                let openings = &p.proof.openings;
+                let ctl_zs = openings
+                    .auxiliary_polys
+                    .iter()
+                    .flatten()
+                    .skip(num_auxiliary);
+                let ctl_zs_next = openings
+                    .auxiliary_polys_next
+                    .iter()
+                    .flatten()
+                    .skip(num_auxiliary);
+                ctl_zs.zip(ctl_zs_next)
+            })
+            .collect::<Vec<_>>();
+
+        let mut ctl_vars_per_table = vec![vec![]; proofs.len()];
+        for ctl in cross_table_lookups {
+            for &challenges in &ctl_challenges.challenges {
+                for table in ctl.tables() {
+                    let (&local_z, &next_z) = ctl_zs[table.table].next().unwrap();
+                    ctl_vars_per_table[table.table].push(Self {
+                        local_z,
+                        next_z,
+                        challenges,
+                        columns: &table.columns,
+                        filter_column: &table.filter_column,
+                    });
+                }
+            }
+        }
+        ctl_vars_per_table
+    }
+}
+
+/// Evaluates the constraints of the CTL `Z` polynomials of a table.
+pub(crate) fn eval_cross_table_lookup_checks<F, FE, P, const D2: usize>(
+    local_values: &[P],
+    next_values: &[P],
+    ctl_vars: &[CtlCheckVars<F, FE, P, D2>],
+    consumer: &mut ConstraintConsumer<P>,
+) where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+{
+    for lookup_vars in ctl_vars {
+        let CtlCheckVars {
+            local_z,
+            next_z,
+            challenges,
+            columns,
+            filter_column,
+        } = lookup_vars;
+
+        let evals = columns
+            .iter()
+            .map(|c| c.eval_with_next(local_values, next_values))
+            .collect::<Vec<_>>();
+        let combined = challenges.combine(evals.iter());
+        let local_filter = match filter_column {
+            Some(column) => column.eval_with_next(local_values, next_values),
+            None => P::ONES,
+        };
+        // The combined row if the filter is 1, and 1 if it is 0.
+        let select = local_filter * combined + P::ONES - local_filter;
+
+        // Check the last partial product `Z(g^(n-1))`.
+        consumer.constraint_last_row(*local_z - select);
+        // Check `Z(w) = Z(gw) * select(w)`.
+        consumer.constraint_transition(*next_z * select - *local_z);
+    }
+}
+
+/// Checks that, for every CTL and challenge, the product of the looking tables' `Z(1)` equals the
+/// looked table's `Z(1)`. `ctl_zs_first[i]` holds the `Z(1)` openings of table `i`, in the order
+/// produced by [`cross_table_lookup_data`].
+pub(crate) fn verify_cross_table_lookups<F: Field>(
+    cross_table_lookups: &[CrossTableLookup<F>],
+    ctl_zs_first: &[Vec<F>],
+    config: &StarkConfig,
+) -> Result<()> {
+    let mut ctl_zs_openings = ctl_zs_first.iter().map(|v| v.iter()).collect::<Vec<_>>();
+    for (index, ctl) in cross_table_lookups.iter().enumerate() {
+        for _ in 0..config.num_challenges {
+            let looking_zs_prod = ctl
+                .looking_tables
+                .iter()
+                .map(|table| *ctl_zs_openings[table.table].next().unwrap())
+                .product::<F>();
+            let looked_z = *ctl_zs_openings[ctl.looked_table.table].next().unwrap();
+            ensure!(
+                looking_zs_prod == looked_z,
+                "Cross-table lookup {} verification failed.",
+                index
+            );
+        }
+    }
+    debug_assert!(ctl_zs_openings.iter_mut().all(|iter| iter.next().is_none()));
+
+    Ok(())
+}
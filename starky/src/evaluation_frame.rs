@@ -1,6 +1,6 @@
 /// A trait for viewing an evaluation frame of a STARK table.
 ///
-/// It allows to access the current and next rows at a given step
+/// It allows to access a window of consecutive rows, starting at a given step,
 /// and can be used to implement constraint evaluation both natively
 /// and recursively.
 pub trait StarkEvaluationFrame<T: Copy + Clone + Default, U: Copy + Clone + Default>:
@@ -9,59 +9,283 @@ pub trait StarkEvaluationFrame<T: Copy + Clone + Default, U: Copy + Clone + Defa
     /// The number of columns for the STARK table this evaluation frame views.
     const COLUMNS: usize;
     const PUBLIC_INPUTS: usize;
+    /// The number of preprocessed columns, i.e. fixed columns which are not part of the witness.
+    const PREPROCESSED_COLUMNS: usize;
+    /// The number of consecutive rows this evaluation frame views, starting with the current one.
+    /// Must be at least 2.
+    const ROWS: usize;
+
+    /// Returns the values of the `i`-th row of this evaluation frame, row 0 being the current row.
+    fn get_row_values(&self, i: usize) -> &[T];
 
     /// Returns the local values (i.e. current row) for this evaluation frame.
-    fn get_local_values(&self) -> &[T];
+    fn get_local_values(&self) -> &[T] {
+        self.get_row_values(0)
+    }
     /// Returns the next values (i.e. next row) for this evaluation frame.
-    fn get_next_values(&self) -> &[T];
+    fn get_next_values(&self) -> &[T] {
+        self.get_row_values(1)
+    }
 
     fn get_public_inputs(&self) -> &[U];
 
-    /// Outputs a new evaluation frame from the provided local and next values.
+    /// Returns the preprocessed values of the `i`-th row of this evaluation frame.
+    fn get_preprocessed_row_values(&self, i: usize) -> &[T];
+
+    /// Returns the preprocessed values of the current row.
+    fn get_local_preprocessed_values(&self) -> &[T] {
+        self.get_preprocessed_row_values(0)
+    }
+    /// Returns the preprocessed values of the next row.
+    fn get_next_preprocessed_values(&self) -> &[T] {
+        self.get_preprocessed_row_values(1)
+    }
+
+    /// Outputs a new evaluation frame from the values of each of its rows, and the preprocessed
+    /// values of each of its rows. The latter may be empty if there are no preprocessed columns.
     ///
     /// **NOTE**: Concrete implementations of this method SHOULD ensure that
-    /// the provided slices lengths match the `Self::COLUMNS` value.
-    fn from_values(lv: &[T], nv: &[T], pis: &[U]) -> Self;
+    /// the provided slices lengths match the `Self::ROWS`, `Self::COLUMNS` and
+    /// `Self::PREPROCESSED_COLUMNS` values.
+    fn from_values(rows: &[&[T]], preprocessed_rows: &[&[T]], pis: &[U]) -> Self;
 }
 
+/// An evaluation frame over `R` consecutive rows of `N` columns, with `N2` public inputs and `N3`
+/// preprocessed columns.
 pub struct StarkFrame<
     T: Copy + Clone + Default,
     U: Copy + Clone + Default,
     const N: usize,
     const N2: usize,
+    const N3: usize = 0,
+    const R: usize = 2,
 > {
-    local_values: [T; N],
-    next_values: [T; N],
+    rows: [[T; N]; R],
     public_inputs: [U; N2],
+    preprocessed_rows: [[T; N3]; R],
 }
 
-impl<T: Copy + Clone + Default, U: Copy + Clone + Default, const N: usize, const N2: usize>
-    StarkEvaluationFrame<T, U> for StarkFrame<T, U, N, N2>
+impl<
+        T: Copy + Clone + Default,
+        U: Copy + Clone + Default,
+        const N: usize,
+        const N2: usize,
+        const N3: usize,
+        const R: usize,
+    > StarkEvaluationFrame<T, U> for StarkFrame<T, U, N, N2, N3, R>
 {
     const COLUMNS: usize = N;
     const PUBLIC_INPUTS: usize = N2;
+    const PREPROCESSED_COLUMNS: usize = N3;
+    const ROWS: usize = R;
 
-    fn get_local_values(&self) -> &[T] {
-        &self.local_values
-    }
-
-    fn get_next_values(&self) -> &[T] {
-        &self.next_values
+    fn get_row_values(&self, i: usize) -> &[T] {
+        &self.rows[i]
     }
 
     fn get_public_inputs(&self) -> &[U] {
         &self.public_inputs
     }
 
-    fn from_values(lv: &[T], nv: &[T], pis: &[U]) -> Self {
-        assert_eq!(lv.len(), Self::COLUMNS);
-        assert_eq!(nv.len(), Self::COLUMNS);
+    fn get_preprocessed_row_values(&self, i: usize) -> &[T] {
+        &self.preprocessed_rows[i]
+    }
+
+    fn from_values(rows: &[&[T]], preprocessed_rows: &[&[T]], pis: &[U]) -> Self {
+        assert!(Self::ROWS >= 2);
+        assert_eq!(rows.len(), Self::ROWS);
+        assert!(rows.iter().all(|row| row.len() == Self::COLUMNS));
+        if Self::PREPROCESSED_COLUMNS > 0 || !preprocessed_rows.is_empty() {
+            assert_eq!(preprocessed_rows.len(), Self::ROWS);
+            assert!(preprocessed_rows
+                .iter()
+                .all(|row| row.len() == Self::PREPROCESSED_COLUMNS));
+        }
         assert_eq!(pis.len(), Self::PUBLIC_INPUTS);
 
         Self {
-            local_values: lv.try_into().unwrap(),
-            next_values: nv.try_into().unwrap(),
+            rows: core::array::from_fn(|i| rows[i].try_into().unwrap()),
             public_inputs: pis.try_into().unwrap(),
+            preprocessed_rows: core::array::from_fn(|i| {
+                preprocessed_rows
+                    .get(i)
+                    .map_or([T::default(); N3], |row| (*row).try_into().unwrap())
+            }),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+
+    use anyhow::Result;
+    use plonky2::field::extension::{Extendable, FieldExtension};
+    use plonky2::field::packed::PackedField;
+    use plonky2::field::polynomial::PolynomialValues;
+    use plonky2::field::types::Field;
+    use plonky2::hash::hash_types::RichField;
+    use plonky2::iop::ext_target::ExtensionTarget;
+    use plonky2::iop::witness::PartialWitness;
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use crate::config::StarkConfig;
+    use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+    use crate::evaluation_frame::{StarkEvaluationFrame, StarkFrame};
+    use crate::prover::prove;
+    use crate::recursive_verifier::{
+        add_virtual_stark_proof_with_pis, set_stark_proof_with_pis_target,
+        verify_stark_proof_circuit,
+    };
+    use crate::stark::Stark;
+    use crate::stark_testing::{test_stark_circuit_constraints, test_stark_low_degree};
+    use crate::verifier::verify_stark_proof;
+
+    const NUM_ROWS: usize = 1 << 14;
+    const FRAME_ROWS: usize = 4;
+
+    /// Computes a Tribonacci sequence in a single column, `x''' <- x + x' + x''`, using frames of
+    /// four rows. The public inputs are the first three values of the sequence and the last one.
+    #[derive(Copy, Clone)]
+    struct TribonacciStark<F: RichField + Extendable<D>, const D: usize> {
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> TribonacciStark<F, D> {
+        fn new() -> Self {
+            Self {
+                _phantom: PhantomData,
+            }
         }
+
+        /// Returns the trace and its public inputs.
+        fn generate_trace(&self, start: [F; 3]) -> (Vec<PolynomialValues<F>>, Vec<F>) {
+            let mut values = start.to_vec();
+            while values.len() < NUM_ROWS {
+                let n = values.len();
+                values.push(values[n - 3] + values[n - 2] + values[n - 1]);
+            }
+            let public_inputs = [&start[..], &[values[NUM_ROWS - 1]]].concat();
+            (vec![PolynomialValues::new(values)], public_inputs)
+        }
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for TribonacciStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, 1, 4, 0, FRAME_ROWS>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget =
+            StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, 1, 4, 0, FRAME_ROWS>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let x = |i| vars.get_row_values(i)[0];
+            let public_inputs = vars.get_public_inputs();
+
+            for (i, &pi) in public_inputs[..3].iter().enumerate() {
+                yield_constr.constraint_first_row(x(i) - pi);
+            }
+            yield_constr.constraint_window(x(3) - x(2) - x(1) - x(0));
+            yield_constr.constraint_last_row(x(0) - public_inputs[3]);
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let x = |i| vars.get_row_values(i)[0];
+            let public_inputs = vars.get_public_inputs();
+
+            for (i, &pi) in public_inputs[..3].iter().enumerate() {
+                let constraint = builder.sub_extension(x(i), pi);
+                yield_constr.constraint_first_row(builder, constraint);
+            }
+            let sum = builder.add_many_extension([x(0), x(1), x(2)]);
+            let constraint = builder.sub_extension(x(3), sum);
+            yield_constr.constraint_window(builder, constraint);
+            let constraint = builder.sub_extension(x(0), public_inputs[3]);
+            yield_constr.constraint_last_row(builder, constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            2
+        }
+    }
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type S = TribonacciStark<F, D>;
+
+    #[test]
+    fn test_wide_frame_stark() -> Result<()> {
+        let stark = S::new();
+        for config in [
+            StarkConfig::standard_fast_config(),
+            StarkConfig::standard_fast_zk_config(),
+        ] {
+            let (trace, public_inputs) = stark.generate_trace([F::ZERO, F::ONE, F::TWO]);
+            let proof = prove::<F, C, S, D>(
+                stark,
+                &config,
+                trace,
+                &public_inputs,
+                &mut TimingTree::default(),
+            )?;
+            verify_stark_proof(stark, proof.clone(), &config)?;
+
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let degree_bits = proof.proof.recover_degree_bits(&config);
+            let pt = add_virtual_stark_proof_with_pis(&mut builder, stark, &config, degree_bits);
+            verify_stark_proof_circuit::<F, C, S, D>(&mut builder, stark, pt.clone(), &config);
+            let data = builder.build::<C>();
+            let mut pw = PartialWitness::new();
+            set_stark_proof_with_pis_target(&mut pw, &pt, &proof);
+            data.verify(data.prove(pw)?)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_wide_frame_stark_wrong_public_inputs() -> Result<()> {
+        let config = StarkConfig::standard_fast_config();
+        let stark = S::new();
+        let (trace, public_inputs) = stark.generate_trace([F::ZERO, F::ONE, F::TWO]);
+        let mut proof = prove::<F, C, S, D>(
+            stark,
+            &config,
+            trace,
+            &public_inputs,
+            &mut TimingTree::default(),
+        )?;
+        proof.public_inputs[3] += F::ONE;
+        assert!(verify_stark_proof(stark, proof, &config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wide_frame_stark_degree() -> Result<()> {
+        test_stark_low_degree(S::new())
+    }
+
+    #[test]
+    fn test_wide_frame_stark_circuit() -> Result<()> {
+        test_stark_circuit_constraints::<F, C, S, D>(S::new())
     }
 }
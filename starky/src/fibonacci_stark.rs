@@ -20,7 +20,7 @@ use crate::util::trace_rows_to_poly_values;
 /// `x0' <- x1, x1' <- x0 + x1, i' <- i+1, j' <- j+1`.
 /// Note: The `i, j` columns are only used to test the permutation argument.
 #[derive(Copy, Clone)]
-struct FibonacciStark<F: RichField + Extendable<D>, const D: usize> {
+pub(crate) struct FibonacciStark<F: RichField + Extendable<D>, const D: usize> {
     num_rows: usize,
     _phantom: PhantomData<F>,
 }
@@ -34,7 +34,7 @@ impl<F: RichField + Extendable<D>, const D: usize> FibonacciStark<F, D> {
     // `num_rows`-th Fibonacci number.
     const PI_INDEX_RES: usize = 2;
 
-    fn new(num_rows: usize) -> Self {
+    pub(crate) fn new(num_rows: usize) -> Self {
         Self {
             num_rows,
             _phantom: PhantomData,
@@ -42,7 +42,7 @@ impl<F: RichField + Extendable<D>, const D: usize> FibonacciStark<F, D> {
     }
 
     /// Generate the trace using `x0, x1, 0, 1` as initial state values.
-    fn generate_trace(&self, x0: F, x1: F) -> Vec<PolynomialValues<F>> {
+    pub(crate) fn generate_trace(&self, x0: F, x1: F) -> Vec<PolynomialValues<F>> {
         let mut trace_rows = (0..self.num_rows)
             .scan([x0, x1, F::ZERO, F::ONE], |acc, _| {
                 let tmp = *acc;
@@ -62,7 +62,8 @@ const COLUMNS: usize = 4;
 const PUBLIC_INPUTS: usize = 3;
 
 impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for FibonacciStark<F, D> {
-    type EvaluationFrame<FE, P, const D2: usize> = StarkFrame<P, P::Scalar, COLUMNS, PUBLIC_INPUTS>
+    type EvaluationFrame<FE, P, const D2: usize>
+        = StarkFrame<P, P::Scalar, COLUMNS, PUBLIC_INPUTS>
     where
         FE: FieldExtension<D2, BaseField = F>,
         P: PackedField<Scalar = FE>;
@@ -134,6 +135,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for FibonacciStar
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
+
     use anyhow::Result;
     use plonky2::field::extension::Extendable;
     use plonky2::field::types::Field;
@@ -142,18 +145,23 @@ mod tests {
     use plonky2::plonk::circuit_builder::CircuitBuilder;
     use plonky2::plonk::circuit_data::CircuitConfig;
     use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::randomness::ProverRandomness;
+    use plonky2::util::serialization::Buffer;
     use plonky2::util::timing::TimingTree;
 
     use crate::config::StarkConfig;
     use crate::fibonacci_stark::FibonacciStark;
-    use crate::proof::StarkProofWithPublicInputs;
-    use crate::prover::prove;
+    use crate::proof::{StarkProofWithPublicInputs, StarkProofWithPublicInputsTarget};
+    use crate::prover::{prove, prove_with_randomness};
     use crate::recursive_verifier::{
-        add_virtual_stark_proof_with_pis, set_stark_proof_with_pis_target,
-        verify_stark_proof_circuit,
+        add_virtual_stark_proof_with_pis, add_virtual_stark_proof_with_variable_degree,
+        set_stark_proof_with_pis_target, set_stark_proof_with_variable_degree_target,
+        verify_stark_proof_circuit, verify_stark_proof_with_variable_degree_circuit,
     };
     use crate::stark::Stark;
-    use crate::stark_testing::{test_stark_circuit_constraints, test_stark_low_degree};
+    use crate::stark_testing::{
+        test_stark_circuit_constraints, test_stark_low_degree, test_stark_trace,
+    };
     use crate::verifier::verify_stark_proof;
 
     fn fibonacci<F: Field>(n: usize, x0: F, x1: F) -> F {
@@ -183,6 +191,185 @@ mod tests {
         verify_stark_proof(stark, proof, &config)
     }
 
+    #[test]
+    fn test_fibonacci_stark_trace() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        let num_rows = 1 << 4;
+        let public_inputs = [F::ZERO, F::ONE, fibonacci(num_rows - 1, F::ZERO, F::ONE)];
+        let stark = S::new(num_rows);
+        let mut trace = stark.generate_trace(public_inputs[0], public_inputs[1]);
+        test_stark_trace::<F, C, S, D>(&stark, &trace, &public_inputs)?;
+
+        // Breaking `x1' <- x0 + x1` between rows 2 and 3, the fifth constraint.
+        trace[1].values[3] += F::ONE;
+        let err = test_stark_trace::<F, C, S, D>(&stark, &trace, &public_inputs).unwrap_err();
+        assert_eq!(err.to_string(), "Constraint 4 is not satisfied on row 2.");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fibonacci_stark_wrong_public_inputs() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        let config = StarkConfig::standard_fast_config();
+        let num_rows = 1 << 5;
+        let public_inputs = [F::ZERO, F::ONE, fibonacci(num_rows - 1, F::ZERO, F::ONE)];
+        let stark = S::new(num_rows);
+        let trace = stark.generate_trace(public_inputs[0], public_inputs[1]);
+        let proof = prove::<F, C, S, D>(
+            stark,
+            &config,
+            trace,
+            &public_inputs,
+            &mut TimingTree::default(),
+        )?;
+
+        let mut tampered = proof.clone();
+        tampered.public_inputs[2] += F::ONE;
+        // The public inputs are part of the transcript, so they change every challenge.
+        let degree_bits = proof.proof.recover_degree_bits(&config);
+        assert_ne!(
+            proof
+                .get_challenges(&stark, None, &config, degree_bits)
+                .stark_alphas,
+            tampered
+                .get_challenges(&stark, None, &config, degree_bits)
+                .stark_alphas
+        );
+        assert!(verify_stark_proof(stark, tampered, &config).is_err());
+
+        verify_stark_proof(stark, proof, &config)
+    }
+
+    #[test]
+    fn test_fibonacci_stark_zk() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        let config = StarkConfig::standard_fast_zk_config();
+        // Masks need as many coefficients as values are revealed about each committed
+        // polynomial, and a degree below the trace length.
+        let num_rows = 1 << 14;
+        assert_eq!(config.min_zk_degree_bits::<D>(S::FRAME_ROWS), 14);
+        let public_inputs = [F::ZERO, F::ONE, fibonacci(num_rows - 1, F::ZERO, F::ONE)];
+        let stark = S::new(num_rows);
+        let prove_once = || {
+            prove::<F, C, S, D>(
+                stark,
+                &config,
+                stark.generate_trace(public_inputs[0], public_inputs[1]),
+                &public_inputs,
+                &mut TimingTree::default(),
+            )
+        };
+        let proof = prove_once()?;
+        assert_eq!(proof.proof.recover_degree_bits(&config), 14);
+
+        // Proofs of the same trace commit to different masked polynomials.
+        assert_ne!(proof.proof.trace_cap, prove_once()?.proof.trace_cap);
+
+        verify_stark_proof(stark, proof.clone(), &config)?;
+        recursive_proof::<F, C, S, C, D>(stark, proof, &config, false)?;
+
+        // Shorter traces can't be masked.
+        let short_stark = S::new(1 << 13);
+        let short_trace = short_stark.generate_trace(F::ZERO, F::ONE);
+        let public_inputs = [F::ZERO, F::ONE, fibonacci((1 << 13) - 1, F::ZERO, F::ONE)];
+        assert!(prove::<F, C, S, D>(
+            short_stark,
+            &config,
+            short_trace,
+            &public_inputs,
+            &mut TimingTree::default(),
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fibonacci_stark_zk_seeded() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        let config = StarkConfig::standard_fast_zk_config();
+        let num_rows = 1 << 14;
+        let public_inputs = [F::ZERO, F::ONE, fibonacci(num_rows - 1, F::ZERO, F::ONE)];
+        let stark = S::new(num_rows);
+        let prove_seeded = |seed| {
+            prove_with_randomness::<F, C, S, D>(
+                stark,
+                &config,
+                stark.generate_trace(public_inputs[0], public_inputs[1]),
+                &public_inputs,
+                ProverRandomness::Seeded(seed),
+                &mut TimingTree::default(),
+            )
+        };
+        let proof = prove_seeded(1)?;
+        assert_eq!(proof.to_bytes(), prove_seeded(1)?.to_bytes());
+        assert_ne!(proof.proof.trace_cap, prove_seeded(2)?.proof.trace_cap);
+        verify_stark_proof(stark, proof, &config)
+    }
+
+    #[test]
+    fn test_fibonacci_stark_serialization() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        let config = StarkConfig::standard_fast_zk_config();
+        let mut config_bytes = Vec::new();
+        config.to_buffer(&mut config_bytes).unwrap();
+        assert_eq!(
+            StarkConfig::from_buffer(&mut Buffer::new(&config_bytes)).unwrap(),
+            config
+        );
+
+        let num_rows = 1 << 14;
+        let public_inputs = [F::ZERO, F::ONE, fibonacci(num_rows - 1, F::ZERO, F::ONE)];
+        let stark = S::new(num_rows);
+        let trace = stark.generate_trace(public_inputs[0], public_inputs[1]);
+        let proof = prove::<F, C, S, D>(
+            stark,
+            &config,
+            trace,
+            &public_inputs,
+            &mut TimingTree::default(),
+        )?;
+
+        let bytes = proof.to_bytes();
+        let decoded = StarkProofWithPublicInputs::<F, C, D>::from_bytes(bytes.clone(), &config)?;
+        assert_eq!(decoded.to_bytes(), bytes);
+
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(StarkProofWithPublicInputs::<F, C, D>::from_bytes(padded, &config).is_err());
+        let truncated = bytes[..bytes.len() - 1].to_vec();
+        assert!(StarkProofWithPublicInputs::<F, C, D>::from_bytes(truncated, &config).is_err());
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&proof)?;
+            let from_json: StarkProofWithPublicInputs<F, C, D> = serde_json::from_str(&json)?;
+            assert_eq!(from_json.to_bytes(), bytes);
+        }
+
+        verify_stark_proof(stark, decoded, &config)
+    }
+
     #[test]
     fn test_fibonacci_stark_degree() -> Result<()> {
         const D: usize = 2;
@@ -232,6 +419,56 @@ mod tests {
         recursive_proof::<F, C, S, C, D>(stark, proof, &config, true)
     }
 
+    #[test]
+    fn test_recursive_stark_verifier_variable_degree() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        // With this config, traces of 2^5 rows have no FRI reductions, while larger ones have one,
+        // with final polynomials of different lengths.
+        let config = StarkConfig::standard_fast_config();
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let stark = S::new(1 << 5);
+        let pt = add_virtual_stark_proof_with_variable_degree(&mut builder, stark, &config, 5..=7);
+        builder.register_public_input(pt.degree_bits);
+        builder.register_public_inputs(&pt.proof_with_pis.public_inputs);
+        verify_stark_proof_with_variable_degree_circuit::<F, C, S, D>(
+            &mut builder,
+            stark,
+            &pt,
+            &config,
+        );
+        let data = builder.build::<C>();
+
+        for degree_bits in [5, 7] {
+            let num_rows = 1 << degree_bits;
+            let public_inputs = [F::ZERO, F::ONE, fibonacci(num_rows - 1, F::ZERO, F::ONE)];
+            let stark = S::new(num_rows);
+            let trace = stark.generate_trace(public_inputs[0], public_inputs[1]);
+            let proof = prove::<F, C, S, D>(
+                stark,
+                &config,
+                trace,
+                &public_inputs,
+                &mut TimingTree::default(),
+            )?;
+
+            let mut pw = PartialWitness::new();
+            set_stark_proof_with_variable_degree_target(&mut pw, &pt, &proof, &config);
+            let recursive_proof = data.prove(pw)?;
+            assert_eq!(
+                recursive_proof.public_inputs[0],
+                F::from_canonical_usize(degree_bits)
+            );
+            assert_eq!(recursive_proof.public_inputs[1..], public_inputs);
+            data.verify(recursive_proof)?;
+        }
+        Ok(())
+    }
+
     fn recursive_proof<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -252,6 +489,10 @@ mod tests {
         let mut pw = PartialWitness::new();
         let degree_bits = inner_proof.proof.recover_degree_bits(inner_config);
         let pt = add_virtual_stark_proof_with_pis(&mut builder, stark, inner_config, degree_bits);
+        let mut pt_bytes = Vec::new();
+        pt.to_buffer(&mut pt_bytes).unwrap();
+        let pt =
+            StarkProofWithPublicInputsTarget::from_buffer(&mut Buffer::new(&pt_bytes)).unwrap();
         set_stark_proof_with_pis_target(&mut pw, &pt, &inner_proof);
 
         verify_stark_proof_circuit::<F, InnerC, S, D>(&mut builder, stark, pt, inner_config);
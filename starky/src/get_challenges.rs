@@ -3,11 +3,12 @@ use alloc::vec::Vec;
 use plonky2::field::extension::Extendable;
 use plonky2::field::polynomial::PolynomialCoeffs;
 use plonky2::fri::proof::{FriProof, FriProofTarget};
+use plonky2::fri::FriParams;
 use plonky2::gadgets::polynomial::PolynomialCoeffsExtTarget;
 use plonky2::hash::hash_types::{MerkleCapTarget, RichField};
 use plonky2::hash::merkle_tree::MerkleCap;
 use plonky2::iop::challenger::{Challenger, RecursiveChallenger};
-use plonky2::iop::target::Target;
+use plonky2::iop::target::{BoolTarget, Target};
 use plonky2::plonk::circuit_builder::CircuitBuilder;
 use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
 
@@ -18,10 +19,12 @@ use crate::permutation::{
 use crate::proof::*;
 use crate::stark::Stark;
 
+/// Computes the challenges of a STARK proof whose public inputs and trace cap have already been
+/// observed by `challenger`.
 fn get_challenges<F, C, S, const D: usize>(
     stark: &S,
-    trace_cap: &MerkleCap<F, C::Hasher>,
-    permutation_zs_cap: Option<&MerkleCap<F, C::Hasher>>,
+    challenger: &mut Challenger<F, C::Hasher>,
+    auxiliary_polys_cap: Option<&MerkleCap<F, C::Hasher>>,
     quotient_polys_cap: &MerkleCap<F, C::Hasher>,
     openings: &StarkOpeningSet<F, D>,
     commit_phase_merkle_caps: &[MerkleCap<F, C::Hasher>],
@@ -37,19 +40,15 @@ where
 {
     let num_challenges = config.num_challenges;
 
-    let mut challenger = Challenger::<F, C::Hasher>::new();
-
-    challenger.observe_cap(trace_cap);
-
-    let permutation_challenge_sets = permutation_zs_cap.map(|permutation_zs_cap| {
-        let tmp = get_n_permutation_challenge_sets(
-            &mut challenger,
-            num_challenges,
-            stark.permutation_batch_size(),
-        );
-        challenger.observe_cap(permutation_zs_cap);
-        tmp
+    let permutation_challenge_sets = stark.uses_permutation_args().then(|| {
+        get_n_permutation_challenge_sets(challenger, num_challenges, stark.permutation_batch_size())
     });
+    let lookup_challenges = stark
+        .uses_lookups()
+        .then(|| challenger.get_n_challenges(num_challenges));
+    if let Some(auxiliary_polys_cap) = auxiliary_polys_cap {
+        challenger.observe_cap(auxiliary_polys_cap);
+    }
 
     let stark_alphas = challenger.get_n_challenges(num_challenges);
 
@@ -60,46 +59,35 @@ where
 
     StarkProofChallenges {
         permutation_challenge_sets,
+        lookup_challenges,
         stark_alphas,
         stark_zeta,
         fri_challenges: challenger.fri_challenges::<C, D>(
             commit_phase_merkle_caps,
             final_poly,
             pow_witness,
-            degree_bits,
+            config.committed_degree_bits(degree_bits),
             &config.fri_config,
         ),
     }
 }
 
-impl<F, C, const D: usize> StarkProofWithPublicInputs<F, C, D>
+impl<F, C, const D: usize> StarkProof<F, C, D>
 where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
 {
-    // TODO: Should be used later in compression?
-    #![allow(dead_code)]
-    pub(crate) fn fri_query_indices<S: Stark<F, D>>(
-        &self,
-        stark: &S,
-        config: &StarkConfig,
-        degree_bits: usize,
-    ) -> Vec<usize> {
-        self.get_challenges(stark, config, degree_bits)
-            .fri_challenges
-            .fri_query_indices
-    }
-
-    /// Computes all Fiat-Shamir challenges used in the STARK proof.
+    /// Computes the Fiat-Shamir challenges following the trace commitment, which `challenger`
+    /// must already have observed.
     pub(crate) fn get_challenges<S: Stark<F, D>>(
         &self,
         stark: &S,
+        challenger: &mut Challenger<F, C::Hasher>,
         config: &StarkConfig,
         degree_bits: usize,
     ) -> StarkProofChallenges<F, D> {
         let StarkProof {
-            trace_cap,
-            permutation_zs_cap,
+            auxiliary_polys_cap,
             quotient_polys_cap,
             openings,
             opening_proof:
@@ -109,12 +97,13 @@ where
                     pow_witness,
                     ..
                 },
-        } = &self.proof;
+            ..
+        } = self;
 
         get_challenges::<F, C, S, D>(
             stark,
-            trace_cap,
-            permutation_zs_cap.as_ref(),
+            challenger,
+            auxiliary_polys_cap.as_ref(),
             quotient_polys_cap,
             openings,
             commit_phase_merkle_caps,
@@ -126,6 +115,46 @@ where
     }
 }
 
+impl<F, C, const D: usize> StarkProofWithPublicInputs<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    // TODO: Should be used later in compression?
+    #![allow(dead_code)]
+    pub(crate) fn fri_query_indices<S: Stark<F, D>>(
+        &self,
+        stark: &S,
+        preprocessed_cap: Option<&MerkleCap<F, C::Hasher>>,
+        config: &StarkConfig,
+        degree_bits: usize,
+    ) -> Vec<usize> {
+        self.get_challenges(stark, preprocessed_cap, config, degree_bits)
+            .fri_challenges
+            .fri_query_indices
+    }
+
+    /// Computes all Fiat-Shamir challenges used in the STARK proof. `preprocessed_cap` is the
+    /// Merkle cap of the preprocessed columns, if the Stark has any.
+    pub(crate) fn get_challenges<S: Stark<F, D>>(
+        &self,
+        stark: &S,
+        preprocessed_cap: Option<&MerkleCap<F, C::Hasher>>,
+        config: &StarkConfig,
+        degree_bits: usize,
+    ) -> StarkProofChallenges<F, D> {
+        let mut challenger = Challenger::<F, C::Hasher>::new();
+        if let Some(preprocessed_cap) = preprocessed_cap {
+            challenger.observe_cap(preprocessed_cap);
+        }
+        challenger.observe_elements(&self.public_inputs);
+        challenger.observe_cap(&self.proof.trace_cap);
+
+        self.proof
+            .get_challenges(stark, &mut challenger, config, degree_bits)
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn get_challenges_target<
     F: RichField + Extendable<D>,
@@ -135,14 +164,17 @@ pub(crate) fn get_challenges_target<
 >(
     builder: &mut CircuitBuilder<F, D>,
     stark: &S,
+    public_inputs: &[Target],
     trace_cap: &MerkleCapTarget,
-    permutation_zs_cap: Option<&MerkleCapTarget>,
+    auxiliary_polys_cap: Option<&MerkleCapTarget>,
     quotient_polys_cap: &MerkleCapTarget,
     openings: &StarkOpeningSetTarget<D>,
     commit_phase_merkle_caps: &[MerkleCapTarget],
     final_poly: &PolynomialCoeffsExtTarget<D>,
     pow_witness: Target,
     config: &StarkConfig,
+    fri_params: &[FriParams],
+    degree_flags: &[BoolTarget],
 ) -> StarkProofChallengesTarget<D>
 where
     C::Hasher: AlgebraicHasher<F>,
@@ -151,18 +183,23 @@ where
 
     let mut challenger = RecursiveChallenger::<F, C::Hasher, D>::new(builder);
 
+    challenger.observe_elements(public_inputs);
     challenger.observe_cap(trace_cap);
 
-    let permutation_challenge_sets = permutation_zs_cap.map(|permutation_zs_cap| {
-        let tmp = get_n_permutation_challenge_sets_target(
+    let permutation_challenge_sets = stark.uses_permutation_args().then(|| {
+        get_n_permutation_challenge_sets_target(
             builder,
             &mut challenger,
             num_challenges,
             stark.permutation_batch_size(),
-        );
-        challenger.observe_cap(permutation_zs_cap);
-        tmp
+        )
     });
+    let lookup_challenges = stark
+        .uses_lookups()
+        .then(|| challenger.get_n_challenges(builder, num_challenges));
+    if let Some(auxiliary_polys_cap) = auxiliary_polys_cap {
+        challenger.observe_cap(auxiliary_polys_cap);
+    }
 
     let stark_alphas = challenger.get_n_challenges(builder, num_challenges);
 
@@ -173,19 +210,23 @@ where
 
     StarkProofChallengesTarget {
         permutation_challenge_sets,
+        lookup_challenges,
         stark_alphas,
         stark_zeta,
-        fri_challenges: challenger.fri_challenges(
+        fri_challenges: challenger.fri_challenges_with_variable_degree(
             builder,
             commit_phase_merkle_caps,
             final_poly,
             pow_witness,
-            &config.fri_config,
+            fri_params,
+            degree_flags,
         ),
     }
 }
 
 impl<const D: usize> StarkProofWithPublicInputsTarget<D> {
+    /// Computes all Fiat-Shamir challenges, for a proof with the FRI parameters `fri_params[i]` for
+    /// the only true `degree_flags[i]`.
     pub(crate) fn get_challenges<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -195,13 +236,15 @@ impl<const D: usize> StarkProofWithPublicInputsTarget<D> {
         builder: &mut CircuitBuilder<F, D>,
         stark: &S,
         config: &StarkConfig,
+        fri_params: &[FriParams],
+        degree_flags: &[BoolTarget],
     ) -> StarkProofChallengesTarget<D>
     where
         C::Hasher: AlgebraicHasher<F>,
     {
         let StarkProofTarget {
             trace_cap,
-            permutation_zs_cap,
+            auxiliary_polys_cap,
             quotient_polys_cap,
             openings,
             opening_proof:
@@ -216,14 +259,17 @@ impl<const D: usize> StarkProofWithPublicInputsTarget<D> {
         get_challenges_target::<F, C, S, D>(
             builder,
             stark,
+            &self.public_inputs,
             trace_cap,
-            permutation_zs_cap.as_ref(),
+            auxiliary_polys_cap.as_ref(),
             quotient_polys_cap,
             openings,
             commit_phase_merkle_caps,
             final_poly,
             *pow_witness,
             config,
+            fri_params,
+            degree_flags,
         )
     }
 }
@@ -6,15 +6,23 @@ extern crate alloc;
 
 mod get_challenges;
 
+pub mod aggregation;
+pub mod column_usage;
 pub mod config;
 pub mod constraint_consumer;
+pub mod constraint_degree;
+pub mod cross_table_lookup;
 pub mod evaluation_frame;
+pub mod lookup;
+pub mod multi_stark;
 pub mod permutation;
+pub mod preprocessed;
 pub mod proof;
 pub mod prover;
 pub mod recursive_verifier;
 pub mod stark;
 pub mod stark_testing;
+pub mod trace;
 pub mod util;
 pub mod vanishing_poly;
 pub mod verifier;
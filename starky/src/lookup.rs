@@ -0,0 +1,451 @@
+//! In-table lookup arguments, using logUp (https://ia.cr/2022/1530).
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use itertools::Itertools;
+use plonky2::field::batch_util::batch_add_inplace;
+use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packed::PackedField;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::{Field, PrimeField64};
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::iop::target::Target;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::util::ceil_div_usize;
+
+use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::stark::Stark;
+
+/// A lookup of the values of some trace columns into a table column of the same trace, e.g. a
+/// column holding every byte for range checks.
+#[derive(Clone, Debug)]
+pub struct Lookup {
+    /// Columns whose values should be contained in the table.
+    /// These are the `f_i(x)` polynomials in the logUp paper.
+    pub columns: Vec<usize>,
+    /// Column containing the table.
+    /// This is the `t(x)` polynomial in the paper.
+    pub table_column: usize,
+    /// Column containing the number of times each row of `table_column` appears in `columns`.
+    /// This is the `m(x)` polynomial in the paper, and can be computed with `lookup_frequencies`.
+    pub frequencies_column: usize,
+}
+
+impl Lookup {
+    /// The number of helper columns needed for each challenge: one for each batch of
+    /// `constraint_degree - 1` looking columns, and the running sum `Z`.
+    pub fn num_helper_columns(&self, constraint_degree: usize) -> usize {
+        ceil_div_usize(self.columns.len(), constraint_degree - 1) + 1
+    }
+}
+
+/// Computes the frequencies column of `lookup`, i.e. the number of times each row of the table
+/// column appears in the looking columns of `trace`. If a value appears several times in the
+/// table, its occurrences are all counted on its first row.
+///
+/// Panics if a looking column has a value which is not in the table.
+pub fn lookup_frequencies<F: PrimeField64>(
+    lookup: &Lookup,
+    trace: &[PolynomialValues<F>],
+) -> PolynomialValues<F> {
+    let table = &trace[lookup.table_column].values;
+    let mut rows = BTreeMap::new();
+    for (i, x) in table.iter().enumerate().rev() {
+        rows.insert(x.to_canonical_u64(), i);
+    }
+
+    let mut frequencies = vec![0u64; table.len()];
+    for &column in &lookup.columns {
+        for x in &trace[column].values {
+            let row = rows
+                .get(&x.to_canonical_u64())
+                .unwrap_or_else(|| panic!("Value {x} in column {column} is not in the table"));
+            frequencies[*row] += 1;
+        }
+    }
+    PolynomialValues::new(frequencies.into_iter().map(F::from_canonical_u64).collect())
+}
+
+/// Computes the helper columns of `lookup` for a single challenge `x`. Given looking columns
+/// `f_0, ..., f_k`, these are `h_i = sum_{j in batch i} 1/(x + f_j)` for each batch of
+/// `constraint_degree - 1` columns, then the running sum `Z` with `Z(1) = 0` and
+/// `Z(gx) = Z(x) + sum_i h_i(x) - m(x)/(x + t(x))`. `Z` wraps around iff the lookup holds.
+pub(crate) fn lookup_helper_columns<F: RichField>(
+    lookup: &Lookup,
+    trace_poly_values: &[PolynomialValues<F>],
+    challenge: F,
+    constraint_degree: usize,
+) -> Vec<PolynomialValues<F>> {
+    let degree = trace_poly_values[0].len();
+    let num_total_logup_entries = degree * lookup.columns.len();
+    // Otherwise the frequencies could wrap around.
+    assert!((num_total_logup_entries as u64) < F::ORDER);
+
+    let inverses_with_challenge = |column: usize| {
+        let shifted = trace_poly_values[column]
+            .values
+            .iter()
+            .map(|&f| challenge + f)
+            .collect_vec();
+        F::batch_multiplicative_inverse(&shifted)
+    };
+
+    let mut helper_columns = lookup
+        .columns
+        .chunks(constraint_degree - 1)
+        .map(|batch| {
+            let mut acc = inverses_with_challenge(batch[0]);
+            for &column in &batch[1..] {
+                batch_add_inplace(&mut acc, &inverses_with_challenge(column));
+            }
+            PolynomialValues::new(acc)
+        })
+        .collect_vec();
+
+    let table_inverses = inverses_with_challenge(lookup.table_column);
+    let frequencies = &trace_poly_values[lookup.frequencies_column].values;
+    let mut z = Vec::with_capacity(degree);
+    z.push(F::ZERO);
+    for i in 0..degree - 1 {
+        let delta = helper_columns.iter().map(|h| h.values[i]).sum::<F>()
+            - frequencies[i] * table_inverses[i];
+        z.push(z[i] + delta);
+    }
+    helper_columns.push(PolynomialValues::new(z));
+
+    helper_columns
+}
+
+pub struct LookupCheckVars<F, FE, P, const D2: usize>
+where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+{
+    pub(crate) local_values: Vec<P>,
+    pub(crate) next_values: Vec<P>,
+    pub(crate) challenges: Vec<F>,
+}
+
+/// Constraints for the logUp lookup arguments of `stark`.
+pub(crate) fn eval_packed_lookups_generic<F, FE, P, S, const D: usize, const D2: usize>(
+    stark: &S,
+    vars: &S::EvaluationFrame<FE, P, D2>,
+    lookup_vars: LookupCheckVars<F, FE, P, D2>,
+    yield_constr: &mut ConstraintConsumer<P>,
+) where
+    F: RichField + Extendable<D>,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+    S: Stark<F, D>,
+{
+    let local_values = vars.get_local_values();
+    let degree = stark.constraint_degree();
+    let mut start = 0;
+    for lookup in stark.lookups() {
+        let num_helper_columns = lookup.num_helper_columns(degree);
+        for &challenge in &lookup_vars.challenges {
+            let challenge = FE::from_basefield(challenge);
+            let helpers = &lookup_vars.local_values[start..start + num_helper_columns - 1];
+
+            // Check that `h_i prod_j (x + f_j) = sum_j prod_{k != j} (x + f_k)` for each batch.
+            for (&h, batch) in helpers.iter().zip(lookup.columns.chunks(degree - 1)) {
+                let shifted = batch
+                    .iter()
+                    .map(|&c| local_values[c] + challenge)
+                    .collect_vec();
+                let numerator = (0..shifted.len())
+                    .map(|j| {
+                        shifted
+                            .iter()
+                            .enumerate()
+                            .filter(|&(k, _)| k != j)
+                            .map(|(_, &s)| s)
+                            .product::<P>()
+                    })
+                    .sum::<P>();
+                yield_constr.constraint(h * shifted.into_iter().product::<P>() - numerator);
+            }
+
+            // Check that `(Z(gx) - Z(x)) (x + t) = (sum_i h_i) (x + t) - m`. This is also checked
+            // on the last row, where it wraps around, so the sum over all rows must be zero.
+            let z = lookup_vars.local_values[start + num_helper_columns - 1];
+            let next_z = lookup_vars.next_values[start + num_helper_columns - 1];
+            let table_with_challenge = local_values[lookup.table_column] + challenge;
+            let y = helpers.iter().copied().sum::<P>() * table_with_challenge
+                - local_values[lookup.frequencies_column];
+            yield_constr.constraint((next_z - z) * table_with_challenge - y);
+
+            start += num_helper_columns;
+        }
+    }
+}
+
+pub struct LookupCheckVarsTarget<const D: usize> {
+    pub(crate) local_values: Vec<ExtensionTarget<D>>,
+    pub(crate) next_values: Vec<ExtensionTarget<D>>,
+    pub(crate) challenges: Vec<Target>,
+}
+
+pub(crate) fn eval_ext_lookups_circuit<
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: &S,
+    vars: &S::EvaluationFrameTarget,
+    lookup_vars: LookupCheckVarsTarget<D>,
+    yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+) {
+    let local_values = vars.get_local_values();
+    let degree = stark.constraint_degree();
+    let mut start = 0;
+    for lookup in stark.lookups() {
+        let num_helper_columns = lookup.num_helper_columns(degree);
+        for &challenge in &lookup_vars.challenges {
+            let challenge = builder.convert_to_ext(challenge);
+            let helpers = &lookup_vars.local_values[start..start + num_helper_columns - 1];
+
+            for (&h, batch) in helpers.iter().zip(lookup.columns.chunks(degree - 1)) {
+                let shifted = batch
+                    .iter()
+                    .map(|&c| builder.add_extension(local_values[c], challenge))
+                    .collect_vec();
+                let numerator_terms = (0..shifted.len())
+                    .map(|j| {
+                        let others = shifted
+                            .iter()
+                            .enumerate()
+                            .filter(|&(k, _)| k != j)
+                            .map(|(_, &s)| s)
+                            .collect_vec();
+                        builder.mul_many_extension(others)
+                    })
+                    .collect_vec();
+                let numerator = builder.add_many_extension(numerator_terms);
+                let product = builder.mul_many_extension(shifted);
+                let constraint = builder.mul_sub_extension(h, product, numerator);
+                yield_constr.constraint(builder, constraint);
+            }
+
+            let z = lookup_vars.local_values[start + num_helper_columns - 1];
+            let next_z = lookup_vars.next_values[start + num_helper_columns - 1];
+            let table_with_challenge =
+                builder.add_extension(local_values[lookup.table_column], challenge);
+            let helpers_sum = builder.add_many_extension(helpers);
+            let y = builder.mul_sub_extension(
+                helpers_sum,
+                table_with_challenge,
+                local_values[lookup.frequencies_column],
+            );
+            let z_diff = builder.sub_extension(next_z, z);
+            let constraint = builder.mul_sub_extension(z_diff, table_with_challenge, y);
+            yield_constr.constraint(builder, constraint);
+
+            start += num_helper_columns;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::marker::PhantomData;
+
+    use anyhow::Result;
+    use plonky2::field::types::Field;
+    use plonky2::iop::witness::PartialWitness;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use super::*;
+    use crate::config::StarkConfig;
+    use crate::evaluation_frame::StarkFrame;
+    use crate::permutation::PermutationPair;
+    use crate::prover::prove;
+    use crate::recursive_verifier::{
+        add_virtual_stark_proof_with_pis, set_stark_proof_with_pis_target,
+        verify_stark_proof_circuit,
+    };
+    use crate::verifier::verify_stark_proof;
+
+    const COLUMNS: usize = 5;
+    const TABLE: usize = 3;
+    const FREQUENCIES: usize = 4;
+    const NUM_ROWS: usize = 1 << 14;
+
+    /// Range-checks columns `0..3` into the byte table in column `TABLE`, padded by repeating its
+    /// last value. Columns 0 and 1 are also permutations of one another, so that lookups are tested
+    /// alongside permutation arguments.
+    #[derive(Copy, Clone)]
+    struct RangeCheckStark<F: RichField + Extendable<D>, const D: usize> {
+        constraint_degree: usize,
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> RangeCheckStark<F, D> {
+        fn new(constraint_degree: usize) -> Self {
+            Self {
+                constraint_degree,
+                _phantom: PhantomData,
+            }
+        }
+
+        fn generate_trace(&self) -> Vec<PolynomialValues<F>> {
+            let looked = |f: fn(usize) -> usize| {
+                PolynomialValues::new(
+                    (0..NUM_ROWS)
+                        .map(|i| F::from_canonical_usize(f(i)))
+                        .collect(),
+                )
+            };
+            let mut trace = vec![
+                looked(|i| (i * 7) % 256),
+                looked(|i| ((NUM_ROWS - 1 - i) * 7) % 256),
+                looked(|i| (i * i) % 256),
+                looked(|i| i.min(u8::MAX as usize)),
+            ];
+            trace.push(PolynomialValues::zero(NUM_ROWS));
+            trace[FREQUENCIES] = lookup_frequencies(&self.lookups()[0], &trace);
+            trace
+        }
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RangeCheckStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, COLUMNS, 0>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget = StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, COLUMNS, 0>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let table = vars.get_local_values()[TABLE];
+            let next_table = vars.get_next_values()[TABLE];
+            yield_constr.constraint_first_row(table);
+            let diff = next_table - table;
+            yield_constr.constraint_transition(diff * (diff - P::ONES));
+            yield_constr.constraint_last_row(table - FE::from_canonical_u8(u8::MAX));
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let table = vars.get_local_values()[TABLE];
+            let next_table = vars.get_next_values()[TABLE];
+            yield_constr.constraint_first_row(builder, table);
+            let diff = builder.sub_extension(next_table, table);
+            let constraint = builder.mul_sub_extension(diff, diff, diff);
+            yield_constr.constraint_transition(builder, constraint);
+            let max = builder.constant_extension(F::Extension::from_canonical_u8(u8::MAX));
+            let constraint = builder.sub_extension(table, max);
+            yield_constr.constraint_last_row(builder, constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            self.constraint_degree
+        }
+
+        fn permutation_pairs(&self) -> Vec<PermutationPair> {
+            vec![PermutationPair::singletons(0, 1)]
+        }
+
+        fn lookups(&self) -> Vec<Lookup> {
+            vec![Lookup {
+                columns: vec![0, 1, 2],
+                table_column: TABLE,
+                frequencies_column: FREQUENCIES,
+            }]
+        }
+    }
+
+    #[test]
+    fn test_lookup_frequencies() {
+        type F = plonky2::field::goldilocks_field::GoldilocksField;
+        let column = |values: &[u64]| {
+            PolynomialValues::new(values.iter().map(|&x| F::from_canonical_u64(x)).collect())
+        };
+        let trace = vec![
+            column(&[1, 1, 3, 0]),
+            column(&[0, 1, 2, 3]),
+            column(&[0; 4]),
+        ];
+        let lookup = Lookup {
+            columns: vec![0],
+            table_column: 1,
+            frequencies_column: 2,
+        };
+        assert_eq!(lookup_frequencies(&lookup, &trace), column(&[1, 2, 0, 1]));
+    }
+
+    #[test]
+    fn test_range_check_stark() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = RangeCheckStark<F, D>;
+
+        // Degree 2 uses one helper column per looking column, degree 3 batches them in pairs.
+        for (stark, config) in [
+            (S::new(2), StarkConfig::standard_fast_zk_config()),
+            (S::new(3), StarkConfig::standard_fast_config()),
+        ] {
+            let proof = prove::<F, C, S, D>(
+                stark,
+                &config,
+                stark.generate_trace(),
+                &[],
+                &mut TimingTree::default(),
+            )?;
+            verify_stark_proof(stark, proof.clone(), &config)?;
+
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let degree_bits = proof.proof.recover_degree_bits(&config);
+            let pt = add_virtual_stark_proof_with_pis(&mut builder, stark, &config, degree_bits);
+            verify_stark_proof_circuit::<F, C, S, D>(&mut builder, stark, pt.clone(), &config);
+            let data = builder.build::<C>();
+            let mut pw = PartialWitness::new();
+            set_stark_proof_with_pis_target(&mut pw, &pt, &proof);
+            data.verify(data.prove(pw)?)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lookup_helper_columns_wrong_frequencies() {
+        type F = plonky2::field::goldilocks_field::GoldilocksField;
+        let stark = RangeCheckStark::<F, 2>::new(3);
+        let mut trace = stark.generate_trace();
+        let lookup = &stark.lookups()[0];
+        let challenge = F::from_canonical_u64(0x1234_5678_9abc);
+        let check_wraps_around = |trace: &[PolynomialValues<F>]| {
+            let helper_columns = lookup_helper_columns(lookup, trace, challenge, 3);
+            let (z, helpers) = helper_columns.split_last().unwrap();
+            let last = NUM_ROWS - 1;
+            let delta = helpers.iter().map(|h| h.values[last]).sum::<F>()
+                - trace[FREQUENCIES].values[last] / (challenge + trace[TABLE].values[last]);
+            z.values[last] + delta == F::ZERO
+        };
+
+        assert!(check_wraps_around(&trace));
+        trace[FREQUENCIES].values[0] += F::ONE;
+        assert!(!check_wraps_around(&trace));
+    }
+}
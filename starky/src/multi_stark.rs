@@ -0,0 +1,666 @@
+//! Proving and verifying several STARKs together, connected by cross-table lookups.
+//!
+//! Tables are registered in a [`MultiStark`] and identified by their registration index. All
+//! tables share one challenger. It first observes the public inputs and trace cap of each table,
+//! in registration order, then draws the cross-table lookup challenges. The rest of each table's
+//! single-STARK transcript (permutation and lookup challenges, auxiliary polynomials cap, alphas,
+//! quotient cap, zeta, openings and FRI) follows, again in registration order.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use itertools::{izip, Itertools};
+use plonky2::field::extension::Extendable;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::challenger::Challenger;
+use plonky2::plonk::config::GenericConfig;
+use plonky2::timed;
+use plonky2::util::log2_strict;
+use plonky2::util::randomness::ProverRandomness;
+use plonky2::util::timing::TimingTree;
+
+use crate::column_usage::{column_report, ColumnReport};
+use crate::config::StarkConfig;
+use crate::cross_table_lookup::{
+    cross_table_lookup_data, verify_cross_table_lookups, CrossTableLookup, CtlCheckVars, CtlData,
+};
+use crate::permutation::get_permutation_challenge_set;
+use crate::proof::{MultiStarkProof, StarkProofWithPublicInputs};
+use crate::prover::{commit_values, prove_with_commitment, TRACE_RANDOMNESS};
+use crate::stark::Stark;
+use crate::verifier::{validate_proof_shape, verify_stark_proof_with_challenges};
+
+/// A set of STARKs proven together, along with the cross-table lookups between them.
+pub struct MultiStark<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    starks: Vec<Box<dyn MultiStarkTable<F, C, D>>>,
+    cross_table_lookups: Vec<CrossTableLookup<F>>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> Default
+    for MultiStark<F, C, D>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> MultiStark<F, C, D> {
+    pub fn new() -> Self {
+        Self {
+            starks: Vec::new(),
+            cross_table_lookups: Vec::new(),
+        }
+    }
+
+    /// Registers a STARK, returning its table index. Tables with preprocessed columns aren't
+    /// supported yet.
+    pub fn add_stark<S: Stark<F, D> + 'static>(&mut self, stark: S) -> usize {
+        assert!(
+            !stark.uses_preprocessed_columns(),
+            "Preprocessed columns are not supported in multi-STARK proofs."
+        );
+        self.starks.push(Box::new(stark));
+        self.starks.len() - 1
+    }
+
+    /// Adds a cross-table lookup. The tables it refers to must already be registered, and have a
+    /// constraint degree of at least 3 if they use a filter, or 2 otherwise.
+    pub fn add_cross_table_lookup(&mut self, ctl: CrossTableLookup<F>) {
+        for table in ctl.tables() {
+            let stark = self
+                .starks
+                .get(table.table)
+                .expect("Cross-table lookup refers to an unregistered table.");
+            assert!(
+                stark.constraint_degree() >= table.constraint_degree(),
+                "Constraint degree of table {} is too low for its cross-table lookup.",
+                table.table
+            );
+        }
+        self.cross_table_lookups.push(ctl);
+    }
+
+    pub fn num_tables(&self) -> usize {
+        self.starks.len()
+    }
+
+    pub fn cross_table_lookups(&self) -> &[CrossTableLookup<F>] {
+        &self.cross_table_lookups
+    }
+
+    /// Reports the columns of each table which are unused or only constrained on the first or
+    /// last row, see [`column_report`]. Columns used by cross-table lookups count as used.
+    pub fn column_reports(&self) -> Vec<ColumnReport> {
+        let mut reports = self
+            .starks
+            .iter()
+            .map(|stark| stark.column_report())
+            .collect_vec();
+        for table in self.cross_table_lookups.iter().flat_map(|ctl| ctl.tables()) {
+            reports[table.table].mark_used(table.column_indices());
+        }
+        reports
+    }
+}
+
+/// Object-safe view of a [`Stark`], so that tables of different types can be stored together.
+trait MultiStarkTable<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    fn constraint_degree(&self) -> usize;
+
+    /// The number of rows spanned by constraints, see [`Stark::FRAME_ROWS`].
+    fn frame_rows(&self) -> usize;
+
+    /// The number of auxiliary polynomials before the cross-table lookup `Z`s.
+    fn num_auxiliary_polys(&self, config: &StarkConfig) -> usize;
+
+    fn column_report(&self) -> ColumnReport;
+
+    fn prove(
+        &self,
+        config: &StarkConfig,
+        trace_poly_values: &[PolynomialValues<F>],
+        trace_commitment: &PolynomialBatch<F, C, D>,
+        ctl_data: &CtlData<F>,
+        public_inputs: &[F],
+        challenger: &mut Challenger<F, C::Hasher>,
+        randomness: ProverRandomness,
+        timing: &mut TimingTree,
+    ) -> Result<StarkProofWithPublicInputs<F, C, D>>;
+
+    fn validate_proof_shape(
+        &self,
+        proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+        num_ctl_zs: usize,
+        config: &StarkConfig,
+    ) -> Result<()>;
+
+    fn verify(
+        &self,
+        config: &StarkConfig,
+        proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+        ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
+        challenger: &mut Challenger<F, C::Hasher>,
+    ) -> Result<()>;
+}
+
+impl<F, C, S, const D: usize> MultiStarkTable<F, C, D> for S
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    fn constraint_degree(&self) -> usize {
+        Stark::constraint_degree(self)
+    }
+
+    fn frame_rows(&self) -> usize {
+        S::FRAME_ROWS
+    }
+
+    fn num_auxiliary_polys(&self, config: &StarkConfig) -> usize {
+        Stark::num_auxiliary_polys(self, config)
+    }
+
+    fn column_report(&self) -> ColumnReport {
+        column_report(self)
+    }
+
+    fn prove(
+        &self,
+        config: &StarkConfig,
+        trace_poly_values: &[PolynomialValues<F>],
+        trace_commitment: &PolynomialBatch<F, C, D>,
+        ctl_data: &CtlData<F>,
+        public_inputs: &[F],
+        challenger: &mut Challenger<F, C::Hasher>,
+        randomness: ProverRandomness,
+        timing: &mut TimingTree,
+    ) -> Result<StarkProofWithPublicInputs<F, C, D>> {
+        prove_with_commitment(
+            self,
+            config,
+            trace_poly_values,
+            trace_commitment,
+            None,
+            ctl_data,
+            public_inputs,
+            challenger,
+            randomness,
+            timing,
+        )
+    }
+
+    fn validate_proof_shape(
+        &self,
+        proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+        num_ctl_zs: usize,
+        config: &StarkConfig,
+    ) -> Result<()> {
+        validate_proof_shape(self, proof_with_pis, num_ctl_zs, config)
+    }
+
+    fn verify(
+        &self,
+        config: &StarkConfig,
+        proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+        ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
+        challenger: &mut Challenger<F, C::Hasher>,
+    ) -> Result<()> {
+        let degree_bits = proof_with_pis.proof.recover_degree_bits(config);
+        let challenges = proof_with_pis
+            .proof
+            .get_challenges(self, challenger, config, degree_bits);
+        verify_stark_proof_with_challenges(
+            self,
+            proof_with_pis,
+            challenges,
+            None,
+            ctl_vars,
+            degree_bits,
+            config,
+        )
+    }
+}
+
+/// Proves all tables of `multi_stark`, given the trace and public inputs of each table.
+pub fn prove_all<F, C, const D: usize>(
+    multi_stark: &MultiStark<F, C, D>,
+    config: &StarkConfig,
+    trace_poly_values: Vec<Vec<PolynomialValues<F>>>,
+    public_inputs: &[Vec<F>],
+    timing: &mut TimingTree,
+) -> Result<MultiStarkProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    prove_all_with_randomness(
+        multi_stark,
+        config,
+        trace_poly_values,
+        public_inputs,
+        ProverRandomness::Os,
+        timing,
+    )
+}
+
+/// Like [`prove_all`], but draws the masks and salts of zero-knowledge proofs from `randomness`,
+/// each table using the randomness derived from its index.
+pub fn prove_all_with_randomness<F, C, const D: usize>(
+    multi_stark: &MultiStark<F, C, D>,
+    config: &StarkConfig,
+    trace_poly_values: Vec<Vec<PolynomialValues<F>>>,
+    public_inputs: &[Vec<F>],
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> Result<MultiStarkProof<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let num_tables = multi_stark.num_tables();
+    ensure!(
+        trace_poly_values.len() == num_tables,
+        "Wrong number of traces."
+    );
+    ensure!(
+        public_inputs.len() == num_tables,
+        "Wrong number of public input vectors."
+    );
+
+    for (stark, trace) in multi_stark.starks.iter().zip(&trace_poly_values) {
+        config.check_zk_degree_bits::<D>(log2_strict(trace[0].len()), stark.frame_rows())?;
+    }
+
+    let trace_commitments = timed!(
+        timing,
+        "compute all trace commitments",
+        izip!(0.., &multi_stark.starks, &trace_poly_values)
+            .map(|(i, stark, trace)| {
+                let randomness = randomness.derive(i).derive(TRACE_RANDOMNESS);
+                commit_values::<F, C, D>(
+                    trace.clone(),
+                    config,
+                    stark.frame_rows(),
+                    randomness,
+                    timing,
+                )
+            })
+            .collect::<Vec<_>>()
+    );
+
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    for (public_inputs, trace_commitment) in public_inputs.iter().zip(&trace_commitments) {
+        challenger.observe_elements(public_inputs);
+        challenger.observe_cap(&trace_commitment.merkle_tree.cap);
+    }
+
+    let ctl_challenges = get_permutation_challenge_set(&mut challenger, config.num_challenges);
+    let ctl_data_per_table = timed!(
+        timing,
+        "compute cross-table lookup Z polynomials",
+        cross_table_lookup_data(
+            &trace_poly_values,
+            &multi_stark.cross_table_lookups,
+            &ctl_challenges,
+        )
+    );
+
+    let stark_proofs = izip!(
+        0..,
+        &multi_stark.starks,
+        &trace_poly_values,
+        &trace_commitments,
+        &ctl_data_per_table,
+        public_inputs
+    )
+    .map(
+        |(i, stark, trace_poly_values, trace_commitment, ctl_data, public_inputs)| {
+            stark.prove(
+                config,
+                trace_poly_values,
+                trace_commitment,
+                ctl_data,
+                public_inputs,
+                &mut challenger,
+                randomness.derive(i),
+                timing,
+            )
+        },
+    )
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok(MultiStarkProof { stark_proofs })
+}
+
+/// Verifies the proofs of all tables of `multi_stark`, and the cross-table lookups between them.
+pub fn verify_all<F, C, const D: usize>(
+    multi_stark: &MultiStark<F, C, D>,
+    proof: MultiStarkProof<F, C, D>,
+    config: &StarkConfig,
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let MultiStark {
+        starks,
+        cross_table_lookups,
+    } = multi_stark;
+    let stark_proofs = &proof.stark_proofs;
+    ensure!(
+        stark_proofs.len() == starks.len(),
+        "Wrong number of STARK proofs."
+    );
+
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    for (table, (stark, proof_with_pis)) in starks.iter().zip(stark_proofs).enumerate() {
+        let num_ctl_zs =
+            CrossTableLookup::num_ctl_zs(cross_table_lookups, table, config.num_challenges);
+        stark.validate_proof_shape(proof_with_pis, num_ctl_zs, config)?;
+        challenger.observe_elements(&proof_with_pis.public_inputs);
+        challenger.observe_cap(&proof_with_pis.proof.trace_cap);
+    }
+
+    let ctl_challenges = get_permutation_challenge_set(&mut challenger, config.num_challenges);
+    let num_auxiliary_polys = starks
+        .iter()
+        .map(|stark| stark.num_auxiliary_polys(config))
+        .collect_vec();
+    let ctl_vars_per_table = CtlCheckVars::from_proofs(
+        stark_proofs,
+        cross_table_lookups,
+        &ctl_challenges,
+        &num_auxiliary_polys,
+    );
+
+    for (stark, proof_with_pis, ctl_vars) in izip!(starks, stark_proofs, &ctl_vars_per_table) {
+        stark.verify(config, proof_with_pis, ctl_vars, &mut challenger)?;
+    }
+
+    let ctl_zs_first = stark_proofs
+        .iter()
+        .map(|p| p.proof.openings.ctl_zs_first.clone().unwrap_or_default())
+        .collect_vec();
+    verify_cross_table_lookups(cross_table_lookups, &ctl_zs_first, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+
+    use anyhow::Result;
+    use plonky2::field::extension::{Extendable, FieldExtension};
+    use plonky2::field::packed::PackedField;
+    use plonky2::field::polynomial::PolynomialValues;
+    use plonky2::field::types::Field;
+    use plonky2::hash::hash_types::RichField;
+    use plonky2::iop::ext_target::ExtensionTarget;
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use crate::column_usage::{column_report, ColumnReport};
+    use crate::config::StarkConfig;
+    use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+    use crate::cross_table_lookup::{Column, CrossTableLookup, TableWithColumns};
+    use crate::evaluation_frame::{StarkEvaluationFrame, StarkFrame};
+    use crate::multi_stark::{prove_all, verify_all, MultiStark};
+    use crate::proof::MultiStarkProof;
+    use crate::stark::Stark;
+    use crate::trace::TraceBuilder;
+    use crate::util::trace_rows_to_poly_values;
+
+    const NUM_SQUARES: usize = 1 << 14;
+
+    /// Lists the pairs `(x, x^2)` for `x` in `0..NUM_SQUARES`.
+    #[derive(Copy, Clone)]
+    struct SquaresStark<F: RichField + Extendable<D>, const D: usize> {
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> SquaresStark<F, D> {
+        const X: usize = 0;
+        const X_SQUARED: usize = 1;
+
+        fn generate_trace() -> Vec<PolynomialValues<F>> {
+            trace_rows_to_poly_values(
+                (0..NUM_SQUARES as u64)
+                    .map(|x| [F::from_canonical_u64(x), F::from_canonical_u64(x * x)])
+                    .collect(),
+            )
+        }
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for SquaresStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, 2, 0>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget = StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, 2, 0>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            let x = local_values[Self::X];
+            yield_constr.constraint_first_row(x);
+            yield_constr.constraint_transition(next_values[Self::X] - x - P::ONES);
+            yield_constr.constraint(local_values[Self::X_SQUARED] - x * x);
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            let x = local_values[Self::X];
+            yield_constr.constraint_first_row(builder, x);
+            let one = builder.one_extension();
+            let constraint = builder.sub_extension(next_values[Self::X], x);
+            let constraint = builder.sub_extension(constraint, one);
+            yield_constr.constraint_transition(builder, constraint);
+            let constraint = builder.mul_sub_extension(x, x, local_values[Self::X_SQUARED]);
+            yield_constr.constraint(builder, constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            2
+        }
+    }
+
+    /// Sums the `b` column over the rows where `filter` is set, exposing the result as a public
+    /// input. That `b = a^2` is only enforced by looking the filtered `(a, b)` rows up in
+    /// `SquaresStark`.
+    #[derive(Copy, Clone)]
+    struct SumOfSquaresStark<F: RichField + Extendable<D>, const D: usize> {
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> SumOfSquaresStark<F, D> {
+        const A: usize = 0;
+        const B: usize = 1;
+        const ACC: usize = 2;
+        const FILTER: usize = 3;
+
+        /// Looks up every square once, in reverse order, followed by as many unfiltered rows.
+        fn generate_trace() -> (Vec<PolynomialValues<F>>, F) {
+            let mut builder = TraceBuilder::new().with_min_rows(2 * NUM_SQUARES);
+            let mut acc = F::ZERO;
+            for a in (0..NUM_SQUARES).rev() {
+                let a = F::from_canonical_usize(a);
+                builder.push([a, a.square(), acc, F::ONE]);
+                acc += a.square();
+            }
+            let padding_row = [F::ZERO, F::from_canonical_u64(7), acc, F::ZERO];
+            (builder.build(padding_row), acc)
+        }
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for SumOfSquaresStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, 4, 1>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget = StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, 4, 1>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            let sum = vars.get_public_inputs()[0];
+            let filter = local_values[Self::FILTER];
+            let acc = local_values[Self::ACC];
+            let new_acc = acc + filter * local_values[Self::B];
+            yield_constr.constraint(filter * (filter - P::ONES));
+            yield_constr.constraint_first_row(acc);
+            yield_constr.constraint_transition(next_values[Self::ACC] - new_acc);
+            yield_constr.constraint_last_row(new_acc - sum);
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let local_values = vars.get_local_values();
+            let next_values = vars.get_next_values();
+            let sum = vars.get_public_inputs()[0];
+            let filter = local_values[Self::FILTER];
+            let acc = local_values[Self::ACC];
+            let new_acc = builder.mul_add_extension(filter, local_values[Self::B], acc);
+            let constraint = builder.mul_sub_extension(filter, filter, filter);
+            yield_constr.constraint(builder, constraint);
+            yield_constr.constraint_first_row(builder, acc);
+            let constraint = builder.sub_extension(next_values[Self::ACC], new_acc);
+            yield_constr.constraint_transition(builder, constraint);
+            let constraint = builder.sub_extension(new_acc, sum);
+            yield_constr.constraint_last_row(builder, constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            // The filtered cross-table lookup has degree 3.
+            3
+        }
+    }
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type Squares = SquaresStark<F, D>;
+    type SumOfSquares = SumOfSquaresStark<F, D>;
+
+    fn multi_stark() -> MultiStark<F, C, D> {
+        let mut multi_stark = MultiStark::new();
+        let squares = multi_stark.add_stark(Squares {
+            _phantom: PhantomData,
+        });
+        let sum_of_squares = multi_stark.add_stark(SumOfSquares {
+            _phantom: PhantomData,
+        });
+        multi_stark.add_cross_table_lookup(CrossTableLookup::new(
+            vec![TableWithColumns::new(
+                sum_of_squares,
+                Column::singles([SumOfSquares::A, SumOfSquares::B]).collect(),
+                Some(Column::single(SumOfSquares::FILTER)),
+            )],
+            TableWithColumns::new(
+                squares,
+                Column::singles([Squares::X, Squares::X_SQUARED]).collect(),
+                None,
+            ),
+        ));
+        multi_stark
+    }
+
+    #[test]
+    fn test_multi_stark() -> Result<()> {
+        let multi_stark = multi_stark();
+        let (sum_trace, sum) = SumOfSquares::generate_trace();
+        let squares_trace = Squares::generate_trace();
+
+        // Degree 3 constraints need a higher rate in zero-knowledge mode.
+        let mut zk_config = StarkConfig::standard_fast_zk_config();
+        zk_config.fri_config.rate_bits = 2;
+
+        for config in [StarkConfig::standard_fast_config(), zk_config] {
+            let proof = prove_all(
+                &multi_stark,
+                &config,
+                vec![squares_trace.clone(), sum_trace.clone()],
+                &[vec![], vec![sum]],
+                &mut TimingTree::default(),
+            )?;
+            let proof = MultiStarkProof::from_bytes(proof.to_bytes(), &config)?;
+            verify_all(&multi_stark, proof, &config)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_reports() {
+        // `A` is only constrained by the cross-table lookup.
+        let sum_of_squares = SumOfSquares {
+            _phantom: PhantomData,
+        };
+        assert_eq!(
+            column_report(&sum_of_squares).unused_columns,
+            vec![SumOfSquares::A]
+        );
+        assert!(multi_stark()
+            .column_reports()
+            .iter()
+            .all(ColumnReport::is_empty));
+    }
+
+    #[test]
+    fn test_multi_stark_wrong_lookup() -> Result<()> {
+        let multi_stark = multi_stark();
+        let config = StarkConfig::standard_fast_config();
+        let (mut sum_trace, sum) = SumOfSquares::generate_trace();
+        let squares_trace = Squares::generate_trace();
+
+        // Claim a wrong square in the last looking row, keeping the accumulator consistent, so that
+        // each table satisfies its own constraints.
+        let last = NUM_SQUARES - 1;
+        sum_trace[SumOfSquares::B].values[last] += F::ONE;
+        for acc in &mut sum_trace[SumOfSquares::ACC].values[last + 1..] {
+            *acc += F::ONE;
+        }
+
+        let proof = prove_all(
+            &multi_stark,
+            &config,
+            vec![squares_trace, sum_trace],
+            &[vec![], vec![sum + F::ONE]],
+            &mut TimingTree::default(),
+        )?;
+        assert!(verify_all(&multi_stark, proof, &config).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+//! Preprocessed columns, i.e. fixed columns of a STARK which do not depend on the witness.
+//!
+//! They are committed once per trace length, and the resulting [`PreprocessedData`] can be reused
+//! for any number of proofs. Verifiers only need the Merkle cap, held in
+//! [`PreprocessedVerifierData`].
+
+use alloc::vec::Vec;
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension::Extendable;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::plonk::config::GenericConfig;
+use plonky2::timed;
+use plonky2::util::timing::TimingTree;
+use plonky2_maybe_rayon::*;
+
+use crate::config::StarkConfig;
+use crate::stark::Stark;
+
+/// The commitment to the preprocessed columns of a STARK, used by the prover.
+pub struct PreprocessedData<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub commitment: PolynomialBatch<F, C, D>,
+    /// The log of the trace length these columns were computed for.
+    pub degree_bits: usize,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    PreprocessedData<F, C, D>
+{
+    /// Computes and commits to the preprocessed columns of `stark`, for traces of length
+    /// `2^degree_bits`.
+    pub fn new<S: Stark<F, D>>(
+        stark: &S,
+        degree_bits: usize,
+        config: &StarkConfig,
+        timing: &mut TimingTree,
+    ) -> Self {
+        let columns = stark.preprocessed_columns(degree_bits);
+        assert_eq!(
+            columns.len(),
+            S::PREPROCESSED_COLUMNS,
+            "Wrong number of preprocessed columns."
+        );
+        assert!(
+            columns
+                .iter()
+                .all(|column| column.len() == 1 << degree_bits),
+            "Preprocessed columns must have the length of the trace."
+        );
+
+        // The columns are public, so they aren't masked. In zero-knowledge mode, they are still
+        // padded to the degree bound of other committed polynomials, so that all oracles share
+        // the same LDE domain.
+        let committed_degree = 1 << config.committed_degree_bits(degree_bits);
+        let polys = columns
+            .into_par_iter()
+            .map(|column| column.ifft().padded(committed_degree))
+            .collect::<Vec<_>>();
+        let commitment = timed!(
+            timing,
+            "compute preprocessed columns commitment",
+            PolynomialBatch::from_coeffs(
+                polys,
+                config.fri_config.rate_bits,
+                false,
+                config.fri_config.cap_height,
+                timing,
+                None,
+            )
+        );
+
+        Self {
+            commitment,
+            degree_bits,
+        }
+    }
+
+    pub fn verifier_data(&self) -> PreprocessedVerifierData<F, C, D> {
+        PreprocessedVerifierData {
+            cap: self.commitment.merkle_tree.cap.clone(),
+            degree_bits: self.degree_bits,
+        }
+    }
+}
+
+/// The part of [`PreprocessedData`] needed to verify proofs.
+#[derive(Debug, Clone)]
+pub struct PreprocessedVerifierData<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    /// Merkle cap of LDEs of the preprocessed columns.
+    pub cap: MerkleCap<F, C::Hasher>,
+    /// The log of the trace length these columns were computed for.
+    pub degree_bits: usize,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    PreprocessedVerifierData<F, C, D>
+{
+    pub(crate) fn check_degree_bits(&self, degree_bits: usize) -> Result<()> {
+        ensure!(
+            self.degree_bits == degree_bits,
+            "Preprocessed columns were computed for traces of length 2^{}, not 2^{}.",
+            self.degree_bits,
+            degree_bits
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+
+    use anyhow::Result;
+    use plonky2::field::extension::{Extendable, FieldExtension};
+    use plonky2::field::packed::PackedField;
+    use plonky2::field::polynomial::PolynomialValues;
+    use plonky2::field::types::Field;
+    use plonky2::hash::hash_types::RichField;
+    use plonky2::iop::ext_target::ExtensionTarget;
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
+
+    use crate::config::StarkConfig;
+    use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+    use crate::evaluation_frame::{StarkEvaluationFrame, StarkFrame};
+    use crate::preprocessed::PreprocessedData;
+    use crate::prover::{prove, prove_with_preprocessed};
+    use crate::stark::Stark;
+    use crate::stark_testing::{test_stark_circuit_constraints, test_stark_low_degree};
+    use crate::util::periodic_column;
+    use crate::verifier::{verify_stark_proof, verify_stark_proof_with_preprocessed};
+
+    const PERIOD: usize = 4;
+    const NUM_ROWS: usize = 1 << 14;
+    const ROUND_CONSTANT: usize = 0;
+    const SELECTOR: usize = 1;
+
+    /// Adds a round constant to the single trace column `x` on every row but the last one of each
+    /// round, i.e. `x' <- x + s k`, where the round constants `k` and selector `s` are
+    /// preprocessed columns of period `PERIOD`. The public inputs are the first and last `x`.
+    #[derive(Copy, Clone)]
+    struct RoundConstantsStark<F: RichField + Extendable<D>, const D: usize> {
+        round_constants: [u64; PERIOD],
+        _phantom: PhantomData<F>,
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> RoundConstantsStark<F, D> {
+        fn new(round_constants: [u64; PERIOD]) -> Self {
+            Self {
+                round_constants,
+                _phantom: PhantomData,
+            }
+        }
+
+        fn selector_pattern() -> [F; PERIOD] {
+            [F::ONE, F::ONE, F::ONE, F::ZERO]
+        }
+
+        /// Returns the trace starting from `x0`, and the final `x`.
+        fn generate_trace(&self, x0: F) -> (Vec<PolynomialValues<F>>, F) {
+            let mut x = x0;
+            let values = (0..NUM_ROWS)
+                .map(|i| {
+                    let row = x;
+                    x += Self::selector_pattern()[i % PERIOD]
+                        * F::from_canonical_u64(self.round_constants[i % PERIOD]);
+                    row
+                })
+                .collect::<Vec<_>>();
+            let last = values[NUM_ROWS - 1];
+            (vec![PolynomialValues::new(values)], last)
+        }
+    }
+
+    impl<F: RichField + Extendable<D>, const D: usize> Stark<F, D> for RoundConstantsStark<F, D> {
+        type EvaluationFrame<FE, P, const D2: usize>
+            = StarkFrame<P, P::Scalar, 1, 2, 2>
+        where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>;
+
+        type EvaluationFrameTarget = StarkFrame<ExtensionTarget<D>, ExtensionTarget<D>, 1, 2, 2>;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            vars: &Self::EvaluationFrame<FE, P, D2>,
+            yield_constr: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE>,
+        {
+            let x = vars.get_local_values()[0];
+            let next_x = vars.get_next_values()[0];
+            let preprocessed = vars.get_local_preprocessed_values();
+            let public_inputs = vars.get_public_inputs();
+
+            yield_constr.constraint_first_row(x - public_inputs[0]);
+            yield_constr.constraint_transition(
+                next_x - x - preprocessed[SELECTOR] * preprocessed[ROUND_CONSTANT],
+            );
+            yield_constr.constraint_last_row(x - public_inputs[1]);
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: &Self::EvaluationFrameTarget,
+            yield_constr: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+            let x = vars.get_local_values()[0];
+            let next_x = vars.get_next_values()[0];
+            let preprocessed = vars.get_local_preprocessed_values();
+            let public_inputs = vars.get_public_inputs();
+
+            let constraint = builder.sub_extension(x, public_inputs[0]);
+            yield_constr.constraint_first_row(builder, constraint);
+            let diff = builder.sub_extension(next_x, x);
+            let constraint = builder.arithmetic_extension(
+                F::NEG_ONE,
+                F::ONE,
+                preprocessed[SELECTOR],
+                preprocessed[ROUND_CONSTANT],
+                diff,
+            );
+            yield_constr.constraint_transition(builder, constraint);
+            let constraint = builder.sub_extension(x, public_inputs[1]);
+            yield_constr.constraint_last_row(builder, constraint);
+        }
+
+        fn constraint_degree(&self) -> usize {
+            2
+        }
+
+        fn preprocessed_columns(&self, degree_bits: usize) -> Vec<PolynomialValues<F>> {
+            vec![
+                periodic_column(
+                    &self.round_constants.map(F::from_canonical_u64),
+                    degree_bits,
+                ),
+                periodic_column(&Self::selector_pattern(), degree_bits),
+            ]
+        }
+    }
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type S = RoundConstantsStark<F, D>;
+
+    #[test]
+    fn test_preprocessed_stark() -> Result<()> {
+        let stark = S::new([1, 2, 3, 4]);
+        for config in [
+            StarkConfig::standard_fast_config(),
+            StarkConfig::standard_fast_zk_config(),
+        ] {
+            let x0 = F::from_canonical_u64(5);
+            let (trace, last) = stark.generate_trace(x0);
+            let proof = prove::<F, C, S, D>(
+                stark,
+                &config,
+                trace,
+                &[x0, last],
+                &mut TimingTree::default(),
+            )?;
+            verify_stark_proof(stark, proof, &config)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_preprocessed_data_reuse() -> Result<()> {
+        let config = StarkConfig::standard_fast_config();
+        let stark = S::new([1, 2, 3, 4]);
+        let degree_bits = NUM_ROWS.trailing_zeros() as usize;
+        let preprocessed_data =
+            PreprocessedData::new(&stark, degree_bits, &config, &mut TimingTree::default());
+        let verifier_data = preprocessed_data.verifier_data();
+
+        for x0 in [0, 7] {
+            let x0 = F::from_canonical_u64(x0);
+            let (trace, last) = stark.generate_trace(x0);
+            let proof = prove_with_preprocessed::<F, C, S, D>(
+                stark,
+                &config,
+                &preprocessed_data,
+                trace,
+                &[x0, last],
+                &mut TimingTree::default(),
+            )?;
+            verify_stark_proof_with_preprocessed(stark, proof, &verifier_data, &config)?;
+        }
+
+        // A proof for other round constants doesn't verify against these preprocessed columns.
+        let other_stark = S::new([4, 3, 2, 1]);
+        let (trace, last) = other_stark.generate_trace(F::ZERO);
+        let proof = prove::<F, C, S, D>(
+            other_stark,
+            &config,
+            trace,
+            &[F::ZERO, last],
+            &mut TimingTree::default(),
+        )?;
+        assert!(
+            verify_stark_proof_with_preprocessed(stark, proof, &verifier_data, &config).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_preprocessed_stark_degree() -> Result<()> {
+        test_stark_low_degree(S::new([1, 2, 3, 4]))
+    }
+
+    #[test]
+    fn test_preprocessed_stark_circuit() -> Result<()> {
+        test_stark_circuit_constraints::<F, C, S, D>(S::new([1, 2, 3, 4]))
+    }
+}
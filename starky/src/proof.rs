@@ -3,9 +3,11 @@ use alloc::vec::Vec;
 
 use itertools::Itertools;
 use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::polynomial::PolynomialCoeffs;
 use plonky2::fri::oracle::PolynomialBatch;
 use plonky2::fri::proof::{
-    CompressedFriProof, FriChallenges, FriChallengesTarget, FriProof, FriProofTarget,
+    CompressedFriProof, FriChallenges, FriChallengesTarget, FriInitialTreeProof, FriProof,
+    FriProofTarget, FriQueryRound, FriQueryStep,
 };
 use plonky2::fri::structure::{
     FriOpeningBatch, FriOpeningBatchTarget, FriOpenings, FriOpeningsTarget,
@@ -14,18 +16,24 @@ use plonky2::hash::hash_types::{MerkleCapTarget, RichField};
 use plonky2::hash::merkle_tree::MerkleCap;
 use plonky2::iop::ext_target::ExtensionTarget;
 use plonky2::iop::target::Target;
-use plonky2::plonk::config::GenericConfig;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+use plonky2::util::serialization::{ArtifactKind, Buffer, IoResult, Read, Remaining, Write};
 use plonky2_maybe_rayon::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::config::StarkConfig;
 use crate::permutation::PermutationChallengeSet;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
 pub struct StarkProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
     /// Merkle cap of LDEs of trace values.
     pub trace_cap: MerkleCap<F, C::Hasher>,
-    /// Merkle cap of LDEs of permutation Z values.
-    pub permutation_zs_cap: Option<MerkleCap<F, C::Hasher>>,
+    /// Merkle cap of LDEs of the auxiliary polynomials: permutation Z values and lookup helper
+    /// columns.
+    pub auxiliary_polys_cap: Option<MerkleCap<F, C::Hasher>>,
     /// Merkle cap of LDEs of trace values.
     pub quotient_polys_cap: MerkleCap<F, C::Hasher>,
     /// Purported values of each polynomial at the challenge point.
@@ -42,13 +50,43 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> S
             .evals_proofs[0]
             .1;
         let lde_bits = config.fri_config.cap_height + initial_merkle_proof.siblings.len();
-        lde_bits - config.fri_config.rate_bits
+        // Committed polynomials have a higher degree than the trace in zero-knowledge mode.
+        lde_bits - config.fri_config.rate_bits - usize::from(config.zero_knowledge)
+    }
+
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        buffer.write_merkle_cap(&self.trace_cap)?;
+        write_option(buffer, &self.auxiliary_polys_cap, |b, cap| {
+            b.write_merkle_cap(cap)
+        })?;
+        buffer.write_merkle_cap(&self.quotient_polys_cap)?;
+        self.openings.to_buffer(buffer)?;
+        write_fri_proof(buffer, &self.opening_proof)
+    }
+
+    /// Deserializes a proof written by [`Self::to_buffer`]. Merkle caps are read with the cap
+    /// height of `config`, which must be the config the proof was generated with.
+    pub fn from_buffer(buffer: &mut Buffer, config: &StarkConfig) -> IoResult<Self> {
+        let cap_height = config.fri_config.cap_height;
+        let trace_cap = buffer.read_merkle_cap(cap_height)?;
+        let auxiliary_polys_cap = read_option(buffer, |b| b.read_merkle_cap(cap_height))?;
+        let quotient_polys_cap = buffer.read_merkle_cap(cap_height)?;
+        let openings = StarkOpeningSet::from_buffer(buffer)?;
+        let opening_proof = read_fri_proof(buffer, cap_height)?;
+        Ok(Self {
+            trace_cap,
+            auxiliary_polys_cap,
+            quotient_polys_cap,
+            openings,
+            opening_proof,
+        })
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct StarkProofTarget<const D: usize> {
     pub trace_cap: MerkleCapTarget,
-    pub permutation_zs_cap: Option<MerkleCapTarget>,
+    pub auxiliary_polys_cap: Option<MerkleCapTarget>,
     pub quotient_polys_cap: MerkleCapTarget,
     pub openings: StarkOpeningSetTarget<D>,
     pub opening_proof: FriProofTarget<D>,
@@ -62,11 +100,39 @@ impl<const D: usize> StarkProofTarget<D> {
             .evals_proofs[0]
             .1;
         let lde_bits = config.fri_config.cap_height + initial_merkle_proof.siblings.len();
-        lde_bits - config.fri_config.rate_bits
+        // Committed polynomials have a higher degree than the trace in zero-knowledge mode.
+        lde_bits - config.fri_config.rate_bits - usize::from(config.zero_knowledge)
+    }
+
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        buffer.write_target_merkle_cap(&self.trace_cap)?;
+        write_option(buffer, &self.auxiliary_polys_cap, |b, cap| {
+            b.write_target_merkle_cap(cap)
+        })?;
+        buffer.write_target_merkle_cap(&self.quotient_polys_cap)?;
+        self.openings.to_buffer(buffer)?;
+        buffer.write_target_fri_proof(&self.opening_proof)
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer) -> IoResult<Self> {
+        let trace_cap = buffer.read_target_merkle_cap()?;
+        let auxiliary_polys_cap = read_option(buffer, |b| b.read_target_merkle_cap())?;
+        let quotient_polys_cap = buffer.read_target_merkle_cap()?;
+        let openings = StarkOpeningSetTarget::from_buffer(buffer)?;
+        let opening_proof = buffer.read_target_fri_proof()?;
+        Ok(Self {
+            trace_cap,
+            auxiliary_polys_cap,
+            quotient_polys_cap,
+            openings,
+            opening_proof,
+        })
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
 pub struct StarkProofWithPublicInputs<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -77,11 +143,116 @@ pub struct StarkProofWithPublicInputs<
     pub public_inputs: Vec<F>,
 }
 
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    StarkProofWithPublicInputs<F, C, D>
+{
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        self.proof.to_buffer(buffer)?;
+        buffer.write_usize(self.public_inputs.len())?;
+        buffer.write_field_vec(&self.public_inputs)
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer, config: &StarkConfig) -> IoResult<Self> {
+        let proof = StarkProof::from_buffer(buffer, config)?;
+        let num_public_inputs = buffer.read_usize()?;
+        let public_inputs = buffer.read_field_vec(num_public_inputs)?;
+        Ok(Self {
+            proof,
+            public_inputs,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer
+            .write_header(ArtifactKind::StarkProof)
+            .and_then(|_| self.to_buffer(&mut buffer))
+            .expect("Writing to a byte-vector cannot fail.");
+        buffer
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>, config: &StarkConfig) -> anyhow::Result<Self> {
+        let mut buffer = Buffer::new(&bytes);
+        buffer
+            .read_header(ArtifactKind::StarkProof)
+            .map_err(anyhow::Error::msg)?;
+        let proof = Self::from_buffer(&mut buffer, config).map_err(anyhow::Error::msg)?;
+        buffer.ensure_empty().map_err(anyhow::Error::msg)?;
+        Ok(proof)
+    }
+}
+
+/// Proofs of all tables of a [`MultiStark`](crate::multi_stark::MultiStark), in registration
+/// order.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
+pub struct MultiStarkProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+{
+    pub stark_proofs: Vec<StarkProofWithPublicInputs<F, C, D>>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    MultiStarkProof<F, C, D>
+{
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        buffer.write_usize(self.stark_proofs.len())?;
+        for proof in &self.stark_proofs {
+            proof.to_buffer(buffer)?;
+        }
+        Ok(())
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer, config: &StarkConfig) -> IoResult<Self> {
+        let num_starks = buffer.read_usize()?;
+        let stark_proofs = (0..num_starks)
+            .map(|_| StarkProofWithPublicInputs::from_buffer(buffer, config))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { stark_proofs })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer
+            .write_header(ArtifactKind::MultiStarkProof)
+            .and_then(|_| self.to_buffer(&mut buffer))
+            .expect("Writing to a byte-vector cannot fail.");
+        buffer
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>, config: &StarkConfig) -> anyhow::Result<Self> {
+        let mut buffer = Buffer::new(&bytes);
+        buffer
+            .read_header(ArtifactKind::MultiStarkProof)
+            .map_err(anyhow::Error::msg)?;
+        let proof = Self::from_buffer(&mut buffer, config).map_err(anyhow::Error::msg)?;
+        buffer.ensure_empty().map_err(anyhow::Error::msg)?;
+        Ok(proof)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct StarkProofWithPublicInputsTarget<const D: usize> {
     pub proof: StarkProofTarget<D>,
     pub public_inputs: Vec<Target>,
 }
 
+impl<const D: usize> StarkProofWithPublicInputsTarget<D> {
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        self.proof.to_buffer(buffer)?;
+        buffer.write_target_vec(&self.public_inputs)
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer) -> IoResult<Self> {
+        let proof = StarkProofTarget::from_buffer(buffer)?;
+        let public_inputs = buffer.read_target_vec()?;
+        Ok(Self {
+            proof,
+            public_inputs,
+        })
+    }
+}
+
 pub struct CompressedStarkProof<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -108,6 +279,9 @@ pub(crate) struct StarkProofChallenges<F: RichField + Extendable<D>, const D: us
     /// Randomness used in any permutation arguments.
     pub permutation_challenge_sets: Option<Vec<PermutationChallengeSet<F>>>,
 
+    /// Randomness used in any lookup arguments.
+    pub lookup_challenges: Option<Vec<F>>,
+
     /// Random values used to combine STARK constraints.
     pub stark_alphas: Vec<F>,
 
@@ -119,6 +293,7 @@ pub(crate) struct StarkProofChallenges<F: RichField + Extendable<D>, const D: us
 
 pub(crate) struct StarkProofChallengesTarget<const D: usize> {
     pub permutation_challenge_sets: Option<Vec<PermutationChallengeSet<Target>>>,
+    pub lookup_challenges: Option<Vec<Target>>,
     pub stark_alphas: Vec<Target>,
     pub stark_zeta: ExtensionTarget<D>,
     pub fri_challenges: FriChallengesTarget<D>,
@@ -126,12 +301,24 @@ pub(crate) struct StarkProofChallengesTarget<const D: usize> {
 
 /// Purported values of each polynomial at the challenge point.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
 pub struct StarkOpeningSet<F: RichField + Extendable<D>, const D: usize> {
     pub local_values: Vec<F::Extension>,
     pub next_values: Vec<F::Extension>,
-    pub permutation_zs: Option<Vec<F::Extension>>,
-    pub permutation_zs_next: Option<Vec<F::Extension>>,
+    pub auxiliary_polys: Option<Vec<F::Extension>>,
+    pub auxiliary_polys_next: Option<Vec<F::Extension>>,
+    /// Openings of the cross-table lookup `Z`s at 1, if this proof is part of a multi-STARK proof
+    /// with cross-table lookups.
+    pub ctl_zs_first: Option<Vec<F>>,
     pub quotient_polys: Vec<F::Extension>,
+    /// Openings of the preprocessed columns, if the Stark has any.
+    pub preprocessed_values: Option<Vec<F::Extension>>,
+    pub preprocessed_values_next: Option<Vec<F::Extension>>,
+    /// Openings of the trace at `g^i zeta`, for each `2 <= i < Stark::FRAME_ROWS`.
+    pub window_values: Vec<Vec<F::Extension>>,
+    /// Openings of the preprocessed columns at `g^i zeta`, for each `2 <= i < Stark::FRAME_ROWS`.
+    pub window_preprocessed_values: Option<Vec<Vec<F::Extension>>>,
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
@@ -139,8 +326,11 @@ impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
         zeta: F::Extension,
         g: F,
         trace_commitment: &PolynomialBatch<F, C, D>,
-        permutation_zs_commitment: Option<&PolynomialBatch<F, C, D>>,
+        auxiliary_polys_commitment: Option<&PolynomialBatch<F, C, D>>,
         quotient_commitment: &PolynomialBatch<F, C, D>,
+        preprocessed_commitment: Option<&PolynomialBatch<F, C, D>>,
+        num_ctl_zs: usize,
+        frame_rows: usize,
     ) -> Self {
         let eval_commitment = |z: F::Extension, c: &PolynomialBatch<F, C, D>| {
             c.polynomials
@@ -149,22 +339,98 @@ impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
                 .collect::<Vec<_>>()
         };
         let zeta_next = zeta.scalar_mul(g);
+        let window_points = (2..frame_rows)
+            .map(|i| zeta.scalar_mul(g.exp_u64(i as u64)))
+            .collect::<Vec<_>>();
+        let eval_window = |c: &PolynomialBatch<F, C, D>| {
+            window_points
+                .iter()
+                .map(|&x| eval_commitment(x, c))
+                .collect::<Vec<_>>()
+        };
         Self {
             local_values: eval_commitment(zeta, trace_commitment),
             next_values: eval_commitment(zeta_next, trace_commitment),
-            permutation_zs: permutation_zs_commitment.map(|c| eval_commitment(zeta, c)),
-            permutation_zs_next: permutation_zs_commitment.map(|c| eval_commitment(zeta_next, c)),
+            auxiliary_polys: auxiliary_polys_commitment.map(|c| eval_commitment(zeta, c)),
+            auxiliary_polys_next: auxiliary_polys_commitment.map(|c| eval_commitment(zeta_next, c)),
+            ctl_zs_first: (num_ctl_zs > 0).then(|| {
+                let polys = &auxiliary_polys_commitment.unwrap().polynomials;
+                polys[polys.len() - num_ctl_zs..]
+                    .par_iter()
+                    .map(|p| p.eval(F::ONE))
+                    .collect()
+            }),
             quotient_polys: eval_commitment(zeta, quotient_commitment),
+            preprocessed_values: preprocessed_commitment.map(|c| eval_commitment(zeta, c)),
+            preprocessed_values_next: preprocessed_commitment
+                .map(|c| eval_commitment(zeta_next, c)),
+            window_values: eval_window(trace_commitment),
+            window_preprocessed_values: preprocessed_commitment.map(eval_window),
         }
     }
 
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        write_ext_vec::<F, D>(buffer, &self.local_values)?;
+        write_ext_vec::<F, D>(buffer, &self.next_values)?;
+        write_option(buffer, &self.auxiliary_polys, |b, v| {
+            write_ext_vec::<F, D>(b, v)
+        })?;
+        write_option(buffer, &self.auxiliary_polys_next, |b, v| {
+            write_ext_vec::<F, D>(b, v)
+        })?;
+        write_option(buffer, &self.ctl_zs_first, |b, v| {
+            b.write_usize(v.len())?;
+            b.write_field_vec(v)
+        })?;
+        write_ext_vec::<F, D>(buffer, &self.quotient_polys)?;
+        write_option(buffer, &self.preprocessed_values, |b, v| {
+            write_ext_vec::<F, D>(b, v)
+        })?;
+        write_option(buffer, &self.preprocessed_values_next, |b, v| {
+            write_ext_vec::<F, D>(b, v)
+        })?;
+        write_ext_vecs::<F, D>(buffer, &self.window_values)?;
+        write_option(buffer, &self.window_preprocessed_values, |b, v| {
+            write_ext_vecs::<F, D>(b, v)
+        })
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer) -> IoResult<Self> {
+        let local_values = read_ext_vec::<F, D>(buffer)?;
+        let next_values = read_ext_vec::<F, D>(buffer)?;
+        let auxiliary_polys = read_option(buffer, read_ext_vec::<F, D>)?;
+        let auxiliary_polys_next = read_option(buffer, read_ext_vec::<F, D>)?;
+        let ctl_zs_first = read_option(buffer, |b| {
+            let len = b.read_usize()?;
+            b.read_field_vec(len)
+        })?;
+        let quotient_polys = read_ext_vec::<F, D>(buffer)?;
+        let preprocessed_values = read_option(buffer, read_ext_vec::<F, D>)?;
+        let preprocessed_values_next = read_option(buffer, read_ext_vec::<F, D>)?;
+        let window_values = read_ext_vecs::<F, D>(buffer)?;
+        let window_preprocessed_values = read_option(buffer, read_ext_vecs::<F, D>)?;
+        Ok(Self {
+            local_values,
+            next_values,
+            auxiliary_polys,
+            auxiliary_polys_next,
+            ctl_zs_first,
+            quotient_polys,
+            preprocessed_values,
+            preprocessed_values_next,
+            window_values,
+            window_preprocessed_values,
+        })
+    }
+
     pub(crate) fn to_fri_openings(&self) -> FriOpenings<F, D> {
         let zeta_batch = FriOpeningBatch {
             values: self
                 .local_values
                 .iter()
-                .chain(self.permutation_zs.iter().flatten())
+                .chain(self.auxiliary_polys.iter().flatten())
                 .chain(&self.quotient_polys)
+                .chain(self.preprocessed_values.iter().flatten())
                 .copied()
                 .collect_vec(),
         };
@@ -172,31 +438,92 @@ impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
             values: self
                 .next_values
                 .iter()
-                .chain(self.permutation_zs_next.iter().flatten())
+                .chain(self.auxiliary_polys_next.iter().flatten())
+                .chain(self.preprocessed_values_next.iter().flatten())
                 .copied()
                 .collect_vec(),
         };
-        FriOpenings {
-            batches: vec![zeta_batch, zeta_next_batch],
+        let mut batches = vec![zeta_batch, zeta_next_batch];
+        for (i, values) in self.window_values.iter().enumerate() {
+            batches.push(FriOpeningBatch {
+                values: values
+                    .iter()
+                    .chain(
+                        self.window_preprocessed_values
+                            .iter()
+                            .flat_map(|window| &window[i]),
+                    )
+                    .copied()
+                    .collect_vec(),
+            });
+        }
+        if let Some(ctl_zs_first) = &self.ctl_zs_first {
+            batches.push(FriOpeningBatch {
+                values: ctl_zs_first
+                    .iter()
+                    .copied()
+                    .map(F::Extension::from_basefield)
+                    .collect(),
+            });
         }
+        FriOpenings { batches }
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct StarkOpeningSetTarget<const D: usize> {
     pub local_values: Vec<ExtensionTarget<D>>,
     pub next_values: Vec<ExtensionTarget<D>>,
-    pub permutation_zs: Option<Vec<ExtensionTarget<D>>>,
-    pub permutation_zs_next: Option<Vec<ExtensionTarget<D>>>,
+    pub auxiliary_polys: Option<Vec<ExtensionTarget<D>>>,
+    pub auxiliary_polys_next: Option<Vec<ExtensionTarget<D>>>,
     pub quotient_polys: Vec<ExtensionTarget<D>>,
+    pub window_values: Vec<Vec<ExtensionTarget<D>>>,
 }
 
 impl<const D: usize> StarkOpeningSetTarget<D> {
+    pub fn to_buffer(&self, buffer: &mut Vec<u8>) -> IoResult<()> {
+        buffer.write_target_ext_vec(&self.local_values)?;
+        buffer.write_target_ext_vec(&self.next_values)?;
+        write_option(buffer, &self.auxiliary_polys, |b, v| {
+            b.write_target_ext_vec(v)
+        })?;
+        write_option(buffer, &self.auxiliary_polys_next, |b, v| {
+            b.write_target_ext_vec(v)
+        })?;
+        buffer.write_target_ext_vec(&self.quotient_polys)?;
+        buffer.write_usize(self.window_values.len())?;
+        for values in &self.window_values {
+            buffer.write_target_ext_vec(values)?;
+        }
+        Ok(())
+    }
+
+    pub fn from_buffer(buffer: &mut Buffer) -> IoResult<Self> {
+        let local_values = buffer.read_target_ext_vec::<D>()?;
+        let next_values = buffer.read_target_ext_vec::<D>()?;
+        let auxiliary_polys = read_option(buffer, |b| b.read_target_ext_vec::<D>())?;
+        let auxiliary_polys_next = read_option(buffer, |b| b.read_target_ext_vec::<D>())?;
+        let quotient_polys = buffer.read_target_ext_vec::<D>()?;
+        let num_windows = buffer.read_usize()?;
+        let window_values = (0..num_windows)
+            .map(|_| buffer.read_target_ext_vec::<D>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            local_values,
+            next_values,
+            auxiliary_polys,
+            auxiliary_polys_next,
+            quotient_polys,
+            window_values,
+        })
+    }
+
     pub(crate) fn to_fri_openings(&self) -> FriOpeningsTarget<D> {
         let zeta_batch = FriOpeningBatchTarget {
             values: self
                 .local_values
                 .iter()
-                .chain(self.permutation_zs.iter().flatten())
+                .chain(self.auxiliary_polys.iter().flatten())
                 .chain(&self.quotient_polys)
                 .copied()
                 .collect_vec(),
@@ -205,12 +532,153 @@ impl<const D: usize> StarkOpeningSetTarget<D> {
             values: self
                 .next_values
                 .iter()
-                .chain(self.permutation_zs_next.iter().flatten())
+                .chain(self.auxiliary_polys_next.iter().flatten())
                 .copied()
                 .collect_vec(),
         };
-        FriOpeningsTarget {
-            batches: vec![zeta_batch, zeta_next_batch],
+        let mut batches = vec![zeta_batch, zeta_next_batch];
+        batches.extend(
+            self.window_values
+                .iter()
+                .map(|values| FriOpeningBatchTarget {
+                    values: values.clone(),
+                }),
+        );
+        FriOpeningsTarget { batches }
+    }
+}
+
+// Unlike plonky2 proofs, whose shape is fixed by their `CommonCircuitData`, the shape of a STARK
+// proof depends on the `Stark` it was generated for, so vectors are written with their lengths.
+// Verifiers check the shape of deserialized proofs against the `Stark` anyway.
+
+fn write_option<T, W: Write>(
+    buffer: &mut W,
+    value: &Option<T>,
+    write: impl FnOnce(&mut W, &T) -> IoResult<()>,
+) -> IoResult<()> {
+    buffer.write_bool(value.is_some())?;
+    match value {
+        Some(value) => write(buffer, value),
+        None => Ok(()),
+    }
+}
+
+fn read_option<T, R: Read>(
+    buffer: &mut R,
+    read: impl FnOnce(&mut R) -> IoResult<T>,
+) -> IoResult<Option<T>> {
+    if buffer.read_bool()? {
+        read(buffer).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn write_ext_vec<F: RichField + Extendable<D>, const D: usize>(
+    buffer: &mut impl Write,
+    values: &[F::Extension],
+) -> IoResult<()> {
+    buffer.write_usize(values.len())?;
+    buffer.write_field_ext_vec::<F, D>(values)
+}
+
+fn read_ext_vec<F: RichField + Extendable<D>, const D: usize>(
+    buffer: &mut impl Read,
+) -> IoResult<Vec<F::Extension>> {
+    let len = buffer.read_usize()?;
+    buffer.read_field_ext_vec::<F, D>(len)
+}
+
+fn write_ext_vecs<F: RichField + Extendable<D>, const D: usize>(
+    buffer: &mut impl Write,
+    values: &[Vec<F::Extension>],
+) -> IoResult<()> {
+    buffer.write_usize(values.len())?;
+    for v in values {
+        write_ext_vec::<F, D>(buffer, v)?;
+    }
+    Ok(())
+}
+
+fn read_ext_vecs<F: RichField + Extendable<D>, const D: usize>(
+    buffer: &mut impl Read,
+) -> IoResult<Vec<Vec<F::Extension>>> {
+    let len = buffer.read_usize()?;
+    (0..len)
+        .map(|_| read_ext_vec::<F, D>(buffer))
+        .collect::<Result<Vec<_>, _>>()
+}
+
+fn write_fri_proof<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize>(
+    buffer: &mut impl Write,
+    proof: &FriProof<F, H, D>,
+) -> IoResult<()> {
+    buffer.write_usize(proof.commit_phase_merkle_caps.len())?;
+    for cap in &proof.commit_phase_merkle_caps {
+        buffer.write_merkle_cap(cap)?;
+    }
+    buffer.write_usize(proof.query_round_proofs.len())?;
+    for round in &proof.query_round_proofs {
+        let evals_proofs = &round.initial_trees_proof.evals_proofs;
+        buffer.write_usize(evals_proofs.len())?;
+        for (evals, merkle_proof) in evals_proofs {
+            buffer.write_usize(evals.len())?;
+            buffer.write_field_vec(evals)?;
+            buffer.write_merkle_proof(merkle_proof)?;
+        }
+        buffer.write_usize(round.steps.len())?;
+        for step in &round.steps {
+            write_ext_vec::<F, D>(buffer, &step.evals)?;
+            buffer.write_merkle_proof(&step.merkle_proof)?;
         }
     }
+    write_ext_vec::<F, D>(buffer, &proof.final_poly.coeffs)?;
+    buffer.write_field(proof.pow_witness)
+}
+
+fn read_fri_proof<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize>(
+    buffer: &mut impl Read,
+    cap_height: usize,
+) -> IoResult<FriProof<F, H, D>> {
+    let num_caps = buffer.read_usize()?;
+    let commit_phase_merkle_caps = (0..num_caps)
+        .map(|_| buffer.read_merkle_cap(cap_height))
+        .collect::<Result<Vec<_>, _>>()?;
+    let num_rounds = buffer.read_usize()?;
+    let query_round_proofs = (0..num_rounds)
+        .map(|_| {
+            let num_oracles = buffer.read_usize()?;
+            let evals_proofs = (0..num_oracles)
+                .map(|_| {
+                    let len = buffer.read_usize()?;
+                    let evals = buffer.read_field_vec(len)?;
+                    Ok((evals, buffer.read_merkle_proof()?))
+                })
+                .collect::<IoResult<Vec<_>>>()?;
+            let num_steps = buffer.read_usize()?;
+            let steps = (0..num_steps)
+                .map(|_| {
+                    let evals = read_ext_vec::<F, D>(buffer)?;
+                    let merkle_proof = buffer.read_merkle_proof()?;
+                    Ok(FriQueryStep {
+                        evals,
+                        merkle_proof,
+                    })
+                })
+                .collect::<IoResult<Vec<_>>>()?;
+            Ok(FriQueryRound {
+                initial_trees_proof: FriInitialTreeProof { evals_proofs },
+                steps,
+            })
+        })
+        .collect::<IoResult<Vec<_>>>()?;
+    let final_poly = PolynomialCoeffs::new(read_ext_vec::<F, D>(buffer)?);
+    let pow_witness = buffer.read_field()?;
+    Ok(FriProof {
+        commit_phase_merkle_caps,
+        query_round_proofs,
+        final_poly,
+        pow_witness,
+    })
 }
@@ -1,3 +1,4 @@
+use alloc::vec;
 use alloc::vec::Vec;
 use core::iter::once;
 
@@ -14,21 +15,31 @@ use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::Challenger;
 use plonky2::plonk::config::GenericConfig;
 use plonky2::timed;
+use plonky2::util::randomness::ProverRandomness;
 use plonky2::util::timing::TimingTree;
-use plonky2::util::{log2_ceil, log2_strict, transpose};
+use plonky2::util::{domain_cache, log2_ceil, log2_strict};
 use plonky2_maybe_rayon::*;
 
 use crate::config::StarkConfig;
 use crate::constraint_consumer::ConstraintConsumer;
+use crate::cross_table_lookup::{CtlCheckVars, CtlData};
 use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::lookup::{lookup_helper_columns, LookupCheckVars};
 use crate::permutation::{
     compute_permutation_z_polys, get_n_permutation_challenge_sets, PermutationChallengeSet,
     PermutationCheckVars,
 };
+use crate::preprocessed::PreprocessedData;
 use crate::proof::{StarkOpeningSet, StarkProof, StarkProofWithPublicInputs};
 use crate::stark::Stark;
 use crate::vanishing_poly::eval_vanishing_poly;
 
+/// The purposes, in the sense of [`ProverRandomness::derive`], of the randomness of a proof.
+pub(crate) const TRACE_RANDOMNESS: u64 = 0;
+const AUXILIARY_RANDOMNESS: u64 = 1;
+const QUOTIENT_RANDOMNESS: u64 = 2;
+const MASK_RANDOMNESS: u64 = 3;
+
 pub fn prove<F, C, S, const D: usize>(
     stark: S,
     config: &StarkConfig,
@@ -41,103 +52,268 @@ where
     C: GenericConfig<D, F = F>,
     S: Stark<F, D>,
 {
-    let degree = trace_poly_values[0].len();
-    let degree_bits = log2_strict(degree);
-    let fri_params = config.fri_params(degree_bits);
-    let rate_bits = config.fri_config.rate_bits;
-    let cap_height = config.fri_config.cap_height;
-    assert!(
-        fri_params.total_arities() <= degree_bits + rate_bits - cap_height,
-        "FRI total reduction arity is too large.",
+    prove_with_randomness(
+        stark,
+        config,
+        trace_poly_values,
+        public_inputs,
+        ProverRandomness::Os,
+        timing,
+    )
+}
+
+/// Like [`prove`], but draws the masks and salts of a zero-knowledge proof from `randomness`,
+/// e.g. to reproduce a proof from a seed.
+pub fn prove_with_randomness<F, C, S, const D: usize>(
+    stark: S,
+    config: &StarkConfig,
+    trace_poly_values: Vec<PolynomialValues<F>>,
+    public_inputs: &[F],
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> Result<StarkProofWithPublicInputs<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    let degree_bits = log2_strict(trace_poly_values[0].len());
+    let preprocessed_data = stark
+        .uses_preprocessed_columns()
+        .then(|| PreprocessedData::new(&stark, degree_bits, config, timing));
+    prove_inner(
+        &stark,
+        config,
+        preprocessed_data.as_ref(),
+        trace_poly_values,
+        public_inputs,
+        randomness,
+        timing,
+    )
+}
+
+/// Like [`prove`], but reuses a commitment to the preprocessed columns of `stark`, which must
+/// have been computed for the length of this trace.
+pub fn prove_with_preprocessed<F, C, S, const D: usize>(
+    stark: S,
+    config: &StarkConfig,
+    preprocessed_data: &PreprocessedData<F, C, D>,
+    trace_poly_values: Vec<PolynomialValues<F>>,
+    public_inputs: &[F],
+    timing: &mut TimingTree,
+) -> Result<StarkProofWithPublicInputs<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    ensure!(
+        stark.uses_preprocessed_columns(),
+        "This Stark has no preprocessed columns."
     );
+    preprocessed_data
+        .verifier_data()
+        .check_degree_bits(log2_strict(trace_poly_values[0].len()))?;
+    prove_inner(
+        &stark,
+        config,
+        Some(preprocessed_data),
+        trace_poly_values,
+        public_inputs,
+        ProverRandomness::Os,
+        timing,
+    )
+}
 
+fn prove_inner<F, C, S, const D: usize>(
+    stark: &S,
+    config: &StarkConfig,
+    preprocessed_data: Option<&PreprocessedData<F, C, D>>,
+    trace_poly_values: Vec<PolynomialValues<F>>,
+    public_inputs: &[F],
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> Result<StarkProofWithPublicInputs<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    config.check_zk_degree_bits::<D>(log2_strict(trace_poly_values[0].len()), S::FRAME_ROWS)?;
     let trace_commitment = timed!(
         timing,
         "compute trace commitment",
-        PolynomialBatch::<F, C, D>::from_values(
+        commit_values::<F, C, D>(
             // TODO: Cloning this isn't great; consider having `from_values` accept a reference,
             // or having `compute_permutation_z_polys` read trace values from the `PolynomialBatch`.
             trace_poly_values.clone(),
-            rate_bits,
-            false,
-            cap_height,
+            config,
+            S::FRAME_ROWS,
+            randomness.derive(TRACE_RANDOMNESS),
             timing,
-            None,
         )
     );
 
     let trace_cap = trace_commitment.merkle_tree.cap.clone();
     let mut challenger = Challenger::new();
+    if let Some(preprocessed_data) = preprocessed_data {
+        challenger.observe_cap(&preprocessed_data.commitment.merkle_tree.cap);
+    }
+    challenger.observe_elements(public_inputs);
     challenger.observe_cap(&trace_cap);
 
-    // Permutation arguments.
-    let permutation_zs_commitment_challenges = stark.uses_permutation_args().then(|| {
-        let permutation_challenge_sets = get_n_permutation_challenge_sets(
-            &mut challenger,
+    prove_with_commitment(
+        stark,
+        config,
+        &trace_poly_values,
+        &trace_commitment,
+        preprocessed_data.map(|data| &data.commitment),
+        &CtlData::default(),
+        public_inputs,
+        &mut challenger,
+        randomness,
+        timing,
+    )
+}
+
+/// Proves a single STARK whose preprocessed cap, public inputs and trace cap have already been
+/// observed by `challenger`. The cross-table lookup `Z`s in `ctl_data` are committed after the
+/// other auxiliary polynomials. The trace must have been committed to with the randomness
+/// `randomness.derive(TRACE_RANDOMNESS)`.
+pub(crate) fn prove_with_commitment<F, C, S, const D: usize>(
+    stark: &S,
+    config: &StarkConfig,
+    trace_poly_values: &[PolynomialValues<F>],
+    trace_commitment: &PolynomialBatch<F, C, D>,
+    preprocessed_commitment: Option<&PolynomialBatch<F, C, D>>,
+    ctl_data: &CtlData<F>,
+    public_inputs: &[F],
+    challenger: &mut Challenger<F, C::Hasher>,
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> Result<StarkProofWithPublicInputs<F, C, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    config.check_security::<F, D>()?;
+    let degree = trace_poly_values[0].len();
+    let degree_bits = log2_strict(degree);
+    let fri_params = config.fri_params(degree_bits);
+    let rate_bits = config.fri_config.rate_bits;
+    let cap_height = config.fri_config.cap_height;
+    assert!(
+        fri_params.total_arities() <= fri_params.lde_bits() - cap_height,
+        "FRI total reduction arity is too large.",
+    );
+    let num_ctl_zs = ctl_data.len();
+
+    let permutation_challenge_sets = stark.uses_permutation_args().then(|| {
+        get_n_permutation_challenge_sets(
+            challenger,
             config.num_challenges,
             stark.permutation_batch_size(),
-        );
-        let permutation_z_polys = compute_permutation_z_polys::<F, S, D>(
-            &stark,
-            config,
-            &trace_poly_values,
-            &permutation_challenge_sets,
-        );
+        )
+    });
+    let lookup_challenges = stark
+        .uses_lookups()
+        .then(|| challenger.get_n_challenges(config.num_challenges));
+
+    // Permutation Z polynomials, followed by lookup helper columns and cross-table lookup Zs.
+    let auxiliary_polys_commitment = (stark.uses_auxiliary_polys() || num_ctl_zs > 0).then(|| {
+        let mut auxiliary_polys = match &permutation_challenge_sets {
+            Some(permutation_challenge_sets) => compute_permutation_z_polys::<F, S, D>(
+                stark,
+                config,
+                trace_poly_values,
+                permutation_challenge_sets,
+            ),
+            None => vec![],
+        };
+        if let Some(lookup_challenges) = &lookup_challenges {
+            timed!(timing, "compute lookup helper columns", {
+                for lookup in &stark.lookups() {
+                    for &challenge in lookup_challenges {
+                        auxiliary_polys.extend(lookup_helper_columns(
+                            lookup,
+                            trace_poly_values,
+                            challenge,
+                            stark.constraint_degree(),
+                        ));
+                    }
+                }
+            });
+        }
+        auxiliary_polys.extend(ctl_data.z_polys());
 
-        let permutation_zs_commitment = timed!(
+        timed!(
             timing,
-            "compute permutation Z commitments",
-            PolynomialBatch::from_values(
-                permutation_z_polys,
-                rate_bits,
-                false,
-                config.fri_config.cap_height,
-                timing,
-                None,
+            "compute auxiliary polynomials commitment",
+            commit_values(
+                auxiliary_polys,
+                config,
+                S::FRAME_ROWS,
+                randomness.derive(AUXILIARY_RANDOMNESS),
+                timing
             )
-        );
-        (permutation_zs_commitment, permutation_challenge_sets)
+        )
     });
-    let permutation_zs_commitment = permutation_zs_commitment_challenges
-        .as_ref()
-        .map(|(comm, _)| comm);
-    let permutation_zs_cap = permutation_zs_commitment
+    let auxiliary_polys_cap = auxiliary_polys_commitment
         .as_ref()
         .map(|commit| commit.merkle_tree.cap.clone());
-    if let Some(cap) = &permutation_zs_cap {
+    if let Some(cap) = &auxiliary_polys_cap {
         challenger.observe_cap(cap);
     }
 
     let alphas = challenger.get_n_challenges(config.num_challenges);
     let quotient_polys = compute_quotient_polys::<F, <F as Packable>::Packing, C, S, D>(
-        &stark,
-        &trace_commitment,
-        &permutation_zs_commitment_challenges,
+        stark,
+        trace_commitment,
+        auxiliary_polys_commitment.as_ref(),
+        preprocessed_commitment,
+        permutation_challenge_sets.as_ref(),
+        lookup_challenges.as_ref(),
+        ctl_data,
         public_inputs,
         alphas,
         degree_bits,
         config,
     );
+    let num_quotient_chunks = stark.num_quotient_chunks(config);
     let all_quotient_chunks = quotient_polys
         .into_par_iter()
         .flat_map(|mut quotient_poly| {
             quotient_poly
-                .trim_to_len(degree * stark.quotient_degree_factor())
+                .trim_to_len(degree * num_quotient_chunks)
                 .expect("Quotient has failed, the vanishing polynomial is not divisible by Z_H");
-            // Split quotient into degree-n chunks.
+            // Split quotient into chunks of the same degree as the trace.
             quotient_poly.chunks(degree)
         })
         .collect();
+    let quotient_randomness = randomness.derive(QUOTIENT_RANDOMNESS);
+    let all_quotient_chunks = if config.zero_knowledge {
+        mask_quotient_chunks(
+            all_quotient_chunks,
+            num_quotient_chunks,
+            config.num_mask_coeffs::<D>(degree_bits, S::FRAME_ROWS),
+            quotient_randomness.derive(MASK_RANDOMNESS),
+        )
+    } else {
+        all_quotient_chunks
+    };
     let quotient_commitment = timed!(
         timing,
         "compute quotient commitment",
-        PolynomialBatch::from_coeffs(
+        PolynomialBatch::from_coeffs_with_randomness(
             all_quotient_chunks,
             rate_bits,
-            false,
+            config.zero_knowledge,
             config.fri_config.cap_height,
             timing,
             None,
+            None,
+            quotient_randomness,
         )
     );
     let quotient_polys_cap = quotient_commitment.merkle_tree.cap.clone();
@@ -155,31 +331,35 @@ where
     let openings = StarkOpeningSet::new(
         zeta,
         g,
-        &trace_commitment,
-        permutation_zs_commitment,
+        trace_commitment,
+        auxiliary_polys_commitment.as_ref(),
         &quotient_commitment,
+        preprocessed_commitment,
+        num_ctl_zs,
+        S::FRAME_ROWS,
     );
     challenger.observe_openings(&openings.to_fri_openings());
 
-    let initial_merkle_trees = once(&trace_commitment)
-        .chain(permutation_zs_commitment)
+    let initial_merkle_trees = once(trace_commitment)
+        .chain(&auxiliary_polys_commitment)
         .chain(once(&quotient_commitment))
+        .chain(preprocessed_commitment)
         .collect_vec();
 
     let opening_proof = timed!(
         timing,
         "compute openings proof",
         PolynomialBatch::prove_openings(
-            &stark.fri_instance(zeta, g, config),
+            &stark.fri_instance(zeta, g, num_ctl_zs, config),
             &initial_merkle_trees,
-            &mut challenger,
+            challenger,
             &fri_params,
             timing,
         )
     );
     let proof = StarkProof {
-        trace_cap,
-        permutation_zs_cap,
+        trace_cap: trace_commitment.merkle_tree.cap.clone(),
+        auxiliary_polys_cap,
         quotient_polys_cap,
         openings,
         opening_proof,
@@ -191,15 +371,122 @@ where
     })
 }
 
+/// Commits to polynomials given by their values on the trace domain `H`. In zero-knowledge mode,
+/// each polynomial `p` is replaced by `p + Z_H r` for a random `r` with
+/// `config.num_mask_coeffs(degree_bits, frame_rows)` coefficients. This has the same values on
+/// `H`, so constraints still hold, but the values it reveals outside `H`, which are no more
+/// numerous than the coefficients of `r`, are independent of `p`. The `r`s and the salts are drawn from
+/// `randomness`.
+pub(crate) fn commit_values<F, C, const D: usize>(
+    values: Vec<PolynomialValues<F>>,
+    config: &StarkConfig,
+    frame_rows: usize,
+    randomness: ProverRandomness,
+    timing: &mut TimingTree,
+) -> PolynomialBatch<F, C, D>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    let rate_bits = config.fri_config.rate_bits;
+    let cap_height = config.fri_config.cap_height;
+    if !config.zero_knowledge {
+        return PolynomialBatch::from_values(values, rate_bits, false, cap_height, timing, None);
+    }
+
+    let num_mask_coeffs = config.num_mask_coeffs::<D>(log2_strict(values[0].len()), frame_rows);
+    let masks = randomness.derive(MASK_RANDOMNESS);
+    let masked_polys = values
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, values)| {
+            let degree = values.len();
+            assert!(
+                num_mask_coeffs <= degree,
+                "Traces must have at least {num_mask_coeffs} rows in zero-knowledge mode."
+            );
+            let mut coeffs = values.ifft().coeffs;
+            let mut rng = masks.rng(i as u64);
+            let r = (0..num_mask_coeffs)
+                .map(|_| F::sample(&mut rng))
+                .collect_vec();
+            // `Z_H r = X^n r - r`.
+            for (c, &r_i) in coeffs.iter_mut().zip(&r) {
+                *c -= r_i;
+            }
+            coeffs.extend(r);
+            PolynomialCoeffs::new(coeffs).padded(2 * degree)
+        })
+        .collect();
+    PolynomialBatch::from_coeffs_with_randomness(
+        masked_polys,
+        rate_bits,
+        true,
+        cap_height,
+        timing,
+        None,
+        None,
+        randomness,
+    )
+}
+
+/// Masks the chunks of each quotient polynomial `t(X) = sum_i X^{n i} t_i(X)`, given as
+/// `num_chunks` consecutive polynomials of degree less than `n`. Each chunk `t_i` is replaced by
+/// `t_i - b_{i-1} + X^n b_i`, for random `b_i`s with `num_mask_coeffs` coefficients and
+/// `b_{-1} = b_{num_chunks-1} = 0`. The masked chunks still recombine to `t`, but as long as at
+/// most `num_mask_coeffs` values of each are revealed, those values are only correlated through
+/// that recombination.
+fn mask_quotient_chunks<F: RichField>(
+    chunks: Vec<PolynomialCoeffs<F>>,
+    num_chunks: usize,
+    num_mask_coeffs: usize,
+    randomness: ProverRandomness,
+) -> Vec<PolynomialCoeffs<F>> {
+    chunks
+        .par_chunks(num_chunks)
+        .enumerate()
+        .flat_map(|(i, chunks)| {
+            let mut rng = randomness.rng(i as u64);
+            let masks = (1..num_chunks)
+                .map(|_| {
+                    (0..num_mask_coeffs)
+                        .map(|_| F::sample(&mut rng))
+                        .collect_vec()
+                })
+                .collect_vec();
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(j, chunk)| {
+                    let degree = chunk.len();
+                    let mut coeffs = chunk.clone().padded(2 * degree).coeffs;
+                    if j > 0 {
+                        for (c, &b_k) in coeffs.iter_mut().zip(&masks[j - 1]) {
+                            *c -= b_k;
+                        }
+                    }
+                    if let Some(b) = masks.get(j) {
+                        for (c, &b_k) in coeffs[degree..].iter_mut().zip(b) {
+                            *c += b_k;
+                        }
+                    }
+                    PolynomialCoeffs::new(coeffs)
+                })
+                .collect_vec()
+        })
+        .collect()
+}
+
 /// Computes the quotient polynomials `(sum alpha^i C_i(x)) / Z_H(x)` for `alpha` in `alphas`,
 /// where the `C_i`s are the Stark constraints.
 fn compute_quotient_polys<'a, F, P, C, S, const D: usize>(
     stark: &S,
     trace_commitment: &'a PolynomialBatch<F, C, D>,
-    permutation_zs_commitment_challenges: &'a Option<(
-        PolynomialBatch<F, C, D>,
-        Vec<PermutationChallengeSet<F>>,
-    )>,
+    auxiliary_polys_commitment: Option<&'a PolynomialBatch<F, C, D>>,
+    preprocessed_commitment: Option<&'a PolynomialBatch<F, C, D>>,
+    permutation_challenge_sets: Option<&'a Vec<PermutationChallengeSet<F>>>,
+    lookup_challenges: Option<&'a Vec<F>>,
+    ctl_data: &CtlData<F>,
     public_inputs: &[F],
     alphas: Vec<F>,
     degree_bits: usize,
@@ -213,23 +500,35 @@ where
 {
     let degree = 1 << degree_bits;
     let rate_bits = config.fri_config.rate_bits;
+    // Committed polynomials may have a higher degree than the trace, in zero-knowledge mode.
+    let committed_degree_bits = config.committed_degree_bits(degree_bits);
+    let mask_bits = committed_degree_bits - degree_bits;
 
-    let quotient_degree_bits = log2_ceil(stark.quotient_degree_factor());
+    // The quotients have degree less than `2^quotient_degree_bits` times the degree bound of
+    // committed polynomials.
+    let quotient_degree_bits =
+        log2_ceil(stark.quotient_degree_factor() + usize::from(config.zero_knowledge));
     assert!(
         quotient_degree_bits <= rate_bits,
         "Having constraints of degree higher than the rate is not supported yet."
     );
     let step = 1 << (rate_bits - quotient_degree_bits);
     // When opening the `Z`s polys at the "next" point, need to look at the point `next_step` steps away.
-    let next_step = 1 << quotient_degree_bits;
+    let next_step = 1 << (quotient_degree_bits + mask_bits);
 
     // Evaluation of the first Lagrange polynomial on the LDE domain.
-    let lagrange_first = PolynomialValues::selector(degree, 0).lde_onto_coset(quotient_degree_bits);
+    let lagrange_first =
+        PolynomialValues::selector(degree, 0).lde_onto_coset(quotient_degree_bits + mask_bits);
     // Evaluation of the last Lagrange polynomial on the LDE domain.
-    let lagrange_last =
-        PolynomialValues::selector(degree, degree - 1).lde_onto_coset(quotient_degree_bits);
+    let lagrange_last = PolynomialValues::selector(degree, degree - 1)
+        .lde_onto_coset(quotient_degree_bits + mask_bits);
 
-    let z_h_on_coset = ZeroPolyOnCoset::<F>::new(degree_bits, quotient_degree_bits);
+    let z_h_on_coset = ZeroPolyOnCoset::<F>::new(degree_bits, quotient_degree_bits + mask_bits);
+
+    // The auxiliary polynomials start with the permutation Zs, followed by lookup helper columns
+    // and cross-table lookup Zs.
+    let num_permutation_zs = stark.num_permutation_batches(config);
+    let num_auxiliary_polys = stark.num_auxiliary_polys(config);
 
     // Retrieve the LDE values at index `i`.
     let get_trace_values_packed =
@@ -237,50 +536,107 @@ where
 
     // Last element of the subgroup.
     let last = F::primitive_root_of_unity(degree_bits).inverse();
-    let size = degree << quotient_degree_bits;
-    let coset = F::cyclic_subgroup_coset_known_order(
-        F::primitive_root_of_unity(degree_bits + quotient_degree_bits),
-        F::coset_shift(),
-        size,
-    );
+    // The last `FRAME_ROWS - 1` elements of the subgroup, where windowed constraints don't apply.
+    let last_rows = last.powers().skip(1).take(S::FRAME_ROWS - 1).collect_vec();
+    let size = 1 << (committed_degree_bits + quotient_degree_bits);
+    // The LDE domain is the coset `shift * subgroup`.
+    let subgroup =
+        domain_cache::two_adic_subgroup::<F>(committed_degree_bits + quotient_degree_bits);
 
-    // We will step by `P::WIDTH`, and in each iteration, evaluate the quotient polynomial at
-    // a batch of `P::WIDTH` points.
-    let quotient_values = (0..size)
-        .into_par_iter()
-        .step_by(P::WIDTH)
-        .flat_map_iter(|i_start| {
+    let num_challenges = alphas.len();
+    // The quotient evaluations, stored point by point in a single buffer. We will step by
+    // `P::WIDTH`, and in each iteration, evaluate the quotient polynomials at a batch of
+    // `P::WIDTH` points.
+    let mut quotient_values = vec![F::ZERO; size * num_challenges];
+    quotient_values
+        .par_chunks_mut(P::WIDTH * num_challenges)
+        .enumerate()
+        .for_each(|(batch, out)| {
+            let i_start = batch * P::WIDTH;
             let i_next_start = (i_start + next_step) % size;
             let i_range = i_start..i_start + P::WIDTH;
 
-            let x = *P::from_slice(&coset[i_range.clone()]);
+            let x = *P::from_slice(&subgroup[i_range.clone()]) * F::coset_shift();
             let z_last = x - last;
+            let z_window = last_rows
+                .iter()
+                .fold(P::ONES, |acc, &last_row| acc * (x - last_row));
             let lagrange_basis_first = *P::from_slice(&lagrange_first.values[i_range.clone()]);
             let lagrange_basis_last = *P::from_slice(&lagrange_last.values[i_range]);
 
             let mut consumer = ConstraintConsumer::new(
                 alphas.clone(),
                 z_last,
+                z_window,
                 lagrange_basis_first,
                 lagrange_basis_last,
             );
+            // The frame's rows are `next_step` apart.
+            let row_starts = (0..S::FRAME_ROWS)
+                .map(|row| (i_start + row * next_step) % size)
+                .collect_vec();
+            let rows = row_starts
+                .iter()
+                .map(|&i| get_trace_values_packed(i))
+                .collect_vec();
+            let preprocessed_rows = preprocessed_commitment
+                .map(|commitment| {
+                    row_starts
+                        .iter()
+                        .map(|&i| commitment.get_lde_values_packed(i, step))
+                        .collect_vec()
+                })
+                .unwrap_or_default();
             let vars = S::EvaluationFrame::from_values(
-                &get_trace_values_packed(i_start),
-                &get_trace_values_packed(i_next_start),
+                &rows.iter().map(|row| &row[..]).collect_vec(),
+                &preprocessed_rows.iter().map(|row| &row[..]).collect_vec(),
                 public_inputs,
             );
-            let permutation_check_data = permutation_zs_commitment_challenges.as_ref().map(
-                |(permutation_zs_commitment, permutation_challenge_sets)| PermutationCheckVars {
-                    local_zs: permutation_zs_commitment.get_lde_values_packed(i_start, step),
-                    next_zs: permutation_zs_commitment.get_lde_values_packed(i_next_start, step),
-                    permutation_challenge_sets: permutation_challenge_sets.to_vec(),
-                },
-            );
+            let auxiliary_values = auxiliary_polys_commitment.map(|commitment| {
+                (
+                    commitment.get_lde_values_packed(i_start, step),
+                    commitment.get_lde_values_packed(i_next_start, step),
+                )
+            });
+            let permutation_check_data =
+                permutation_challenge_sets.map(|permutation_challenge_sets| {
+                    let (local, next) = auxiliary_values.as_ref().unwrap();
+                    PermutationCheckVars {
+                        local_zs: local[..num_permutation_zs].to_vec(),
+                        next_zs: next[..num_permutation_zs].to_vec(),
+                        permutation_challenge_sets: permutation_challenge_sets.to_vec(),
+                    }
+                });
+            let lookup_vars = lookup_challenges.map(|challenges| {
+                let (local, next) = auxiliary_values.as_ref().unwrap();
+                LookupCheckVars {
+                    local_values: local[num_permutation_zs..num_auxiliary_polys].to_vec(),
+                    next_values: next[num_permutation_zs..num_auxiliary_polys].to_vec(),
+                    challenges: challenges.to_vec(),
+                }
+            });
+            let ctl_vars = ctl_data
+                .zs_columns
+                .iter()
+                .enumerate()
+                .map(|(i, zs_columns)| {
+                    let (local, next) = auxiliary_values.as_ref().unwrap();
+                    CtlCheckVars::<F, F, P, 1> {
+                        local_z: local[num_auxiliary_polys + i],
+                        next_z: next[num_auxiliary_polys + i],
+                        challenges: zs_columns.challenge,
+                        columns: &zs_columns.columns,
+                        filter_column: &zs_columns.filter_column,
+                    }
+                })
+                .collect::<Vec<_>>();
             eval_vanishing_poly::<F, F, P, S, D, 1>(
                 stark,
                 config,
                 &vars,
                 permutation_check_data,
+                lookup_vars,
+                &ctl_vars,
                 &mut consumer,
             );
 
@@ -292,19 +648,19 @@ where
                 *eval *= denominator_inv;
             }
 
-            let num_challenges = alphas.len();
-
-            (0..P::WIDTH).map(move |i| {
-                (0..num_challenges)
-                    .map(|j| constraints_evals[j].as_slice()[i])
-                    .collect()
-            })
-        })
-        .collect::<Vec<_>>();
+            for (j, eval) in constraints_evals.iter().enumerate() {
+                for (i, &value) in eval.as_slice().iter().enumerate() {
+                    out[i * num_challenges + j] = value;
+                }
+            }
+        });
 
-    transpose(&quotient_values)
+    (0..num_challenges)
         .into_par_iter()
-        .map(PolynomialValues::new)
+        .map(|j| {
+            let values = quotient_values.iter().skip(j).step_by(num_challenges);
+            PolynomialValues::new(values.copied().collect())
+        })
         .map(|values| values.coset_ifft(F::coset_shift()))
         .collect()
 }
@@ -1,22 +1,26 @@
 use alloc::vec::Vec;
 use core::iter::once;
+use core::ops::RangeInclusive;
 
 use anyhow::{ensure, Result};
 use itertools::Itertools;
 use plonky2::field::extension::Extendable;
-use plonky2::field::types::Field;
-use plonky2::fri::witness_util::set_fri_proof_target;
+use plonky2::fri::witness_util::{set_fri_proof_target, set_fri_proof_target_with_variable_degree};
+use plonky2::fri::FriParams;
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::iop::target::{BoolTarget, Target};
 use plonky2::iop::witness::Witness;
 use plonky2::plonk::circuit_builder::CircuitBuilder;
 use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2::plonk::plonk_common::salt_size;
 use plonky2::util::reducing::ReducingFactorTarget;
 use plonky2::with_context;
 
 use crate::config::StarkConfig;
 use crate::constraint_consumer::RecursiveConstraintConsumer;
 use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::lookup::LookupCheckVarsTarget;
 use crate::permutation::PermutationCheckDataTarget;
 use crate::proof::{
     StarkOpeningSetTarget, StarkProof, StarkProofChallengesTarget, StarkProofTarget,
@@ -39,11 +43,22 @@ pub fn verify_stark_proof_circuit<
     C::Hasher: AlgebraicHasher<F>,
 {
     assert_eq!(proof_with_pis.public_inputs.len(), S::PUBLIC_INPUTS);
+    assert!(
+        !stark.uses_preprocessed_columns(),
+        "Recursive verification of preprocessed columns is not supported."
+    );
     let degree_bits = proof_with_pis.proof.recover_degree_bits(inner_config);
+    let degree_flags = [builder._true()];
     let challenges = with_context!(
         builder,
         "compute challenges",
-        proof_with_pis.get_challenges::<F, C, S>(builder, &stark, inner_config)
+        proof_with_pis.get_challenges::<F, C, S>(
+            builder,
+            &stark,
+            inner_config,
+            &[inner_config.fri_params(degree_bits)],
+            &degree_flags,
+        )
     );
 
     verify_stark_proof_with_challenges_circuit::<F, C, S, D>(
@@ -52,11 +67,86 @@ pub fn verify_stark_proof_circuit<
         proof_with_pis,
         challenges,
         inner_config,
+        &[degree_bits],
+        &degree_flags,
+    );
+}
+
+/// A STARK proof target which can hold proofs of traces of several lengths, so that a single
+/// recursive circuit can verify all of them.
+#[derive(Clone, Debug)]
+pub struct StarkProofWithVariableDegreeTarget<const D: usize> {
+    /// Sized for the largest supported trace.
+    pub proof_with_pis: StarkProofWithPublicInputsTarget<D>,
+    /// The log of the trace length of the proof. It is range-checked by
+    /// `verify_stark_proof_with_variable_degree_circuit`, and can be registered as a public input
+    /// if the outer verifier needs to know it.
+    pub degree_bits: Target,
+    pub degree_bits_range: RangeInclusive<usize>,
+}
+
+/// Like `verify_stark_proof_circuit`, for a proof whose trace length is only known when proving.
+pub fn verify_stark_proof_with_variable_degree_circuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: S,
+    proof: &StarkProofWithVariableDegreeTarget<D>,
+    inner_config: &StarkConfig,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let StarkProofWithVariableDegreeTarget {
+        proof_with_pis,
         degree_bits,
+        degree_bits_range,
+    } = proof;
+    assert_eq!(proof_with_pis.public_inputs.len(), S::PUBLIC_INPUTS);
+
+    // Exactly one flag is set iff `degree_bits` is in range.
+    let supported_degree_bits = degree_bits_range.clone().collect_vec();
+    let degree_flags = supported_degree_bits
+        .iter()
+        .map(|&d| {
+            let d = builder.constant(F::from_canonical_usize(d));
+            builder.is_equal(*degree_bits, d)
+        })
+        .collect_vec();
+    let num_selected = builder.add_many(degree_flags.iter().map(|flag| flag.target));
+    builder.assert_one(num_selected);
+
+    let fri_params = supported_degree_bits
+        .iter()
+        .map(|&d| inner_config.fri_params(d))
+        .collect_vec();
+    let challenges = with_context!(
+        builder,
+        "compute challenges",
+        proof_with_pis.get_challenges::<F, C, S>(
+            builder,
+            &stark,
+            inner_config,
+            &fri_params,
+            &degree_flags,
+        )
+    );
+
+    verify_stark_proof_with_challenges_circuit::<F, C, S, D>(
+        builder,
+        stark,
+        proof_with_pis.clone(),
+        challenges,
+        inner_config,
+        &supported_degree_bits,
+        &degree_flags,
     );
 }
 
-/// Recursively verifies an inner proof.
+/// Recursively verifies an inner proof, whose trace has length `2^degree_bits[i]` for the only
+/// true `degree_flags[i]`. The flags are ignored if there is a single degree.
 fn verify_stark_proof_with_challenges_circuit<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
@@ -68,11 +158,12 @@ fn verify_stark_proof_with_challenges_circuit<
     proof_with_pis: StarkProofWithPublicInputsTarget<D>,
     challenges: StarkProofChallengesTarget<D>,
     inner_config: &StarkConfig,
-    degree_bits: usize,
+    degree_bits: &[usize],
+    degree_flags: &[BoolTarget],
 ) where
     C::Hasher: AlgebraicHasher<F>,
 {
-    check_permutation_options(&stark, &proof_with_pis, &challenges).unwrap();
+    check_auxiliary_options(&stark, &proof_with_pis, &challenges).unwrap();
     let one = builder.one_extension();
 
     let StarkProofWithPublicInputsTarget {
@@ -82,43 +173,88 @@ fn verify_stark_proof_with_challenges_circuit<
     let StarkOpeningSetTarget {
         local_values,
         next_values,
-        permutation_zs,
-        permutation_zs_next,
+        auxiliary_polys,
+        auxiliary_polys_next,
         quotient_polys,
+        window_values,
     } = &proof.openings;
 
+    let rows = [local_values, next_values]
+        .into_iter()
+        .chain(window_values)
+        .map(|row| &row[..])
+        .collect_vec();
     let vars = S::EvaluationFrameTarget::from_values(
-        local_values,
-        next_values,
+        &rows,
+        &[],
         &public_inputs
             .into_iter()
             .map(|t| builder.convert_to_ext(t))
             .collect::<Vec<_>>(),
     );
 
-    let zeta_pow_deg = builder.exp_power_of_2_extension(challenges.stark_zeta, degree_bits);
+    let zeta_pow_deg = {
+        let mut power = challenges.stark_zeta;
+        let mut power_log = 0;
+        let powers = degree_bits
+            .iter()
+            .map(|&d| {
+                power = builder.exp_power_of_2_extension(power, d - power_log);
+                power_log = d;
+                power
+            })
+            .collect_vec();
+        match &powers[..] {
+            [power] => *power,
+            _ => builder.select_one_hot_ext(degree_flags, &powers),
+        }
+    };
     let z_h_zeta = builder.sub_extension(zeta_pow_deg, one);
-    let (l_0, l_last) =
-        eval_l_0_and_l_last_circuit(builder, degree_bits, challenges.stark_zeta, z_h_zeta);
-    let last =
-        builder.constant_extension(F::Extension::primitive_root_of_unity(degree_bits).inverse());
+    let n = constant_by_degree(builder, degree_bits, degree_flags, |d| {
+        F::from_canonical_usize(1 << d)
+    });
+    let g = constant_by_degree(
+        builder,
+        degree_bits,
+        degree_flags,
+        F::primitive_root_of_unity,
+    );
+    let (l_0, l_last) = eval_l_0_and_l_last_circuit(builder, n, g, challenges.stark_zeta, z_h_zeta);
+    let last = constant_by_degree(builder, degree_bits, degree_flags, |d| {
+        F::primitive_root_of_unity(d).inverse()
+    });
+    let last = builder.convert_to_ext(last);
     let z_last = builder.sub_extension(challenges.stark_zeta, last);
+    let mut z_window = z_last;
+    let mut last_row = last;
+    for _ in 2..S::FRAME_ROWS {
+        last_row = builder.mul_extension(last_row, last);
+        let factor = builder.sub_extension(challenges.stark_zeta, last_row);
+        z_window = builder.mul_extension(z_window, factor);
+    }
 
     let mut consumer = RecursiveConstraintConsumer::<F, D>::new(
         builder.zero_extension(),
         challenges.stark_alphas,
         z_last,
+        z_window,
         l_0,
         l_last,
     );
 
+    let num_permutation_zs = stark.num_permutation_batches(inner_config);
     let permutation_data = stark
         .uses_permutation_args()
         .then(|| PermutationCheckDataTarget {
-            local_zs: permutation_zs.as_ref().unwrap().clone(),
-            next_zs: permutation_zs_next.as_ref().unwrap().clone(),
+            local_zs: auxiliary_polys.as_ref().unwrap()[..num_permutation_zs].to_vec(),
+            next_zs: auxiliary_polys_next.as_ref().unwrap()[..num_permutation_zs].to_vec(),
             permutation_challenge_sets: challenges.permutation_challenge_sets.unwrap(),
         });
+    let lookup_vars = stark.uses_lookups().then(|| LookupCheckVarsTarget {
+        local_values: auxiliary_polys.as_ref().unwrap()[num_permutation_zs..].to_vec(),
+        next_values: auxiliary_polys_next.as_ref().unwrap()[num_permutation_zs..].to_vec(),
+        challenges: challenges.lookup_challenges.unwrap(),
+    });
 
     with_context!(
         builder,
@@ -129,6 +265,7 @@ fn verify_stark_proof_with_challenges_circuit<
             inner_config,
             &vars,
             permutation_data,
+            lookup_vars,
             &mut consumer,
         )
     );
@@ -137,7 +274,7 @@ fn verify_stark_proof_with_challenges_circuit<
     // Check each polynomial identity, of the form `vanishing(x) = Z_H(x) quotient(x)`, at zeta.
     let mut scale = ReducingFactorTarget::new(zeta_pow_deg);
     for (i, chunk) in quotient_polys
-        .chunks(stark.quotient_degree_factor())
+        .chunks(stark.num_quotient_chunks(inner_config))
         .enumerate()
     {
         let recombined_quotient = scale.reduce(chunk, builder);
@@ -146,34 +283,54 @@ fn verify_stark_proof_with_challenges_circuit<
     }
 
     let merkle_caps = once(proof.trace_cap)
-        .chain(proof.permutation_zs_cap)
+        .chain(proof.auxiliary_polys_cap)
         .chain(once(proof.quotient_polys_cap))
         .collect_vec();
 
-    let fri_instance = stark.fri_instance_target(
-        builder,
-        challenges.stark_zeta,
-        F::primitive_root_of_unity(degree_bits),
-        inner_config,
-    );
-    builder.verify_fri_proof::<C>(
+    let fri_instance = stark.fri_instance_target(builder, challenges.stark_zeta, g, inner_config);
+    let fri_params = degree_bits
+        .iter()
+        .map(|&d| inner_config.fri_params(d))
+        .collect_vec();
+    builder.verify_fri_proof_with_variable_degree::<C>(
         &fri_instance,
         &proof.openings.to_fri_openings(),
         &challenges.fri_challenges,
         &merkle_caps,
         &proof.opening_proof,
-        &inner_config.fri_params(degree_bits),
+        &fri_params,
+        degree_flags,
     );
 }
 
+/// Returns `f(degree_bits[i])` for the only true `degree_flags[i]`.
+fn constant_by_degree<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    degree_bits: &[usize],
+    degree_flags: &[BoolTarget],
+    f: impl Fn(usize) -> F,
+) -> Target {
+    let values = degree_bits
+        .iter()
+        .map(|&d| builder.constant(f(d)))
+        .collect_vec();
+    match &values[..] {
+        [value] => *value,
+        _ => builder.select_one_hot(degree_flags, &values),
+    }
+}
+
+/// Evaluates the Lagrange polynomials of the first and last rows at `x`, where `n` is the trace
+/// length, `g` generates the trace domain and `z_x` is the domain's vanishing polynomial at `x`.
 fn eval_l_0_and_l_last_circuit<F: RichField + Extendable<D>, const D: usize>(
     builder: &mut CircuitBuilder<F, D>,
-    log_n: usize,
+    n: Target,
+    g: Target,
     x: ExtensionTarget<D>,
     z_x: ExtensionTarget<D>,
 ) -> (ExtensionTarget<D>, ExtensionTarget<D>) {
-    let n = builder.constant_extension(F::Extension::from_canonical_usize(1 << log_n));
-    let g = builder.constant_extension(F::Extension::primitive_root_of_unity(log_n));
+    let n = builder.convert_to_ext(n);
+    let g = builder.convert_to_ext(g);
     let one = builder.one_extension();
     let l_0_deno = builder.mul_sub_extension(n, x, n);
     let l_last_deno = builder.mul_sub_extension(g, x, one);
@@ -209,28 +366,78 @@ pub fn add_virtual_stark_proof<F: RichField + Extendable<D>, S: Stark<F, D>, con
     config: &StarkConfig,
     degree_bits: usize,
 ) -> StarkProofTarget<D> {
-    let fri_params = config.fri_params(degree_bits);
-    let cap_height = fri_params.config.cap_height;
+    add_virtual_stark_proof_with_fri_params(
+        builder,
+        stark,
+        config,
+        &[config.fri_params(degree_bits)],
+    )
+}
+
+/// Adds a proof target which can hold proofs of traces of length `2^degree_bits` for any
+/// `degree_bits` in `degree_bits_range`, to be verified by
+/// `verify_stark_proof_with_variable_degree_circuit`.
+pub fn add_virtual_stark_proof_with_variable_degree<
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: S,
+    config: &StarkConfig,
+    degree_bits_range: RangeInclusive<usize>,
+) -> StarkProofWithVariableDegreeTarget<D> {
+    assert!(!degree_bits_range.is_empty(), "No supported trace lengths");
+    let fri_params = degree_bits_range
+        .clone()
+        .map(|d| config.fri_params(d))
+        .collect_vec();
+    let proof = add_virtual_stark_proof_with_fri_params(builder, stark, config, &fri_params);
+    let public_inputs = builder.add_virtual_targets(S::PUBLIC_INPUTS);
+    StarkProofWithVariableDegreeTarget {
+        proof_with_pis: StarkProofWithPublicInputsTarget {
+            proof,
+            public_inputs,
+        },
+        degree_bits: builder.add_virtual_target(),
+        degree_bits_range,
+    }
+}
+
+/// Adds a proof target which can hold proofs with any of the FRI parameters `fri_params`.
+fn add_virtual_stark_proof_with_fri_params<
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: S,
+    config: &StarkConfig,
+    fri_params: &[FriParams],
+) -> StarkProofTarget<D> {
+    let cap_height = config.fri_config.cap_height;
 
     let num_leaves_per_oracle = once(S::COLUMNS)
         .chain(
             stark
-                .uses_permutation_args()
-                .then(|| stark.num_permutation_batches(config)),
+                .uses_auxiliary_polys()
+                .then(|| stark.num_auxiliary_polys(config)),
         )
-        .chain(once(stark.quotient_degree_factor() * config.num_challenges))
+        .chain(once(stark.num_quotient_polys(config)))
+        .map(|num_polys| num_polys + salt_size(config.zero_knowledge))
         .collect_vec();
 
-    let permutation_zs_cap = stark
-        .uses_permutation_args()
+    let auxiliary_polys_cap = stark
+        .uses_auxiliary_polys()
         .then(|| builder.add_virtual_cap(cap_height));
 
     StarkProofTarget {
         trace_cap: builder.add_virtual_cap(cap_height),
-        permutation_zs_cap,
+        auxiliary_polys_cap,
         quotient_polys_cap: builder.add_virtual_cap(cap_height),
         openings: add_stark_opening_set_target::<F, S, D>(builder, stark, config),
-        opening_proof: builder.add_virtual_fri_proof(&num_leaves_per_oracle, &fri_params),
+        opening_proof: builder
+            .add_virtual_fri_proof_with_variable_degree(&num_leaves_per_oracle, fri_params),
     }
 }
 
@@ -239,18 +446,19 @@ fn add_stark_opening_set_target<F: RichField + Extendable<D>, S: Stark<F, D>, co
     stark: S,
     config: &StarkConfig,
 ) -> StarkOpeningSetTarget<D> {
-    let num_challenges = config.num_challenges;
     StarkOpeningSetTarget {
         local_values: builder.add_virtual_extension_targets(S::COLUMNS),
         next_values: builder.add_virtual_extension_targets(S::COLUMNS),
-        permutation_zs: stark
-            .uses_permutation_args()
-            .then(|| builder.add_virtual_extension_targets(stark.num_permutation_batches(config))),
-        permutation_zs_next: stark
-            .uses_permutation_args()
-            .then(|| builder.add_virtual_extension_targets(stark.num_permutation_batches(config))),
-        quotient_polys: builder
-            .add_virtual_extension_targets(stark.quotient_degree_factor() * num_challenges),
+        auxiliary_polys: stark
+            .uses_auxiliary_polys()
+            .then(|| builder.add_virtual_extension_targets(stark.num_auxiliary_polys(config))),
+        auxiliary_polys_next: stark
+            .uses_auxiliary_polys()
+            .then(|| builder.add_virtual_extension_targets(stark.num_auxiliary_polys(config))),
+        quotient_polys: builder.add_virtual_extension_targets(stark.num_quotient_polys(config)),
+        window_values: (2..S::FRAME_ROWS)
+            .map(|_| builder.add_virtual_extension_targets(S::COLUMNS))
+            .collect(),
     }
 }
 
@@ -280,6 +488,49 @@ pub fn set_stark_proof_with_pis_target<F, C: GenericConfig<D, F = F>, W, const D
     set_stark_proof_target(witness, pt, proof);
 }
 
+/// Sets a target created with `add_virtual_stark_proof_with_variable_degree`.
+pub fn set_stark_proof_with_variable_degree_target<
+    F,
+    C: GenericConfig<D, F = F>,
+    W,
+    const D: usize,
+>(
+    witness: &mut W,
+    proof_target: &StarkProofWithVariableDegreeTarget<D>,
+    stark_proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+    config: &StarkConfig,
+) where
+    F: RichField + Extendable<D>,
+    C::Hasher: AlgebraicHasher<F>,
+    W: Witness<F>,
+{
+    let StarkProofWithPublicInputs {
+        proof,
+        public_inputs,
+    } = stark_proof_with_pis;
+    let StarkProofWithPublicInputsTarget {
+        proof: pt,
+        public_inputs: pi_targets,
+    } = &proof_target.proof_with_pis;
+
+    let degree_bits = proof.recover_degree_bits(config);
+    assert!(
+        proof_target.degree_bits_range.contains(&degree_bits),
+        "Unsupported trace length 2^{degree_bits}"
+    );
+    witness.set_target(
+        proof_target.degree_bits,
+        F::from_canonical_usize(degree_bits),
+    );
+
+    for (&pi_t, &pi) in pi_targets.iter().zip_eq(public_inputs) {
+        witness.set_target(pi_t, pi);
+    }
+
+    set_stark_proof_commitments_and_openings_target(witness, pt, proof);
+    set_fri_proof_target_with_variable_degree(witness, &pt.opening_proof, &proof.opening_proof);
+}
+
 pub fn set_stark_proof_target<F, C: GenericConfig<D, F = F>, W, const D: usize>(
     witness: &mut W,
     proof_target: &StarkProofTarget<D>,
@@ -289,6 +540,33 @@ pub fn set_stark_proof_target<F, C: GenericConfig<D, F = F>, W, const D: usize>(
     C::Hasher: AlgebraicHasher<F>,
     W: Witness<F>,
 {
+    set_stark_proof_commitments_and_openings_target(witness, proof_target, proof);
+    set_fri_proof_target(witness, &proof_target.opening_proof, &proof.opening_proof);
+}
+
+/// Sets all targets of a STARK proof, except those of its FRI proof.
+fn set_stark_proof_commitments_and_openings_target<
+    F,
+    C: GenericConfig<D, F = F>,
+    W,
+    const D: usize,
+>(
+    witness: &mut W,
+    proof_target: &StarkProofTarget<D>,
+    proof: &StarkProof<F, C, D>,
+) where
+    F: RichField + Extendable<D>,
+    C::Hasher: AlgebraicHasher<F>,
+    W: Witness<F>,
+{
+    assert!(
+        proof.openings.ctl_zs_first.is_none(),
+        "Recursive verification of cross-table lookups is not supported."
+    );
+    assert!(
+        proof.openings.preprocessed_values.is_none(),
+        "Recursive verification of preprocessed columns is not supported."
+    );
     witness.set_cap_target(&proof_target.trace_cap, &proof.trace_cap);
     witness.set_cap_target(&proof_target.quotient_polys_cap, &proof.quotient_polys_cap);
 
@@ -297,33 +575,39 @@ pub fn set_stark_proof_target<F, C: GenericConfig<D, F = F>, W, const D: usize>(
         &proof.openings.to_fri_openings(),
     );
 
-    if let (Some(permutation_zs_cap_target), Some(permutation_zs_cap)) =
-        (&proof_target.permutation_zs_cap, &proof.permutation_zs_cap)
-    {
-        witness.set_cap_target(permutation_zs_cap_target, permutation_zs_cap);
+    if let (Some(permutation_zs_cap_target), Some(auxiliary_polys_cap)) = (
+        &proof_target.auxiliary_polys_cap,
+        &proof.auxiliary_polys_cap,
+    ) {
+        witness.set_cap_target(permutation_zs_cap_target, auxiliary_polys_cap);
     }
-
-    set_fri_proof_target(witness, &proof_target.opening_proof, &proof.opening_proof);
 }
 
-/// Utility function to check that all permutation data wrapped in `Option`s are `Some` iff
-/// the Stark uses a permutation argument.
-fn check_permutation_options<F: RichField + Extendable<D>, S: Stark<F, D>, const D: usize>(
+/// Utility function to check that all auxiliary data wrapped in `Option`s are `Some` iff the
+/// Stark uses the corresponding argument.
+fn check_auxiliary_options<F: RichField + Extendable<D>, S: Stark<F, D>, const D: usize>(
     stark: &S,
     proof_with_pis: &StarkProofWithPublicInputsTarget<D>,
     challenges: &StarkProofChallengesTarget<D>,
 ) -> Result<()> {
-    let options_is_some = [
-        proof_with_pis.proof.permutation_zs_cap.is_some(),
-        proof_with_pis.proof.openings.permutation_zs.is_some(),
-        proof_with_pis.proof.openings.permutation_zs_next.is_some(),
-        challenges.permutation_challenge_sets.is_some(),
+    let auxiliary_options_is_some = [
+        proof_with_pis.proof.auxiliary_polys_cap.is_some(),
+        proof_with_pis.proof.openings.auxiliary_polys.is_some(),
+        proof_with_pis.proof.openings.auxiliary_polys_next.is_some(),
     ];
     ensure!(
-        options_is_some
+        auxiliary_options_is_some
             .into_iter()
-            .all(|b| b == stark.uses_permutation_args()),
+            .all(|b| b == stark.uses_auxiliary_polys()),
+        "Auxiliary polynomials don't match with Stark configuration."
+    );
+    ensure!(
+        challenges.permutation_challenge_sets.is_some() == stark.uses_permutation_args(),
         "Permutation data doesn't match with Stark configuration."
     );
+    ensure!(
+        challenges.lookup_challenges.is_some() == stark.uses_lookups(),
+        "Lookup data doesn't match with Stark configuration."
+    );
     Ok(())
 }
@@ -3,18 +3,22 @@ use alloc::vec::Vec;
 
 use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::packed::PackedField;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::Field;
 use plonky2::fri::structure::{
     FriBatchInfo, FriBatchInfoTarget, FriInstanceInfo, FriInstanceInfoTarget, FriOracleInfo,
     FriPolynomialInfo,
 };
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::ext_target::ExtensionTarget;
+use plonky2::iop::target::Target;
 use plonky2::plonk::circuit_builder::CircuitBuilder;
 use plonky2::util::ceil_div_usize;
 
 use crate::config::StarkConfig;
 use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
 use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::lookup::Lookup;
 use crate::permutation::PermutationPair;
 
 /// Represents a STARK system.
@@ -22,6 +26,10 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
     /// The total number of columns in the trace.
     const COLUMNS: usize = Self::EvaluationFrameTarget::COLUMNS;
     const PUBLIC_INPUTS: usize = Self::EvaluationFrameTarget::PUBLIC_INPUTS;
+    /// The number of preprocessed columns, see [`Stark::preprocessed_columns`].
+    const PREPROCESSED_COLUMNS: usize = Self::EvaluationFrameTarget::PREPROCESSED_COLUMNS;
+    /// The number of consecutive rows each constraint evaluation can access.
+    const FRAME_ROWS: usize = Self::EvaluationFrameTarget::ROWS;
 
     /// This is used to evaluate constraints natively.
     type EvaluationFrame<FE, P, const D2: usize>: StarkEvaluationFrame<P, FE>
@@ -83,15 +91,31 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         1.max(self.constraint_degree() - 1)
     }
 
+    /// The number of chunks each quotient polynomial is split into, each holding `n` of its
+    /// coefficients. Masking in zero-knowledge mode up to doubles the degree of committed
+    /// polynomials but not the degree of `Z_H`, so quotients then need twice as many chunks, plus
+    /// one.
+    fn num_quotient_chunks(&self, config: &StarkConfig) -> usize {
+        if config.zero_knowledge {
+            2 * self.quotient_degree_factor() + 1
+        } else {
+            self.quotient_degree_factor()
+        }
+    }
+
     fn num_quotient_polys(&self, config: &StarkConfig) -> usize {
-        self.quotient_degree_factor() * config.num_challenges
+        self.num_quotient_chunks(config) * config.num_challenges
     }
 
-    /// Computes the FRI instance used to prove this Stark.
+    /// Computes the FRI instance used to prove this Stark. `num_ctl_zs` is the number of
+    /// cross-table lookup `Z`s appended to the auxiliary polynomials, which are also opened at 1.
+    /// Preprocessed columns, if any, form the last oracle. For frames wider than two rows, the
+    /// trace and preprocessed columns are also opened at `g^i zeta` for `2 <= i < FRAME_ROWS`.
     fn fri_instance(
         &self,
         zeta: F::Extension,
         g: F,
+        num_ctl_zs: usize,
         config: &StarkConfig,
     ) -> FriInstanceInfo<F, D> {
         let mut oracles = vec![];
@@ -99,42 +123,78 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         let trace_info = FriPolynomialInfo::from_range(oracles.len(), 0..Self::COLUMNS);
         oracles.push(FriOracleInfo {
             num_polys: Self::COLUMNS,
-            blinding: false,
+            blinding: config.zero_knowledge,
         });
 
-        let permutation_zs_info = if self.uses_permutation_args() {
-            let num_z_polys = self.num_permutation_batches(config);
-            let polys = FriPolynomialInfo::from_range(oracles.len(), 0..num_z_polys);
+        let num_auxiliary_polys = self.num_auxiliary_polys(config) + num_ctl_zs;
+        let auxiliary_oracle = oracles.len();
+        let auxiliary_polys_info = if num_auxiliary_polys > 0 {
+            let polys = FriPolynomialInfo::from_range(auxiliary_oracle, 0..num_auxiliary_polys);
             oracles.push(FriOracleInfo {
-                num_polys: num_z_polys,
-                blinding: false,
+                num_polys: num_auxiliary_polys,
+                blinding: config.zero_knowledge,
             });
             polys
         } else {
             vec![]
         };
 
-        let num_quotient_polys = self.quotient_degree_factor() * config.num_challenges;
+        let num_quotient_polys = self.num_quotient_polys(config);
         let quotient_info = FriPolynomialInfo::from_range(oracles.len(), 0..num_quotient_polys);
         oracles.push(FriOracleInfo {
             num_polys: num_quotient_polys,
-            blinding: false,
+            blinding: config.zero_knowledge,
         });
 
+        // Preprocessed columns are public, so they are never blinded.
+        let preprocessed_info = if self.uses_preprocessed_columns() {
+            let polys = FriPolynomialInfo::from_range(oracles.len(), 0..Self::PREPROCESSED_COLUMNS);
+            oracles.push(FriOracleInfo {
+                num_polys: Self::PREPROCESSED_COLUMNS,
+                blinding: false,
+            });
+            polys
+        } else {
+            vec![]
+        };
+
         let zeta_batch = FriBatchInfo {
             point: zeta,
             polynomials: [
                 trace_info.clone(),
-                permutation_zs_info.clone(),
+                auxiliary_polys_info.clone(),
                 quotient_info,
+                preprocessed_info.clone(),
             ]
             .concat(),
         };
         let zeta_next_batch = FriBatchInfo {
             point: zeta.scalar_mul(g),
-            polynomials: [trace_info, permutation_zs_info].concat(),
+            polynomials: [
+                trace_info.clone(),
+                auxiliary_polys_info,
+                preprocessed_info.clone(),
+            ]
+            .concat(),
         };
-        let batches = vec![zeta_batch, zeta_next_batch];
+        let mut batches = vec![zeta_batch, zeta_next_batch];
+        let window_polys = [trace_info, preprocessed_info].concat();
+        for i in 2..Self::FRAME_ROWS {
+            batches.push(FriBatchInfo {
+                point: zeta.scalar_mul(g.exp_u64(i as u64)),
+                polynomials: window_polys.clone(),
+            });
+        }
+
+        if num_ctl_zs > 0 {
+            batches.push(FriBatchInfo {
+                point: F::Extension::ONE,
+                polynomials: FriPolynomialInfo::from_range(
+                    auxiliary_oracle,
+                    num_auxiliary_polys - num_ctl_zs..num_auxiliary_polys,
+                ),
+            });
+        }
 
         FriInstanceInfo { oracles, batches }
     }
@@ -144,7 +204,7 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         &self,
         builder: &mut CircuitBuilder<F, D>,
         zeta: ExtensionTarget<D>,
-        g: F,
+        g: Target,
         config: &StarkConfig,
     ) -> FriInstanceInfoTarget<D> {
         let mut oracles = vec![];
@@ -152,47 +212,66 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
         let trace_info = FriPolynomialInfo::from_range(oracles.len(), 0..Self::COLUMNS);
         oracles.push(FriOracleInfo {
             num_polys: Self::COLUMNS,
-            blinding: false,
+            blinding: config.zero_knowledge,
         });
 
-        let permutation_zs_info = if self.uses_permutation_args() {
-            let num_z_polys = self.num_permutation_batches(config);
-            let polys = FriPolynomialInfo::from_range(oracles.len(), 0..num_z_polys);
+        let auxiliary_polys_info = if self.uses_auxiliary_polys() {
+            let num_auxiliary_polys = self.num_auxiliary_polys(config);
+            let polys = FriPolynomialInfo::from_range(oracles.len(), 0..num_auxiliary_polys);
             oracles.push(FriOracleInfo {
-                num_polys: num_z_polys,
-                blinding: false,
+                num_polys: num_auxiliary_polys,
+                blinding: config.zero_knowledge,
             });
             polys
         } else {
             vec![]
         };
 
-        let num_quotient_polys = self.quotient_degree_factor() * config.num_challenges;
+        let num_quotient_polys = self.num_quotient_polys(config);
         let quotient_info = FriPolynomialInfo::from_range(oracles.len(), 0..num_quotient_polys);
         oracles.push(FriOracleInfo {
             num_polys: num_quotient_polys,
-            blinding: false,
+            blinding: config.zero_knowledge,
         });
 
         let zeta_batch = FriBatchInfoTarget {
             point: zeta,
             polynomials: [
                 trace_info.clone(),
-                permutation_zs_info.clone(),
+                auxiliary_polys_info.clone(),
                 quotient_info,
             ]
             .concat(),
         };
-        let zeta_next = builder.mul_const_extension(g, zeta);
+        let mut zeta_next = builder.scalar_mul_ext(g, zeta);
         let zeta_next_batch = FriBatchInfoTarget {
             point: zeta_next,
-            polynomials: [trace_info, permutation_zs_info].concat(),
+            polynomials: [trace_info.clone(), auxiliary_polys_info].concat(),
         };
-        let batches = vec![zeta_batch, zeta_next_batch];
+        let mut batches = vec![zeta_batch, zeta_next_batch];
+        for _ in 2..Self::FRAME_ROWS {
+            zeta_next = builder.scalar_mul_ext(g, zeta_next);
+            batches.push(FriBatchInfoTarget {
+                point: zeta_next,
+                polynomials: trace_info.clone(),
+            });
+        }
 
         FriInstanceInfoTarget { oracles, batches }
     }
 
+    /// The values of the preprocessed columns for a trace of length `2^degree_bits`. These are
+    /// fixed columns, such as round constants or selectors, which are committed once in a
+    /// [`PreprocessedData`](crate::preprocessed::PreprocessedData) rather than in every trace.
+    /// Must return `Self::PREPROCESSED_COLUMNS` columns. Empty by default.
+    fn preprocessed_columns(&self, _degree_bits: usize) -> Vec<PolynomialValues<F>> {
+        vec![]
+    }
+
+    fn uses_preprocessed_columns(&self) -> bool {
+        Self::PREPROCESSED_COLUMNS > 0
+    }
+
     /// Pairs of lists of columns that should be permutations of one another. A permutation argument
     /// will be used for each such pair. Empty by default.
     fn permutation_pairs(&self) -> Vec<PermutationPair> {
@@ -222,4 +301,32 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
             self.permutation_batch_size(),
         )
     }
+
+    /// Lookups of some columns into a table column of the same trace. A logUp argument will be
+    /// used for each of them. Empty by default.
+    fn lookups(&self) -> Vec<Lookup> {
+        vec![]
+    }
+
+    fn uses_lookups(&self) -> bool {
+        !self.lookups().is_empty()
+    }
+
+    fn num_lookup_helper_columns(&self, config: &StarkConfig) -> usize {
+        self.lookups()
+            .iter()
+            .map(|lookup| lookup.num_helper_columns(self.constraint_degree()))
+            .sum::<usize>()
+            * config.num_challenges
+    }
+
+    /// Whether the proof commits to auxiliary polynomials, i.e. permutation `Z`s and lookup helper
+    /// columns, in that order.
+    fn uses_auxiliary_polys(&self) -> bool {
+        self.uses_permutation_args() || self.uses_lookups()
+    }
+
+    fn num_auxiliary_polys(&self, config: &StarkConfig) -> usize {
+        self.num_permutation_batches(config) + self.num_lookup_helper_columns(config)
+    }
 }
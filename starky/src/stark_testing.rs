@@ -1,8 +1,10 @@
-use alloc::vec;
 use alloc::vec::Vec;
+use alloc::{format, vec};
 
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Result};
 use plonky2::field::extension::{Extendable, FieldExtension};
+use plonky2::field::packable::Packable;
+use plonky2::field::packed::PackedField;
 use plonky2::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use plonky2::field::types::{Field, Sample};
 use plonky2::hash::hash_types::RichField;
@@ -27,26 +29,51 @@ pub fn test_stark_low_degree<F: RichField + Extendable<D>, S: Stark<F, D>, const
 
     let trace_ldes = random_low_degree_matrix::<F>(S::COLUMNS, rate_bits);
     let size = trace_ldes.len();
+    let preprocessed_ldes = if S::PREPROCESSED_COLUMNS > 0 {
+        random_low_degree_matrix::<F>(S::PREPROCESSED_COLUMNS, rate_bits)
+    } else {
+        vec![]
+    };
     let public_inputs = F::rand_vec(S::PUBLIC_INPUTS);
 
     let lagrange_first = PolynomialValues::selector(WITNESS_SIZE, 0).lde(rate_bits);
     let lagrange_last = PolynomialValues::selector(WITNESS_SIZE, WITNESS_SIZE - 1).lde(rate_bits);
 
     let last = F::primitive_root_of_unity(log2_strict(WITNESS_SIZE)).inverse();
+    let last_rows = last
+        .powers()
+        .skip(1)
+        .take(S::FRAME_ROWS - 1)
+        .collect::<Vec<_>>();
     let subgroup =
         F::cyclic_subgroup_known_order(F::primitive_root_of_unity(log2_strict(size)), size);
     let alpha = F::rand();
     let constraint_evals = (0..size)
         .map(|i| {
-            let vars = S::EvaluationFrame::from_values(
-                &trace_ldes[i],
-                &trace_ldes[(i + (1 << rate_bits)) % size],
-                &public_inputs,
-            );
+            let row_indices = (0..S::FRAME_ROWS)
+                .map(|row| (i + (row << rate_bits)) % size)
+                .collect::<Vec<_>>();
+            let rows = row_indices
+                .iter()
+                .map(|&j| &trace_ldes[j][..])
+                .collect::<Vec<_>>();
+            let preprocessed_rows = if preprocessed_ldes.is_empty() {
+                vec![]
+            } else {
+                row_indices
+                    .iter()
+                    .map(|&j| &preprocessed_ldes[j][..])
+                    .collect()
+            };
+            let vars = S::EvaluationFrame::from_values(&rows, &preprocessed_rows, &public_inputs);
 
             let mut consumer = ConstraintConsumer::<F>::new(
                 vec![alpha],
                 subgroup[i] - last,
+                last_rows
+                    .iter()
+                    .map(|&last_row| subgroup[i] - last_row)
+                    .product(),
                 lagrange_first.values[i],
                 lagrange_last.values[i],
             );
@@ -80,63 +107,335 @@ pub fn test_stark_circuit_constraints<
     stark: S,
 ) -> Result<()> {
     // Compute native constraint evaluation on random values.
+    let rows = (0..S::FRAME_ROWS)
+        .map(|_| F::Extension::rand_vec(S::COLUMNS))
+        .collect::<Vec<_>>();
+    let preprocessed_rows = (0..S::FRAME_ROWS)
+        .map(|_| F::Extension::rand_vec(S::PREPROCESSED_COLUMNS))
+        .collect::<Vec<_>>();
     let vars = S::EvaluationFrame::from_values(
-        &F::Extension::rand_vec(S::COLUMNS),
-        &F::Extension::rand_vec(S::COLUMNS),
+        &rows.iter().map(|row| &row[..]).collect::<Vec<_>>(),
+        &preprocessed_rows
+            .iter()
+            .map(|row| &row[..])
+            .collect::<Vec<_>>(),
         &F::Extension::rand_vec(S::PUBLIC_INPUTS),
     );
-    let alphas = F::rand_vec(1);
-    let z_last = F::Extension::rand();
-    let lagrange_first = F::Extension::rand();
-    let lagrange_last = F::Extension::rand();
-    let mut consumer = ConstraintConsumer::<F::Extension>::new(
-        alphas
+    let filters = [
+        F::Extension::rand(),
+        F::Extension::rand(),
+        F::Extension::rand(),
+        F::Extension::rand(),
+    ];
+
+    // Compute circuit constraint evaluation on same random values.
+    let circuit_config = CircuitConfig::standard_recursion_config();
+    let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
+    let mut pw = PartialWitness::<F>::new();
+    connect_circuit_constraints(&mut builder, &mut pw, &stark, &vars, filters, F::rand());
+
+    let data = builder.build::<C>();
+    let proof = data.prove(pw)?;
+    data.verify(proof)
+}
+
+/// Checks that `trace` satisfies the constraints of `stark` with the given public inputs, and
+/// returns an error naming the first failing row and constraint, constraints being indexed in the
+/// order `eval_packed_generic` gives them. Along the way, checks that the packed and extension
+/// field evaluations of the constraints agree with the base field one on every row, and that the
+/// recursive evaluation agrees on the first, middle and last rows.
+///
+/// Permutation arguments, lookups and cross-table lookups are not checked.
+pub fn test_stark_trace<F, C, S, const D: usize>(
+    stark: &S,
+    trace: &[PolynomialValues<F>],
+    public_inputs: &[F],
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+{
+    ensure!(
+        trace.len() == S::COLUMNS,
+        "Expected {} columns, got {}.",
+        S::COLUMNS,
+        trace.len()
+    );
+    ensure!(
+        public_inputs.len() == S::PUBLIC_INPUTS,
+        "Expected {} public inputs, got {}.",
+        S::PUBLIC_INPUTS,
+        public_inputs.len()
+    );
+    let n = trace[0].len();
+    let degree_bits = log2_strict(n);
+    let rows = transpose(
+        &trace
             .iter()
-            .copied()
-            .map(F::Extension::from_basefield)
-            .collect(),
+            .map(|col| col.values.clone())
+            .collect::<Vec<_>>(),
+    );
+    let preprocessed_rows = if S::PREPROCESSED_COLUMNS > 0 {
+        let preprocessed_columns = stark
+            .preprocessed_columns(degree_bits)
+            .into_iter()
+            .map(|col| col.values)
+            .collect::<Vec<_>>();
+        transpose(&preprocessed_columns)
+    } else {
+        vec![]
+    };
+    let frame = |rows: &[Vec<F>], i: usize| -> Vec<Vec<F>> {
+        if rows.is_empty() {
+            return vec![];
+        }
+        (0..S::FRAME_ROWS)
+            .map(|r| rows[(i + r) % n].clone())
+            .collect()
+    };
+
+    let subgroup = F::two_adic_subgroup(degree_bits);
+    let last = subgroup[n - 1];
+    let filters = |i: usize| -> [F; 4] {
+        let x = subgroup[i];
+        [
+            x - last,
+            last.powers()
+                .skip(1)
+                .take(S::FRAME_ROWS - 1)
+                .map(|last_row| x - last_row)
+                .product(),
+            F::from_bool(i == 0),
+            F::from_bool(i == n - 1),
+        ]
+    };
+
+    let base_evals = (0..n)
+        .map(|i| {
+            eval_recorded_constraints::<F, F, F, S, D, 1>(
+                &frame(&rows, i),
+                &frame(&preprocessed_rows, i),
+                public_inputs,
+                filters(i),
+                |vars, consumer| stark.eval_packed_base(vars, consumer),
+            )
+        })
+        .collect::<Vec<_>>();
+    for (i, evals) in base_evals.iter().enumerate() {
+        if let Some(j) = evals.iter().position(|eval| eval.is_nonzero()) {
+            bail!("Constraint {} is not satisfied on row {}.", j, i);
+        }
+    }
+
+    let to_ext = |values: Vec<Vec<F>>| -> Vec<Vec<F::Extension>> {
+        values
+            .into_iter()
+            .map(|row| row.into_iter().map(F::Extension::from_basefield).collect())
+            .collect()
+    };
+    let public_inputs_ext = public_inputs
+        .iter()
+        .copied()
+        .map(F::Extension::from_basefield)
+        .collect::<Vec<_>>();
+    let ext_frame = |i: usize| {
+        (
+            to_ext(frame(&rows, i)),
+            to_ext(frame(&preprocessed_rows, i)),
+            filters(i).map(F::Extension::from_basefield),
+        )
+    };
+    for (i, base_evals) in base_evals.iter().enumerate() {
+        let (rows, preprocessed_rows, filters) = ext_frame(i);
+        let ext_evals = eval_recorded_constraints::<F, F::Extension, F::Extension, S, D, D>(
+            &rows,
+            &preprocessed_rows,
+            &public_inputs_ext,
+            filters,
+            |vars, consumer| stark.eval_ext(vars, consumer),
+        );
+        if let Some(j) = ext_evals
+            .iter()
+            .zip(base_evals)
+            .position(|(&ext, &base)| ext != F::Extension::from_basefield(base))
+        {
+            bail!(
+                "The extension field evaluation of constraint {} on row {} differs from the base \
+                 field one.",
+                j,
+                i
+            );
+        }
+    }
+
+    type P<F> = <F as Packable>::Packing;
+    let width = P::<F>::WIDTH;
+    if n >= width {
+        let pack = |value: &dyn Fn(usize) -> F| -> P<F> {
+            *P::<F>::from_slice(&(0..width).map(value).collect::<Vec<_>>())
+        };
+        let pack_frame = |rows: &[Vec<F>], i: usize| -> Vec<Vec<P<F>>> {
+            let frames = (0..width).map(|k| frame(rows, i + k)).collect::<Vec<_>>();
+            (0..frames[0].len())
+                .map(|r| {
+                    (0..frames[0][r].len())
+                        .map(|c| pack(&|k| frames[k][r][c]))
+                        .collect()
+                })
+                .collect()
+        };
+        for i in (0..n).step_by(width) {
+            let packed_filters = [0, 1, 2, 3].map(|f| pack(&|k| filters(i + k)[f]));
+            let packed_evals = eval_recorded_constraints::<F, F, P<F>, S, D, 1>(
+                &pack_frame(&rows, i),
+                &pack_frame(&preprocessed_rows, i),
+                public_inputs,
+                packed_filters,
+                |vars, consumer| stark.eval_packed_base(vars, consumer),
+            );
+            for (j, packed_eval) in packed_evals.iter().enumerate() {
+                if let Some(k) =
+                    (0..width).find(|&k| packed_eval.as_slice()[k] != base_evals[i + k][j])
+                {
+                    bail!(
+                        "The packed evaluation of constraint {} on row {} differs from the base \
+                         field one.",
+                        j,
+                        i + k
+                    );
+                }
+            }
+        }
+    }
+
+    let mut checked_rows = vec![0, n / 2, n - 1];
+    checked_rows.dedup();
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let mut pw = PartialWitness::<F>::new();
+    for &i in &checked_rows {
+        let (rows, preprocessed_rows, filters) = ext_frame(i);
+        let vars = S::EvaluationFrame::from_values(
+            &rows.iter().map(|row| &row[..]).collect::<Vec<_>>(),
+            &preprocessed_rows
+                .iter()
+                .map(|row| &row[..])
+                .collect::<Vec<_>>(),
+            &public_inputs_ext,
+        );
+        connect_circuit_constraints(&mut builder, &mut pw, stark, &vars, filters, F::rand());
+    }
+    let data = builder.build::<C>();
+    data.prove(pw)
+        .and_then(|proof| data.verify(proof))
+        .map_err(|e| {
+            e.context(format!(
+                "The recursive evaluation of rows {checked_rows:?} differs from the native one"
+            ))
+        })
+}
+
+/// Evaluates `stark`'s constraints at the frame made of `rows`, with the filters `z_last`,
+/// `z_window` and the first and last row selectors set to `filters`, using `eval`, and returns
+/// each constraint, in order.
+pub(crate) fn eval_recorded_constraints<F, FE, P, S, const D: usize, const D2: usize>(
+    rows: &[Vec<P>],
+    preprocessed_rows: &[Vec<P>],
+    public_inputs: &[FE],
+    filters: [P; 4],
+    eval: impl FnOnce(&S::EvaluationFrame<FE, P, D2>, &mut ConstraintConsumer<P>),
+) -> Vec<P>
+where
+    F: RichField + Extendable<D>,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: PackedField<Scalar = FE>,
+    S: Stark<F, D>,
+{
+    let vars = S::EvaluationFrame::from_values(
+        &rows.iter().map(|row| &row[..]).collect::<Vec<_>>(),
+        &preprocessed_rows
+            .iter()
+            .map(|row| &row[..])
+            .collect::<Vec<_>>(),
+        public_inputs,
+    );
+    let [z_last, z_window, lagrange_first, lagrange_last] = filters;
+    let mut consumer =
+        ConstraintConsumer::new(vec![], z_last, z_window, lagrange_first, lagrange_last)
+            .recording();
+    eval(&vars, &mut consumer);
+    consumer.recorded_constraints().unwrap_or_default().to_vec()
+}
+
+/// Adds the recursive evaluation of `stark`'s constraints at `vars` to `builder`, with the filters
+/// `z_last`, `z_window` and the first and last row selectors set to `filters`, and connects it to
+/// the native evaluation, both combining constraints with `alpha`.
+fn connect_circuit_constraints<F, S, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    pw: &mut PartialWitness<F>,
+    stark: &S,
+    vars: &S::EvaluationFrame<F::Extension, F::Extension, D>,
+    filters: [F::Extension; 4],
+    alpha: F,
+) where
+    F: RichField + Extendable<D>,
+    S: Stark<F, D>,
+{
+    let [z_last, z_window, lagrange_first, lagrange_last] = filters;
+    let mut consumer = ConstraintConsumer::<F::Extension>::new(
+        vec![F::Extension::from_basefield(alpha)],
         z_last,
+        z_window,
         lagrange_first,
         lagrange_last,
     );
-    stark.eval_ext(&vars, &mut consumer);
+    stark.eval_ext(vars, &mut consumer);
     let native_eval = consumer.accumulators()[0];
-    // Compute circuit constraint evaluation on same random values.
-    let circuit_config = CircuitConfig::standard_recursion_config();
-    let mut builder = CircuitBuilder::<F, D>::new(circuit_config);
-    let mut pw = PartialWitness::<F>::new();
 
-    let locals_t = builder.add_virtual_extension_targets(S::COLUMNS);
-    pw.set_extension_targets(&locals_t, vars.get_local_values());
-    let nexts_t = builder.add_virtual_extension_targets(S::COLUMNS);
-    pw.set_extension_targets(&nexts_t, vars.get_next_values());
+    let rows_t = (0..S::FRAME_ROWS)
+        .map(|i| {
+            let row_t = builder.add_virtual_extension_targets(S::COLUMNS);
+            pw.set_extension_targets(&row_t, vars.get_row_values(i));
+            row_t
+        })
+        .collect::<Vec<_>>();
+    let preprocessed_rows_t = (0..S::FRAME_ROWS)
+        .map(|i| {
+            let row_t = builder.add_virtual_extension_targets(S::PREPROCESSED_COLUMNS);
+            pw.set_extension_targets(&row_t, vars.get_preprocessed_row_values(i));
+            row_t
+        })
+        .collect::<Vec<_>>();
     let pis_t = builder.add_virtual_extension_targets(S::PUBLIC_INPUTS);
     pw.set_extension_targets(&pis_t, vars.get_public_inputs());
     let alphas_t = builder.add_virtual_targets(1);
-    pw.set_target(alphas_t[0], alphas[0]);
-    let z_last_t = builder.add_virtual_extension_target();
-    pw.set_extension_target(z_last_t, z_last);
-    let lagrange_first_t = builder.add_virtual_extension_target();
-    pw.set_extension_target(lagrange_first_t, lagrange_first);
-    let lagrange_last_t = builder.add_virtual_extension_target();
-    pw.set_extension_target(lagrange_last_t, lagrange_last);
-
-    let vars = S::EvaluationFrameTarget::from_values(&locals_t, &nexts_t, &pis_t);
+    pw.set_target(alphas_t[0], alpha);
+    let [z_last_t, z_window_t, lagrange_first_t, lagrange_last_t] = filters.map(|filter| {
+        let filter_t = builder.add_virtual_extension_target();
+        pw.set_extension_target(filter_t, filter);
+        filter_t
+    });
+
+    let vars = S::EvaluationFrameTarget::from_values(
+        &rows_t.iter().map(|row| &row[..]).collect::<Vec<_>>(),
+        &preprocessed_rows_t
+            .iter()
+            .map(|row| &row[..])
+            .collect::<Vec<_>>(),
+        &pis_t,
+    );
     let mut consumer = RecursiveConstraintConsumer::<F, D>::new(
         builder.zero_extension(),
         alphas_t,
         z_last_t,
+        z_window_t,
         lagrange_first_t,
         lagrange_last_t,
     );
-    stark.eval_ext_circuit(&mut builder, &vars, &mut consumer);
+    stark.eval_ext_circuit(builder, &vars, &mut consumer);
     let circuit_eval = consumer.accumulators()[0];
     let native_eval_t = builder.constant_extension(native_eval);
     builder.connect_extension(circuit_eval, native_eval_t);
-
-    let data = builder.build::<C>();
-    let proof = data.prove(pw)?;
-    data.verify(proof)
 }
 
 fn random_low_degree_matrix<F: Field>(num_polys: usize, rate_bits: usize) -> Vec<Vec<F>> {
@@ -0,0 +1,176 @@
+//! Helpers to generate traces row by row.
+
+use alloc::vec::Vec;
+
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::Field;
+use plonky2_maybe_rayon::*;
+
+/// Accumulates the rows of a trace, then pads them to a power of two and transposes them into the
+/// columns expected by [`prove`](crate::prover::prove).
+///
+/// Rows can be pushed as arrays or as any typed row view convertible into `[F; COLUMNS]`.
+#[derive(Clone, Debug)]
+pub struct TraceBuilder<F: Field, const COLUMNS: usize> {
+    rows: Vec<[F; COLUMNS]>,
+    min_rows: usize,
+}
+
+impl<F: Field, const COLUMNS: usize> Default for TraceBuilder<F, COLUMNS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field, const COLUMNS: usize> TraceBuilder<F, COLUMNS> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            rows: Vec::with_capacity(capacity),
+            min_rows: 1,
+        }
+    }
+
+    /// Makes the padded trace at least `min_rows` long, e.g. for traces which must have the same
+    /// length as another table, or to meet the minimum degree of a FRI configuration.
+    pub fn with_min_rows(mut self, min_rows: usize) -> Self {
+        self.min_rows = min_rows;
+        self
+    }
+
+    pub fn push(&mut self, row: impl Into<[F; COLUMNS]>) {
+        self.rows.push(row.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The rows pushed so far, before padding.
+    pub fn rows(&self) -> &[[F; COLUMNS]] {
+        &self.rows
+    }
+
+    pub fn last_row(&self) -> Option<&[F; COLUMNS]> {
+        self.rows.last()
+    }
+
+    /// The length of the trace once padded.
+    pub fn padded_len(&self) -> usize {
+        self.rows.len().max(self.min_rows).next_power_of_two()
+    }
+
+    /// Pads the trace with copies of `padding_row` and returns its columns. An empty trace is
+    /// made of padding rows only.
+    pub fn build(mut self, padding_row: impl Into<[F; COLUMNS]>) -> Vec<PolynomialValues<F>> {
+        let padded_len = self.padded_len();
+        self.rows.resize(padded_len, padding_row.into());
+        self.into_columns()
+    }
+
+    /// Pads the trace with rows computed from the previous row, e.g. to keep incrementing a
+    /// counter, and returns its columns. Panics if no row was pushed, since the first padding row
+    /// would have no predecessor.
+    pub fn build_with(
+        mut self,
+        mut padding: impl FnMut(&[F; COLUMNS]) -> [F; COLUMNS],
+    ) -> Vec<PolynomialValues<F>> {
+        assert!(!self.rows.is_empty(), "Cannot pad an empty trace.");
+        let padded_len = self.padded_len();
+        while self.rows.len() < padded_len {
+            let row = padding(self.rows.last().unwrap());
+            self.rows.push(row);
+        }
+        self.into_columns()
+    }
+
+    fn into_columns(self) -> Vec<PolynomialValues<F>> {
+        let rows = self.rows;
+        debug_assert!(rows.len().is_power_of_two());
+        (0..COLUMNS)
+            .into_par_iter()
+            .map(|col| PolynomialValues::new(rows.iter().map(|row| row[col]).collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use plonky2::field::goldilocks_field::GoldilocksField;
+    use plonky2::field::polynomial::PolynomialValues;
+    use plonky2::field::types::Field;
+
+    use crate::trace::TraceBuilder;
+
+    type F = GoldilocksField;
+
+    struct PairRow {
+        x: F,
+        y: F,
+    }
+
+    impl From<PairRow> for [F; 2] {
+        fn from(row: PairRow) -> Self {
+            [row.x, row.y]
+        }
+    }
+
+    fn column(values: &[u64]) -> PolynomialValues<F> {
+        PolynomialValues::new(values.iter().map(|&v| F::from_canonical_u64(v)).collect())
+    }
+
+    #[test]
+    fn test_build_pads_with_row() {
+        let mut builder = TraceBuilder::<F, 2>::new();
+        for i in 1..=3 {
+            builder.push(PairRow {
+                x: F::from_canonical_u64(i),
+                y: F::from_canonical_u64(2 * i),
+            });
+        }
+        assert_eq!(builder.padded_len(), 4);
+        let trace = builder.build([F::ZERO, F::ONE]);
+        assert_eq!(trace, vec![column(&[1, 2, 3, 0]), column(&[2, 4, 6, 1])]);
+    }
+
+    #[test]
+    fn test_build_with_min_rows() {
+        let mut builder = TraceBuilder::<F, 1>::new().with_min_rows(5);
+        builder.push([F::ONE]);
+        builder.push([F::TWO]);
+        let trace = builder.build_with(|&[x]| [x + F::ONE]);
+        assert_eq!(trace, vec![column(&[1, 2, 3, 4, 5, 6, 7, 8])]);
+    }
+
+    #[test]
+    fn test_exact_power_of_two_is_not_padded() {
+        let mut builder = TraceBuilder::<F, 1>::new();
+        for i in 0..4 {
+            builder.push([F::from_canonical_u64(i)]);
+        }
+        let trace = builder.build_with(|_| panic!("No padding expected."));
+        assert_eq!(trace, vec![column(&[0, 1, 2, 3])]);
+    }
+
+    #[test]
+    fn test_empty_trace() {
+        let trace = TraceBuilder::<F, 1>::new().with_min_rows(2).build([F::ONE]);
+        assert_eq!(trace, vec![column(&[1, 1])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot pad an empty trace.")]
+    fn test_empty_trace_padded_from_previous_row() {
+        let _: Vec<_> = TraceBuilder::<F, 1>::new().build_with(|&row| row);
+    }
+}
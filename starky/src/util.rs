@@ -16,3 +16,15 @@ pub fn trace_rows_to_poly_values<F: Field, const COLUMNS: usize>(
         .map(|column| PolynomialValues::new(column))
         .collect()
 }
+
+/// Repeats `pattern` into a column of length `2^degree_bits`, e.g. to build round constants or
+/// selectors which cycle with a fixed period. The length of `pattern` must be a power of two, at
+/// most the trace length.
+pub fn periodic_column<F: Field>(pattern: &[F], degree_bits: usize) -> PolynomialValues<F> {
+    let n = 1 << degree_bits;
+    assert!(
+        pattern.len().is_power_of_two() && pattern.len() <= n,
+        "The period must divide the trace length."
+    );
+    PolynomialValues::new(pattern.iter().copied().cycle().take(n).collect())
+}
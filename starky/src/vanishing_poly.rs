@@ -5,6 +5,11 @@ use plonky2::plonk::circuit_builder::CircuitBuilder;
 
 use crate::config::StarkConfig;
 use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::cross_table_lookup::{eval_cross_table_lookup_checks, CtlCheckVars};
+use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::lookup::{
+    eval_ext_lookups_circuit, eval_packed_lookups_generic, LookupCheckVars, LookupCheckVarsTarget,
+};
 use crate::permutation::{
     eval_permutation_checks, eval_permutation_checks_circuit, PermutationCheckDataTarget,
     PermutationCheckVars,
@@ -16,6 +21,8 @@ pub(crate) fn eval_vanishing_poly<F, FE, P, S, const D: usize, const D2: usize>(
     config: &StarkConfig,
     vars: &S::EvaluationFrame<FE, P, D2>,
     permutation_data: Option<PermutationCheckVars<F, FE, P, D2>>,
+    lookup_vars: Option<LookupCheckVars<F, FE, P, D2>>,
+    ctl_vars: &[CtlCheckVars<F, FE, P, D2>],
     consumer: &mut ConstraintConsumer<P>,
 ) where
     F: RichField + Extendable<D>,
@@ -33,6 +40,15 @@ pub(crate) fn eval_vanishing_poly<F, FE, P, S, const D: usize, const D2: usize>(
             consumer,
         );
     }
+    if let Some(lookup_vars) = lookup_vars {
+        eval_packed_lookups_generic::<F, FE, P, S, D, D2>(stark, vars, lookup_vars, consumer);
+    }
+    eval_cross_table_lookup_checks::<F, FE, P, D2>(
+        vars.get_local_values(),
+        vars.get_next_values(),
+        ctl_vars,
+        consumer,
+    );
 }
 
 pub(crate) fn eval_vanishing_poly_circuit<F, S, const D: usize>(
@@ -41,6 +57,7 @@ pub(crate) fn eval_vanishing_poly_circuit<F, S, const D: usize>(
     config: &StarkConfig,
     vars: &S::EvaluationFrameTarget,
     permutation_data: Option<PermutationCheckDataTarget<D>>,
+    lookup_vars: Option<LookupCheckVarsTarget<D>>,
     consumer: &mut RecursiveConstraintConsumer<F, D>,
 ) where
     F: RichField + Extendable<D>,
@@ -57,4 +74,7 @@ pub(crate) fn eval_vanishing_poly_circuit<F, S, const D: usize>(
             consumer,
         );
     }
+    if let Some(lookup_vars) = lookup_vars {
+        eval_ext_lookups_circuit::<F, S, D>(builder, stark, vars, lookup_vars, consumer);
+    }
 }
@@ -1,4 +1,6 @@
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
+use alloc::{format, vec};
 use core::iter::once;
 
 use anyhow::{anyhow, ensure, Result};
@@ -7,13 +9,19 @@ use plonky2::field::extension::{Extendable, FieldExtension};
 use plonky2::field::types::Field;
 use plonky2::fri::verifier::verify_fri_proof;
 use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
 use plonky2::plonk::config::GenericConfig;
 use plonky2::plonk::plonk_common::reduce_with_powers;
+use plonky2::util::timing::TimingTree;
+use plonky2_maybe_rayon::*;
 
 use crate::config::StarkConfig;
 use crate::constraint_consumer::ConstraintConsumer;
+use crate::cross_table_lookup::CtlCheckVars;
 use crate::evaluation_frame::StarkEvaluationFrame;
+use crate::lookup::LookupCheckVars;
 use crate::permutation::PermutationCheckVars;
+use crate::preprocessed::{PreprocessedData, PreprocessedVerifierData};
 use crate::proof::{StarkOpeningSet, StarkProof, StarkProofChallenges, StarkProofWithPublicInputs};
 use crate::stark::Stark;
 use crate::vanishing_poly::eval_vanishing_poly;
@@ -28,26 +36,141 @@ pub fn verify_stark_proof<
     proof_with_pis: StarkProofWithPublicInputs<F, C, D>,
     config: &StarkConfig,
 ) -> Result<()> {
-    ensure!(proof_with_pis.public_inputs.len() == S::PUBLIC_INPUTS);
     let degree_bits = proof_with_pis.proof.recover_degree_bits(config);
-    let challenges = proof_with_pis.get_challenges(&stark, config, degree_bits);
-    verify_stark_proof_with_challenges(stark, proof_with_pis, challenges, degree_bits, config)
+    let preprocessed_verifier_data = stark.uses_preprocessed_columns().then(|| {
+        PreprocessedData::<F, C, D>::new(&stark, degree_bits, config, &mut TimingTree::default())
+            .verifier_data()
+    });
+    verify_stark_proof_inner(
+        &stark,
+        proof_with_pis,
+        preprocessed_verifier_data.as_ref(),
+        config,
+    )
 }
 
-pub(crate) fn verify_stark_proof_with_challenges<
+/// Like [`verify_stark_proof`], but takes the Merkle cap of the preprocessed columns of `stark`
+/// instead of recomputing it.
+pub fn verify_stark_proof_with_preprocessed<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    stark: S,
+    proof_with_pis: StarkProofWithPublicInputs<F, C, D>,
+    preprocessed_verifier_data: &PreprocessedVerifierData<F, C, D>,
+    config: &StarkConfig,
+) -> Result<()> {
+    ensure!(
+        stark.uses_preprocessed_columns(),
+        "This Stark has no preprocessed columns."
+    );
+    preprocessed_verifier_data
+        .check_degree_bits(proof_with_pis.proof.recover_degree_bits(config))?;
+    verify_stark_proof_inner(
+        &stark,
+        proof_with_pis,
+        Some(preprocessed_verifier_data),
+        config,
+    )
+}
+
+/// Verifies proofs of the same `stark` under the same `config` in parallel. This is not batch
+/// verification: each proof gets its own full check, including FRI, and only the commitments to
+/// the preprocessed columns, if any, are shared between proofs of the same trace length. On
+/// failure, the error names the failing proof with the lowest index.
+pub fn verify_stark_proofs_in_parallel<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
     S: Stark<F, D>,
     const D: usize,
 >(
     stark: S,
+    proofs: Vec<StarkProofWithPublicInputs<F, C, D>>,
+    config: &StarkConfig,
+) -> Result<()> {
+    let mut preprocessed_verifier_data = BTreeMap::new();
+    if stark.uses_preprocessed_columns() {
+        for proof_with_pis in &proofs {
+            let degree_bits = proof_with_pis.proof.recover_degree_bits(config);
+            preprocessed_verifier_data
+                .entry(degree_bits)
+                .or_insert_with(|| {
+                    PreprocessedData::<F, C, D>::new(
+                        &stark,
+                        degree_bits,
+                        config,
+                        &mut TimingTree::default(),
+                    )
+                    .verifier_data()
+                });
+        }
+    }
+
+    let results = proofs
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, proof_with_pis)| {
+            let degree_bits = proof_with_pis.proof.recover_degree_bits(config);
+            verify_stark_proof_inner(
+                &stark,
+                proof_with_pis,
+                preprocessed_verifier_data.get(&degree_bits),
+                config,
+            )
+            .map_err(|e| e.context(format!("Invalid proof at index {i}")))
+        })
+        .collect::<Vec<_>>();
+    results.into_iter().collect()
+}
+
+fn verify_stark_proof_inner<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    stark: &S,
     proof_with_pis: StarkProofWithPublicInputs<F, C, D>,
+    preprocessed_verifier_data: Option<&PreprocessedVerifierData<F, C, D>>,
+    config: &StarkConfig,
+) -> Result<()> {
+    ensure!(proof_with_pis.public_inputs.len() == S::PUBLIC_INPUTS);
+    let degree_bits = proof_with_pis.proof.recover_degree_bits(config);
+    let preprocessed_cap = preprocessed_verifier_data.map(|data| &data.cap);
+    let challenges = proof_with_pis.get_challenges(stark, preprocessed_cap, config, degree_bits);
+    verify_stark_proof_with_challenges(
+        stark,
+        &proof_with_pis,
+        challenges,
+        preprocessed_cap,
+        &[],
+        degree_bits,
+        config,
+    )
+}
+
+/// Verifies a STARK proof given its challenges. `preprocessed_cap` is the Merkle cap of the
+/// preprocessed columns, if the Stark has any. `ctl_vars` holds the cross-table lookup `Z`s of
+/// the proof, which are checked here; their consistency with other tables is checked separately.
+pub(crate) fn verify_stark_proof_with_challenges<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    stark: &S,
+    proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
     challenges: StarkProofChallenges<F, D>,
+    preprocessed_cap: Option<&MerkleCap<F, C::Hasher>>,
+    ctl_vars: &[CtlCheckVars<F, F::Extension, F::Extension, D>],
     degree_bits: usize,
     config: &StarkConfig,
 ) -> Result<()> {
-    validate_proof_shape(&stark, &proof_with_pis, config)?;
-    check_permutation_options(&stark, &proof_with_pis, &challenges)?;
+    let num_ctl_zs = ctl_vars.len();
+    validate_proof_shape(stark, proof_with_pis, num_ctl_zs, config)?;
+    check_auxiliary_options(stark, proof_with_pis, &challenges, num_ctl_zs)?;
     let StarkProofWithPublicInputs {
         proof,
         public_inputs,
@@ -55,13 +178,35 @@ pub(crate) fn verify_stark_proof_with_challenges<
     let StarkOpeningSet {
         local_values,
         next_values,
-        permutation_zs,
-        permutation_zs_next,
+        auxiliary_polys,
+        auxiliary_polys_next,
+        ctl_zs_first: _,
         quotient_polys,
+        preprocessed_values,
+        preprocessed_values_next,
+        window_values,
+        window_preprocessed_values,
     } = &proof.openings;
+    let rows = [local_values, next_values]
+        .into_iter()
+        .chain(window_values)
+        .map(|row| &row[..])
+        .collect_vec();
+    let preprocessed_rows = match (
+        preprocessed_values,
+        preprocessed_values_next,
+        window_preprocessed_values,
+    ) {
+        (Some(local), Some(next), Some(window)) => [local, next]
+            .into_iter()
+            .chain(window)
+            .map(|row| &row[..])
+            .collect_vec(),
+        _ => vec![],
+    };
     let vars = S::EvaluationFrame::from_values(
-        local_values,
-        next_values,
+        &rows,
+        &preprocessed_rows,
         &public_inputs
             .iter()
             .copied()
@@ -71,6 +216,12 @@ pub(crate) fn verify_stark_proof_with_challenges<
     let (l_0, l_last) = eval_l_0_and_l_last(degree_bits, challenges.stark_zeta);
     let last = F::primitive_root_of_unity(degree_bits).inverse();
     let z_last = challenges.stark_zeta - last.into();
+    let z_window = last
+        .powers()
+        .skip(1)
+        .take(S::FRAME_ROWS - 1)
+        .map(|last_row| challenges.stark_zeta - last_row.into())
+        .product();
     let mut consumer = ConstraintConsumer::<F::Extension>::new(
         challenges
             .stark_alphas
@@ -78,19 +229,32 @@ pub(crate) fn verify_stark_proof_with_challenges<
             .map(|&alpha| F::Extension::from_basefield(alpha))
             .collect::<Vec<_>>(),
         z_last,
+        z_window,
         l_0,
         l_last,
     );
+    let num_permutation_zs = stark.num_permutation_batches(config);
+    let num_auxiliary_polys = stark.num_auxiliary_polys(config);
     let permutation_data = stark.uses_permutation_args().then(|| PermutationCheckVars {
-        local_zs: permutation_zs.as_ref().unwrap().clone(),
-        next_zs: permutation_zs_next.as_ref().unwrap().clone(),
+        local_zs: auxiliary_polys.as_ref().unwrap()[..num_permutation_zs].to_vec(),
+        next_zs: auxiliary_polys_next.as_ref().unwrap()[..num_permutation_zs].to_vec(),
         permutation_challenge_sets: challenges.permutation_challenge_sets.unwrap(),
     });
+    let lookup_vars = stark.uses_lookups().then(|| LookupCheckVars {
+        local_values: auxiliary_polys.as_ref().unwrap()[num_permutation_zs..num_auxiliary_polys]
+            .to_vec(),
+        next_values: auxiliary_polys_next.as_ref().unwrap()
+            [num_permutation_zs..num_auxiliary_polys]
+            .to_vec(),
+        challenges: challenges.lookup_challenges.unwrap(),
+    });
     eval_vanishing_poly::<F, F::Extension, F::Extension, S, D, D>(
-        &stark,
+        stark,
         config,
         &vars,
         permutation_data,
+        lookup_vars,
+        ctl_vars,
         &mut consumer,
     );
     let vanishing_polys_zeta = consumer.accumulators();
@@ -98,13 +262,13 @@ pub(crate) fn verify_stark_proof_with_challenges<
     // Check each polynomial identity, of the form `vanishing(x) = Z_H(x) quotient(x)`, at zeta.
     let zeta_pow_deg = challenges.stark_zeta.exp_power_of_2(degree_bits);
     let z_h_zeta = zeta_pow_deg - F::Extension::ONE;
-    // `quotient_polys_zeta` holds `num_challenges * quotient_degree_factor` evaluations.
-    // Each chunk of `quotient_degree_factor` holds the evaluations of `t_0(zeta),...,t_{quotient_degree_factor-1}(zeta)`
+    // `quotient_polys_zeta` holds `num_challenges * num_quotient_chunks` evaluations.
+    // Each chunk of `num_quotient_chunks` holds the evaluations of `t_0(zeta),...,t_{num_quotient_chunks-1}(zeta)`
     // where the "real" quotient polynomial is `t(X) = t_0(X) + t_1(X)*X^n + t_2(X)*X^{2n} + ...`.
     // So to reconstruct `t(zeta)` we can compute `reduce_with_powers(chunk, zeta^n)` for each
-    // `quotient_degree_factor`-sized chunk of the original evaluations.
+    // `num_quotient_chunks`-sized chunk of the original evaluations.
     for (i, chunk) in quotient_polys
-        .chunks(stark.quotient_degree_factor())
+        .chunks(stark.num_quotient_chunks(config))
         .enumerate()
     {
         ensure!(
@@ -113,15 +277,17 @@ pub(crate) fn verify_stark_proof_with_challenges<
         );
     }
 
-    let merkle_caps = once(proof.trace_cap)
-        .chain(proof.permutation_zs_cap)
-        .chain(once(proof.quotient_polys_cap))
+    let merkle_caps = once(proof.trace_cap.clone())
+        .chain(proof.auxiliary_polys_cap.clone())
+        .chain(once(proof.quotient_polys_cap.clone()))
+        .chain(preprocessed_cap.cloned())
         .collect_vec();
 
     verify_fri_proof::<F, C, D>(
         &stark.fri_instance(
             challenges.stark_zeta,
             F::primitive_root_of_unity(degree_bits),
+            num_ctl_zs,
             config,
         ),
         &proof.openings.to_fri_openings(),
@@ -134,9 +300,10 @@ pub(crate) fn verify_stark_proof_with_challenges<
     Ok(())
 }
 
-fn validate_proof_shape<F, C, S, const D: usize>(
+pub(crate) fn validate_proof_shape<F, C, S, const D: usize>(
     stark: &S,
     proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+    num_ctl_zs: usize,
     config: &StarkConfig,
 ) -> anyhow::Result<()>
 where
@@ -152,7 +319,7 @@ where
 
     let StarkProof {
         trace_cap,
-        permutation_zs_cap,
+        auxiliary_polys_cap,
         quotient_polys_cap,
         openings,
         // The shape of the opening proof will be checked in the FRI verifier (see
@@ -163,42 +330,80 @@ where
     let StarkOpeningSet {
         local_values,
         next_values,
-        permutation_zs,
-        permutation_zs_next,
+        auxiliary_polys,
+        auxiliary_polys_next,
+        ctl_zs_first,
         quotient_polys,
+        preprocessed_values,
+        preprocessed_values_next,
+        window_values,
+        window_preprocessed_values,
     } = openings;
 
     ensure!(public_inputs.len() == S::PUBLIC_INPUTS);
 
     let fri_params = config.fri_params(degree_bits);
     let cap_height = fri_params.config.cap_height;
-    let num_zs = stark.num_permutation_batches(config);
+    let num_auxiliary_polys = stark.num_auxiliary_polys(config) + num_ctl_zs;
 
     ensure!(trace_cap.height() == cap_height);
     ensure!(quotient_polys_cap.height() == cap_height);
 
     ensure!(local_values.len() == S::COLUMNS);
     ensure!(next_values.len() == S::COLUMNS);
+    ensure!(window_values.len() == S::FRAME_ROWS - 2);
+    ensure!(window_values.iter().all(|row| row.len() == S::COLUMNS));
     ensure!(quotient_polys.len() == stark.num_quotient_polys(config));
 
-    if stark.uses_permutation_args() {
-        let permutation_zs_cap = permutation_zs_cap
+    if num_auxiliary_polys > 0 {
+        let auxiliary_polys_cap = auxiliary_polys_cap
             .as_ref()
-            .ok_or_else(|| anyhow!("Missing Zs cap"))?;
-        let permutation_zs = permutation_zs
+            .ok_or_else(|| anyhow!("Missing auxiliary polynomials cap"))?;
+        let auxiliary_polys = auxiliary_polys
             .as_ref()
-            .ok_or_else(|| anyhow!("Missing permutation_zs"))?;
-        let permutation_zs_next = permutation_zs_next
+            .ok_or_else(|| anyhow!("Missing auxiliary_polys"))?;
+        let auxiliary_polys_next = auxiliary_polys_next
             .as_ref()
-            .ok_or_else(|| anyhow!("Missing permutation_zs_next"))?;
+            .ok_or_else(|| anyhow!("Missing auxiliary_polys_next"))?;
 
-        ensure!(permutation_zs_cap.height() == cap_height);
-        ensure!(permutation_zs.len() == num_zs);
-        ensure!(permutation_zs_next.len() == num_zs);
+        ensure!(auxiliary_polys_cap.height() == cap_height);
+        ensure!(auxiliary_polys.len() == num_auxiliary_polys);
+        ensure!(auxiliary_polys_next.len() == num_auxiliary_polys);
     } else {
-        ensure!(permutation_zs_cap.is_none());
-        ensure!(permutation_zs.is_none());
-        ensure!(permutation_zs_next.is_none());
+        ensure!(auxiliary_polys_cap.is_none());
+        ensure!(auxiliary_polys.is_none());
+        ensure!(auxiliary_polys_next.is_none());
+    }
+
+    if num_ctl_zs > 0 {
+        let ctl_zs_first = ctl_zs_first
+            .as_ref()
+            .ok_or_else(|| anyhow!("Missing ctl_zs_first"))?;
+        ensure!(ctl_zs_first.len() == num_ctl_zs);
+    } else {
+        ensure!(ctl_zs_first.is_none());
+    }
+
+    if stark.uses_preprocessed_columns() {
+        let preprocessed_values = preprocessed_values
+            .as_ref()
+            .ok_or_else(|| anyhow!("Missing preprocessed_values"))?;
+        let preprocessed_values_next = preprocessed_values_next
+            .as_ref()
+            .ok_or_else(|| anyhow!("Missing preprocessed_values_next"))?;
+        let window_preprocessed_values = window_preprocessed_values
+            .as_ref()
+            .ok_or_else(|| anyhow!("Missing window_preprocessed_values"))?;
+        ensure!(preprocessed_values.len() == S::PREPROCESSED_COLUMNS);
+        ensure!(preprocessed_values_next.len() == S::PREPROCESSED_COLUMNS);
+        ensure!(window_preprocessed_values.len() == S::FRAME_ROWS - 2);
+        ensure!(window_preprocessed_values
+            .iter()
+            .all(|row| row.len() == S::PREPROCESSED_COLUMNS));
+    } else {
+        ensure!(preprocessed_values.is_none());
+        ensure!(preprocessed_values_next.is_none());
+        ensure!(window_preprocessed_values.is_none());
     }
 
     Ok(())
@@ -216,9 +421,9 @@ fn eval_l_0_and_l_last<F: Field>(log_n: usize, x: F) -> (F, F) {
     (z_x * invs[0], z_x * invs[1])
 }
 
-/// Utility function to check that all permutation data wrapped in `Option`s are `Some` iff
-/// the Stark uses a permutation argument.
-fn check_permutation_options<
+/// Utility function to check that all auxiliary data wrapped in `Option`s are `Some` iff the
+/// Stark uses the corresponding argument, or has cross-table lookups.
+fn check_auxiliary_options<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
     S: Stark<F, D>,
@@ -227,29 +432,46 @@ fn check_permutation_options<
     stark: &S,
     proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
     challenges: &StarkProofChallenges<F, D>,
+    num_ctl_zs: usize,
 ) -> Result<()> {
-    let options_is_some = [
-        proof_with_pis.proof.permutation_zs_cap.is_some(),
-        proof_with_pis.proof.openings.permutation_zs.is_some(),
-        proof_with_pis.proof.openings.permutation_zs_next.is_some(),
-        challenges.permutation_challenge_sets.is_some(),
+    let auxiliary_options_is_some = [
+        proof_with_pis.proof.auxiliary_polys_cap.is_some(),
+        proof_with_pis.proof.openings.auxiliary_polys.is_some(),
+        proof_with_pis.proof.openings.auxiliary_polys_next.is_some(),
     ];
     ensure!(
-        options_is_some
+        auxiliary_options_is_some
             .into_iter()
-            .all(|b| b == stark.uses_permutation_args()),
+            .all(|b| b == (stark.uses_auxiliary_polys() || num_ctl_zs > 0)),
+        "Auxiliary polynomials don't match with Stark configuration."
+    );
+    ensure!(
+        challenges.permutation_challenge_sets.is_some() == stark.uses_permutation_args(),
         "Permutation data doesn't match with Stark configuration."
     );
+    ensure!(
+        challenges.lookup_challenges.is_some() == stark.uses_lookups(),
+        "Lookup data doesn't match with Stark configuration."
+    );
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use alloc::format;
+    use alloc::vec::Vec;
+
+    use anyhow::Result;
     use plonky2::field::goldilocks_field::GoldilocksField;
     use plonky2::field::polynomial::PolynomialValues;
-    use plonky2::field::types::Sample;
+    use plonky2::field::types::{Field, Sample};
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use plonky2::util::timing::TimingTree;
 
-    use crate::verifier::eval_l_0_and_l_last;
+    use crate::config::StarkConfig;
+    use crate::fibonacci_stark::FibonacciStark;
+    use crate::prover::prove;
+    use crate::verifier::{eval_l_0_and_l_last, verify_stark_proofs_in_parallel};
 
     #[test]
     fn test_eval_l_0_and_l_last() {
@@ -265,4 +487,38 @@ mod tests {
         assert_eq!(l_first_x, expected_l_first_x);
         assert_eq!(l_last_x, expected_l_last_x);
     }
+
+    #[test]
+    fn test_verify_stark_proofs_in_parallel() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type S = FibonacciStark<F, D>;
+
+        let config = StarkConfig::standard_fast_config();
+        let num_rows = 1 << 5;
+        let stark = S::new(num_rows);
+        let mut proofs = (1..5)
+            .map(|x1| {
+                let x1 = F::from_canonical_u64(x1);
+                let last = (1..num_rows).fold((F::ZERO, x1), |x, _| (x.1, x.0 + x.1)).1;
+                let trace = stark.generate_trace(F::ZERO, x1);
+                prove::<F, C, S, D>(
+                    stark,
+                    &config,
+                    trace,
+                    &[F::ZERO, x1, last],
+                    &mut TimingTree::default(),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        verify_stark_proofs_in_parallel(stark, proofs.clone(), &config)?;
+
+        // The reported proof is the first invalid one, whichever is checked first.
+        proofs[3].public_inputs[2] += F::ONE;
+        proofs[1].public_inputs[2] += F::ONE;
+        let err = verify_stark_proofs_in_parallel(stark, proofs, &config).unwrap_err();
+        assert!(format!("{err}").contains("index 1"));
+        Ok(())
+    }
 }
@@ -0,0 +1,182 @@
+//! Derive macros for typed views over the columns of a STARK trace.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, GenericParam, Result};
+
+/// Derives the boilerplate of a columns view, i.e. a `#[repr(C)]` struct generic over a single
+/// type `T`, whose fields are all `T`s, arrays of `T`s or other column views.
+///
+/// For `FooColumnsView<T>`, this generates:
+/// - the associated constant `FooColumnsView::<T>::NUM_COLUMNS`,
+/// - the constant `FooColumnsView::<usize>::COL_MAP`, which holds the index of each column,
+/// - conversions between the view and `[T; NUM_COLUMNS]`, by value with `From` and by reference
+///   with `Borrow` and `BorrowMut`,
+/// - `Index` and `IndexMut` by column index, and `Default` when `T: Default`,
+/// - compile-time checks that the view has the size and alignment of `[T; NUM_COLUMNS]`, so that
+///   the conversions above are sound.
+#[proc_macro_derive(Columns)]
+pub fn derive_columns(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    impl_columns(&ast)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn impl_columns(ast: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+
+    if !matches!(ast.data, Data::Struct(_)) {
+        return Err(Error::new(
+            Span::call_site(),
+            "Columns can only be derived for structs",
+        ));
+    }
+
+    let is_repr_c = ast.attrs.iter().any(|attr| {
+        attr.path().is_ident("repr")
+            && attr
+                .parse_nested_meta(|meta| {
+                    if meta.path.is_ident("C") {
+                        Ok(())
+                    } else {
+                        Err(meta.error("unexpected repr"))
+                    }
+                })
+                .is_ok()
+    });
+    if !is_repr_c {
+        return Err(Error::new(
+            Span::call_site(),
+            "column views must be #[repr(C)]",
+        ));
+    }
+
+    let params = ast.generics.params.iter().collect::<Vec<_>>();
+    let t = match params.as_slice() {
+        [GenericParam::Type(t)] => &t.ident,
+        _ => {
+            return Err(Error::new_spanned(
+                &ast.generics,
+                "column views must have exactly one type parameter",
+            ))
+        }
+    };
+    if ast.generics.where_clause.is_some() {
+        return Err(Error::new_spanned(
+            &ast.generics.where_clause,
+            "column views can't have where clauses",
+        ));
+    }
+
+    let num_columns = quote!(#name::<u8>::NUM_COLUMNS);
+    Ok(quote! {
+        impl<#t: ::core::marker::Copy> #name<#t> {
+            /// The number of columns in this view.
+            pub const NUM_COLUMNS: usize = ::core::mem::size_of::<#name<u8>>();
+        }
+
+        impl #name<usize> {
+            /// The index of each column.
+            pub const COL_MAP: Self = {
+                let mut indices = [0; #num_columns];
+                let mut i = 0;
+                while i < #num_columns {
+                    indices[i] = i;
+                    i += 1;
+                }
+                unsafe { ::core::mem::transmute::<[usize; #num_columns], Self>(indices) }
+            };
+        }
+
+        // Fields which aren't made of `T`s take the same space for any `T`, so the size of the
+        // view only scales with the size of `T` if there are none. The alignment check rules out
+        // fields aligned more strictly than `T`, which could add padding.
+        const _: () = {
+            assert!(
+                ::core::mem::size_of::<#name<[u8; 3]>>() == 3 * #num_columns,
+                "column views must only contain columns of type T"
+            );
+            assert!(
+                ::core::mem::size_of::<#name<u64>>()
+                    == #num_columns * ::core::mem::size_of::<u64>(),
+                "column views must only contain columns of type T"
+            );
+            assert!(
+                ::core::mem::align_of::<#name<u64>>() == ::core::mem::align_of::<u64>(),
+                "column views must only contain columns of type T"
+            );
+        };
+
+        impl<#t: ::core::marker::Copy> ::core::convert::From<[#t; #num_columns]> for #name<#t> {
+            fn from(value: [#t; #num_columns]) -> Self {
+                *::core::borrow::Borrow::<#name<#t>>::borrow(&value)
+            }
+        }
+
+        impl<#t: ::core::marker::Copy> ::core::convert::From<#name<#t>> for [#t; #num_columns] {
+            fn from(value: #name<#t>) -> Self {
+                *::core::borrow::Borrow::<[#t; #num_columns]>::borrow(&value)
+            }
+        }
+
+        impl<#t: ::core::marker::Copy> ::core::borrow::Borrow<#name<#t>> for [#t; #num_columns] {
+            fn borrow(&self) -> &#name<#t> {
+                unsafe { &*(self as *const [#t; #num_columns]).cast::<#name<#t>>() }
+            }
+        }
+
+        impl<#t: ::core::marker::Copy> ::core::borrow::BorrowMut<#name<#t>>
+            for [#t; #num_columns]
+        {
+            fn borrow_mut(&mut self) -> &mut #name<#t> {
+                unsafe { &mut *(self as *mut [#t; #num_columns]).cast::<#name<#t>>() }
+            }
+        }
+
+        impl<#t: ::core::marker::Copy> ::core::borrow::Borrow<[#t; #num_columns]> for #name<#t> {
+            fn borrow(&self) -> &[#t; #num_columns] {
+                unsafe { &*(self as *const #name<#t>).cast::<[#t; #num_columns]>() }
+            }
+        }
+
+        impl<#t: ::core::marker::Copy> ::core::borrow::BorrowMut<[#t; #num_columns]>
+            for #name<#t>
+        {
+            fn borrow_mut(&mut self) -> &mut [#t; #num_columns] {
+                unsafe { &mut *(self as *mut #name<#t>).cast::<[#t; #num_columns]>() }
+            }
+        }
+
+        impl<#t: ::core::marker::Copy, I> ::core::ops::Index<I> for #name<#t>
+        where
+            [#t]: ::core::ops::Index<I>,
+        {
+            type Output = <[#t] as ::core::ops::Index<I>>::Output;
+
+            fn index(&self, index: I) -> &Self::Output {
+                let arr: &[#t; #num_columns] = ::core::borrow::Borrow::borrow(self);
+                <[#t] as ::core::ops::Index<I>>::index(arr, index)
+            }
+        }
+
+        impl<#t: ::core::marker::Copy, I> ::core::ops::IndexMut<I> for #name<#t>
+        where
+            [#t]: ::core::ops::IndexMut<I>,
+        {
+            fn index_mut(&mut self, index: I) -> &mut Self::Output {
+                let arr: &mut [#t; #num_columns] = ::core::borrow::BorrowMut::borrow_mut(self);
+                <[#t] as ::core::ops::IndexMut<I>>::index_mut(arr, index)
+            }
+        }
+
+        impl<#t: ::core::marker::Copy + ::core::default::Default> ::core::default::Default
+            for #name<#t>
+        {
+            fn default() -> Self {
+                Self::from([#t::default(); #num_columns])
+            }
+        }
+    })
+}
@@ -0,0 +1,56 @@
+use core::borrow::{Borrow, BorrowMut};
+
+use starky_derive::Columns;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Columns)]
+struct LimbsView<T: Copy> {
+    lo: T,
+    hi: T,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Columns)]
+struct AddColumnsView<T: Copy> {
+    is_add: T,
+    inputs: [LimbsView<T>; 2],
+    output: LimbsView<T>,
+    carry: T,
+}
+
+#[test]
+fn test_num_columns() {
+    assert_eq!(LimbsView::<u8>::NUM_COLUMNS, 2);
+    assert_eq!(AddColumnsView::<u64>::NUM_COLUMNS, 8);
+}
+
+#[test]
+fn test_col_map() {
+    let col_map = AddColumnsView::<usize>::COL_MAP;
+    assert_eq!(col_map.is_add, 0);
+    assert_eq!(col_map.inputs[1].lo, 3);
+    assert_eq!(col_map.output, LimbsView { lo: 5, hi: 6 });
+    assert_eq!(col_map.carry, 7);
+}
+
+#[test]
+fn test_conversions() {
+    let mut row = [0u64, 1, 2, 3, 4, 5, 6, 7];
+    let view: &AddColumnsView<u64> = row.borrow();
+    assert_eq!(view.inputs[0].hi, 2);
+    assert_eq!(view[7], 7);
+    assert_eq!(view[1..3], [1, 2]);
+
+    let view: &mut AddColumnsView<u64> = row.borrow_mut();
+    view.carry = 10;
+    view[0] = 20;
+    assert_eq!(row[0], 20);
+    assert_eq!(row[7], 10);
+
+    let view = AddColumnsView::from(row);
+    assert_eq!(<[u64; 8]>::from(view), row);
+    let arr: &[u64; 8] = view.borrow();
+    assert_eq!(arr, &row);
+
+    assert_eq!(<[u64; 8]>::from(AddColumnsView::default()), [0; 8]);
+}